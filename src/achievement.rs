@@ -0,0 +1,152 @@
+use serde::{Deserialize, Serialize};
+use crate::modifier::{Modifier, ModifierApplication, ModifierSource, ModifierTarget};
+
+/// 斩妖成就所需的累计斩杀数
+pub const MONSTER_SLAYER_TARGET: u32 = 10;
+
+/// 道侣成就所需的道侣对数
+pub const DAO_COUPLES_TARGET: u32 = 3;
+
+/// 声望成就所需的声望
+pub const RENOWN_TARGET: u32 = 1000;
+
+/// 成就全体buff的持续回合数
+pub const ACHIEVEMENT_BLESSING_DURATION: u32 = 5;
+
+/// 成就
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Achievement {
+    FirstGoldenCore, // 首位弟子结丹
+    MonsterSlayer,   // 累计斩杀妖魔
+    GrandArchitect,  // 建成全部建筑
+    DaoCouples,      // 宗门内结成多对道侣
+    Renowned,        // 声望破千
+}
+
+/// 成就的一次性奖励
+#[derive(Debug, Clone)]
+pub enum AchievementReward {
+    SpiritStones(u32),  // 灵石
+    Blessing(Modifier), // 全体弟子的临时buff
+}
+
+impl AchievementReward {
+    /// 奖励描述
+    pub fn describe(&self) -> String {
+        match self {
+            Self::SpiritStones(amount) => format!("灵石+{}", amount),
+            Self::Blessing(modifier) => format!("全体弟子获得「{}」{}回合", modifier.name, ACHIEVEMENT_BLESSING_DURATION),
+        }
+    }
+}
+
+impl Achievement {
+    pub const ALL: [Achievement; 5] = [
+        Self::FirstGoldenCore,
+        Self::MonsterSlayer,
+        Self::GrandArchitect,
+        Self::DaoCouples,
+        Self::Renowned,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::FirstGoldenCore => "金丹初成",
+            Self::MonsterSlayer => "斩妖除魔",
+            Self::GrandArchitect => "宗门大成",
+            Self::DaoCouples => "神仙眷侣",
+            Self::Renowned => "名动天下",
+        }
+    }
+
+    pub fn description(&self) -> String {
+        match self {
+            Self::FirstGoldenCore => "首位弟子晋升金丹期".to_string(),
+            Self::MonsterSlayer => format!("累计斩杀{}只妖魔", MONSTER_SLAYER_TARGET),
+            Self::GrandArchitect => "建成建筑树上的全部建筑".to_string(),
+            Self::DaoCouples => format!("宗门内结成{}对道侣", DAO_COUPLES_TARGET),
+            Self::Renowned => format!("宗门声望达到{}", RENOWN_TARGET),
+        }
+    }
+
+    /// 解锁时发放的奖励
+    pub fn reward(&self) -> AchievementReward {
+        let blessing = |name: &str, target, bonus| {
+            AchievementReward::Blessing(Modifier::new_temporary(
+                name,
+                target,
+                ModifierApplication::Multiplicative(bonus),
+                ModifierSource::Buff,
+                ACHIEVEMENT_BLESSING_DURATION,
+            ))
+        };
+        match self {
+            Self::FirstGoldenCore => AchievementReward::SpiritStones(300),
+            Self::MonsterSlayer => blessing("斩妖威名", ModifierTarget::TaskReward, 0.2),
+            Self::GrandArchitect => AchievementReward::SpiritStones(1000),
+            Self::DaoCouples => blessing("琴瑟和鸣", ModifierTarget::CultivationSpeed, 0.15),
+            Self::Renowned => AchievementReward::SpiritStones(2000),
+        }
+    }
+}
+
+/// 已解锁的成就
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnlockedAchievement {
+    pub achievement: Achievement,
+    pub year: u32, // 解锁年份
+}
+
+/// 成就进度
+#[derive(Debug, Clone)]
+pub struct AchievementProgress {
+    pub achievement: Achievement,
+    pub current: u32,
+    pub target: u32,
+    pub unlocked_year: Option<u32>,
+}
+
+impl AchievementProgress {
+    pub fn is_complete(&self) -> bool {
+        self.current >= self.target
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disciple::{Disciple, DiscipleType};
+    use crate::sect::Sect;
+
+    #[test]
+    fn test_slaying_enough_monsters_unlocks_achievement_and_grants_blessing() {
+        let mut sect = Sect::new("测试宗".to_string());
+        sect.disciples = vec![
+            Disciple::new(1, "叶凡".to_string(), DiscipleType::Inner, vec![]),
+            Disciple::new(2, "庞博".to_string(), DiscipleType::Outer, vec![]),
+        ];
+        let reward_before = sect.disciples[0].modifiers.calculate_effective(&ModifierTarget::TaskReward, 1.0);
+
+        for _ in 0..MONSTER_SLAYER_TARGET - 1 {
+            sect.record_monster_slain();
+        }
+        assert!(sect.check_achievements().0.is_empty());
+        let progress = sect.achievement_progress();
+        let slayer = progress.iter().find(|p| p.achievement == Achievement::MonsterSlayer).unwrap();
+        assert_eq!((slayer.current, slayer.target), (MONSTER_SLAYER_TARGET - 1, MONSTER_SLAYER_TARGET));
+
+        // 第十只妖魔伏诛，解锁成就并为全体弟子挂上斩妖威名
+        sect.record_monster_slain();
+        assert_eq!(sect.check_achievements().0, vec![Achievement::MonsterSlayer]);
+        for disciple in &sect.disciples {
+            let reward = disciple.modifiers.calculate_effective(&ModifierTarget::TaskReward, 1.0);
+            assert!(reward > reward_before);
+        }
+        assert!(sect.achievements.iter().any(|u| u.achievement == Achievement::MonsterSlayer));
+
+        // 成就只解锁一次，奖励不重复发放
+        sect.record_monster_slain();
+        assert!(sect.check_achievements().0.is_empty());
+        assert_eq!(sect.disciples[0].modifiers.get_modifiers_for_target(&ModifierTarget::TaskReward).len(), 1);
+    }
+}
@@ -1,6 +1,11 @@
 use serde::{Deserialize, Serialize};
-use crate::disciple::{Disciple, Talent, Heritage};
-use crate::sect::Sect;
+use crate::achievement::AchievementProgress;
+use crate::disciple::{Disciple, Talent, Heritage, TaskTypePreference};
+use crate::sect::{EffectiveAttributes, ModifierOrigin, Sect, SectRank};
+use crate::technique::Technique;
+use crate::equipment::{Equipment, EquipmentSlot};
+use crate::modifier::{ModifierExplanation, ModifierStep};
+use crate::interactive::{GameLogEntry, TribulationAftermath};
 use crate::relationship::{Relationship, RelationDimension, RelationLevel, RelationScores};
 
 /// API响应包装
@@ -31,6 +36,18 @@ impl<T> ApiResponse<T> {
             }),
         }
     }
+
+    pub fn error_with_details(code: String, message: String, details: String) -> Self {
+        Self {
+            success: false,
+            data: None,
+            error: Some(ApiError {
+                code,
+                message,
+                details: Some(details),
+            }),
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -51,6 +68,24 @@ pub struct VersionResponse {
 #[derive(Debug, Deserialize)]
 pub struct CreateGameRequest {
     pub sect_name: String,
+    pub map_width: Option<i32>,          // 默认20
+    pub map_height: Option<i32>,         // 默认20
+    pub initial_disciples: Option<usize>, // 默认1
+    pub starting_resources: Option<u32>, // 初始灵石，默认1000
+}
+
+/// 活跃对局信息
+#[derive(Debug, Serialize)]
+pub struct ActiveGameDto {
+    pub game_id: String,
+    pub idle_seconds: u64, // 距最后一次访问的秒数
+}
+
+/// 活跃对局列表响应
+#[derive(Debug, Serialize)]
+pub struct ActiveGamesResponse {
+    pub count: usize,
+    pub games: Vec<ActiveGameDto>,
 }
 
 /// 游戏信息响应
@@ -61,14 +96,84 @@ pub struct GameInfoResponse {
     pub state: String,
 }
 
+/// 存档响应
+#[derive(Debug, Serialize)]
+pub struct SaveGameResponse {
+    pub game_id: String,
+    pub path: String, // 存档文件路径
+    pub year: u32,    // 存档时的年份
+}
+
+/// 配置热重载响应
+#[derive(Debug, Serialize)]
+pub struct ConfigReloadResponse {
+    pub added_elements: Vec<String>,    // 新增的地图元素
+    pub removed_elements: Vec<String>,  // 移除的地图元素
+    pub updated_elements: usize,        // 刷新模板的地图元素数量
+    pub added_buildings: Vec<String>,   // 新增的建筑
+    pub removed_buildings: Vec<String>, // 移除的建筑
+    pub cancelled_tasks: Vec<String>,   // 因地点移除而取消的未开始任务
+    pub orphaned_tasks: Vec<String>,    // 地点已移除但仍会执行完毕的任务
+}
+
+impl From<crate::interactive::ConfigReloadReport> for ConfigReloadResponse {
+    fn from(report: crate::interactive::ConfigReloadReport) -> Self {
+        Self {
+            added_elements: report.added_elements,
+            removed_elements: report.removed_elements,
+            updated_elements: report.updated_elements,
+            added_buildings: report.added_buildings,
+            removed_buildings: report.removed_buildings,
+            cancelled_tasks: report.cancelled_tasks,
+            orphaned_tasks: report.orphaned_tasks,
+        }
+    }
+}
+
 /// 宗门DTO
 #[derive(Debug, Serialize, Clone)]
 pub struct SectDto {
     pub name: String,
     pub year: u32,
-    pub resources: u32,
+    pub resources: u32,    // 灵石
+    pub contribution: u32, // 贡献点
     pub reputation: i32,
     pub disciples_count: usize,
+    pub rank: SectRankDto,
+}
+
+/// 宗门等级DTO
+#[derive(Debug, Serialize, Clone)]
+pub struct SectRankDto {
+    pub rank: String, // Mortal/FirstClass/Super/Immortal
+    pub name: String,
+    pub next: Option<SectRankRequirementDto>, // 晋升下一级所需条件（已是仙门时为空）
+}
+
+/// 宗门晋升条件DTO
+#[derive(Debug, Serialize, Clone)]
+pub struct SectRankRequirementDto {
+    pub rank: String,
+    pub name: String,
+    pub min_cultivation: String, // 存活弟子最高修为
+    pub min_reputation: i32,
+    pub min_buildings: usize,
+}
+
+impl From<SectRank> for SectRankDto {
+    fn from(rank: SectRank) -> Self {
+        Self {
+            rank: format!("{:?}", rank),
+            name: rank.name().to_string(),
+            next: rank.next_requirement().map(|r| SectRankRequirementDto {
+                rank: format!("{:?}", r.rank),
+                name: r.rank.name().to_string(),
+                min_cultivation: format!("{:?}", r.min_cultivation),
+                min_reputation: r.min_reputation,
+                min_buildings: r.min_buildings,
+            }),
+        }
+    }
 }
 
 impl From<&Sect> for SectDto {
@@ -76,9 +181,11 @@ impl From<&Sect> for SectDto {
         Self {
             name: sect.name.clone(),
             year: sect.year,
-            resources: sect.resources,
+            resources: sect.spirit_stones,
+            contribution: sect.contribution,
             reputation: sect.reputation,
             disciples_count: sect.alive_disciples().len(),
+            rank: sect.sect_rank().into(),
         }
     }
 }
@@ -91,6 +198,7 @@ pub struct RelationScoresDto {
     pub comrade: u32,       // 战友关系 0-100
     pub understanding: u32, // 认知程度 0-100
     pub fateful_bond: u32,  // 机缘关系 0-100
+    pub enmity: u32,        // 仇恨敌意 0-100
 }
 
 impl From<&RelationScores> for RelationScoresDto {
@@ -101,6 +209,7 @@ impl From<&RelationScores> for RelationScoresDto {
             comrade: scores.comrade,
             understanding: scores.understanding,
             fateful_bond: scores.fateful_bond,
+            enmity: scores.enmity,
         }
     }
 }
@@ -112,13 +221,44 @@ pub struct RelationshipDto {
     pub target_name: String,
     pub scores: RelationScoresDto,
     pub established_year: u32,
+    pub last_interaction_year: u32,
     pub is_dao_companion: bool,
     pub is_master: bool,
     pub is_disciple: bool,
+    pub deceased: bool, // 对方已故
     pub primary_relation: String,
     pub highest_level: String,
 }
 
+/// 家族成员DTO
+#[derive(Debug, Serialize, Clone)]
+pub struct FamilyMemberDto {
+    pub id: usize,
+    pub name: String,
+    pub alive: bool,
+    pub cultivation_level: String,
+}
+
+impl From<&Disciple> for FamilyMemberDto {
+    fn from(disciple: &Disciple) -> Self {
+        Self {
+            id: disciple.id,
+            name: disciple.name.clone(),
+            alive: disciple.is_alive(),
+            cultivation_level: format!("{:?}", disciple.cultivation.current_level),
+        }
+    }
+}
+
+/// 家族树响应
+#[derive(Debug, Serialize)]
+pub struct FamilyTreeResponse {
+    pub disciple: FamilyMemberDto,
+    pub parents: Vec<FamilyMemberDto>,
+    pub dao_companion: Option<FamilyMemberDto>,
+    pub children: Vec<FamilyMemberDto>,
+}
+
 /// 关系摘要DTO（用于弟子列表）
 #[derive(Debug, Serialize, Clone)]
 pub struct RelationshipSummaryDto {
@@ -148,6 +288,20 @@ pub struct DiscipleDto {
     pub position: PositionDto,  // 弟子在地图上的位置
     pub movement_range: u32,    // 每回合可移动的最大距离（格子数）
     pub moves_remaining: u32,   // 本回合剩余移动距离
+    pub injuries: Vec<String>,  // 伤势（轻伤、重伤、垂危）
+    pub equipped_technique: Option<String>,  // 装备的功法名称
+    pub equipment: Vec<EquipmentDto>,        // 已穿戴的装备（按槽位排列）
+    pub personality: Option<String>,         // 性格
+    pub tasks_completed: u32,                // 累计完成任务数
+    pub reputation_contributed: u32,         // 累计声望贡献
+    pub total_resources_contributed: u32,    // 累计灵石贡献
+    pub monsters_slain: u32,                 // 累计斩杀妖魔数
+    pub in_seclusion: bool,                  // 是否正在闭关
+    pub patrolling: bool,                    // 是否正在巡逻
+    pub seclusion_turns: u32,                // 剩余闭关回合数
+    pub fatigue: u32,                        // 疲劳度
+    pub task_preference: Option<TaskTypePreference>, // 长期任务偏好
+    pub specialization: Option<String>,      // 专精路线
 }
 
 /// 当前任务详情
@@ -157,6 +311,8 @@ pub struct CurrentTaskInfo {
     pub task_name: String,
     pub duration: u32,
     pub progress: u32,
+    pub phase: String,                  // 前往中/执行中/返程中
+    pub estimated_remaining_turns: u32, // 预计剩余回合数（含路程）
 }
 
 impl From<&Disciple> for DiscipleDto {
@@ -194,12 +350,30 @@ impl From<&Disciple> for DiscipleDto {
             },
             children_count: disciple.children.len(),
             current_task_info: None,  // 将在web_server中填充
-            movement_range: disciple.cultivation.current_level.movement_range(),
+            movement_range: disciple.get_effective_movement_range(),
             moves_remaining: disciple.moves_remaining,
             position: PositionDto {
                 x: disciple.position.x,
                 y: disciple.position.y,
             },
+            injuries: disciple.injuries.iter().map(|i| i.severity.name().to_string()).collect(),
+            equipped_technique: disciple.current_technique().map(|t| t.name.clone()),
+            equipment: EquipmentSlot::ALL
+                .iter()
+                .filter_map(|slot| disciple.equipment.get(slot))
+                .map(|e| e.into())
+                .collect(),
+            personality: disciple.personality.map(|p| p.name().to_string()),
+            tasks_completed: disciple.tasks_completed,
+            reputation_contributed: disciple.reputation_contributed,
+            total_resources_contributed: disciple.total_resources_contributed,
+            monsters_slain: disciple.monsters_slain,
+            in_seclusion: disciple.is_in_seclusion(),
+            patrolling: disciple.patrolling,
+            seclusion_turns: disciple.seclusion_turns,
+            fatigue: disciple.fatigue,
+            task_preference: disciple.task_preference.clone(),
+            specialization: disciple.specialization.map(|s| s.name().to_string()),
         }
     }
 }
@@ -240,6 +414,8 @@ pub struct HeritageDto {
     pub name: String,
     pub level: String,
     pub tribulation_bonus: f32,
+    pub required_talent: Option<String>, // 继承所需灵根
+    pub required_dao_heart: u32,         // 继承所需道心
 }
 
 impl From<&Heritage> for HeritageDto {
@@ -248,10 +424,63 @@ impl From<&Heritage> for HeritageDto {
             name: heritage.name.clone(),
             level: format!("{:?}", heritage.level),
             tribulation_bonus: heritage.tribulation_bonus,
+            required_talent: heritage.talent.as_ref().map(|t| format!("{:?}", t)),
+            required_dao_heart: heritage.required_dao_heart(),
+        }
+    }
+}
+
+/// 传承库条目（heritage_index用于领取）
+#[derive(Debug, Serialize)]
+pub struct HeritageEntryDto {
+    pub heritage_index: usize,
+    #[serde(flatten)]
+    pub heritage: HeritageDto,
+}
+
+/// 成就及进度
+#[derive(Debug, Serialize)]
+pub struct AchievementDto {
+    pub id: String, // 成就标识，如 "MonsterSlayer"
+    pub name: String,
+    pub description: String,
+    pub reward: String,
+    pub current: u32,
+    pub target: u32,
+    pub unlocked: bool,
+    pub unlocked_year: Option<u32>,
+}
+
+impl From<&AchievementProgress> for AchievementDto {
+    fn from(progress: &AchievementProgress) -> Self {
+        let achievement = progress.achievement;
+        Self {
+            id: format!("{:?}", achievement),
+            name: achievement.name().to_string(),
+            description: achievement.description(),
+            reward: achievement.reward().describe(),
+            current: progress.current.min(progress.target),
+            target: progress.target,
+            unlocked: progress.unlocked_year.is_some(),
+            unlocked_year: progress.unlocked_year,
         }
     }
 }
 
+/// 领取传承请求
+#[derive(Debug, Deserialize)]
+pub struct InheritHeritageRequest {
+    pub heritage_index: usize,
+}
+
+/// 领取传承响应
+#[derive(Debug, Serialize)]
+pub struct InheritHeritageResponse {
+    pub disciple_id: usize,
+    pub heritage: HeritageDto,
+    pub tribulation_success_rate: f32, // 继承后的渡劫成功率
+}
+
 /// 任务DTO
 #[derive(Debug, Serialize, Clone)]
 pub struct TaskDto {
@@ -274,6 +503,18 @@ pub struct TaskDto {
     pub enemy_info: Option<EnemyInfo>,  // 敌人信息（战斗任务，包含唯一ID）
     pub position: Option<PositionDto>,  // 任务主位置（用于显示）
     pub valid_positions: Option<Vec<PositionDto>>,  // 所有有效位置（用于大型建筑）
+    pub risk_level: String,                // 风险等级（Low/Medium/High/Deadly）
+    pub estimated_success_rate: Option<f32>,  // 指定弟子的预估成功率（查询时传 disciple_id）
+    pub prerequisite_task_name: Option<String>, // 前置任务名称
+    pub locked: bool,                           // 前置任务未完成，暂不可分配
+    pub retryable: bool,                        // 上次部分成功，可重试一次
+    pub exploration_depth: Option<u32>,         // 探索深度（仅探索任务）
+}
+
+/// 任务列表查询参数
+#[derive(Debug, Deserialize)]
+pub struct TaskQuery {
+    pub disciple_id: Option<usize>, // 为该弟子预估各任务成功率
 }
 
 /// 敌人信息（用于定位地图上的具体怪物）
@@ -302,6 +543,148 @@ pub struct SuitableDisciples {
 #[derive(Debug, Deserialize)]
 pub struct AssignTaskRequest {
     pub disciple_id: usize,
+    pub depth: Option<u32>, // 探索深度（仅探索任务，0为浅层）
+}
+
+/// 任务续期请求
+#[derive(Debug, Deserialize)]
+pub struct ExtendTaskRequest {
+    pub turns: Option<u32>, // 续期回合数（默认1，单次最多10）
+}
+
+/// 任务续期响应
+#[derive(Debug, Serialize)]
+pub struct ExtendTaskResponse {
+    pub task_id: usize,
+    pub expiry_turns: u32,
+    pub remaining_turns: u32,
+    pub cost: u32,
+    pub resources_remaining: u32,
+}
+
+/// 闭关请求
+#[derive(Debug, Deserialize)]
+pub struct SeclusionRequest {
+    pub turns: Option<u32>, // 闭关回合数（默认1）
+}
+
+/// 闭关状态响应
+#[derive(Debug, Serialize)]
+pub struct SeclusionResponse {
+    pub disciple_id: usize,
+    pub seclusion_turns: u32,
+    pub cost_per_turn: u32,
+    pub progress_per_turn: u32,
+}
+
+/// 赠礼物品
+#[derive(Debug, Deserialize)]
+#[serde(tag = "kind")]
+pub enum GiftItemDto {
+    Pill { pill_type: String, grade: Option<String> },
+    Herb { name: String, quality: String },
+}
+
+/// 同门赠礼请求
+#[derive(Debug, Deserialize)]
+pub struct GiftRequest {
+    pub from_id: usize,
+    pub to_id: usize,
+    pub item: GiftItemDto,
+}
+
+/// 同门赠礼响应
+#[derive(Debug, Serialize)]
+pub struct GiftResponse {
+    pub item_name: String,
+    pub comrade_gain: i32,   // 双方战友分数提升
+    pub romance_gain: i32,   // 双方情感分数提升
+    pub from_scores: Option<RelationScoresDto>, // 赠礼者对受礼者的最新关系
+    pub to_scores: Option<RelationScoresDto>,   // 受礼者对赠礼者的最新关系
+}
+
+/// 设置任务偏好请求（task_type为空表示清除偏好）
+#[derive(Debug, Deserialize)]
+pub struct TaskPreferenceRequest {
+    pub task_type: Option<String>,   // Gathering / Combat / Exploration / Auxiliary / Investment
+    pub on_conflict: Option<String>, // PreferenceFirst（默认）/ PathFirst
+}
+
+/// 任务偏好响应
+#[derive(Debug, Serialize)]
+pub struct TaskPreferenceResponse {
+    pub disciple_id: usize,
+    pub task_preference: Option<TaskTypePreference>,
+}
+
+/// 巡逻状态响应
+#[derive(Debug, Serialize)]
+pub struct PatrolResponse {
+    pub disciple_id: usize,
+    pub patrolling: bool,
+    pub center: PositionDto, // 警戒中心（弟子所在位置）
+    pub radius: i32,      // 警戒半径（曼哈顿距离）
+}
+
+/// 比武对决DTO
+#[derive(Debug, Serialize)]
+pub struct TournamentMatchDto {
+    pub winner_id: usize,
+    pub winner_name: String,
+    pub loser_id: usize,
+    pub loser_name: String,
+    pub winner_rate: f32, // 胜者赛前胜率
+}
+
+/// 比武排名DTO
+#[derive(Debug, Serialize)]
+pub struct TournamentStandingDto {
+    pub rank: usize,
+    pub disciple_id: usize,
+    pub name: String,
+    pub wins: u32,
+    pub power: f32,
+}
+
+/// 比武大会响应
+#[derive(Debug, Serialize)]
+pub struct TournamentResponse {
+    pub year: u32,
+    pub matches: Vec<TournamentMatchDto>,
+    pub standings: Vec<TournamentStandingDto>,
+    pub reputation_gained: i32,
+}
+
+impl From<&crate::tournament::TournamentResult> for TournamentResponse {
+    fn from(result: &crate::tournament::TournamentResult) -> Self {
+        Self {
+            year: result.year,
+            matches: result
+                .matches
+                .iter()
+                .map(|m| TournamentMatchDto {
+                    winner_id: m.winner_id,
+                    winner_name: m.winner_name.clone(),
+                    loser_id: m.loser_id,
+                    loser_name: m.loser_name.clone(),
+                    winner_rate: m.winner_rate,
+                })
+                .collect(),
+            standings: result
+                .standings
+                .iter()
+                .enumerate()
+                .map(|(i, s)| TournamentStandingDto {
+                    rank: i + 1,
+                    disciple_id: s.disciple_id,
+                    name: s.name.clone(),
+                    wins: s.wins,
+                    power: s.power,
+                })
+                .collect(),
+            reputation_gained: result.reputation_gained,
+        }
+    }
 }
 
 /// 任务分配响应
@@ -312,6 +695,27 @@ pub struct AssignTaskResponse {
     pub message: String,
 }
 
+/// 批量分配中的单条分配
+#[derive(Debug, Deserialize)]
+pub struct BatchAssignItem {
+    pub task_id: usize,
+    pub disciple_ids: Vec<usize>,
+}
+
+/// 批量分配后单个任务的人数
+#[derive(Debug, Serialize)]
+pub struct BatchAssignResultDto {
+    pub task_id: usize,
+    pub participant_count: usize,
+    pub max_participants: u32,
+}
+
+/// 批量分配响应
+#[derive(Debug, Serialize)]
+pub struct BatchAssignResponse {
+    pub results: Vec<BatchAssignResultDto>,
+}
+
 /// 宗门被袭击状态
 #[derive(Debug, Serialize, Clone)]
 pub struct SectInvasionDto {
@@ -327,8 +731,9 @@ pub struct TurnStartResponse {
     pub events: Vec<GameEventDto>,
     pub tasks: Vec<TaskDto>,
     pub disciples: Vec<DiscipleDto>,
-    pub pending_recruitment: Option<DiscipleDto>,  // 待招募的弟子（需要确认）
+    pub recruitment_pool: Vec<RecruitCandidateDto>, // 本回合的招募池
     pub sect_invasion: Option<SectInvasionDto>,    // 宗门被袭击状态
+    pub harvest: String,                           // 当年年景（Bountiful/Normal/Disaster）
 }
 
 #[derive(Debug, Serialize)]
@@ -337,6 +742,62 @@ pub struct GameEventDto {
     pub message: String,
 }
 
+/// 撤销回合响应
+#[derive(Debug, Serialize)]
+pub struct UndoTurnResponse {
+    pub year: u32,            // 回滚后的年份
+    pub resources: u32,       // 回滚后的资源
+    pub undo_remaining: usize, // 还可撤销的回合数
+}
+
+/// 自动分配查询参数
+#[derive(Debug, Deserialize)]
+pub struct AutoAssignQuery {
+    pub strategy: Option<String>, // FirstFit（默认）/ MaxReward / CultivationPath
+}
+
+/// 事件日志查询参数
+#[derive(Debug, Deserialize)]
+pub struct EventLogQuery {
+    pub since_year: Option<u32>,  // 只返回该年份及之后的日志
+    pub page: Option<usize>,      // 页码（从1开始）
+    pub page_size: Option<usize>,
+}
+
+/// 事件日志条目DTO
+#[derive(Debug, Serialize)]
+pub struct GameLogEntryDto {
+    pub year: u32,
+    pub category: String,
+    pub message: String,
+}
+
+impl From<&GameLogEntry> for GameLogEntryDto {
+    fn from(entry: &GameLogEntry) -> Self {
+        Self {
+            year: entry.year,
+            category: format!("{:?}", entry.category),
+            message: entry.message.clone(),
+        }
+    }
+}
+
+/// 事件日志响应
+#[derive(Debug, Serialize)]
+pub struct EventLogResponse {
+    pub total: usize,  // 符合条件的日志总数
+    pub page: usize,
+    pub page_size: usize,
+    pub entries: Vec<GameLogEntryDto>,
+}
+
+/// 一致性检查响应
+#[derive(Debug, Serialize)]
+pub struct ValidateResponse {
+    pub valid: bool,
+    pub issues: Vec<String>, // 发现的问题
+}
+
 /// 回合结束请求
 #[derive(Debug, Deserialize)]
 pub struct TurnEndRequest {
@@ -353,18 +814,207 @@ pub struct TaskAssignmentDto {
 #[derive(Debug, Serialize)]
 pub struct TurnEndResponse {
     pub results: Vec<TaskResultDto>,
+    pub summary: TurnSummaryDto, // 本回合综合结算
     pub game_state: String,
 }
 
+/// 回合结算汇总DTO
+#[derive(Debug, Serialize)]
+pub struct TurnSummaryDto {
+    pub annual_income: u32,                // 本回合开始时入账的年度收入
+    pub spirit_stones_delta: i64,          // 结算期间灵石净变化
+    pub contribution_delta: i64,           // 结算期间贡献净变化
+    pub reputation_delta: i32,             // 结算期间声望净变化
+    pub tasks_completed: usize,
+    pub tasks_failed: usize,
+    pub casualties: Vec<String>,           // 陨落的弟子
+    pub breakthrough_ready: Vec<String>,   // 修为圆满、可以突破的弟子
+    pub relationship_changes: Vec<String>, // 关系提升
+}
+
+impl From<&crate::interactive::TurnSummary> for TurnSummaryDto {
+    fn from(summary: &crate::interactive::TurnSummary) -> Self {
+        Self {
+            annual_income: summary.annual_income,
+            spirit_stones_delta: summary.spirit_stones_delta,
+            contribution_delta: summary.contribution_delta,
+            reputation_delta: summary.reputation_delta,
+            tasks_completed: summary.tasks_completed,
+            tasks_failed: summary.tasks_failed,
+            casualties: summary.casualties.clone(),
+            breakthrough_ready: summary.breakthrough_ready.clone(),
+            relationship_changes: summary.relationship_changes.clone(),
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct TaskResultDto {
     pub task_id: usize,
     pub disciple_id: usize,
     pub disciple_name: String,
     pub success: bool,
+    pub outcome: String,      // 成功/部分成功/完全失败
+    pub completion: f32,      // 完成度
     pub rewards: Option<TaskRewards>,
     pub message: String,
     pub disciple_died: bool,  // 弟子是否死亡
+    pub combat: Option<CombatDetailDto>, // 战斗细节（仅战斗任务）
+    pub team_size: usize,     // 组队人数
+    pub synergy_bonus: f32,   // 组队协同加成
+    pub drops: Vec<MonsterDropDto>, // 讨伐妖魔的掉落
+}
+
+/// 妖魔掉落DTO
+#[derive(Debug, Serialize)]
+pub struct MonsterDropDto {
+    pub item_type: String, // Herb / Pill / Material
+    pub name: String,
+    pub grade: Option<String>, // 草药品质或丹药品阶
+    pub count: u32,
+}
+
+impl From<&crate::map::MonsterDrop> for MonsterDropDto {
+    fn from(drop: &crate::map::MonsterDrop) -> Self {
+        use crate::map::MonsterDrop;
+        match drop {
+            MonsterDrop::Herb { name, quality } => Self {
+                item_type: "Herb".to_string(),
+                name: name.clone(),
+                grade: Some(quality.name().to_string()),
+                count: 1,
+            },
+            MonsterDrop::Pill { pill_type, grade } => Self {
+                item_type: "Pill".to_string(),
+                name: pill_type.name().to_string(),
+                grade: Some(grade.name().to_string()),
+                count: 1,
+            },
+            MonsterDrop::Material { name, count } => Self {
+                item_type: "Material".to_string(),
+                name: name.clone(),
+                grade: None,
+                count: *count,
+            },
+        }
+    }
+}
+
+/// 战斗细节DTO
+#[derive(Debug, Serialize)]
+pub struct CombatDetailDto {
+    pub disciple_level: u32,
+    pub enemy_level: u32,
+    pub success_rate: f32,
+    pub reward_multiplier: f32,
+    pub constitution_lost: u32,
+}
+
+impl From<&crate::event::CombatDetail> for CombatDetailDto {
+    fn from(detail: &crate::event::CombatDetail) -> Self {
+        Self {
+            disciple_level: detail.disciple_level,
+            enemy_level: detail.enemy_level,
+            success_rate: detail.success_rate,
+            reward_multiplier: detail.reward_multiplier,
+            constitution_lost: detail.constitution_lost,
+        }
+    }
+}
+
+/// 任务奖励预览查询参数
+#[derive(Debug, Deserialize)]
+pub struct TaskPreviewQuery {
+    pub disciple_id: usize,
+}
+
+/// 任务奖励预览响应（不执行任务，与实际结算数值一致）
+#[derive(Debug, Serialize)]
+pub struct TaskRewardPreviewResponse {
+    pub task_id: usize,
+    pub disciple_id: usize,
+    pub progress: u32,         // 修为
+    pub resources: u32,        // 灵石
+    pub contribution: u32,     // 贡献点
+    pub reputation: i32,       // 声望
+    pub dao_heart_change: i32, // 道心变化
+    pub success_rate: f32,     // 预估成功率
+}
+
+/// 排行榜查询参数
+#[derive(Debug, Deserialize)]
+pub struct LeaderboardQuery {
+    pub by: Option<String>, // resources | tasks | cultivation，默认 resources
+}
+
+/// 排行榜条目
+#[derive(Debug, Serialize)]
+pub struct LeaderboardEntryDto {
+    pub rank: usize,
+    pub disciple_id: usize,
+    pub name: String,
+    pub cultivation: CultivationDto,
+    pub tasks_completed: u32,
+    pub total_resources_contributed: u32,
+    pub monsters_slain: u32,
+}
+
+/// 排行榜响应
+#[derive(Debug, Serialize)]
+pub struct LeaderboardResponse {
+    pub by: String,
+    pub entries: Vec<LeaderboardEntryDto>,
+}
+
+/// 收入明细条目
+#[derive(Debug, Serialize)]
+pub struct IncomeEntryDto {
+    pub source: String, // 产出元素或建筑名称
+    pub amount: u32,
+}
+
+/// 年度收入明细响应
+#[derive(Debug, Serialize)]
+pub struct IncomeResponse {
+    pub sources: Vec<IncomeEntryDto>,          // 村庄、势力的收入
+    pub building_bonuses: Vec<IncomeEntryDto>, // 建筑收入加成
+    pub total: u32,                            // 年度总收入
+}
+
+/// 纪念堂记录DTO
+#[derive(Debug, Serialize)]
+pub struct FallenRecordDto {
+    pub disciple_id: usize,
+    pub name: String,
+    pub highest_level: String, // 最高修为
+    pub cause: String,         // 寿终/战死/渡劫身死/叛离
+    pub year: u32,             // 卒年
+    pub age: u32,
+    pub tasks_completed: u32,
+    pub resources_contributed: u32,
+    pub reputation_contributed: u32,
+}
+
+impl From<&crate::sect::FallenRecord> for FallenRecordDto {
+    fn from(record: &crate::sect::FallenRecord) -> Self {
+        Self {
+            disciple_id: record.disciple_id,
+            name: record.name.clone(),
+            highest_level: format!("{:?}", record.highest_level),
+            cause: record.cause.name().to_string(),
+            year: record.year,
+            age: record.age,
+            tasks_completed: record.tasks_completed,
+            resources_contributed: record.resources_contributed,
+            reputation_contributed: record.reputation_contributed,
+        }
+    }
+}
+
+/// 纪念堂响应
+#[derive(Debug, Serialize)]
+pub struct MemorialResponse {
+    pub records: Vec<FallenRecordDto>,
 }
 
 /// 统计信息响应
@@ -373,9 +1023,21 @@ pub struct StatisticsResponse {
     pub year: u32,
     pub total_disciples: usize,
     pub disciples_by_type: DisciplesByType,
-    pub resources: u32,
+    pub spirit_stones: u32, // 灵石
+    pub spirit_stone_capacity: u32, // 灵石仓储上限
+    pub contribution: u32,  // 贡献点
     pub reputation: i32,
     pub cultivation_distribution: std::collections::HashMap<String, usize>,
+    pub debt: u32,       // 负债（0表示无负债）
+    pub debt_turns: u32, // 连续负债的年数
+    pub average_age: f32,
+    pub average_dao_heart: f32,
+    pub survival_rate: f32,    // 存活率 0-1
+    pub total_recruited: u32,  // 本局累计招募
+    pub total_deaths: u32,     // 本局累计陨落
+    pub rank: SectRankDto,
+    pub morale_score: i32, // 士气值（平均道心、近期胜败、负债综合）
+    pub morale: String,    // 士气高昂 / 人心安定 / 人心涣散
 }
 
 #[derive(Debug, Serialize)]
@@ -399,12 +1061,15 @@ pub struct TribulationCandidateDto {
     pub success_rate: f32,
     pub dao_heart: u32,
     pub heritage_bonus: f32,
+    pub bottleneck: bool, // 是否卡在瓶颈（成功率已折减）
 }
 
 /// 渡劫请求
 #[derive(Debug, Deserialize)]
 pub struct TribulationRequest {
     pub disciple_id: usize,
+    #[serde(default)]
+    pub guardian_ids: Vec<usize>, // 护法弟子ID（消耗精力，提升渡劫成功率）
 }
 
 /// 渡劫响应
@@ -414,7 +1079,49 @@ pub struct TribulationResponse {
     pub disciple_id: usize,
     pub name: String,
     pub new_level: Option<String>,
+    pub outcome: String, // "Success" | "Injured"（重伤，可再试） | "Died"（身死） | "NotReady"
     pub message: String,
+    pub aftermath: Option<TribulationAftermathDto>, // 化神及以上渡劫波及周边的影响
+    pub injured_guardians: Vec<usize>,              // 被天劫波及受伤的护法ID
+}
+
+/// 道心受天劫波动的弟子
+#[derive(Debug, Serialize)]
+pub struct DaoHeartShakeDto {
+    pub disciple_id: usize,
+    pub dao_heart_change: i32,
+}
+
+/// 高阶渡劫对周边的波及
+#[derive(Debug, Serialize)]
+pub struct TribulationAftermathDto {
+    pub shaken_disciples: Vec<DaoHeartShakeDto>,
+    pub affected_monsters: Vec<String>, // 成功时被震退，失败时被吸引靠近
+    pub blessed_disciples: usize,       // 获得天劫气运的弟子数
+}
+
+impl From<&TribulationAftermath> for TribulationAftermathDto {
+    fn from(aftermath: &TribulationAftermath) -> Self {
+        Self {
+            shaken_disciples: aftermath
+                .shaken
+                .iter()
+                .map(|&(disciple_id, dao_heart_change)| DaoHeartShakeDto { disciple_id, dao_heart_change })
+                .collect(),
+            affected_monsters: aftermath.monsters.clone(),
+            blessed_disciples: aftermath.blessed,
+        }
+    }
+}
+
+/// 疗伤响应
+#[derive(Debug, Serialize)]
+pub struct HealDiscipleResponse {
+    pub disciple_id: usize,
+    pub healed: Vec<String>,      // 治愈的伤势
+    pub cost: u32,                // 花费资源
+    pub resources_remaining: u32,
+    pub debt: u32,                // 透支后的负债
 }
 
 /// 地图元素DTO
@@ -428,7 +1135,7 @@ pub struct MapElementDto {
     pub details: MapElementDetails,
 }
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct PositionDto {
     pub x: i32,
     pub y: i32,
@@ -449,8 +1156,10 @@ pub enum MapElementDetails {
     DangerousLocation { danger_level: u32 },
     SecretRealm { realm_type: String, difficulty: u32, under_attack: Option<AttackInfo> },
     Monster { monster_id: String, level: u32, is_demon: bool, growth_rate: f64, invading_location: Option<String> },
+    MonsterNest { level: u32, turns_until_spawn: u32, aura_radius: u32, aura_level_bonus: u32 },
     Terrain { terrain_type: String, variant_type: Option<String> },
     Herb { herb_id: String, quality: String, growth_stage: u32, max_growth: u32, is_mature: bool },
+    Opportunity { opportunity_id: String, turns_left: u32 },
 }
 
 /// 攻击信息
@@ -461,12 +1170,48 @@ pub struct AttackInfo {
     pub is_demon: bool,          // 是否为魔物
 }
 
+/// 地图查询参数
+#[derive(Debug, Deserialize)]
+pub struct MapQuery {
+    pub element_type: Option<String>, // 只返回该类型的元素（如 "Monster"）
+    pub min_x: Option<i32>,           // 包围盒（闭区间，可只给部分边界）
+    pub max_x: Option<i32>,
+    pub min_y: Option<i32>,
+    pub max_y: Option<i32>,
+}
+
+/// 地图渲染查询参数
+#[derive(Debug, Deserialize)]
+pub struct MapRenderQuery {
+    pub format: Option<String>, // 渲染格式，目前支持 "ascii"（默认）
+}
+
+/// 地图渲染响应
+#[derive(Debug, Serialize)]
+pub struct MapRenderResponse {
+    pub width: i32,
+    pub height: i32,
+    pub text: String, // 字符网格，每行一个y坐标
+    pub legend: String,
+}
+
 /// 地图数据响应
 #[derive(Debug, Serialize)]
 pub struct MapDataResponse {
     pub width: i32,
     pub height: i32,
     pub elements: Vec<MapElementDto>,
+    pub rival_sects: Vec<RivalSectDto>, // AI敌对宗门
+}
+
+/// 敌对宗门信息
+#[derive(Debug, Serialize)]
+pub struct RivalSectDto {
+    pub name: String,
+    pub position: PositionDto,
+    pub disciples: u32,
+    pub power: u32,                   // 实力（宗门战敌人等级）
+    pub occupied_realms: Vec<String>, // 占据的秘境（location_id）
 }
 
 /// 丹药库存响应
@@ -477,12 +1222,102 @@ pub struct PillInventoryResponse {
 
 #[derive(Debug, Serialize)]
 pub struct PillInfo {
-    pub count: u32,
+    pub count: u32,  // 各品级合计（指定品级时为该品级数量）
     pub name: String,
     pub description: String,
     pub energy_restore: u32,
     pub constitution_restore: u32,
     pub cultivation_boost: u32,
+    pub grades: Vec<PillGradeInfo>,  // 各品级的库存与药效
+}
+
+/// 单一品级丹药的库存与药效
+#[derive(Debug, Serialize)]
+pub struct PillGradeInfo {
+    pub grade: String,       // Low/Middle/High
+    pub grade_name: String,  // 下品/中品/上品
+    pub count: u32,
+    pub energy_restore: u32,
+    pub constitution_restore: u32,
+    pub cultivation_boost: u32,
+}
+
+/// 丹药库存查询参数
+#[derive(Debug, Deserialize)]
+pub struct PillInventoryQuery {
+    pub grade: Option<String>, // 只返回该品级（Low/Middle/High）
+}
+
+/// 批量操作的弟子筛选条件（未指定的条件不限制）
+#[derive(Debug, Deserialize, Default)]
+pub struct DiscipleFilterDto {
+    pub min_level: Option<String>,     // 最低修为，如 "QiRefining"
+    pub max_level: Option<String>,     // 最高修为
+    pub disciple_type: Option<String>, // Outer / Inner / Personal
+    pub idle: Option<bool>,            // 是否空闲（未执行任务）
+    pub talent: Option<String>,        // 拥有的灵根，如 "Fire"
+}
+
+/// 批量操作的动作
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+pub enum BatchActionDto {
+    UsePill { pill_type: String, grade: Option<String> },
+    Move { x: i32, y: i32 },
+    AutoAssign { strategy: Option<String> },
+}
+
+/// 按条件批量操作弟子请求
+#[derive(Debug, Deserialize)]
+pub struct BatchDiscipleRequest {
+    #[serde(default)]
+    pub filter: DiscipleFilterDto,
+    pub action: BatchActionDto,
+}
+
+/// 批量操作中单个弟子的结果
+#[derive(Debug, Serialize)]
+pub struct BatchDiscipleResultDto {
+    pub disciple_id: usize,
+    pub disciple_name: String,
+    pub success: bool,
+    pub code: Option<String>, // 失败时的错误码
+    pub message: String,
+}
+
+/// 按条件批量操作弟子响应
+#[derive(Debug, Serialize)]
+pub struct BatchDiscipleResponse {
+    pub matched: usize,
+    pub succeeded: usize,
+    pub results: Vec<BatchDiscipleResultDto>,
+}
+
+/// 编队移动请求
+#[derive(Debug, Deserialize)]
+pub struct FormationMoveRequest {
+    pub disciple_ids: Vec<usize>,
+    pub target: PositionDto, // 集结目标
+}
+
+/// 编队移动中单个弟子的结果
+#[derive(Debug, Serialize)]
+pub struct FormationMoveResultDto {
+    pub disciple_id: usize,
+    pub disciple_name: String,
+    pub success: bool,
+    pub code: Option<String>,             // 失败时的错误码
+    pub message: String,
+    pub destination: Option<PositionDto>, // 分配到的集结位置
+    pub position: Option<PositionDto>,    // 本回合移动后的位置
+    pub arrived: bool,                    // 是否已抵达集结位置
+}
+
+/// 编队移动响应
+#[derive(Debug, Serialize)]
+pub struct FormationMoveResponse {
+    pub arrived: usize,
+    pub results: Vec<FormationMoveResultDto>,
 }
 
 /// 服用丹药请求
@@ -490,6 +1325,7 @@ pub struct PillInfo {
 pub struct UsePillRequest {
     pub disciple_id: usize,
     pub pill_type: String,
+    pub grade: Option<String>, // 丹药品级（默认下品）
 }
 
 /// 服用丹药响应
@@ -504,6 +1340,16 @@ pub struct UsePillResponse {
     pub constitution_after: u32,
     pub progress_before: u32,
     pub progress_after: u32,
+    pub lifespan_before: u32,
+    pub lifespan_after: u32,
+    pub pills_taken_this_turn: u32,   // 本回合已服药次数
+    pub overdosed: bool,              // 是否超过服药上限
+    pub side_effect_triggered: bool,  // 是否触发副作用
+    pub side_effect: Option<String>,  // 副作用名称
+    pub poisoned: bool,               // 是否药毒入体
+    pub bottleneck_broken: bool,      // 是否破除了修为瓶颈
+    pub buff: Option<String>,         // 获得的临时增益
+    pub grade: String,                // 服用的丹药品级
 }
 
 /// 建筑DTO
@@ -516,6 +1362,8 @@ pub struct BuildingDto {
     pub actual_cost: u32,  // 考虑倍增后的实际成本
     pub parent_id: Option<String>,
     pub is_built: bool,
+    pub build_turns: u32,  // 建造工期（回合数）
+    pub turns_remaining: Option<u32>,  // 在建时的剩余回合数
     pub can_build: bool,  // 是否可以建造（父节点已建造且自己未建造）
     pub effects: Vec<String>,  // 效果描述
 }
@@ -547,6 +1395,7 @@ pub struct BuildBuildingResponse {
     pub resources_before: u32,
     pub resources_after: u32,
     pub effects_count: usize,
+    pub turns_remaining: u32,  // 剩余工期（0表示已建成）
 }
 
 /// 任务资格检查请求
@@ -570,10 +1419,41 @@ pub struct TaskEligibilityResponse {
     pub enemy_level: Option<u32>,  // 敌人等级
 }
 
+/// 招募池中的候选弟子
+#[derive(Debug, Serialize)]
+pub struct RecruitCandidateDto {
+    pub candidate_index: usize,
+    pub disciple: DiscipleDto, // 含天赋、家传等信息
+    pub potential: u32,        // 预估潜力 0-100
+    pub price: u32,            // 招募价格
+}
+
+impl RecruitCandidateDto {
+    pub fn from_pool(pool: &[Disciple]) -> Vec<Self> {
+        pool.iter()
+            .enumerate()
+            .map(|(candidate_index, disciple)| Self {
+                candidate_index,
+                disciple: disciple.into(),
+                potential: crate::event::RecruitmentSystem::candidate_potential(disciple),
+                price: crate::event::RecruitmentSystem::candidate_price(disciple),
+            })
+            .collect()
+    }
+}
+
+/// 招募池响应
+#[derive(Debug, Serialize)]
+pub struct RecruitmentPoolResponse {
+    pub candidates: Vec<RecruitCandidateDto>,
+    pub refresh_cost: u32, // 刷新招募池的花费
+    pub resources: u32,    // 当前灵石
+}
+
 /// 招募弟子请求
 #[derive(Debug, Deserialize)]
 pub struct RecruitDiscipleRequest {
-    pub accept: bool,  // true=接受招募, false=拒绝招募
+    pub candidate_index: usize, // 招募池中的候选序号
 }
 
 /// 招募弟子响应
@@ -584,7 +1464,7 @@ pub struct RecruitDiscipleResponse {
     pub disciple: Option<DiscipleDto>,  // 招募成功时返回弟子信息
     pub resources_before: u32,
     pub resources_after: u32,
-    pub cost: u32,  // 招募成本（1000）
+    pub cost: u32,  // 招募成本（按候选潜力定价）
 }
 
 /// 移动弟子请求
@@ -594,6 +1474,251 @@ pub struct MoveDiscipleRequest {
     pub y: i32,
 }
 
+/// 功法DTO
+#[derive(Debug, Serialize, Clone)]
+pub struct TechniqueDto {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub talent_type: String,           // 适配灵根
+    pub cultivation_speed_bonus: f32,  // 修炼速度加成
+    pub task_reward_bonus: f32,        // 任务奖励加成
+    pub learn_cost: u32,               // 学习消耗的贡献点
+}
+
+impl From<&Technique> for TechniqueDto {
+    fn from(technique: &Technique) -> Self {
+        Self {
+            id: technique.id.clone(),
+            name: technique.name.clone(),
+            description: technique.description.clone(),
+            talent_type: format!("{:?}", technique.talent_type),
+            cultivation_speed_bonus: technique.cultivation_speed_bonus,
+            task_reward_bonus: technique.task_reward_bonus,
+            learn_cost: technique.learn_cost,
+        }
+    }
+}
+
+/// 弟子功法列表响应
+#[derive(Debug, Serialize)]
+pub struct DiscipleTechniquesResponse {
+    pub disciple_id: usize,
+    pub learned: Vec<TechniqueDto>,
+    pub active: Option<String>, // 当前激活功法的ID
+    pub capacity: usize,        // 可学会的功法上限（由藏书楼等级决定）
+}
+
+/// 学习功法请求
+#[derive(Debug, Deserialize)]
+pub struct LearnTechniqueRequest {
+    pub technique_id: String,
+}
+
+/// 学习功法响应
+#[derive(Debug, Serialize)]
+pub struct LearnTechniqueResponse {
+    pub disciple_id: usize,
+    pub technique: TechniqueDto,
+    pub active: Option<String>,
+    pub cost: u32,
+    pub contribution_remaining: u32,
+}
+
+/// 属性的原始值与有效值
+#[derive(Debug, Serialize)]
+pub struct AttributeValueDto {
+    pub native: f32,
+    pub effective: f32,
+    pub steps: Vec<ModifierStep>,      // 逐步应用的modifier
+    pub overridden_by: Option<String>, // 被哪条Override短路
+}
+
+impl From<&ModifierExplanation> for AttributeValueDto {
+    fn from(explanation: &ModifierExplanation) -> Self {
+        Self {
+            native: explanation.native,
+            effective: explanation.effective,
+            steps: explanation.steps.clone(),
+            overridden_by: explanation.overridden_by.clone(),
+        }
+    }
+}
+
+/// 资质加成（原始值与有效值）
+#[derive(Debug, Serialize)]
+pub struct TalentBonusDto {
+    pub talent_type: String,
+    pub native: f32,
+    pub effective: f32,
+}
+
+/// 生效的modifier明细
+#[derive(Debug, Serialize)]
+pub struct ModifierDetailDto {
+    pub name: String,
+    pub origin: String,      // 出处：个人 / 宗门 / 建筑名称
+    pub source: String,      // Modifier来源类型（功法、装备、伤势等）
+    pub target: String,
+    pub application: String, // Additive / Multiplicative / Override
+    pub value: f32,
+    pub duration: Option<u32>,
+}
+
+impl From<&ModifierOrigin> for ModifierDetailDto {
+    fn from(origin: &ModifierOrigin) -> Self {
+        use crate::modifier::ModifierApplication;
+
+        let modifier = &origin.modifier;
+        let (application, value) = match modifier.application {
+            ModifierApplication::Additive(v) => ("Additive", v),
+            ModifierApplication::Multiplicative(v) => ("Multiplicative", v),
+            ModifierApplication::Override(v) => ("Override", v),
+        };
+        Self {
+            name: modifier.name.clone(),
+            origin: origin.origin.clone(),
+            source: format!("{:?}", modifier.source),
+            target: format!("{:?}", modifier.target),
+            application: application.to_string(),
+            value,
+            duration: modifier.duration,
+        }
+    }
+}
+
+/// 弟子有效属性响应（应用个人、宗门、建筑modifier后）
+#[derive(Debug, Serialize)]
+pub struct EffectiveAttributesResponse {
+    pub disciple_id: usize,
+    pub dao_heart: AttributeValueDto,
+    pub energy: AttributeValueDto,
+    pub constitution: AttributeValueDto,
+    pub talent_bonuses: Vec<TalentBonusDto>,
+    pub tribulation_success_rate: f32,
+    pub modifiers: Vec<ModifierDetailDto>,
+}
+
+impl EffectiveAttributesResponse {
+    pub fn new(disciple: &Disciple, attributes: &EffectiveAttributes) -> Self {
+        let native_talent_bonus = |talent_type| {
+            disciple
+                .talents
+                .iter()
+                .find(|t| &t.talent_type == talent_type)
+                .map(|t| t.level as f32 * 0.1)
+                .unwrap_or(0.0)
+        };
+        Self {
+            disciple_id: disciple.id,
+            dao_heart: (&attributes.dao_heart).into(),
+            energy: (&attributes.energy).into(),
+            constitution: (&attributes.constitution).into(),
+            talent_bonuses: attributes
+                .talent_bonuses
+                .iter()
+                .map(|(talent_type, effective)| TalentBonusDto {
+                    talent_type: format!("{:?}", talent_type),
+                    native: native_talent_bonus(talent_type),
+                    effective: *effective,
+                })
+                .collect(),
+            tribulation_success_rate: attributes.tribulation_success_rate,
+            modifiers: attributes.modifiers.iter().map(|m| m.into()).collect(),
+        }
+    }
+}
+
+/// 切换激活功法请求（technique_id为空表示停用功法）
+#[derive(Debug, Deserialize)]
+pub struct EquipTechniqueRequest {
+    pub technique_id: Option<String>,
+}
+
+/// 装备功法响应
+#[derive(Debug, Serialize)]
+pub struct EquipTechniqueResponse {
+    pub disciple_id: usize,
+    pub equipped: Option<TechniqueDto>,
+    pub talent_matched: bool,              // 是否具备对应灵根（否则加成减半）
+    pub effective_cultivation_speed: f32,  // 装备后的有效修炼速度倍率
+}
+
+/// 装备DTO
+#[derive(Debug, Serialize, Clone)]
+pub struct EquipmentDto {
+    pub id: String,
+    pub name: String,
+    pub slot: EquipmentSlot,
+    pub slot_name: String,
+    pub cost: u32,
+    pub effects: Vec<String>, // 提供的modifier描述
+}
+
+impl From<&Equipment> for EquipmentDto {
+    fn from(equipment: &Equipment) -> Self {
+        Self {
+            id: equipment.id.clone(),
+            name: equipment.name.clone(),
+            slot: equipment.slot,
+            slot_name: equipment.slot.name().to_string(),
+            cost: equipment.cost,
+            effects: equipment
+                .modifiers
+                .iter()
+                .map(|m| format!("{:?} {:?}", m.target, m.application))
+                .collect(),
+        }
+    }
+}
+
+/// 穿戴装备请求（equipment_id为空表示卸下slot槽位的装备）
+#[derive(Debug, Deserialize)]
+pub struct EquipRequest {
+    pub equipment_id: Option<String>,
+    pub slot: Option<String>,
+}
+
+/// 选择专精请求
+#[derive(Debug, Deserialize)]
+pub struct SpecializeRequest {
+    pub specialization: String, // Sword / Alchemy / Formation / Body
+}
+
+/// 选择专精响应
+#[derive(Debug, Serialize)]
+pub struct SpecializeResponse {
+    pub disciple_id: usize,
+    pub specialization: String,
+    pub cost: u32, // 改修消耗的资源（首次为0）
+}
+
+/// 说服弟子响应
+#[derive(Debug, Serialize)]
+pub struct PersuadeDiscipleResponse {
+    pub disciple_id: usize,
+    pub cost: u32,         // 花费的贡献点
+    pub contribution: u32, // 剩余贡献点
+}
+
+/// 弟子晋升响应
+#[derive(Debug, Serialize)]
+pub struct PromoteDiscipleResponse {
+    pub disciple_id: usize,
+    pub disciple_type: String,   // 晋升后的类型（Inner/Personal）
+    pub personal_count: usize,   // 当前亲传弟子数
+    pub personal_quota: usize,   // 亲传弟子名额
+}
+
+/// 穿戴装备响应
+#[derive(Debug, Serialize)]
+pub struct EquipResponse {
+    pub disciple_id: usize,
+    pub equipment: Vec<EquipmentDto>,   // 当前穿戴的装备
+    pub replaced: Option<EquipmentDto>, // 被替换或卸下的装备
+    pub remaining_resources: u32,
+}
+
 /// 采集的草药信息
 #[derive(Debug, Serialize)]
 pub struct CollectedHerbInfo {
@@ -612,6 +1737,7 @@ pub struct MoveDiscipleResponse {
     pub new_position: PositionDto,
     pub moves_remaining: u32,  // 移动后剩余的移动距离
     pub collected_herb: Option<CollectedHerbInfo>,
+    pub opportunity: Option<String>, // 踩中机缘点的奇遇描述
 }
 
 // === 关系系统相关 ===
@@ -713,6 +1839,22 @@ pub struct HerbInventoryResponse {
     pub herbs: Vec<HerbEntryDto>,
 }
 
+/// 草药交易请求
+#[derive(Debug, Deserialize)]
+pub struct HerbTradeRequest {
+    pub name: String,
+    pub quality: String,  // Common/Uncommon/Rare/Epic/Legendary 或中文品质名
+    pub count: u32,
+}
+
+/// 草药交易响应
+#[derive(Debug, Serialize)]
+pub struct HerbTradeResponse {
+    pub amount: u32,               // 成交金额（卖出时为实际入账金额）
+    pub resources_remaining: u32,  // 交易后资源
+    pub herb_count: u32,           // 交易后该草药库存
+    pub inventory: HerbInventoryResponse,
+}
 
 /// 丹药配方DTO
 #[derive(Debug, Serialize)]
@@ -725,6 +1867,9 @@ pub struct PillRecipeDto {
     pub resource_cost: u32,
     pub success_rate: f64,
     pub output_count: u32,
+    pub tier: String,                     // 配方等级（基础/高级/顶级）
+    pub unlocked: bool,                   // 是否已由建筑解锁
+    pub unlock_condition: Option<String>, // 解锁条件
     pub can_craft: bool,
     pub reason: Option<String>,
 }
@@ -739,6 +1884,8 @@ pub struct AllRecipesResponse {
 #[derive(Debug, Deserialize)]
 pub struct RefinePillRequest {
     pub pill_type: String,
+    pub alchemist_id: usize, // 执行炼丹的弟子
+    pub herb_quality: Option<String>, // 使用的草药品质（不低于配方要求，默认按配方）
 }
 
 /// 炼制丹药响应
@@ -748,4 +1895,63 @@ pub struct RefinePillResponse {
     pub message: String,
     pub pill_name: Option<String>,
     pub output_count: Option<u32>,
+    pub grade: Option<String>, // 成品品级
+}
+
+/// 批量炼丹中的一种丹药
+#[derive(Debug, Deserialize)]
+pub struct RefineBatchItem {
+    pub pill_type: String,
+    pub times: u32, // 炼制炉数
+}
+
+/// 批量炼丹请求（按顺序炼制，资源或草药不足时停止）
+#[derive(Debug, Deserialize)]
+pub struct BatchRefineRequest {
+    pub alchemist_id: usize,
+    pub items: Vec<RefineBatchItem>,
+}
+
+/// 批量炼丹中单种丹药的结果
+#[derive(Debug, Serialize)]
+pub struct BatchRefineEntryDto {
+    pub pill_type: String,
+    pub pill_name: String,
+    pub requested: u32,
+    pub succeeded: u32,
+    pub failed: u32,
+    pub output_count: u32,
+}
+
+/// 批量炼丹响应
+#[derive(Debug, Serialize)]
+pub struct BatchRefineResponse {
+    pub entries: Vec<BatchRefineEntryDto>,
+    pub spirit_stones_spent: u32,
+    pub herbs_consumed: u32,
+    pub completed: bool,             // 是否全部炼完
+    pub stop_reason: Option<String>, // 中途停止的原因
+}
+
+/// 加入炼丹队列请求
+#[derive(Debug, Deserialize)]
+pub struct QueueRefineRequest {
+    pub pill_type: String,
+    pub alchemist_id: usize,
+    pub times: u32,
+}
+
+/// 炼丹队列中的订单
+#[derive(Debug, Serialize)]
+pub struct RefineOrderDto {
+    pub pill_type: String,
+    pub pill_name: String,
+    pub alchemist_id: usize,
+    pub remaining: u32,
+}
+
+/// 炼丹队列响应
+#[derive(Debug, Serialize)]
+pub struct RefineQueueResponse {
+    pub orders: Vec<RefineOrderDto>,
 }
@@ -2,6 +2,13 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use crate::modifier::ConditionalModifier;
 
+/// 默认建造工期（回合数）
+pub const DEFAULT_BUILD_TURNS: u32 = 2;
+
+fn default_build_turns() -> u32 {
+    DEFAULT_BUILD_TURNS
+}
+
 /// 建筑定义
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Building {
@@ -12,6 +19,8 @@ pub struct Building {
     pub parent_id: Option<String>,  // 父建筑ID，None表示根节点
     pub conditional_modifiers: Vec<ConditionalModifier>,  // 建筑提供的条件modifier
     pub is_built: bool,  // 是否已建造
+    #[serde(default = "default_build_turns")]
+    pub build_turns: u32,  // 建造工期（回合数，0表示立即建成）
 }
 
 impl Building {
@@ -32,9 +41,16 @@ impl Building {
             parent_id,
             conditional_modifiers,
             is_built: false,
+            build_turns: DEFAULT_BUILD_TURNS,
         }
     }
 
+    /// 设置建造工期
+    pub fn with_build_turns(mut self, build_turns: u32) -> Self {
+        self.build_turns = build_turns;
+        self
+    }
+
     /// 创建根建筑
     pub fn new_root(
         id: impl Into<String>,
@@ -65,6 +81,8 @@ pub struct BuildingTree {
     pub buildings: HashMap<String, Building>,
     pub root_id: String,
     pub buildings_built_count: u32,  // 已建造的建筑数量（用于计算成本倍增）
+    #[serde(default)]
+    pub under_construction: HashMap<String, u32>,  // 在建建筑 -> 剩余回合（可同时建造多座）
 }
 
 impl BuildingTree {
@@ -78,6 +96,7 @@ impl BuildingTree {
             buildings,
             root_id,
             buildings_built_count: 0,
+            under_construction: HashMap::new(),
         }
     }
 
@@ -113,7 +132,12 @@ impl BuildingTree {
             return Err(format!("建筑'{}'已经建造", building.name));
         }
 
-        // 3. 检查父节点是否已建造
+        // 3. 检查是否正在建造
+        if self.under_construction.contains_key(building_id) {
+            return Err(format!("建筑'{}'正在建造中", building.name));
+        }
+
+        // 4. 检查父节点是否已建造
         if let Some(ref parent_id) = building.parent_id {
             let parent = self.buildings.get(parent_id)
                 .ok_or_else(|| format!("父建筑'{}'不存在", parent_id))?;
@@ -158,6 +182,51 @@ impl BuildingTree {
         Ok(building.conditional_modifiers.clone())
     }
 
+    /// 开工建造建筑，返回工期（工期为0时立即建成）
+    pub fn start_construction(&mut self, building_id: &str) -> Result<u32, String> {
+        self.can_build(building_id)?;
+
+        let build_turns = self.buildings.get(building_id)
+            .map(|b| b.build_turns)
+            .ok_or_else(|| format!("建筑'{}'不存在", building_id))?;
+
+        if build_turns == 0 {
+            self.build(building_id)?;
+        } else {
+            // 开工即计入成本倍增，避免同时开工多座建筑绕过倍增
+            self.under_construction.insert(building_id.to_string(), build_turns);
+            self.buildings_built_count += 1;
+        }
+
+        Ok(build_turns)
+    }
+
+    /// 推进一回合工期，返回本回合竣工的建筑ID
+    pub fn advance_construction(&mut self) -> Vec<String> {
+        let mut completed = Vec::new();
+        for (id, turns_remaining) in self.under_construction.iter_mut() {
+            *turns_remaining = turns_remaining.saturating_sub(1);
+            if *turns_remaining == 0 {
+                completed.push(id.clone());
+            }
+        }
+        completed.sort();
+
+        for id in &completed {
+            self.under_construction.remove(id);
+            if let Some(building) = self.buildings.get_mut(id) {
+                building.is_built = true;
+            }
+        }
+
+        completed
+    }
+
+    /// 获取在建建筑的剩余工期
+    pub fn turns_remaining(&self, building_id: &str) -> Option<u32> {
+        self.under_construction.get(building_id).copied()
+    }
+
     /// 获取所有已建造建筑提供的modifiers
     pub fn get_all_modifiers(&self) -> Vec<ConditionalModifier> {
         self.buildings
@@ -219,6 +288,7 @@ impl BuildingTree {
             building.is_built = false;
         }
         self.buildings_built_count = 0;
+        self.under_construction.clear();
     }
 }
 
@@ -280,52 +350,85 @@ impl Default for BuildingTreeBuilder {
 
 /// 从配置文件创建宗门建筑树
 pub fn create_sect_building_tree() -> BuildingTree {
-    use crate::config::BuildingsConfig;
-
-    let config = BuildingsConfig::load();
-
-    // 找到根节点（parent_id 为 None 的建筑）
-    let root_config = config.buildings.iter()
-        .find(|b| b.parent_id.is_none())
-        .expect("建筑配置必须包含一个根节点");
-
-    let modifiers: Vec<ConditionalModifier> = root_config.modifiers.iter()
-        .map(|mc| mc.to_conditional_modifier())
-        .collect();
-
-    let root = Building::new_root(
-        &root_config.id,
-        &root_config.name,
-        &root_config.description,
-        root_config.base_cost,
-        modifiers,
-    );
-
-    let mut tree = BuildingTree::new(root);
-
-    // 添加所有子建筑
-    for bc in &config.buildings {
-        if bc.parent_id.is_some() {
-            let modifiers: Vec<ConditionalModifier> = bc.modifiers.iter()
-                .map(|mc| mc.to_conditional_modifier())
-                .collect();
-
-            let building = Building::new_child(
-                &bc.id,
-                &bc.name,
-                &bc.description,
-                bc.base_cost,
-                bc.parent_id.as_ref().unwrap(),
-                modifiers,
-            );
-
-            if let Err(e) = tree.add_building(building) {
-                eprintln!("添加建筑 {} 失败: {}", bc.name, e);
+    BuildingTree::from_config(&crate::config::BuildingsConfig::load())
+}
+
+impl BuildingTree {
+    /// 从建筑配置创建建筑树
+    pub fn from_config(config: &crate::config::BuildingsConfig) -> BuildingTree {
+        // 找到根节点（parent_id 为 None 的建筑）
+        let root_config = config.buildings.iter()
+            .find(|b| b.parent_id.is_none())
+            .expect("建筑配置必须包含一个根节点");
+
+        let modifiers: Vec<ConditionalModifier> = root_config.modifiers.iter()
+            .map(|mc| mc.to_conditional_modifier())
+            .collect();
+
+        let root = Building::new_root(
+            &root_config.id,
+            &root_config.name,
+            &root_config.description,
+            root_config.base_cost,
+            modifiers,
+        ).with_build_turns(root_config.build_turns);
+
+        let mut tree = Self::new(root);
+
+        // 添加所有子建筑
+        for bc in &config.buildings {
+            if let Some(parent_id) = &bc.parent_id {
+                let modifiers: Vec<ConditionalModifier> = bc.modifiers.iter()
+                    .map(|mc| mc.to_conditional_modifier())
+                    .collect();
+
+                let building = Building::new_child(
+                    &bc.id,
+                    &bc.name,
+                    &bc.description,
+                    bc.base_cost,
+                    parent_id,
+                    modifiers,
+                ).with_build_turns(bc.build_turns);
+
+                if let Err(e) = tree.add_building(building) {
+                    eprintln!("添加建筑 {} 失败: {}", bc.name, e);
+                }
             }
         }
+
+        tree
     }
 
-    tree
+    /// 应用新的建筑配置：保留仍存在建筑的建造进度，返回 (新增, 移除) 的建筑名
+    pub fn apply_config(&mut self, config: &crate::config::BuildingsConfig) -> (Vec<String>, Vec<String>) {
+        let mut tree = Self::from_config(config);
+
+        let mut added: Vec<String> = tree.buildings.values()
+            .filter(|b| !self.buildings.contains_key(&b.id))
+            .map(|b| b.name.clone())
+            .collect();
+        let mut removed: Vec<String> = self.buildings.values()
+            .filter(|b| !tree.buildings.contains_key(&b.id))
+            .map(|b| b.name.clone())
+            .collect();
+        added.sort();
+        removed.sort();
+
+        for (id, building) in tree.buildings.iter_mut() {
+            if let Some(old) = self.buildings.get(id) {
+                building.is_built = old.is_built;
+            }
+        }
+        tree.under_construction = self.under_construction.iter()
+            .filter(|(id, _)| tree.buildings.contains_key(*id))
+            .map(|(id, turns)| (id.clone(), *turns))
+            .collect();
+        tree.buildings_built_count = self.buildings_built_count;
+
+        *self = tree;
+        (added, removed)
+    }
 }
 
 /// 创建默认的修仙宗门建筑树（兼容旧代码）
@@ -443,4 +546,45 @@ mod tests {
         let modifiers = tree.get_all_modifiers();
         assert_eq!(modifiers.len(), 1);
     }
+
+    #[test]
+    fn test_construction_takes_effect_after_build_turns() {
+        let modifier = ConditionalModifier::new(
+            ModifierCondition::Always,
+            Modifier::new(
+                "功法加成",
+                ModifierTarget::CultivationSpeed,
+                ModifierApplication::Multiplicative(0.15),
+                ModifierSource::System,
+            ),
+        );
+
+        let root = Building::new_root("root", "大殿", "核心", 100, vec![]).with_build_turns(0);
+        let mut tree = BuildingTree::new(root);
+        tree.add_building(Building::new_child("lib", "藏书楼", "书", 200, "root", vec![modifier]).with_build_turns(2)).unwrap();
+        tree.add_building(Building::new_child("forge", "炼器坊", "器", 200, "root", vec![]).with_build_turns(1)).unwrap();
+
+        // 工期为0的建筑立即建成
+        assert_eq!(tree.start_construction("root").unwrap(), 0);
+        assert!(tree.buildings["root"].is_built);
+
+        // 多座建筑可以同时开工，在建期间不能重复开工
+        assert_eq!(tree.start_construction("lib").unwrap(), 2);
+        assert_eq!(tree.start_construction("forge").unwrap(), 1);
+        assert!(tree.start_construction("lib").is_err());
+        assert_eq!(tree.buildings_built_count, 3);
+        assert_eq!(tree.turns_remaining("lib"), Some(2));
+
+        // 第一回合：炼器坊竣工，藏书楼仍在建，效果未生效
+        assert_eq!(tree.advance_construction(), vec!["forge".to_string()]);
+        assert!(!tree.buildings["lib"].is_built);
+        assert_eq!(tree.turns_remaining("lib"), Some(1));
+        assert!(tree.get_all_modifiers().is_empty());
+
+        // 第二回合：藏书楼竣工，效果生效
+        assert_eq!(tree.advance_construction(), vec!["lib".to_string()]);
+        assert!(tree.buildings["lib"].is_built);
+        assert_eq!(tree.turns_remaining("lib"), None);
+        assert_eq!(tree.get_all_modifiers().len(), 1);
+    }
 }
@@ -13,6 +13,48 @@ pub struct MapElementsConfig {
     pub factions: Vec<FactionTemplate>,
     pub dangerous_locations: Vec<DangerousLocationTemplate>,
     pub secret_realms: Vec<SecretRealmTemplate>,
+    #[serde(default)]
+    pub opportunities: OpportunityConfig, // 机缘点生成与奇遇概率表
+}
+
+/// 奇遇的收获类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum OpportunityRewardKind {
+    TechniqueScroll, // 功法残卷
+    PillRecipe,      // 丹方
+    TalentAwakening, // 天赋觉醒
+    SpiritStones,    // 一笔灵石
+}
+
+/// 奇遇概率表中的一项
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct OpportunityRewardWeight {
+    pub reward: OpportunityRewardKind,
+    pub weight: u32,
+}
+
+/// 机缘点配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct OpportunityConfig {
+    pub spawn_chance: f64,                     // 每回合在空地生成机缘点的概率
+    pub duration: u32,                         // 机缘点存在的回合数
+    pub rewards: Vec<OpportunityRewardWeight>, // 奇遇概率表（按权重抽取）
+}
+
+impl Default for OpportunityConfig {
+    fn default() -> Self {
+        let entry = |reward, weight| OpportunityRewardWeight { reward, weight };
+        Self {
+            spawn_chance: 0.1,
+            duration: 5,
+            rewards: vec![
+                entry(OpportunityRewardKind::TechniqueScroll, 2),
+                entry(OpportunityRewardKind::PillRecipe, 2),
+                entry(OpportunityRewardKind::TalentAwakening, 1),
+                entry(OpportunityRewardKind::SpiritStones, 5),
+            ],
+        }
+    }
 }
 
 /// 村庄模板
@@ -81,7 +123,7 @@ pub struct SizeConfig {
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct TaskTemplateConfig {
     pub name_template: String, // 支持 {name} 等占位符
-    pub task_type: String,      // "Gathering", "Combat", "Exploration", "Auxiliary"
+    pub task_type: String,      // "Gathering", "Combat", "Exploration", "Auxiliary", "Investment"
     pub progress_reward: u32,
     pub resource_reward: u32,
     pub reputation_reward: i32,
@@ -96,6 +138,30 @@ pub struct TaskTemplateConfig {
     pub danger_level: Option<u32>, // for Exploration
     #[serde(default)]
     pub skill_required: Option<String>, // for Auxiliary
+    #[serde(default)]
+    pub capital: Option<u32>, // for Investment
+    #[serde(default)]
+    pub return_rate: Option<f32>, // for Investment
+    #[serde(default)]
+    pub maturity_turns: Option<u32>, // for Investment
+
+    // 任务链
+    #[serde(default)]
+    pub prerequisite: Option<String>, // 前置任务名称（支持 {name} 占位符），完成前该任务锁定
+    #[serde(default)]
+    pub unlocks: Vec<TaskTemplateConfig>, // 完成后在同一地点解锁的后续任务
+}
+
+impl TaskTemplateConfig {
+    /// 将模板（含整条后续任务链）中的 {name} 占位符替换为地点名称
+    pub fn with_location_name(&self, name: &str) -> Self {
+        Self {
+            name_template: self.name_template.replace("{name}", name),
+            prerequisite: self.prerequisite.as_ref().map(|p| p.replace("{name}", name)),
+            unlocks: self.unlocks.iter().map(|t| t.with_location_name(name)).collect(),
+            ..self.clone()
+        }
+    }
 }
 
 /// 妖魔配置
@@ -103,6 +169,8 @@ pub struct TaskTemplateConfig {
 pub struct MonstersConfig {
     pub monster_templates: Vec<MonsterTemplate>,
     pub spawn_rules: SpawnRules,
+    #[serde(default)]
+    pub nests: Vec<MonsterNestTemplate>,
 }
 
 /// 妖魔模板
@@ -114,6 +182,66 @@ pub struct MonsterTemplate {
     pub growth_rate: f64, // 成长速率（每回合的成长概率）
     pub position: Option<PositionConfig>, // 初始位置（如果有）
     pub task_templates: Vec<TaskTemplateConfig>,
+    #[serde(default)]
+    pub drops: Vec<MonsterDropConfig>, // 讨伐成功后的掉落表
+}
+
+/// 妖魔掉落物品
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(remote = "Self", tag = "type")]
+pub enum DropItemConfig {
+    Herb { name: String },      // 草药（品质随妖魔等级提升）
+    Pill { pill_type: String }, // 丹药（品阶随妖魔等级提升）
+    Material { name: String },  // 炼器材料
+}
+
+/// 二进制格式下的掉落物品表示（bincode 无法解析内部标签枚举，改用外部标签）
+#[derive(Deserialize, Serialize)]
+#[serde(remote = "DropItemConfig")]
+enum CompactDropItemConfig {
+    Herb { name: String },
+    Pill { pill_type: String },
+    Material { name: String },
+}
+
+impl Serialize for DropItemConfig {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            DropItemConfig::serialize(self, serializer)
+        } else {
+            CompactDropItemConfig::serialize(self, serializer)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for DropItemConfig {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            DropItemConfig::deserialize(deserializer)
+        } else {
+            CompactDropItemConfig::deserialize(deserializer)
+        }
+    }
+}
+
+/// 妖魔掉落表条目
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MonsterDropConfig {
+    pub item: DropItemConfig,
+    pub chance: f64, // 基础掉落概率（妖魔等级越高、成魔后越容易掉落）
+}
+
+/// 妖魔巢穴模板
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MonsterNestTemplate {
+    pub name: String,
+    pub level: u32,           // 巢穴等级（讨伐难度，孵化妖魔的等级随之提高）
+    pub spawn_interval: u32,  // 每隔多少回合孵化一只妖魔
+    pub max_brood: u32,       // 同时存活的孵化妖魔上限
+    pub aura_radius: u32,     // 妖气笼罩范围（曼哈顿距离）
+    pub aura_level_bonus: u32, // 范围内妖魔的等级加成
+    pub position: PositionConfig,
+    pub task_templates: Vec<TaskTemplateConfig>,
 }
 
 /// 妖魔生成规则
@@ -124,6 +252,79 @@ pub struct SpawnRules {
     pub random_names: Vec<String>, // 随机妖魔名称池
 }
 
+/// 配置中允许的任务类型
+const TASK_TYPES: [&str; 5] = ["Gathering", "Combat", "Exploration", "Auxiliary", "Investment"];
+
+/// 配置中允许的资质类型
+const TALENT_TYPES: [&str; 9] = [
+    "Fire", "Water", "Wood", "Metal", "Earth", "Sword", "Alchemy", "Formation", "Medical",
+];
+
+impl TaskTemplateConfig {
+    /// 校验任务模板，错误信息以所属元素为前缀
+    fn collect_errors(&self, owner: &str, errors: &mut Vec<String>) {
+        if self.name_template.trim().is_empty() {
+            errors.push(format!("{}: 任务名称模板不能为空", owner));
+        }
+        if !TASK_TYPES.contains(&self.task_type.as_str()) {
+            errors.push(format!("{}: 未知任务类型 '{}'", owner, self.task_type));
+        }
+        if self.task_type == "Gathering" && self.resource_type.is_none() {
+            errors.push(format!("{}: 采集任务 '{}' 缺少 resource_type", owner, self.name_template));
+        }
+        if self.task_type == "Investment" && self.capital.is_none() {
+            errors.push(format!("{}: 投资任务 '{}' 缺少 capital", owner, self.name_template));
+        }
+        if let Some(ref skill) = self.skill_required {
+            if !TALENT_TYPES.contains(&skill.as_str()) {
+                errors.push(format!("{}: 未知技能要求 '{}'", owner, skill));
+            }
+        }
+    }
+}
+
+/// 校验地图元素的名称、位置与尺寸
+fn collect_element_errors(
+    owner: &str,
+    position: &PositionConfig,
+    size: &Option<SizeConfig>,
+    errors: &mut Vec<String>,
+) {
+    if position.x < 0 || position.y < 0 {
+        errors.push(format!("{}: 位置 ({}, {}) 不能为负", owner, position.x, position.y));
+    }
+    if let Some(size) = size {
+        if size.width == 0 || size.height == 0 {
+            errors.push(format!("{}: 尺寸必须大于0", owner));
+        }
+    }
+}
+
+/// 校验同类元素名称非空且不重复
+fn collect_name_errors<'a>(
+    kind: &str,
+    names: impl Iterator<Item = &'a str>,
+    errors: &mut Vec<String>,
+) {
+    let mut seen = std::collections::HashSet::new();
+    for name in names {
+        if name.trim().is_empty() {
+            errors.push(format!("{}名称不能为空", kind));
+        } else if !seen.insert(name) {
+            errors.push(format!("{}名称重复: '{}'", kind, name));
+        }
+    }
+}
+
+/// 汇总校验结果
+fn into_validation_result(errors: Vec<String>) -> Result<(), Vec<String>> {
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
 impl MapElementsConfig {
     /// 从文件加载配置
     pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
@@ -139,9 +340,61 @@ impl MapElementsConfig {
         Ok(())
     }
 
+    /// 校验配置，返回所有错误
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+
+        collect_name_errors("村庄", self.villages.iter().map(|v| v.name.as_str()), &mut errors);
+        for v in &self.villages {
+            let owner = format!("村庄'{}'", v.name);
+            collect_element_errors(&owner, &v.position, &v.size, &mut errors);
+            v.task_templates.iter().for_each(|t| t.collect_errors(&owner, &mut errors));
+        }
+
+        collect_name_errors("势力", self.factions.iter().map(|f| f.name.as_str()), &mut errors);
+        for f in &self.factions {
+            let owner = format!("势力'{}'", f.name);
+            collect_element_errors(&owner, &f.position, &f.size, &mut errors);
+            f.friendly_task_templates.iter()
+                .chain(f.hostile_task_templates.iter())
+                .for_each(|t| t.collect_errors(&owner, &mut errors));
+        }
+
+        collect_name_errors("险地", self.dangerous_locations.iter().map(|d| d.name.as_str()), &mut errors);
+        for d in &self.dangerous_locations {
+            let owner = format!("险地'{}'", d.name);
+            collect_element_errors(&owner, &d.position, &d.size, &mut errors);
+            d.task_templates.iter().for_each(|t| t.collect_errors(&owner, &mut errors));
+        }
+
+        collect_name_errors("秘境", self.secret_realms.iter().map(|r| r.name.as_str()), &mut errors);
+        for r in &self.secret_realms {
+            let owner = format!("秘境'{}'", r.name);
+            collect_element_errors(&owner, &r.position, &r.size, &mut errors);
+            if !TALENT_TYPES.contains(&r.realm_type.as_str()) {
+                errors.push(format!("{}: 未知秘境类型 '{}'", owner, r.realm_type));
+            }
+            r.task_templates.iter().for_each(|t| t.collect_errors(&owner, &mut errors));
+        }
+
+        let opportunities = &self.opportunities;
+        if !(0.0..=1.0).contains(&opportunities.spawn_chance) {
+            errors.push(format!("机缘点: 生成概率 {} 应在 0~1 之间", opportunities.spawn_chance));
+        }
+        if opportunities.duration == 0 {
+            errors.push("机缘点: 存在回合数必须大于0".to_string());
+        }
+        if opportunities.rewards.iter().map(|r| r.weight).sum::<u32>() == 0 {
+            errors.push("机缘点: 奇遇概率表的总权重必须大于0".to_string());
+        }
+
+        into_validation_result(errors)
+    }
+
     /// 创建默认配置
     pub fn default_config() -> Self {
         Self {
+            opportunities: OpportunityConfig::default(),
             villages: vec![
                 VillageTemplate {
                     name: "清风镇".to_string(),
@@ -161,6 +414,11 @@ impl MapElementsConfig {
                             difficulty: Some(1),
                             danger_level: None,
                             skill_required: None,
+                            capital: None,
+                            return_rate: None,
+                            maturity_turns: None,
+                            prerequisite: None,
+                            unlocks: vec![],
                         },
                         TaskTemplateConfig {
                             name_template: "在{name}行医".to_string(),
@@ -173,6 +431,11 @@ impl MapElementsConfig {
                             difficulty: None,
                             danger_level: None,
                             skill_required: Some("Medical".to_string()),
+                            capital: None,
+                            return_rate: None,
+                            maturity_turns: None,
+                            prerequisite: None,
+                            unlocks: vec![],
                         },
                     ],
                 },
@@ -194,6 +457,11 @@ impl MapElementsConfig {
                             difficulty: Some(2),
                             danger_level: None,
                             skill_required: None,
+                            capital: None,
+                            return_rate: None,
+                            maturity_turns: None,
+                            prerequisite: None,
+                            unlocks: vec![],
                         },
                     ],
                 },
@@ -217,6 +485,11 @@ impl MapElementsConfig {
                             difficulty: None,
                             danger_level: None,
                             skill_required: None,
+                            capital: None,
+                            return_rate: None,
+                            maturity_turns: None,
+                            prerequisite: None,
+                            unlocks: vec![],
                         },
                     ],
                     hostile_task_templates: vec![
@@ -231,6 +504,11 @@ impl MapElementsConfig {
                             difficulty: Some(3),
                             danger_level: None,
                             skill_required: None,
+                            capital: None,
+                            return_rate: None,
+                            maturity_turns: None,
+                            prerequisite: None,
+                            unlocks: vec![],
                         },
                     ],
                 },
@@ -253,6 +531,11 @@ impl MapElementsConfig {
                             difficulty: None,
                             danger_level: Some(20),
                             skill_required: None,
+                            capital: None,
+                            return_rate: None,
+                            maturity_turns: None,
+                            prerequisite: None,
+                            unlocks: vec![],
                         },
                     ],
                 },
@@ -276,6 +559,11 @@ impl MapElementsConfig {
                             difficulty: None,
                             danger_level: Some(30),
                             skill_required: None,
+                            capital: None,
+                            return_rate: None,
+                            maturity_turns: None,
+                            prerequisite: None,
+                            unlocks: vec![],
                         },
                     ],
                 },
@@ -299,6 +587,60 @@ impl MonstersConfig {
         Ok(())
     }
 
+    /// 校验配置，返回所有错误
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+
+        collect_name_errors("妖魔", self.monster_templates.iter().map(|m| m.name.as_str()), &mut errors);
+        for m in &self.monster_templates {
+            let owner = format!("妖魔'{}'", m.name);
+            if !(0.0..=1.0).contains(&m.growth_rate) {
+                errors.push(format!("{}: 成长速率 {} 必须在0到1之间", owner, m.growth_rate));
+            }
+            if let Some(ref position) = m.position {
+                collect_element_errors(&owner, position, &None, &mut errors);
+            }
+            m.task_templates.iter().for_each(|t| t.collect_errors(&owner, &mut errors));
+            for drop in &m.drops {
+                if !(0.0..=1.0).contains(&drop.chance) {
+                    errors.push(format!("{}: 掉落概率 {} 必须在0到1之间", owner, drop.chance));
+                }
+                match &drop.item {
+                    DropItemConfig::Pill { pill_type } if crate::pill::PillType::from_str(pill_type).is_none() => {
+                        errors.push(format!("{}: 未知的掉落丹药类型 '{}'", owner, pill_type));
+                    }
+                    DropItemConfig::Herb { name } | DropItemConfig::Material { name } if name.trim().is_empty() => {
+                        errors.push(format!("{}: 掉落物品名称不能为空", owner));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        collect_name_errors("妖魔巢穴", self.nests.iter().map(|n| n.name.as_str()), &mut errors);
+        for n in &self.nests {
+            let owner = format!("妖魔巢穴'{}'", n.name);
+            if n.spawn_interval == 0 {
+                errors.push(format!("{}: 孵化间隔必须大于0", owner));
+            }
+            collect_element_errors(&owner, &n.position, &None, &mut errors);
+            n.task_templates.iter().for_each(|t| t.collect_errors(&owner, &mut errors));
+        }
+
+        let rules = &self.spawn_rules;
+        if !(0.0..=1.0).contains(&rules.spawn_chance) {
+            errors.push(format!("生成规则: 生成概率 {} 必须在0到1之间", rules.spawn_chance));
+        }
+        if rules.level_range.0 > rules.level_range.1 {
+            errors.push(format!(
+                "生成规则: 等级范围 ({}, {}) 下限大于上限",
+                rules.level_range.0, rules.level_range.1
+            ));
+        }
+
+        into_validation_result(errors)
+    }
+
     /// 创建默认配置
     pub fn default_config() -> Self {
         Self {
@@ -309,6 +651,16 @@ impl MonstersConfig {
                     is_demon: false,
                     growth_rate: 0.1,
                     position: Some(PositionConfig { x: 8, y: 12 }),
+                    drops: vec![
+                        MonsterDropConfig {
+                            item: DropItemConfig::Material { name: "虎骨".to_string() },
+                            chance: 0.3,
+                        },
+                        MonsterDropConfig {
+                            item: DropItemConfig::Herb { name: "噬魂草".to_string() },
+                            chance: 0.2,
+                        },
+                    ],
                     task_templates: vec![
                         TaskTemplateConfig {
                             name_template: "讨伐{name}".to_string(),
@@ -321,6 +673,11 @@ impl MonstersConfig {
                             difficulty: Some(2),
                             danger_level: None,
                             skill_required: None,
+                            capital: None,
+                            return_rate: None,
+                            maturity_turns: None,
+                            prerequisite: None,
+                            unlocks: vec![],
                         },
                     ],
                 },
@@ -330,6 +687,16 @@ impl MonstersConfig {
                     is_demon: false,
                     growth_rate: 0.15,
                     position: Some(PositionConfig { x: 12, y: 16 }),
+                    drops: vec![
+                        MonsterDropConfig {
+                            item: DropItemConfig::Material { name: "狼皮".to_string() },
+                            chance: 0.3,
+                        },
+                        MonsterDropConfig {
+                            item: DropItemConfig::Pill { pill_type: "QiRecovery".to_string() },
+                            chance: 0.15,
+                        },
+                    ],
                     task_templates: vec![
                         TaskTemplateConfig {
                             name_template: "讨伐{name}".to_string(),
@@ -342,6 +709,11 @@ impl MonstersConfig {
                             difficulty: Some(3),
                             danger_level: None,
                             skill_required: None,
+                            capital: None,
+                            return_rate: None,
+                            maturity_turns: None,
+                            prerequisite: None,
+                            unlocks: vec![],
                         },
                     ],
                 },
@@ -358,6 +730,36 @@ impl MonstersConfig {
                     "魔猿".to_string(),
                 ],
             },
+            nests: vec![
+                MonsterNestTemplate {
+                    name: "万妖窟".to_string(),
+                    level: 10,
+                    spawn_interval: 3,
+                    max_brood: 3,
+                    aura_radius: 3,
+                    aura_level_bonus: 2,
+                    position: PositionConfig { x: 16, y: 15 },
+                    task_templates: vec![
+                        TaskTemplateConfig {
+                            name_template: "荡平{name}".to_string(),
+                            task_type: "Combat".to_string(),
+                            progress_reward: 60,
+                            resource_reward: 200,
+                            reputation_reward: 120,
+                            dao_heart_impact: 8,
+                            resource_type: None,
+                            difficulty: Some(12),
+                            danger_level: None,
+                            skill_required: None,
+                            capital: None,
+                            return_rate: None,
+                            maturity_turns: None,
+                            prerequisite: None,
+                            unlocks: vec![],
+                        },
+                    ],
+                },
+            ],
         }
     }
 }
@@ -377,9 +779,15 @@ pub enum ModifierTargetConfig {
     TaskSuitability,
     TaskDifficulty,
     Income,
+    StorageCapacity,
     EnergyConsumption,
     ConstitutionConsumption,
+    EnergyRecovery,
+    ConstitutionRecovery,
     CultivationSpeed,
+    AlchemySuccessRate,
+    Lifespan,
+    MovementRange,
 }
 
 impl ModifierTargetConfig {
@@ -394,9 +802,15 @@ impl ModifierTargetConfig {
             Self::TaskSuitability => ModifierTarget::TaskSuitability,
             Self::TaskDifficulty => ModifierTarget::TaskDifficulty,
             Self::Income => ModifierTarget::Income,
+            Self::StorageCapacity => ModifierTarget::StorageCapacity,
             Self::EnergyConsumption => ModifierTarget::EnergyConsumption,
             Self::ConstitutionConsumption => ModifierTarget::ConstitutionConsumption,
+            Self::EnergyRecovery => ModifierTarget::EnergyRecovery,
+            Self::ConstitutionRecovery => ModifierTarget::ConstitutionRecovery,
             Self::CultivationSpeed => ModifierTarget::CultivationSpeed,
+            Self::AlchemySuccessRate => ModifierTarget::AlchemySuccessRate,
+            Self::Lifespan => ModifierTarget::Lifespan,
+            Self::MovementRange => ModifierTarget::MovementRange,
         }
     }
 }
@@ -551,6 +965,7 @@ impl ModifierConfig {
             "Heritage" => ModifierSource::Heritage,
             "Environment" => ModifierSource::Environment,
             "Relationship" => ModifierSource::Relationship,
+            "Technique" => ModifierSource::Technique,
             _ => ModifierSource::System,
         }
     }
@@ -582,6 +997,12 @@ pub struct BuildingConfig {
     pub parent_id: Option<String>,
     #[serde(default)]
     pub modifiers: Vec<ModifierConfig>,
+    #[serde(default = "default_build_turns")]
+    pub build_turns: u32, // 建造工期（回合数）
+}
+
+fn default_build_turns() -> u32 {
+    crate::building::DEFAULT_BUILD_TURNS
 }
 
 /// 建筑配置文件
@@ -605,6 +1026,51 @@ impl BuildingsConfig {
         Ok(())
     }
 
+    /// 校验配置，返回所有错误
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+
+        collect_name_errors("建筑ID", self.buildings.iter().map(|b| b.id.as_str()), &mut errors);
+
+        let roots: Vec<&str> = self.buildings.iter()
+            .filter(|b| b.parent_id.is_none())
+            .map(|b| b.id.as_str())
+            .collect();
+        if roots.len() != 1 {
+            errors.push(format!("建筑配置必须恰好有一个根节点，当前为 {} 个", roots.len()));
+        }
+
+        for b in &self.buildings {
+            if let Some(ref parent_id) = b.parent_id {
+                if !self.buildings.iter().any(|p| &p.id == parent_id) {
+                    errors.push(format!("建筑'{}': 父建筑 '{}' 不存在", b.id, parent_id));
+                }
+            }
+        }
+
+        // 从根节点出发应能到达所有建筑（排除父子成环）
+        if roots.len() == 1 {
+            let mut reachable = std::collections::HashSet::new();
+            let mut frontier = vec![roots[0]];
+            while let Some(id) = frontier.pop() {
+                if reachable.insert(id) {
+                    frontier.extend(self.buildings.iter()
+                        .filter(|b| b.parent_id.as_deref() == Some(id))
+                        .map(|b| b.id.as_str()));
+                }
+            }
+            for b in &self.buildings {
+                let parent_exists = b.parent_id.as_ref()
+                    .map_or(true, |p| self.buildings.iter().any(|o| &o.id == p));
+                if parent_exists && !reachable.contains(b.id.as_str()) {
+                    errors.push(format!("建筑'{}': 无法从根节点到达（父子关系成环）", b.id));
+                }
+            }
+        }
+
+        into_validation_result(errors)
+    }
+
     /// 加载建筑配置（自动创建默认配置）
     pub fn load() -> Self {
         match Self::load_from_file("config/buildings.json") {
@@ -633,6 +1099,7 @@ impl BuildingsConfig {
                     name: "宗门大殿".to_string(),
                     description: "宗门的核心建筑，象征着宗门的威严".to_string(),
                     base_cost: 100,
+                    build_turns: 1,
                     parent_id: None,
                     modifiers: vec![
                         ModifierConfig {
@@ -646,12 +1113,33 @@ impl BuildingsConfig {
                         },
                     ],
                 },
+                // 第一层：储物库
+                BuildingConfig {
+                    id: "storehouse".to_string(),
+                    name: "储物库".to_string(),
+                    description: "修建库房储存灵石，提升宗门灵石上限".to_string(),
+                    base_cost: 150,
+                    build_turns: 2,
+                    parent_id: Some("main_hall".to_string()),
+                    modifiers: vec![
+                        ModifierConfig {
+                            name: "库房扩建".to_string(),
+                            target: ModifierTargetConfig::StorageCapacity,
+                            application: ModifierApplicationConfig::Additive { value: 3000.0 },
+                            source: "System".to_string(),
+                            condition: ModifierConditionConfig::Always,
+                            priority: 0,
+                            duration: None,
+                        },
+                    ],
+                },
                 // 第一层：藏书楼
                 BuildingConfig {
                     id: "library".to_string(),
                     name: "藏书楼".to_string(),
                     description: "收藏功法典籍，提升弟子修炼速度".to_string(),
                     base_cost: 150,
+                    build_turns: 2,
                     parent_id: Some("main_hall".to_string()),
                     modifiers: vec![
                         ModifierConfig {
@@ -671,6 +1159,7 @@ impl BuildingsConfig {
                     name: "炼丹房".to_string(),
                     description: "炼制丹药，帮助弟子恢复精力".to_string(),
                     base_cost: 150,
+                    build_turns: 2,
                     parent_id: Some("main_hall".to_string()),
                     modifiers: vec![
                         ModifierConfig {
@@ -682,6 +1171,15 @@ impl BuildingsConfig {
                             priority: 0,
                             duration: None,
                         },
+                        ModifierConfig {
+                            name: "丹火纯青".to_string(),
+                            target: ModifierTargetConfig::AlchemySuccessRate,
+                            application: ModifierApplicationConfig::Additive { value: 0.1 },
+                            source: "System".to_string(),
+                            condition: ModifierConditionConfig::Always,
+                            priority: 0,
+                            duration: None,
+                        },
                     ],
                 },
                 // 第一层：演武场
@@ -690,6 +1188,7 @@ impl BuildingsConfig {
                     name: "演武场".to_string(),
                     description: "弟子切磋武艺之处，强健体魄".to_string(),
                     base_cost: 150,
+                    build_turns: 2,
                     parent_id: Some("main_hall".to_string()),
                     modifiers: vec![
                         ModifierConfig {
@@ -709,6 +1208,7 @@ impl BuildingsConfig {
                     name: "天机阁".to_string(),
                     description: "推演天机，内门弟子任务奖励提升".to_string(),
                     base_cost: 200,
+                    build_turns: 3,
                     parent_id: Some("library".to_string()),
                     modifiers: vec![
                         ModifierConfig {
@@ -728,6 +1228,7 @@ impl BuildingsConfig {
                     name: "灵药园".to_string(),
                     description: "种植灵药，增加宗门收入".to_string(),
                     base_cost: 200,
+                    build_turns: 3,
                     parent_id: Some("alchemy_room".to_string()),
                     modifiers: vec![
                         ModifierConfig {
@@ -747,6 +1248,7 @@ impl BuildingsConfig {
                     name: "炼器坊".to_string(),
                     description: "炼制法宝，提升战斗能力".to_string(),
                     base_cost: 200,
+                    build_turns: 3,
                     parent_id: Some("training_ground".to_string()),
                     modifiers: vec![
                         ModifierConfig {
@@ -766,6 +1268,7 @@ impl BuildingsConfig {
                     name: "传承殿".to_string(),
                     description: "存放宗门至高传承，亲传弟子修炼速度大幅提升".to_string(),
                     base_cost: 300,
+                    build_turns: 4,
                     parent_id: Some("heavenly_pavilion".to_string()),
                     modifiers: vec![
                         ModifierConfig {
@@ -783,8 +1286,9 @@ impl BuildingsConfig {
                 BuildingConfig {
                     id: "spirit_array".to_string(),
                     name: "聚灵阵".to_string(),
-                    description: "汇聚天地灵气，筑基期以上弟子修炼速度提升".to_string(),
+                    description: "汇聚天地灵气，筑基期以上弟子修炼速度提升，全宗弟子精力恢复加快".to_string(),
                     base_cost: 300,
+                    build_turns: 4,
                     parent_id: Some("spirit_garden".to_string()),
                     modifiers: vec![
                         ModifierConfig {
@@ -796,6 +1300,15 @@ impl BuildingsConfig {
                             priority: 0,
                             duration: None,
                         },
+                        ModifierConfig {
+                            name: "灵气回春".to_string(),
+                            target: ModifierTargetConfig::EnergyRecovery,
+                            application: ModifierApplicationConfig::Multiplicative { value: 0.5 },
+                            source: "System".to_string(),
+                            condition: ModifierConditionConfig::Always,
+                            priority: 0,
+                            duration: None,
+                        },
                     ],
                 },
                 // 第三层：护宗大阵
@@ -804,6 +1317,7 @@ impl BuildingsConfig {
                     name: "护宗大阵".to_string(),
                     description: "守护宗门，提升弟子道心".to_string(),
                     base_cost: 300,
+                    build_turns: 4,
                     parent_id: Some("weapon_forge".to_string()),
                     modifiers: vec![
                         ModifierConfig {
@@ -822,6 +1336,136 @@ impl BuildingsConfig {
     }
 }
 
+/// 单个境界修炼路径的任务总数上限
+pub const MAX_CULTIVATION_PATH_TASKS: u32 = 20;
+
+/// 单个境界的修炼路径任务配比
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PathQuotaConfig {
+    pub level: CultivationLevel,
+    #[serde(default)]
+    pub combat: u32,
+    #[serde(default)]
+    pub exploration: u32,
+    #[serde(default)]
+    pub gathering: u32,
+    #[serde(default)]
+    pub auxiliary: u32,
+}
+
+impl PathQuotaConfig {
+    fn new(level: CultivationLevel, combat: u32, exploration: u32, gathering: u32, auxiliary: u32) -> Self {
+        Self { level, combat, exploration, gathering, auxiliary }
+    }
+
+    /// 路径任务总数
+    pub fn total(&self) -> u32 {
+        self.combat + self.exploration + self.gathering + self.auxiliary
+    }
+
+    /// 转换为修炼路径要求（省略数量为0的任务类型）
+    pub fn requirements(&self) -> std::collections::HashMap<String, u32> {
+        [
+            ("Combat", self.combat),
+            ("Exploration", self.exploration),
+            ("Gathering", self.gathering),
+            ("Auxiliary", self.auxiliary),
+        ]
+        .into_iter()
+        .filter(|(_, count)| *count > 0)
+        .map(|(task_type, count)| (task_type.to_string(), count))
+        .collect()
+    }
+}
+
+/// 修炼路径配置文件
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CultivationPathsConfig {
+    pub paths: Vec<PathQuotaConfig>,
+}
+
+impl CultivationPathsConfig {
+    /// 从文件加载配置
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(path)?;
+        let config: Self = serde_json::from_str(&content)?;
+        Ok(config)
+    }
+
+    /// 保存配置到文件
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn std::error::Error>> {
+        let content = serde_json::to_string_pretty(self)?;
+        fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// 校验配置：每个境界恰好配置一次，任务总数在 1..=MAX_CULTIVATION_PATH_TASKS 之间
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        for level in CultivationLevel::ALL {
+            let count = self.paths.iter().filter(|p| p.level == level).count();
+            if count != 1 {
+                errors.push(format!("修炼路径: 境界 {} 应恰好配置一次，当前为 {} 次", level, count));
+            }
+        }
+        for quota in &self.paths {
+            let total = quota.total();
+            if total == 0 || total > MAX_CULTIVATION_PATH_TASKS {
+                errors.push(format!(
+                    "修炼路径: 境界 {} 的任务总数为 {}，应在 1~{} 之间",
+                    quota.level, total, MAX_CULTIVATION_PATH_TASKS
+                ));
+            }
+        }
+        into_validation_result(errors)
+    }
+
+    /// 加载修炼路径配置（自动创建默认配置，校验失败时使用默认配置）
+    pub fn load() -> Self {
+        match Self::load_from_file("config/cultivation_paths.json") {
+            Ok(config) => match config.validate() {
+                Ok(()) => config,
+                Err(errors) => {
+                    println!("修炼路径配置无效，使用默认配置: {}", errors.join("; "));
+                    Self::default_config()
+                }
+            },
+            Err(_) => {
+                println!("未找到修炼路径配置文件，使用默认配置");
+                let config = Self::default_config();
+                // 尝试保存默认配置
+                if let Err(e) = std::fs::create_dir_all("config") {
+                    println!("创建config目录失败: {}", e);
+                } else if let Err(e) = config.save_to_file("config/cultivation_paths.json") {
+                    println!("保存默认修炼路径配置失败: {}", e);
+                }
+                config
+            }
+        }
+    }
+
+    /// 某境界的任务配比
+    pub fn quota_for(&self, level: CultivationLevel) -> Option<&PathQuotaConfig> {
+        self.paths.iter().find(|p| p.level == level)
+    }
+
+    /// 创建默认修炼路径配置（每个境界共12个任务）
+    pub fn default_config() -> Self {
+        use CultivationLevel::*;
+        Self {
+            paths: vec![
+                PathQuotaConfig::new(QiRefining, 2, 3, 4, 3),     // 练气：多采集
+                PathQuotaConfig::new(Foundation, 4, 3, 2, 3),     // 筑基：多战斗
+                PathQuotaConfig::new(GoldenCore, 5, 4, 1, 2),     // 结丹：战斗+探索
+                PathQuotaConfig::new(NascentSoul, 6, 4, 0, 2),    // 凝婴：更多战斗
+                PathQuotaConfig::new(SpiritSevering, 7, 4, 0, 1), // 化神：主要战斗
+                PathQuotaConfig::new(VoidRefinement, 8, 3, 0, 1), // 练虚：几乎全战斗
+                PathQuotaConfig::new(Ascension, 10, 2, 0, 0),     // 飞升：纯战斗
+            ],
+        }
+    }
+}
+
 /// 配置管理器
 #[derive(Debug)]
 pub struct ConfigManager {
@@ -830,7 +1474,7 @@ pub struct ConfigManager {
 }
 
 impl ConfigManager {
-    /// 加载所有配置
+    /// 加载所有配置（缺失的文件使用默认配置），校验失败时返回所有错误
     pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
         let map_elements = match MapElementsConfig::load_from_file("config/map_elements.json") {
             Ok(config) => config,
@@ -862,10 +1506,14 @@ impl ConfigManager {
             }
         };
 
-        Ok(Self {
+        let config = Self {
             map_elements,
             monsters,
-        })
+        };
+        config
+            .validate()
+            .map_err(|errors| format!("配置校验失败：{}", errors.join("；")))?;
+        Ok(config)
     }
 
     /// 创建默认配置
@@ -875,4 +1523,54 @@ impl ConfigManager {
             monsters: MonstersConfig::default_config(),
         }
     }
+
+    /// 校验地图元素与妖魔配置，返回所有错误
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        if let Err(e) = self.map_elements.validate() {
+            errors.extend(e);
+        }
+        if let Err(e) = self.monsters.validate() {
+            errors.extend(e);
+        }
+        into_validation_result(errors)
+    }
+
+    /// 从磁盘重新读取全部配置（不回退默认配置），读取或校验失败时返回所有错误
+    pub fn reload_from_disk() -> Result<(Self, BuildingsConfig), Vec<String>> {
+        let mut errors = Vec::new();
+
+        let map_elements = MapElementsConfig::load_from_file("config/map_elements.json")
+            .map_err(|e| errors.push(format!("config/map_elements.json: {}", e)))
+            .ok();
+        let monsters = MonstersConfig::load_from_file("config/monsters.json")
+            .map_err(|e| errors.push(format!("config/monsters.json: {}", e)))
+            .ok();
+        let buildings = BuildingsConfig::load_from_file("config/buildings.json")
+            .map_err(|e| errors.push(format!("config/buildings.json: {}", e)))
+            .ok();
+
+        if let Some(ref config) = map_elements {
+            if let Err(e) = config.validate() {
+                errors.extend(e);
+            }
+        }
+        if let Some(ref config) = monsters {
+            if let Err(e) = config.validate() {
+                errors.extend(e);
+            }
+        }
+        if let Some(ref config) = buildings {
+            if let Err(e) = config.validate() {
+                errors.extend(e);
+            }
+        }
+
+        match (map_elements, monsters, buildings) {
+            (Some(map_elements), Some(monsters), Some(buildings)) if errors.is_empty() => {
+                Ok((Self { map_elements, monsters }, buildings))
+            }
+            _ => Err(errors),
+        }
+    }
 }
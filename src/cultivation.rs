@@ -1,5 +1,5 @@
 /// 小境界
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize)]
 pub enum SubLevel {
     Early,          // 初期
     Middle,         // 中期
@@ -40,6 +40,17 @@ pub enum CultivationLevel {
 }
 
 impl CultivationLevel {
+    /// 按境界由低到高排列的全部修为等级
+    pub const ALL: [CultivationLevel; 7] = [
+        CultivationLevel::QiRefining,
+        CultivationLevel::Foundation,
+        CultivationLevel::GoldenCore,
+        CultivationLevel::NascentSoul,
+        CultivationLevel::SpiritSevering,
+        CultivationLevel::VoidRefinement,
+        CultivationLevel::Ascension,
+    ];
+
     /// 获取基础寿元
     pub fn base_lifespan(&self) -> u32 {
         match self {
@@ -53,6 +64,14 @@ impl CultivationLevel {
         }
     }
 
+    /// 该修为的理论寿元上限（丹药、道心等加成都不能超过）
+    pub fn max_lifespan(&self) -> u32 {
+        match self {
+            CultivationLevel::Ascension => u32::MAX,
+            _ => self.base_lifespan() * 3 / 2,
+        }
+    }
+
     /// 是否需要渡劫
     pub fn requires_tribulation(&self) -> bool {
         matches!(
@@ -85,6 +104,20 @@ impl CultivationLevel {
         *self as u32
     }
 
+    /// 获取每回合基础恢复量 (精力, 体魄)
+    /// 练气=(5,2), 筑基=(6,3), 结丹=(8,4), 凝婴=(10,5), 化神=(12,6), 练虚=(15,8), 飞升=(20,10)
+    pub fn recovery_rate(&self) -> (u32, u32) {
+        match self {
+            CultivationLevel::QiRefining => (5, 2),
+            CultivationLevel::Foundation => (6, 3),
+            CultivationLevel::GoldenCore => (8, 4),
+            CultivationLevel::NascentSoul => (10, 5),
+            CultivationLevel::SpiritSevering => (12, 6),
+            CultivationLevel::VoidRefinement => (15, 8),
+            CultivationLevel::Ascension => (20, 10),
+        }
+    }
+
     /// 获取每回合移动范围（格子数）
     /// 练气=2, 筑基=3, 结丹=4, 凝婴=5, 化神=6, 练虚=8, 飞升=10
     pub fn movement_range(&self) -> u32 {
@@ -116,7 +149,7 @@ impl std::fmt::Display for CultivationLevel {
 }
 
 /// 修炼路径 - 需要完成的任务类型和数量
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct CultivationPath {
     pub required: std::collections::HashMap<String, u32>,  // 需要完成的任务类型和数量
     pub completed: std::collections::HashMap<String, u32>, // 每种类型已完成的数量
@@ -1,8 +1,15 @@
 use crate::cultivation::{CultivationLevel, SubLevel, CultivationPath};
 use crate::task::{Task, TaskType};
-use crate::modifier::{ModifierStack, ModifierTarget, Modifier, ModifierSource};
+use crate::modifier::{ConditionalModifier, ModifierCondition, ModifierStack, ModifierTarget, Modifier, ModifierSource, ModifierApplication, ModifierExplanation};
+use crate::technique::Technique;
+use crate::equipment::{Equipment, EquipmentSlot};
+use std::collections::HashMap;
+use crate::pill::{
+    PillGrade, PillOutcome, PillType, ALCHEMY_SURPLUS_TALENT_LEVEL, MAX_ALCHEMY_SUCCESS_RATE, MAX_PILLS_PER_TURN,
+    PILL_TOXICITY_CHANCE,
+};
 use crate::map::Position;
-use crate::relationship::Relationship;
+use crate::relationship::{enmity_modifier, Relationship};
 
 /// 弟子类型
 #[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
@@ -12,6 +19,25 @@ pub enum DiscipleType {
     Personal,   // 亲传
 }
 
+impl DiscipleType {
+    /// 晋升后的弟子类型（亲传已是最高）
+    pub fn next(&self) -> Option<DiscipleType> {
+        match self {
+            DiscipleType::Outer => Some(DiscipleType::Inner),
+            DiscipleType::Inner => Some(DiscipleType::Personal),
+            DiscipleType::Personal => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DiscipleType::Outer => "外门",
+            DiscipleType::Inner => "内门",
+            DiscipleType::Personal => "亲传",
+        }
+    }
+}
+
 /// 资质类型
 #[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 pub enum TalentType {
@@ -30,28 +56,511 @@ pub enum TalentType {
     Medical,        // 医道天赋
 }
 
+impl TalentType {
+    pub const ALL: [TalentType; 13] = [
+        Self::Fire, Self::Water, Self::Wood, Self::Metal, Self::Earth, Self::Thunder, Self::Ice,
+        Self::Wind, Self::Sword, Self::Alchemy, Self::Formation, Self::Beast, Self::Medical,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Fire => "火灵根",
+            Self::Water => "水灵根",
+            Self::Wood => "木灵根",
+            Self::Metal => "金灵根",
+            Self::Earth => "土灵根",
+            Self::Thunder => "雷灵根",
+            Self::Ice => "冰灵根",
+            Self::Wind => "风灵根",
+            Self::Sword => "剑道天赋",
+            Self::Alchemy => "炼丹天赋",
+            Self::Formation => "阵法天赋",
+            Self::Beast => "御兽天赋",
+            Self::Medical => "医道天赋",
+        }
+    }
+}
+
+/// 渡劫失败身死的概率
+pub const TRIBULATION_DEATH_CHANCE: f32 = 0.25;
+/// 渡劫重伤损失的体魄
+const TRIBULATION_INJURY_CONSTITUTION_LOSS: u32 = 60;
+/// 渡劫重伤debuff持续回合数
+const TRIBULATION_INJURY_DURATION: u32 = 3;
+/// 渡劫引动天地异象、波及周边的最低境界（化神）
+pub const TRIBULATION_AFTERMATH_MIN_LEVEL: CultivationLevel = CultivationLevel::SpiritSevering;
+/// 天劫惊扰低修为弟子道心的范围（曼哈顿距离）
+pub const TRIBULATION_SHAKE_RADIUS: i32 = 3;
+/// 高阶渡劫成功后全宗获得的天劫气运持续回合数
+pub const TRIBULATION_BLESSING_DURATION: u32 = 3;
+/// 天劫气运的修炼速度加成
+const TRIBULATION_BLESSING_BONUS: f32 = 0.2;
+
+/// 天劫气运：高阶渡劫成功后泽被全宗的临时修炼加成
+pub fn tribulation_blessing() -> Modifier {
+    Modifier::new_temporary(
+        "天劫气运",
+        ModifierTarget::CultivationSpeed,
+        ModifierApplication::Multiplicative(TRIBULATION_BLESSING_BONUS),
+        ModifierSource::Environment,
+        TRIBULATION_BLESSING_DURATION,
+    )
+}
+
+/// 渡劫护法人数上限
+pub const MAX_TRIBULATION_GUARDIANS: usize = 3;
+/// 每名护法消耗的精力
+pub const GUARDIAN_ENERGY_COST: u32 = 30;
+/// 每名护法按修为（境界序数+1）提供的渡劫成功率加成
+const GUARDIAN_BONUS_PER_LEVEL: f32 = 0.05;
+/// 护法加成上限
+const GUARDIAN_MAX_BONUS: f32 = 0.5;
+/// 渡劫失败时每名护法被天劫波及的概率
+pub const GUARDIAN_BACKLASH_CHANCE: f64 = 0.5;
+
+/// 护法加成：按护法修为累加的渡劫成功率modifier（无护法返回None）
+pub fn tribulation_guardian_modifier(guardians: &[&Disciple]) -> Option<Modifier> {
+    if guardians.is_empty() {
+        return None;
+    }
+    let bonus: f32 = guardians
+        .iter()
+        .map(|g| (g.cultivation.current_level as u32 + 1) as f32 * GUARDIAN_BONUS_PER_LEVEL)
+        .sum();
+    Some(Modifier::new(
+        "同门护法",
+        ModifierTarget::TribulationSuccessRate,
+        ModifierApplication::Multiplicative(bonus.min(GUARDIAN_MAX_BONUS)),
+        ModifierSource::Relationship,
+    ))
+}
+
+/// 有效道心低于该值时，恶战或闭关可能走火入魔
+pub const HEART_DEMON_DAO_HEART_THRESHOLD: f32 = 30.0;
+/// 有效道心为零时走火入魔的概率（道心越接近阈值概率越低）
+const HEART_DEMON_MAX_CHANCE: f32 = 0.3;
+/// 走火入魔倒退的小境界进度
+const HEART_DEMON_PROGRESS_LOSS: u32 = 50;
+/// 心魔debuff持续回合数
+const HEART_DEMON_DURATION: u32 = 5;
+/// 有效道心低于该值时走火入魔会暴走伤及同门
+pub const HEART_DEMON_RAMPAGE_DAO_HEART: f32 = 10.0;
+/// 进入大圆满时遭遇瓶颈的概率
+pub const BOTTLENECK_CHANCE: f64 = 0.3;
+/// 瓶颈未破时渡劫成功率的惩罚系数
+pub const BOTTLENECK_TRIBULATION_PENALTY: f32 = 0.5;
+/// 道心超过该值的弟子突破时额外增寿
+const DAO_HEART_LIFESPAN_THRESHOLD: u32 = 60;
+/// 突破增寿比例 = (道心 - 阈值) / 该值（道心满值时增寿两成）
+const DAO_HEART_LIFESPAN_DIVISOR: u64 = 200;
+
+/// 伤势等级
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize)]
+pub enum InjurySeverity {
+    Minor,    // 轻伤
+    Serious,  // 重伤
+    Critical, // 垂危
+}
+
+impl InjurySeverity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            InjurySeverity::Minor => "轻伤",
+            InjurySeverity::Serious => "重伤",
+            InjurySeverity::Critical => "垂危",
+        }
+    }
+
+    /// 伤势对任务奖励的削弱比例
+    pub fn task_reward_penalty(&self) -> f32 {
+        match self {
+            InjurySeverity::Minor => 0.1,
+            InjurySeverity::Serious => 0.25,
+            InjurySeverity::Critical => 0.5,
+        }
+    }
+
+    /// 伤势削减的每回合移动力比例
+    pub fn movement_penalty(&self) -> f32 {
+        match self {
+            InjurySeverity::Minor => 0.0,
+            InjurySeverity::Serious => 0.5,
+            InjurySeverity::Critical => 1.0,
+        }
+    }
+
+    /// 花费资源快速疗伤的费用
+    pub fn heal_cost(&self) -> u32 {
+        match self {
+            InjurySeverity::Minor => 50,
+            InjurySeverity::Serious => 150,
+            InjurySeverity::Critical => 300,
+        }
+    }
+}
+
+/// 伤势（附带一个挂在弟子modifiers上的Debuff，痊愈时移除）
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Injury {
+    pub severity: InjurySeverity,
+    pub modifier_id: String, // 对应Debuff modifier的ID
+}
+
+/// 每回合轻伤自愈的概率
+const MINOR_INJURY_SELF_HEAL_CHANCE: f64 = 0.1;
+
+/// 战斗失败时身负重伤的概率
+pub const COMBAT_INJURY_CHANCE: f64 = 0.6;
+
+/// 探索受伤的最低危险度
+pub const EXPLORATION_INJURY_DANGER_THRESHOLD: u32 = 30;
+
+/// 闭关每回合的基础修为
+pub const SECLUSION_BASE_PROGRESS: f32 = 20.0;
+
+/// 闭关每回合消耗的资源
+pub const SECLUSION_COST_PER_TURN: u32 = 15;
+
+/// 单次闭关的最长回合数
+pub const MAX_SECLUSION_TURNS: u32 = 10;
+
+/// 执行任务每回合累积的疲劳
+pub const FATIGUE_PER_WORK_TURN: u32 = 20;
+
+/// 每回合自然消退的疲劳
+const FATIGUE_RECOVERY_PER_TURN: u32 = 10;
+
+/// 服用恢复类丹药消除的疲劳
+const PILL_FATIGUE_RELIEF: u32 = 20;
+
+/// 疲劳上限
+pub const MAX_FATIGUE: u32 = 100;
+
+/// 高疲劳阈值：达到后任务收益下降，自动分配会避开
+pub const HIGH_FATIGUE_THRESHOLD: u32 = 50;
+
+/// 低精力阈值：低于此值任务收益下降
+pub const LOW_ENERGY_THRESHOLD: u32 = 20;
+
+/// 低精力时任务收益的降幅
+const LOW_ENERGY_REWARD_PENALTY: f32 = -0.3;
+
+/// 精力连续归零达到此回合数后开始折损寿元
+pub const EXHAUSTION_LIFESPAN_TURNS: u32 = 3;
+
+/// 根据探索危险度和随机数判定伤势（危险度越高越容易受伤、伤得越重）
+pub fn exploration_injury(danger_level: u32, roll: f32) -> Option<InjurySeverity> {
+    if danger_level < EXPLORATION_INJURY_DANGER_THRESHOLD {
+        return None;
+    }
+    let chance = (danger_level as f32 / 200.0).min(0.5);
+    if roll >= chance {
+        return None;
+    }
+    Some(if danger_level >= 60 {
+        InjurySeverity::Serious
+    } else {
+        InjurySeverity::Minor
+    })
+}
+
+/// 性格特质
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Personality {
+    Diligent,    // 勤奋
+    Lazy,        // 懒散
+    Belligerent, // 好战
+    Cautious,    // 谨慎
+    Greedy,      // 贪婪
+    Arrogant,    // 高傲
+}
+
+impl Personality {
+    pub const ALL: [Personality; 6] = [
+        Personality::Diligent,
+        Personality::Lazy,
+        Personality::Belligerent,
+        Personality::Cautious,
+        Personality::Greedy,
+        Personality::Arrogant,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Personality::Diligent => "勤奋",
+            Personality::Lazy => "懒散",
+            Personality::Belligerent => "好战",
+            Personality::Cautious => "谨慎",
+            Personality::Greedy => "贪婪",
+            Personality::Arrogant => "高傲",
+        }
+    }
+
+    /// 性格常驻的modifier
+    pub fn modifiers(&self) -> Vec<Modifier> {
+        let modifier = |target, application| {
+            Modifier::new(format!("性格：{}", self.name()), target, application, ModifierSource::System)
+        };
+        match self {
+            Personality::Diligent => vec![
+                modifier(ModifierTarget::CultivationSpeed, ModifierApplication::Multiplicative(0.1)),
+            ],
+            Personality::Lazy => vec![
+                modifier(ModifierTarget::CultivationSpeed, ModifierApplication::Multiplicative(-0.1)),
+                modifier(ModifierTarget::EnergyConsumption, ModifierApplication::Multiplicative(-0.1)),
+            ],
+            Personality::Belligerent => vec![],
+            Personality::Cautious => vec![
+                modifier(ModifierTarget::TribulationSuccessRate, ModifierApplication::Multiplicative(0.05)),
+            ],
+            Personality::Greedy => vec![
+                modifier(ModifierTarget::DaoHeart, ModifierApplication::Additive(-5.0)),
+            ],
+            Personality::Arrogant => vec![
+                modifier(ModifierTarget::TaskSuitability, ModifierApplication::Additive(3.0)),
+            ],
+        }
+    }
+
+    /// 执行特定类型任务时额外生效的TaskReward modifier
+    pub fn task_modifier(&self, task_type: &TaskType) -> Option<Modifier> {
+        let bonus = match (self, task_type) {
+            (Personality::Belligerent, TaskType::Combat(_)) => 0.2,
+            (Personality::Greedy, TaskType::Gathering(_)) => 0.15,
+            _ => return None,
+        };
+        Some(Modifier::new(
+            format!("性格：{}", self.name()),
+            ModifierTarget::TaskReward,
+            ModifierApplication::Multiplicative(bonus),
+            ModifierSource::System,
+        ))
+    }
+
+    /// 调整任务对道心的影响（好战的弟子受负面影响更大）
+    pub fn adjust_dao_heart_impact(&self, impact: i32) -> i32 {
+        match self {
+            Personality::Belligerent if impact < 0 => impact * 3 / 2,
+            _ => impact,
+        }
+    }
+}
+
+/// 选择专精所需的最低境界
+pub const SPECIALIZATION_MIN_LEVEL: CultivationLevel = CultivationLevel::Foundation;
+/// 改修其他专精消耗的资源
+pub const RESPECIALIZE_COST: u32 = 500;
+/// 改修其他专精折损的道心
+pub const RESPECIALIZE_DAO_HEART_LOSS: u32 = 20;
+/// 专精对口任务的奖励加成
+const SPECIALIZATION_TASK_BONUS: f32 = 0.25;
+
+/// 专精路线（筑基后选择，选定后难以更改）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Specialization {
+    Sword,     // 剑修
+    Alchemy,   // 丹修
+    Formation, // 阵修
+    Body,      // 体修
+}
+
+impl Specialization {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Sword => "剑修",
+            Self::Alchemy => "丹修",
+            Self::Formation => "阵修",
+            Self::Body => "体修",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "Sword" => Some(Self::Sword),
+            "Alchemy" => Some(Self::Alchemy),
+            "Formation" => Some(Self::Formation),
+            "Body" => Some(Self::Body),
+            _ => None,
+        }
+    }
+
+    /// 专精视同具备的天赋（可承接需要该天赋的辅助任务）
+    pub fn skill(&self) -> Option<TalentType> {
+        match self {
+            Self::Sword => Some(TalentType::Sword),
+            Self::Alchemy => Some(TalentType::Alchemy),
+            Self::Formation => Some(TalentType::Formation),
+            Self::Body => None,
+        }
+    }
+
+    /// 是否为专精对口的任务类型（自动分配优先、奖励加成）
+    pub fn favors(&self, task_type: &TaskType) -> bool {
+        matches!(
+            (self, task_type),
+            (Self::Sword, TaskType::Combat(_))
+                | (Self::Alchemy, TaskType::Gathering(_))
+                | (Self::Formation, TaskType::Auxiliary(_))
+                | (Self::Body, TaskType::Exploration(_))
+        )
+    }
+
+    /// 专精路线的条件modifier集合
+    pub fn conditional_modifiers(&self) -> Vec<ConditionalModifier> {
+        let modifier = |condition, target, application| {
+            ConditionalModifier::new(
+                condition,
+                Modifier::new(format!("专精：{}", self.name()), target, application, ModifierSource::Specialization),
+            )
+        };
+        match self {
+            Self::Sword => vec![
+                modifier(ModifierCondition::Always, ModifierTarget::TaskSuitability, ModifierApplication::Additive(1.0)),
+                modifier(ModifierCondition::HasTalent(TalentType::Sword), ModifierTarget::ConstitutionConsumption, ModifierApplication::Multiplicative(-0.2)),
+            ],
+            Self::Alchemy => vec![
+                modifier(ModifierCondition::Always, ModifierTarget::AlchemySuccessRate, ModifierApplication::Additive(0.15)),
+                modifier(ModifierCondition::HasTalent(TalentType::Alchemy), ModifierTarget::AlchemySuccessRate, ModifierApplication::Additive(0.05)),
+            ],
+            Self::Formation => vec![
+                modifier(ModifierCondition::Always, ModifierTarget::CultivationSpeed, ModifierApplication::Multiplicative(0.1)),
+                modifier(ModifierCondition::HasTalent(TalentType::Formation), ModifierTarget::EnergyConsumption, ModifierApplication::Multiplicative(-0.2)),
+            ],
+            Self::Body => vec![
+                modifier(ModifierCondition::Always, ModifierTarget::ConstitutionRecovery, ModifierApplication::Multiplicative(0.5)),
+                modifier(ModifierCondition::Always, ModifierTarget::ConstitutionConsumption, ModifierApplication::Multiplicative(-0.2)),
+            ],
+        }
+    }
+
+    /// 执行对口任务时额外生效的TaskReward modifier
+    pub fn task_modifier(&self, task_type: &TaskType) -> Option<Modifier> {
+        self.favors(task_type).then(|| {
+            Modifier::new(
+                format!("专精：{}", self.name()),
+                ModifierTarget::TaskReward,
+                ModifierApplication::Multiplicative(SPECIALIZATION_TASK_BONUS),
+                ModifierSource::Specialization,
+            )
+        })
+    }
+}
+
+/// 走火入魔的后果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeartDemon {
+    pub progress_lost: u32, // 倒退的修为进度
+    pub rampage: bool,      // 是否暴走攻击同门
+}
+
+/// 渡劫结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TribulationOutcome {
+    Success,  // 渡劫成功，晋升大境界
+    Injured,  // 渡劫失败，重伤（可养伤后再试）
+    Died,     // 渡劫失败，身死道消
+    NotReady, // 不满足渡劫条件
+}
+
 /// 资质
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Talent {
     pub talent_type: TalentType,
     pub level: u32, // 资质等级 1-10
 }
 
+/// 任务奖励预览
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskRewardPreview {
+    pub progress: u32,         // 修为
+    pub resources: u32,        // 灵石
+    pub contribution: u32,     // 贡献点
+    pub reputation: i32,       // 声望
+    pub dao_heart_change: i32, // 道心变化
+    pub success_rate: f32,     // 预估成功率
+}
+
+/// 资质等级上限
+pub const TALENT_MAX_LEVEL: u32 = 10;
+/// 完成相关任务后灵根成长的基础概率（等级越高越难成长）
+pub const TALENT_GROWTH_CHANCE: f32 = 0.08;
+/// 没有该灵根的弟子长期接触相关任务后觉醒新灵根的概率
+pub const TALENT_AWAKEN_CHANCE: f32 = 0.01;
+
+/// 与任务强相关的灵根（战斗-剑道、采集-木灵根、辅助-阵法、秘境探索-秘境对应灵根）
+pub fn related_talent(task: &Task) -> Option<TalentType> {
+    match &task.task_type {
+        TaskType::Gathering(_) => Some(TalentType::Wood),
+        TaskType::Combat(_) => Some(TalentType::Sword),
+        TaskType::Exploration(exploration) => exploration.realm_type.clone(),
+        TaskType::Auxiliary(_) => Some(TalentType::Formation),
+        TaskType::Investment(_) => None,
+    }
+}
+
 /// 传承
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Heritage {
     pub name: String,
     pub level: CultivationLevel,
     pub tribulation_bonus: f32, // 渡劫成功率加成
+    #[serde(default)]
+    pub talent: Option<TalentType>, // 继承所需灵根（传承者的主修灵根）
+}
+
+impl Heritage {
+    /// 继承所需道心（传承修为越高要求越高）
+    pub fn required_dao_heart(&self) -> u32 {
+        match self.level {
+            CultivationLevel::NascentSoul => 50,
+            CultivationLevel::SpiritSevering => 60,
+            CultivationLevel::VoidRefinement => 70,
+            CultivationLevel::Ascension => 80,
+            _ => 0,
+        }
+    }
+
+    /// 弟子是否满足继承条件
+    pub fn check_inheritor(&self, disciple: &Disciple) -> Result<(), String> {
+        if let Some(talent) = &self.talent {
+            if !disciple.talents.iter().any(|t| &t.talent_type == talent) {
+                return Err(format!("{}不具备{}灵根，无法继承{}", disciple.name, talent.name(), self.name));
+            }
+        }
+        let required = self.required_dao_heart();
+        if disciple.dao_heart < required {
+            return Err(format!("{}道心{}，继承{}需要道心{}", disciple.name, disciple.dao_heart, self.name, required));
+        }
+        Ok(())
+    }
+
+    /// 继承后获得的起始modifier：传承者的修炼心得加快修炼
+    pub fn starting_modifier(&self) -> Modifier {
+        let bonus = match self.level {
+            CultivationLevel::NascentSoul => 0.1,
+            CultivationLevel::SpiritSevering => 0.15,
+            CultivationLevel::VoidRefinement => 0.2,
+            CultivationLevel::Ascension => 0.3,
+            _ => 0.05,
+        };
+        Modifier::new(
+            self.name.clone(),
+            ModifierTarget::CultivationSpeed,
+            ModifierApplication::Multiplicative(bonus),
+            ModifierSource::Heritage,
+        )
+    }
 }
 
 /// 修行进度
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct CultivationProgress {
     pub current_level: CultivationLevel,
     pub sub_level: SubLevel,         // 小境界
     pub progress: u32,                // 当前小境界进度 0-100
     pub cultivation_path: Option<CultivationPath>,  // 修炼路径（大圆满时需要）
+    #[serde(default)]
+    pub bottleneck: bool,             // 是否卡在瓶颈（大圆满时可能遭遇）
 }
 
 impl CultivationProgress {
@@ -61,6 +570,7 @@ impl CultivationProgress {
             sub_level: SubLevel::Early,
             progress: 0,
             cultivation_path: Some(CultivationPath::new()), // 每个境界都有修炼路径
+            bottleneck: false,
         }
     }
 
@@ -81,7 +591,7 @@ impl CultivationProgress {
     }
 
     /// 尝试突破小境界
-    pub fn try_sublevel_breakthrough(&mut self) -> bool {
+    pub fn try_sublevel_breakthrough<R: rand::Rng>(&mut self, rng: &mut R) -> bool {
         if !self.is_sub_level_complete() {
             return false;
         }
@@ -90,6 +600,10 @@ impl CultivationProgress {
             Some(next_sub) => {
                 self.sub_level = next_sub;
                 self.progress = 0;
+                // 进入大圆满时可能卡在瓶颈
+                if next_sub == SubLevel::Perfect {
+                    self.bottleneck = rng.gen_bool(BOTTLENECK_CHANCE);
+                }
                 true
             }
             None => false,
@@ -112,11 +626,55 @@ impl CultivationProgress {
         self.progress = 0;
         // 创建新的空修炼路径（任务将由InteractiveGame从当前任务中选择）
         self.cultivation_path = Some(CultivationPath::new());
+        self.bottleneck = false;
+    }
+
+    /// 破除瓶颈，返回此前是否处于瓶颈
+    pub fn break_bottleneck(&mut self) -> bool {
+        std::mem::replace(&mut self.bottleneck, false)
+    }
+}
+
+/// 可设置为长期偏好的任务类型
+pub const PREFERABLE_TASK_TYPES: [&str; 5] = ["Gathering", "Combat", "Exploration", "Auxiliary", "Investment"];
+
+/// 偏好与修炼路径需求冲突时的权衡
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum PreferenceConflict {
+    #[default]
+    PreferenceFirst, // 坚持偏好
+    PathFirst,       // 修炼路径尚缺的任务类型视同偏好
+}
+
+impl PreferenceConflict {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "PreferenceFirst" => Some(Self::PreferenceFirst),
+            "PathFirst" => Some(Self::PathFirst),
+            _ => None,
+        }
+    }
+}
+
+/// 弟子的长期任务偏好（自动分配时优先尊重）
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TaskTypePreference {
+    pub task_type: String, // 任务类型（同 Task::get_task_type_str）
+    #[serde(default)]
+    pub on_conflict: PreferenceConflict,
+}
+
+impl TaskTypePreference {
+    pub fn new(task_type: &str, on_conflict: PreferenceConflict) -> Result<Self, String> {
+        if !PREFERABLE_TASK_TYPES.contains(&task_type) {
+            return Err(format!("未知的任务类型: {}", task_type));
+        }
+        Ok(Self { task_type: task_type.to_string(), on_conflict })
     }
 }
 
 /// 弟子
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Disciple {
     pub id: usize,
     pub name: String,
@@ -134,6 +692,40 @@ pub struct Disciple {
     pub modifiers: ModifierStack, // Modifier系统
     pub position: Position, // 弟子在地图上的位置
     pub moves_remaining: u32, // 本回合剩余移动距离
+    #[serde(default)]
+    pub injuries: Vec<Injury>, // 伤势
+    #[serde(default)]
+    pub learned_techniques: Vec<Technique>, // 已学会的功法
+    #[serde(default)]
+    pub active_technique: Option<usize>, // 当前激活功法在已学功法中的索引
+    #[serde(default)]
+    pub equipment: HashMap<EquipmentSlot, Equipment>, // 各槽位的装备
+    #[serde(default)]
+    pub personality: Option<Personality>, // 性格
+    #[serde(default)]
+    pub pills_taken_this_turn: u32, // 本回合已服药次数
+    #[serde(default)]
+    pub tasks_completed: u32, // 累计完成任务数（功劳）
+    #[serde(default)]
+    pub reputation_contributed: u32, // 累计为宗门赢得的声望（功劳）
+    #[serde(default)]
+    pub total_resources_contributed: u32, // 累计为宗门赚取的灵石（组队时按人数均分）
+    #[serde(default)]
+    pub monsters_slain: u32, // 累计参与斩杀的妖魔数
+    #[serde(default)]
+    pub seclusion_turns: u32, // 剩余闭关回合数（0表示未闭关）
+    #[serde(default)]
+    pub patrolling: bool, // 是否在所在位置巡逻（巡逻期间不可接任务）
+    #[serde(default)]
+    pub task_preference: Option<TaskTypePreference>, // 自动分配时优先的任务类型
+    #[serde(default)]
+    pub fatigue: u32, // 疲劳度（连续执行任务累积）
+    #[serde(default)]
+    pub exhausted_turns: u32, // 精力连续归零的回合数
+    #[serde(default)]
+    pub specialization: Option<Specialization>, // 专精路线
+    #[serde(default)]
+    pub persuaded: bool, // 已被贡献点说服，下次指派不再抗命
 }
 
 impl Disciple {
@@ -158,12 +750,29 @@ impl Disciple {
             modifiers: ModifierStack::new(),
             position: Position { x: 9, y: 8 }, // 初始位置在宗门
             moves_remaining: movement_range, // 初始化为移动范围
+            injuries: Vec::new(),
+            learned_techniques: Vec::new(),
+            active_technique: None,
+            equipment: HashMap::new(),
+            personality: None,
+            pills_taken_this_turn: 0,
+            tasks_completed: 0,
+            reputation_contributed: 0,
+            total_resources_contributed: 0,
+            monsters_slain: 0,
+            seclusion_turns: 0,
+            patrolling: false,
+            task_preference: None,
+            fatigue: 0,
+            exhausted_turns: 0,
+            specialization: None,
+            persuaded: false,
         }
     }
 
     /// 是否存活
     pub fn is_alive(&self) -> bool {
-        self.age < self.lifespan && self.constitution > 0
+        self.age < self.get_effective_lifespan() && self.constitution > 0
     }
 
     /// 是否达到仙道
@@ -195,7 +804,7 @@ impl Disciple {
 
     /// 获取有效道心值（应用modifier后，包含宗门modifiers）
     pub fn get_effective_dao_heart_with_sect_modifiers(&self, sect_modifiers: &[&crate::modifier::Modifier]) -> f32 {
-        self.modifiers.calculate_effective_with_extras(&ModifierTarget::DaoHeart, self.dao_heart as f32, sect_modifiers)
+        self.calculate_effective(&ModifierTarget::DaoHeart, self.dao_heart as f32, sect_modifiers)
     }
 
     /// 获取有效精力值（应用modifier后）
@@ -205,7 +814,7 @@ impl Disciple {
 
     /// 获取有效精力值（应用modifier后，包含宗门modifiers）
     pub fn get_effective_energy_with_sect_modifiers(&self, sect_modifiers: &[&crate::modifier::Modifier]) -> f32 {
-        self.modifiers.calculate_effective_with_extras(&ModifierTarget::Energy, self.energy as f32, sect_modifiers)
+        self.calculate_effective(&ModifierTarget::Energy, self.energy as f32, sect_modifiers)
     }
 
     /// 获取有效体魄值（应用modifier后）
@@ -215,7 +824,254 @@ impl Disciple {
 
     /// 获取有效体魄值（应用modifier后，包含宗门modifiers）
     pub fn get_effective_constitution_with_sect_modifiers(&self, sect_modifiers: &[&crate::modifier::Modifier]) -> f32 {
-        self.modifiers.calculate_effective_with_extras(&ModifierTarget::Constitution, self.constitution as f32, sect_modifiers)
+        self.calculate_effective(&ModifierTarget::Constitution, self.constitution as f32, sect_modifiers)
+    }
+
+    /// 获取有效寿元（应用modifier后）
+    pub fn get_effective_lifespan(&self) -> u32 {
+        self.modifiers
+            .calculate_effective(&ModifierTarget::Lifespan, self.lifespan as f32)
+            .max(0.0) as u32
+    }
+
+    /// 获取有效移动范围（应用modifier后）
+    pub fn get_effective_movement_range(&self) -> u32 {
+        self.get_effective_movement_range_with_sect_modifiers(&[])
+    }
+
+    /// 获取有效移动范围（应用modifier后，包含宗门modifiers）
+    pub fn get_effective_movement_range_with_sect_modifiers(&self, sect_modifiers: &[&crate::modifier::Modifier]) -> u32 {
+        let native = self.cultivation.current_level.movement_range() as f32;
+        self.modifiers
+            .calculate_effective_with_extras(&ModifierTarget::MovementRange, native, sect_modifiers)
+            .max(0.0) as u32
+    }
+
+    /// 每回合可用的移动力：闭关中无法走动，伤势按最重一处削减有效移动范围
+    pub fn moves_per_turn(&self, sect_modifiers: &[&crate::modifier::Modifier]) -> u32 {
+        if self.is_in_seclusion() {
+            return 0;
+        }
+        let range = self.get_effective_movement_range_with_sect_modifiers(sect_modifiers);
+        let penalty = self.injuries.iter().map(|i| i.severity.movement_penalty()).fold(0.0, f32::max);
+        (range as f32 * (1.0 - penalty)).round() as u32
+    }
+
+    /// 获取有效修炼速度倍率（应用modifier后，基准为1.0）
+    pub fn get_effective_cultivation_speed(&self) -> f32 {
+        self.calculate_effective(&ModifierTarget::CultivationSpeed, 1.0, &[])
+    }
+
+    /// 设置性格并挂上对应的modifier
+    pub fn set_personality(&mut self, personality: Personality) {
+        if let Some(old) = self.personality {
+            let name = format!("性格：{}", old.name());
+            let ids: Vec<String> = self.modifiers.get_all_modifiers().iter()
+                .filter(|m| m.name == name)
+                .map(|m| m.id.clone())
+                .collect();
+            for id in ids {
+                self.modifiers.remove_modifier(&id);
+            }
+        }
+        for modifier in personality.modifiers() {
+            self.add_modifier(modifier);
+        }
+        self.personality = Some(personality);
+    }
+
+    /// 应用任务对道心的影响（受性格影响）
+    pub fn apply_dao_heart_impact(&mut self, impact: i32) {
+        self.dao_heart = self.dao_heart_after_impact(impact);
+    }
+
+    /// 受到道心冲击后的道心（性格调整冲击幅度，结果限制在0-100）
+    pub fn dao_heart_after_impact(&self, impact: i32) -> u32 {
+        let impact = self.personality
+            .map(|p| p.adjust_dao_heart_impact(impact))
+            .unwrap_or(impact);
+        ((self.dao_heart as i32 + impact).max(0) as u32).min(100)
+    }
+
+    /// 分配该任务是否违背弟子的任务偏好（按偏好的冲突权衡处理修炼路径需求）
+    pub fn deviates_from_preference(&self, task: &Task) -> bool {
+        let Some(preference) = &self.task_preference else {
+            return false;
+        };
+        let task_type = task.get_task_type_str();
+        if preference.task_type == task_type {
+            return false;
+        }
+        let path_needs = self.cultivation.cultivation_path.as_ref().is_some_and(|path| {
+            let (completed, required) = path.progress_by_type(task_type);
+            completed < required
+        });
+        !(preference.on_conflict == PreferenceConflict::PathFirst && path_needs)
+    }
+
+    /// 是否正在闭关
+    pub fn is_in_seclusion(&self) -> bool {
+        self.seclusion_turns > 0
+    }
+
+    /// 累积疲劳
+    pub fn add_fatigue(&mut self, amount: u32) {
+        self.fatigue = (self.fatigue + amount).min(MAX_FATIGUE);
+    }
+
+    /// 是否处于高疲劳状态
+    pub fn is_fatigued(&self) -> bool {
+        self.fatigue >= HIGH_FATIGUE_THRESHOLD
+    }
+
+    /// 高疲劳时任务收益下降（满疲劳减半）
+    pub fn fatigue_modifier(&self) -> Option<Modifier> {
+        self.is_fatigued().then(|| {
+            Modifier::new(
+                "疲劳",
+                ModifierTarget::TaskReward,
+                ModifierApplication::Multiplicative(-0.5 * self.fatigue as f32 / MAX_FATIGUE as f32),
+                ModifierSource::Debuff,
+            )
+        })
+    }
+
+    /// 精力耗尽后强制休息，本回合不能接任务
+    pub fn is_exhausted(&self) -> bool {
+        self.exhausted_turns > 0
+    }
+
+    /// 精力不足时任务收益下降
+    pub fn low_energy_modifier(&self) -> Option<Modifier> {
+        (self.energy < LOW_ENERGY_THRESHOLD).then(|| {
+            Modifier::new(
+                "精力不济",
+                ModifierTarget::TaskReward,
+                ModifierApplication::Multiplicative(LOW_ENERGY_REWARD_PENALTY),
+                ModifierSource::Debuff,
+            )
+        })
+    }
+
+    /// 疲劳导致的受伤概率倍率（满疲劳翻倍）
+    pub fn fatigue_injury_multiplier(&self) -> f32 {
+        1.0 + self.fatigue as f32 / MAX_FATIGUE as f32
+    }
+
+    /// 闭关每回合获得的修为（受修炼速度modifier影响，境界越高收益越低）
+    pub fn seclusion_progress(&self, sect_modifiers: &[&Modifier]) -> u32 {
+        let disciple_level = self.cultivation.current_level.to_numeric() as f32;
+        let level_penalty = 1.0 / (1.0 + disciple_level / 6.0);
+        let speed = self.calculate_effective(
+            &ModifierTarget::CultivationSpeed,
+            1.0,
+            sect_modifiers,
+        );
+        ((SECLUSION_BASE_PROGRESS * level_penalty * speed) as u32).max(1)
+    }
+
+    /// 闭关修炼一回合，返回获得的修为
+    pub fn cultivate_in_seclusion<R: rand::Rng>(&mut self, sect_modifiers: &[&Modifier], rng: &mut R) -> u32 {
+        let progress = self.seclusion_progress(sect_modifiers);
+        self.cultivation.add_progress(progress);
+        self.cultivation.try_sublevel_breakthrough(rng);
+        self.seclusion_turns = self.seclusion_turns.saturating_sub(1);
+        progress
+    }
+
+    /// 当前激活的功法
+    pub fn current_technique(&self) -> Option<&Technique> {
+        self.active_technique.and_then(|i| self.learned_techniques.get(i))
+    }
+
+    /// 检查能否学习功法（需具备对应灵根且尚未学会）
+    pub fn can_learn_technique(&self, technique: &Technique) -> Result<(), String> {
+        if self.learned_techniques.iter().any(|t| t.id == technique.id) {
+            return Err(format!("{}已学会{}", self.name, technique.name));
+        }
+        if !technique.matches(self) {
+            return Err(format!("{}不具备{:?}灵根，无法修习{}", self.name, technique.talent_type, technique.name));
+        }
+        Ok(())
+    }
+
+    /// 学习新功法，尚无激活功法时自动激活
+    pub fn learn_technique(&mut self, technique: Technique) -> Result<(), String> {
+        self.can_learn_technique(&technique)?;
+        self.learned_techniques.push(technique);
+        if self.active_technique.is_none() {
+            self.activate_technique(self.learned_techniques.len() - 1)?;
+        }
+        Ok(())
+    }
+
+    /// 切换激活的功法，按新功法重算功法modifier
+    pub fn activate_technique(&mut self, index: usize) -> Result<(), String> {
+        let technique = self
+            .learned_techniques
+            .get(index)
+            .cloned()
+            .ok_or_else(|| format!("{}尚未学会该功法", self.name))?;
+        self.deactivate_technique();
+        for modifier in technique.modifiers_for(self) {
+            self.add_modifier(modifier);
+        }
+        for cm in technique.conditional_modifiers_for(self) {
+            self.modifiers.add_conditional_modifier(cm);
+        }
+        self.active_technique = Some(index);
+        Ok(())
+    }
+
+    /// 选择专精路线，按新路线重算专精modifier
+    pub fn specialize(&mut self, specialization: Specialization) -> Result<(), String> {
+        if self.cultivation.current_level < SPECIALIZATION_MIN_LEVEL {
+            return Err(format!("{}需达到{}才能选择专精", self.name, SPECIALIZATION_MIN_LEVEL));
+        }
+        if self.specialization == Some(specialization) {
+            return Err(format!("{}已是{}", self.name, specialization.name()));
+        }
+        self.remove_modifiers_by_source(&ModifierSource::Specialization);
+        for cm in specialization.conditional_modifiers() {
+            if let Some(modifier) = cm.get_modifier_if_applies(self) {
+                self.add_modifier(modifier.clone());
+            }
+        }
+        self.specialization = Some(specialization);
+        Ok(())
+    }
+
+    /// 是否具备某项天赋（专精视同具备对应天赋）
+    pub fn has_skill(&self, skill: &TalentType) -> bool {
+        self.talents.iter().any(|t| &t.talent_type == skill)
+            || self.specialization.and_then(|s| s.skill()).as_ref() == Some(skill)
+    }
+
+    /// 停用当前功法，清除功法提供的modifier
+    pub fn deactivate_technique(&mut self) -> Option<usize> {
+        self.remove_modifiers_by_source(&ModifierSource::Technique);
+        self.active_technique.take()
+    }
+
+    /// 穿戴装备（同槽位先卸下旧装备），返回被替换的装备
+    pub fn equip(&mut self, equipment: Equipment) -> Option<Equipment> {
+        let previous = self.unequip(equipment.slot);
+        for modifier in &equipment.modifiers {
+            self.add_modifier(modifier.clone());
+        }
+        self.equipment.insert(equipment.slot, equipment);
+        previous
+    }
+
+    /// 卸下指定槽位的装备，其余槽位装备的modifier重新挂上
+    pub fn unequip(&mut self, slot: EquipmentSlot) -> Option<Equipment> {
+        let removed = self.equipment.remove(&slot)?;
+        self.remove_modifiers_by_source(&ModifierSource::Equipment);
+        let remaining: Vec<Modifier> = self.equipment.values().flat_map(|e| e.modifiers.clone()).collect();
+        for modifier in remaining {
+            self.add_modifier(modifier);
+        }
+        Some(removed)
     }
 
     /// 每回合更新modifier（减少持续时间，移除过期的modifier）
@@ -233,6 +1089,29 @@ impl Disciple {
         self.modifiers.remove_modifiers_by_source(source);
     }
 
+    /// 按当前状态对自身生效的条件modifier
+    pub fn applicable_conditional_modifiers(&self) -> Vec<&Modifier> {
+        self.modifiers
+            .get_conditional_modifiers()
+            .iter()
+            .filter_map(|cm| cm.get_modifier_if_applies(self))
+            .collect()
+    }
+
+    /// 计算effective值（个人modifier + 当前生效的条件modifier + 额外modifiers）
+    pub fn calculate_effective(&self, target: &ModifierTarget, native: f32, extra_modifiers: &[&Modifier]) -> f32 {
+        let mut extras = self.applicable_conditional_modifiers();
+        extras.extend_from_slice(extra_modifiers);
+        self.modifiers.calculate_effective_with_extras(target, native, &extras)
+    }
+
+    /// 逐步解释effective值的计算过程（同calculate_effective）
+    pub fn explain_effective(&self, target: &ModifierTarget, native: f32, extra_modifiers: &[&Modifier]) -> ModifierExplanation {
+        let mut extras = self.applicable_conditional_modifiers();
+        extras.extend_from_slice(extra_modifiers);
+        self.modifiers.explain(target, native, &extras)
+    }
+
     /// 获取资质加成（应用modifier后的有效值）
     pub fn get_talent_bonus(&self, talent_type: &TalentType) -> f32 {
         self.get_talent_bonus_with_sect_modifiers(talent_type, &[])
@@ -250,7 +1129,7 @@ impl Disciple {
         // 2. 应用modifier获取effective值（包含宗门modifiers）
         let talent_type_str = format!("{:?}", talent_type);
         let target = ModifierTarget::TalentBonus(talent_type_str);
-        self.modifiers.calculate_effective_with_extras(&target, native_bonus, sect_modifiers)
+        self.calculate_effective(&target, native_bonus, sect_modifiers)
     }
 
     /// 计算渡劫成功率（应用modifier后的有效值）
@@ -274,38 +1153,146 @@ impl Disciple {
         let native_rate = (base_rate + dao_heart_bonus + heritage_bonus).min(0.95);
 
         // 3. 应用TribulationSuccessRate modifier（包含宗门modifiers）
-        let effective_rate = self.modifiers.calculate_effective_with_extras(
+        let effective_rate = self.calculate_effective(
             &ModifierTarget::TribulationSuccessRate,
             native_rate,
             sect_modifiers
         );
 
-        // 4. 确保在合理范围内 (0.0 - 0.95)
+        // 4. 瓶颈未破时成功率大打折扣
+        let effective_rate = if self.cultivation.bottleneck {
+            effective_rate * BOTTLENECK_TRIBULATION_PENALTY
+        } else {
+            effective_rate
+        };
+
+        // 5. 确保在合理范围内 (0.0 - 0.95)
         effective_rate.max(0.0).min(0.95)
     }
 
+    /// 炼丹天赋等级（无炼丹天赋为0）
+    pub fn alchemy_talent_level(&self) -> u32 {
+        self.talents
+            .iter()
+            .filter(|t| t.talent_type == TalentType::Alchemy)
+            .map(|t| t.level)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// 计算炼丹成功率（炼丹天赋与修为提升native值，再应用modifier，包含宗门modifiers）
+    pub fn alchemy_success_rate(&self, recipe_rate: f64, sect_modifiers: &[&Modifier]) -> f64 {
+        let talent_bonus = self.alchemy_talent_level() as f32 * 0.04;
+        let cultivation_bonus = self.cultivation.current_level as u32 as f32 * 0.02;
+        let native_rate = recipe_rate as f32 + talent_bonus + cultivation_bonus;
+
+        let effective_rate = self.calculate_effective(
+            &ModifierTarget::AlchemySuccessRate,
+            native_rate,
+            sect_modifiers,
+        );
+        effective_rate.clamp(0.0, MAX_ALCHEMY_SUCCESS_RATE) as f64
+    }
+
+    /// 炼丹成功时超额产出一炉的概率（炼丹天赋越高越可能）
+    pub fn alchemy_surplus_chance(&self) -> f64 {
+        self.alchemy_talent_level().saturating_sub(ALCHEMY_SURPLUS_TALENT_LEVEL) as f64 * 0.1
+    }
+
     /// 尝试渡劫
     /// 渡劫
-    pub fn attempt_tribulation(&mut self) -> bool {
+    pub fn attempt_tribulation<R: rand::Rng>(&mut self, rng: &mut R) -> TribulationOutcome {
+        self.attempt_tribulation_with_modifiers(&[], rng)
+    }
+
+    /// 渡劫（计入额外的modifier，如护法加成）
+    pub fn attempt_tribulation_with_modifiers<R: rand::Rng>(&mut self, extra_modifiers: &[&Modifier], rng: &mut R) -> TribulationOutcome {
+        let roll: f32 = rng.gen();
+        let death_roll: f32 = rng.gen();
+        self.resolve_tribulation(roll, death_roll, extra_modifiers)
+    }
+
+    /// 根据随机数结算渡劫结果（计入额外的modifier）
+    /// 失败时有 TRIBULATION_DEATH_CHANCE 的概率身死，否则重伤并挂上渡劫重伤debuff
+    pub fn resolve_tribulation(&mut self, roll: f32, death_roll: f32, extra_modifiers: &[&Modifier]) -> TribulationOutcome {
         // 检查是否满足渡劫条件
         if !self.cultivation.can_tribulate() {
-            return false;
+            return TribulationOutcome::NotReady;
         }
 
-        use rand::Rng;
-        let mut rng = rand::thread_rng();
-        let success_rate = self.tribulation_success_rate();
-        let roll: f32 = rng.gen();
-
-        if roll < success_rate {
+        if roll < self.tribulation_success_rate_with_sect_modifiers(extra_modifiers) {
             if let Some(next_level) = self.cultivation.current_level.next() {
                 self.cultivation.breakthrough_major_level(next_level);
-                self.lifespan = next_level.base_lifespan();
-                return true;
+                self.reset_lifespan_for(next_level);
+                return TribulationOutcome::Success;
             }
+            return TribulationOutcome::NotReady;
         }
 
-        false
+        if death_roll < TRIBULATION_DEATH_CHANCE {
+            self.constitution = 0; // 设置体魄为0，标记死亡
+            return TribulationOutcome::Died;
+        }
+
+        // 重伤：体魄大幅下降，数回合内再次渡劫成功率降低
+        self.constitution = self.constitution.saturating_sub(TRIBULATION_INJURY_CONSTITUTION_LOSS).max(1);
+        self.add_modifier(Modifier::new_temporary(
+            "渡劫重伤",
+            ModifierTarget::TribulationSuccessRate,
+            ModifierApplication::Multiplicative(-0.3),
+            ModifierSource::Debuff,
+            TRIBULATION_INJURY_DURATION,
+        ));
+        TribulationOutcome::Injured
+    }
+
+    /// 走火入魔的概率（按包含宗门modifier的有效道心计算）
+    pub fn heart_demon_chance(&self, sect_modifiers: &[&Modifier]) -> f32 {
+        let dao_heart = self.get_effective_dao_heart_with_sect_modifiers(sect_modifiers);
+        if dao_heart >= HEART_DEMON_DAO_HEART_THRESHOLD {
+            return 0.0;
+        }
+        HEART_DEMON_MAX_CHANCE * (HEART_DEMON_DAO_HEART_THRESHOLD - dao_heart.max(0.0)) / HEART_DEMON_DAO_HEART_THRESHOLD
+    }
+
+    /// 恶战或闭关时判定心魔
+    pub fn attempt_heart_demon<R: rand::Rng>(&mut self, sect_modifiers: &[&Modifier], rng: &mut R) -> Option<HeartDemon> {
+        let roll: f32 = rng.gen();
+        self.resolve_heart_demon(roll, sect_modifiers)
+    }
+
+    /// 根据随机数结算心魔：修为进度倒退并挂上心魔debuff，道心极低时暴走
+    pub fn resolve_heart_demon(&mut self, roll: f32, sect_modifiers: &[&Modifier]) -> Option<HeartDemon> {
+        if roll >= self.heart_demon_chance(sect_modifiers) {
+            return None;
+        }
+        let progress_lost = self.cultivation.progress.min(HEART_DEMON_PROGRESS_LOSS);
+        self.cultivation.progress -= progress_lost;
+        self.add_modifier(Modifier::new_temporary(
+            "心魔缠身",
+            ModifierTarget::CultivationSpeed,
+            ModifierApplication::Multiplicative(-0.5),
+            ModifierSource::Debuff,
+            HEART_DEMON_DURATION,
+        ));
+        let rampage = self.get_effective_dao_heart_with_sect_modifiers(sect_modifiers) < HEART_DEMON_RAMPAGE_DAO_HEART;
+        Some(HeartDemon { progress_lost, rampage })
+    }
+
+    /// 突破大境界后重定寿元：道心坚定者额外增寿，不超过该境界的寿元上限
+    fn reset_lifespan_for(&mut self, level: CultivationLevel) {
+        let base = level.base_lifespan();
+        let bonus = base as u64 * self.dao_heart.saturating_sub(DAO_HEART_LIFESPAN_THRESHOLD) as u64
+            / DAO_HEART_LIFESPAN_DIVISOR;
+        self.lifespan = (base as u64 + bonus).min(level.max_lifespan() as u64) as u32;
+    }
+
+    /// 延长寿元（不超过当前修为的寿元上限），返回实际增加的年数
+    pub fn extend_lifespan(&mut self, years: u32) -> u32 {
+        let max = self.cultivation.current_level.max_lifespan();
+        let before = self.lifespan;
+        self.lifespan = self.lifespan.saturating_add(years).min(max).max(before);
+        self.lifespan - before
     }
 
     /// 尝试突破（现在只用于练气期突破到筑基）
@@ -315,23 +1302,126 @@ impl Disciple {
            self.cultivation.can_tribulate() {
             if let Some(next_level) = self.cultivation.current_level.next() {
                 self.cultivation.breakthrough_major_level(next_level);
-                self.lifespan = next_level.base_lifespan();
+                self.reset_lifespan_for(next_level);
                 return true;
             }
         }
         false
     }
 
+    /// 资质等级（没有该灵根为0）
+    pub fn talent_level(&self, talent_type: &TalentType) -> u32 {
+        self.talents.iter().find(|t| &t.talent_type == talent_type).map_or(0, |t| t.level)
+    }
+
+    /// 结算一次灵根成长：已有灵根按等级递减的概率提升一级，没有则小概率觉醒一级新灵根
+    /// 返回成长后的等级
+    pub fn resolve_talent_growth(&mut self, talent_type: TalentType, roll: f32) -> Option<u32> {
+        match self.talents.iter_mut().find(|t| t.talent_type == talent_type) {
+            Some(talent) => {
+                if talent.level >= TALENT_MAX_LEVEL {
+                    return None;
+                }
+                let chance = TALENT_GROWTH_CHANCE * (TALENT_MAX_LEVEL - talent.level) as f32 / TALENT_MAX_LEVEL as f32;
+                if roll >= chance {
+                    return None;
+                }
+                talent.level += 1;
+                Some(talent.level)
+            }
+            None => {
+                if roll >= TALENT_AWAKEN_CHANCE {
+                    return None;
+                }
+                self.talents.push(Talent { talent_type, level: 1 });
+                Some(1)
+            }
+        }
+    }
+
     /// 完成任务（应用modifier后的有效奖励）
-    pub fn complete_task(&mut self, task: &Task) -> u32 {
-        // 1. 天赋加成（已经应用了modifier）
-        let talent_bonus = match &task.task_type {
-            TaskType::Gathering(_) => self.get_talent_bonus(&TalentType::Wood),
-            TaskType::Combat(_) => self.get_talent_bonus(&TalentType::Sword),
-            TaskType::Exploration(_) => 0.0,
-            TaskType::Auxiliary(_) => self.get_talent_bonus(&TalentType::Formation),
-            TaskType::Investment(_) => 0.0,
+    pub fn complete_task<R: rand::Rng>(&mut self, task: &Task, rng: &mut R) -> u32 {
+        self.complete_task_with_modifiers(task, &[], rng)
+    }
+
+    /// 完成任务，额外应用传入的modifier（如与仇敌共事的掣肘）
+    pub fn complete_task_with_modifiers<R: rand::Rng>(
+        &mut self,
+        task: &Task,
+        extra_modifiers: &[&Modifier],
+        rng: &mut R,
+    ) -> u32 {
+        let actual_progress = self.estimate_task_reward(task, extra_modifiers);
+
+        // 添加修为进度
+        self.cultivation.add_progress(actual_progress);
+
+        // 记录功劳
+        self.tasks_completed += 1;
+        self.reputation_contributed += task.reputation_reward.max(0) as u32;
+
+        // 尝试完成修炼路径任务（按任务类型）
+        let task_type_str = match &task.task_type {
+            TaskType::Combat(_) => "Combat",
+            TaskType::Exploration(_) => "Exploration",
+            TaskType::Gathering(_) => "Gathering",
+            TaskType::Auxiliary(_) => "Auxiliary",
+            TaskType::Investment(_) => "Investment",
         };
+        self.cultivation.try_complete_path_task_by_type(task_type_str);
+
+        // 历练中灵根成长或觉醒
+        if let Some(talent) = related_talent(task) {
+            self.resolve_talent_growth(talent, rng.gen());
+        }
+
+        // 感悟天道破除瓶颈
+        if task.is_bottleneck_insight() {
+            self.cultivation.break_bottleneck();
+        }
+
+        // 自动检查并突破小境界
+        if self.cultivation.is_sub_level_complete() {
+            self.cultivation.try_sublevel_breakthrough(rng);
+        }
+
+        actual_progress
+    }
+
+    /// 预览单人执行任务的收获（按弟子当前状态估算，不修改任何状态）
+    /// sect_modifiers 为结算时额外生效的modifier（如与仇敌共事的掣肘）
+    pub fn preview_task_reward(&self, task: &Task, sect_modifiers: &[&Modifier]) -> TaskRewardPreview {
+        self.preview_team_task_reward(task, sect_modifiers, &[self], false)
+    }
+
+    /// 预览组队执行任务的收获（team 为含本人在内的全队，据此计算协同加成和战斗兵力对比）
+    pub fn preview_team_task_reward(
+        &self,
+        task: &Task,
+        sect_modifiers: &[&Modifier],
+        team: &[&Disciple],
+        enemy_is_demon: bool,
+    ) -> TaskRewardPreview {
+        let synergy = crate::task::team_synergy_bonus(team);
+        let reward_multiplier = match &task.task_type {
+            TaskType::Combat(combat) => crate::event::CombatDetail::new(self, combat).reward_multiplier,
+            _ => 1.0,
+        };
+        let (resources, contribution, reputation) = task.settlement_rewards(reward_multiplier, synergy);
+        TaskRewardPreview {
+            progress: self.estimate_task_reward(task, sect_modifiers),
+            resources,
+            contribution,
+            reputation,
+            dao_heart_change: self.dao_heart_after_impact(task.dao_heart_impact) as i32 - self.dao_heart as i32,
+            success_rate: task.settlement_success_rate(Some(self), team, synergy, enemy_is_demon) as f32,
+        }
+    }
+
+    /// 预估完成任务可获得的修为（不修改弟子状态）
+    pub fn estimate_task_reward(&self, task: &Task, extra_modifiers: &[&Modifier]) -> u32 {
+        // 1. 天赋加成（已经应用了modifier）
+        let talent_bonus = related_talent(task).map_or(0.0, |talent| self.get_talent_bonus(&talent));
 
         // 2. 动态修为奖励计算
         let base_progress = task.progress_reward as f32;
@@ -349,67 +1439,71 @@ impl Disciple {
         // 5. 天赋乘数
         let talent_multiplier = 1.0 + talent_bonus;
 
-        // 6. 计算native奖励
-        let native_reward = base_progress * difficulty_multiplier * level_penalty * talent_multiplier;
-
-        // 7. 应用TaskReward modifier获取effective奖励
-        let effective_reward = self.modifiers.calculate_effective(
+        // 6. 计算native奖励（修炼速度影响修为获取）
+        let native_reward = base_progress * difficulty_multiplier * level_penalty * talent_multiplier
+            * self.get_effective_cultivation_speed();
+
+        // 7. 应用TaskReward modifier获取effective奖励（包含性格对该类任务的加成）
+        let personality_modifier = self.personality.and_then(|p| p.task_modifier(&task.task_type));
+        let specialization_modifier = self.specialization.and_then(|s| s.task_modifier(&task.task_type));
+        let realm_modifier = (task.realm_affinity(self) == Some(true)).then(|| {
+            Modifier::new(
+                "灵根契合秘境",
+                ModifierTarget::TaskReward,
+                ModifierApplication::Multiplicative(crate::task::REALM_AFFINITY_REWARD_BONUS),
+                ModifierSource::System,
+            )
+        });
+        let depth_modifier = match &task.task_type {
+            TaskType::Exploration(exploration) if exploration.depth > 0 => Some(Modifier::new(
+                "深入险地",
+                ModifierTarget::TaskReward,
+                ModifierApplication::Multiplicative(exploration.depth_reward_multiplier() - 1.0),
+                ModifierSource::System,
+            )),
+            _ => None,
+        };
+        let fatigue_modifier = self.fatigue_modifier();
+        let low_energy_modifier = self.low_energy_modifier();
+        let mut extras: Vec<&Modifier> = personality_modifier
+            .iter()
+            .chain(realm_modifier.iter())
+            .chain(depth_modifier.iter())
+            .chain(fatigue_modifier.iter())
+            .chain(low_energy_modifier.iter())
+            .chain(specialization_modifier.iter())
+            .collect();
+        extras.extend_from_slice(extra_modifiers);
+        let effective_reward = self.calculate_effective(
             &ModifierTarget::TaskReward,
-            native_reward
+            native_reward,
+            &extras,
         );
 
         // 8. 转换为整数，确保至少给予1点修为
-        let actual_progress = (effective_reward as u32).max(1);
-
-        // 添加修为进度
-        self.cultivation.add_progress(actual_progress);
-
-        // 尝试完成修炼路径任务（按任务类型）
-        let task_type_str = match &task.task_type {
-            TaskType::Combat(_) => "Combat",
-            TaskType::Exploration(_) => "Exploration",
-            TaskType::Gathering(_) => "Gathering",
-            TaskType::Auxiliary(_) => "Auxiliary",
-            TaskType::Investment(_) => "Investment",
-        };
-        self.cultivation.try_complete_path_task_by_type(task_type_str);
-
-        // 自动检查并突破小境界
-        if self.cultivation.is_sub_level_complete() {
-            self.cultivation.try_sublevel_breakthrough();
-        }
-
-        actual_progress
+        (effective_reward as u32).max(1)
     }
 
     /// 消耗精力（应用modifier后的有效消耗）
     pub fn consume_energy(&mut self, amount: u32) {
         // 1. 应用EnergyConsumption modifier
-        let effective_consumption = self.modifiers.calculate_effective(
+        let effective_consumption = self.calculate_effective(
             &ModifierTarget::EnergyConsumption,
-            amount as f32
+            amount as f32,
+            &[],
         ) as u32;
 
-        // 2. 执行消耗
-        if self.energy >= effective_consumption {
-            self.energy -= effective_consumption;
-        } else {
-            self.energy = 0;
-        }
-
-        // 如果精力降到0，减少1年寿命
-        if self.energy == 0 && self.lifespan > 0 {
-            self.lifespan = self.lifespan.saturating_sub(1);
-            println!("   ⚠️ {}精力耗尽，寿命减少1年（剩余{}年）", self.name, self.lifespan - self.age);
-        }
+        // 2. 执行消耗（归零后于回合结算时强制休息）
+        self.energy = self.energy.saturating_sub(effective_consumption);
     }
 
     /// 消耗体魄（应用modifier后的有效消耗）
     pub fn consume_constitution(&mut self, amount: u32) {
         // 1. 应用ConstitutionConsumption modifier
-        let effective_consumption = self.modifiers.calculate_effective(
+        let effective_consumption = self.calculate_effective(
             &ModifierTarget::ConstitutionConsumption,
-            amount as f32
+            amount as f32,
+            &[],
         ) as u32;
 
         // 2. 执行消耗
@@ -435,11 +1529,150 @@ impl Disciple {
         self.constitution = (self.constitution + amount).min(100);
     }
 
-    /// 每回合自然恢复
-    pub fn natural_recovery(&mut self) {
-        // 每回合恢复5点精力和2点体魄
-        self.restore_energy(5);
-        self.restore_constitution(2);
+    /// 每回合自然恢复（恢复量随修为等级递增，并受宗门modifier影响）
+    pub fn natural_recovery(&mut self, sect_modifiers: &[&Modifier]) {
+        use rand::Rng;
+
+        // 精力归零则强制休息，连续多回合归零才折损寿元
+        if self.energy == 0 {
+            self.exhausted_turns += 1;
+            if self.exhausted_turns >= EXHAUSTION_LIFESPAN_TURNS && self.lifespan > 0 {
+                self.lifespan -= 1;
+                println!("   ⚠️ {}精力连续{}回合耗尽，寿命减少1年", self.name, self.exhausted_turns);
+            }
+        } else {
+            self.exhausted_turns = 0;
+        }
+
+        let (energy, constitution) = self.cultivation.current_level.recovery_rate();
+        let energy = self.calculate_effective(
+            &ModifierTarget::EnergyRecovery,
+            energy as f32,
+            sect_modifiers,
+        );
+        let constitution = self.calculate_effective(
+            &ModifierTarget::ConstitutionRecovery,
+            constitution as f32,
+            sect_modifiers,
+        );
+        self.restore_energy(energy.max(0.0) as u32);
+        self.restore_constitution(constitution.max(0.0) as u32);
+
+        // 新回合重置服药次数
+        self.pills_taken_this_turn = 0;
+
+        // 疲劳逐回合消退
+        self.fatigue = self.fatigue.saturating_sub(FATIGUE_RECOVERY_PER_TURN);
+
+        // 轻伤有小概率自愈
+        if self.injuries.iter().any(|i| i.severity == InjurySeverity::Minor)
+            && rand::thread_rng().gen_bool(MINOR_INJURY_SELF_HEAL_CHANCE)
+        {
+            self.heal_injury_of(InjurySeverity::Minor);
+        }
+    }
+
+    /// 服用丹药：超过每回合上限后不再增加修为，并必定触发副作用、可能药毒入体
+    pub fn take_pill<R: rand::Rng>(&mut self, pill_type: PillType, grade: PillGrade, rng: &mut R) -> PillOutcome {
+        let effects = pill_type.effects_of_grade(grade);
+        self.pills_taken_this_turn += 1;
+        let overdosed = self.pills_taken_this_turn > MAX_PILLS_PER_TURN;
+
+        self.restore_energy(effects.energy_restore);
+        self.restore_constitution(effects.constitution_restore);
+        if effects.energy_restore > 0 || effects.constitution_restore > 0 {
+            self.fatigue = self.fatigue.saturating_sub(PILL_FATIGUE_RELIEF);
+        }
+
+        if effects.cultivation_boost > 0 && !overdosed {
+            self.cultivation.add_progress(effects.cultivation_boost);
+        }
+        let bottleneck_broken = effects.breaks_bottleneck && !overdosed && self.cultivation.break_bottleneck();
+        if effects.lifespan_extension > 0 && !overdosed {
+            self.extend_lifespan(effects.lifespan_extension);
+        }
+
+        // 临时增益与副作用重复服用时刷新持续时间而不叠加
+        let buff = effects.buff.filter(|_| !overdosed);
+        if let Some(buff) = buff {
+            self.refresh_modifier(buff.modifier(grade));
+        }
+        let side_effect = effects
+            .side_effect
+            .filter(|_| overdosed || rng.gen_bool(effects.side_effect_chance));
+        if let Some(side_effect) = side_effect {
+            self.refresh_modifier(side_effect.modifier());
+        }
+
+        let poisoned = overdosed && rng.gen_bool(PILL_TOXICITY_CHANCE);
+        if poisoned {
+            self.add_injury(InjurySeverity::Minor);
+        }
+
+        PillOutcome {
+            overdosed,
+            side_effect,
+            poisoned,
+            bottleneck_broken,
+            buff,
+        }
+    }
+
+    /// 添加modifier，替换同名的旧modifier
+    fn refresh_modifier(&mut self, modifier: Modifier) {
+        let stale: Vec<String> = self.modifiers.get_all_modifiers().iter()
+            .filter(|m| m.name == modifier.name)
+            .map(|m| m.id.clone())
+            .collect();
+        for id in stale {
+            self.modifiers.remove_modifier(&id);
+        }
+        self.add_modifier(modifier);
+    }
+
+    // === 伤势系统 ===
+
+    /// 添加伤势（同时挂上降低任务奖励的Debuff）
+    pub fn add_injury(&mut self, severity: InjurySeverity) {
+        let modifier = Modifier::new(
+            format!("伤势：{}", severity.name()),
+            ModifierTarget::TaskReward,
+            ModifierApplication::Multiplicative(-severity.task_reward_penalty()),
+            ModifierSource::Debuff,
+        );
+        self.injuries.push(Injury {
+            severity,
+            modifier_id: modifier.id.clone(),
+        });
+        self.add_modifier(modifier);
+    }
+
+    /// 是否有伤在身
+    pub fn is_injured(&self) -> bool {
+        !self.injuries.is_empty()
+    }
+
+    /// 治愈最严重的一处伤势
+    pub fn heal_worst_injury(&mut self) -> Option<Injury> {
+        let worst = self.injuries.iter().map(|i| i.severity).max()?;
+        self.heal_injury_of(worst)
+    }
+
+    /// 治愈一处指定等级的伤势
+    fn heal_injury_of(&mut self, severity: InjurySeverity) -> Option<Injury> {
+        let index = self.injuries.iter().position(|i| i.severity == severity)?;
+        let injury = self.injuries.remove(index);
+        self.modifiers.remove_modifier(&injury.modifier_id);
+        Some(injury)
+    }
+
+    /// 治愈所有伤势
+    pub fn heal_all_injuries(&mut self) -> Vec<Injury> {
+        let healed: Vec<Injury> = self.injuries.drain(..).collect();
+        for injury in &healed {
+            self.modifiers.remove_modifier(&injury.modifier_id);
+        }
+        healed
     }
 
     /// 死亡后生成传承
@@ -454,6 +1687,11 @@ impl Disciple {
                     CultivationLevel::VoidRefinement => 0.2,
                     _ => 0.05,
                 },
+                talent: self
+                    .talents
+                    .iter()
+                    .max_by_key(|t| t.level)
+                    .map(|t| t.talent_type.clone()),
             })
         } else {
             None
@@ -480,6 +1718,23 @@ impl Disciple {
         self.get_relationship_mut(target_id).unwrap()
     }
 
+    /// 与队友共事时的仇怨Debuff（任意一方敌意达到阈值即互相掣肘）
+    pub fn enmity_modifiers(&self, teammates: &[&Disciple]) -> Vec<Modifier> {
+        teammates
+            .iter()
+            .filter(|mate| mate.id != self.id)
+            .filter_map(|mate| {
+                let mine = enmity_modifier(mate.id);
+                let theirs = enmity_modifier(self.id);
+                if mine.applies_to(self) || theirs.applies_to(mate) {
+                    Some(mine.modifier)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
     /// 移除与指定弟子的关系
     pub fn remove_relationship(&mut self, target_id: usize) {
         self.relationships.retain(|r| r.target_id != target_id);
@@ -515,3 +1770,361 @@ impl Disciple {
         self.relationships.iter().any(|r| r.is_dao_companion)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pill::{PillBuff, PillSideEffect, PILL_BUFF_DURATION};
+    use crate::sect::{DepartureCause, Sect};
+
+    fn tribulation_ready_disciple(level: CultivationLevel) -> Disciple {
+        let mut disciple = Disciple::new(1, "韩立".to_string(), DiscipleType::Inner, vec![]);
+        disciple.cultivation.current_level = level;
+        disciple.cultivation.sub_level = SubLevel::Perfect;
+        disciple.lifespan = level.base_lifespan();
+        disciple
+    }
+
+    #[test]
+    fn test_tribulation_injury_adds_debuff() {
+        let mut disciple = tribulation_ready_disciple(CultivationLevel::Foundation);
+        let rate_before = disciple.tribulation_success_rate();
+
+        // roll 高于成功率 → 失败；death_roll 高于身死概率 → 重伤
+        let outcome = disciple.resolve_tribulation(0.99, 0.99, &[]);
+
+        assert_eq!(outcome, TribulationOutcome::Injured);
+        assert!(disciple.is_alive());
+        assert_eq!(disciple.cultivation.current_level, CultivationLevel::Foundation);
+        assert!(disciple.constitution < 100);
+        assert!((disciple.tribulation_success_rate() - rate_before * 0.7).abs() < 1e-4);
+
+        // debuff 数回合后消失
+        for _ in 0..TRIBULATION_INJURY_DURATION {
+            disciple.tick_modifiers();
+        }
+        assert!((disciple.tribulation_success_rate() - rate_before).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_injury_debuff_and_heal() {
+        let mut disciple = Disciple::new(1, "韩立".to_string(), DiscipleType::Inner, vec![]);
+        disciple.add_injury(InjurySeverity::Minor);
+        disciple.add_injury(InjurySeverity::Serious);
+
+        let reward = disciple.modifiers.calculate_effective(&ModifierTarget::TaskReward, 100.0);
+        assert!((reward - 100.0 * 0.9 * 0.75).abs() < 1e-3);
+
+        let healed = disciple.heal_worst_injury().unwrap();
+        assert_eq!(healed.severity, InjurySeverity::Serious);
+        assert_eq!(disciple.injuries.len(), 1);
+
+        disciple.heal_all_injuries();
+        assert!(!disciple.is_injured());
+        let reward = disciple.modifiers.calculate_effective(&ModifierTarget::TaskReward, 100.0);
+        assert!((reward - 100.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_exploration_injury_threshold() {
+        assert_eq!(exploration_injury(20, 0.0), None);
+        assert_eq!(exploration_injury(30, 0.1), Some(InjurySeverity::Minor));
+        assert_eq!(exploration_injury(30, 0.2), None);
+        assert_eq!(exploration_injury(80, 0.3), Some(InjurySeverity::Serious));
+    }
+
+    #[test]
+    fn test_belligerent_personality_boosts_combat_reward() {
+        use crate::task::{CombatTask, GatheringTask};
+
+        let combat = Task::new(1, "斩妖".to_string(), TaskType::Combat(CombatTask {
+            enemy_id: None,
+            enemy_name: "妖狼".to_string(),
+            enemy_level: 0,
+            difficulty: 20,
+        }), 20, 0);
+        let gathering = Task::new(2, "采药".to_string(), TaskType::Gathering(GatheringTask {
+            resource_type: "草药".to_string(),
+            difficulty: 20,
+        }), 20, 0);
+
+        let mut calm = Disciple::new(1, "韩立".to_string(), DiscipleType::Inner, vec![]);
+        let mut fierce = Disciple::new(2, "厉飞雨".to_string(), DiscipleType::Inner, vec![]);
+        fierce.set_personality(Personality::Belligerent);
+        let mut rng = rand::thread_rng();
+
+        assert!(fierce.complete_task(&combat, &mut rng) > calm.complete_task(&combat, &mut rng));
+        assert_eq!(fierce.complete_task(&gathering, &mut rng), calm.complete_task(&gathering, &mut rng));
+
+        fierce.apply_dao_heart_impact(-10);
+        calm.apply_dao_heart_impact(-10);
+        assert!(fierce.dao_heart < calm.dao_heart);
+    }
+
+    #[test]
+    fn test_tribulation_death_leaves_heritage() {
+        let mut sect = Sect::new("测试宗".to_string());
+        sect.recruit_disciple(tribulation_ready_disciple(CultivationLevel::NascentSoul));
+        let id = sect.disciples[0].id;
+
+        let outcome = sect.disciples[0].resolve_tribulation(0.99, 0.0, &[]);
+        assert_eq!(outcome, TribulationOutcome::Died);
+        assert!(!sect.disciples[0].is_alive());
+
+        sect.handle_disciple_death(id, DepartureCause::Tribulation);
+        assert_eq!(sect.heritages.len(), 1);
+        assert_eq!(sect.heritages[0].level, CultivationLevel::NascentSoul);
+    }
+
+    #[test]
+    fn test_pill_limit_and_side_effects() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut disciple = Disciple::new(1, "韩立".to_string(), DiscipleType::Inner, vec![]);
+
+        // 上限以内每颗修炼丹都增加修为
+        for _ in 0..MAX_PILLS_PER_TURN {
+            let progress_before = disciple.cultivation.progress;
+            let outcome = disciple.take_pill(PillType::CultivationBoost, PillGrade::Low, &mut rng);
+            assert!(!outcome.overdosed);
+            assert_eq!(disciple.cultivation.progress, progress_before + 10);
+        }
+
+        // 超过上限后不再增加修为，且必定触发副作用
+        let progress_before = disciple.cultivation.progress;
+        let outcome = disciple.take_pill(PillType::CultivationBoost, PillGrade::Low, &mut rng);
+        assert!(outcome.overdosed);
+        assert_eq!(outcome.side_effect, Some(PillSideEffect::CultivationSlowdown));
+        assert_eq!(disciple.cultivation.progress, progress_before);
+        assert!(disciple.get_effective_cultivation_speed() < 1.0);
+        assert_eq!(outcome.poisoned, disciple.is_injured());
+
+        // 副作用不叠加
+        disciple.take_pill(PillType::CultivationBoost, PillGrade::Low, &mut rng);
+        assert!((disciple.get_effective_cultivation_speed() - 0.8).abs() < 1e-6);
+
+        // 新回合重置服药次数
+        disciple.natural_recovery(&[]);
+        assert!(disciple.take_pill(PillType::QiRecovery, PillGrade::Low, &mut rng).side_effect.is_none());
+        assert!(!disciple.take_pill(PillType::CultivationBoost, PillGrade::Low, &mut rng).overdosed);
+    }
+
+    #[test]
+    fn test_recovery_scales_with_level_and_spirit_array() {
+        use crate::building::BuildingTree;
+        use crate::config::BuildingsConfig;
+
+        let mut novice = Disciple::new(1, "韩立".to_string(), DiscipleType::Outer, vec![]);
+        let mut elder = Disciple::new(2, "南宫婉".to_string(), DiscipleType::Inner, vec![]);
+        elder.cultivation.current_level = CultivationLevel::SpiritSevering;
+        for d in [&mut novice, &mut elder] {
+            d.energy = 50;
+            d.constitution = 50;
+        }
+
+        novice.natural_recovery(&[]);
+        elder.natural_recovery(&[]);
+        assert_eq!((novice.energy, novice.constitution), (55, 52));
+        assert_eq!((elder.energy, elder.constitution), (62, 56));
+
+        // 聚灵阵建成后精力恢复提升五成
+        let mut sect = Sect::new("测试宗".to_string());
+        let mut tree = BuildingTree::from_config(&BuildingsConfig::default_config());
+        tree.buildings.get_mut("spirit_array").unwrap().is_built = true;
+        sect.init_building_tree(tree);
+        let modifiers = sect.get_applicable_modifiers_owned(&elder);
+        let modifiers: Vec<&Modifier> = modifiers.iter().collect();
+        elder.natural_recovery(&modifiers);
+        assert_eq!((elder.energy, elder.constitution), (80, 62));
+    }
+
+    #[test]
+    fn test_bottleneck_lowers_tribulation_rate_until_broken() {
+        use crate::task::Task;
+        use rand::SeedableRng;
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        let mut disciple = tribulation_ready_disciple(CultivationLevel::Foundation);
+        let normal_rate = disciple.tribulation_success_rate();
+
+        disciple.cultivation.bottleneck = true;
+        assert!(disciple.cultivation.can_tribulate());
+        let blocked_rate = disciple.tribulation_success_rate();
+        assert!((blocked_rate - normal_rate * BOTTLENECK_TRIBULATION_PENALTY).abs() < 1e-6);
+
+        // 完成感悟天道破除瓶颈
+        let insight = Task::bottleneck_insight(1);
+        assert!(insight.is_suitable_for_disciple(&disciple));
+        disciple.complete_task(&insight, &mut rng);
+        assert!(!disciple.cultivation.bottleneck);
+        assert!(!insight.is_suitable_for_disciple(&disciple));
+        assert!((disciple.tribulation_success_rate() - normal_rate).abs() < 1e-6);
+
+        // 破障丹同样可以破除瓶颈
+        disciple.cultivation.bottleneck = true;
+        assert!(disciple.take_pill(PillType::BottleneckBreaker, PillGrade::Low, &mut rng).bottleneck_broken);
+        assert!(!disciple.cultivation.bottleneck);
+    }
+
+    #[test]
+    fn test_fire_root_disciple_profits_more_from_flame_realm() {
+        use crate::task::ExplorationTask;
+
+        let realm_task = |danger_level| {
+            Task::new(1, "探索火焰秘境".to_string(), TaskType::Exploration(ExplorationTask {
+                location: "火焰秘境".to_string(),
+                danger_level,
+                realm_type: Some(TalentType::Fire),
+                depth: 0,
+            }), 50, 0)
+        };
+        let with_root = |talent_type| {
+            let mut disciple = Disciple::new(1, "萧炎".to_string(), DiscipleType::Inner, vec![Talent { talent_type, level: 5 }]);
+            disciple.cultivation.current_level = CultivationLevel::NascentSoul;
+            disciple
+        };
+        let fire = with_root(TalentType::Fire);
+        let water = with_root(TalentType::Water);
+
+        let task = realm_task(20);
+        assert!(fire.estimate_task_reward(&task, &[]) > water.estimate_task_reward(&task, &[]));
+
+        // 灵根不符时危险度放大：元婴(3级)可应对30，但应对不了30×1.5
+        let dangerous = realm_task(30);
+        assert!(dangerous.is_suitable_for_disciple(&fire));
+        assert!(!dangerous.is_suitable_for_disciple(&water));
+    }
+
+    #[test]
+    fn test_longevity_pill_extends_lifespan_up_to_level_cap() {
+        use rand::SeedableRng;
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+        let mut disciple = Disciple::new(1, "彭祖".to_string(), DiscipleType::Inner, vec![]);
+        let cap = CultivationLevel::QiRefining.max_lifespan();
+        assert_eq!(disciple.lifespan, 150);
+
+        disciple.take_pill(PillType::LongevityPill, PillGrade::Low, &mut rng);
+        assert_eq!(disciple.lifespan, 170);
+        disciple.take_pill(PillType::LongevityPill, PillGrade::High, &mut rng);
+        assert_eq!(disciple.lifespan, 210);
+
+        // 再多的丹药也突破不了练气期的寿元上限
+        disciple.pills_taken_this_turn = 0;
+        for _ in 0..3 {
+            disciple.take_pill(PillType::LongevityPill, PillGrade::High, &mut rng);
+        }
+        assert_eq!(disciple.lifespan, cap);
+
+        // 道心坚定者突破时额外增寿，同样受上限约束
+        let mut steadfast = tribulation_ready_disciple(CultivationLevel::Foundation);
+        steadfast.dao_heart = 100;
+        assert_eq!(steadfast.resolve_tribulation(0.0, 1.0, &[]), TribulationOutcome::Success);
+        assert_eq!(steadfast.lifespan, 600);
+        assert!(steadfast.lifespan <= CultivationLevel::GoldenCore.max_lifespan());
+    }
+
+    #[test]
+    fn test_repeated_combat_grows_sword_talent_and_combat_reward() {
+        use crate::task::CombatTask;
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let combat = Task::new(1, "斩妖".to_string(), TaskType::Combat(CombatTask {
+            enemy_id: None,
+            enemy_name: "妖狼".to_string(),
+            enemy_level: 0,
+            difficulty: 20,
+        }), 20, 0);
+        let sword = vec![Talent { talent_type: TalentType::Sword, level: 1 }];
+        let novice = Disciple::new(1, "李慕白".to_string(), DiscipleType::Inner, sword);
+        let mut veteran = novice.clone();
+
+        let mut rng = StdRng::seed_from_u64(11);
+        for _ in 0..300 {
+            veteran.complete_task(&combat, &mut rng);
+        }
+        let level = veteran.talent_level(&TalentType::Sword);
+        assert!(level > 1 && level <= TALENT_MAX_LEVEL);
+
+        // 只比较灵根差异：成长后的剑道天赋带来更高的战斗修为
+        let mut trained = novice.clone();
+        trained.talents = veteran.talents.clone();
+        assert!(trained.estimate_task_reward(&combat, &[]) > novice.estimate_task_reward(&combat, &[]));
+
+        // 满级不再成长；没有灵根时小概率觉醒一级新灵根
+        trained.talents = vec![Talent { talent_type: TalentType::Sword, level: TALENT_MAX_LEVEL }];
+        assert_eq!(trained.resolve_talent_growth(TalentType::Sword, 0.0), None);
+        assert_eq!(trained.resolve_talent_growth(TalentType::Fire, TALENT_AWAKEN_CHANCE), None);
+        assert_eq!(trained.resolve_talent_growth(TalentType::Fire, 0.0), Some(1));
+        assert_eq!(trained.talent_level(&TalentType::Fire), 1);
+    }
+
+    #[test]
+    fn test_low_energy_lowers_rewards_and_only_prolonged_exhaustion_costs_lifespan() {
+        use crate::task::GatheringTask;
+
+        let mut disciple = Disciple::new(1, "石昊".to_string(), DiscipleType::Outer, vec![]);
+        let task = Task::new(1, "采集灵草".to_string(), TaskType::Gathering(GatheringTask {
+            resource_type: "灵草".to_string(),
+            difficulty: 10,
+        }), 30, 0);
+        let rested_reward = disciple.estimate_task_reward(&task, &[]);
+        disciple.energy = LOW_ENERGY_THRESHOLD - 1;
+        assert!(disciple.estimate_task_reward(&task, &[]) < rested_reward);
+
+        // 精力归零不再立刻扣寿，而是下回合强制休息
+        let lifespan = disciple.lifespan;
+        disciple.consume_energy(200);
+        assert_eq!((disciple.energy, disciple.lifespan), (0, lifespan));
+        disciple.natural_recovery(&[]);
+        assert!(disciple.is_exhausted());
+        assert!(!task.is_suitable_for_disciple(&disciple));
+        assert_eq!(disciple.lifespan, lifespan);
+
+        // 连续多回合归零才折损寿元
+        for _ in 1..EXHAUSTION_LIFESPAN_TURNS {
+            disciple.consume_energy(200);
+            disciple.natural_recovery(&[]);
+        }
+        assert_eq!(disciple.exhausted_turns, EXHAUSTION_LIFESPAN_TURNS);
+        assert_eq!(disciple.lifespan, lifespan - 1);
+
+        // 休整一回合后恢复正常
+        disciple.natural_recovery(&[]);
+        assert!(!disciple.is_exhausted());
+        assert!(task.is_suitable_for_disciple(&disciple));
+    }
+
+    #[test]
+    fn test_bottleneck_breaker_raises_tribulation_rate_for_a_few_turns() {
+        use rand::SeedableRng;
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(11);
+        let mut disciple = tribulation_ready_disciple(CultivationLevel::Foundation);
+        disciple.dao_heart = 40;
+        let rate_before = disciple.tribulation_success_rate();
+
+        let outcome = disciple.take_pill(PillType::BottleneckBreaker, PillGrade::Low, &mut rng);
+        assert_eq!(outcome.buff, Some(PillBuff::TribulationInsight));
+
+        // 药力持续期间渡劫成功率提升（即使心浮气躁也抵不过破障明悟）
+        for _ in 0..PILL_BUFF_DURATION {
+            assert!(disciple.tribulation_success_rate() > rate_before + 0.04);
+            disciple.tick_modifiers();
+        }
+
+        // 药力散去后回落
+        assert!((disciple.tribulation_success_rate() - rate_before).abs() < 1e-6);
+        assert!(disciple.modifiers.get_all_modifiers().is_empty());
+
+        // 凝神丹临时提升道心，重复服用只刷新不叠加
+        let dao_heart = |d: &Disciple| d.modifiers.calculate_effective(&ModifierTarget::DaoHeart, d.dao_heart as f32);
+        let before = dao_heart(&disciple);
+        disciple.take_pill(PillType::FocusPill, PillGrade::Low, &mut rng);
+        disciple.take_pill(PillType::FocusPill, PillGrade::Low, &mut rng);
+        assert!((dao_heart(&disciple) - before - 10.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,146 @@
+use serde::{Deserialize, Serialize};
+use crate::modifier::{Modifier, ModifierApplication, ModifierSource, ModifierTarget};
+
+/// 炼制装备所需的建筑
+pub const EQUIPMENT_BUILDING_ID: &str = "weapon_forge";
+
+/// 装备槽位
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum EquipmentSlot {
+    Weapon,   // 武器
+    Armor,    // 护甲
+    Artifact, // 法宝
+}
+
+impl EquipmentSlot {
+    pub const ALL: [EquipmentSlot; 3] = [Self::Weapon, Self::Armor, Self::Artifact];
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "Weapon" => Some(Self::Weapon),
+            "Armor" => Some(Self::Armor),
+            "Artifact" => Some(Self::Artifact),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Weapon => "武器",
+            Self::Armor => "护甲",
+            Self::Artifact => "法宝",
+        }
+    }
+}
+
+/// 装备（由炼器坊炼制）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Equipment {
+    pub id: String,
+    pub name: String,
+    pub slot: EquipmentSlot,
+    pub cost: u32,                // 炼制消耗的资源
+    pub modifiers: Vec<Modifier>, // 装备提供的modifier
+}
+
+impl Equipment {
+    fn new(id: &str, name: &str, slot: EquipmentSlot, cost: u32, effects: Vec<(ModifierTarget, ModifierApplication)>) -> Self {
+        let modifiers = effects
+            .into_iter()
+            .map(|(target, application)| Modifier::new(name, target, application, ModifierSource::Equipment))
+            .collect();
+        Self {
+            id: id.to_string(),
+            name: name.to_string(),
+            slot,
+            cost,
+            modifiers,
+        }
+    }
+
+    /// 炼器坊可炼制的装备
+    pub fn all_equipment() -> Vec<Equipment> {
+        vec![
+            // 青锋剑：可应对更危险的探索
+            Equipment::new(
+                "azure_blade",
+                "青锋剑",
+                EquipmentSlot::Weapon,
+                80,
+                vec![(ModifierTarget::TaskSuitability, ModifierApplication::Additive(1.0))],
+            ),
+            // 赤霄剑：上品飞剑
+            Equipment::new(
+                "crimson_sky_sword",
+                "赤霄剑",
+                EquipmentSlot::Weapon,
+                200,
+                vec![
+                    (ModifierTarget::TaskSuitability, ModifierApplication::Additive(2.0)),
+                    (ModifierTarget::TaskReward, ModifierApplication::Multiplicative(0.1)),
+                ],
+            ),
+            // 玄铁甲：减少任务体魄消耗
+            Equipment::new(
+                "dark_iron_armor",
+                "玄铁甲",
+                EquipmentSlot::Armor,
+                100,
+                vec![(ModifierTarget::ConstitutionConsumption, ModifierApplication::Multiplicative(-0.25))],
+            ),
+            // 聚灵珠：加快精力恢复
+            Equipment::new(
+                "spirit_pearl",
+                "聚灵珠",
+                EquipmentSlot::Artifact,
+                120,
+                vec![(ModifierTarget::EnergyRecovery, ModifierApplication::Multiplicative(0.5))],
+            ),
+        ]
+    }
+
+    /// 根据ID获取装备
+    pub fn find(id: &str) -> Option<Equipment> {
+        Self::all_equipment().into_iter().find(|e| e.id == id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disciple::{Disciple, DiscipleType};
+    use crate::task::{ExplorationTask, Task, TaskType};
+
+    #[test]
+    fn test_equipment_raises_suitability_and_swap_drops_old_bonus() {
+        let mut disciple = Disciple::new(1, "石昊".to_string(), DiscipleType::Inner, vec![]);
+        let exploration = |danger_level| {
+            Task::new(1, "探索".to_string(), TaskType::Exploration(ExplorationTask {
+                location: "荒域".to_string(),
+                danger_level,
+                realm_type: None,
+                depth: 0,
+            }), 10, 0)
+        };
+        assert!(!exploration(10).is_suitable_for_disciple(&disciple));
+
+        disciple.equip(Equipment::find("azure_blade").unwrap());
+        assert!(exploration(10).is_suitable_for_disciple(&disciple));
+        assert!(!exploration(20).is_suitable_for_disciple(&disciple));
+
+        // 同槽位换装：旧剑加成消失，只保留新剑
+        let previous = disciple.equip(Equipment::find("crimson_sky_sword").unwrap());
+        assert_eq!(previous.unwrap().id, "azure_blade");
+        assert!(exploration(20).is_suitable_for_disciple(&disciple));
+        assert!(!exploration(30).is_suitable_for_disciple(&disciple));
+
+        // 其他槽位不受影响
+        disciple.equip(Equipment::find("dark_iron_armor").unwrap());
+        assert!(exploration(20).is_suitable_for_disciple(&disciple));
+
+        let removed = disciple.unequip(EquipmentSlot::Weapon).unwrap();
+        assert_eq!(removed.id, "crimson_sky_sword");
+        assert!(!exploration(10).is_suitable_for_disciple(&disciple));
+        assert!(disciple.equipment.contains_key(&EquipmentSlot::Armor));
+    }
+}
@@ -1,9 +1,13 @@
-use crate::disciple::{Disciple, DiscipleType, Talent, TalentType};
-use crate::sect::Sect;
+use crate::cultivation::CultivationLevel;
+use crate::disciple::{Disciple, DiscipleType, Heritage, Personality, Talent, TalentType};
+use crate::sect::{DepartureCause, Sect};
 use crate::task::Task;
 use crate::relationship::{RelationDimension, RelationLevel};
 use rand::Rng;
 
+/// 负声望宗门招到道心偏低弟子的概率
+const LOW_REPUTATION_WEAK_DAO_HEART_CHANCE: f64 = 0.5;
+
 /// 游戏事件
 #[derive(Debug, Clone)]
 pub enum GameEvent {
@@ -12,7 +16,7 @@ pub enum GameEvent {
     DiscipleRecruited(usize),      // 收徒
     DiscipleBreakthrough(usize),   // 弟子突破
     DiscipleTribulation(usize, bool), // 弟子渡劫 (弟子ID, 是否成功)
-    DiscipleDeath(usize),          // 弟子死亡
+    DiscipleDeath(usize, DepartureCause), // 弟子死亡 (弟子ID, 死因)
     YearlyIncome(u32),             // 年度收入
     MapUpdate,                     // 地图更新
     ChildBorn(usize, usize),       // 子女出生 (父母ID)
@@ -35,11 +39,59 @@ pub struct TaskResult {
     pub task_id: usize,
     pub disciple_id: usize,
     pub disciple_name: String,
-    pub success: bool,
+    pub success: bool,          // 是否完全成功（outcome 为 Success）
+    pub outcome: TaskOutcome,
+    pub completion: f32,        // 完成度（0~1，部分成功时按此折算奖励）
     pub resources_gained: u32,
+    pub contribution_gained: u32, // 获得的贡献点
     pub reputation_gained: i32,
     pub progress_gained: u32,
     pub disciple_died: bool,  // 弟子是否死亡（战斗任务失败）
+    pub combat: Option<CombatDetail>, // 战斗细节（仅战斗任务）
+    pub team_size: usize,     // 组队人数
+    pub synergy_bonus: f32,   // 组队协同加成（成功率和资源奖励）
+    pub drops: Vec<crate::map::MonsterDrop>, // 讨伐妖魔的掉落
+}
+
+/// 任务结算结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskOutcome {
+    Success,        // 成功
+    PartialSuccess, // 部分成功：按完成度获得部分奖励，任务可重试
+    Failure,        // 完全失败：战斗惨败，可能负伤陨落
+}
+
+impl TaskOutcome {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Success => "成功",
+            Self::PartialSuccess => "部分成功",
+            Self::Failure => "完全失败",
+        }
+    }
+}
+
+/// 战斗细节
+#[derive(Debug, Clone)]
+pub struct CombatDetail {
+    pub disciple_level: u32,     // 弟子战斗等级
+    pub enemy_level: u32,        // 敌人有效等级
+    pub success_rate: f32,       // 胜率
+    pub reward_multiplier: f32,  // 胜利奖励倍率
+    pub constitution_lost: u32,  // 战斗失败损失的体魄
+}
+
+impl CombatDetail {
+    /// 根据弟子和战斗任务计算战斗细节
+    pub fn new(disciple: &Disciple, combat: &crate::task::CombatTask) -> Self {
+        Self {
+            disciple_level: Task::calculate_disciple_combat_level(disciple),
+            enemy_level: crate::task::combat_enemy_level(combat),
+            success_rate: crate::task::combat_success_rate(disciple, combat),
+            reward_multiplier: crate::task::combat_reward_multiplier(disciple, combat),
+            constitution_lost: 0,
+        }
+    }
 }
 
 /// 事件系统
@@ -72,12 +124,12 @@ impl EventSystem {
                 GameEvent::DiscipleTribulation(id, success) => {
                     self.handle_tribulation(sect, id, success);
                 }
-                GameEvent::DiscipleDeath(id) => {
-                    sect.handle_disciple_death(id);
+                GameEvent::DiscipleDeath(id, cause) => {
+                    sect.handle_disciple_death(id, cause);
                 }
                 GameEvent::YearlyIncome(amount) => {
-                    sect.add_resources(amount);
-                    println!("年度收入：{} 资源", amount);
+                    let credited = Self::credit_spirit_stones(sect, amount);
+                    println!("年度收入：{} 资源", credited);
                 }
                 GameEvent::DiscipleRecruited(id) => {
                     if let Some(disciple) = sect.disciples.iter().find(|d| d.id == id) {
@@ -89,16 +141,27 @@ impl EventSystem {
         }
     }
 
+    /// 灵石入库，库房已满时提示散失的数量，返回实际入账的灵石
+    fn credit_spirit_stones(sect: &mut Sect, amount: u32) -> u32 {
+        let wasted = sect.add_spirit_stones(amount);
+        if wasted > 0 {
+            println!("库房已满（上限{}），{} 灵石无处存放而散失", sect.spirit_stone_capacity(), wasted);
+        }
+        amount - wasted
+    }
+
     fn handle_task_completed(&self, sect: &mut Sect, result: TaskResult) {
         if result.success {
-            sect.add_resources(result.resources_gained);
+            let credited = Self::credit_spirit_stones(sect, result.resources_gained);
+            sect.add_contribution(result.contribution_gained);
             sect.add_reputation(result.reputation_gained);
 
             if let Some(disciple) = sect.disciples.iter().find(|d| d.id == result.disciple_id) {
                 println!(
-                    "{}完成任务，获得 {} 资源，{} 声望，{} 修为进度",
+                    "{}完成任务，获得 {} 灵石，{} 贡献点，{} 声望，{} 修为进度",
                     disciple.name,
-                    result.resources_gained,
+                    credited,
+                    result.contribution_gained,
                     result.reputation_gained,
                     result.progress_gained
                 );
@@ -122,18 +185,24 @@ impl EventSystem {
                     "{}成功渡劫，晋升至{}期！",
                     disciple.name, disciple.cultivation.current_level
                 );
+            } else if disciple.is_alive() {
+                println!("{}渡劫失败，身受重伤...", disciple.name);
             } else {
                 println!("{}渡劫失败，身死道消...", disciple.name);
-                self.add_event_later(GameEvent::DiscipleDeath(disciple_id));
             }
         }
     }
-
-    fn add_event_later(&self, _event: GameEvent) {
-        // 这里可以添加延迟事件处理
-    }
 }
 
+/// 每回合招募池中的候选人数
+pub const RECRUITMENT_POOL_SIZE: usize = 3;
+/// 招募的基础价格
+pub const RECRUITMENT_BASE_PRICE: u32 = 300;
+/// 每点潜力增加的招募价格
+pub const RECRUITMENT_PRICE_PER_POTENTIAL: u32 = 10;
+/// 刷新招募池的花费
+pub const RECRUITMENT_POOL_REFRESH_COST: u32 = 100;
+
 /// 招募系统
 pub struct RecruitmentSystem {
     next_disciple_id: usize,
@@ -146,10 +215,41 @@ impl RecruitmentSystem {
         }
     }
 
-    /// 随机生成弟子
-    pub fn generate_random_disciple(&mut self) -> Disciple {
-        let mut rng = rand::thread_rng();
+    /// 从存档恢复（指定下一个弟子ID）
+    pub fn with_next_id(next_disciple_id: usize) -> Self {
+        Self { next_disciple_id }
+    }
+
+    /// 下一个弟子ID
+    pub fn next_disciple_id(&self) -> usize {
+        self.next_disciple_id
+    }
+
+    /// 分配一个新的弟子ID
+    pub fn allocate_id(&mut self) -> usize {
+        let id = self.next_disciple_id;
+        self.next_disciple_id += 1;
+        id
+    }
+
+    /// 声望带来的资质等级加成（每50点声望+1，负声望降低资质）
+    pub fn talent_level_bonus(reputation: i32) -> i32 {
+        (reputation / 50).clamp(-2, 3)
+    }
 
+    /// 新弟子自带家传的概率
+    pub fn heritage_chance(reputation: i32) -> f64 {
+        if reputation > 100 {
+            0.15
+        } else if reputation > 50 {
+            0.05
+        } else {
+            0.0
+        }
+    }
+
+    /// 随机生成弟子（宗门声望影响资质、家传与道心）
+    pub fn generate_random_disciple<R: Rng>(&mut self, reputation: i32, rng: &mut R) -> Disciple {
         let names = vec![
             "张三", "李四", "王五", "赵六", "陈七", "林八", "周九", "吴十",
             "云飞扬", "剑无心", "莫问天", "风清扬", "叶孤城", "独孤求败",
@@ -175,7 +275,7 @@ impl RecruitmentSystem {
 
         for _ in 0..num_talents {
             let talent_type = all_talents[rng.gen_range(0..all_talents.len())].clone();
-            let level = rng.gen_range(1..8); // 1-7的资质等级
+            let level = (rng.gen_range(1..8) + Self::talent_level_bonus(reputation)).clamp(1, 10) as u32; // 基础1-7的资质等级
 
             talents.push(Talent {
                 talent_type,
@@ -190,16 +290,56 @@ impl RecruitmentSystem {
             _ => DiscipleType::Personal,
         };
 
-        let id = self.next_disciple_id;
-        self.next_disciple_id += 1;
+        let id = self.allocate_id();
+
+        let mut disciple = Disciple::new(id, name, disciple_type, talents);
+        // 随机性格
+        disciple.set_personality(Personality::ALL[rng.gen_range(0..Personality::ALL.len())]);
 
-        Disciple::new(id, name, disciple_type, talents)
+        // 名门大派更容易吸引身负家传的弟子
+        if rng.gen_bool(Self::heritage_chance(reputation)) {
+            disciple.heritage = Some(Heritage {
+                name: format!("{}家传", disciple.name),
+                level: CultivationLevel::Foundation,
+                tribulation_bonus: 0.05,
+                talent: None,
+            });
+        }
+
+        // 声名狼藉的宗门只能招到心性不坚之人
+        if reputation < 0 && rng.gen_bool(LOW_REPUTATION_WEAK_DAO_HEART_CHANCE) {
+            disciple.dao_heart = disciple.dao_heart.saturating_sub(rng.gen_range(10..=30));
+        }
+        disciple
     }
 
-    /// 尝试招募弟子
-    pub fn try_recruit(&mut self, sect: &Sect) -> Option<Disciple> {
-        let mut rng = rand::thread_rng();
+    /// 生成一批候选弟子组成招募池
+    pub fn generate_pool<R: Rng>(&mut self, reputation: i32, rng: &mut R) -> Vec<Disciple> {
+        (0..RECRUITMENT_POOL_SIZE)
+            .map(|_| self.generate_random_disciple(reputation, rng))
+            .collect()
+    }
+
+    /// 候选弟子的预估潜力（0-100）：最高资质、灵根数量、家传和身份共同决定
+    pub fn candidate_potential(disciple: &Disciple) -> u32 {
+        let best_talent = disciple.talents.iter().map(|t| t.level).max().unwrap_or(0);
+        let extra_talents = disciple.talents.len().saturating_sub(1) as u32;
+        let heritage = if disciple.heritage.is_some() { 15 } else { 0 };
+        let status = match disciple.disciple_type {
+            DiscipleType::Outer => 0,
+            DiscipleType::Inner => 5,
+            DiscipleType::Personal => 15,
+        };
+        (best_talent * 6 + extra_talents * 5 + heritage + status).min(100)
+    }
 
+    /// 候选弟子的招募价格（按潜力定价）
+    pub fn candidate_price(disciple: &Disciple) -> u32 {
+        RECRUITMENT_BASE_PRICE + Self::candidate_potential(disciple) * RECRUITMENT_PRICE_PER_POTENTIAL
+    }
+
+    /// 尝试招募弟子
+    pub fn try_recruit<R: Rng>(&mut self, sect: &Sect, rng: &mut R) -> Option<Disciple> {
         // 根据声望决定招募概率（低概率，使招募成为稀有事件）
         let recruit_chance = if sect.reputation > 100 {
             0.15  // 15% - 约每7回合一次
@@ -212,7 +352,7 @@ impl RecruitmentSystem {
         };
 
         if rng.gen_bool(recruit_chance) {
-            Some(self.generate_random_disciple())
+            Some(self.generate_random_disciple(sect.reputation, rng))
         } else {
             None
         }
@@ -228,3 +368,35 @@ impl Disciple {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_reputation_raises_average_talent_level() {
+        let mut rng = StdRng::seed_from_u64(31);
+        let mut recruitment = RecruitmentSystem::new();
+        let mut sample = |reputation: i32| {
+            let disciples: Vec<Disciple> = (0..500)
+                .map(|_| recruitment.generate_random_disciple(reputation, &mut rng))
+                .collect();
+            let levels: Vec<u32> = disciples.iter().flat_map(|d| d.talents.iter().map(|t| t.level)).collect();
+            let average = levels.iter().sum::<u32>() as f32 / levels.len() as f32;
+            let heritages = disciples.iter().filter(|d| d.heritage.is_some()).count();
+            let weak_hearts = disciples.iter().filter(|d| d.dao_heart < 50).count();
+            (average, heritages, weak_hearts)
+        };
+
+        let (famous_avg, famous_heritages, famous_weak) = sample(200);
+        let (infamous_avg, infamous_heritages, infamous_weak) = sample(-100);
+
+        assert!(famous_avg - infamous_avg > 3.0, "{} vs {}", famous_avg, infamous_avg);
+        assert!(famous_heritages > 0);
+        assert_eq!(infamous_heritages, 0);
+        assert_eq!(famous_weak, 0);
+        assert!(infamous_weak > 100);
+    }
+}
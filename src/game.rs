@@ -1,8 +1,105 @@
-use crate::event::{EventSystem, GameEvent, RecruitmentSystem, TaskResult};
+use crate::cultivation::CultivationLevel;
+use crate::disciple::{exploration_injury, InjurySeverity, TalentType, TribulationOutcome, COMBAT_INJURY_CHANCE};
+use crate::event::{CombatDetail, EventSystem, GameEvent, RecruitmentSystem, TaskOutcome, TaskResult};
 use crate::map::GameMap;
-use crate::sect::Sect;
-use crate::task::Task;
-use rand::Rng;
+use crate::sect::{DepartureCause, Sect};
+use crate::task::{Task, TaskType};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// 战斗失败损失的体魄
+const COMBAT_DEFEAT_CONSTITUTION_LOSS: u32 = 40;
+
+/// 年龄分段（上界不含，最后一段不设上限）
+pub const AGE_BRACKETS: [(u32, &str); 5] = [
+    (30, "<30"),
+    (60, "30-59"),
+    (100, "60-99"),
+    (200, "100-199"),
+    (u32::MAX, "200+"),
+];
+
+/// 模拟报告中的一年
+#[derive(Debug, Clone, PartialEq)]
+pub struct YearRecord {
+    pub year: u32,
+    pub population: u32,
+    pub age_distribution: [u32; AGE_BRACKETS.len()],         // 按 AGE_BRACKETS 分段的人数
+    pub level_counts: [u32; CultivationLevel::ALL.len()], // 按 CultivationLevel::ALL 排列的人数
+    pub births: u32,
+    pub deaths: u32,
+}
+
+/// 自动模式的时间序列报告，用于分析年龄结构与代际更替
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SimulationReport {
+    pub years: Vec<YearRecord>,
+    pub peak_population: u32,
+    pub peak_population_year: u32,
+    pub destroyed_year: Option<u32>,                       // 灭门年份
+    pub highest_level: Option<(CultivationLevel, u32)>, // 最高修为及其首次达成年份
+}
+
+impl SimulationReport {
+    /// 记录一年的数据并更新峰值等关键指标
+    fn push(&mut self, record: YearRecord, destroyed: bool) {
+        if record.population > self.peak_population {
+            self.peak_population = record.population;
+            self.peak_population_year = record.year;
+        }
+        if destroyed && self.destroyed_year.is_none() {
+            self.destroyed_year = Some(record.year);
+        }
+        let highest = CultivationLevel::ALL
+            .iter()
+            .zip(record.level_counts)
+            .filter(|(_, count)| *count > 0)
+            .map(|(level, _)| *level)
+            .next_back();
+        if let Some(level) = highest {
+            if self.highest_level.map_or(true, |(best, _)| level > best) {
+                self.highest_level = Some((level, record.year));
+            }
+        }
+        self.years.push(record);
+    }
+
+    /// 导出为 CSV（每年一行）
+    pub fn to_csv(&self) -> String {
+        let mut header = vec!["year".to_string(), "population".to_string(), "births".to_string(), "deaths".to_string()];
+        header.extend(AGE_BRACKETS.iter().map(|(_, label)| format!("age {}", label)));
+        header.extend(CultivationLevel::ALL.iter().map(|level| level.to_string()));
+
+        let mut lines = vec![header.join(",")];
+        for record in &self.years {
+            let mut row = vec![record.year, record.population, record.births, record.deaths];
+            row.extend(record.age_distribution);
+            row.extend(record.level_counts);
+            lines.push(row.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(","));
+        }
+        lines.join("\n") + "\n"
+    }
+
+    /// 关键指标摘要
+    pub fn summary(&self) -> String {
+        let destroyed = match self.destroyed_year {
+            Some(year) => format!("第{}年灭门", year),
+            None => "未灭门".to_string(),
+        };
+        let highest = match self.highest_level {
+            Some((level, year)) => format!("{}（第{}年达成）", level, year),
+            None => "无".to_string(),
+        };
+        format!(
+            "模拟{}年，人口峰值{}人（第{}年），{}，最高修为：{}",
+            self.years.len(),
+            self.peak_population,
+            self.peak_population_year,
+            destroyed,
+            highest
+        )
+    }
+}
 
 /// 游戏状态
 #[derive(Debug, PartialEq)]
@@ -19,12 +116,18 @@ pub struct Game {
     pub event_system: EventSystem,
     pub recruitment_system: RecruitmentSystem,
     pub state: GameState,
+    rng: StdRng, // 所有随机事件共用，相同种子可复现整局
 }
 
 impl Game {
-    pub fn new(sect_name: String) -> Self {
+    /// 创建游戏，指定种子时结果可复现
+    pub fn new(sect_name: String, seed: Option<u64>) -> Self {
+        let mut rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
         let mut map = GameMap::new();
-        map.initialize();
+        map.initialize(&mut rng);
 
         let mut game = Self {
             sect: Sect::new(sect_name),
@@ -32,11 +135,12 @@ impl Game {
             event_system: EventSystem::new(),
             recruitment_system: RecruitmentSystem::new(),
             state: GameState::Running,
+            rng,
         };
 
         // 初始招募几个弟子
         for _ in 0..1 {
-            let disciple = game.recruitment_system.generate_random_disciple();
+            let disciple = game.recruitment_system.generate_random_disciple(game.sect.reputation, &mut game.rng);
             let id = disciple.id;
             game.sect.recruit_disciple(disciple);
             game.event_system
@@ -46,16 +150,18 @@ impl Game {
         game
     }
 
-    /// 游戏年度循环
-    pub fn yearly_cycle(&mut self) {
+    /// 游戏年度循环，返回本年的人口结构记录
+    pub fn yearly_cycle(&mut self) -> YearRecord {
+        let deaths_before = self.sect.total_deaths;
         println!("\n========== 第{}年 ==========", self.sect.year + 1);
 
-        // 1. 年度收入
+        // 1. 年景与年度收入
+        self.map.roll_harvest(&mut self.rng);
         let income = self.map.calculate_income(self.sect.reputation);
         self.event_system.add_event(GameEvent::YearlyIncome(income));
 
         // 2. 尝试招募弟子
-        if let Some(disciple) = self.recruitment_system.try_recruit(&self.sect) {
+        if let Some(disciple) = self.recruitment_system.try_recruit(&self.sect, &mut self.rng) {
             let id = disciple.id;
             self.sect.recruit_disciple(disciple);
             self.event_system
@@ -63,7 +169,8 @@ impl Game {
         }
 
         // 3. 生成任务
-        let tasks = self.map.get_available_tasks();
+        let max_risk = self.sect.sect_rank().max_task_risk();
+        let tasks = self.map.get_available_tasks(max_risk, &mut self.rng);
         if !tasks.is_empty() {
             self.event_system
                 .add_event(GameEvent::TaskAvailable(tasks.clone()));
@@ -72,14 +179,17 @@ impl Game {
             self.auto_assign_tasks(tasks);
         }
 
-        // 4. 弟子年龄增长和寿元检查
-        self.sect.yearly_update();
+        // 4. 弟子年龄增长和寿元检查，叛离弟子可能在地图上另立门户
+        let yearly = self.sect.yearly_update(&mut self.recruitment_system, &mut self.rng);
+        for deserter in yearly.deserters {
+            self.map.spawn_renegade(&deserter, &mut self.rng);
+        }
 
         // 5. 检查突破
         self.check_breakthroughs();
 
         // 6. 地图更新
-        self.map.update();
+        self.map.update(&std::collections::HashSet::new(), &[], &mut self.rng);
         self.event_system.add_event(GameEvent::MapUpdate);
 
         // 7. 处理事件
@@ -90,12 +200,35 @@ impl Game {
 
         // 9. 显示统计
         println!("\n{}", self.sect.get_statistics());
+
+        self.year_record(yearly.newborns.len() as u32, self.sect.total_deaths - deaths_before)
+    }
+
+    /// 当前存活弟子的年龄分布与修为分布
+    fn year_record(&self, births: u32, deaths: u32) -> YearRecord {
+        let mut age_distribution = [0; AGE_BRACKETS.len()];
+        let mut level_counts = [0; CultivationLevel::ALL.len()];
+        let alive = self.sect.alive_disciples();
+        for disciple in &alive {
+            if let Some(bracket) = AGE_BRACKETS.iter().position(|(upper, _)| disciple.age < *upper) {
+                age_distribution[bracket] += 1;
+            }
+            if let Some(level) = CultivationLevel::ALL.iter().position(|l| *l == disciple.cultivation.current_level) {
+                level_counts[level] += 1;
+            }
+        }
+        YearRecord {
+            year: self.sect.year,
+            population: alive.len() as u32,
+            age_distribution,
+            level_counts,
+            births,
+            deaths,
+        }
     }
 
     /// 自动分配任务
     fn auto_assign_tasks(&mut self, tasks: Vec<Task>) {
-        let mut rng = rand::thread_rng();
-
         for task in tasks {
             // 找到合适的弟子
             let suitable_disciples: Vec<usize> = self
@@ -108,7 +241,7 @@ impl Game {
 
             if !suitable_disciples.is_empty() {
                 // 随机选择一个弟子执行任务
-                let disciple_id = suitable_disciples[rng.gen_range(0..suitable_disciples.len())];
+                let disciple_id = suitable_disciples[self.rng.gen_range(0..suitable_disciples.len())];
 
                 // 执行任务
                 self.execute_task(disciple_id, task);
@@ -118,39 +251,100 @@ impl Game {
 
     /// 执行任务
     fn execute_task(&mut self, disciple_id: usize, task: Task) {
-        let mut rng = rand::thread_rng();
+        let rng = &mut self.rng;
 
-        // 任务成功率基于弟子修为和任务难度
-        let success = rng.gen_bool(0.8); // 简化版，80%成功率
+        let disciple = match self.sect.disciples.iter_mut().find(|d| d.id == disciple_id) {
+            Some(d) => d,
+            None => return,
+        };
 
-        if success {
-            if let Some(disciple) = self
-                .sect
-                .disciples
-                .iter_mut()
-                .find(|d| d.id == disciple_id)
-            {
-                let progress_gained = disciple.complete_task(&task);
-
-                // 更新道心
-                disciple.dao_heart =
-                    ((disciple.dao_heart as i32 + task.dao_heart_impact).max(0) as u32).min(100);
-
-                let result = TaskResult {
-                    task_id: task.id,
-                    disciple_id,
-                    disciple_name: disciple.name.clone(),
-                    success: true,
-                    resources_gained: task.resource_reward,
-                    reputation_gained: task.reputation_reward,
-                    progress_gained,
-                    disciple_died: false,
-                };
-
-                self.event_system
-                    .add_event(GameEvent::TaskCompleted(result));
+        // 战斗任务按敌我实力计算胜率，其他任务80%成功率
+        let mut combat_detail = match &task.task_type {
+            TaskType::Combat(combat) => Some(CombatDetail::new(disciple, combat)),
+            _ => None,
+        };
+        let success_rate = combat_detail.as_ref().map(|c| c.success_rate as f64).unwrap_or(0.8);
+        let success = rng.gen_bool(success_rate);
+
+        let result = if success {
+            let progress_gained = disciple.complete_task(&task, rng);
+
+            // 更新道心
+            disciple.apply_dao_heart_impact(task.dao_heart_impact);
+
+            // 战斗胜利按等级差调整奖励
+            let multiplier = combat_detail.as_ref().map(|c| c.reward_multiplier).unwrap_or(1.0);
+
+            TaskResult {
+                task_id: task.id,
+                disciple_id,
+                disciple_name: disciple.name.clone(),
+                success: true,
+                outcome: TaskOutcome::Success,
+                completion: 1.0,
+                resources_gained: (task.resource_reward as f32 * multiplier) as u32,
+                contribution_gained: (task.contribution_reward() as f32 * multiplier) as u32,
+                reputation_gained: (task.reputation_reward as f32 * multiplier) as i32,
+                progress_gained,
+                disciple_died: false,
+                combat: combat_detail,
+                team_size: 1,
+                synergy_bonus: 0.0,
+                drops: Vec::new(),
+            }
+        } else {
+            // 战斗失败损失体魄，体魄耗尽则身死
+            let mut disciple_died = false;
+            if let Some(detail) = combat_detail.as_mut() {
+                let before = disciple.constitution;
+                disciple.consume_constitution(COMBAT_DEFEAT_CONSTITUTION_LOSS);
+                detail.constitution_lost = before - disciple.constitution;
+                if !disciple.is_alive() {
+                    disciple_died = true;
+                    self.event_system
+                        .add_event(GameEvent::DiscipleDeath(disciple_id, DepartureCause::Battle));
+                } else if rng.gen_bool(COMBAT_INJURY_CHANCE) {
+                    disciple.add_injury(InjurySeverity::Serious);
+                }
+            }
+
+            TaskResult {
+                task_id: task.id,
+                disciple_id,
+                disciple_name: disciple.name.clone(),
+                success: false,
+                outcome: TaskOutcome::Failure,
+                completion: 0.0,
+                resources_gained: 0,
+                contribution_gained: 0,
+                reputation_gained: 0,
+                progress_gained: 0,
+                disciple_died,
+                combat: combat_detail,
+                team_size: 1,
+                synergy_bonus: 0.0,
+                drops: Vec::new(),
+            }
+        };
+
+        // 高危险度探索可能受伤
+        if let TaskType::Exploration(exploration) = &task.task_type {
+            if let Some(severity) = exploration_injury(exploration.danger_level, rng.gen()) {
+                disciple.add_injury(severity);
             }
         }
+
+        // 医道弟子完成疗伤任务后为同门疗伤
+        if result.success {
+            if let TaskType::Auxiliary(aux) = &task.task_type {
+                if aux.skill_required == Some(TalentType::Medical) {
+                    self.sect.treat_injuries(disciple_id);
+                }
+            }
+        }
+
+        self.event_system
+            .add_event(GameEvent::TaskCompleted(result));
     }
 
     /// 检查弟子是否可以突破
@@ -162,13 +356,14 @@ impl Game {
             if disciple.cultivation.can_tribulate() {
                 if disciple.cultivation.current_level.requires_tribulation() {
                     // 需要渡劫
-                    let success = disciple.attempt_tribulation();
+                    let outcome = disciple.attempt_tribulation(&mut self.rng);
+                    let success = outcome == TribulationOutcome::Success;
                     tribulation_results.push((disciple.id, success));
 
-                    if !success {
+                    if outcome == TribulationOutcome::Died {
                         // 渡劫失败，弟子身死
                         self.event_system
-                            .add_event(GameEvent::DiscipleDeath(disciple.id));
+                            .add_event(GameEvent::DiscipleDeath(disciple.id, DepartureCause::Tribulation));
                     }
                 } else {
                     // 直接突破
@@ -214,25 +409,31 @@ impl Game {
         }
     }
 
-    /// 运行游戏
-    pub fn run(&mut self, max_years: u32) {
+    /// 运行游戏，返回时间序列报告
+    pub fn run(&mut self, max_years: u32) -> SimulationReport {
         println!("欢迎来到修仙模拟器！");
         println!("宗门名称：{}", self.sect.name);
         println!("\n游戏开始！\n");
 
+        let report = self.run_with_report(max_years);
+
+        // 游戏结束统计
+        self.print_final_statistics();
+        println!("\n{}", report.summary());
+        report
+    }
+
+    /// 运行至多 max_years 年（游戏结束即停止），逐年收集年龄分布、各修为人数与出生/死亡数
+    pub fn run_with_report(&mut self, max_years: u32) -> SimulationReport {
+        let mut report = SimulationReport::default();
         for _ in 0..max_years {
             if self.state != GameState::Running {
                 break;
             }
-
-            self.yearly_cycle();
-
-            // 简单延迟，让玩家看清楚
-            // std::thread::sleep(std::time::Duration::from_millis(500));
+            let record = self.yearly_cycle();
+            report.push(record, self.sect.is_destroyed());
         }
-
-        // 游戏结束统计
-        self.print_final_statistics();
+        report
     }
 
     /// 打印最终统计
@@ -285,3 +486,52 @@ impl Game {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_seed_reproduces_statistics() {
+        let simulate = |seed: u64| {
+            let mut game = Game::new("青云宗".to_string(), Some(seed));
+            for _ in 0..30 {
+                if game.state != GameState::Running {
+                    break;
+                }
+                game.yearly_cycle();
+            }
+            game.sect.get_statistics()
+        };
+
+        for seed in [1, 42, 2024] {
+            assert_eq!(simulate(seed), simulate(seed));
+        }
+    }
+
+    #[test]
+    fn test_fixed_seed_simulation_report_is_stable() {
+        let simulate = |seed: u64| Game::new("青云宗".to_string(), Some(seed)).run_with_report(60);
+
+        for seed in [7, 2024] {
+            let report = simulate(seed);
+            assert_eq!(report, simulate(seed));
+            assert!(!report.years.is_empty() && report.years.len() <= 60);
+
+            // 每年的年龄分段与修为分布都覆盖全部存活弟子
+            for record in &report.years {
+                assert_eq!(record.age_distribution.iter().sum::<u32>(), record.population);
+                assert_eq!(record.level_counts.iter().sum::<u32>(), record.population);
+                assert!(record.population <= report.peak_population);
+            }
+            let peak = report.years.iter().find(|r| r.year == report.peak_population_year).unwrap();
+            assert_eq!(peak.population, report.peak_population);
+            assert_eq!(report.destroyed_year.is_some(), report.years.last().unwrap().population == 0);
+            assert!(report.highest_level.is_some());
+
+            let csv = report.to_csv();
+            assert_eq!(csv.lines().count(), report.years.len() + 1);
+            assert!(csv.starts_with("year,population,births,deaths,"));
+        }
+    }
+}
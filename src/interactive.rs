@@ -1,29 +1,129 @@
-use crate::disciple::Disciple;
-use crate::event::{EventSystem, RecruitmentSystem, TaskResult};
-use crate::map::GameMap;
-use crate::sect::Sect;
-use crate::task::Task;
+use crate::achievement::Achievement;
+use crate::config::{CultivationPathsConfig, OpportunityRewardKind};
+use crate::cultivation::CultivationLevel;
+use crate::disciple::{
+    exploration_injury, tribulation_blessing, tribulation_guardian_modifier, Disciple,
+    DiscipleType, Heritage, InjurySeverity, Personality, TalentType, TaskTypePreference,
+    TribulationOutcome, COMBAT_INJURY_CHANCE, FATIGUE_PER_WORK_TURN, GUARDIAN_BACKLASH_CHANCE,
+    GUARDIAN_ENERGY_COST, MAX_SECLUSION_TURNS, MAX_TRIBULATION_GUARDIANS, SECLUSION_COST_PER_TURN,
+    TALENT_MAX_LEVEL, TRIBULATION_AFTERMATH_MIN_LEVEL, TRIBULATION_BLESSING_DURATION,
+    TRIBULATION_SHAKE_RADIUS,
+};
+use crate::event::{CombatDetail, EventSystem, RecruitmentSystem, TaskOutcome, TaskResult, RECRUITMENT_POOL_REFRESH_COST};
+use crate::map::{GameMap, Herb, PatrolZone, Position, DEFAULT_MAP_SIZE, LOCATION_FALL_REPUTATION_PENALTY, MAX_MAP_SIZE, MIN_MAP_SIZE};
+use crate::modifier::Modifier;
+use crate::pill::{PillGrade, PillOutcome, PillRecipe, PillType};
+use crate::sect::{DepartureCause, GiftItem, GiftOutcome, Sect, STARTING_SPIRIT_STONES};
+use crate::technique::Technique;
+use crate::task::{team_synergy_bonus, Task, TaskType, CRUSHING_DEFEAT_COMPLETION, EXPLORATION_DEPTH_LIFESPAN_LOSS, MAX_TASK_EXTENSION_TURNS, PARTIAL_REWARD_SHARE};
+use crate::tournament::{TournamentResult, TOURNAMENT_INTERVAL_YEARS};
 use crate::ui::UI;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use tokio::sync::broadcast;
 
 /// 游戏状态
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum GameState {
     Running,
     Victory,
     Defeat,
 }
 
+/// 远征途中每回合遭遇妖兽伏击的概率
+pub const EXPEDITION_AMBUSH_CHANCE: f64 = 0.1;
+
+/// 编队集结时向目标四周分散的最大距离
+pub const FORMATION_RADIUS: i32 = 2;
+
+/// 高傲的弟子被指派任务时抗命的概率
+pub const ARROGANT_REFUSAL_CHANCE: f64 = 0.3;
+
+/// 道心达到该值的弟子顾全大局，不再抗命
+pub const REFUSAL_DAO_HEART_THRESHOLD: f32 = 70.0;
+
+/// 奇遇所得灵石（或其他奇遇无从落实时的替代奖励）的范围
+const OPPORTUNITY_SPIRIT_STONES: std::ops::RangeInclusive<u32> = 100..=300;
+
+/// 护法护持下的渡劫结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct GuardedTribulation {
+    pub outcome: TribulationOutcome,
+    pub from_level: CultivationLevel,           // 渡劫前境界
+    pub injured_guardians: Vec<(usize, String)>, // 被天劫波及受伤的护法 (ID, 姓名)
+}
+
+/// 任务分配的子状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum AssignmentPhase {
+    Traveling, // 前往中
+    #[default]
+    Executing, // 执行中
+    Returning, // 返程中
+}
+
+impl AssignmentPhase {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Traveling => "前往中",
+            Self::Executing => "执行中",
+            Self::Returning => "返程中",
+        }
+    }
+}
+
 /// 回合中的任务分配
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TaskAssignment {
     pub task_id: usize,
     pub disciple_ids: Vec<usize>,    // 参与任务的弟子ID列表（支持多人）
     pub started_turn: Option<u32>,   // 任务开始的回合数
     pub progress: u32,               // 已执行的回合数
+    #[serde(default)]
+    pub phase: AssignmentPhase,      // 子状态（远征时先前往任务地点）
+    #[serde(default)]
+    pub home: Option<Position>,      // 远征出发地，完成后返程至此（非远征为None）
+}
+
+/// 完成任务后返程中的远征队
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExpeditionReturn {
+    pub task_id: usize,
+    pub task_name: String,
+    pub duration: u32,
+    pub disciple_ids: Vec<usize>,
+    pub home: Position,
+}
+
+/// 远征目的地：大型建筑取离出发点最近的有效位置
+fn expedition_target(task: &Task, from: Position) -> Option<Position> {
+    let distance = |p: &Position| (p.x - from.x).abs() + (p.y - from.y).abs();
+    task.valid_positions
+        .as_ref()
+        .and_then(|positions| positions.iter().min_by_key(|p| distance(p)).copied())
+        .or(task.position)
+}
+
+/// 弟子当前任务的状态
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscipleTaskStatus {
+    pub task_id: usize,
+    pub task_name: String,
+    pub duration: u32,
+    pub progress: u32,
+    pub phase: AssignmentPhase,
+    pub remaining_turns: u32, // 预计剩余回合数（含路程）
 }
 
 impl TaskAssignment {
+    /// 踏上远征：先前往任务地点，完成后返回出发地
+    pub fn begin_expedition(&mut self, home: Position) {
+        self.phase = AssignmentPhase::Traveling;
+        self.home.get_or_insert(home);
+    }
+
     /// 向后兼容：获取第一个弟子ID
     pub fn disciple_id(&self) -> Option<usize> {
         self.disciple_ids.first().copied()
@@ -52,6 +152,441 @@ impl TaskAssignment {
     }
 }
 
+/// 日志分类
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LogCategory {
+    Income,       // 收入
+    Recruitment,  // 招募
+    Birth,        // 子嗣出生
+    Breakthrough, // 突破
+    Tribulation,  // 渡劫
+    Task,         // 任务完成
+    Death,        // 弟子陨落
+    Invasion,     // 妖魔入侵
+    Construction, // 建筑竣工
+    Relationship, // 关系变化
+    Seclusion,    // 闭关
+    Tournament,   // 比武大会
+    HeartDemon,   // 走火入魔
+    Alchemy,      // 炼丹
+    Talent,       // 灵根成长
+    Opportunity,  // 奇遇
+    Heritage,     // 传承继承
+    Achievement,  // 成就解锁
+}
+
+impl LogCategory {
+    /// 需要实时推送给客户端的关键事件（突破、渡劫、陨落、妖魔入侵、走火入魔）
+    pub fn is_live(&self) -> bool {
+        matches!(self, Self::Breakthrough | Self::Tribulation | Self::Death | Self::Invasion | Self::HeartDemon)
+    }
+}
+
+/// 实时事件通道容量（订阅者落后超过该条数时丢弃最早的事件）
+const LIVE_EVENT_CAPACITY: usize = 64;
+
+/// 自动分配策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AssignStrategy {
+    #[default]
+    FirstFit,        // 为每个任务找第一个合适的空闲弟子
+    MaxReward,       // 按预估修为奖励贪心匹配
+    CultivationPath, // 优先补齐弟子修炼路径缺口的任务类型
+}
+
+impl AssignStrategy {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "FirstFit" => Some(AssignStrategy::FirstFit),
+            "MaxReward" => Some(AssignStrategy::MaxReward),
+            "CultivationPath" => Some(AssignStrategy::CultivationPath),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AssignStrategy::FirstFit => "顺序分配",
+            AssignStrategy::MaxReward => "奖励最大化",
+            AssignStrategy::CultivationPath => "修炼路径优先",
+        }
+    }
+}
+
+/// 弟子操作失败的原因（错误码 + 描述）
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActionError {
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl ActionError {
+    fn new(code: &'static str, message: String) -> Self {
+        Self { code, message }
+    }
+}
+
+/// 任务的候选弟子（按弟子名册顺序，契合秘境者优先）
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TaskCandidates {
+    pub free: Vec<usize>, // 空闲的合适弟子ID
+    pub busy: Vec<usize>, // 正在执行其他任务的合适弟子ID
+}
+
+/// 任务匹配索引：每回合构建一次，避免对每个任务重复扫描全部弟子与分配
+pub struct TaskMatcher<'a> {
+    disciples: &'a [Disciple],
+    by_task: HashMap<usize, &'a TaskAssignment>,    // 任务ID -> 分配
+    busy_counts: HashMap<usize, usize>,             // 弟子ID -> 参与的任务数
+    by_position: HashMap<(i32, i32), Vec<usize>>,    // 位置 -> 弟子下标
+    by_talent: HashMap<TalentType, Vec<usize>>,     // 灵根 -> 弟子下标
+    available: Vec<usize>,                          // 未闭关、未巡逻的弟子下标
+}
+
+impl<'a> TaskMatcher<'a> {
+    pub fn new(disciples: &'a [Disciple], assignments: &'a [TaskAssignment]) -> Self {
+        let by_task = assignments.iter().map(|a| (a.task_id, a)).collect();
+        let mut busy_counts = HashMap::new();
+        for id in assignments.iter().flat_map(|a| a.disciple_ids.iter()) {
+            *busy_counts.entry(*id).or_insert(0) += 1;
+        }
+        let mut by_position: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
+        let mut by_talent: HashMap<TalentType, Vec<usize>> = HashMap::new();
+        let mut available = Vec::new();
+        for (i, disciple) in disciples.iter().enumerate() {
+            by_position.entry((disciple.position.x, disciple.position.y)).or_default().push(i);
+            // 专精视同具备对应天赋
+            let skill = disciple.specialization.and_then(|s| s.skill());
+            for talent_type in disciple.talents.iter().map(|t| &t.talent_type).chain(skill.iter()) {
+                let indices = by_talent.entry(talent_type.clone()).or_default();
+                if indices.last() != Some(&i) {
+                    indices.push(i);
+                }
+            }
+            if !disciple.is_in_seclusion() && !disciple.patrolling {
+                available.push(i);
+            }
+        }
+        Self { disciples, by_task, busy_counts, by_position, by_talent, available }
+    }
+
+    /// 找出适合该任务的弟子，区分空闲与忙碌（结果与逐个弟子判定一致）
+    pub fn candidates(&self, task: &Task) -> TaskCandidates {
+        if task.locked {
+            return TaskCandidates::default();
+        }
+        // 有位置要求的任务只看该位置的弟子，需要灵根的辅助任务只看有该灵根的弟子
+        let skill = match &task.task_type {
+            TaskType::Auxiliary(auxiliary) => auxiliary.skill_required.as_ref(),
+            _ => None,
+        };
+        // 大型元素的任务可在其占据的任一格子接取
+        let located = match (&task.valid_positions, &task.position) {
+            (Some(positions), _) => Some(self.disciples_at(positions)),
+            (None, Some(position)) => Some(self.disciples_at(std::slice::from_ref(position))),
+            (None, None) => None,
+        };
+        let pool: &[usize] = match (&located, skill) {
+            (Some(indices), _) => indices,
+            (None, Some(skill)) => self.by_talent.get(skill).map_or(&[], |v| v),
+            (None, None) => &self.available,
+        };
+
+        let own = self.by_task.get(&task.id);
+        let mut candidates = TaskCandidates::default();
+        let mut free = Vec::new();
+        for disciple in pool.iter().map(|&i| &self.disciples[i]) {
+            if !task.is_suitable_for_disciple(disciple) {
+                continue;
+            }
+            // 参与的任务中除本任务外还有别的，即视为忙碌
+            let assigned = self.busy_counts.get(&disciple.id).copied().unwrap_or(0);
+            let on_this_task = own.is_some_and(|a| a.disciple_ids.contains(&disciple.id)) as usize;
+            if assigned > on_this_task {
+                candidates.busy.push(disciple.id);
+            } else {
+                free.push((task.realm_affinity(disciple) != Some(true), disciple.id));
+            }
+        }
+        // 灵根与秘境契合的弟子优先推荐（稳定排序，保持名册顺序）
+        free.sort_by_key(|(mismatched, _)| *mismatched);
+        candidates.free = free.into_iter().map(|(_, id)| id).collect();
+        candidates
+    }
+
+    /// 位于任一给定格子上的弟子下标（按名册顺序，去重）
+    fn disciples_at(&self, positions: &[Position]) -> Vec<usize> {
+        let mut indices: Vec<usize> = positions
+            .iter()
+            .filter_map(|p| self.by_position.get(&(p.x, p.y)))
+            .flatten()
+            .copied()
+            .collect();
+        indices.sort_unstable();
+        indices.dedup();
+        indices
+    }
+}
+
+/// 年度收入明细
+#[derive(Debug, Clone)]
+pub struct IncomeReport {
+    pub sources: Vec<(String, u32)>,          // 地图各产出元素的收入
+    pub building_bonuses: Vec<(String, u32)>, // 各建筑收入加成
+    pub total: u32,
+}
+
+/// 回合结算汇总：资源变化按结算前后宗门状态计算，与实际增减一致
+#[derive(Debug, Clone, Default)]
+pub struct TurnSummary {
+    pub task_results: Vec<TaskResult>,
+    pub annual_income: u32,                // 本回合开始时入账的年度收入
+    pub spirit_stones_delta: i64,          // 结算期间灵石净变化（含任务奖励、掉落、投资本金）
+    pub contribution_delta: i64,           // 结算期间贡献净变化
+    pub reputation_delta: i32,             // 结算期间声望净变化
+    pub tasks_completed: usize,            // 成功的任务数
+    pub tasks_failed: usize,               // 未成功的任务数（含部分成功）
+    pub casualties: Vec<String>,           // 陨落的弟子
+    pub breakthrough_ready: Vec<String>,   // 本回合修为圆满、可以突破的弟子
+    pub relationship_changes: Vec<String>, // 关系提升
+}
+
+/// 弟子移动结果
+#[derive(Debug, Clone)]
+pub struct MoveOutcome {
+    pub disciple_name: String,
+    pub from: Position,
+    pub moves_remaining: u32,
+    pub collected_herb: Option<Herb>, // 到达后采集的成熟草药
+    pub immature_herb: Option<Herb>,  // 到达处尚未成熟的草药
+    pub opportunity: Option<OpportunityEncounter>, // 踩中机缘点触发的奇遇
+}
+
+/// 奇遇收获
+#[derive(Debug, Clone, PartialEq)]
+pub enum OpportunityGain {
+    Technique(String),             // 功法残卷（已学会的功法名）
+    Recipe(PillType),              // 丹方
+    TalentAwakened(TalentType, u32), // 天赋觉醒（成长后的等级）
+    SpiritStones(u32),             // 一笔灵石
+}
+
+impl OpportunityGain {
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Technique(name) => format!("参悟功法残卷，习得{}", name),
+            Self::Recipe(pill_type) => format!("获得{}丹方", pill_type.name()),
+            Self::TalentAwakened(talent, level) => format!("{}觉醒至{}级", talent.name(), level),
+            Self::SpiritStones(amount) => format!("获得灵石{}", amount),
+        }
+    }
+}
+
+/// 弟子在机缘点触发的奇遇
+#[derive(Debug, Clone)]
+pub struct OpportunityEncounter {
+    pub name: String, // 机缘点名称
+    pub gain: OpportunityGain,
+}
+
+/// 任务判定结果
+#[derive(Debug, Clone, Copy)]
+struct TaskRoll {
+    success: bool,
+    completion: f32, // 完成度（成功为1）
+}
+
+impl TaskRoll {
+    /// 掷骰判定：失败时掷骰越接近成功线完成度越高；必定结算的任务（投资）总是成功
+    fn new(roll: f64, success_rate: f64, always_succeeds: bool) -> Self {
+        let success = always_succeeds || roll < success_rate;
+        let completion = if success { 1.0 } else { ((1.0 - roll) / (1.0 - success_rate).max(f64::EPSILON)).min(1.0) as f32 };
+        Self { success, completion }
+    }
+}
+
+/// 编队移动中单个弟子的结果
+#[derive(Debug, Clone)]
+pub struct FormationMoveResult {
+    pub disciple_id: usize,
+    pub disciple_name: String,
+    pub destination: Option<Position>,         // 分配到的集结位置（目标附近无可达空位时为None）
+    pub result: Result<Position, ActionError>, // 本回合移动后所在的位置
+}
+
+impl FormationMoveResult {
+    /// 是否已抵达集结位置
+    pub fn arrived(&self) -> bool {
+        match (&self.destination, &self.result) {
+            (Some(dest), Ok(pos)) => dest.x == pos.x && dest.y == pos.y,
+            _ => false,
+        }
+    }
+}
+
+/// 高阶渡劫对周边的波及
+#[derive(Debug, Clone, Default)]
+pub struct TribulationAftermath {
+    pub shaken: Vec<(usize, i32)>, // 道心受天劫波动的弟子（弟子ID, 道心变化）
+    pub monsters: Vec<String>,     // 被震退或吸引的妖魔
+    pub blessed: usize,            // 获得天劫气运的弟子数
+}
+
+/// 批量操作的弟子筛选条件（未指定的条件不限制）
+#[derive(Debug, Clone, Default)]
+pub struct DiscipleFilter {
+    pub min_level: Option<CultivationLevel>,
+    pub max_level: Option<CultivationLevel>,
+    pub disciple_type: Option<DiscipleType>,
+    pub idle: Option<bool>,
+    pub talent: Option<TalentType>,
+}
+
+impl DiscipleFilter {
+    fn matches(&self, disciple: &Disciple, busy: bool) -> bool {
+        let level = disciple.cultivation.current_level;
+        self.min_level.map_or(true, |min| level >= min)
+            && self.max_level.map_or(true, |max| level <= max)
+            && self.disciple_type.as_ref().map_or(true, |t| &disciple.disciple_type == t)
+            && self.idle != Some(busy)
+            && self.talent.as_ref().map_or(true, |t| disciple.talents.iter().any(|talent| &talent.talent_type == t))
+    }
+}
+
+/// 批量操作的动作
+#[derive(Debug, Clone)]
+pub enum BatchAction {
+    UsePill { pill_type: PillType, grade: PillGrade },
+    MoveTo(Position),
+    AutoAssign(AssignStrategy),
+}
+
+/// 批量操作中单个弟子的执行结果
+#[derive(Debug, Clone)]
+pub struct BatchActionResult {
+    pub disciple_id: usize,
+    pub disciple_name: String,
+    pub result: Result<String, ActionError>,
+}
+
+/// 配置热重载结果
+#[derive(Debug, Default)]
+pub struct ConfigReloadReport {
+    pub added_elements: Vec<String>,    // 新增的地图元素
+    pub removed_elements: Vec<String>,  // 移除的地图元素
+    pub updated_elements: usize,        // 刷新模板的地图元素数量
+    pub added_buildings: Vec<String>,   // 新增的建筑
+    pub removed_buildings: Vec<String>, // 移除的建筑
+    pub cancelled_tasks: Vec<String>,   // 因地点移除而取消的未开始任务
+    pub orphaned_tasks: Vec<String>,    // 地点已移除但仍在进行、将执行完毕的任务
+}
+
+/// 事件日志条目
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameLogEntry {
+    pub year: u32,
+    pub category: LogCategory,
+    pub message: String,
+}
+
+/// 游戏存档（InteractiveGame 中需要持久化的部分）
+#[derive(Serialize, Deserialize)]
+struct GameSave {
+    sect: Sect,
+    map_elements: Vec<crate::map::PositionedElement>,
+    sect_invasion: Option<crate::map::SectInvasion>,
+    current_tasks: Vec<Task>,
+    task_assignments: Vec<TaskAssignment>,
+    #[serde(default)]
+    expedition_returns: Vec<ExpeditionReturn>,
+    state: GameState,
+    next_disciple_id: usize,
+    #[serde(default)]
+    next_monster_id: usize,
+    #[serde(default)]
+    next_opportunity_id: usize,
+    #[serde(default, skip_serializing)]
+    pending_recruitment: Option<Disciple>, // 旧存档的单个待招募弟子，读档时并入招募池
+    #[serde(default)]
+    recruitment_pool: Vec<Disciple>,
+    #[serde(default)]
+    event_log: Vec<GameLogEntry>,
+    #[serde(default)]
+    harvest: crate::map::Harvest,
+    #[serde(default)]
+    rival_sects: Vec<crate::map::RivalSect>,
+    #[serde(default = "default_map_size")]
+    map_width: i32,
+    #[serde(default = "default_map_size")]
+    map_height: i32,
+}
+
+fn default_map_size() -> i32 {
+    DEFAULT_MAP_SIZE
+}
+
+/// 开局初始弟子数上限
+pub const MAX_INITIAL_DISCIPLES: usize = 10;
+
+/// 开局配置（用于调整难度）
+#[derive(Debug, Clone)]
+pub struct GameSetup {
+    pub map_width: i32,
+    pub map_height: i32,
+    pub initial_disciples: usize,
+    pub starting_resources: u32, // 初始灵石（不超过库房上限）
+}
+
+impl Default for GameSetup {
+    fn default() -> Self {
+        Self {
+            map_width: DEFAULT_MAP_SIZE,
+            map_height: DEFAULT_MAP_SIZE,
+            initial_disciples: 1,
+            starting_resources: STARTING_SPIRIT_STONES,
+        }
+    }
+}
+
+impl GameSetup {
+    /// 校验地图尺寸与初始弟子数在合理范围内
+    pub fn validate(&self) -> Result<(), String> {
+        let size_range = MIN_MAP_SIZE..=MAX_MAP_SIZE;
+        if !size_range.contains(&self.map_width) || !size_range.contains(&self.map_height) {
+            return Err(format!(
+                "地图尺寸需在{}到{}之间，当前为{}×{}",
+                MIN_MAP_SIZE, MAX_MAP_SIZE, self.map_width, self.map_height
+            ));
+        }
+        if !(1..=MAX_INITIAL_DISCIPLES).contains(&self.initial_disciples) {
+            return Err(format!("初始弟子数需在1到{}之间", MAX_INITIAL_DISCIPLES));
+        }
+        Ok(())
+    }
+}
+
+/// 最多保留的回合快照数
+const MAX_TURN_SNAPSHOTS: usize = 3;
+
+/// 回合快照（用于撤销回合，完整覆盖地图状态以抵消妖魔移动、成长等随机性）
+#[derive(Debug, Clone)]
+struct GameSnapshot {
+    sect: Sect,
+    map_elements: Vec<crate::map::PositionedElement>,
+    sect_invasion: Option<crate::map::SectInvasion>,
+    next_monster_id: usize,
+    next_opportunity_id: usize,
+    harvest: crate::map::Harvest,
+    rival_sects: Vec<crate::map::RivalSect>,
+    current_tasks: Vec<Task>,
+    task_assignments: Vec<TaskAssignment>,
+    expedition_returns: Vec<ExpeditionReturn>,
+    state: GameState,
+    next_disciple_id: usize,
+    recruitment_pool: Vec<Disciple>,
+    event_log_len: usize,
+}
+
 /// 交互式游戏
 pub struct InteractiveGame {
     pub sect: Sect,
@@ -61,8 +596,14 @@ pub struct InteractiveGame {
     pub state: GameState,
     pub current_tasks: Vec<Task>,
     pub task_assignments: Vec<TaskAssignment>,
+    pub expedition_returns: Vec<ExpeditionReturn>, // 返程中的远征队
     pub is_web_mode: bool, // Web模式下不显示UI和等待输入
-    pub pending_recruitment: Option<Disciple>, // 待招募的弟子（需要用户确认）
+    pub recruitment_pool: Vec<Disciple>,       // 本回合的招募池（Web模式下由玩家挑选）
+    pub event_log: Vec<GameLogEntry>,          // 事件日志
+    history: VecDeque<GameSnapshot>,           // 回合快照（最近的在末尾）
+    pub cultivation_paths: CultivationPathsConfig, // 各境界修炼路径的任务配比
+    last_income: u32,                          // 本回合开始时入账的年度收入
+    live_events: broadcast::Sender<GameLogEntry>, // 关键事件实时推送
 }
 
 impl InteractiveGame {
@@ -71,10 +612,17 @@ impl InteractiveGame {
     }
 
     pub fn new_with_mode(sect_name: String, is_web_mode: bool) -> Self {
-        let mut map = GameMap::new();
-        map.initialize();
+        Self::with_setup(sect_name, is_web_mode, &GameSetup::default()).expect("默认开局配置应当合法")
+    }
+
+    /// 按开局配置创建游戏：地图尺寸、初始弟子数与初始灵石
+    pub fn with_setup(sect_name: String, is_web_mode: bool, setup: &GameSetup) -> Result<Self, String> {
+        setup.validate()?;
+        let mut map = GameMap::with_size(setup.map_width, setup.map_height);
+        map.initialize(&mut rand::thread_rng());
 
         let mut sect = Sect::new(sect_name);
+        sect.spirit_stones = setup.starting_resources.min(sect.spirit_stone_capacity());
 
         // 初始化建筑树
         let building_tree = crate::building::create_sect_building_tree();
@@ -88,29 +636,292 @@ impl InteractiveGame {
             state: GameState::Running,
             current_tasks: Vec::new(),
             task_assignments: Vec::new(),
+            expedition_returns: Vec::new(),
             is_web_mode,
-            pending_recruitment: None,
+            recruitment_pool: Vec::new(),
+            event_log: Vec::new(),
+            history: VecDeque::new(),
+            last_income: 0,
+            cultivation_paths: CultivationPathsConfig::load(),
+            live_events: broadcast::channel(LIVE_EVENT_CAPACITY).0,
         };
 
-        // 初始招募1个弟子
-        for _ in 0..1 {
-            let disciple = game.recruitment_system.generate_random_disciple();
+        // 初始招募弟子
+        for _ in 0..setup.initial_disciples {
+            let disciple = game.recruitment_system.generate_random_disciple(game.sect.reputation, &mut rand::thread_rng());
             game.sect.recruit_disciple(disciple);
         }
 
-        game
+        Ok(game)
+    }
+
+    /// 保存游戏到JSON文件
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn std::error::Error>> {
+        let save = GameSave {
+            sect: self.sect.clone(),
+            map_elements: self.map.elements.clone(),
+            sect_invasion: self.map.sect_invasion.clone(),
+            current_tasks: self.current_tasks.clone(),
+            task_assignments: self.task_assignments.clone(),
+            expedition_returns: self.expedition_returns.clone(),
+            state: self.state.clone(),
+            next_disciple_id: self.recruitment_system.next_disciple_id(),
+            next_monster_id: self.map.next_monster_id,
+            next_opportunity_id: self.map.next_opportunity_id,
+            pending_recruitment: None,
+            recruitment_pool: self.recruitment_pool.clone(),
+            event_log: self.event_log.clone(),
+            harvest: self.map.harvest,
+            rival_sects: self.map.rival_sects.clone(),
+            map_width: self.map.width,
+            map_height: self.map.height,
+        };
+
+        if let Some(parent) = path.as_ref().parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string_pretty(&save)?;
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// 从JSON文件加载游戏（以Web模式运行）
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = std::fs::read_to_string(path)?;
+        let save: GameSave = serde_json::from_str(&content)?;
+        let mut map = GameMap::restore(save.map_elements, save.sect_invasion, save.next_monster_id, save.next_opportunity_id);
+        map.harvest = save.harvest;
+        map.rival_sects = save.rival_sects;
+        map.width = save.map_width;
+        map.height = save.map_height;
+
+        Ok(Self {
+            sect: save.sect,
+            map,
+            event_system: EventSystem::new(),
+            recruitment_system: RecruitmentSystem::with_next_id(save.next_disciple_id),
+            state: save.state,
+            current_tasks: save.current_tasks,
+            task_assignments: save.task_assignments,
+            expedition_returns: save.expedition_returns,
+            is_web_mode: true,
+            recruitment_pool: save.recruitment_pool.into_iter().chain(save.pending_recruitment).collect(),
+            event_log: save.event_log,
+            history: VecDeque::new(),
+            last_income: 0,
+            cultivation_paths: CultivationPathsConfig::load(),
+            live_events: broadcast::channel(LIVE_EVENT_CAPACITY).0,
+        })
+    }
+
+    /// 保存回合快照（超过上限时丢弃最早的快照）
+    fn push_snapshot(&mut self) {
+        self.history.push_back(GameSnapshot {
+            sect: self.sect.clone(),
+            map_elements: self.map.elements.clone(),
+            sect_invasion: self.map.sect_invasion.clone(),
+            next_monster_id: self.map.next_monster_id,
+            next_opportunity_id: self.map.next_opportunity_id,
+            harvest: self.map.harvest,
+            rival_sects: self.map.rival_sects.clone(),
+            current_tasks: self.current_tasks.clone(),
+            task_assignments: self.task_assignments.clone(),
+            expedition_returns: self.expedition_returns.clone(),
+            state: self.state.clone(),
+            next_disciple_id: self.recruitment_system.next_disciple_id(),
+            recruitment_pool: self.recruitment_pool.clone(),
+            event_log_len: self.event_log.len(),
+        });
+        while self.history.len() > MAX_TURN_SNAPSHOTS {
+            self.history.pop_front();
+        }
+    }
+
+    /// 可撤销的回合数
+    pub fn undo_available(&self) -> usize {
+        self.history.len()
+    }
+
+    /// 撤销上一回合，回到上次结算前的状态
+    pub fn undo_turn(&mut self) -> Result<(), String> {
+        let snapshot = self.history.pop_back().ok_or("没有可撤销的回合")?;
+
+        self.sect = snapshot.sect;
+        self.map.elements = snapshot.map_elements;
+        self.map.sect_invasion = snapshot.sect_invasion;
+        self.map.next_monster_id = snapshot.next_monster_id;
+        self.map.next_opportunity_id = snapshot.next_opportunity_id;
+        self.map.harvest = snapshot.harvest;
+        self.map.rival_sects = snapshot.rival_sects;
+        self.current_tasks = snapshot.current_tasks;
+        self.task_assignments = snapshot.task_assignments;
+        self.expedition_returns = snapshot.expedition_returns;
+        self.state = snapshot.state;
+        self.recruitment_system = RecruitmentSystem::with_next_id(snapshot.next_disciple_id);
+        self.recruitment_pool = snapshot.recruitment_pool;
+        self.event_log.truncate(snapshot.event_log_len);
+        Ok(())
+    }
+
+    /// 执行一次写操作，并在调试构建下校验其没有引入新的分配不一致
+    pub fn with_write<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        let before = if cfg!(debug_assertions) { self.check_assignment_invariants() } else { Vec::new() };
+        let result = f(self);
+        if cfg!(debug_assertions) {
+            let introduced: Vec<String> = self
+                .check_assignment_invariants()
+                .into_iter()
+                .filter(|issue| !before.contains(issue))
+                .collect();
+            debug_assert!(introduced.is_empty(), "写操作破坏了任务分配一致性: {:?}", introduced);
+        }
+        result
+    }
+
+    /// 任务分配的核心不变量：不引用已不存在的任务、每个任务只有一条记录、弟子不重复分配
+    pub fn check_assignment_invariants(&self) -> Vec<String> {
+        let mut issues = Vec::new();
+        let mut seen_tasks: HashMap<usize, usize> = HashMap::new();
+        let mut busy: HashMap<usize, usize> = HashMap::new();
+        for assignment in &self.task_assignments {
+            if !self.current_tasks.iter().any(|t| t.id == assignment.task_id) {
+                issues.push(format!("分配记录引用了不存在的任务 #{}", assignment.task_id));
+            }
+            *seen_tasks.entry(assignment.task_id).or_default() += 1;
+            for (i, &disciple_id) in assignment.disciple_ids.iter().enumerate() {
+                if assignment.disciple_ids[..i].contains(&disciple_id) {
+                    issues.push(format!("弟子 #{} 在任务 #{} 中重复出现", disciple_id, assignment.task_id));
+                } else if let Some(other) = busy.insert(disciple_id, assignment.task_id) {
+                    if other != assignment.task_id {
+                        issues.push(format!(
+                            "弟子 #{} 同时被分配到任务 #{} 与 #{}",
+                            disciple_id, other, assignment.task_id
+                        ));
+                    }
+                }
+            }
+        }
+        let mut duplicated: Vec<(usize, usize)> = seen_tasks.into_iter().filter(|(_, n)| *n > 1).collect();
+        duplicated.sort();
+        for (task_id, count) in duplicated {
+            issues.push(format!("任务 #{} 存在 {} 条分配记录", task_id, count));
+        }
+        issues
+    }
+
+    /// 完整一致性检查：核心不变量之外，再核对参与弟子的状态和任务人数上限
+    pub fn validate(&self) -> Vec<String> {
+        let mut issues = self.check_assignment_invariants();
+        for assignment in &self.task_assignments {
+            for &disciple_id in &assignment.disciple_ids {
+                match self.sect.disciples.iter().find(|d| d.id == disciple_id) {
+                    Some(d) if d.is_alive() => {}
+                    _ => issues.push(format!(
+                        "任务 #{} 分配了不存在或已陨落的弟子 #{}",
+                        assignment.task_id, disciple_id
+                    )),
+                }
+            }
+            if let Some(task) = self.current_tasks.iter().find(|t| t.id == assignment.task_id) {
+                if assignment.disciple_ids.len() > task.max_participants as usize {
+                    issues.push(format!(
+                        "任务 #{} 参与人数 {} 超过上限 {}",
+                        task.id, assignment.disciple_ids.len(), task.max_participants
+                    ));
+                }
+            }
+        }
+        issues
+    }
+
+    /// 记录一条事件日志（记在当前年份）
+    /// 关键事件同时推送给所有订阅者
+    pub fn log(&mut self, category: LogCategory, message: impl Into<String>) {
+        let entry = GameLogEntry {
+            year: self.sect.year,
+            category,
+            message: message.into(),
+        };
+        if category.is_live() {
+            // 没有订阅者时发送失败，忽略即可
+            let _ = self.live_events.send(entry.clone());
+        }
+        self.event_log.push(entry);
+    }
+
+    /// 订阅本局的关键事件推送（接收端被丢弃即取消订阅）
+    pub fn subscribe_live_events(&self) -> broadcast::Receiver<GameLogEntry> {
+        self.live_events.subscribe()
+    }
+
+    /// 是否仍有客户端订阅本局的关键事件
+    pub fn has_live_subscribers(&self) -> bool {
+        self.live_events.receiver_count() > 0
     }
 
     /// 开始新的回合
     pub fn start_turn(&mut self) {
-        // 弟子年龄增长和寿元检查（这会增加年份）
-        self.sect.yearly_update();
+        let alive_before: Vec<usize> = self.sect.alive_disciples().iter().map(|d| d.id).collect();
+
+        // 弟子年龄增长、寿元检查和道侣生育（这会增加年份）
+        let yearly = self.sect.yearly_update(&mut self.recruitment_system, &mut rand::thread_rng());
+
+        let expired: Vec<String> = self.sect.disciples.iter()
+            .filter(|d| alive_before.contains(&d.id) && !d.is_alive())
+            .map(|d| d.name.clone())
+            .collect();
+        for name in expired {
+            self.log(LogCategory::Death, format!("{} 寿元耗尽，坐化而逝", name));
+        }
+        for child_id in yearly.newborns {
+            let parents: Vec<String> = self.sect.parents_of(child_id).iter().map(|d| d.name.clone()).collect();
+            if let Some(child) = self.sect.disciples.iter().find(|d| d.id == child_id) {
+                let message = format!("{} 喜得子嗣：{}", parents.join("与"), child.name);
+                self.log(LogCategory::Birth, message);
+            }
+        }
+        for (id1, id2) in yearly.estranged_companions {
+            let name_of = |id: usize| {
+                self.sect.disciples.iter().find(|d| d.id == id).map(|d| d.name.clone()).unwrap_or_default()
+            };
+            let message = format!("{} 与 {} 久未相伴，貌合神离", name_of(id1), name_of(id2));
+            self.log(LogCategory::Relationship, message);
+        }
+        for deserter in yearly.deserters {
+            self.log(LogCategory::Death, format!("{} 叛出宗门", deserter.name));
+            if let Some(name) = self.map.spawn_renegade(&deserter, &mut rand::thread_rng()) {
+                self.log(LogCategory::Invasion, format!("{} 另立门户，化为「{}」与宗门为敌", deserter.name, name));
+            }
+        }
+        // 坐化或叛离的弟子退出任务
+        self.release_dead_disciple_tasks();
 
-        // 弟子自然恢复精力和体魄，并重置移动距离
+        // 按平均道心、近期胜败与负债重算宗门氛围
+        self.sect.refresh_morale();
+
+        // 弟子自然恢复精力和体魄（叠加宗门modifier）
+        let sect_modifiers: HashMap<usize, Vec<Modifier>> = self
+            .sect
+            .alive_disciples()
+            .into_iter()
+            .map(|d| (d.id, self.sect.get_applicable_modifiers_owned(d)))
+            .collect();
         for disciple in self.sect.alive_disciples_mut() {
-            disciple.natural_recovery();
-            // 重置每回合的移动距离
-            disciple.moves_remaining = disciple.cultivation.current_level.movement_range();
+            let modifiers: Vec<&Modifier> = sect_modifiers
+                .get(&disciple.id)
+                .map(|mods| mods.iter().collect())
+                .unwrap_or_default();
+            disciple.natural_recovery(&modifiers);
+        }
+
+        // 推进建筑工期，竣工后效果生效
+        for name in self.sect.advance_construction() {
+            self.log(LogCategory::Construction, format!("{} 竣工", name));
+        }
+
+        // 每隔若干年举办比武大会（参赛人数不足则本届停办）
+        if self.sect.year % TOURNAMENT_INTERVAL_YEARS == 0 {
+            let _ = self.hold_tournament();
         }
 
         if !self.is_web_mode {
@@ -118,34 +929,66 @@ impl InteractiveGame {
             UI::print_title(&format!("第 {} 年", self.sect.year));
         }
 
-        // 1. 年度收入
-        let income = self.map.calculate_income(self.sect.reputation);
-        self.sect.add_resources(income);
+        // 1. 年景与年度收入
+        let harvest = self.map.roll_harvest(&mut rand::thread_rng());
+        let income = self.income_report().total;
+        let wasted = self.sect.add_spirit_stones(income);
+        self.last_income = income;
+        self.log(LogCategory::Income, format!("{}，年度收入：{} 资源", harvest.name(), income));
+        self.log_wasted_spirit_stones(wasted);
         if !self.is_web_mode {
-            UI::success(&format!("年度收入：{} 资源", income));
+            UI::success(&format!("{}，年度收入：{} 资源", harvest.name(), income));
         }
 
-        // 2. 尝试招募弟子
-        if let Some(disciple) = self.recruitment_system.try_recruit(&self.sect) {
-            if self.is_web_mode {
-                // Web模式：存储为待确认招募
-                self.pending_recruitment = Some(disciple);
-            } else {
-                // 命令行模式：直接招募
-                UI::success(&format!(
-                    "新弟子加入：{} ({})",
-                    disciple.name,
-                    self.disciple_type_str(&disciple)
-                ));
-                self.sect.recruit_disciple(disciple);
-            }
+        // 闭关弟子消耗资源修炼
+        self.advance_seclusion(&sect_modifiers);
+
+        // 重置每回合的移动距离（闭关结算之后，期满出关的弟子本回合即可走动）
+        for disciple in self.sect.alive_disciples_mut() {
+            let modifiers: Vec<&Modifier> = sect_modifiers
+                .get(&disciple.id)
+                .map(|mods| mods.iter().collect())
+                .unwrap_or_default();
+            disciple.moves_remaining = disciple.moves_per_turn(&modifiers);
+        }
+
+        // 炼丹队列每回合自动开一炉
+        if let Some(message) = self.sect.refine_from_queue(&mut rand::thread_rng()) {
+            self.log(LogCategory::Alchemy, message);
+        }
+
+        // 2. 招募弟子
+        if self.is_web_mode {
+            // Web模式：每回合换一批候选，由玩家挑选招募
+            self.recruitment_pool = self.recruitment_system.generate_pool(self.sect.reputation, &mut rand::thread_rng());
+            let names: Vec<String> = self
+                .recruitment_pool
+                .iter()
+                .map(|d| format!("{} ({})", d.name, self.disciple_type_str(d)))
+                .collect();
+            self.log(LogCategory::Recruitment, format!("{} 前来拜山", names.join("、")));
+        } else if let Some(disciple) = self.recruitment_system.try_recruit(&self.sect, &mut rand::thread_rng()) {
+            self.log(
+                LogCategory::Recruitment,
+                format!("{} ({}) 前来拜山", disciple.name, self.disciple_type_str(&disciple)),
+            );
+            // 命令行模式：直接招募
+            UI::success(&format!(
+                "新弟子加入：{} ({})",
+                disciple.name,
+                self.disciple_type_str(&disciple)
+            ));
+            self.sect.recruit_disciple(disciple);
         }
 
         // 3. 清理过期任务
         self.remove_expired_tasks();
+        self.warn_expiring_tasks();
 
         // 4. 生成新任务
-        let mut new_tasks = self.map.get_available_tasks();
+        // 宗门等级不足时不开放高风险任务
+        let max_risk = self.sect.sect_rank().max_task_risk();
+        let mut new_tasks = self.map.get_available_tasks(max_risk, &mut rand::thread_rng());
         for task in &mut new_tasks {
             task.created_turn = self.sect.year;
         }
@@ -177,6 +1020,19 @@ impl InteractiveGame {
             .collect();
 
         self.current_tasks.extend(filtered_tasks);
+        self.refresh_task_locks();
+
+        // 有弟子卡在瓶颈时发布“感悟天道”任务
+        let bottlenecked = self.sect.alive_disciples().iter().any(|d| d.cultivation.bottleneck);
+        if bottlenecked && !self.current_tasks.iter().any(|t| t.is_bottleneck_insight()) {
+            let task_id = self.current_tasks.iter().map(|t| t.id + 1).max().unwrap_or(0);
+            let mut task = Task::bottleneck_insight(task_id);
+            task.created_turn = self.sect.year;
+            self.current_tasks.push(task);
+        }
+
+        // 守卫宗门任务置顶
+        self.current_tasks.sort_by_key(|t| !t.is_sect_defense());
 
         // 初始化新任务的分配记录
         let existing_task_ids: Vec<usize> = self.task_assignments.iter().map(|a| a.task_id).collect();
@@ -187,6 +1043,7 @@ impl InteractiveGame {
                     disciple_ids: Vec::new(),
                     started_turn: None,
                     progress: 0,
+                    ..Default::default()
                 });
             }
         }
@@ -195,7 +1052,49 @@ impl InteractiveGame {
         self.check_breakthroughs();
 
         // 6. 地图更新
-        self.map.update();
+        let was_invaded = self.map.get_sect_invasion().is_some();
+        // 有弟子执行任务的地点视为有人驻守
+        let guarded: std::collections::HashSet<String> = self
+            .current_tasks
+            .iter()
+            .filter(|t| self.task_assignments.iter().any(|a| a.task_id == t.id && !a.disciple_ids.is_empty()))
+            .filter_map(|t| t.location_id.clone())
+            .collect();
+        let patrols = self.patrol_zones();
+        let map_update = self.map.update(&guarded, &patrols, &mut rand::thread_rng());
+        for interception in map_update.patrol_interceptions {
+            if let Some(disciple) = self.sect.disciples.iter().find(|d| d.id == interception.disciple_id) {
+                let message = format!("{} 巡逻时拦下了 {}", disciple.name, interception.monster_name);
+                self.log(LogCategory::Invasion, message);
+            }
+        }
+        for occupation in map_update.rival_occupations {
+            self.cancel_location_tasks(&occupation.location_id);
+            self.log(
+                LogCategory::Invasion,
+                format!("{} 趁无人驻守，占据了秘境 {}", occupation.rival_name, occupation.realm_name),
+            );
+        }
+        for name in map_update.destroyed_villages {
+            // 村庄荒废后，尚未开始的任务随之作废
+            self.cancel_location_tasks(&format!("village_{}", name));
+            self.log(LogCategory::Invasion, format!("{} 被妖魔劫掠一空，从此荒废", name));
+        }
+        // 长期无人守卫的地点失守
+        for fallen in self.map.occupy_undefended_locations() {
+            self.cancel_location_tasks(&fallen.location_id);
+            self.sect.add_reputation(-LOCATION_FALL_REPUTATION_PENALTY);
+            self.log(
+                LogCategory::Invasion,
+                format!("{}，宗门声望-{}", fallen.describe(), LOCATION_FALL_REPUTATION_PENALTY),
+            );
+        }
+        if !was_invaded {
+            if let Some(invasion) = self.map.get_sect_invasion() {
+                let message = format!("{} 来袭，宗门告急！", invasion.monster_name);
+                self.log(LogCategory::Invasion, message);
+            }
+        }
 
         // 7. 同步战斗任务位置与怪物位置
         self.sync_combat_task_positions();
@@ -203,6 +1102,9 @@ impl InteractiveGame {
         // 8. 检查守卫任务有效性（妖魔是否已离开）
         self.check_and_remove_invalid_defense_tasks();
 
+        // 9. 建筑竣工、突破、声望等可能达成成就
+        self.check_achievements();
+
         if !self.is_web_mode {
             UI::wait_for_enter("\n按回车键继续...");
         }
@@ -375,7 +1277,9 @@ impl InteractiveGame {
             match choice {
                 Some(1) => self.assign_single_task(),
                 Some(2) => self.unassign_task(),
-                Some(3) => self.auto_assign_remaining(),
+                Some(3) => {
+                    self.auto_assign_remaining(AssignStrategy::FirstFit);
+                }
                 Some(4) => break,
                 _ => {}
             }
@@ -519,74 +1423,842 @@ impl InteractiveGame {
         UI::wait_for_enter("\n按回车继续...");
     }
 
-    /// 自动分配剩余任务
-    pub fn auto_assign_remaining(&mut self) {
-        let mut assigned_count = 0;
+    /// 按指定策略自动分配剩余任务，返回分配的任务数
+    pub fn auto_assign_remaining(&mut self, strategy: AssignStrategy) -> usize {
+        let assigned_count = self.auto_assign_disciples(strategy, None).len();
+
+        if !self.is_web_mode {
+            UI::success(&format!("自动分配了 {} 个任务（{}）", assigned_count, strategy.name()));
+            UI::wait_for_enter("\n按回车继续...");
+        }
 
-        // 收集需要分配的任务ID和弟子ID对
-        let mut assignments_to_make = Vec::new();
+        assigned_count
+    }
 
-        for task in &self.current_tasks {
-            // 查找该任务的分配记录
-            let assignment = self.task_assignments.iter().find(|a| a.task_id == task.id);
+    /// 按指定策略为空闲弟子（可限定范围）自动分配任务，返回 (任务ID, 弟子ID) 列表
+    pub fn auto_assign_disciples(&mut self, strategy: AssignStrategy, only: Option<&[usize]>) -> Vec<(usize, usize)> {
+        // 先分配守卫宗门任务，再分配其他战斗任务，好战的弟子不会被其他任务占用
+        let mut tasks: Vec<&Task> = self
+            .current_tasks
+            .iter()
+            .filter(|t| {
+                self.task_assignments
+                    .iter()
+                    .any(|a| a.task_id == t.id && !a.has_disciples())
+            })
+            .collect();
+        tasks.sort_by_key(|t| {
+            (!t.is_sect_defense(), !matches!(t.task_type, crate::task::TaskType::Combat(_)))
+        });
+
+        // 空闲弟子
+        let busy_disciples: std::collections::HashSet<usize> =
+            self.task_assignments.iter().flat_map(|a| a.disciple_ids.iter().copied()).collect();
+        let idle: Vec<&Disciple> = self
+            .sect
+            .alive_disciples()
+            .into_iter()
+            .filter(|d| !busy_disciples.contains(&d.id))
+            .filter(|d| only.map_or(true, |ids| ids.contains(&d.id)))
+            // 高疲劳弟子需要休息
+            .filter(|d| !d.is_fatigued())
+            .collect();
 
-            if let Some(assignment) = assignment {
-                if assignment.has_disciples() {
-                    continue; // 已分配，跳过
+        // 列出所有可行的 (任务, 弟子) 组合，按策略排序后贪心匹配
+        let mut candidates = Vec::new();
+        for (task_index, task) in tasks.iter().enumerate() {
+            for (disciple_index, disciple) in idle.iter().enumerate() {
+                if !task.is_disciple_at_valid_position(&disciple.position) || !task.is_suitable_for_disciple(disciple) {
+                    continue;
                 }
 
-                // 找到适合的且未被分配任务的弟子
-                let suitable: Vec<&Disciple> = self
-                    .sect
-                    .alive_disciples()
-                    .into_iter()
-                    .filter(|d| {
-                        task.is_suitable_for_disciple(d) &&
-                        // 检查弟子是否在任务位置（如果任务有位置要求）
-                        task.position.as_ref().map_or(true, |task_pos| {
-                            d.position.x == task_pos.x && d.position.y == task_pos.y
-                        }) &&
-                        // 确保该弟子还没有被分配任务
-                        !self.task_assignments.iter().any(|a| a.contains_disciple(d.id)) &&
-                        // 也不在待分配列表中
-                        !assignments_to_make.iter().any(|(_, did)| *did == d.id)
-                    })
-                    .collect();
+                // 优先交给专精对口的弟子，战斗任务也优先交给好战的弟子
+                let favored = disciple.specialization.is_some_and(|s| s.favors(&task.task_type))
+                    || (matches!(task.task_type, crate::task::TaskType::Combat(_))
+                        && disciple.personality == Some(Personality::Belligerent));
+                let not_preferred = !favored;
+                let score = match strategy {
+                    AssignStrategy::FirstFit => 0,
+                    AssignStrategy::MaxReward => disciple.preview_task_reward(task, &[]).progress as i64,
+                    AssignStrategy::CultivationPath => disciple
+                        .cultivation
+                        .cultivation_path
+                        .as_ref()
+                        .map(|path| {
+                            let (completed, required) = path.progress_by_type(task.get_task_type_str());
+                            required.saturating_sub(completed) as i64
+                        })
+                        .unwrap_or(0),
+                };
+                // 守卫宗门任务之外，弟子的长期偏好先于策略得分
+                let deviates = disciple.deviates_from_preference(task);
+                // FirstFit 按任务顺序逐个分配；其余策略守卫宗门任务仍然优先，再按得分从高到低
+                let key = match strategy {
+                    AssignStrategy::FirstFit => (!task.is_sect_defense(), deviates, task_index as i64, not_preferred),
+                    _ => (!task.is_sect_defense(), deviates, -score, not_preferred),
+                };
+                candidates.push(((key, task_index, disciple_index), task.id, disciple.id));
+            }
+        }
+        candidates.sort_by_key(|&(order, _, _)| order);
 
-                if let Some(disciple) = suitable.first() {
-                    assignments_to_make.push((task.id, disciple.id));
-                }
+        let mut assignments_to_make: Vec<(usize, usize)> = Vec::new();
+        for (_, task_id, disciple_id) in candidates {
+            if assignments_to_make
+                .iter()
+                .any(|&(t, d)| t == task_id || d == disciple_id)
+            {
+                continue;
             }
+            assignments_to_make.push((task_id, disciple_id));
         }
 
         // 执行分配
+        let mut assigned = Vec::new();
         for (task_id, disciple_id) in assignments_to_make {
             if let Some(assignment) = self.task_assignments.iter_mut().find(|a| a.task_id == task_id) {
                 assignment.add_disciple(disciple_id);
-                assigned_count += 1;
+                assigned.push((task_id, disciple_id));
             }
         }
+        assigned
+    }
 
-        if !self.is_web_mode {
-            UI::success(&format!("自动分配了 {} 个任务", assigned_count));
-            UI::wait_for_enter("\n按回车继续...");
+    /// 年度收入明细：地图产出加上建筑收入加成
+    pub fn income_report(&self) -> IncomeReport {
+        let sources = self.map.income_breakdown(self.sect.reputation);
+        let map_income: u32 = sources.iter().map(|(_, income)| income).sum();
+        let building_bonuses = self.sect.building_income_bonuses(map_income);
+        let total = map_income + building_bonuses.iter().map(|(_, bonus)| bonus).sum::<u32>();
+        IncomeReport { sources, building_bonuses, total }
+    }
+
+    /// 构建本回合的任务匹配索引
+    pub fn task_matcher(&self) -> TaskMatcher<'_> {
+        TaskMatcher::new(&self.sect.disciples, &self.task_assignments)
+    }
+
+    /// 弟子是否正在执行任务
+    pub fn is_disciple_busy(&self, disciple_id: usize) -> bool {
+        self.task_assignments.iter().any(|a| a.contains_disciple(disciple_id))
+    }
+
+    /// 筛选符合条件的存活弟子
+    pub fn filter_disciples(&self, filter: &DiscipleFilter) -> Vec<usize> {
+        self.sect
+            .alive_disciples()
+            .into_iter()
+            .filter(|d| filter.matches(d, self.is_disciple_busy(d.id)))
+            .map(|d| d.id)
+            .collect()
+    }
+
+    /// 移动弟子：校验移动范围、可通行性、寻路消耗与剩余移动力，到达后采集成熟草药
+    pub fn move_disciple(&mut self, disciple_id: usize, to: Position) -> Result<MoveOutcome, ActionError> {
+        let disciple = self
+            .sect
+            .disciples
+            .iter()
+            .find(|d| d.id == disciple_id)
+            .ok_or_else(|| ActionError::new("DISCIPLE_NOT_FOUND", "弟子不存在".to_string()))?;
+        let from = disciple.position;
+        let disciple_name = disciple.name.clone();
+        let sect_modifiers = self.sect.get_applicable_modifiers_owned(disciple);
+        let sect_modifier_refs: Vec<&Modifier> = sect_modifiers.iter().collect();
+        let max_range = disciple.get_effective_movement_range_with_sect_modifiers(&sect_modifier_refs);
+        let moves_remaining = disciple.moves_remaining;
+
+        // 检查移动距离是否在范围内（曼哈顿距离）
+        let distance = ((to.x - from.x).abs() + (to.y - from.y).abs()) as u32;
+        if distance > max_range {
+            return Err(ActionError::new(
+                "MOVEMENT_OUT_OF_RANGE",
+                format!("移动距离({})超出范围！{}的最大移动距离为{}格", distance, disciple_name, max_range),
+            ));
+        }
+
+        // 检查目标位置是否可通行
+        if !self.map.is_passable(to.x, to.y) {
+            return Err(ActionError::new(
+                "POSITION_BLOCKED",
+                format!("目标位置 ({}, {}) 不可通行！该位置可能是山脉", to.x, to.y),
+            ));
+        }
+
+        // 寻路（山脉不可通行，水域消耗翻倍）
+        let path_cost = match self.map.find_path(from, to, max_range) {
+            Some(path) => self.map.path_cost(&path),
+            None => {
+                return Err(ActionError::new(
+                    "PATH_BLOCKED",
+                    format!("{}无法在{}格移动力内到达 ({}, {})，道路被阻挡", disciple_name, max_range, to.x, to.y),
+                ));
+            }
+        };
+
+        // 检查本回合剩余移动距离
+        if path_cost > moves_remaining {
+            return Err(ActionError::new(
+                "INSUFFICIENT_MOVES",
+                format!("本回合移动距离不足！需要{}格，剩余{}格", path_cost, moves_remaining),
+            ));
+        }
+
+        let disciple = self.sect.disciples.iter_mut().find(|d| d.id == disciple_id).unwrap();
+        disciple.moves_remaining -= path_cost;
+        disciple.move_to(to);
+        let moves_remaining = disciple.moves_remaining;
+
+        // 检查并采集草药（仅成熟草药可采集）
+        let (collected_herb, immature_herb) = match self.map.collect_herb_at(to.x, to.y) {
+            Ok(Some(herb)) => {
+                self.sect.add_herb(&herb.name, herb.quality);
+                (Some(herb), None)
+            }
+            Ok(None) => (None, None),
+            Err(herb) => (None, Some(herb)),
+        };
+
+        // 踩中机缘点触发奇遇，机缘点随即消失
+        let opportunity = self.map.take_opportunity_at(to.x, to.y).map(|opportunity| {
+            let mut rng = rand::thread_rng();
+            let kind = self.map.roll_opportunity_reward(&mut rng);
+            let gain = self.resolve_opportunity(disciple_id, kind, &mut rng);
+            self.log(
+                LogCategory::Opportunity,
+                format!("{}于{}觅得机缘：{}", disciple_name, opportunity.name, gain.describe()),
+            );
+            OpportunityEncounter { name: opportunity.name, gain }
+        });
+
+        Ok(MoveOutcome { disciple_name, from, moves_remaining, collected_herb, immature_herb, opportunity })
+    }
+
+    /// 记录因仓储已满而浪费的灵石
+    fn log_wasted_spirit_stones(&mut self, wasted: u32) {
+        if wasted > 0 {
+            self.log(
+                LogCategory::Income,
+                format!("库房已满（上限{}），{} 灵石无处存放而散失", self.sect.spirit_stone_capacity(), wasted),
+            );
+        }
+    }
+
+    /// 落实一次奇遇：功法、丹方、天赋无从落实时（已全部掌握）改为发放灵石
+    fn resolve_opportunity<R: Rng>(&mut self, disciple_id: usize, kind: OpportunityRewardKind, rng: &mut R) -> OpportunityGain {
+        let disciple = self.sect.disciples.iter_mut().find(|d| d.id == disciple_id).unwrap();
+        let gain = match kind {
+            OpportunityRewardKind::TechniqueScroll => {
+                let mut candidates: Vec<Technique> = Technique::all_techniques()
+                    .into_iter()
+                    .filter(|t| disciple.can_learn_technique(t).is_ok())
+                    .collect();
+                if candidates.is_empty() {
+                    None
+                } else {
+                    let technique = candidates.swap_remove(rng.gen_range(0..candidates.len()));
+                    let name = technique.name.clone();
+                    disciple.learn_technique(technique).ok().map(|_| OpportunityGain::Technique(name))
+                }
+            }
+            OpportunityRewardKind::TalentAwakening => {
+                let mut candidates: Vec<TalentType> = TalentType::ALL
+                    .into_iter()
+                    .filter(|t| disciple.talent_level(t) < TALENT_MAX_LEVEL)
+                    .collect();
+                if candidates.is_empty() {
+                    None
+                } else {
+                    let talent = candidates.swap_remove(rng.gen_range(0..candidates.len()));
+                    // 机缘必定成长：以零点骰值结算
+                    disciple.resolve_talent_growth(talent.clone(), 0.0).map(|level| OpportunityGain::TalentAwakened(talent, level))
+                }
+            }
+            OpportunityRewardKind::PillRecipe => {
+                let locked: Vec<PillType> = PillRecipe::all_recipes()
+                    .into_iter()
+                    .filter(|r| !self.sect.is_recipe_unlocked(r))
+                    .map(|r| r.pill_type)
+                    .collect();
+                if locked.is_empty() {
+                    None
+                } else {
+                    let pill_type = locked[rng.gen_range(0..locked.len())];
+                    self.sect.discovered_recipes.push(pill_type);
+                    Some(OpportunityGain::Recipe(pill_type))
+                }
+            }
+            OpportunityRewardKind::SpiritStones => None,
+        };
+
+        gain.unwrap_or_else(|| {
+            let amount = rng.gen_range(OPPORTUNITY_SPIRIT_STONES);
+            let wasted = self.sect.add_spirit_stones(amount);
+            self.log_wasted_spirit_stones(wasted);
+            OpportunityGain::SpiritStones(amount - wasted)
+        })
+    }
+
+    /// 编队移动：为每名弟子分配目标格或其周围的一个空位（由近及远），寻路前往；
+    /// 移动力不足时沿路径走到本回合能到达的最远处
+    pub fn move_formation(&mut self, disciple_ids: &[usize], target: Position) -> Vec<FormationMoveResult> {
+        // 离目标近的弟子优先占据内圈位置
+        let mut ordered: Vec<usize> = disciple_ids.to_vec();
+        ordered.sort_by_key(|id| {
+            self.sect.disciples.iter().find(|d| d.id == *id)
+                .map_or(i32::MAX, |d| (d.position.x - target.x).abs() + (d.position.y - target.y).abs())
+        });
+
+        let mut slots: Vec<Position> = Vec::new();
+        for dy in -FORMATION_RADIUS..=FORMATION_RADIUS {
+            for dx in -FORMATION_RADIUS..=FORMATION_RADIUS {
+                let slot = Position { x: target.x + dx, y: target.y + dy };
+                if dx.abs() + dy.abs() <= FORMATION_RADIUS && self.map.is_passable(slot.x, slot.y) {
+                    slots.push(slot);
+                }
+            }
+        }
+        slots.sort_by_key(|p| ((p.x - target.x).abs() + (p.y - target.y).abs(), p.y, p.x));
+
+        let search_cost = (self.map.width * self.map.height * 2) as u32;
+        let mut taken = vec![false; slots.len()];
+        let mut results = Vec::new();
+        for id in ordered {
+            let Some(disciple) = self.sect.disciples.iter().find(|d| d.id == id && d.is_alive()) else {
+                results.push(FormationMoveResult {
+                    disciple_id: id,
+                    disciple_name: String::new(),
+                    destination: None,
+                    result: Err(ActionError::new("DISCIPLE_NOT_FOUND", "弟子不存在".to_string())),
+                });
+                continue;
+            };
+            let (from, disciple_name) = (disciple.position, disciple.name.clone());
+            let sect_modifiers = self.sect.get_applicable_modifiers_owned(disciple);
+            let sect_modifier_refs: Vec<&Modifier> = sect_modifiers.iter().collect();
+            let budget = disciple
+                .moves_remaining
+                .min(disciple.get_effective_movement_range_with_sect_modifiers(&sect_modifier_refs));
+
+            // 占据最靠近目标且能走到的空位
+            let assigned = (0..slots.len())
+                .filter(|&i| !taken[i])
+                .find_map(|i| self.map.find_path(from, slots[i], search_cost).map(|path| (i, path)));
+            let Some((slot_index, path)) = assigned else {
+                let message = format!("{}无法抵达目标附近的任何空位", disciple_name);
+                results.push(FormationMoveResult {
+                    disciple_id: id,
+                    disciple_name,
+                    destination: None,
+                    result: Err(ActionError::new("FORMATION_NO_SLOT", message)),
+                });
+                continue;
+            };
+            taken[slot_index] = true;
+
+            // 沿路径走到移动力允许的最远处
+            let mut stop = path[0];
+            let mut spent = 0;
+            for step in path.iter().skip(1) {
+                spent += self.map.move_cost(step.x, step.y).unwrap_or(0);
+                if spent > budget {
+                    break;
+                }
+                stop = *step;
+            }
+
+            let result = if stop.x == from.x && stop.y == from.y {
+                if path.len() == 1 {
+                    Ok(from)
+                } else {
+                    Err(ActionError::new(
+                        "INSUFFICIENT_MOVES",
+                        format!("{}本回合移动力不足，无法向集结点前进", disciple_name),
+                    ))
+                }
+            } else {
+                self.move_disciple(id, stop).map(|_| stop)
+            };
+            results.push(FormationMoveResult { disciple_id: id, disciple_name, destination: Some(slots[slot_index]), result });
+        }
+        // 按请求顺序返回
+        results.sort_by_key(|r| disciple_ids.iter().position(|id| *id == r.disciple_id));
+        results
+    }
+
+    /// 弟子姓名（以顿号分隔）
+    fn disciple_names(&self, disciple_ids: &[usize]) -> String {
+        disciple_ids
+            .iter()
+            .filter_map(|id| self.sect.disciples.iter().find(|d| d.id == *id))
+            .map(|d| d.name.as_str())
+            .collect::<Vec<_>>()
+            .join("、")
+    }
+
+    /// 弟子每回合的移动力（计入宗门加成、伤势与闭关）
+    fn disciple_moves_per_turn(&self, disciple: &Disciple) -> u32 {
+        let sect_modifiers = self.sect.get_applicable_modifiers_owned(disciple);
+        let sect_modifier_refs: Vec<&Modifier> = sect_modifiers.iter().collect();
+        disciple.moves_per_turn(&sect_modifier_refs)
+    }
+
+    /// 弟子前往目的地所需的回合数，无路可达返回 None
+    fn travel_turns(&self, disciple: &Disciple, to: Position) -> Option<u32> {
+        let path = self.map.find_path(disciple.position, to, u32::MAX)?;
+        let moves = self.disciple_moves_per_turn(disciple).max(1);
+        Some((self.map.path_cost(&path) + moves - 1) / moves)
+    }
+
+    /// 规划弟子前往任务地点的远征，返回预计路程回合数（弟子已在任务地点返回 None）
+    pub fn plan_expedition(&self, disciple_id: usize, task_id: usize) -> Result<Option<u32>, ActionError> {
+        let task = self
+            .current_tasks
+            .iter()
+            .find(|t| t.id == task_id)
+            .ok_or_else(|| ActionError::new("TASK_NOT_FOUND", "任务不存在".to_string()))?;
+        let disciple = self
+            .sect
+            .disciples
+            .iter()
+            .find(|d| d.id == disciple_id)
+            .ok_or_else(|| ActionError::new("DISCIPLE_NOT_FOUND", "弟子不存在".to_string()))?;
+        if task.is_disciple_at_valid_position(&disciple.position) {
+            return Ok(None);
+        }
+        let Some(target) = expedition_target(task, disciple.position) else {
+            return Ok(None);
+        };
+
+        // 已开始的任务不再等人，中途加入须本人在场
+        if self.task_assignments.iter().any(|a| a.task_id == task_id && a.started_turn.is_some()) {
+            return Err(ActionError::new(
+                "TASK_ALREADY_STARTED",
+                format!("任务「{}」已开始，{}需先抵达 ({}, {}) 才能加入", task.name, disciple.name, target.x, target.y),
+            ));
+        }
+        self.travel_turns(disciple, target).map(Some).ok_or_else(|| {
+            ActionError::new(
+                "PATH_BLOCKED",
+                format!("{}无法抵达任务地点 ({}, {})，道路被阻挡", disciple.name, target.x, target.y),
+            )
+        })
+    }
+
+    /// 弟子是否愿意接受指派：与队中仇敌同行、或高傲（按 roll 判定）时抗命，
+    /// 道心达标或已被说服的弟子总会接受；planned 为同批一并派往该任务的弟子，与已在队中者同样视为同行
+    pub fn check_assignment_consent(
+        &self,
+        disciple_id: usize,
+        task_id: usize,
+        planned: &[usize],
+        roll: f64,
+    ) -> Result<(), ActionError> {
+        let disciple = self
+            .sect
+            .disciples
+            .iter()
+            .find(|d| d.id == disciple_id)
+            .ok_or_else(|| ActionError::new("DISCIPLE_NOT_FOUND", "弟子不存在".to_string()))?;
+        let sect_modifiers = self.sect.get_applicable_modifiers_owned(disciple);
+        let sect_modifier_refs: Vec<&Modifier> = sect_modifiers.iter().collect();
+        if disciple.persuaded || disciple.get_effective_dao_heart_with_sect_modifiers(&sect_modifier_refs) >= REFUSAL_DAO_HEART_THRESHOLD {
+            return Ok(());
+        }
+
+        let enemy = self
+            .task_assignments
+            .iter()
+            .find(|a| a.task_id == task_id)
+            .into_iter()
+            .flat_map(|a| &a.disciple_ids)
+            .chain(planned)
+            .filter(|id| **id != disciple_id)
+            .find(|id| disciple.get_relationship(**id).is_some_and(|r| r.is_sworn_enemy()))
+            .and_then(|id| self.sect.disciples.iter().find(|d| d.id == *id));
+        if let Some(enemy) = enemy {
+            return Err(ActionError::new(
+                "DISCIPLE_REFUSED",
+                format!("{}与{}势同水火，拒绝与其同行", disciple.name, enemy.name),
+            ));
+        }
+        if disciple.personality == Some(Personality::Arrogant) && roll < ARROGANT_REFUSAL_CHANCE {
+            let task_name = self.current_tasks.iter().find(|t| t.id == task_id).map_or("", |t| t.name.as_str());
+            return Err(ActionError::new(
+                "DISCIPLE_REFUSED",
+                format!("{}心高气傲，不屑接下「{}」", disciple.name, task_name),
+            ));
+        }
+        Ok(())
+    }
+
+    /// 将弟子派往任务：不在任务地点时踏上远征，返回预计路程回合数
+    pub fn dispatch_to_task(&mut self, task_id: usize, disciple_id: usize) -> Result<Option<u32>, ActionError> {
+        let travel_turns = self.plan_expedition(disciple_id, task_id)?;
+        let home = self.sect.disciples.iter_mut().find(|d| d.id == disciple_id).map(|d| {
+            // 说服只管一次指派
+            d.persuaded = false;
+            d.position
+        });
+        let assignment = self
+            .task_assignments
+            .iter_mut()
+            .find(|a| a.task_id == task_id)
+            .ok_or_else(|| ActionError::new("ASSIGNMENT_NOT_FOUND", "任务分配记录不存在".to_string()))?;
+        assignment.add_disciple(disciple_id);
+        if let (Some(_), Some(home)) = (travel_turns, home) {
+            assignment.begin_expedition(home);
         }
+        Ok(travel_turns)
     }
 
-    /// 执行回合任务，返回任务执行结果
-    pub fn execute_turn(&mut self) -> Vec<TaskResult> {
+    /// 按顺序整批派遣（每项为任务ID与弟子ID列表），与单个派遣一样规划远征；
+    /// 任一派遣失败时撤回本批已生效的派遣，返回失败项的下标、弟子ID与原因
+    pub fn dispatch_batch(&mut self, batch: &[(usize, &[usize])]) -> Result<(), (usize, usize, ActionError)> {
+        let assignments = self.task_assignments.clone();
+        let persuaded: Vec<(usize, bool)> = self.sect.disciples.iter().map(|d| (d.id, d.persuaded)).collect();
+        for (index, &(task_id, disciple_ids)) in batch.iter().enumerate() {
+            for &disciple_id in disciple_ids {
+                // 已在队中的弟子不重复派遣，以免重置远征出发地
+                if self.task_assignments.iter().any(|a| a.task_id == task_id && a.contains_disciple(disciple_id)) {
+                    continue;
+                }
+                if let Err(error) = self.dispatch_to_task(task_id, disciple_id) {
+                    self.task_assignments = assignments;
+                    for disciple in &mut self.sect.disciples {
+                        if let Some((_, flag)) = persuaded.iter().find(|(id, _)| *id == disciple.id) {
+                            disciple.persuaded = *flag;
+                        }
+                    }
+                    return Err((index, disciple_id, error));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 弟子当前任务的子状态与预计剩余回合数（前往中的队伍按最慢的成员估算路程）
+    pub fn disciple_task_status(&self, disciple_id: usize) -> Option<DiscipleTaskStatus> {
+        let disciple = self.sect.disciples.iter().find(|d| d.id == disciple_id)?;
+        if let Some(assignment) = self.task_assignments.iter().find(|a| a.contains_disciple(disciple_id)) {
+            let task = self.current_tasks.iter().find(|t| t.id == assignment.task_id)?;
+            let travel = match assignment.phase {
+                AssignmentPhase::Traveling => assignment
+                    .disciple_ids
+                    .iter()
+                    .filter_map(|id| self.sect.disciples.iter().find(|d| d.id == *id))
+                    .filter(|d| !task.is_disciple_at_valid_position(&d.position))
+                    .filter_map(|d| expedition_target(task, d.position).and_then(|to| self.travel_turns(d, to)))
+                    .max()
+                    .unwrap_or(0),
+                _ => 0,
+            };
+            return Some(DiscipleTaskStatus {
+                task_id: task.id,
+                task_name: task.name.clone(),
+                duration: task.duration,
+                progress: assignment.progress,
+                phase: assignment.phase,
+                remaining_turns: travel + task.duration.saturating_sub(assignment.progress),
+            });
+        }
+
+        let expedition = self.expedition_returns.iter().find(|e| e.disciple_ids.contains(&disciple_id))?;
+        Some(DiscipleTaskStatus {
+            task_id: expedition.task_id,
+            task_name: expedition.task_name.clone(),
+            duration: expedition.duration,
+            progress: expedition.duration,
+            phase: AssignmentPhase::Returning,
+            remaining_turns: self.travel_turns(disciple, expedition.home).unwrap_or(0),
+        })
+    }
+
+    /// 弟子沿最短路线朝目的地行进一回合的路程，途中可能遭遇妖兽伏击
+    fn march<R: Rng>(&mut self, disciple_id: usize, target: Position, rng: &mut R) {
+        let Some(disciple) = self.sect.disciples.iter().find(|d| d.id == disciple_id && d.is_alive()) else {
+            return;
+        };
+        if disciple.is_at_position(&target) {
+            return;
+        }
+        let moves = self.disciple_moves_per_turn(disciple);
+        let Some(path) = self.map.find_path(disciple.position, target, u32::MAX) else {
+            return;
+        };
+        if moves == 0 {
+            return;
+        }
+        // 伏击失利则负伤滞留原地
+        if rng.gen_bool(EXPEDITION_AMBUSH_CHANCE) && !self.resolve_ambush(disciple_id, rng) {
+            return;
+        }
+
+        let mut spent = 0;
+        let mut reached = path[0];
+        for step in path.iter().skip(1) {
+            spent += self.map.move_cost(step.x, step.y).unwrap_or(u32::MAX);
+            if spent > moves {
+                break;
+            }
+            reached = *step;
+        }
+        if let Some(disciple) = self.sect.disciples.iter_mut().find(|d| d.id == disciple_id) {
+            disciple.move_to(reached);
+        }
+    }
+
+    /// 远征途中遭遇妖兽伏击，与其临时交战，返回是否击退
+    fn resolve_ambush<R: Rng>(&mut self, disciple_id: usize, rng: &mut R) -> bool {
+        let Some(disciple) = self.sect.disciples.iter_mut().find(|d| d.id == disciple_id) else {
+            return true;
+        };
+        let level = Task::calculate_disciple_combat_level(disciple);
+        let ambusher = crate::task::CombatTask {
+            enemy_id: None,
+            enemy_name: "拦路妖兽".to_string(),
+            enemy_level: (level + rng.gen_range(0..=2)).saturating_sub(1),
+            difficulty: 0,
+        };
+        let won = rng.gen_bool(crate::task::combat_success_rate(disciple, &ambusher) as f64);
+        let message = if won {
+            disciple.monsters_slain += 1;
+            format!("{} 远征途中遭{}伏击，将其斩于剑下", disciple.name, ambusher.enemy_name)
+        } else {
+            disciple.add_injury(InjurySeverity::Minor);
+            format!("{} 远征途中遭{}伏击，负伤滞留原地", disciple.name, ambusher.enemy_name)
+        };
+        if won {
+            self.sect.record_monster_slain();
+        }
+        self.log(LogCategory::Invasion, message);
+        won
+    }
+
+    /// 远征队行进一回合：前往中的队伍全员抵达后开始执行，返程的弟子回到出发地后归队
+    fn advance_expeditions<R: Rng>(&mut self, rng: &mut R) {
+        let traveling: Vec<(usize, Vec<usize>)> = self
+            .task_assignments
+            .iter()
+            .filter(|a| a.phase == AssignmentPhase::Traveling)
+            .map(|a| (a.task_id, a.disciple_ids.clone()))
+            .collect();
+        for (task_id, disciple_ids) in traveling {
+            let Some(task) = self.current_tasks.iter().find(|t| t.id == task_id).cloned() else {
+                continue;
+            };
+            for &disciple_id in &disciple_ids {
+                let from = self.sect.disciples.iter().find(|d| d.id == disciple_id).map(|d| d.position);
+                if let Some(target) = from.and_then(|from| expedition_target(&task, from)) {
+                    self.march(disciple_id, target, rng);
+                }
+            }
+
+            let arrived = disciple_ids.iter().all(|id| {
+                self.sect.disciples.iter().any(|d| d.id == *id && task.is_disciple_at_valid_position(&d.position))
+            });
+            if arrived && !disciple_ids.is_empty() {
+                if let Some(assignment) = self.task_assignments.iter_mut().find(|a| a.task_id == task_id) {
+                    assignment.phase = AssignmentPhase::Executing;
+                }
+                self.log(LogCategory::Task, format!("{} 抵达「{}」，着手执行", self.disciple_names(&disciple_ids), task.name));
+            }
+        }
+
+        // 返程途中陨落或被派往新任务的弟子不再返程
+        for mut expedition in std::mem::take(&mut self.expedition_returns) {
+            expedition.disciple_ids.retain(|&id| {
+                !self.is_disciple_busy(id) && self.sect.disciples.iter().any(|d| d.id == id && d.is_alive())
+            });
+            for &disciple_id in &expedition.disciple_ids {
+                self.march(disciple_id, expedition.home, rng);
+            }
+            let (home, away): (Vec<usize>, Vec<usize>) = expedition.disciple_ids.iter().partition(|&&id| {
+                self.sect.disciples.iter().any(|d| d.id == id && d.is_at_position(&expedition.home))
+            });
+            if !home.is_empty() {
+                self.log(LogCategory::Task, format!("{} 完成「{}」后返回出发地", self.disciple_names(&home), expedition.task_name));
+            }
+            if !away.is_empty() {
+                expedition.disciple_ids = away;
+                self.expedition_returns.push(expedition);
+            }
+        }
+    }
+
+    /// 弟子服用库存中的丹药
+    pub fn use_pill(&mut self, disciple_id: usize, pill_type: PillType, grade: PillGrade) -> Result<PillOutcome, ActionError> {
+        let out_of_stock = || ActionError::new("NO_PILLS", format!("{}{}库存不足", grade.name(), pill_type.name()));
+        if self.sect.pill_inventory.get_count(pill_type, grade) == 0 {
+            return Err(out_of_stock());
+        }
+        let index = self
+            .sect
+            .disciples
+            .iter()
+            .position(|d| d.id == disciple_id)
+            .ok_or_else(|| ActionError::new("DISCIPLE_NOT_FOUND", "弟子不存在".to_string()))?;
+        if !self.sect.pill_inventory.consume(pill_type, grade) {
+            return Err(out_of_stock());
+        }
+        Ok(self.sect.disciples[index].take_pill(pill_type, grade, &mut rand::thread_rng()))
+    }
+
+    /// 对所有符合条件的弟子执行同一动作，逐个返回结果
+    pub fn batch_disciple_action(&mut self, filter: &DiscipleFilter, action: &BatchAction) -> Vec<BatchActionResult> {
+        let disciple_ids = self.filter_disciples(filter);
+        let names: HashMap<usize, String> = self
+            .sect
+            .disciples
+            .iter()
+            .filter(|d| disciple_ids.contains(&d.id))
+            .map(|d| (d.id, d.name.clone()))
+            .collect();
+
+        let mut results: Vec<(usize, Result<String, ActionError>)> = Vec::new();
+        match action {
+            BatchAction::UsePill { pill_type, grade } => {
+                for &id in &disciple_ids {
+                    let result = self.use_pill(id, *pill_type, *grade).map(|outcome| {
+                        let mut message = format!("服用了{}{}", grade.name(), pill_type.name());
+                        if outcome.overdosed {
+                            message.push_str("，但本回合服药过多，药力已无法化开");
+                        }
+                        message
+                    });
+                    results.push((id, result));
+                }
+            }
+            BatchAction::MoveTo(to) => {
+                for &id in &disciple_ids {
+                    let result = self
+                        .move_disciple(id, *to)
+                        .map(|outcome| match outcome.opportunity {
+                            Some(encounter) => format!(
+                                "已移动至({}, {})，剩余移动力{}，于{}{}",
+                                to.x, to.y, outcome.moves_remaining, encounter.name, encounter.gain.describe()
+                            ),
+                            None => format!("已移动至({}, {})，剩余移动力{}", to.x, to.y, outcome.moves_remaining),
+                        });
+                    results.push((id, result));
+                }
+            }
+            BatchAction::AutoAssign(strategy) => {
+                let assigned = self.auto_assign_disciples(*strategy, Some(&disciple_ids));
+                for &id in &disciple_ids {
+                    let result = assigned
+                        .iter()
+                        .find(|(_, disciple_id)| *disciple_id == id)
+                        .and_then(|(task_id, _)| self.current_tasks.iter().find(|t| t.id == *task_id))
+                        .map(|task| format!("已分配至「{}」", task.name))
+                        .ok_or_else(|| ActionError::new("NO_SUITABLE_TASK", "没有可分配的合适任务".to_string()));
+                    results.push((id, result));
+                }
+            }
+        }
+
+        results
+            .into_iter()
+            .map(|(disciple_id, result)| BatchActionResult {
+                disciple_id,
+                disciple_name: names.get(&disciple_id).cloned().unwrap_or_default(),
+                result,
+            })
+            .collect()
+    }
+
+    /// 从招募池招募第 index 名候选，按其标价扣除灵石，返回新弟子ID和花费
+    pub fn recruit_candidate(&mut self, index: usize) -> Result<(usize, u32), String> {
+        let candidate = self.recruitment_pool.get(index).ok_or("招募池中没有该候选")?;
+        let price = RecruitmentSystem::candidate_price(candidate);
+        if !self.sect.consume_spirit_stones(price) {
+            return Err(format!("资源不足，招募{}需要{}资源", candidate.name, price));
+        }
+        let disciple = self.recruitment_pool.remove(index);
+        let id = disciple.id;
+        self.log(LogCategory::Recruitment, format!("{} 拜入宗门，花费{}资源", disciple.name, price));
+        self.sect.recruit_disciple(disciple);
+        Ok((id, price))
+    }
+
+    /// 花费灵石刷新招募池
+    pub fn reroll_recruitment_pool(&mut self) -> Result<(), String> {
+        if !self.sect.consume_spirit_stones(RECRUITMENT_POOL_REFRESH_COST) {
+            return Err(format!("资源不足，刷新招募池需要{}资源", RECRUITMENT_POOL_REFRESH_COST));
+        }
+        self.recruitment_pool = self.recruitment_system.generate_pool(self.sect.reputation, &mut rand::thread_rng());
+        Ok(())
+    }
+
+    /// 预览弟子执行任务的收获：按加入该任务现有队伍计算协同加成和仇敌掣肘，与实际结算一致
+    pub fn preview_task_reward(&self, task: &Task, disciple: &Disciple) -> crate::disciple::TaskRewardPreview {
+        let mut team: Vec<&Disciple> = self
+            .task_assignments
+            .iter()
+            .filter(|a| a.task_id == task.id)
+            .flat_map(|a| a.disciple_ids.iter())
+            .filter(|id| **id != disciple.id)
+            .filter_map(|id| self.sect.disciples.iter().find(|d| d.id == *id))
+            .collect();
+        team.push(disciple);
+        let enmity_modifiers = disciple.enmity_modifiers(&team);
+        disciple.preview_team_task_reward(
+            task,
+            &enmity_modifiers.iter().collect::<Vec<_>>(),
+            &team,
+            self.enemy_is_demon(task),
+        )
+    }
+
+    /// 执行回合任务，返回本回合的结算汇总
+    pub fn execute_turn(&mut self) -> TurnSummary {
+        // 结算前保存快照，撤销时回到本回合分配完成、尚未结算的状态
+        self.push_snapshot();
+
+        let stones_before = self.sect.spirit_stones;
+        let contribution_before = self.sect.contribution;
+        let reputation_before = self.sect.reputation;
+        let ready_before: Vec<usize> = self.sect.disciples.iter()
+            .filter(|d| d.cultivation.is_sub_level_complete())
+            .map(|d| d.id)
+            .collect();
+        let mut relationship_changes = Vec::new();
+
         if !self.is_web_mode {
             UI::clear_screen();
             UI::print_title("任务执行结果");
         }
 
         // 更新任务进度并收集完成的任务
-        let mut completed_tasks: Vec<(Vec<usize>, Task)> = Vec::new();
+        let mut completed_tasks: Vec<(Vec<usize>, Task, Option<Position>)> = Vec::new();
 
         for assignment in &mut self.task_assignments {
-            if assignment.has_disciples() {
+            // 远征途中尚未抵达的任务不推进进度
+            if assignment.has_disciples() && assignment.phase == AssignmentPhase::Executing {
+                // 全队精力耗尽、被迫休息时任务停滞
+                let disciples = &self.sect.disciples;
+                let all_resting = assignment.disciple_ids.iter().all(|id| {
+                    disciples.iter().find(|d| d.id == *id).is_some_and(|d| d.is_exhausted())
+                });
+                if all_resting {
+                    continue;
+                }
+
                 // 如果任务刚开始，设置开始回合
                 if assignment.started_turn.is_none() {
+                    // 投资任务开始时投入本金，资源不足则暂不开始
+                    let capital = self.current_tasks.iter()
+                        .find(|t| t.id == assignment.task_id)
+                        .and_then(|t| match &t.task_type {
+                            crate::task::TaskType::Investment(inv) => Some(inv.capital),
+                            _ => None,
+                        });
+                    if let Some(capital) = capital {
+                        if !self.sect.consume_spirit_stones(capital) {
+                            println!("❌ 资源不足，投资任务需要本金 {}", capital);
+                            continue;
+                        }
+                    }
                     assignment.started_turn = Some(self.sect.year);
                 }
 
@@ -596,49 +2268,95 @@ impl InteractiveGame {
                 // 消耗精力和体魄（每回合，每个参与者都消耗）
                 if let Some(task) = self.current_tasks.iter().find(|t| t.id == assignment.task_id) {
                     for &disciple_id in &assignment.disciple_ids {
-                        if let Some(disciple) = self.sect.disciples.iter_mut().find(|d| d.id == disciple_id) {
+                        // 强制休息的弟子本回合不出力
+                        if let Some(disciple) = self.sect.disciples.iter_mut().find(|d| d.id == disciple_id && !d.is_exhausted()) {
                             disciple.consume_energy(task.energy_cost);
                             disciple.consume_constitution(task.constitution_cost);
+                            disciple.add_fatigue(FATIGUE_PER_WORK_TURN);
                         }
                     }
 
                     // 检查任务是否完成
                     if assignment.progress >= task.duration {
-                        completed_tasks.push((assignment.disciple_ids.clone(), task.clone()));
+                        completed_tasks.push((assignment.disciple_ids.clone(), task.clone(), assignment.home));
                     }
                 }
             }
         }
 
+        // 远征队赶路（本回合抵达的队伍下回合开始执行）
+        self.advance_expeditions(&mut rand::thread_rng());
+
         // 执行完成的任务
         let mut results = Vec::new();
-        for (disciple_ids, task) in completed_tasks {
+        for (disciple_ids, task, home) in completed_tasks {
+            // 组队协同加成（按任务开始前的战友关系计算）
+            let synergy = {
+                let team: Vec<&Disciple> = self.sect.disciples.iter()
+                    .filter(|d| disciple_ids.contains(&d.id))
+                    .collect();
+                team_synergy_bonus(&team)
+            };
+
+            // 战斗任务整队按兵力对比结算，其余任务每个参与者各自判定
+            let team_results = if matches!(task.task_type, crate::task::TaskType::Combat(_)) {
+                self.execute_combat_task(&disciple_ids, &task, synergy)
+            } else {
+                let mut team_results: Vec<TaskResult> = Vec::new();
+                for &disciple_id in &disciple_ids {
+                    let settle_once = !team_results.iter().any(|r| r.success);
+                    team_results.push(self.execute_single_task(disciple_id, task.clone(), disciple_ids.len(), synergy, settle_once));
+                }
+                team_results
+            };
+            let task_succeeded = team_results.iter().any(|r| r.success);
+            let task_partial = team_results.iter().any(|r| r.outcome == TaskOutcome::PartialSuccess);
+            results.extend(team_results);
+
             // 更新参与者之间的关系
             if disciple_ids.len() > 1 {
                 let level_ups = self.sect.update_relationship_from_task(&disciple_ids, &task.task_type);
                 for (from_id, to_id, dim, level) in level_ups {
+                    let from_name = self.sect.disciples.iter().find(|d| d.id == from_id).map(|d| d.name.as_str()).unwrap_or("?");
+                    let to_name = self.sect.disciples.iter().find(|d| d.id == to_id).map(|d| d.name.as_str()).unwrap_or("?");
+                    let change = format!("{} 与 {} 的{}关系提升至「{}」", from_name, to_name, dim.name(), level.name());
                     if !self.is_web_mode {
-                        let from_name = self.sect.disciples.iter().find(|d| d.id == from_id).map(|d| d.name.as_str()).unwrap_or("?");
-                        let to_name = self.sect.disciples.iter().find(|d| d.id == to_id).map(|d| d.name.as_str()).unwrap_or("?");
-                        println!("💕 {} 与 {} 的{}关系提升至「{}」！", from_name, to_name, dim.name(), level.name());
+                        println!("💕 {}！", change);
                     }
+                    relationship_changes.push(change);
                 }
             }
 
-            // 为每个参与者执行任务
-            let mut task_succeeded = false;
-            for &disciple_id in &disciple_ids {
-                let result = self.execute_single_task(disciple_id, task.clone());
-                if result.success {
-                    task_succeeded = true;
+            // 部分成功的任务保留一回合供重试（仅一次），其余从当前任务中移除
+            let retry = !task_succeeded && task_partial && !task.retryable;
+            if retry {
+                let current_turn = self.sect.year;
+                if let Some(t) = self.current_tasks.iter_mut().find(|t| t.id == task.id) {
+                    t.open_retry(current_turn);
                 }
-                results.push(result);
+                self.log(LogCategory::Task, format!("任务 [{}] 未竟全功，可在下回合重新派遣弟子重试", task.name));
+            } else {
+                self.current_tasks.retain(|t| t.id != task.id);
             }
-
-            // 从当前任务中移除已完成的任务
-            self.current_tasks.retain(|t| t.id != task.id);
             self.task_assignments.retain(|a| a.task_id != task.id);
 
+            // 远征队完成任务后返回出发地
+            if let Some(home) = home {
+                self.expedition_returns.push(ExpeditionReturn {
+                    task_id: task.id,
+                    task_name: task.name.clone(),
+                    duration: task.duration,
+                    disciple_ids: disciple_ids.clone(),
+                    home,
+                });
+            }
+
+            // 任务链：记录完成并解锁后续任务
+            if task_succeeded {
+                self.sect.completed_task_names.insert(task.name.clone());
+                self.unlock_chained_tasks(&task);
+            }
+
             // 清除妖魔的任务关联和解锁移动
             self.map.clear_monster_task(task.id);
 
@@ -648,64 +2366,324 @@ impl InteractiveGame {
                     if task_succeeded {
                         // 讨伐成功，移除怪物（remove_monster_by_id 会自动清除 is_being_fought）
                         self.map.remove_monster_by_id(enemy_id);
+                        self.sect.record_monster_slain();
                     } else {
                         // 任务失败，清除战斗状态，让怪物可以移动
                         self.map.set_monster_being_fought(enemy_id, false);
                         if task.name.contains("守卫") {
                             // 守卫任务失败，额外解锁 has_active_defense_task
-                            self.map.unlock_monster_by_id(enemy_id);
+                            self.map.unlock_monster_for_defense_task(enemy_id);
                         }
                     }
                 }
             }
+
+            // 讨伐巢穴成功，巢穴被摧毁后不再孵化妖魔
+            if task_succeeded {
+                if let Some(name) = task.location_id.as_deref().and_then(|id| self.map.destroy_nest(id)) {
+                    self.log(LogCategory::Invasion, format!("{} 已被荡平", name));
+                }
+            }
         }
 
-        // 处理结果（资源和声望只计算一次，不重复）
-        let mut processed_tasks: std::collections::HashSet<usize> = std::collections::HashSet::new();
+        self.release_dead_disciple_tasks();
+        self.refresh_task_locks();
+
         for result in &results {
-            if result.success && !processed_tasks.contains(&result.task_id) {
-                self.sect.add_resources(result.resources_gained);
+            let (category, message) = if result.disciple_died {
+                (LogCategory::Death, format!("{} 在战斗中陨落", result.disciple_name))
+            } else if result.success {
+                (LogCategory::Task, format!(
+                    "{} 完成任务，获得修为+{}, 灵石+{}, 贡献+{}, 声望+{}",
+                    result.disciple_name, result.progress_gained, result.resources_gained,
+                    result.contribution_gained, result.reputation_gained
+                ))
+            } else if result.outcome == TaskOutcome::PartialSuccess {
+                (LogCategory::Task, format!(
+                    "{} 任务部分成功（完成度{:.0}%），获得修为+{}, 灵石+{}, 贡献+{}",
+                    result.disciple_name, result.completion * 100.0, result.progress_gained,
+                    result.resources_gained, result.contribution_gained
+                ))
+            } else {
+                (LogCategory::Task, format!("{} 执行任务失败", result.disciple_name))
+            };
+            self.log(category, message);
+        }
+
+        // 处理结果（灵石、贡献和声望只计算一次，不重复；同一任务优先结算成功者的奖励）
+        let mut processed_tasks: std::collections::HashSet<usize> = std::collections::HashSet::new();
+        let mut ordered: Vec<&TaskResult> = results.iter().collect();
+        ordered.sort_by_key(|r| !r.success);
+        let mut wasted = 0;
+        for result in ordered {
+            self.sect.record_task_outcome(result.success);
+            if result.outcome != TaskOutcome::Failure && !processed_tasks.contains(&result.task_id) {
+                wasted += self.sect.add_spirit_stones(result.resources_gained);
+                self.sect.add_contribution(result.contribution_gained);
                 self.sect.add_reputation(result.reputation_gained);
                 processed_tasks.insert(result.task_id);
             }
         }
+        self.log_wasted_spirit_stones(wasted);
+        self.check_achievements();
+
+        if !self.is_web_mode {
+            UI::wait_for_enter("\n按回车键查看回合总结...");
+        }
+
+        let mut finished: Vec<(usize, bool)> = Vec::new();
+        for result in &results {
+            match finished.iter_mut().find(|(id, _)| *id == result.task_id) {
+                Some((_, success)) => *success |= result.success,
+                None => finished.push((result.task_id, result.success)),
+            }
+        }
+        let tasks_completed = finished.iter().filter(|(_, success)| *success).count();
+        TurnSummary {
+            annual_income: self.last_income,
+            spirit_stones_delta: self.sect.spirit_stones as i64 - stones_before as i64,
+            contribution_delta: self.sect.contribution as i64 - contribution_before as i64,
+            reputation_delta: self.sect.reputation - reputation_before,
+            tasks_completed,
+            tasks_failed: finished.len() - tasks_completed,
+            casualties: results.iter().filter(|r| r.disciple_died).map(|r| r.disciple_name.clone()).collect(),
+            breakthrough_ready: self.sect.disciples.iter()
+                .filter(|d| d.is_alive() && d.cultivation.is_sub_level_complete() && !ready_before.contains(&d.id))
+                .map(|d| d.name.clone())
+                .collect(),
+            relationship_changes,
+            task_results: results,
+        }
+    }
+
+    /// 作废某地点尚未开始的任务（地点荒废或失守时调用）
+    fn cancel_location_tasks(&mut self, location_id: &str) {
+        let cancelled: Vec<usize> = self
+            .current_tasks
+            .iter()
+            .filter(|t| t.location_id.as_deref() == Some(location_id))
+            .filter(|t| {
+                !self
+                    .task_assignments
+                    .iter()
+                    .any(|a| a.task_id == t.id && a.started_turn.is_some())
+            })
+            .map(|t| t.id)
+            .collect();
+        self.current_tasks.retain(|t| !cancelled.contains(&t.id));
+        self.task_assignments.retain(|a| !cancelled.contains(&a.task_id));
+    }
+
+    /// 将已故弟子移出任务分配，无人留守的战斗任务解除妖魔锁定
+    /// 返回被释放的 (弟子ID, 任务ID)
+    pub fn release_dead_disciple_tasks(&mut self) -> Vec<(usize, usize)> {
+        let alive: Vec<usize> = self.sect.alive_disciples().iter().map(|d| d.id).collect();
+        let mut released = Vec::new();
+        let mut emptied = Vec::new();
+        for assignment in &mut self.task_assignments {
+            let before = assignment.disciple_ids.len();
+            for &id in assignment.disciple_ids.iter().filter(|id| !alive.contains(id)) {
+                released.push((id, assignment.task_id));
+            }
+            assignment.disciple_ids.retain(|id| alive.contains(id));
+            if before > 0 && assignment.disciple_ids.is_empty() {
+                emptied.push(assignment.task_id);
+            }
+        }
+
+        for task_id in emptied {
+            let Some(task) = self.current_tasks.iter().find(|t| t.id == task_id) else {
+                continue;
+            };
+            if let crate::task::TaskType::Combat(combat) = &task.task_type {
+                if let Some(enemy_id) = combat.enemy_id {
+                    self.map.set_monster_being_fought(enemy_id, false);
+                    if task.name.contains("守卫") {
+                        self.map.unlock_monster_for_defense_task(enemy_id);
+                    }
+                }
+            }
+        }
+        released
+    }
 
-        if !self.is_web_mode {
-            UI::wait_for_enter("\n按回车键查看回合总结...");
+    /// 执行单个任务
+    /// synergy 为组队协同加成，提升成功率和资源奖励
+    /// settle_once 为真时结算整队只结算一次的收益（掉落、势力关系）
+    fn execute_single_task(&mut self, disciple_id: usize, task: Task, team_size: usize, synergy: f32, settle_once: bool) -> TaskResult {
+        // 根据任务类型计算成功率（组队协同提升成功率，投资任务到期按收益率结算，不做成功判定）
+        let disciple = self.sect.disciples.iter().find(|d| d.id == disciple_id);
+        let is_investment = matches!(&task.task_type, crate::task::TaskType::Investment(_));
+        let team: Vec<&Disciple> = disciple.into_iter().collect();
+        let success_rate = task.settlement_success_rate(disciple, &team, synergy, self.enemy_is_demon(&task));
+        let roll = TaskRoll::new(rand::thread_rng().gen(), success_rate, is_investment);
+        self.settle_task(disciple_id, task, team_size, synergy, settle_once, roll)
+    }
+
+    /// 战斗任务按兵力对比整队结算：全队战力之和（计入协同加成）对比敌方战力，
+    /// 压倒性优势时速胜无伤，劣势险胜可能有人负伤，惨败时参战弟子或重伤或阵亡
+    fn execute_combat_task(&mut self, disciple_ids: &[usize], task: &Task, synergy: f32) -> Vec<TaskResult> {
+        let mut rng = rand::thread_rng();
+        let power_ratio = self.team_power_ratio(disciple_ids, task, synergy);
+        let success_rate = crate::task::team_combat_success_rate(power_ratio);
+        let roll = TaskRoll::new(rng.gen(), success_rate, false);
+        if power_ratio >= crate::task::OVERWHELMING_POWER_RATIO {
+            self.log(LogCategory::Task, format!("众弟子以压倒之势速胜 [{}]", task.name));
         }
 
+        let mut results = Vec::new();
+        for (index, &disciple_id) in disciple_ids.iter().enumerate() {
+            let mut result = self.settle_task(disciple_id, task.clone(), disciple_ids.len(), synergy, index == 0, roll);
+            if let Some(combat) = result.combat.as_mut() {
+                combat.success_rate = success_rate as f32;
+            }
+            // 以弱胜强的恶战难免挂彩
+            if roll.success && power_ratio < 1.0 && rng.gen_bool(((1.0 - power_ratio) * 0.5) as f64) {
+                if let Some(disciple) = self.sect.disciples.iter_mut().find(|d| d.id == disciple_id && d.is_alive()) {
+                    disciple.add_injury(InjurySeverity::Minor);
+                    let name = disciple.name.clone();
+                    self.log(LogCategory::Task, format!("{} 在恶战中负了轻伤", name));
+                }
+            }
+            results.push(result);
+        }
         results
     }
 
-    /// 执行单个任务
-    fn execute_single_task(&mut self, disciple_id: usize, task: Task) -> TaskResult {
+    /// 参战弟子战力之和（计入协同加成）与敌方战力之比，非战斗任务返回 0
+    pub fn team_power_ratio(&self, disciple_ids: &[usize], task: &Task, synergy: f32) -> f32 {
+        let crate::task::TaskType::Combat(combat) = &task.task_type else {
+            return 0.0;
+        };
+        let team: Vec<&Disciple> = self.sect.disciples.iter().filter(|d| disciple_ids.contains(&d.id)).collect();
+        crate::task::team_power_ratio(combat, &team, synergy, self.enemy_is_demon(task))
+    }
+
+    /// 战斗任务的敌人是否已入魔（入魔妖兽战力更强）
+    pub fn enemy_is_demon(&self, task: &Task) -> bool {
+        match &task.task_type {
+            crate::task::TaskType::Combat(combat) => combat
+                .enemy_id
+                .and_then(|id| self.map.find_monster(id))
+                .is_some_and(|m| m.is_demon),
+            _ => false,
+        }
+    }
+
+    /// 按判定结果结算单个弟子的任务（奖励、掉落、伤亡）
+    fn settle_task(&mut self, disciple_id: usize, task: Task, team_size: usize, synergy: f32, settle_once: bool, roll: TaskRoll) -> TaskResult {
         let mut rng = rand::thread_rng();
 
-        // 获取弟子信息用于计算成功率
         let disciple = self.sect.disciples.iter().find(|d| d.id == disciple_id);
         let disciple_name = disciple.map(|d| d.name.clone()).unwrap_or_default();
 
         // 判断是否是战斗任务
         let is_combat_task = matches!(&task.task_type, crate::task::TaskType::Combat(_));
 
-        // 根据任务类型计算成功率
-        let success_rate = if let Some(d) = disciple {
-            task.calculate_combat_success_rate(d)
+        // 战斗、探索完成度过低为惨败，其余失败为部分成功
+        let TaskRoll { success, completion } = roll;
+        let is_risky_task = is_combat_task || matches!(&task.task_type, crate::task::TaskType::Exploration(_));
+        let outcome = if success {
+            TaskOutcome::Success
+        } else if is_risky_task && completion < CRUSHING_DEFEAT_COMPLETION {
+            TaskOutcome::Failure
         } else {
-            0.8
+            TaskOutcome::PartialSuccess
         };
-        let success = rng.gen_bool(success_rate);
 
-        if success {
+        // 战斗细节：胜利时按敌我等级差调整资源和声望奖励
+        let combat_detail = match (&task.task_type, disciple) {
+            (crate::task::TaskType::Combat(combat), Some(d)) => Some(CombatDetail::new(d, combat)),
+            _ => None,
+        };
+        // 深入险地的探索按深度提高奖励
+        let reward_multiplier = match &task.task_type {
+            crate::task::TaskType::Exploration(exploration) => exploration.depth_reward_multiplier(),
+            _ => combat_detail.as_ref().map(|c| c.reward_multiplier).unwrap_or(1.0),
+        };
+        let (resources_gained, contribution_gained, reputation_gained) = task.settlement_rewards(reward_multiplier, synergy);
+
+        // 与仇敌共事时互相掣肘
+        let enmity_modifiers = match disciple {
+            Some(d) => {
+                let teammates: Vec<&Disciple> = self.task_assignments.iter()
+                    .filter(|a| a.task_id == task.id)
+                    .flat_map(|a| a.disciple_ids.iter())
+                    .filter_map(|id| self.sect.disciples.iter().find(|m| m.id == *id))
+                    .collect();
+                d.enmity_modifiers(&teammates)
+            }
+            None => Vec::new(),
+        };
+
+        // 讨伐妖魔成功按掉落表结算（组队时只结算一次）
+        let drops = match &task.task_type {
+            crate::task::TaskType::Combat(combat) if success && settle_once => combat
+                .enemy_id
+                .and_then(|id| self.map.find_monster(id))
+                .map(|monster| monster.roll_drops(&mut rng))
+                .unwrap_or_default(),
+            crate::task::TaskType::Exploration(exploration) if success && settle_once => {
+                exploration.roll_depth_drop(&mut rng).into_iter().collect()
+            }
+            _ => Vec::new(),
+        };
+
+        // 势力任务影响宗门与该势力的关系
+        if success && settle_once {
+            if let Some((name, before, after)) = self.map.settle_faction_task(&task) {
+                let message = if before >= 0 && after < 0 {
+                    Some(format!("与{}的关系恶化，对方不再发布友好任务", name))
+                } else if before >= crate::map::FACTION_HOSTILE_THRESHOLD && after < crate::map::FACTION_HOSTILE_THRESHOLD {
+                    Some(format!("与{}彻底交恶，双方势同水火", name))
+                } else if before < 0 && after >= 0 {
+                    Some(format!("与{}重归于好", name))
+                } else {
+                    None
+                };
+                if let Some(message) = message {
+                    self.log(LogCategory::Relationship, message);
+                }
+            }
+            if let Some(outcome) = self.map.settle_rival_war(&task) {
+                if let Some(realm) = &outcome.released_realm {
+                    self.log(LogCategory::Invasion, format!("宗门战告捷，从{}手中夺回{}", outcome.rival_name, realm.trim_start_matches("realm_")));
+                }
+                if outcome.destroyed {
+                    self.cancel_location_tasks(&format!("rival_{}", outcome.rival_name));
+                    self.log(LogCategory::Invasion, format!("{} 弟子折损殆尽，就此覆灭", outcome.rival_name));
+                } else {
+                    self.log(LogCategory::Task, format!("宗门战击败{}，对方元气大伤", outcome.rival_name));
+                }
+            }
+        }
+
+        let mut talent_growth = None;
+        let result = if success {
             if let Some(disciple) = self
                 .sect
                 .disciples
                 .iter_mut()
                 .find(|d| d.id == disciple_id)
             {
-                let progress_gained = disciple.complete_task(&task);
-                disciple.dao_heart =
-                    ((disciple.dao_heart as i32 + task.dao_heart_impact).max(0) as u32).min(100);
+                let talent = crate::disciple::related_talent(&task);
+                let talent_before = talent.as_ref().map_or(0, |t| disciple.talent_level(t));
+                let progress_gained = disciple.complete_task_with_modifiers(
+                    &task,
+                    &enmity_modifiers.iter().collect::<Vec<_>>(),
+                    &mut rng,
+                );
+                disciple.apply_dao_heart_impact(task.dao_heart_impact);
+                disciple.total_resources_contributed += resources_gained / team_size.max(1) as u32;
+                if matches!(&task.task_type, crate::task::TaskType::Combat(c) if c.enemy_id.is_some()) {
+                    disciple.monsters_slain += 1;
+                }
+                talent_growth = talent
+                    .filter(|t| disciple.talent_level(t) > talent_before)
+                    .map(|t| {
+                        let level = disciple.talent_level(&t);
+                        (t, level)
+                    });
 
                 // 获取任务类型字符串
                 use crate::task::TaskType;
@@ -725,10 +2703,14 @@ impl InteractiveGame {
                     disciple_name, task.name
                 );
                 println!(
-                    "   获得: 修为+{}, 资源+{}, 声望+{}",
-                    progress_gained, task.resource_reward, task.reputation_reward
+                    "   获得: 修为+{}, 灵石+{}, 贡献+{}, 声望+{}",
+                    progress_gained, resources_gained, contribution_gained, reputation_gained
                 );
 
+                if team_size > 1 {
+                    println!("   🤝 {}人组队，协同加成 +{:.0}%", team_size, synergy * 100.0);
+                }
+
                 if path_task_completed {
                     let (completed, total) = disciple.cultivation.cultivation_path
                         .as_ref()
@@ -746,10 +2728,17 @@ impl InteractiveGame {
                     disciple_id,
                     disciple_name: disciple_name.clone(),
                     success: true,
-                    resources_gained: task.resource_reward,
-                    reputation_gained: task.reputation_reward,
+                    outcome,
+                    completion,
+                    resources_gained,
+                    contribution_gained,
+                    reputation_gained,
                     progress_gained,
                     disciple_died: false,
+                    combat: combat_detail,
+                    team_size,
+                    synergy_bonus: synergy,
+                    drops,
                 }
             } else {
                 TaskResult {
@@ -757,14 +2746,55 @@ impl InteractiveGame {
                     disciple_id,
                     disciple_name: disciple_name.clone(),
                     success: false,
+                    outcome: TaskOutcome::Failure,
+                    completion: 0.0,
                     resources_gained: 0,
+                    contribution_gained: 0,
                     reputation_gained: 0,
                     progress_gained: 0,
                     disciple_died: false,
+                    combat: combat_detail,
+                    team_size,
+                    synergy_bonus: synergy,
+                    drops: Vec::new(),
                 }
             }
+        } else if outcome == TaskOutcome::PartialSuccess {
+            // 部分成功：按完成度折算奖励，不折损弟子
+            let (resources_gained, contribution_gained, reputation_gained) =
+                task.partial_rewards(reward_multiplier, synergy, completion);
+            let progress_gained = self.sect.disciples.iter_mut()
+                .find(|d| d.id == disciple_id)
+                .map_or(0, |d| {
+                    let full = d.estimate_task_reward(&task, &enmity_modifiers.iter().collect::<Vec<_>>());
+                    let progress = (full as f32 * completion * PARTIAL_REWARD_SHARE) as u32;
+                    d.cultivation.add_progress(progress);
+                    progress
+                });
+            println!(
+                "⚠️ {} 未能完成任务 [{}]（完成度{:.0}%），获得: 修为+{}, 灵石+{}, 贡献+{}",
+                disciple_name, task.name, completion * 100.0, progress_gained, resources_gained, contribution_gained
+            );
+
+            TaskResult {
+                task_id: task.id,
+                disciple_id,
+                disciple_name: disciple_name.clone(),
+                success: false,
+                outcome,
+                completion,
+                resources_gained,
+                contribution_gained,
+                reputation_gained,
+                progress_gained,
+                disciple_died: false,
+                combat: combat_detail,
+                team_size,
+                synergy_bonus: synergy,
+                drops: Vec::new(),
+            }
         } else {
-            // 战斗任务失败，弟子死亡
+            // 战斗惨败，弟子有机会重伤逃生，否则陨落
             let disciple_died = if is_combat_task {
                 if let Some(disciple) = self
                     .sect
@@ -772,11 +2802,17 @@ impl InteractiveGame {
                     .iter_mut()
                     .find(|d| d.id == disciple_id)
                 {
-                    disciple.constitution = 0;  // 设置体魄为0，标记死亡
-                    // 处理弟子死亡（生成传承等）
-                    self.sect.handle_disciple_death(disciple_id);
-                    println!("💀 {} 在执行任务 [{}] 时陨落", disciple_name, task.name);
-                    true
+                    if rng.gen_bool(COMBAT_INJURY_CHANCE) {
+                        disciple.add_injury(InjurySeverity::Serious);
+                        println!("🩸 {} 在执行任务 [{}] 时身受重伤", disciple_name, task.name);
+                        false
+                    } else {
+                        disciple.constitution = 0;  // 设置体魄为0，标记死亡
+                        // 处理弟子死亡（生成传承等）
+                        self.sect.handle_disciple_death(disciple_id, DepartureCause::Battle);
+                        println!("💀 {} 在执行任务 [{}] 时陨落", disciple_name, task.name);
+                        true
+                    }
                 } else {
                     false
                 }
@@ -790,18 +2826,88 @@ impl InteractiveGame {
                 disciple_id,
                 disciple_name: disciple_name.clone(),
                 success: false,
+                outcome,
+                completion,
                 resources_gained: 0,
+                contribution_gained: 0,
                 reputation_gained: 0,
                 progress_gained: 0,
                 disciple_died,
+                combat: combat_detail,
+                team_size,
+                synergy_bonus: synergy,
+                drops: Vec::new(),
+            }
+        };
+
+        // 掉落入库并记录
+        if !result.drops.is_empty() {
+            for drop in &result.drops {
+                self.sect.receive_drop(drop);
+            }
+            let names: Vec<String> = result.drops.iter().map(|d| d.describe()).collect();
+            self.log(LogCategory::Task, format!("{} 完成任务 [{}]，获得掉落：{}", disciple_name, task.name, names.join("、")));
+        }
+
+        // 历练中灵根成长或觉醒
+        if let Some((talent, level)) = talent_growth {
+            let message = if level == 1 {
+                format!("{} 在历练中觉醒了{}", disciple_name, talent.name())
+            } else {
+                format!("{} 的{}精进至 {} 级", disciple_name, talent.name(), level)
+            };
+            self.log(LogCategory::Talent, message);
+        }
+
+        if let Some(disciple) = self
+            .sect
+            .disciples
+            .iter_mut()
+            .find(|d| d.id == disciple_id && d.is_alive())
+        {
+            // 高危险度探索可能受伤，深入险地失败更易受伤并折损寿元
+            if let crate::task::TaskType::Exploration(exploration) = &task.task_type {
+                // 疲劳时更容易受伤
+                let failure_multiplier = if result.outcome == TaskOutcome::Failure { 2.0 } else { 1.0 };
+                let roll = rng.gen::<f32>() / disciple.fatigue_injury_multiplier() / failure_multiplier;
+                if let Some(severity) = exploration_injury(exploration.depth_danger_level(), roll) {
+                    disciple.add_injury(severity);
+                    println!("🩸 {} 在探索中受了{}", disciple_name, severity.name());
+                }
+                if result.outcome == TaskOutcome::Failure && exploration.depth > 0 {
+                    let lifespan_loss = exploration.depth * EXPLORATION_DEPTH_LIFESPAN_LOSS;
+                    disciple.lifespan = disciple.lifespan.saturating_sub(lifespan_loss);
+                    println!("⌛ {} 深陷险地，折损寿元{}年", disciple_name, lifespan_loss);
+                }
+            }
+        }
+
+        // 敌强我弱的恶战之后，道心不稳者可能走火入魔
+        if result.combat.as_ref().is_some_and(|c| c.enemy_level >= c.disciple_level) {
+            self.check_heart_demon(disciple_id);
+        }
+
+        // 医道弟子完成疗伤任务后为同门疗伤
+        if result.success {
+            if let crate::task::TaskType::Auxiliary(aux) = &task.task_type {
+                if aux.skill_required == Some(TalentType::Medical) {
+                    for (patient_id, severity) in self.sect.treat_injuries(disciple_id) {
+                        if let Some(patient) = self.sect.disciples.iter().find(|d| d.id == patient_id) {
+                            println!("💊 {} 治好了 {} 的{}", disciple_name, patient.name, severity.name());
+                        }
+                    }
+                }
             }
         }
+
+        result
     }
 
     /// 检查突破
     fn check_breakthroughs(&mut self) {
         let mut events = Vec::new();
         let mut disciples_need_path = Vec::new();
+        let mut breakthroughs = Vec::new();
 
         for disciple in self.sect.alive_disciples_mut() {
             // 检查修炼路径是否为空（刚进入新境界）
@@ -822,11 +2928,19 @@ impl InteractiveGame {
                             "✅ {} 成功突破至 {}！",
                             disciple.name, disciple.cultivation.current_level
                         );
+                        breakthroughs.push(format!(
+                            "{} 成功突破至 {}",
+                            disciple.name, disciple.cultivation.current_level
+                        ));
                     }
                 }
             }
         }
 
+        for message in breakthroughs {
+            self.log(LogCategory::Breakthrough, message);
+        }
+
         // 为需要的弟子生成修炼路径
         for disciple_id in disciples_need_path {
             self.generate_cultivation_path_tasks(disciple_id);
@@ -841,6 +2955,9 @@ impl InteractiveGame {
                     name
                 ));
                 println!("当前渡劫成功率: {:.1}%", success_rate * 100.0);
+                if disciple.cultivation.bottleneck {
+                    println!("  ⚠ 修为瓶颈未破，成功率大打折扣");
+                }
                 println!("  道心: {}", disciple.dao_heart);
                 if let Some(ref heritage) = disciple.heritage {
                     println!(
@@ -850,16 +2967,33 @@ impl InteractiveGame {
                 }
 
                 if UI::confirm("\n是否尝试渡劫?") {
-                    if let Some(disciple) = self.sect.disciples.iter_mut().find(|d| d.id == id) {
-                        let success = disciple.attempt_tribulation();
-                        if success {
-                            UI::success(&format!(
-                                "{} 渡劫成功！晋升至 {}",
-                                name, disciple.cultivation.current_level
-                            ));
-                        } else {
-                            UI::error(&format!("{} 渡劫失败，身死道消...", name));
-                            // 弟子会在年度更新时处理
+                    if let Ok(GuardedTribulation { outcome, from_level, .. }) =
+                        self.attempt_guarded_tribulation(id, &[], &mut rand::thread_rng())
+                    {
+                        let level = self.sect.disciples.iter().find(|d| d.id == id).map_or(from_level, |d| d.cultivation.current_level);
+                        match outcome {
+                            TribulationOutcome::Success => {
+                                UI::success(&format!("{} 渡劫成功！晋升至 {}", name, level));
+                            }
+                            TribulationOutcome::Injured => {
+                                UI::warning(&format!("{} 渡劫失败，身受重伤，需休养数年再图突破", name));
+                            }
+                            TribulationOutcome::Died => {
+                                UI::error(&format!("{} 渡劫失败，身死道消...", name));
+                                self.sect.handle_disciple_death(id, DepartureCause::Tribulation);
+                                self.release_dead_disciple_tasks();
+                            }
+                            TribulationOutcome::NotReady => {}
+                        }
+                        self.log_tribulation(&name, outcome, level);
+                        self.check_achievements();
+                        if let Some(aftermath) = self.apply_tribulation_aftermath(id, from_level, outcome, &mut rand::thread_rng()) {
+                            if !aftermath.monsters.is_empty() {
+                                UI::warning(&format!("天劫惊动妖魔：{}", aftermath.monsters.join("、")));
+                            }
+                            if aftermath.blessed > 0 {
+                                UI::success(&format!("天劫气运泽被全宗，{}名弟子修炼加速", aftermath.blessed));
+                            }
                         }
                     }
                 } else {
@@ -869,6 +3003,166 @@ impl InteractiveGame {
         }
     }
 
+    /// 检查成就解锁并记入日志，返回本次新解锁的成就
+    pub fn check_achievements(&mut self) -> Vec<Achievement> {
+        let (unlocked, wasted) = self.sect.check_achievements();
+        for achievement in &unlocked {
+            let message = format!("达成成就「{}」，{}", achievement.name(), achievement.reward().describe());
+            self.log(LogCategory::Achievement, message);
+        }
+        self.log_wasted_spirit_stones(wasted);
+        unlocked
+    }
+
+    /// 在同门护法下渡劫：护法各消耗精力，按修为提升渡劫成功率；渡劫失败时护法可能被天劫波及受伤
+    pub fn attempt_guarded_tribulation<R: Rng>(
+        &mut self,
+        disciple_id: usize,
+        guardian_ids: &[usize],
+        rng: &mut R,
+    ) -> Result<GuardedTribulation, ActionError> {
+        let disciple = self
+            .sect
+            .disciples
+            .iter()
+            .find(|d| d.id == disciple_id && d.is_alive())
+            .ok_or_else(|| ActionError::new("DISCIPLE_NOT_FOUND", "弟子不存在".to_string()))?;
+        let from_level = disciple.cultivation.current_level;
+        if guardian_ids.len() > MAX_TRIBULATION_GUARDIANS {
+            return Err(ActionError::new(
+                "TOO_MANY_GUARDIANS",
+                format!("护法最多{}人", MAX_TRIBULATION_GUARDIANS),
+            ));
+        }
+
+        let mut guardians = Vec::new();
+        for (i, &guardian_id) in guardian_ids.iter().enumerate() {
+            if guardian_id == disciple_id || guardian_ids[..i].contains(&guardian_id) {
+                return Err(ActionError::new("INVALID_GUARDIAN", "护法不能是渡劫者本人，也不能重复指定".to_string()));
+            }
+            let guardian = self
+                .sect
+                .disciples
+                .iter()
+                .find(|d| d.id == guardian_id && d.is_alive())
+                .ok_or_else(|| ActionError::new("DISCIPLE_NOT_FOUND", format!("护法弟子 {} 不存在", guardian_id)))?;
+            if guardian.is_in_seclusion() {
+                return Err(ActionError::new("DISCIPLE_IN_SECLUSION", format!("护法弟子 {} 正在闭关", guardian.name)));
+            }
+            if guardian.energy < GUARDIAN_ENERGY_COST {
+                return Err(ActionError::new(
+                    "GUARDIAN_EXHAUSTED",
+                    format!("{} 精力不足，护法需要{}点精力", guardian.name, GUARDIAN_ENERGY_COST),
+                ));
+            }
+            guardians.push(guardian);
+        }
+
+        // 未到渡劫之时，护法不必出手
+        if !disciple.cultivation.can_tribulate() {
+            return Ok(GuardedTribulation { outcome: TribulationOutcome::NotReady, from_level, injured_guardians: Vec::new() });
+        }
+        let guardian_modifier = tribulation_guardian_modifier(&guardians);
+
+        for guardian in self.sect.disciples.iter_mut().filter(|d| guardian_ids.contains(&d.id)) {
+            guardian.consume_energy(GUARDIAN_ENERGY_COST);
+        }
+        let extra_modifiers: Vec<&Modifier> = guardian_modifier.iter().collect();
+        let disciple = self.sect.disciples.iter_mut().find(|d| d.id == disciple_id).unwrap();
+        let outcome = disciple.attempt_tribulation_with_modifiers(&extra_modifiers, rng);
+
+        // 渡劫失败，天劫余威波及护法（渡劫者身死时伤势更重）
+        let backlash = match outcome {
+            TribulationOutcome::Injured => Some(InjurySeverity::Minor),
+            TribulationOutcome::Died => Some(InjurySeverity::Serious),
+            _ => None,
+        };
+        let mut injured_guardians = Vec::new();
+        if let Some(severity) = backlash {
+            for guardian in self.sect.disciples.iter_mut().filter(|d| guardian_ids.contains(&d.id)) {
+                if rng.gen_bool(GUARDIAN_BACKLASH_CHANCE) {
+                    guardian.add_injury(severity);
+                    injured_guardians.push((guardian.id, guardian.name.clone()));
+                }
+            }
+        }
+        if !injured_guardians.is_empty() {
+            let names: Vec<&str> = injured_guardians.iter().map(|(_, name)| name.as_str()).collect();
+            self.log(LogCategory::Tribulation, format!("护法的{}被天劫余威所伤", names.join("、")));
+        }
+
+        Ok(GuardedTribulation { outcome, from_level, injured_guardians })
+    }
+
+    /// 记录渡劫结果
+    pub fn log_tribulation(&mut self, name: &str, outcome: TribulationOutcome, level: CultivationLevel) {
+        let (category, message) = match outcome {
+            TribulationOutcome::Success => (LogCategory::Tribulation, format!("{} 渡劫成功，晋升至 {}", name, level)),
+            TribulationOutcome::Injured => (LogCategory::Tribulation, format!("{} 渡劫失败，身受重伤", name)),
+            TribulationOutcome::Died => (LogCategory::Death, format!("{} 渡劫失败，身死道消", name)),
+            TribulationOutcome::NotReady => return,
+        };
+        self.log(category, message);
+    }
+
+    /// 化神及以上渡劫的天地异象：惊扰附近修为更低的弟子道心，震退或吸引附近妖魔，
+    /// 成功时天劫气运泽被全宗。from_level为渡劫前境界，未达化神或未能渡劫时返回None
+    pub fn apply_tribulation_aftermath<R: Rng>(
+        &mut self,
+        disciple_id: usize,
+        from_level: CultivationLevel,
+        outcome: TribulationOutcome,
+        rng: &mut R,
+    ) -> Option<TribulationAftermath> {
+        if from_level < TRIBULATION_AFTERMATH_MIN_LEVEL || outcome == TribulationOutcome::NotReady {
+            return None;
+        }
+        let center = self.sect.disciples.iter().find(|d| d.id == disciple_id)?.position;
+        let success = outcome == TribulationOutcome::Success;
+        let mut aftermath = TribulationAftermath::default();
+
+        // 成功时同门多有感悟，失败时劫雷余威更易乱人心神
+        let impact_range = if success { -3..=5 } else { -6..=2 };
+        for disciple in self.sect.disciples.iter_mut() {
+            if disciple.id == disciple_id || !disciple.is_alive() || disciple.cultivation.current_level >= from_level {
+                continue;
+            }
+            let distance = (disciple.position.x - center.x).abs() + (disciple.position.y - center.y).abs();
+            if distance > TRIBULATION_SHAKE_RADIUS {
+                continue;
+            }
+            let impact = rng.gen_range(impact_range.clone());
+            if impact != 0 {
+                disciple.apply_dao_heart_impact(impact);
+                aftermath.shaken.push((disciple.id, impact));
+            }
+        }
+
+        aftermath.monsters = self.map.tribulation_shockwave(center, success);
+
+        if success {
+            for disciple in self.sect.disciples.iter_mut().filter(|d| d.is_alive()) {
+                disciple.add_modifier(tribulation_blessing());
+                aftermath.blessed += 1;
+            }
+        }
+
+        if !aftermath.shaken.is_empty() {
+            self.log(LogCategory::Tribulation, format!("天劫声势浩大，附近{}名弟子道心随之波动", aftermath.shaken.len()));
+        }
+        if !aftermath.monsters.is_empty() {
+            let effect = if success { "被天威震退" } else { "被溃散的劫力吸引而来" };
+            self.log(LogCategory::Tribulation, format!("{} {}", aftermath.monsters.join("、"), effect));
+        }
+        if success {
+            self.log(
+                LogCategory::Tribulation,
+                format!("天劫气运泽被全宗，{}名弟子修炼速度提升{}回合", aftermath.blessed, TRIBULATION_BLESSING_DURATION),
+            );
+        }
+        Some(aftermath)
+    }
+
     /// 同步战斗任务位置与怪物位置
     /// 当怪物移动后，更新相关战斗任务的位置
     fn sync_combat_task_positions(&mut self) {
@@ -891,19 +3185,12 @@ impl InteractiveGame {
         let invalid_task_ids = self.map.check_defense_tasks_validity(&self.current_tasks);
 
         if !invalid_task_ids.is_empty() {
-            // 收集需要解锁的任务信息（task_id, task_name, enemy_name）
-            let invalid_tasks: Vec<(usize, String, Option<String>)> = self
+            // 收集需要解锁的任务信息（task_id, enemy_id）
+            let invalid_tasks: Vec<(usize, Option<usize>)> = self
                 .current_tasks
                 .iter()
                 .filter(|t| invalid_task_ids.contains(&t.id))
-                .map(|t| {
-                    let enemy_name = if let crate::task::TaskType::Combat(combat_task) = &t.task_type {
-                        Some(combat_task.enemy_name.clone())
-                    } else {
-                        None
-                    };
-                    (t.id, t.name.clone(), enemy_name)
-                })
+                .map(|t| (t.id, t.combat_enemy_id()))
                 .collect();
 
             // 移除无效任务
@@ -911,11 +3198,11 @@ impl InteractiveGame {
             self.task_assignments.retain(|a| !invalid_task_ids.contains(&a.task_id));
 
             // 清除弟子的current_task和解锁妖魔
-            for (task_id, task_name, enemy_name_opt) in invalid_tasks {
+            for (task_id, enemy_id) in invalid_tasks {
                 // 清除妖魔的任务关联和解锁移动
                 self.map.clear_monster_task(task_id);
-                if let Some(enemy_name) = enemy_name_opt {
-                    self.map.unlock_monster_for_defense_task(&enemy_name);
+                if let Some(enemy_id) = enemy_id {
+                    self.map.unlock_monster_for_defense_task(enemy_id);
                 }
             }
         }
@@ -925,18 +3212,16 @@ impl InteractiveGame {
     /// 移除过期任务
     fn remove_expired_tasks(&mut self) {
         let current_turn = self.sect.year;
-        let expired_tasks: Vec<(usize, String, Option<String>)> = self
+        let expired_tasks: Vec<(usize, String, Option<usize>)> = self
             .current_tasks
             .iter()
             .filter(|t| t.is_expired(current_turn))
-            .map(|t| {
-                let enemy_name = if let crate::task::TaskType::Combat(combat_task) = &t.task_type {
-                    Some(combat_task.enemy_name.clone())
-                } else {
-                    None
-                };
-                (t.id, t.name.clone(), enemy_name)
+            // 已投入本金的投资任务不会过期，等待到期结算
+            .filter(|t| {
+                !matches!(t.task_type, crate::task::TaskType::Investment(_))
+                    || !self.task_assignments.iter().any(|a| a.task_id == t.id && a.started_turn.is_some())
             })
+            .map(|t| (t.id, t.name.clone(), t.combat_enemy_id()))
             .collect();
 
         if !expired_tasks.is_empty() {
@@ -953,16 +3238,408 @@ impl InteractiveGame {
                 .retain(|a| !expired_task_ids.contains(&a.task_id));
 
             // 清除正在执行过期任务的弟子和解锁妖魔
-            for (task_id, task_name, enemy_name_opt) in expired_tasks {
+            for (task_id, task_name, enemy_id) in expired_tasks {
                 // 清除妖魔的任务关联和解锁移动
                 self.map.clear_monster_task(task_id);
-                if task_name.contains("守卫") {
-                    if let Some(enemy_name) = enemy_name_opt {
-                        self.map.unlock_monster_for_defense_task(&enemy_name);
-                    }
-                }
+                if task_name.contains("守卫") {
+                    if let Some(enemy_id) = enemy_id {
+                        self.map.unlock_monster_for_defense_task(enemy_id);
+                    }
+                }
+            }
+        }
+    }
+
+    /// 根据已完成的任务刷新任务链的锁定状态
+    fn refresh_task_locks(&mut self) {
+        let completed = &self.sect.completed_task_names;
+        for task in &mut self.current_tasks {
+            task.locked = task
+                .prerequisite_task_name
+                .as_ref()
+                .is_some_and(|name| !completed.contains(name));
+        }
+    }
+
+    /// 将完成的任务所解锁的后续任务加入任务列表（同名任务已在列表中或已完成过则不再解锁）
+    fn unlock_chained_tasks(&mut self, completed: &Task) {
+        for template in &completed.unlocks {
+            let name = &template.name_template;
+            if self.sect.completed_task_names.contains(name) || self.current_tasks.iter().any(|t| &t.name == name) {
+                continue;
+            }
+            let task_id = self.current_tasks.iter().map(|t| t.id + 1).max().unwrap_or(0);
+            if let Some(mut task) = crate::map::unlocked_task(task_id, template, completed) {
+                task.created_turn = self.sect.year;
+                self.log(LogCategory::Task, format!("「{}」完成，解锁后续任务「{}」", completed.name, task.name));
+                self.current_tasks.push(task);
+                self.task_assignments.push(TaskAssignment {
+                    task_id,
+                    disciple_ids: Vec::new(),
+                    started_turn: None,
+                    progress: 0,
+                    ..Default::default()
+                });
+            }
+        }
+    }
+
+    /// 已分配弟子的任务即将到期时发出预警
+    fn warn_expiring_tasks(&mut self) {
+        let current_turn = self.sect.year;
+        let expiring: Vec<String> = self
+            .current_tasks
+            .iter()
+            .filter(|t| t.remaining_turns(current_turn) <= 1)
+            .filter(|t| {
+                self.task_assignments
+                    .iter()
+                    .any(|a| a.task_id == t.id && !a.disciple_ids.is_empty())
+            })
+            .map(|t| t.name.clone())
+            .collect();
+
+        for name in expiring {
+            let message = format!("「{}」即将到期，可消耗资源续期", name);
+            if !self.is_web_mode {
+                UI::warning(&format!("⏰ {}", message));
+            }
+            self.log(LogCategory::Task, message);
+        }
+    }
+
+    /// 弟子开始闭关，闭关期间不可接任务
+    pub fn enter_seclusion(&mut self, disciple_id: usize, turns: u32) -> Result<(), String> {
+        if turns == 0 || turns > MAX_SECLUSION_TURNS {
+            return Err(format!("闭关回合数须在1到{}之间", MAX_SECLUSION_TURNS));
+        }
+        if self.task_assignments.iter().any(|a| a.contains_disciple(disciple_id)) {
+            return Err("弟子正在执行任务，无法闭关".to_string());
+        }
+        let disciple = self
+            .sect
+            .alive_disciples_mut()
+            .into_iter()
+            .find(|d| d.id == disciple_id)
+            .ok_or_else(|| format!("弟子 {} 不存在", disciple_id))?;
+        if disciple.is_in_seclusion() {
+            return Err(format!("{}已在闭关中", disciple.name));
+        }
+        if disciple.patrolling {
+            return Err(format!("{}正在巡逻，无法闭关", disciple.name));
+        }
+        disciple.seclusion_turns = turns;
+        let message = format!("{} 开始闭关，为期{}年", disciple.name, turns);
+        self.log(LogCategory::Seclusion, message);
+        Ok(())
+    }
+
+    /// 弟子向同门赠送宗门库存中的丹药或草药
+    pub fn give_gift(&mut self, from_id: usize, to_id: usize, item: &GiftItem) -> Result<GiftOutcome, String> {
+        let outcome = self.sect.give_gift(from_id, to_id, item)?;
+        let name_of = |id: usize| {
+            self.sect.disciples.iter().find(|d| d.id == id).map(|d| d.name.clone()).unwrap_or_default()
+        };
+        let mut messages = vec![format!(
+            "{} 赠予 {} {}，战友+{} 情感+{}",
+            name_of(from_id),
+            name_of(to_id),
+            outcome.item_name,
+            outcome.comrade_gain,
+            outcome.romance_gain
+        )];
+        for (id, dimension, level) in &outcome.level_ups {
+            let other = if *id == from_id { to_id } else { from_id };
+            messages.push(format!(
+                "{} 对 {} 的{}关系提升至「{}」",
+                name_of(*id),
+                name_of(other),
+                dimension.name(),
+                level.name()
+            ));
+        }
+        for message in messages {
+            self.log(LogCategory::Relationship, message);
+        }
+        Ok(outcome)
+    }
+
+    /// 设置（或清除）弟子的长期任务偏好
+    pub fn set_task_preference(&mut self, disciple_id: usize, preference: Option<TaskTypePreference>) -> Result<(), String> {
+        let disciple = self
+            .sect
+            .alive_disciples_mut()
+            .into_iter()
+            .find(|d| d.id == disciple_id)
+            .ok_or_else(|| format!("弟子 {} 不存在", disciple_id))?;
+        disciple.task_preference = preference;
+        Ok(())
+    }
+
+    /// 弟子在当前位置巡逻，拦截进入警戒范围的妖魔，巡逻期间不可接任务
+    pub fn start_patrol(&mut self, disciple_id: usize) -> Result<PatrolZone, String> {
+        if self.is_disciple_busy(disciple_id) {
+            return Err("弟子正在执行任务，无法巡逻".to_string());
+        }
+        let disciple = self
+            .sect
+            .alive_disciples_mut()
+            .into_iter()
+            .find(|d| d.id == disciple_id)
+            .ok_or_else(|| format!("弟子 {} 不存在", disciple_id))?;
+        if disciple.is_in_seclusion() {
+            return Err(format!("{}正在闭关", disciple.name));
+        }
+        if disciple.patrolling {
+            return Err(format!("{}已在巡逻", disciple.name));
+        }
+        disciple.patrolling = true;
+        let zone = PatrolZone::new(disciple.id, disciple.position);
+        let message = format!(
+            "{} 在({}, {})一带巡逻，警戒{}格",
+            disciple.name, zone.center.x, zone.center.y, zone.radius
+        );
+        self.log(LogCategory::Task, message);
+        Ok(zone)
+    }
+
+    /// 结束巡逻
+    pub fn end_patrol(&mut self, disciple_id: usize) -> Result<(), String> {
+        let disciple = self
+            .sect
+            .alive_disciples_mut()
+            .into_iter()
+            .find(|d| d.id == disciple_id)
+            .ok_or_else(|| format!("弟子 {} 不存在", disciple_id))?;
+        if !disciple.patrolling {
+            return Err(format!("{}未在巡逻", disciple.name));
+        }
+        disciple.patrolling = false;
+        let message = format!("{} 结束巡逻", disciple.name);
+        self.log(LogCategory::Task, message);
+        Ok(())
+    }
+
+    /// 所有巡逻弟子的警戒区域
+    pub fn patrol_zones(&self) -> Vec<PatrolZone> {
+        self.sect
+            .alive_disciples()
+            .into_iter()
+            .filter(|d| d.patrolling)
+            .map(|d| PatrolZone::new(d.id, d.position))
+            .collect()
+    }
+
+    /// 弟子领取宗门传承库中的传承
+    pub fn inherit_heritage(&mut self, disciple_id: usize, heritage_index: usize) -> Result<Heritage, String> {
+        let heritage = self.sect.inherit_heritage(disciple_id, heritage_index)?;
+        if let Some(disciple) = self.sect.disciples.iter().find(|d| d.id == disciple_id) {
+            let message = format!(
+                "{} 继承了{}，渡劫成功率+{:.0}%",
+                disciple.name,
+                heritage.name,
+                heritage.tribulation_bonus * 100.0
+            );
+            self.log(LogCategory::Heritage, message);
+        }
+        Ok(heritage)
+    }
+
+    /// 提前结束闭关，返回未用完的回合数
+    pub fn end_seclusion(&mut self, disciple_id: usize) -> Result<u32, String> {
+        let disciple = self
+            .sect
+            .alive_disciples_mut()
+            .into_iter()
+            .find(|d| d.id == disciple_id)
+            .ok_or_else(|| format!("弟子 {} 不存在", disciple_id))?;
+        if !disciple.is_in_seclusion() {
+            return Err(format!("{}未在闭关", disciple.name));
+        }
+        let remaining = std::mem::take(&mut disciple.seclusion_turns);
+        let message = format!("{} 提前出关", disciple.name);
+        self.log(LogCategory::Seclusion, message);
+        Ok(remaining)
+    }
+
+    /// 闭关弟子消耗资源修炼一回合，资源不足时被迫出关
+    fn advance_seclusion(&mut self, sect_modifiers: &HashMap<usize, Vec<Modifier>>) {
+        let mut messages = Vec::new();
+        let mut secluded = Vec::new();
+        let mut rng = rand::thread_rng();
+        for disciple in self.sect.disciples.iter_mut().filter(|d| d.is_alive() && d.is_in_seclusion()) {
+            if self.sect.spirit_stones < SECLUSION_COST_PER_TURN {
+                disciple.seclusion_turns = 0;
+                messages.push(format!("宗门资源不足，{} 被迫出关", disciple.name));
+                continue;
+            }
+            self.sect.spirit_stones -= SECLUSION_COST_PER_TURN;
+            let modifiers: Vec<&Modifier> = sect_modifiers
+                .get(&disciple.id)
+                .map(|mods| mods.iter().collect())
+                .unwrap_or_default();
+            let progress = disciple.cultivate_in_seclusion(&modifiers, &mut rng);
+            secluded.push(disciple.id);
+            if !disciple.is_in_seclusion() {
+                messages.push(format!("{} 闭关期满出关，本年修为 +{}", disciple.name, progress));
+            }
+        }
+        for message in messages {
+            self.log(LogCategory::Seclusion, message);
+        }
+        for disciple_id in secluded {
+            self.check_heart_demon(disciple_id);
+        }
+    }
+
+    /// 判定心魔：走火入魔时记入日志，暴走则波及同门
+    fn check_heart_demon(&mut self, disciple_id: usize) {
+        let mut rng = rand::thread_rng();
+        let Some(disciple) = self.sect.disciples.iter().find(|d| d.id == disciple_id && d.is_alive()) else {
+            return;
+        };
+        let modifiers = self.sect.get_applicable_modifiers_owned(disciple);
+        let disciple = self.sect.disciples.iter_mut().find(|d| d.id == disciple_id).unwrap();
+        let Some(demon) = disciple.attempt_heart_demon(&modifiers.iter().collect::<Vec<_>>(), &mut rng) else {
+            return;
+        };
+        let name = disciple.name.clone();
+        self.log(LogCategory::HeartDemon, format!("{} 道心失守，走火入魔，修为倒退 {}", name, demon.progress_lost));
+
+        if demon.rampage {
+            let victims: Vec<String> = self
+                .sect
+                .heart_demon_rampage(disciple_id, &mut rng)
+                .into_iter()
+                .filter_map(|(id, injured)| {
+                    let victim = self.sect.disciples.iter().find(|d| d.id == id)?;
+                    Some(if injured { format!("{}（负伤）", victim.name) } else { victim.name.clone() })
+                })
+                .collect();
+            if !victims.is_empty() {
+                self.log(LogCategory::HeartDemon, format!("{} 心魔暴走，波及同门：{}", name, victims.join("、")));
+            }
+        }
+    }
+
+    /// 举办比武大会：未执行任务、未闭关的弟子全部参赛
+    pub fn hold_tournament(&mut self) -> Result<TournamentResult, String> {
+        let participants: Vec<usize> = self
+            .sect
+            .alive_disciples()
+            .into_iter()
+            .filter(|d| !d.is_in_seclusion() && !self.is_disciple_busy(d.id))
+            .map(|d| d.id)
+            .collect();
+        let result = self.sect.hold_tournament(&participants, &mut rand::thread_rng())?;
+        if let Some(champion) = result.champion() {
+            let message = format!(
+                "比武大会落幕，{}人参赛，{} 以{}胜夺魁，宗门声望+{}",
+                result.standings.len(),
+                champion.name,
+                champion.wins,
+                result.reputation_gained
+            );
+            self.log(LogCategory::Tournament, message);
+        }
+        Ok(result)
+    }
+
+    /// 消耗资源为任务续期，返回花费的资源
+    pub fn extend_task(&mut self, task_id: usize, turns: u32) -> Result<u32, String> {
+        if turns == 0 || turns > MAX_TASK_EXTENSION_TURNS {
+            return Err(format!("续期回合数必须在1到{}之间", MAX_TASK_EXTENSION_TURNS));
+        }
+        let current_turn = self.sect.year;
+        let task = self
+            .current_tasks
+            .iter_mut()
+            .find(|t| t.id == task_id)
+            .ok_or_else(|| format!("任务 {} 不存在", task_id))?;
+        if task.is_expired(current_turn) {
+            return Err(format!("任务「{}」已过期", task.name));
+        }
+
+        let cost = task.extension_cost(turns);
+        if !self.sect.consume_spirit_stones(cost) {
+            return Err(format!("资源不足，续期需要 {} 资源", cost));
+        }
+        task.expiry_turns = task.expiry_turns.saturating_add(turns);
+        Ok(cost)
+    }
+
+    /// 导入二进制地图，沿用当前配置；旧地图上的任务与分配一并作废
+    pub fn import_map(&mut self, bytes: &[u8]) -> Result<(), String> {
+        let mut map = GameMap::from_bytes(bytes)?;
+        std::mem::swap(&mut map.config, &mut self.map.config);
+        self.map = map;
+        self.current_tasks.clear();
+        self.task_assignments.clear();
+        Ok(())
+    }
+
+    /// 应用重新加载的配置；引用已移除元素的任务：未开始的取消，进行中的照常执行至结束
+    pub fn apply_config_reload(
+        &mut self,
+        config: crate::config::ConfigManager,
+        buildings: &crate::config::BuildingsConfig,
+    ) -> ConfigReloadReport {
+        let map_report = self.map.apply_config(config);
+        let mut report = ConfigReloadReport {
+            added_elements: map_report.added,
+            removed_elements: map_report.removed,
+            updated_elements: map_report.updated,
+            ..Default::default()
+        };
+
+        if let Some(ref mut tree) = self.sect.building_tree {
+            let (added, removed) = tree.apply_config(buildings);
+            report.added_buildings = added;
+            report.removed_buildings = removed;
+        }
+
+        let affected: Vec<(usize, String)> = self
+            .current_tasks
+            .iter()
+            .filter(|t| {
+                t.location_id.as_ref().is_some_and(|id| map_report.removed_location_ids.contains(id))
+                    || matches!(&t.task_type, crate::task::TaskType::Combat(c)
+                        if c.enemy_id.is_some_and(|id| map_report.removed_monster_ids.contains(&id)))
+            })
+            .map(|t| (t.id, t.name.clone()))
+            .collect();
+
+        for (task_id, task_name) in affected {
+            let started = self
+                .task_assignments
+                .iter()
+                .any(|a| a.task_id == task_id && a.started_turn.is_some());
+            if started {
+                report.orphaned_tasks.push(task_name);
+            } else {
+                self.current_tasks.retain(|t| t.id != task_id);
+                self.task_assignments.retain(|a| a.task_id != task_id);
+                self.map.clear_monster_task(task_id);
+                report.cancelled_tasks.push(task_name);
             }
         }
+
+        report
+    }
+
+    /// 是否有弟子正在迎战袭击宗门的妖魔
+    fn is_sect_defended(&self) -> bool {
+        let monster_id = match self.map.get_sect_invasion() {
+            Some(invasion) => invasion.monster_id,
+            None => return false,
+        };
+        self.current_tasks.iter().any(|task| {
+            matches!(&task.task_type, crate::task::TaskType::Combat(c) if c.enemy_id == Some(monster_id))
+                && self
+                    .task_assignments
+                    .iter()
+                    .any(|a| a.task_id == task.id && a.has_disciples())
+        })
     }
 
     pub fn check_game_state(&mut self) -> bool {
@@ -1002,8 +3679,9 @@ impl InteractiveGame {
             return false;
         }
 
-        // 检查宗门是否被怪物摧毁
-        if self.map.update_sect_invasion() {
+        // 检查宗门是否被怪物摧毁（连续无人守卫）
+        let defended = self.is_sect_defended();
+        if self.map.update_sect_invasion(defended) {
             if !self.is_web_mode {
                 UI::clear_screen();
                 UI::print_title("💀 游戏失败");
@@ -1052,6 +3730,7 @@ impl InteractiveGame {
                         "查看宗门状态",
                         "查看弟子列表",
                         "查看任务列表",
+                        "查看地图",
                         "分配任务",
                         "执行任务，结束回合",
                     ],
@@ -1070,9 +3749,13 @@ impl InteractiveGame {
                         UI::wait_for_enter("\n按回车继续...");
                     }
                     3 => {
-                        self.assign_tasks();
+                        UI::print_map(&self.map, &self.sect.disciples);
+                        UI::wait_for_enter("\n按回车继续...");
                     }
                     4 => {
+                        self.assign_tasks();
+                    }
+                    5 => {
                         self.execute_turn();
                         break;
                     }
@@ -1129,8 +3812,6 @@ impl InteractiveGame {
     /// 为弟子生成修炼路径任务
     /// 为弟子生成修炼路径（设置需要完成的任务类型和数量）
     pub fn generate_cultivation_path_tasks(&mut self, disciple_id: usize) {
-        use crate::cultivation::CultivationLevel;
-
         // 找到弟子
         let disciple = if let Some(d) = self.sect.disciples.iter_mut().find(|d| d.id == disciple_id) {
             d
@@ -1138,43 +3819,2035 @@ impl InteractiveGame {
             return;
         };
 
-        let level = disciple.cultivation.current_level;
-
-        // 根据境界决定任务配比（总共12个）
-        let (combat, exploration, gathering, auxiliary) = match level {
-            CultivationLevel::QiRefining => (2, 3, 4, 3),      // 练气：多采集
-            CultivationLevel::Foundation => (4, 3, 2, 3),      // 筑基：多战斗
-            CultivationLevel::GoldenCore => (5, 4, 1, 2),      // 结丹：战斗+探索
-            CultivationLevel::NascentSoul => (6, 4, 0, 2),     // 凝婴：更多战斗
-            CultivationLevel::SpiritSevering => (7, 4, 0, 1),  // 化神：主要战斗
-            CultivationLevel::VoidRefinement => (8, 3, 0, 1),  // 练虚：几乎全战斗
-            CultivationLevel::Ascension => (10, 2, 0, 0),      // 飞升：纯战斗
+        // 根据境界读取任务配比
+        let Some(quota) = self.cultivation_paths.quota_for(disciple.cultivation.current_level) else {
+            return;
         };
 
-        // 创建修炼路径要求
+        // 设置修炼路径
+        disciple.cultivation.cultivation_path =
+            Some(crate::cultivation::CultivationPath::with_requirements(quota.requirements()));
+
+        if !self.is_web_mode {
+            UI::success(&format!(
+                "✨ {} 获得了新的修炼路径（需完成{}个战斗、{}个探索、{}个采集、{}个辅助任务）！",
+                disciple.name, quota.combat, quota.exploration, quota.gathering, quota.auxiliary
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::relationship::RelationDimension;
+    use crate::task::{DEFENSE_TASK_EXTENSION_COST_MULTIPLIER, TASK_EXTENSION_COST_PER_TURN};
+
+    #[test]
+    fn test_save_load_round_trip() {
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        let disciple = game.recruitment_system.generate_random_disciple(game.sect.reputation, &mut rand::thread_rng());
+        game.sect.recruit_disciple(disciple);
+
+        let first_id = game.sect.disciples[0].id;
+        let second_id = game.sect.disciples[1].id;
+
+        // 设置修炼路径进度和关系分数
         let mut requirements = std::collections::HashMap::new();
-        if combat > 0 {
-            requirements.insert("Combat".to_string(), combat);
+        requirements.insert("Combat".to_string(), 3);
+        game.sect.disciples[0].cultivation.cultivation_path =
+            Some(crate::cultivation::CultivationPath::with_requirements(requirements));
+        game.sect.disciples[0].cultivation.try_complete_path_task_by_type("Combat");
+        game.sect
+            .update_relationship_score(first_id, second_id, RelationDimension::Comrade, 42)
+            .unwrap();
+
+        let path = std::env::temp_dir().join(format!("xiuxian_save_{}.json", uuid::Uuid::new_v4()));
+        game.save_to_file(&path).unwrap();
+        let loaded = InteractiveGame::load_from_file(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        let disciple = &loaded.sect.disciples[0];
+        let cultivation_path = disciple.cultivation.cultivation_path.as_ref().unwrap();
+        assert_eq!(cultivation_path.completed.get("Combat"), Some(&1));
+        assert_eq!(cultivation_path.required.get("Combat"), Some(&3));
+        assert_eq!(disciple.get_relationship(second_id).unwrap().scores.comrade, 42);
+        assert_eq!(loaded.sect.disciples.len(), 2);
+        assert_eq!(loaded.map.elements.len(), game.map.elements.len());
+        assert_eq!(loaded.state, GameState::Running);
+        assert_eq!(loaded.recruitment_system.next_disciple_id(), 2);
+    }
+
+    #[test]
+    fn test_investment_task_settles_at_maturity() {
+        use crate::config::TaskTemplateConfig;
+        use crate::task::TaskType;
+
+        let template: TaskTemplateConfig = serde_json::from_value(serde_json::json!({
+            "name_template": "商路投资",
+            "task_type": "Investment",
+            "progress_reward": 0,
+            "resource_reward": 0,
+            "reputation_reward": 0,
+            "dao_heart_impact": 0,
+            "capital": 200,
+            "return_rate": 0.5,
+            "maturity_turns": 3
+        }))
+        .unwrap();
+        let task_type = crate::map::parse_task_type(&template).expect("应能解析投资任务");
+        assert!(matches!(task_type, TaskType::Investment(_)));
+
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        let task = Task::new(9999, "商路投资".to_string(), task_type, 0, 0);
+        assert_eq!(task.duration, 3);
+        let disciple_id = game.sect.disciples[0].id;
+        game.current_tasks.push(task);
+        game.task_assignments.push(TaskAssignment {
+            task_id: 9999,
+            disciple_ids: vec![disciple_id],
+            started_turn: None,
+            progress: 0,
+            ..Default::default()
+        });
+        game.sect.spirit_stones = 1000;
+
+        // 第一回合投入本金
+        game.execute_turn();
+        assert_eq!(game.sect.spirit_stones, 800);
+        game.execute_turn();
+        assert_eq!(game.sect.spirit_stones, 800);
+
+        // 到期结算本金和收益
+        let results = game.execute_turn().task_results;
+        assert_eq!(results.len(), 1);
+        assert!(results[0].success);
+        assert_eq!(results[0].resources_gained, 300);
+        assert_eq!(game.sect.spirit_stones, 1100);
+        assert!(game.current_tasks.iter().all(|t| t.id != 9999));
+
+        // 任务结果写入事件日志
+        let task_logs: Vec<&GameLogEntry> = game
+            .event_log
+            .iter()
+            .filter(|e| e.category == LogCategory::Task)
+            .collect();
+        assert_eq!(task_logs.len(), 1);
+        assert_eq!(task_logs[0].year, game.sect.year);
+    }
+
+    #[test]
+    fn test_undo_turn_restores_state() {
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        let disciple_id = game.sect.disciples[0].id;
+        let task = Task::new(
+            9999,
+            "采药".to_string(),
+            crate::task::TaskType::Gathering(crate::task::GatheringTask {
+                resource_type: "草药".to_string(),
+                difficulty: 10,
+            }),
+            30,
+            50,
+        );
+        game.current_tasks.push(task);
+        game.task_assignments.push(TaskAssignment {
+            task_id: 9999,
+            disciple_ids: vec![disciple_id],
+            started_turn: None,
+            progress: 0,
+            ..Default::default()
+        });
+
+        let resources = game.sect.spirit_stones;
+        let progress = game.sect.disciples[0].cultivation.progress;
+        let energy = game.sect.disciples[0].energy;
+        let elements = serde_json::to_string(&game.map.elements).unwrap();
+
+        game.execute_turn();
+        game.start_turn();
+        assert!(game.current_tasks.iter().all(|t| t.id != 9999 || t.retryable));
+
+        game.undo_turn().unwrap();
+        assert_eq!(game.sect.spirit_stones, resources);
+        assert_eq!(game.sect.disciples[0].cultivation.progress, progress);
+        assert_eq!(game.sect.disciples[0].energy, energy);
+        assert_eq!(serde_json::to_string(&game.map.elements).unwrap(), elements);
+        assert!(game.current_tasks.iter().any(|t| t.id == 9999));
+        assert!(game.undo_turn().is_err());
+    }
+
+    #[test]
+    fn test_undo_history_is_bounded() {
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        for _ in 0..5 {
+            game.execute_turn();
+        }
+        assert_eq!(game.undo_available(), MAX_TURN_SNAPSHOTS);
+    }
+
+    /// 多次执行采集任务，返回第一次成功时的资源奖励
+    fn gathering_reward(team_size: usize) -> u32 {
+        for attempt in 0..50 {
+            let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+            while game.sect.disciples.len() < team_size {
+                let disciple = game.recruitment_system.generate_random_disciple(game.sect.reputation, &mut rand::thread_rng());
+                game.sect.recruit_disciple(disciple);
+            }
+            let task_id = 10000 + attempt;
+            game.current_tasks.push(Task::new(
+                task_id,
+                "采药".to_string(),
+                crate::task::TaskType::Gathering(crate::task::GatheringTask {
+                    resource_type: "草药".to_string(),
+                    difficulty: 10,
+                }),
+                10,
+                100,
+            ));
+            game.task_assignments.push(TaskAssignment {
+                task_id,
+                disciple_ids: game.sect.disciples.iter().map(|d| d.id).take(team_size).collect(),
+                started_turn: None,
+                progress: 0,
+                ..Default::default()
+            });
+
+            let results = game.execute_turn().task_results;
+            assert!(results.iter().all(|r| r.team_size == team_size));
+            if let Some(result) = results.iter().find(|r| r.success) {
+                return result.resources_gained;
+            }
+        }
+        panic!("任务始终未成功");
+    }
+
+    #[test]
+    fn test_team_task_earns_synergy_bonus() {
+        let solo = gathering_reward(1);
+        let team = gathering_reward(2);
+        assert_eq!(solo, 100);
+        assert!(team > solo, "两人组队奖励 {} 应高于单人 {}", team, solo);
+    }
+
+    #[test]
+    fn test_start_turn_logs_income() {
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        game.start_turn();
+        let year = game.sect.year;
+        assert!(game
+            .event_log
+            .iter()
+            .any(|e| e.category == LogCategory::Income && e.year == year));
+    }
+
+    #[test]
+    fn test_undefended_sect_invasion_causes_defeat() {
+        use crate::map::{MapElement, Monster, Position, PositionedElement, SECT_INVASION_GRACE_TURNS};
+
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        game.map.elements.retain(|p| !matches!(p.element, MapElement::Monster(_)));
+
+        // 在宗门旁放置一只已锁定宗门的妖魔
+        let sect = game.map.sect_position;
+        let start = [(1, 0), (-1, 0), (0, 1), (0, -1)]
+            .iter()
+            .map(|(dx, dy)| Position { x: sect.x + dx, y: sect.y + dy })
+            .find(|p| game.map.is_passable(p.x, p.y))
+            .unwrap();
+        let mut monster = Monster::new(game.map.allocate_monster_id(), "赤目妖狼".to_string(), 40, vec![]);
+        monster.targeting_sect = true;
+        game.map.elements.push(PositionedElement {
+            element: MapElement::Monster(monster),
+            position: start,
+            size: None,
+            positions: None,
+        });
+
+        game.map.update(&std::collections::HashSet::new(), &[], &mut rand::thread_rng());
+        assert!(game.map.is_sect_under_attack());
+
+        // 有弟子守卫时倒计时重置
+        let task = game
+            .map
+            .generate_defense_tasks(900)
+            .into_iter()
+            .find(|t| t.is_sect_defense())
+            .unwrap();
+        let disciple_id = game.sect.disciples[0].id;
+        let mut assignment = TaskAssignment {
+            task_id: task.id,
+            disciple_ids: Vec::new(),
+            started_turn: None,
+            progress: 0,
+            ..Default::default()
+        };
+        assignment.add_disciple(disciple_id);
+        game.current_tasks.push(task);
+        game.task_assignments.push(assignment);
+        assert!(game.check_game_state());
+        assert_eq!(game.map.get_sect_invasion().unwrap().turns_remaining, SECT_INVASION_GRACE_TURNS);
+
+        // 连续无人守卫则宗门被攻破
+        game.task_assignments.clear();
+        for _ in 1..SECT_INVASION_GRACE_TURNS {
+            assert!(game.check_game_state());
+        }
+        assert!(!game.check_game_state());
+        assert_eq!(game.state, GameState::Defeat);
+    }
+
+    #[test]
+    fn test_config_reload_removes_elements_and_degrades_tasks() {
+        use crate::config::{BuildingsConfig, ConfigManager, PositionConfig, VillageTemplate};
+        use crate::task::{GatheringTask, Task, TaskType};
+
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        let mut config = ConfigManager {
+            map_elements: game.map.config.map_elements.clone(),
+            monsters: game.map.config.monsters.clone(),
+        };
+        let faction = config.map_elements.factions.remove(0);
+        config.map_elements.villages.push(VillageTemplate {
+            name: "新建村".to_string(),
+            population: 100,
+            prosperity: 50,
+            position: PositionConfig { x: 1, y: 1 },
+            size: None,
+            task_templates: vec![],
+        });
+
+        // 仓库自带的配置应能通过校验
+        assert!(ConfigManager::reload_from_disk().is_ok());
+
+        // 无效配置返回全部错误
+        let mut invalid = config.map_elements.clone();
+        invalid.villages.push(invalid.villages.last().unwrap().clone());
+        invalid.villages[0].position.x = -1;
+        assert_eq!(invalid.validate().unwrap_err().len(), 2);
+        assert!(config.validate().is_ok());
+
+        let location_id = format!("faction_{}", faction.name);
+        for (id, started) in [(900, false), (901, true)] {
+            let mut task = Task::new(
+                id,
+                format!("任务{}", id),
+                TaskType::Gathering(GatheringTask { resource_type: "灵草".to_string(), difficulty: 1 }),
+                0,
+                0,
+            );
+            task.location_id = Some(location_id.clone());
+            game.current_tasks.push(task);
+            game.task_assignments.push(TaskAssignment {
+                task_id: id,
+                disciple_ids: vec![],
+                started_turn: started.then_some(game.sect.year),
+                progress: 0,
+                ..Default::default()
+            });
         }
-        if exploration > 0 {
-            requirements.insert("Exploration".to_string(), exploration);
+
+        let report = game.apply_config_reload(config, &BuildingsConfig::default_config());
+
+        assert_eq!(report.removed_elements, vec![faction.name.clone()]);
+        assert_eq!(report.added_elements, vec!["新建村".to_string()]);
+        assert_eq!(report.cancelled_tasks, vec!["任务900".to_string()]);
+        assert_eq!(report.orphaned_tasks, vec!["任务901".to_string()]);
+        assert!(game.map.elements.iter().all(|p| p.element.get_location_id() != location_id));
+        let village = game.map.elements.iter()
+            .find(|p| p.element.get_name() == "新建村")
+            .unwrap();
+        assert_eq!((village.position.x, village.position.y), (1, 1));
+        assert!(game.current_tasks.iter().any(|t| t.id == 901));
+        assert!(!game.task_assignments.iter().any(|a| a.task_id == 900));
+    }
+
+    #[test]
+    fn test_assign_strategies_produce_different_assignments() {
+        use crate::cultivation::CultivationPath;
+        use crate::disciple::DiscipleType;
+        use crate::modifier::{Modifier, ModifierApplication, ModifierSource, ModifierTarget};
+        use crate::task::{AuxiliaryTask, CombatTask, GatheringTask, TaskType};
+
+        // 返回 (讨伐, 采药, 布阵) 各自分配到的弟子
+        fn assign(strategy: AssignStrategy) -> [Option<usize>; 3] {
+            let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+            game.current_tasks.clear();
+            game.task_assignments.clear();
+            game.sect.disciples.clear();
+
+            // 甲：修炼路径缺采集；乙：任务奖励翻倍，修炼路径缺辅助
+            let mut a = Disciple::new(1, "甲".to_string(), DiscipleType::Outer, vec![]);
+            let mut b = Disciple::new(2, "乙".to_string(), DiscipleType::Outer, vec![]);
+            a.cultivation.cultivation_path =
+                Some(CultivationPath::with_requirements([("Gathering".to_string(), 1)].into()));
+            b.cultivation.cultivation_path =
+                Some(CultivationPath::with_requirements([("Auxiliary".to_string(), 1)].into()));
+            b.modifiers.add_modifier(Modifier::new(
+                "福缘",
+                ModifierTarget::TaskReward,
+                ModifierApplication::Multiplicative(1.0),
+                ModifierSource::System,
+            ));
+            game.sect.disciples = vec![a, b];
+
+            let tasks = [
+                Task::new(1, "讨伐".to_string(), TaskType::Combat(CombatTask {
+                    enemy_id: None,
+                    enemy_name: "山贼".to_string(),
+                    enemy_level: 1,
+                    difficulty: 1,
+                }), 100, 0),
+                Task::new(2, "采药".to_string(), TaskType::Gathering(GatheringTask {
+                    resource_type: "草药".to_string(),
+                    difficulty: 1,
+                }), 10, 0),
+                Task::new(3, "布阵".to_string(), TaskType::Auxiliary(AuxiliaryTask {
+                    task_name: "布阵".to_string(),
+                    skill_required: None,
+                }), 10, 0),
+            ];
+            for task in tasks {
+                game.task_assignments.push(TaskAssignment {
+                    task_id: task.id,
+                    disciple_ids: vec![],
+                    started_turn: None,
+                    progress: 0,
+                    ..Default::default()
+                });
+                game.current_tasks.push(task);
+            }
+
+            game.auto_assign_remaining(strategy);
+            [1, 2, 3].map(|task_id| {
+                game.task_assignments
+                    .iter()
+                    .find(|a| a.task_id == task_id)
+                    .and_then(|a| a.disciple_id())
+            })
         }
-        if gathering > 0 {
-            requirements.insert("Gathering".to_string(), gathering);
+
+        assert_eq!(assign(AssignStrategy::FirstFit), [Some(1), Some(2), None]);
+        assert_eq!(assign(AssignStrategy::MaxReward), [Some(2), None, Some(1)]);
+        assert_eq!(assign(AssignStrategy::CultivationPath), [None, Some(1), Some(2)]);
+    }
+
+    #[test]
+    fn test_low_dao_heart_disciple_deserts_and_becomes_hostile_faction() {
+        use crate::cultivation::CultivationLevel;
+        use crate::map::MapElement;
+
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        let deserter = &mut game.sect.disciples[0];
+        let (deserter_id, deserter_name) = (deserter.id, deserter.name.clone());
+        deserter.cultivation.current_level = CultivationLevel::NascentSoul;
+        deserter.lifespan = CultivationLevel::NascentSoul.base_lifespan();
+        deserter.dao_heart = 0;
+        game.task_assignments.push(TaskAssignment {
+            task_id: 9999,
+            disciple_ids: vec![deserter_id],
+            started_turn: None,
+            progress: 0,
+            ..Default::default()
+        });
+
+        for _ in 0..30 {
+            if game.sect.disciples.iter().all(|d| d.id != deserter_id) {
+                break;
+            }
+            game.start_turn();
         }
-        if auxiliary > 0 {
-            requirements.insert("Auxiliary".to_string(), auxiliary);
+
+        assert!(game.sect.disciples.iter().all(|d| d.id != deserter_id));
+        assert!(game.task_assignments.iter().all(|a| !a.disciple_ids.contains(&deserter_id)));
+        let faction = game
+            .map
+            .elements
+            .iter()
+            .find_map(|p| match &p.element {
+                MapElement::Faction(f) if f.name == format!("{}叛宗", deserter_name) => Some(f),
+                _ => None,
+            })
+            .expect("叛离弟子应自立为敌对势力");
+        assert!(faction.relationship < 0);
+        assert!(faction
+            .generate_tasks(0)
+            .iter()
+            .any(|t| matches!(t.task_type, crate::task::TaskType::Combat(_))));
+    }
+
+    #[test]
+    fn test_live_events_broadcast_to_every_subscriber() {
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        let mut first = game.subscribe_live_events();
+        let mut second = game.subscribe_live_events();
+
+        game.log(LogCategory::Income, "年度收入：10 资源");
+        game.log(LogCategory::Breakthrough, "韩立 突破至筑基");
+        game.log_tribulation("韩立", TribulationOutcome::Died, CultivationLevel::GoldenCore);
+
+        // 两个客户端都收到关键事件，收入等普通日志不推送
+        for receiver in [&mut first, &mut second] {
+            let breakthrough = receiver.try_recv().unwrap();
+            assert_eq!(breakthrough.category, LogCategory::Breakthrough);
+            assert_eq!(receiver.try_recv().unwrap().category, LogCategory::Death);
+            assert!(receiver.try_recv().is_err());
         }
 
-        // 设置修炼路径
-        disciple.cultivation.cultivation_path =
-            Some(crate::cultivation::CultivationPath::with_requirements(requirements));
+        // 断开的客户端不再占用订阅
+        drop(second);
+        assert_eq!(game.live_events.receiver_count(), 1);
+        game.log(LogCategory::Invasion, "妖魔来袭");
+        assert_eq!(first.try_recv().unwrap().message, "妖魔来袭");
+        assert_eq!(game.event_log.len(), 4);
+    }
 
-        if !self.is_web_mode {
-            UI::success(&format!(
-                "✨ {} 获得了新的修炼路径（需完成{}个战斗、{}个探索、{}个采集、{}个辅助任务）！",
-                disciple.name, combat, exploration, gathering, auxiliary
+    #[test]
+    fn test_extended_task_survives_next_turn() {
+        use crate::task::{GatheringTask, TaskType};
+
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        let year = game.sect.year;
+        let gathering = |id, name: &str| {
+            let mut task = Task::new(id, name.to_string(), TaskType::Gathering(GatheringTask {
+                resource_type: "灵草".to_string(),
+                difficulty: 10,
+            }), 10, 0);
+            task.created_turn = year;
+            task.expiry_turns = 1;
+            task
+        };
+        game.current_tasks = vec![gathering(900, "采集灵草"), gathering(901, "守卫青石村")];
+        game.task_assignments = vec![TaskAssignment {
+            task_id: 900,
+            disciple_ids: vec![1],
+            started_turn: Some(year),
+            progress: 0,
+            ..Default::default()
+        }];
+
+        // 已分配弟子的任务即将到期时预警
+        game.warn_expiring_tasks();
+        assert!(game.event_log.iter().any(|e| e.category == LogCategory::Task && e.message.contains("采集灵草")));
+        assert!(!game.event_log.iter().any(|e| e.message.contains("守卫青石村")));
+
+        game.sect.spirit_stones = 100;
+        assert!(game.extend_task(900, 0).is_err());
+        assert!(game.extend_task(900, u32::MAX).is_err());
+        assert!(game.extend_task(999, 1).is_err());
+        assert_eq!(game.extend_task(900, 2), Ok(2 * TASK_EXTENSION_COST_PER_TURN));
+        // 守卫任务加收
+        assert_eq!(
+            game.extend_task(901, 1),
+            Ok(TASK_EXTENSION_COST_PER_TURN * DEFENSE_TASK_EXTENSION_COST_MULTIPLIER)
+        );
+        assert_eq!(game.sect.spirit_stones, 100 - 4 * TASK_EXTENSION_COST_PER_TURN);
+        assert!(game.extend_task(900, 10).is_err());
+
+        game.start_turn();
+        assert!(game.current_tasks.iter().any(|t| t.id == 900));
+        assert!(game.current_tasks.iter().any(|t| t.id == 901));
+    }
+
+    #[test]
+    fn test_task_chain_locks_until_prerequisite_completes() {
+        use crate::config::TaskTemplateConfig;
+        use crate::map::Position;
+
+        let template: TaskTemplateConfig = serde_json::from_value(serde_json::json!({
+            "name_template": "侦查{name}",
+            "task_type": "Investment",
+            "progress_reward": 0,
+            "resource_reward": 0,
+            "reputation_reward": 0,
+            "dao_heart_impact": 0,
+            "capital": 0,
+            "maturity_turns": 1,
+            "unlocks": [{
+                "name_template": "剿灭{name}",
+                "task_type": "Gathering",
+                "progress_reward": 10,
+                "resource_reward": 50,
+                "reputation_reward": 5,
+                "dao_heart_impact": 0,
+                "resource_type": "匪赃",
+                "difficulty": 10
+            }]
+        }))
+        .unwrap();
+        let follow_up: TaskTemplateConfig = serde_json::from_value(serde_json::json!({
+            "name_template": "追查{name}匪首",
+            "task_type": "Gathering",
+            "progress_reward": 10,
+            "resource_reward": 50,
+            "reputation_reward": 0,
+            "dao_heart_impact": 0,
+            "resource_type": "线索",
+            "difficulty": 10,
+            "prerequisite": "侦查{name}"
+        }))
+        .unwrap();
+
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        if game.sect.disciples.is_empty() {
+            let disciple = game.recruitment_system.generate_random_disciple(0, &mut rand::thread_rng());
+            game.sect.recruit_disciple(disciple);
+        }
+        let disciple_id = game.sect.disciples[0].id;
+
+        let mut scout = Task::new(900, "侦查黑风寨".to_string(), crate::map::parse_task_type(&template).unwrap(), 0, 0);
+        scout.attach_chain(&template, "黑风寨");
+        scout.position = Some(Position { x: 3, y: 4 });
+        let mut hunt = Task::new(901, "追查黑风寨匪首".to_string(), crate::map::parse_task_type(&follow_up).unwrap(), 10, 50);
+        hunt.attach_chain(&follow_up, "黑风寨");
+        assert_eq!(hunt.prerequisite_task_name.as_deref(), Some("侦查黑风寨"));
+
+        // 前置未完成：后续任务锁定，不可分配
+        assert!(hunt.locked);
+        assert!(!hunt.is_suitable_for_disciple(&game.sect.disciples[0]));
+
+        game.current_tasks = vec![scout.clone(), hunt];
+        game.task_assignments = vec![
+            TaskAssignment { task_id: 900, disciple_ids: vec![disciple_id], started_turn: None, progress: 0, ..Default::default() },
+            TaskAssignment { task_id: 901, disciple_ids: vec![], started_turn: None, progress: 0, ..Default::default() },
+        ];
+        let results = game.execute_turn().task_results;
+        assert!(results.iter().any(|r| r.task_id == 900 && r.success));
+
+        // 前置完成：锁定解除，并在同一地点出现解锁的后续任务
+        let hunt = game.current_tasks.iter().find(|t| t.id == 901).unwrap();
+        assert!(!hunt.locked);
+        let raid = game.current_tasks.iter().find(|t| t.name == "剿灭黑风寨").unwrap();
+        assert!(!raid.locked);
+        assert_eq!(raid.position.map(|p| (p.x, p.y)), Some((3, 4)));
+        assert!(raid.is_suitable_for_disciple(&game.sect.disciples[0]));
+
+        // 同一条链不会重复解锁
+        game.unlock_chained_tasks(&scout);
+        assert_eq!(game.current_tasks.iter().filter(|t| t.name == "剿灭黑风寨").count(), 1);
+    }
+
+    #[test]
+    fn test_seclusion_gives_steady_progress_and_blocks_tasks() {
+        use crate::task::{GatheringTask, TaskType};
+
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        let id = game.sect.alive_disciples()[0].id;
+        let task = Task::new(900, "采集灵草".to_string(), TaskType::Gathering(GatheringTask {
+            resource_type: "灵草".to_string(),
+            difficulty: 10,
+        }), 10, 0);
+        assert!(game.enter_seclusion(id, 0).is_err());
+        assert!(game.enter_seclusion(id, MAX_SECLUSION_TURNS + 1).is_err());
+
+        game.enter_seclusion(id, 3).unwrap();
+        assert!(game.enter_seclusion(id, 1).is_err());
+        let disciple = game.sect.disciples.iter().find(|d| d.id == id).unwrap();
+        assert!(!task.is_suitable_for_disciple(disciple));
+        assert!(!task.check_eligibility(disciple, &[], true, false, false, 0).eligible);
+
+        // 每回合修为增长稳定，并扣除资源
+        game.sect.spirit_stones = 100;
+        game.sect.disciples.iter_mut().find(|d| d.id == id).unwrap().cultivation.progress = 0;
+        let progress = |game: &InteractiveGame| game.sect.disciples.iter().find(|d| d.id == id).unwrap().cultivation.progress;
+        let mut gains = Vec::new();
+        for _ in 0..2 {
+            let before = progress(&game);
+            game.advance_seclusion(&HashMap::new());
+            gains.push(progress(&game) - before);
+        }
+        assert!(gains[0] > 0);
+        assert_eq!(gains[0], gains[1]);
+        assert_eq!(game.sect.spirit_stones, 100 - 2 * SECLUSION_COST_PER_TURN);
+
+        // 提前出关后可以接任务
+        assert_eq!(game.end_seclusion(id), Ok(1));
+        let disciple = game.sect.disciples.iter().find(|d| d.id == id).unwrap();
+        assert!(task.is_suitable_for_disciple(disciple));
+        assert!(game.end_seclusion(id).is_err());
+    }
+
+    #[test]
+    fn test_batch_pill_for_idle_qi_refining_disciples() {
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        let disciple = |id, name: &str| Disciple::new(id, name.to_string(), DiscipleType::Outer, vec![]);
+        let mut foundation = disciple(3, "筑基弟子");
+        foundation.cultivation.current_level = CultivationLevel::Foundation;
+        game.sect.disciples = vec![disciple(1, "甲"), disciple(2, "忙碌弟子"), foundation, disciple(4, "乙")];
+        game.task_assignments = vec![TaskAssignment {
+            task_id: 900,
+            disciple_ids: vec![2],
+            started_turn: None,
+            progress: 0,
+            ..Default::default()
+        }];
+        // 库存只剩一枚回气丹
+        while game.sect.pill_inventory.get_count(PillType::QiRecovery, PillGrade::Low) > 1 {
+            game.sect.pill_inventory.consume(PillType::QiRecovery, PillGrade::Low);
+        }
+
+        let filter = DiscipleFilter {
+            max_level: Some(CultivationLevel::QiRefining),
+            idle: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(game.filter_disciples(&filter), vec![1, 4]);
+
+        let action = BatchAction::UsePill { pill_type: PillType::QiRecovery, grade: PillGrade::Low };
+        let results = game.batch_disciple_action(&filter, &action);
+        assert_eq!(results.len(), 2);
+        assert!(results[0].result.is_ok());
+        // 库存只够一人服用，第二人逐个返回失败原因
+        assert_eq!(results[1].disciple_id, 4);
+        assert_eq!(results[1].result.as_ref().unwrap_err().code, "NO_PILLS");
+
+        let pills_taken: Vec<u32> = game.sect.disciples.iter().map(|d| d.pills_taken_this_turn).collect();
+        assert_eq!(pills_taken, vec![1, 0, 0, 0]);
+        assert_eq!(game.sect.pill_inventory.get_count(PillType::QiRecovery, PillGrade::Low), 0);
+    }
+
+    #[test]
+    fn test_movement_range_building_extends_move_distance() {
+        use crate::building::{Building, BuildingTree};
+        use crate::modifier::{ConditionalModifier, ModifierApplication, ModifierCondition, ModifierSource, ModifierTarget};
+
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        game.map = GameMap::new();
+        game.sect.disciples = vec![Disciple::new(1, "韩立".to_string(), DiscipleType::Outer, vec![])];
+        let start = Position { x: 5, y: 5 };
+        let target = Position { x: 5, y: 9 };
+        game.sect.disciples[0].move_to(start);
+
+        // 练气期只能移动2格
+        let err = game.move_disciple(1, target).unwrap_err();
+        assert_eq!(err.code, "MOVEMENT_OUT_OF_RANGE");
+
+        let modifier = ConditionalModifier::new(
+            ModifierCondition::Always,
+            Modifier::new(
+                "神行阵",
+                ModifierTarget::MovementRange,
+                ModifierApplication::Additive(2.0),
+                ModifierSource::System,
+            ),
+        );
+        let mut tree = BuildingTree::new(Building::new_root("root", "神行殿", "移动范围+2", 100, vec![modifier]));
+        tree.buildings.get_mut("root").unwrap().is_built = true;
+        game.sect.init_building_tree(tree);
+
+        // 新回合按有效移动范围重置移动力
+        game.start_turn();
+        let disciple = &mut game.sect.disciples[0];
+        assert_eq!(disciple.moves_remaining, 4);
+        disciple.move_to(start);
+
+        let outcome = game.move_disciple(1, target).unwrap();
+        assert_eq!(outcome.moves_remaining, 0);
+        assert!(game.sect.disciples[0].is_at_position(&target));
+    }
+
+    #[test]
+    fn test_consecutive_work_builds_fatigue_and_rest_recovers() {
+        use crate::task::{GatheringTask, TaskType};
+
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        game.sect.disciples = vec![Disciple::new(1, "石昊".to_string(), DiscipleType::Outer, vec![])];
+        let mut task = Task::new(900, "采集灵草".to_string(), TaskType::Gathering(GatheringTask {
+            resource_type: "灵草".to_string(),
+            difficulty: 10,
+        }), 30, 0);
+        task.duration = 10;
+        task.energy_cost = 0;
+        task.constitution_cost = 0;
+        game.current_tasks = vec![task.clone()];
+        game.task_assignments = vec![TaskAssignment {
+            task_id: 900,
+            disciple_ids: vec![1],
+            started_turn: None,
+            progress: 0,
+            ..Default::default()
+        }];
+        let rested_reward = game.sect.disciples[0].estimate_task_reward(&task, &[]);
+
+        // 连续五回合执行任务
+        for _ in 0..5 {
+            game.sect.disciples[0].natural_recovery(&[]);
+            game.execute_turn();
+        }
+        let disciple = &game.sect.disciples[0];
+        assert!(disciple.is_fatigued());
+        assert!(disciple.estimate_task_reward(&task, &[]) < rested_reward);
+
+        // 疲惫的弟子不会被自动分配
+        game.task_assignments = vec![TaskAssignment {
+            task_id: 900,
+            disciple_ids: vec![],
+            started_turn: None,
+            progress: 0,
+            ..Default::default()
+        }];
+        assert!(game.auto_assign_disciples(AssignStrategy::FirstFit, None).is_empty());
+
+        // 休息数回合后疲劳消退，收益恢复
+        for _ in 0..6 {
+            game.sect.disciples[0].natural_recovery(&[]);
+        }
+        let disciple = &game.sect.disciples[0];
+        assert_eq!(disciple.fatigue, 0);
+        assert_eq!(disciple.estimate_task_reward(&task, &[]), rested_reward);
+        assert_eq!(game.auto_assign_disciples(AssignStrategy::FirstFit, None), vec![(900, 1)]);
+    }
+
+    #[test]
+    fn test_task_yields_stones_and_contribution_and_promotion_spends_contribution() {
+        use crate::task::{AuxiliaryTask, TaskType};
+
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        game.sect.disciples = vec![Disciple::new(1, "石昊".to_string(), DiscipleType::Outer, vec![])];
+        game.sect.spirit_stones = 0;
+        game.sect.contribution = 0;
+
+        // 非战斗任务有一定失败率，重复执行直至成功
+        let mut succeeded = false;
+        for id in 900..930 {
+            let mut task = Task::new(id, "布阵".to_string(), TaskType::Auxiliary(AuxiliaryTask {
+                task_name: "布阵".to_string(),
+                skill_required: None,
+            }), 10, 100);
+            task.duration = 1;
+            task.energy_cost = 0;
+            task.constitution_cost = 0;
+            game.current_tasks = vec![task];
+            game.task_assignments = vec![TaskAssignment {
+                task_id: id,
+                disciple_ids: vec![1],
+                started_turn: None,
+                progress: 0,
+                ..Default::default()
+            }];
+            if game.execute_turn().task_results.iter().any(|r| r.success) {
+                succeeded = true;
+                break;
+            }
+        }
+        assert!(succeeded);
+        assert!(game.sect.spirit_stones > 0);
+        assert!(game.sect.contribution > 0);
+
+        // 晋升只消耗贡献点，不动灵石
+        game.sect.disciples[0].tasks_completed = 10;
+        game.sect.contribution = 0;
+        assert!(game.sect.promote_disciple(1).unwrap_err().contains("贡献点不足"));
+        let cost = crate::sect::PROMOTION_CONTRIBUTION_COSTS[0].1;
+        game.sect.contribution = cost + 5;
+        let stones = game.sect.spirit_stones;
+        assert_eq!(game.sect.promote_disciple(1), Ok(DiscipleType::Inner));
+        assert_eq!(game.sect.contribution, 5);
+        assert_eq!(game.sect.spirit_stones, stones);
+    }
+
+    #[test]
+    fn test_friendly_faction_tasks_raise_relationship_and_income() {
+        use crate::config::ConfigManager;
+        use crate::map::{Faction, GameMap, MapElement, PositionedElement, Position, FACTION_HOSTILE_THRESHOLD};
+
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        game.sect.disciples = vec![Disciple::new(1, "石昊".to_string(), DiscipleType::Outer, vec![])];
+        game.sect.reputation = 100;
+        let mut template = ConfigManager::create_default().map_elements.factions[0].clone();
+        template.relationship = 0;
+        game.map = GameMap::new();
+        game.map.elements.push(PositionedElement {
+            element: MapElement::Faction(Faction::from_template(&template)),
+            position: Position { x: 3, y: 3 },
+            size: None,
+            positions: None,
+        });
+        let faction = |game: &InteractiveGame| match &game.map.elements[0].element {
+            MapElement::Faction(f) => f.clone(),
+            _ => unreachable!(),
+        };
+        let income_before = game.map.calculate_income(game.sect.reputation);
+
+        // 反复完成友好任务，关系逐步升温直至结盟
+        for id in 0..100 {
+            if faction(&game).relationship > 50 {
+                break;
+            }
+            let mut task = game.map.elements[0].element.generate_tasks(900 + id, &mut rand::thread_rng()).remove(0);
+            task.energy_cost = 0;
+            task.constitution_cost = 0;
+            game.current_tasks = vec![task.clone()];
+            game.task_assignments = vec![TaskAssignment {
+                task_id: task.id,
+                disciple_ids: vec![1],
+                started_turn: None,
+                progress: 0,
+                ..Default::default()
+            }];
+            game.execute_turn();
+        }
+        assert!(faction(&game).relationship > 50);
+        assert!(game.map.calculate_income(game.sect.reputation) > income_before);
+
+        // 镇压任务使关系恶化，跌破阈值后只发布敌对任务
+        let mut hostile = faction(&game);
+        hostile.relationship = FACTION_HOSTILE_THRESHOLD - 1;
+        let suppress = hostile.generate_tasks(0).remove(0);
+        assert!(hostile.is_hostile_task(&suppress));
+        hostile.relationship = FACTION_HOSTILE_THRESHOLD + 5;
+        assert!(hostile.generate_tasks(0).is_empty());
+        hostile.settle_task(&suppress);
+        assert!(hostile.relationship < FACTION_HOSTILE_THRESHOLD);
+        assert!(!hostile.generate_tasks(0).is_empty());
+    }
+
+    #[test]
+    fn test_validate_detects_dangling_assignment() {
+        use crate::task::{GatheringTask, TaskType};
+
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        for task_id in [900, 901] {
+            game.current_tasks.push(Task::new(task_id, "采集灵草".to_string(), TaskType::Gathering(GatheringTask {
+                resource_type: "灵草".to_string(),
+                difficulty: 10,
+            }), 10, 0));
+            game.task_assignments.push(TaskAssignment {
+                task_id,
+                disciple_ids: vec![],
+                started_turn: None,
+                progress: 0,
+                ..Default::default()
+            });
+        }
+        assert!(game.validate().is_empty());
+
+        // 绕过 with_write 直接制造悬挂分配，完整检查能发现
+        let task_id = 900;
+        game.current_tasks.retain(|t| t.id != task_id);
+        let issues = game.validate();
+        assert!(issues.iter().any(|i| i.contains(&format!("不存在的任务 #{}", task_id))));
+
+        // 已存在的问题不归咎于后续写操作
+        game.with_write(|game| game.sect.spirit_stones += 1);
+
+        // 写操作引入的新问题在调试构建下被断言拦截
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            game.with_write(|game| game.current_tasks.retain(|t| t.id != 901));
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_dead_disciple_releases_defense_task_and_monster() {
+        use crate::map::{GameMap, MapElement, Monster, Position, PositionedElement};
+        use crate::task::{CombatTask, TaskType};
+
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        game.sect.disciples = vec![
+            Disciple::new(1, "石昊".to_string(), DiscipleType::Outer, vec![]),
+            Disciple::new(2, "火灵儿".to_string(), DiscipleType::Outer, vec![]),
+        ];
+        game.sect.disciples[1].get_or_create_relationship(1, game.sect.year).scores.comrade = 40;
+        game.map = GameMap::new();
+        let monster_id = game.map.allocate_monster_id();
+        game.map.elements.push(PositionedElement {
+            element: MapElement::Monster(Monster::new(monster_id, "赤目妖狼".to_string(), 10, vec![])),
+            position: Position { x: 3, y: 3 },
+            size: None,
+            positions: None,
+        });
+        game.current_tasks = vec![Task::new(900, "守卫青石村".to_string(), TaskType::Combat(CombatTask {
+            enemy_id: Some(monster_id),
+            enemy_name: "赤目妖狼".to_string(),
+            enemy_level: 10,
+            difficulty: 10,
+        }), 10, 0)];
+        game.task_assignments = vec![TaskAssignment {
+            task_id: 900,
+            disciple_ids: vec![1],
+            started_turn: Some(game.sect.year),
+            progress: 0,
+            ..Default::default()
+        }];
+        game.map.set_monster_being_fought(monster_id, true);
+        game.map.lock_monster_for_defense_task(monster_id);
+
+        // 守卫弟子陨落
+        game.sect.disciples[0].constitution = 0;
+        game.sect.handle_disciple_death(1, DepartureCause::Battle);
+        assert_eq!(game.release_dead_disciple_tasks(), vec![(1, 900)]);
+
+        assert!(game.task_assignments[0].disciple_ids.is_empty());
+        let monster = game.map.find_monster(monster_id).unwrap();
+        assert!(!monster.is_being_fought);
+        assert!(!monster.has_active_defense_task);
+        assert!(game.validate().is_empty());
+
+        // 同门的关系保留但标记为已故
+        let rel = &game.sect.disciples[1].relationships[0];
+        assert_eq!(rel.target_id, 1);
+        assert!(rel.deceased);
+    }
+
+    #[test]
+    fn test_completed_tasks_feed_disciple_contribution_leaderboard() {
+        use crate::cultivation::CultivationLevel;
+        use crate::sect::LeaderboardMetric;
+        use crate::task::{AuxiliaryTask, TaskType};
+
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        let mut elder = Disciple::new(3, "药老".to_string(), DiscipleType::Inner, vec![]);
+        elder.cultivation.current_level = CultivationLevel::GoldenCore;
+        game.sect.disciples = vec![
+            Disciple::new(1, "石昊".to_string(), DiscipleType::Outer, vec![]),
+            Disciple::new(2, "火灵儿".to_string(), DiscipleType::Outer, vec![]),
+            elder,
+        ];
+
+        // 石昊与火灵儿组队历练，之后石昊单独接任务直至完成数领先
+        let mut expected_tasks = [0u32; 2];
+        let mut expected_resources = [0u32; 2];
+        for id in 900..1000 {
+            let team = if expected_tasks.iter().any(|&n| n < 2) {
+                vec![1, 2]
+            } else if expected_tasks[0] <= expected_tasks[1] {
+                vec![1]
+            } else {
+                break;
+            };
+            let mut task = Task::new(id, "布阵".to_string(), TaskType::Auxiliary(AuxiliaryTask {
+                task_name: "布阵".to_string(),
+                skill_required: None,
+            }), 10, 100);
+            task.duration = 1;
+            task.energy_cost = 0;
+            task.constitution_cost = 0;
+            game.current_tasks = vec![task];
+            game.task_assignments = vec![TaskAssignment {
+                task_id: id,
+                disciple_ids: team.clone(),
+                started_turn: None,
+                progress: 0,
+                ..Default::default()
+            }];
+            for result in game.execute_turn().task_results.iter().filter(|r| r.success) {
+                expected_tasks[result.disciple_id - 1] += 1;
+                // 组队收益按人数均分计入个人贡献
+                expected_resources[result.disciple_id - 1] += result.resources_gained / team.len() as u32;
+            }
+        }
+        assert!(expected_tasks[0] > expected_tasks[1]);
+
+        let find = |id: usize| game.sect.disciples.iter().find(|d| d.id == id).unwrap();
+        for id in [1, 2] {
+            assert_eq!(find(id).tasks_completed, expected_tasks[id - 1]);
+            assert_eq!(find(id).total_resources_contributed, expected_resources[id - 1]);
+            assert_eq!(find(id).monsters_slain, 0);
+        }
+
+        let ranking = |metric| game.sect.leaderboard(metric).iter().map(|d| d.id).collect::<Vec<_>>();
+        assert_eq!(ranking(LeaderboardMetric::Tasks), vec![1, 2, 3]);
+        let by_resources = game.sect.leaderboard(LeaderboardMetric::Resources);
+        assert!(by_resources.windows(2).all(|w| w[0].total_resources_contributed >= w[1].total_resources_contributed));
+        assert_eq!(by_resources[2].id, 3);
+        assert_eq!(ranking(LeaderboardMetric::Cultivation)[0], 3);
+    }
+
+    #[test]
+    fn test_task_reward_preview_matches_actual_settlement() {
+        use crate::task::{CombatTask, TaskType};
+
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        let mut settled = false;
+        for id in 900..960 {
+            let mut disciple = Disciple::new(1, "石昊".to_string(), DiscipleType::Outer, vec![]);
+            disciple.dao_heart = 50;
+            game.sect.disciples = vec![disciple];
+            let mut task = Task::new(id, "斩妖".to_string(), TaskType::Combat(CombatTask {
+                enemy_id: None,
+                enemy_name: "山魈".to_string(),
+                enemy_level: 0,
+                difficulty: 1,
+            }), 30, 120);
+            task.duration = 1;
+            task.energy_cost = 0;
+            task.constitution_cost = 0;
+            task.reputation_reward = 6;
+            task.dao_heart_impact = 3;
+            game.current_tasks = vec![task.clone()];
+            game.task_assignments = vec![TaskAssignment {
+                task_id: id,
+                disciple_ids: vec![1],
+                started_turn: None,
+                progress: 0,
+                ..Default::default()
+            }];
+
+            let preview = game.preview_task_reward(&task, &game.sect.disciples[0]);
+            assert!(preview.success_rate > 0.0 && preview.success_rate <= 0.95);
+            let results = game.execute_turn().task_results;
+            let Some(result) = results.iter().find(|r| r.success) else {
+                continue;
+            };
+            assert_eq!(result.progress_gained, preview.progress);
+            assert_eq!(result.resources_gained, preview.resources);
+            assert_eq!(result.contribution_gained, preview.contribution);
+            assert_eq!(result.reputation_gained, preview.reputation);
+            assert_eq!(game.sect.disciples[0].dao_heart as i32, 50 + preview.dao_heart_change);
+            settled = true;
+            break;
+        }
+        assert!(settled);
+    }
+
+    #[test]
+    fn test_recruit_two_of_three_candidates_until_resources_run_out() {
+        use crate::disciple::Talent;
+
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        let candidate = |id: usize, name: &str, level: u32| {
+            Disciple::new(id, name.to_string(), DiscipleType::Outer, vec![Talent { talent_type: TalentType::Sword, level }])
+        };
+        game.recruitment_pool = vec![candidate(101, "萧炎", 9), candidate(102, "林动", 5), candidate(103, "牧尘", 2)];
+        let prices: Vec<u32> = game.recruitment_pool.iter().map(RecruitmentSystem::candidate_price).collect();
+        // 天赋越高潜力越大、标价越高
+        assert!(prices[0] > prices[1] && prices[1] > prices[2]);
+
+        game.sect.spirit_stones = prices[0] + prices[1] + prices[2] - 1;
+        let disciples_before = game.sect.disciples.len();
+
+        assert_eq!(game.recruit_candidate(0), Ok((101, prices[0])));
+        // 池中候选前移，原第二名现在位于序号0
+        assert_eq!(game.recruit_candidate(0), Ok((102, prices[1])));
+        assert_eq!(game.sect.spirit_stones, prices[2] - 1);
+
+        let err = game.recruit_candidate(0).unwrap_err();
+        assert!(err.contains("资源不足"));
+        assert_eq!(game.recruitment_pool.len(), 1);
+        assert_eq!(game.sect.disciples.len(), disciples_before + 2);
+        assert!(game.recruit_candidate(1).is_err());
+
+        // 刷新招募池需要花费
+        game.sect.spirit_stones = RECRUITMENT_POOL_REFRESH_COST;
+        game.reroll_recruitment_pool().unwrap();
+        assert_eq!(game.recruitment_pool.len(), crate::event::RECRUITMENT_POOL_SIZE);
+        assert!(game.reroll_recruitment_pool().is_err());
+    }
+
+    #[test]
+    fn test_task_matcher_matches_naive_scan_at_scale() {
+        use crate::cultivation::CultivationLevel;
+        use crate::disciple::Talent;
+        use crate::task::{AuxiliaryTask, CombatTask, ExplorationTask, GatheringTask};
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(73);
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        let position = |rng: &mut StdRng| Position { x: rng.gen_range(0..6), y: rng.gen_range(0..6) };
+
+        game.sect.disciples = (0..500)
+            .map(|id| {
+                let talents = (0..rng.gen_range(0..3))
+                    .map(|_| Talent { talent_type: TalentType::ALL[rng.gen_range(0..TalentType::ALL.len())].clone(), level: 5 })
+                    .collect();
+                let mut disciple = Disciple::new(id, format!("弟子{}", id), DiscipleType::Outer, talents);
+                disciple.cultivation.current_level = CultivationLevel::ALL[rng.gen_range(0..4)];
+                disciple.position = position(&mut rng);
+                disciple.seclusion_turns = rng.gen_bool(0.05) as u32;
+                disciple.patrolling = rng.gen_bool(0.05);
+                disciple
+            })
+            .collect();
+
+        game.current_tasks = (0..300)
+            .map(|id| {
+                let task_type = match id % 4 {
+                    0 => TaskType::Combat(CombatTask { enemy_id: None, enemy_name: "妖狼".to_string(), enemy_level: 3, difficulty: 3 }),
+                    1 => TaskType::Exploration(ExplorationTask {
+                        location: "秘境".to_string(),
+                        danger_level: rng.gen_range(0..40),
+                        realm_type: rng.gen_bool(0.5).then_some(TalentType::Fire),
+                        depth: 0,
+                    }),
+                    2 => TaskType::Auxiliary(AuxiliaryTask {
+                        task_name: "炼器".to_string(),
+                        skill_required: rng.gen_bool(0.7).then(|| TalentType::ALL[rng.gen_range(0..TalentType::ALL.len())].clone()),
+                    }),
+                    _ => TaskType::Gathering(GatheringTask { resource_type: "灵草".to_string(), difficulty: 1 }),
+                };
+                let mut task = Task::new(id, format!("任务{}", id), task_type, 10, 0);
+                task.position = rng.gen_bool(0.7).then(|| position(&mut rng));
+                // 部分任务位于占据多格的大型元素上
+                if task.position.is_some() && rng.gen_bool(0.2) {
+                    task.valid_positions = Some((0..4).map(|_| position(&mut rng)).collect());
+                }
+                task.locked = rng.gen_bool(0.05);
+                task
+            })
+            .collect();
+
+        // 前100个任务各有1~3名弟子执行
+        let mut next_disciple = 0;
+        game.task_assignments = (0..100)
+            .map(|task_id| {
+                let team = rng.gen_range(1..=3);
+                let disciple_ids = (next_disciple..next_disciple + team).collect();
+                next_disciple += team;
+                TaskAssignment { task_id, disciple_ids, started_turn: None, progress: 0, ..Default::default() }
+            })
+            .collect();
+
+        // 朴素实现：对每个任务逐个扫描所有弟子与分配
+        let naive = |game: &InteractiveGame, task: &Task| {
+            let mut candidates = TaskCandidates::default();
+            for disciple in &game.sect.disciples {
+                if !task.is_suitable_for_disciple(disciple) {
+                    continue;
+                }
+                if !task.is_disciple_at_valid_position(&disciple.position) {
+                    continue;
+                }
+                let is_busy = game
+                    .task_assignments
+                    .iter()
+                    .any(|a| a.disciple_ids.contains(&disciple.id) && a.task_id != task.id);
+                if is_busy {
+                    candidates.busy.push(disciple.id);
+                } else {
+                    candidates.free.push(disciple.id);
+                }
+            }
+            candidates.free.sort_by_key(|id| {
+                game.sect.disciples.iter().find(|d| d.id == *id).and_then(|d| task.realm_affinity(d)) != Some(true)
+            });
+            candidates
+        };
+
+        let expected: Vec<TaskCandidates> = game.current_tasks.iter().map(|t| naive(&game, t)).collect();
+        let matcher = game.task_matcher();
+        let actual: Vec<TaskCandidates> = game.current_tasks.iter().map(|t| matcher.candidates(t)).collect();
+
+        assert_eq!(actual, expected);
+        assert!(expected.iter().any(|c| !c.busy.is_empty()));
+        assert!(expected.iter().filter(|c| !c.free.is_empty()).count() > 100);
+    }
+
+    #[test]
+    fn test_auto_assign_puts_gathering_lover_on_gathering_task() {
+        use crate::cultivation::CultivationPath;
+        use crate::disciple::PreferenceConflict;
+        use crate::task::{CombatTask, GatheringTask};
+
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        game.sect.disciples = vec![
+            Disciple::new(1, "药童".to_string(), DiscipleType::Outer, vec![]),
+            Disciple::new(2, "剑童".to_string(), DiscipleType::Outer, vec![]),
+        ];
+        game.current_tasks = vec![
+            Task::new(10, "采药".to_string(), TaskType::Gathering(GatheringTask {
+                resource_type: "灵草".to_string(),
+                difficulty: 1,
+            }), 10, 10),
+            Task::new(11, "除妖".to_string(), TaskType::Combat(CombatTask {
+                enemy_id: None,
+                enemy_name: "山魈".to_string(),
+                enemy_level: 1,
+                difficulty: 1,
+            }), 10, 10),
+        ];
+        let reset = |game: &mut InteractiveGame| {
+            game.task_assignments = [10, 11]
+                .into_iter()
+                .map(|task_id| TaskAssignment { task_id, disciple_ids: vec![], started_turn: None, progress: 0, ..Default::default() })
+                .collect();
+        };
+        let task_of = |game: &InteractiveGame, disciple_id| {
+            game.task_assignments.iter().find(|a| a.contains_disciple(disciple_id)).map(|a| a.task_id)
+        };
+
+        // 无偏好时战斗任务先分配，名册第一位的药童被派去除妖
+        reset(&mut game);
+        game.auto_assign_remaining(AssignStrategy::FirstFit);
+        assert_eq!(task_of(&game, 1), Some(11));
+
+        // 设置采集偏好后药童优先采药
+        let gathering = TaskTypePreference::new("Gathering", PreferenceConflict::PreferenceFirst).unwrap();
+        game.set_task_preference(1, Some(gathering)).unwrap();
+        reset(&mut game);
+        game.auto_assign_remaining(AssignStrategy::FirstFit);
+        assert_eq!(task_of(&game, 1), Some(10));
+        assert_eq!(task_of(&game, 2), Some(11));
+
+        // 修炼路径尚缺战斗任务：坚持偏好仍去采药，路径优先则视同偏好
+        let requirements = [("Combat".to_string(), 2)].into_iter().collect();
+        game.sect.disciples[0].cultivation.cultivation_path = Some(CultivationPath::with_requirements(requirements));
+        reset(&mut game);
+        game.auto_assign_remaining(AssignStrategy::CultivationPath);
+        assert_eq!(task_of(&game, 1), Some(10));
+
+        let path_first = TaskTypePreference::new("Gathering", PreferenceConflict::PathFirst).unwrap();
+        game.set_task_preference(1, Some(path_first)).unwrap();
+        reset(&mut game);
+        game.auto_assign_remaining(AssignStrategy::CultivationPath);
+        assert_eq!(task_of(&game, 1), Some(11));
+
+        assert!(TaskTypePreference::new("Fishing", PreferenceConflict::PathFirst).is_err());
+    }
+
+    #[test]
+    fn test_spirit_severing_tribulation_blesses_whole_sect() {
+        use crate::disciple::{TRIBULATION_AFTERMATH_MIN_LEVEL, TRIBULATION_BLESSING_DURATION};
+        use crate::map::{MapElement, Monster, PositionedElement, TRIBULATION_SHOCK_RADIUS};
+        use crate::modifier::{ModifierSource, ModifierTarget};
+        use crate::cultivation::SubLevel;
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        let mut elder = Disciple::new(1, "韩立".to_string(), DiscipleType::Personal, vec![]);
+        elder.cultivation.current_level = TRIBULATION_AFTERMATH_MIN_LEVEL;
+        elder.cultivation.sub_level = SubLevel::Perfect;
+        elder.lifespan = TRIBULATION_AFTERMATH_MIN_LEVEL.base_lifespan();
+        elder.position = Position { x: 5, y: 5 };
+        let mut junior = Disciple::new(2, "厉飞雨".to_string(), DiscipleType::Outer, vec![]);
+        junior.position = Position { x: 5, y: 6 };
+        let mut faraway = Disciple::new(3, "南宫婉".to_string(), DiscipleType::Inner, vec![]);
+        faraway.position = Position { x: 18, y: 18 };
+        game.sect.disciples = vec![elder, junior, faraway];
+        let mut monster = Monster::new(7, "噬魂虎".to_string(), 5, vec![]);
+        monster.targeting_sect = true;
+        game.map.elements = vec![PositionedElement {
+            element: MapElement::Monster(monster),
+            position: Position { x: 6, y: 5 },
+            size: None,
+            positions: None,
+        }];
+        let speed_before: Vec<f32> = game.sect.disciples.iter().map(|d| d.get_effective_cultivation_speed()).collect();
+
+        let from_level = game.sect.disciples[0].cultivation.current_level;
+        let outcome = game.sect.disciples[0].resolve_tribulation(0.0, 0.99, &[]);
+        assert_eq!(outcome, TribulationOutcome::Success);
+        let aftermath = game
+            .apply_tribulation_aftermath(1, from_level, outcome, &mut StdRng::seed_from_u64(76))
+            .unwrap();
+
+        // 全宗弟子（含远处弟子）获得临时的环境修炼加成
+        assert_eq!(aftermath.blessed, 3);
+        for (disciple, before) in game.sect.disciples.iter().zip(speed_before) {
+            let blessing = disciple.modifiers.get_modifiers_for_target(&ModifierTarget::CultivationSpeed);
+            assert!(blessing
+                .iter()
+                .any(|m| m.source == ModifierSource::Environment && m.duration == Some(TRIBULATION_BLESSING_DURATION)));
+            assert!(disciple.get_effective_cultivation_speed() > before);
+        }
+        // 只有附近修为更低的弟子道心受到波及
+        assert!(aftermath.shaken.iter().all(|&(id, _)| id == 2));
+        // 附近妖魔被天威震退并放弃进攻宗门
+        assert_eq!(aftermath.monsters, vec!["噬魂虎".to_string()]);
+        let positioned = &game.map.elements[0];
+        assert!((positioned.position.x - 5).abs() + (positioned.position.y - 5).abs() > TRIBULATION_SHOCK_RADIUS);
+        assert!(matches!(&positioned.element, MapElement::Monster(m) if !m.targeting_sect));
+
+        // 化神以下渡劫不会引动天地异象
+        assert!(game
+            .apply_tribulation_aftermath(2, CultivationLevel::GoldenCore, TribulationOutcome::Success, &mut StdRng::seed_from_u64(76))
+            .is_none());
+    }
+
+    #[test]
+    fn test_create_game_with_custom_map_size_and_disciples() {
+        let setup = GameSetup {
+            map_width: 40,
+            map_height: 40,
+            initial_disciples: 5,
+            starting_resources: 2500,
+        };
+        let game = InteractiveGame::with_setup("测试宗".to_string(), true, &setup).unwrap();
+        assert_eq!((game.map.width, game.map.height), (40, 40));
+        assert_eq!(game.sect.disciples.len(), 5);
+        assert_eq!(game.sect.spirit_stones, 2500);
+
+        // 初始灵石不超过库房上限
+        let rich = GameSetup { starting_resources: u32::MAX, ..GameSetup::default() };
+        let game = InteractiveGame::with_setup("测试宗".to_string(), true, &rich).unwrap();
+        assert_eq!(game.sect.spirit_stones, game.sect.spirit_stone_capacity());
+
+        // 小地图上超出范围的配置元素被裁剪
+        let small = GameSetup { map_width: MIN_MAP_SIZE, map_height: MIN_MAP_SIZE, ..GameSetup::default() };
+        let game = InteractiveGame::with_setup("测试宗".to_string(), true, &small).unwrap();
+        assert!(game
+            .map
+            .elements
+            .iter()
+            .flat_map(|e| e.get_all_positions())
+            .all(|p| p.x >= 0 && p.x < MIN_MAP_SIZE && p.y >= 0 && p.y < MIN_MAP_SIZE));
+
+        // 尺寸或弟子数越界时拒绝开局
+        let too_large = GameSetup { map_width: MAX_MAP_SIZE + 1, ..GameSetup::default() };
+        assert!(InteractiveGame::with_setup("测试宗".to_string(), true, &too_large).is_err());
+        let no_disciples = GameSetup { initial_disciples: 0, ..GameSetup::default() };
+        assert!(InteractiveGame::with_setup("测试宗".to_string(), true, &no_disciples).is_err());
+    }
+
+    #[test]
+    fn test_moves_reset_every_turn_and_scale_with_injury_and_seclusion() {
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        game.map = GameMap::new();
+        game.sect.disciples = vec![Disciple::new(1, "韩立".to_string(), DiscipleType::Outer, vec![])];
+        game.sect.disciples[0].move_to(Position { x: 5, y: 5 });
+        let full = game.sect.disciples[0].get_effective_movement_range();
+
+        // 连续两回合各自走满移动力，回合内耗尽后无法再走
+        for target in [Position { x: 5, y: 7 }, Position { x: 5, y: 5 }] {
+            game.start_turn();
+            assert_eq!(game.sect.disciples[0].moves_remaining, full);
+            let outcome = game.move_disciple(1, target).unwrap();
+            assert_eq!(outcome.moves_remaining, 0);
+            let err = game.move_disciple(1, Position { x: target.x + 1, y: target.y }).unwrap_err();
+            assert_eq!(err.code, "INSUFFICIENT_MOVES");
+        }
+
+        // 重伤弟子移动力减半，闭关弟子无法走动
+        game.sect.disciples[0].add_injury(InjurySeverity::Serious);
+        game.start_turn();
+        assert_eq!(game.sect.disciples[0].moves_remaining, full / 2);
+        game.sect.disciples[0].heal_all_injuries();
+        game.sect.disciples[0].seclusion_turns = 3;
+        game.start_turn();
+        assert_eq!(game.sect.disciples[0].moves_remaining, 0);
+    }
+
+    #[test]
+    fn test_deep_exploration_pays_more_but_injures_more_often() {
+        use crate::task::{ExplorationTask, MAX_EXPLORATION_DEPTH};
+
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        let mut sample = |depth: u32| {
+            let mut task = Task::new(1, "探索黑风岭".to_string(), TaskType::Exploration(ExplorationTask {
+                location: "黑风岭".to_string(),
+                danger_level: 40,
+                realm_type: None,
+                depth: 0,
+            }), 20, 100);
+            task.set_exploration_depth(depth).unwrap();
+            let (mut gained, mut injured, mut aged) = (0u32, 0u32, 0u32);
+            const SAMPLES: u32 = 400;
+            for _ in 0..SAMPLES {
+                let disciple = Disciple::new(1, "韩立".to_string(), DiscipleType::Inner, vec![]);
+                let lifespan = disciple.lifespan;
+                game.sect.disciples = vec![disciple];
+                let result = game.execute_single_task(1, task.clone(), 1, 0.0, true);
+                gained += result.resources_gained + result.progress_gained;
+                let disciple = &game.sect.disciples[0];
+                injured += disciple.is_injured() as u32;
+                aged += (disciple.lifespan < lifespan) as u32;
+            }
+            (gained as f32 / SAMPLES as f32, injured as f32 / SAMPLES as f32, aged)
+        };
+
+        let (shallow_gain, shallow_injury, shallow_aged) = sample(0);
+        let (deep_gain, deep_injury, deep_aged) = sample(MAX_EXPLORATION_DEPTH);
+        // 深层期望收益更高，但受伤率明显上升，失败还会折损寿元
+        assert!(deep_gain > shallow_gain * 1.2, "{} vs {}", deep_gain, shallow_gain);
+        assert!(deep_injury > shallow_injury + 0.2, "{} vs {}", deep_injury, shallow_injury);
+        assert_eq!(shallow_aged, 0);
+        assert!(deep_aged > 0);
+
+        // 深度有上限，非探索任务不可指定深度
+        let mut task = Task::new(2, "采药".to_string(), TaskType::Gathering(crate::task::GatheringTask {
+            resource_type: "灵草".to_string(),
+            difficulty: 1,
+        }), 10, 10);
+        assert!(task.set_exploration_depth(1).is_err());
+    }
+
+    #[test]
+    fn test_distant_gathering_task_becomes_multi_turn_expedition() {
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        game.map = GameMap::new();
+        let home = Position { x: 2, y: 2 };
+        let village = Position { x: 2, y: 12 };
+        let mut disciple = Disciple::new(1, "韩立".to_string(), DiscipleType::Outer, vec![]);
+        disciple.move_to(home);
+        game.sect.disciples = vec![disciple];
+        let mut task = Task::new(1, "村落采集".to_string(), TaskType::Gathering(crate::task::GatheringTask {
+            resource_type: "灵草".to_string(),
+            difficulty: 1,
+        }), 10, 10);
+        task.position = Some(village);
+        task.duration = 2;
+        game.current_tasks = vec![task];
+        game.task_assignments = vec![TaskAssignment { task_id: 1, ..Default::default() }];
+
+        // 练气期每回合走2格，10格路程需5回合
+        assert_eq!(game.dispatch_to_task(1, 1), Ok(Some(5)));
+        let status = game.disciple_task_status(1).unwrap();
+        assert_eq!((status.phase, status.remaining_turns), (AssignmentPhase::Traveling, 7));
+
+        // 赶路期间任务不推进，抵达后才开始执行（途中遇伏击失利会耽搁行程）
+        let mut travel_turns = 0;
+        while game.task_assignments[0].phase == AssignmentPhase::Traveling {
+            assert_eq!(game.task_assignments[0].progress, 0);
+            assert!(game.task_assignments[0].started_turn.is_none());
+            game.execute_turn();
+            travel_turns += 1;
+            assert!(travel_turns < 50);
+        }
+        assert!(travel_turns >= 5);
+        assert!(game.sect.disciples[0].is_at_position(&village));
+
+        game.execute_turn();
+        assert_eq!(game.task_assignments[0].progress, 1);
+        game.execute_turn();
+        // 任务已结算（部分成功时保留为可重试）
+        assert!(game.current_tasks.iter().all(|t| t.retryable));
+
+        // 任务结算后返程，回到出发地归队
+        assert_eq!(game.disciple_task_status(1).unwrap().phase, AssignmentPhase::Returning);
+        while game.disciple_task_status(1).is_some() {
+            game.execute_turn();
+            travel_turns += 1;
+            assert!(travel_turns < 100);
+        }
+        assert!(game.sect.disciples[0].is_at_position(&home));
+    }
+
+    #[test]
+    fn test_tribulation_guardians_raise_success_rate_and_share_backlash() {
+        use crate::cultivation::SubLevel;
+        use crate::modifier::{ModifierApplication, ModifierSource, ModifierTarget};
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let tributee = || {
+            let mut disciple = Disciple::new(1, "韩立".to_string(), DiscipleType::Personal, vec![]);
+            disciple.cultivation.current_level = CultivationLevel::Foundation;
+            disciple.cultivation.sub_level = SubLevel::Perfect;
+            disciple
+        };
+        let guardian = |id: usize, name: &str| {
+            let mut disciple = Disciple::new(id, name.to_string(), DiscipleType::Inner, vec![]);
+            disciple.cultivation.current_level = CultivationLevel::GoldenCore;
+            disciple
+        };
+        let guardians = [guardian(2, "南宫婉"), guardian(3, "厉飞雨")];
+
+        // 护法按修为提升成功率：同一掷骰无护法失败、有护法成功
+        let alone = tributee().tribulation_success_rate();
+        let modifier = tribulation_guardian_modifier(&guardians.iter().collect::<Vec<_>>()).unwrap();
+        let guarded = tributee().tribulation_success_rate_with_sect_modifiers(&[&modifier]);
+        assert!(guarded > alone, "{} vs {}", guarded, alone);
+        let roll = (alone + guarded) / 2.0;
+        assert_eq!(tributee().resolve_tribulation(roll, 0.99, &[]), TribulationOutcome::Injured);
+        assert_eq!(tributee().resolve_tribulation(roll, 0.99, &[&modifier]), TribulationOutcome::Success);
+
+        // 渡劫必败时，护法消耗精力并可能被天劫波及受伤
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        let mut rng = StdRng::seed_from_u64(85);
+        let mut injured = Vec::new();
+        for _ in 0..20 {
+            let mut doomed = tributee();
+            doomed.add_modifier(Modifier::new(
+                "天劫必败",
+                ModifierTarget::TribulationSuccessRate,
+                ModifierApplication::Override(0.0),
+                ModifierSource::Debuff,
             ));
+            game.sect.disciples = vec![doomed, guardians[0].clone(), guardians[1].clone()];
+            let energy_before = game.sect.disciples[1].energy;
+            let result = game.attempt_guarded_tribulation(1, &[2, 3], &mut rng).unwrap();
+            assert_ne!(result.outcome, TribulationOutcome::Success);
+            assert!(game.sect.disciples[1].energy < energy_before);
+            injured = result.injured_guardians;
+            if !injured.is_empty() {
+                break;
+            }
+        }
+        assert!(!injured.is_empty());
+        for (id, _) in &injured {
+            assert!(game.sect.disciples.iter().find(|d| d.id == *id).unwrap().is_injured());
+        }
+
+        // 渡劫者本人不能兼任护法
+        let err = game.attempt_guarded_tribulation(2, &[2], &mut rng).unwrap_err();
+        assert_eq!(err.code, "INVALID_GUARDIAN");
+    }
+
+    #[test]
+    fn test_partial_success_grants_rewards_proportional_to_completion() {
+        use crate::task::{GatheringTask, TaskType};
+
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        let disciple_id = game.sect.disciples[0].id;
+        let mut task = Task::new(900, "采集灵草".to_string(), TaskType::Gathering(GatheringTask {
+            resource_type: "灵草".to_string(),
+            difficulty: 10,
+        }), 10, 100);
+        task.created_turn = game.sect.year;
+
+        // 反复结算直到出现一次部分成功
+        let result = (0..1000)
+            .map(|_| game.execute_single_task(disciple_id, task.clone(), 1, 0.0, true))
+            .find(|r| r.outcome == TaskOutcome::PartialSuccess)
+            .expect("采集任务失败时应为部分成功");
+        assert!(!result.success);
+        assert!(result.completion > 0.0 && result.completion <= 1.0);
+        let (full_resources, full_contribution, _) = task.settlement_rewards(1.0, 0.0);
+        let share = result.completion * PARTIAL_REWARD_SHARE;
+        assert_eq!(result.resources_gained, (full_resources as f32 * share) as u32);
+        assert_eq!(result.contribution_gained, (full_contribution as f32 * share) as u32);
+        assert!(result.resources_gained <= full_resources / 2);
+        assert_eq!(result.reputation_gained, 0);
+        assert!(!result.disciple_died);
+
+        // 失败的任务保留一回合供重新派遣
+        task.open_retry(game.sect.year);
+        assert!(task.retryable);
+        game.current_tasks = vec![task];
+        game.start_turn();
+        assert!(game.current_tasks.iter().any(|t| t.id == 900));
+        game.start_turn();
+        assert!(!game.current_tasks.iter().any(|t| t.id == 900));
+    }
+
+    #[test]
+    fn test_water_root_disciple_cannot_explore_flame_realm() {
+        use crate::disciple::Talent;
+        use crate::task::ExplorationTask;
+
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        let with_root = |id, talent_type, level| {
+            let mut disciple = Disciple::new(id, format!("弟子{}", id), DiscipleType::Inner, vec![Talent { talent_type, level }]);
+            disciple.cultivation.current_level = CultivationLevel::NascentSoul;
+            disciple
+        };
+        game.sect.disciples = vec![
+            with_root(1, TalentType::Fire, 5),
+            with_root(2, TalentType::Water, 9),
+            with_root(3, TalentType::Fire, 1),
+        ];
+        let mut task = Task::new(900, "探索火焰秘境".to_string(), TaskType::Exploration(ExplorationTask {
+            location: "火焰秘境".to_string(),
+            danger_level: 10,
+            realm_type: Some(TalentType::Fire),
+            depth: 0,
+        }), 50, 0);
+
+        // 水灵根弟子无论资质多高都进不了火焰秘境
+        let water = &game.sect.disciples[1];
+        assert!(!task.is_suitable_for_disciple(water));
+        let eligibility = task.check_eligibility(water, &[], true, false, false, 0);
+        assert!(!eligibility.eligible);
+        assert!(eligibility.reason.unwrap().contains("火"));
+        let mut free = game.task_matcher().candidates(&task).free;
+        free.sort();
+        assert_eq!(free, vec![1, 3]);
+
+        // 深入秘境要求更高的灵根等级
+        task.set_exploration_depth(1).unwrap();
+        assert_eq!(game.task_matcher().candidates(&task).free, vec![1]);
+    }
+
+    #[test]
+    fn test_turn_summary_matches_sect_resource_changes() {
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        while game.sect.disciples.len() < 3 {
+            let disciple = game.recruitment_system.generate_random_disciple(game.sect.reputation, &mut rand::thread_rng());
+            game.sect.recruit_disciple(disciple);
         }
+        let ids: Vec<usize> = game.sect.disciples.iter().map(|d| d.id).take(3).collect();
+        for (i, &disciple_id) in ids.iter().enumerate() {
+            let task_id = 9100 + i;
+            let mut task = Task::new(
+                task_id,
+                format!("采药{}", i),
+                crate::task::TaskType::Gathering(crate::task::GatheringTask {
+                    resource_type: "草药".to_string(),
+                    difficulty: 10,
+                }),
+                10,
+                100,
+            );
+            task.reputation_reward = 5;
+            game.current_tasks.push(task);
+            game.task_assignments.push(TaskAssignment {
+                task_id,
+                disciple_ids: vec![disciple_id],
+                ..Default::default()
+            });
+        }
+        game.sect.spirit_stones = 1000;
+        let reputation_before = game.sect.reputation;
+        let contribution_before = game.sect.contribution;
+
+        let summary = game.execute_turn();
+
+        assert_eq!(summary.task_results.len(), 3);
+        assert_eq!(summary.tasks_completed + summary.tasks_failed, 3);
+        let rewarded = summary.task_results.iter().filter(|r| r.outcome != TaskOutcome::Failure);
+        let stones: u32 = rewarded.clone().map(|r| r.resources_gained).sum();
+        let reputation: i32 = rewarded.map(|r| r.reputation_gained).sum();
+        assert_eq!(summary.spirit_stones_delta, stones as i64);
+        assert_eq!(summary.spirit_stones_delta, game.sect.spirit_stones as i64 - 1000);
+        assert_eq!(summary.reputation_delta, reputation);
+        assert_eq!(summary.reputation_delta, game.sect.reputation - reputation_before);
+        assert_eq!(summary.contribution_delta, game.sect.contribution as i64 - contribution_before as i64);
+        assert!(summary.casualties.is_empty());
+    }
+
+    #[test]
+    fn test_cultivation_path_follows_custom_quota_config() {
+        let mut config = CultivationPathsConfig::default_config();
+        config.paths.retain(|p| p.level != CultivationLevel::Foundation);
+        let custom: crate::config::PathQuotaConfig = serde_json::from_value(serde_json::json!({
+            "level": "Foundation",
+            "combat": 1,
+            "gathering": 5
+        }))
+        .unwrap();
+        config.paths.push(custom);
+        let path = std::env::temp_dir().join(format!("xiuxian_paths_{}.json", uuid::Uuid::new_v4()));
+        config.save_to_file(&path).unwrap();
+        let loaded = CultivationPathsConfig::load_from_file(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert!(loaded.validate().is_ok());
+
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        game.cultivation_paths = loaded;
+        let id = game.sect.disciples[0].id;
+        game.sect.disciples[0].cultivation.current_level = CultivationLevel::Foundation;
+        game.generate_cultivation_path_tasks(id);
+
+        let required = &game.sect.disciples[0].cultivation.cultivation_path.as_ref().unwrap().required;
+        let expected: HashMap<String, u32> = [("Combat".to_string(), 1), ("Gathering".to_string(), 5)].into();
+        assert_eq!(required, &expected);
+
+        // 缺少境界或配比总数超限的配置校验失败
+        let mut invalid = CultivationPathsConfig::default_config();
+        invalid.paths[0].combat = crate::config::MAX_CULTIVATION_PATH_TASKS;
+        invalid.paths.pop();
+        assert_eq!(invalid.validate().unwrap_err().len(), 2);
+    }
+
+    #[test]
+    fn test_formation_gathers_around_village_and_slow_disciples_advance_partially() {
+        use crate::map::{MapElement, PositionedElement, Village};
+
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        game.map = GameMap::new();
+        let village = Position { x: 10, y: 10 };
+        game.map.elements.push(PositionedElement {
+            element: MapElement::Village(Village {
+                name: "青石村".to_string(),
+                population: 300,
+                prosperity: 50,
+                task_templates: vec![],
+                peak_prosperity: 50,
+                recovering: false,
+            }),
+            position: village,
+            size: None,
+            positions: None,
+        });
+        game.sect.disciples = vec![
+            Disciple::new(1, "韩立".to_string(), DiscipleType::Outer, vec![]),
+            Disciple::new(2, "厉飞雨".to_string(), DiscipleType::Outer, vec![]),
+            Disciple::new(3, "张铁".to_string(), DiscipleType::Outer, vec![]),
+        ];
+        let starts = [Position { x: 10, y: 12 }, Position { x: 11, y: 9 }, Position { x: 10, y: 0 }];
+        for (disciple, start) in game.sect.disciples.iter_mut().zip(starts) {
+            disciple.move_to(start);
+            disciple.moves_remaining = disciple.get_effective_movement_range();
+        }
+        let range = game.sect.disciples[2].moves_remaining as i32;
+
+        let results = game.move_formation(&[1, 2, 3], village);
+        assert_eq!(results.iter().map(|r| r.disciple_id).collect::<Vec<_>>(), vec![1, 2, 3]);
+
+        // 近处两人抵达村庄及其相邻格，各占一格
+        assert!(results[0].arrived() && results[1].arrived());
+        let dest = |i: usize| results[i].destination.unwrap();
+        assert!(dest(0).x == village.x && dest(0).y == village.y);
+        assert_eq!((dest(1).x - village.x).abs() + (dest(1).y - village.y).abs(), 1);
+
+        // 远处弟子移动力不足，沿路走满本回合移动力后停下
+        let slow = &results[2];
+        assert!(!slow.arrived());
+        let third = dest(2);
+        assert!((third.x - village.x).abs() + (third.y - village.y).abs() <= FORMATION_RADIUS);
+        assert!(third.x != dest(1).x || third.y != dest(1).y);
+        let pos = *slow.result.as_ref().unwrap();
+        assert_eq!((pos.x - 10).abs() + pos.y, range);
+        assert_eq!(game.sect.disciples[2].moves_remaining, 0);
+        assert!(game.sect.disciples[2].is_at_position(&pos));
+    }
+
+    #[test]
+    fn test_stepping_on_opportunity_grants_fortune_and_removes_point() {
+        use crate::config::{OpportunityRewardKind, OpportunityRewardWeight};
+        use crate::disciple::Talent;
+        use crate::map::{MapElement, Opportunity, PositionedElement};
+
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        game.map = GameMap::new();
+        game.map.config.map_elements.opportunities.rewards =
+            vec![OpportunityRewardWeight { reward: OpportunityRewardKind::TechniqueScroll, weight: 1 }];
+        let spots = [Position { x: 5, y: 6 }, Position { x: 8, y: 6 }];
+        for spot in spots {
+            let id = game.map.allocate_opportunity_id();
+            game.map.elements.push(PositionedElement {
+                element: MapElement::Opportunity(Opportunity::new_random(id, 5, &mut rand::thread_rng())),
+                position: spot,
+                size: None,
+                positions: None,
+            });
+        }
+        game.sect.disciples = vec![
+            Disciple::new(1, "韩立".to_string(), DiscipleType::Outer, vec![Talent { talent_type: TalentType::Fire, level: 5 }]),
+            Disciple::new(2, "张铁".to_string(), DiscipleType::Outer, vec![]),
+        ];
+        for (disciple, x) in game.sect.disciples.iter_mut().zip([5, 8]) {
+            disciple.move_to(Position { x, y: 5 });
+            disciple.moves_remaining = disciple.get_effective_movement_range();
+        }
+
+        // 火灵根弟子踩中机缘点，参悟残卷习得火系功法，机缘点随即消失
+        let outcome = game.move_disciple(1, spots[0]).unwrap();
+        let encounter = outcome.opportunity.expect("踩中机缘点应触发奇遇");
+        let OpportunityGain::Technique(name) = encounter.gain else { panic!("应获得功法残卷") };
+        assert!(game.sect.disciples[0].learned_techniques.iter().any(|t| t.name == name));
+        assert!(game.map.take_opportunity_at(spots[0].x, spots[0].y).is_none());
+        assert!(game.map.elements.iter().any(|e| matches!(e.element, MapElement::Opportunity(_))));
+
+        // 无灵根弟子学不了任何功法，奇遇改为一笔灵石
+        let stones_before = game.sect.spirit_stones;
+        let gain = game.move_disciple(2, spots[1]).unwrap().opportunity.unwrap().gain;
+        let OpportunityGain::SpiritStones(amount) = gain else { panic!("应改为发放灵石") };
+        assert!(OPPORTUNITY_SPIRIT_STONES.contains(&amount));
+        assert_eq!(game.sect.spirit_stones, stones_before + amount);
+        assert!(!game.map.elements.iter().any(|e| matches!(e.element, MapElement::Opportunity(_))));
+
+        // 再次经过原处不会重复触发
+        game.sect.disciples[1].moves_remaining = 2;
+        game.move_disciple(2, Position { x: 8, y: 5 }).unwrap();
+        assert!(game.move_disciple(2, spots[1]).unwrap().opportunity.is_none());
+    }
+
+    #[test]
+    fn test_disciple_refuses_to_team_with_enemy_until_dao_heart_is_raised() {
+        use crate::relationship::RelationDimension;
+        use crate::sect::PERSUASION_COST;
+
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        game.map = GameMap::new();
+        game.sect.disciples = vec![
+            Disciple::new(1, "韩立".to_string(), DiscipleType::Outer, vec![]),
+            Disciple::new(2, "墨大夫".to_string(), DiscipleType::Outer, vec![]),
+            Disciple::new(3, "南宫婉".to_string(), DiscipleType::Outer, vec![]),
+        ];
+        for disciple in &mut game.sect.disciples {
+            disciple.dao_heart = 40;
+        }
+        game.sect.disciples[2].set_personality(Personality::Arrogant);
+        game.current_tasks = vec![Task::new(1, "采集灵草".to_string(), TaskType::Gathering(crate::task::GatheringTask {
+            resource_type: "灵草".to_string(),
+            difficulty: 1,
+        }), 10, 10)];
+        game.task_assignments = vec![TaskAssignment { task_id: 1, ..Default::default() }];
+        assert_eq!(game.dispatch_to_task(1, 2), Ok(None));
+        game.sect.update_relationship_score(1, 2, RelationDimension::Enmity, 50).unwrap();
+
+        // 韩立与队中仇敌势同水火，抗命
+        let refused = game.check_assignment_consent(1, 1, &[], 1.0).unwrap_err();
+        assert_eq!(refused.code, "DISCIPLE_REFUSED");
+        assert!(refused.message.contains("墨大夫"));
+
+        // 道心提升后顾全大局，接受指派
+        game.sect.disciples[0].dao_heart = 80;
+        assert_eq!(game.check_assignment_consent(1, 1, &[], 1.0), Ok(()));
+
+        // 情谊压过敌意后也不再视其为仇敌
+        game.sect.disciples[0].dao_heart = 40;
+        game.sect.update_relationship_score(1, 2, RelationDimension::Comrade, 60).unwrap();
+        assert_eq!(game.check_assignment_consent(1, 1, &[], 1.0), Ok(()));
+
+        // 高傲弟子按概率抗命，花费贡献点可说服一次
+        assert_eq!(game.check_assignment_consent(3, 1, &[], 0.0).unwrap_err().code, "DISCIPLE_REFUSED");
+        assert_eq!(game.check_assignment_consent(3, 1, &[], ARROGANT_REFUSAL_CHANCE), Ok(()));
+        game.sect.contribution = PERSUASION_COST;
+        game.sect.persuade_disciple(3).unwrap();
+        assert_eq!(game.sect.contribution, 0);
+        assert_eq!(game.check_assignment_consent(3, 1, &[], 0.0), Ok(()));
+        game.dispatch_to_task(1, 3).unwrap();
+        assert!(!game.sect.disciples[2].persuaded);
+    }
+
+    #[test]
+    fn test_three_disciples_overpower_monster_that_defeats_one() {
+        use crate::map::{MapElement, Monster, PositionedElement};
+        use crate::task::{team_combat_success_rate, CombatTask};
+
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        game.map = GameMap::new();
+        game.map.elements.push(PositionedElement {
+            element: MapElement::Monster(Monster::new(7, "赤焰狼".to_string(), 2, vec![])),
+            position: Position { x: 5, y: 5 },
+            size: None,
+            positions: None,
+        });
+        let task = Task::new(1, "守卫青石村".to_string(), TaskType::Combat(CombatTask {
+            enemy_id: Some(7),
+            enemy_name: "赤焰狼".to_string(),
+            enemy_level: 2,
+            difficulty: 0,
+        }), 10, 10);
+        let recruits = || {
+            ["韩立", "厉飞雨", "张铁"]
+                .iter()
+                .enumerate()
+                .map(|(i, name)| Disciple::new(i + 1, name.to_string(), DiscipleType::Outer, vec![]))
+                .collect::<Vec<_>>()
+        };
+        game.sect.disciples = recruits();
+
+        // 练气初期弟子单打独斗，战力只及妖狼一半
+        let solo_ratio = game.team_power_ratio(&[1], &task, 0.0);
+        assert!((solo_ratio - 0.5).abs() < 1e-4);
+        assert!(team_combat_success_rate(solo_ratio) < 0.2);
+        let (mut wins, mut casualties) = (0, 0);
+        for _ in 0..100 {
+            game.sect.disciples = recruits();
+            let result = game.execute_combat_task(&[1], &task, 0.0).remove(0);
+            wins += result.success as u32;
+            casualties += (result.disciple_died || !game.sect.disciples[0].injuries.is_empty()) as u32;
+        }
+        assert!(wins < 40, "单人胜场过多：{}", wins);
+        assert!(casualties > 0);
+
+        // 三人合力兵力压倒，速胜且无人伤亡
+        game.sect.disciples = recruits();
+        let synergy = team_synergy_bonus(&game.sect.disciples.iter().collect::<Vec<_>>());
+        assert!(game.team_power_ratio(&[1, 2, 3], &task, synergy) >= crate::task::OVERWHELMING_POWER_RATIO);
+        let results = game.execute_combat_task(&[1, 2, 3], &task, synergy);
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().all(|r| r.success && !r.disciple_died));
+        assert!(results.iter().all(|r| r.combat.as_ref().is_some_and(|c| c.success_rate == 1.0)));
+        assert!(game.sect.disciples.iter().all(|d| d.is_alive() && d.injuries.is_empty()));
+    }
+
+    #[test]
+    fn test_combat_preview_matches_team_settlement_rate() {
+        use crate::map::{MapElement, Monster, PositionedElement};
+        use crate::task::{team_combat_success_rate, CombatTask};
+
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        game.map = GameMap::new();
+        game.map.elements.push(PositionedElement {
+            element: MapElement::Monster(Monster::new(7, "赤焰狼".to_string(), 2, vec![])),
+            position: Position { x: 5, y: 5 },
+            size: None,
+            positions: None,
+        });
+        let task = Task::new(1, "守卫青石村".to_string(), TaskType::Combat(CombatTask {
+            enemy_id: Some(7),
+            enemy_name: "赤焰狼".to_string(),
+            enemy_level: 2,
+            difficulty: 0,
+        }), 10, 10);
+        game.sect.disciples = ["韩立", "厉飞雨"]
+            .iter()
+            .enumerate()
+            .map(|(i, name)| Disciple::new(i + 1, name.to_string(), DiscipleType::Outer, vec![]))
+            .collect();
+
+        // 单人预览与单人结算一致
+        let solo = game.preview_task_reward(&task, &game.sect.disciples[0]);
+        let solo_rate = team_combat_success_rate(game.team_power_ratio(&[1], &task, 0.0));
+        assert!((solo.success_rate as f64 - solo_rate).abs() < 1e-6);
+        assert!((task.estimated_success_rate(&game.sect.disciples[0], false) as f64 - solo_rate).abs() < 1e-6);
+
+        // 加入已有队伍后按全队兵力预览
+        game.task_assignments.push(TaskAssignment {
+            task_id: task.id,
+            disciple_ids: vec![1],
+            started_turn: None,
+            progress: 0,
+            ..Default::default()
+        });
+        let preview = game.preview_task_reward(&task, &game.sect.disciples[1]);
+        let synergy = team_synergy_bonus(&game.sect.disciples.iter().collect::<Vec<_>>());
+        let team_rate = team_combat_success_rate(game.team_power_ratio(&[1, 2], &task, synergy));
+        assert!((preview.success_rate as f64 - team_rate).abs() < 1e-6);
+        assert!(preview.success_rate > solo.success_rate);
+    }
+
+    #[test]
+    fn test_large_element_task_lists_disciples_on_any_tile() {
+        use crate::task::GatheringTask;
+
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        game.sect.disciples = vec![
+            Disciple::new(1, "韩立".to_string(), DiscipleType::Outer, vec![]),
+            Disciple::new(2, "南宫婉".to_string(), DiscipleType::Outer, vec![]),
+        ];
+        game.sect.disciples[0].position = Position { x: 6, y: 5 };
+        game.sect.disciples[1].position = Position { x: 9, y: 9 };
+        let mut task = Task::new(10, "采集灵草".to_string(), TaskType::Gathering(GatheringTask {
+            resource_type: "灵草".to_string(),
+            difficulty: 1,
+        }), 10, 10);
+        task.position = Some(Position { x: 5, y: 5 });
+        task.valid_positions = Some(vec![Position { x: 5, y: 5 }, Position { x: 6, y: 5 }]);
+        game.current_tasks = vec![task.clone()];
+        game.task_assignments = vec![TaskAssignment { task_id: 10, ..Default::default() }];
+
+        // 站在大型元素非锚点格子上的弟子同样列为候选，并可被自动分配
+        assert_eq!(game.task_matcher().candidates(&task).free, vec![1]);
+        game.auto_assign_remaining(AssignStrategy::FirstFit);
+        assert!(game.task_assignments[0].contains_disciple(1));
+        assert!(!game.task_assignments[0].contains_disciple(2));
+    }
+
+    #[test]
+    fn test_dispatch_batch_plans_expeditions_and_rolls_back_on_failure() {
+        use crate::task::GatheringTask;
+
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        game.map = GameMap::new();
+        game.sect.disciples = vec![
+            Disciple::new(1, "韩立".to_string(), DiscipleType::Outer, vec![]),
+            Disciple::new(2, "厉飞雨".to_string(), DiscipleType::Outer, vec![]),
+            Disciple::new(3, "张铁".to_string(), DiscipleType::Outer, vec![]),
+        ];
+        for disciple in &mut game.sect.disciples {
+            disciple.position = Position { x: 10, y: 10 };
+        }
+        game.sect.disciples[0].persuaded = true;
+        let gathering = |id: usize| {
+            let mut task = Task::new(id, "采集灵草".to_string(), TaskType::Gathering(GatheringTask {
+                resource_type: "灵草".to_string(),
+                difficulty: 1,
+            }), 10, 10);
+            task.position = Some(Position { x: 4, y: 10 });
+            task
+        };
+        game.current_tasks = vec![gathering(1), gathering(2)];
+        // 任务2缺少分配记录，派遣必然失败
+        game.task_assignments = vec![TaskAssignment { task_id: 1, ..Default::default() }];
+
+        // 中间一项失败，前面已生效的派遣全部撤回
+        let (index, disciple_id, error) = game.dispatch_batch(&[(1, &[1]), (2, &[2]), (1, &[3])]).unwrap_err();
+        assert_eq!((index, disciple_id, error.code), (1, 2, "ASSIGNMENT_NOT_FOUND"));
+        assert!(game.task_assignments[0].disciple_ids.is_empty());
+        assert_eq!(game.task_assignments[0].phase, AssignmentPhase::default());
+        assert!(game.sect.disciples[0].persuaded);
+
+        // 全部成功时与单个派遣一样踏上远征
+        game.dispatch_batch(&[(1, &[1, 3])]).unwrap();
+        assert_eq!(game.task_assignments[0].disciple_ids, vec![1, 3]);
+        assert_eq!(game.task_assignments[0].phase, AssignmentPhase::Traveling);
+        assert!(!game.sect.disciples[0].persuaded);
     }
 }
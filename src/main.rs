@@ -15,6 +15,10 @@ mod config;
 mod modifier;
 mod building;
 mod relationship;
+mod technique;
+mod equipment;
+mod tournament;
+mod achievement;
 
 use interactive::InteractiveGame;
 use ui::UI;
@@ -75,10 +79,22 @@ async fn main() {
             let years = UI::get_number_input("模拟年数 (建议100): ", 1, 1000);
             let years = years.unwrap_or(100);
 
+            // 输入种子可复现同一局模拟，留空则随机
+            let seed = UI::get_input("随机种子 (留空随机): ").parse::<u64>().ok();
+
             UI::wait_for_enter("\n按回车开始模拟...");
 
-            let mut game = game::Game::new(sect_name);
-            game.run(years as u32);
+            let mut game = game::Game::new(sect_name, seed);
+            let report = game.run(years as u32);
+
+            // 导出时间序列报告
+            let path = UI::get_input("\n导出报告CSV路径 (留空跳过): ");
+            if !path.is_empty() {
+                match std::fs::write(&path, report.to_csv()) {
+                    Ok(()) => UI::success(&format!("报告已导出至 {}", path)),
+                    Err(e) => UI::error(&format!("导出失败：{}", e)),
+                }
+            }
         }
         Some(3) => {
             // Web服务器模式
@@ -1,25 +1,31 @@
-use crate::task::{Task, TaskType, GatheringTask, CombatTask, ExplorationTask, AuxiliaryTask};
-use crate::disciple::TalentType;
+use crate::task::{Task, TaskType, GatheringTask, CombatTask, ExplorationTask, AuxiliaryTask, InvestmentTask, RiskLevel};
+use crate::disciple::{Disciple, TalentType};
+use crate::cultivation::CultivationLevel;
 use crate::config::{
     ConfigManager, TaskTemplateConfig, VillageTemplate, FactionTemplate,
-    DangerousLocationTemplate, SecretRealmTemplate, MonsterTemplate,
+    DangerousLocationTemplate, SecretRealmTemplate, MonsterTemplate, MonsterNestTemplate, PositionConfig, SizeConfig,
+    DropItemConfig, MonsterDropConfig, OpportunityRewardKind,
 };
-use serde::Serialize;
+use crate::pill::{PillGrade, PillType};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 
 /// 地图元素类型
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum MapElement {
     Village(Village),
     Faction(Faction),
     DangerousLocation(DangerousLocation),
     SecretRealm(SecretRealm),
     Monster(Monster),
+    MonsterNest(MonsterNest), // 妖魔巢穴
     Terrain(Terrain),  // 基础地形要素
     Herb(Herb),        // 草药
+    Opportunity(Opportunity), // 机缘点
 }
 
 /// 地形类型
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TerrainType {
     Mountain,  // 山
     Water,     // 水
@@ -28,7 +34,7 @@ pub enum TerrainType {
 }
 
 /// 地形要素（不产生任务）
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Terrain {
     pub terrain_type: TerrainType,
     pub name: String,
@@ -36,14 +42,14 @@ pub struct Terrain {
 }
 
 /// 地图坐标
-#[derive(Debug, Clone, Copy, Serialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Position {
     pub x: i32,
     pub y: i32,
 }
 
 /// 带坐标的地图元素
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PositionedElement {
     pub element: MapElement,
     pub position: Position,  // core_position
@@ -90,9 +96,68 @@ impl PositionedElement {
     pub fn get_size(&self) -> (u32, u32) {
         self.size.unwrap_or((1, 1))
     }
+
+    /// 元素是否有格子落在包围盒内
+    pub fn intersects(&self, bounds: &MapBounds) -> bool {
+        self.get_all_positions().iter().any(|p| bounds.contains(p))
+    }
+
+    /// 是否固定占据格子（草药、妖魔不参与占格登记）
+    pub fn occupies_tiles(&self) -> bool {
+        !matches!(self.element, MapElement::Herb(_) | MapElement::Monster(_) | MapElement::Opportunity(_))
+    }
+
+    /// 将任务定位到此元素（大型元素占据的每一格都可执行任务）
+    pub fn locate_task(&self, task: &mut Task) {
+        task.position = Some(self.position);
+        let all_positions = self.get_all_positions();
+        if all_positions.len() > 1 {
+            task.valid_positions = Some(all_positions);
+        }
+    }
+}
+
+/// 地图查询的包围盒（闭区间，未指定的边界不限制）
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MapBounds {
+    pub min_x: Option<i32>,
+    pub max_x: Option<i32>,
+    pub min_y: Option<i32>,
+    pub max_y: Option<i32>,
+}
+
+impl MapBounds {
+    /// 坐标是否在包围盒内
+    pub fn contains(&self, pos: &Position) -> bool {
+        self.min_x.map_or(true, |v| pos.x >= v)
+            && self.max_x.map_or(true, |v| pos.x <= v)
+            && self.min_y.map_or(true, |v| pos.y >= v)
+            && self.max_y.map_or(true, |v| pos.y <= v)
+    }
 }
 
 impl MapElement {
+    /// 所有元素类型名称
+    pub const TYPE_NAMES: [&'static str; 9] = [
+        "Village", "Faction", "DangerousLocation", "SecretRealm", "Monster", "MonsterNest", "Terrain", "Herb",
+        "Opportunity",
+    ];
+
+    /// 获取元素类型名称
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            MapElement::Village(_) => "Village",
+            MapElement::Faction(_) => "Faction",
+            MapElement::DangerousLocation(_) => "DangerousLocation",
+            MapElement::SecretRealm(_) => "SecretRealm",
+            MapElement::Monster(_) => "Monster",
+            MapElement::MonsterNest(_) => "MonsterNest",
+            MapElement::Terrain(_) => "Terrain",
+            MapElement::Herb(_) => "Herb",
+            MapElement::Opportunity(_) => "Opportunity",
+        }
+    }
+
     /// 获取地图元素的唯一标识符
     pub fn get_location_id(&self) -> String {
         match self {
@@ -101,22 +166,41 @@ impl MapElement {
             MapElement::DangerousLocation(d) => format!("danger_{}", d.name),
             MapElement::SecretRealm(s) => format!("realm_{}", s.name),
             MapElement::Monster(m) => format!("monster_{}", m.id),
+            MapElement::MonsterNest(n) => format!("nest_{}", n.name),
             MapElement::Terrain(t) => format!("terrain_{}", t.name),
             MapElement::Herb(h) => format!("herb_{}", h.id),
+            MapElement::Opportunity(o) => format!("opportunity_{}", o.id),
+        }
+    }
+
+    /// 获取地图元素的名称
+    pub fn get_name(&self) -> String {
+        match self {
+            MapElement::Village(v) => v.name.clone(),
+            MapElement::Faction(f) => f.name.clone(),
+            MapElement::DangerousLocation(d) => d.name.clone(),
+            MapElement::SecretRealm(s) => s.name.clone(),
+            MapElement::Monster(m) => m.name.clone(),
+            MapElement::MonsterNest(n) => n.name.clone(),
+            MapElement::Terrain(t) => t.name.clone(),
+            MapElement::Herb(h) => h.name.clone(),
+            MapElement::Opportunity(o) => o.name.clone(),
         }
     }
 
     /// 生成对应的任务
-    pub fn generate_tasks(&self, task_id_start: usize) -> Vec<Task> {
+    pub fn generate_tasks<R: rand::Rng>(&self, task_id_start: usize, rng: &mut R) -> Vec<Task> {
         let location_id = self.get_location_id();
         let mut tasks = match self {
-            MapElement::Village(v) => v.generate_tasks(task_id_start),
+            MapElement::Village(v) => v.generate_tasks(task_id_start, rng),
             MapElement::Faction(f) => f.generate_tasks(task_id_start),
-            MapElement::DangerousLocation(d) => d.generate_tasks(task_id_start),
-            MapElement::SecretRealm(s) => s.generate_tasks(task_id_start),
+            MapElement::DangerousLocation(d) => d.generate_tasks(task_id_start, rng),
+            MapElement::SecretRealm(s) => s.generate_tasks(task_id_start, rng),
             MapElement::Monster(m) => m.generate_tasks(task_id_start),
+            MapElement::MonsterNest(n) => n.generate_tasks(task_id_start),
             MapElement::Terrain(_) => Vec::new(),  // 地形不产生任务
             MapElement::Herb(_) => Vec::new(),     // 草药不产生任务
+            MapElement::Opportunity(_) => Vec::new(), // 机缘点靠弟子踩中触发
         };
 
         // 为所有任务设置location_id
@@ -155,6 +239,26 @@ impl MapElement {
         }
     }
 
+    /// 字符网格中的显示优先级与符号
+    pub fn ascii_symbol(&self) -> (u8, char) {
+        match self {
+            MapElement::Monster(_) => (5, 'M'),
+            MapElement::MonsterNest(_) => (4, 'N'),
+            MapElement::Village(_) => (3, 'V'),
+            MapElement::Faction(_) => (3, 'F'),
+            MapElement::SecretRealm(_) => (3, 'S'),
+            MapElement::DangerousLocation(_) => (3, 'D'),
+            MapElement::Herb(_) => (2, '*'),
+            MapElement::Opportunity(_) => (2, '?'),
+            MapElement::Terrain(t) => (1, match t.terrain_type {
+                TerrainType::Mountain => '^',
+                TerrainType::Water => '~',
+                TerrainType::Forest => 'T',
+                TerrainType::Plain => ',',
+            }),
+        }
+    }
+
     /// 检查是否是妖魔并且返回可变引用
     pub fn as_monster_mut(&mut self) -> Option<&mut Monster> {
         match self {
@@ -173,12 +277,16 @@ impl MapElement {
 }
 
 /// 村庄
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Village {
     pub name: String,
     pub population: u32,
     pub prosperity: u32, // 繁荣度
     pub task_templates: Vec<TaskTemplateConfig>,
+    #[serde(default)]
+    pub peak_prosperity: u32, // 未遭劫掠时的繁荣度（恢复上限）
+    #[serde(default)]
+    pub recovering: bool, // 守卫成功后逐步恢复繁荣
 }
 
 impl Village {
@@ -188,15 +296,34 @@ impl Village {
             population: template.population,
             prosperity: template.prosperity,
             task_templates: template.task_templates.clone(),
+            peak_prosperity: template.prosperity,
+            recovering: false,
         }
     }
 
-    pub fn generate_tasks(&self, task_id_start: usize) -> Vec<Task> {
+    /// 遭受一回合妖魔劫掠
+    pub fn suffer_raid(&mut self) {
+        self.peak_prosperity = self.peak_prosperity.max(self.prosperity);
+        self.prosperity = self.prosperity.saturating_sub(VILLAGE_RAID_PROSPERITY_LOSS);
+        self.population -= (self.population as f32 * VILLAGE_RAID_POPULATION_LOSS_RATE).ceil() as u32;
+        self.recovering = false;
+    }
+
+    /// 恢复一回合繁荣，恢复到劫掠前水平后停止
+    pub fn recover(&mut self) {
+        let peak = self.peak_prosperity.max(self.prosperity);
+        self.prosperity = (self.prosperity + VILLAGE_RECOVERY_PER_TURN).min(peak);
+        if self.prosperity >= peak {
+            self.recovering = false;
+        }
+    }
+
+    pub fn generate_tasks<R: rand::Rng>(&self, task_id_start: usize, rng: &mut R) -> Vec<Task> {
         use rand::seq::SliceRandom;
-        use std::collections::HashMap;
+        use std::collections::BTreeMap;
 
-        // 按任务类型分组
-        let mut templates_by_type: HashMap<String, Vec<&TaskTemplateConfig>> = HashMap::new();
+        // 按任务类型分组（有序遍历，保证相同种子生成相同任务）
+        let mut templates_by_type: BTreeMap<String, Vec<&TaskTemplateConfig>> = BTreeMap::new();
         for template in &self.task_templates {
             templates_by_type
                 .entry(template.task_type.clone())
@@ -206,11 +333,10 @@ impl Village {
 
         // 每种任务类型只随机选择一个模板
         let mut tasks = Vec::new();
-        let mut rng = rand::thread_rng();
         let mut task_id = task_id_start;
 
         for (_task_type, template_list) in templates_by_type {
-            if let Some(template) = template_list.choose(&mut rng) {
+            if let Some(template) = template_list.choose(rng) {
                 if let Some(task) = self.generate_task_from_template(task_id, template) {
                     tasks.push(task);
                     task_id += 1;
@@ -235,6 +361,7 @@ impl Village {
         task.reputation_reward = template.reputation_reward;
         task.dao_heart_impact = template.dao_heart_impact;
 
+        task.attach_chain(template, &self.name);
         Some(task)
     }
 
@@ -251,8 +378,17 @@ impl Village {
     }
 }
 
+/// 完成势力友好任务提升的关系
+pub const FACTION_FRIENDLY_TASK_RELATION: i32 = 5;
+
+/// 完成针对势力的敌对/镇压任务降低的关系
+pub const FACTION_HOSTILE_TASK_RELATION: i32 = -10;
+
+/// 势力关系低于该值时只发布敌对任务
+pub const FACTION_HOSTILE_THRESHOLD: i32 = -30;
+
 /// 势力
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Faction {
     pub name: String,
     pub power_level: u32,
@@ -282,7 +418,7 @@ impl Faction {
                     tasks.push(task);
                 }
             }
-        } else if self.relationship < -30 {
+        } else if self.relationship < FACTION_HOSTILE_THRESHOLD {
             // 使用敌对任务模板
             for (i, template) in self.hostile_task_templates.iter().enumerate() {
                 let task_type = match template.task_type.as_str() {
@@ -327,9 +463,25 @@ impl Faction {
         task.reputation_reward = template.reputation_reward;
         task.dao_heart_impact = template.dao_heart_impact;
 
+        task.attach_chain(template, &self.name);
         Some(task)
     }
 
+    /// 是否为针对该势力的敌对/镇压任务
+    pub fn is_hostile_task(&self, task: &Task) -> bool {
+        matches!(&task.task_type, TaskType::Combat(combat) if combat.enemy_name == self.name)
+    }
+
+    /// 完成任务后的关系变化：友好任务拉近关系，敌对任务加深敌意
+    pub fn settle_task(&mut self, task: &Task) {
+        let delta = if self.is_hostile_task(task) {
+            FACTION_HOSTILE_TASK_RELATION
+        } else {
+            FACTION_FRIENDLY_TASK_RELATION
+        };
+        self.relationship = (self.relationship + delta).clamp(-100, 100);
+    }
+
     pub fn get_income(&self, reputation: i32) -> u32 {
         if self.relationship > 50 && reputation > 80 {
             self.power_level * 5
@@ -342,7 +494,7 @@ impl Faction {
 }
 
 /// 险要之地
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DangerousLocation {
     pub name: String,
     pub danger_level: u32,
@@ -358,12 +510,12 @@ impl DangerousLocation {
         }
     }
 
-    pub fn generate_tasks(&self, task_id_start: usize) -> Vec<Task> {
+    pub fn generate_tasks<R: rand::Rng>(&self, task_id_start: usize, rng: &mut R) -> Vec<Task> {
         use rand::seq::SliceRandom;
-        use std::collections::HashMap;
+        use std::collections::BTreeMap;
 
-        // 按任务类型分组
-        let mut templates_by_type: HashMap<String, Vec<&TaskTemplateConfig>> = HashMap::new();
+        // 按任务类型分组（有序遍历，保证相同种子生成相同任务）
+        let mut templates_by_type: BTreeMap<String, Vec<&TaskTemplateConfig>> = BTreeMap::new();
         for template in &self.task_templates {
             templates_by_type
                 .entry(template.task_type.clone())
@@ -373,11 +525,10 @@ impl DangerousLocation {
 
         // 每种任务类型只随机选择一个模板
         let mut tasks = Vec::new();
-        let mut rng = rand::thread_rng();
         let mut task_id = task_id_start;
 
         for (_task_type, template_list) in templates_by_type {
-            if let Some(template) = template_list.choose(&mut rng) {
+            if let Some(template) = template_list.choose(rng) {
                 if let Some(task) = self.generate_task_from_template(task_id, template) {
                     tasks.push(task);
                     task_id += 1;
@@ -402,12 +553,13 @@ impl DangerousLocation {
         task.reputation_reward = template.reputation_reward;
         task.dao_heart_impact = template.dao_heart_impact;
 
+        task.attach_chain(template, &self.name);
         Some(task)
     }
 }
 
 /// 秘境
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SecretRealm {
     pub name: String,
     pub realm_type: TalentType, // 秘境类型，对应某种资质
@@ -426,12 +578,12 @@ impl SecretRealm {
         }
     }
 
-    pub fn generate_tasks(&self, task_id_start: usize) -> Vec<Task> {
+    pub fn generate_tasks<R: rand::Rng>(&self, task_id_start: usize, rng: &mut R) -> Vec<Task> {
         use rand::seq::SliceRandom;
-        use std::collections::HashMap;
+        use std::collections::BTreeMap;
 
-        // 按任务类型分组
-        let mut templates_by_type: HashMap<String, Vec<&TaskTemplateConfig>> = HashMap::new();
+        // 按任务类型分组（有序遍历，保证相同种子生成相同任务）
+        let mut templates_by_type: BTreeMap<String, Vec<&TaskTemplateConfig>> = BTreeMap::new();
         for template in &self.task_templates {
             templates_by_type
                 .entry(template.task_type.clone())
@@ -441,11 +593,10 @@ impl SecretRealm {
 
         // 每种任务类型只随机选择一个模板
         let mut tasks = Vec::new();
-        let mut rng = rand::thread_rng();
         let mut task_id = task_id_start;
 
         for (_task_type, template_list) in templates_by_type {
-            if let Some(template) = template_list.choose(&mut rng) {
+            if let Some(template) = template_list.choose(rng) {
                 if let Some(task) = self.generate_task_from_template(task_id, template) {
                     tasks.push(task);
                     task_id += 1;
@@ -458,7 +609,10 @@ impl SecretRealm {
 
     fn generate_task_from_template(&self, task_id: usize, template: &TaskTemplateConfig) -> Option<Task> {
         let name = template.name_template.replace("{name}", &self.name);
-        let task_type = parse_task_type(template)?;
+        let mut task_type = parse_task_type(template)?;
+        if let TaskType::Exploration(exploration) = &mut task_type {
+            exploration.realm_type = Some(self.realm_type.clone());
+        }
 
         let mut task = Task::new(
             task_id,
@@ -470,12 +624,13 @@ impl SecretRealm {
         task.reputation_reward = template.reputation_reward;
         task.dao_heart_impact = template.dao_heart_impact;
 
+        task.attach_chain(template, &self.name);
         Some(task)
     }
 }
 
 /// 怪物/妖魔
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Monster {
     pub id: usize, // 唯一标识符
     pub name: String,
@@ -487,14 +642,47 @@ pub struct Monster {
     pub is_being_fought: bool, // 是否正在被战斗
     pub invaded_location_id: Option<String>, // 当前入侵的地点ID
     pub has_active_defense_task: bool, // 是否有正在执行的守卫任务（用于锁定移动）
+    #[serde(default)]
+    pub targeting_sect: bool, // 是否已锁定宗门为进攻目标
+    #[serde(default)]
+    pub nest_name: Option<String>, // 孵化出它的巢穴
+    #[serde(default)]
+    pub drops: Vec<MonsterDropConfig>, // 掉落表
+    #[serde(default)]
+    pub undefended_turns: u32, // 入侵地点连续无人守卫的回合数
 }
 
-// 全局妖魔ID计数器
-static NEXT_MONSTER_ID: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+/// 每级妖魔提升的掉落概率比例
+const DROP_CHANCE_PER_LEVEL: f64 = 0.02;
+/// 成魔后的掉落概率倍率
+const DEMON_DROP_MULTIPLIER: f64 = 1.5;
+/// 每多少级掉落品质提升一档
+const DROP_TIER_LEVELS: u32 = 20;
+/// 掉落品质额外提升一档的概率
+const DROP_UPGRADE_CHANCE: f64 = 0.3;
+
+/// 妖魔掉落物
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MonsterDrop {
+    Herb { name: String, quality: HerbQuality },
+    Pill { pill_type: PillType, grade: PillGrade },
+    Material { name: String, count: u32 },
+}
+
+impl MonsterDrop {
+    /// 掉落物描述
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Herb { name, quality } => format!("{}（{}）", name, quality.name()),
+            Self::Pill { pill_type, grade } => format!("{}{}", grade.name(), pill_type.name()),
+            Self::Material { name, count } => format!("{}x{}", name, count),
+        }
+    }
+}
 
 impl Monster {
-    pub fn from_template(template: &MonsterTemplate) -> Self {
-        let id = NEXT_MONSTER_ID.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    /// 从模板创建妖魔（id 由 GameMap::allocate_monster_id 分配）
+    pub fn from_template(id: usize, template: &MonsterTemplate) -> Self {
         Self {
             id,
             name: template.name.clone(),
@@ -506,12 +694,15 @@ impl Monster {
             is_being_fought: false,
             invaded_location_id: None,
             has_active_defense_task: false,
+            targeting_sect: false,
+            nest_name: None,
+            drops: template.drops.clone(),
+            undefended_turns: 0,
         }
     }
 
     /// 创建新妖魔（用于随机生成）
-    pub fn new(name: String, level: u32, task_templates: Vec<TaskTemplateConfig>) -> Self {
-        let id = NEXT_MONSTER_ID.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    pub fn new(id: usize, name: String, level: u32, task_templates: Vec<TaskTemplateConfig>) -> Self {
         Self {
             id,
             name,
@@ -523,7 +714,60 @@ impl Monster {
             is_being_fought: false,
             invaded_location_id: None,
             has_active_defense_task: false,
+            targeting_sect: false,
+            nest_name: None,
+            drops: Vec::new(),
+            undefended_turns: 0,
+        }
+    }
+
+    /// 单项掉落的实际概率（等级越高、成魔后越容易掉落）
+    pub fn drop_chance(&self, base_chance: f64) -> f64 {
+        let demon_multiplier = if self.is_demon { DEMON_DROP_MULTIPLIER } else { 1.0 };
+        (base_chance * (1.0 + self.level as f64 * DROP_CHANCE_PER_LEVEL) * demon_multiplier).min(1.0)
+    }
+
+    /// 掉落品质档位（成魔额外提升一档）
+    fn drop_tier(&self) -> usize {
+        (self.level / DROP_TIER_LEVELS) as usize + usize::from(self.is_demon)
+    }
+
+    /// 按掉落表抽取讨伐所得
+    pub fn roll_drops<R: rand::Rng>(&self, rng: &mut R) -> Vec<MonsterDrop> {
+        const QUALITIES: [HerbQuality; 5] = [
+            HerbQuality::Common,
+            HerbQuality::Uncommon,
+            HerbQuality::Rare,
+            HerbQuality::Epic,
+            HerbQuality::Legendary,
+        ];
+
+        let mut drops = Vec::new();
+        for entry in &self.drops {
+            if !rng.gen_bool(self.drop_chance(entry.chance)) {
+                continue;
+            }
+            let tier = self.drop_tier() + usize::from(rng.gen_bool(DROP_UPGRADE_CHANCE));
+            let drop = match &entry.item {
+                DropItemConfig::Herb { name } => MonsterDrop::Herb {
+                    name: name.clone(),
+                    quality: QUALITIES[tier.min(QUALITIES.len() - 1)],
+                },
+                DropItemConfig::Pill { pill_type } => match PillType::from_str(pill_type) {
+                    Some(pill_type) => MonsterDrop::Pill {
+                        pill_type,
+                        grade: PillGrade::ALL[tier.min(PillGrade::ALL.len() - 1)],
+                    },
+                    None => continue,
+                },
+                DropItemConfig::Material { name } => MonsterDrop::Material {
+                    name: name.clone(),
+                    count: 1 + tier as u32,
+                },
+            };
+            drops.push(drop);
         }
+        drops
     }
 
     /// 生成任务（只有在没有关联任务时才生成）
@@ -567,6 +811,7 @@ impl Monster {
         task.reputation_reward = template.reputation_reward;
         task.dao_heart_impact = template.dao_heart_impact;
 
+        task.attach_chain(template, &task_display_name);
         Some(task)
     }
 
@@ -594,8 +839,88 @@ impl Monster {
     }
 }
 
-/// 草药品质
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// 讨伐巢穴任务的参与人数上限
+pub const NEST_RAID_MAX_PARTICIPANTS: u32 = 5;
+
+/// 妖魔巢穴（周期性孵化妖魔，并让附近妖魔获得等级加成）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MonsterNest {
+    pub name: String,
+    pub level: u32,
+    pub spawn_interval: u32,
+    pub turns_until_spawn: u32, // 距下次孵化的回合数
+    pub max_brood: u32,
+    pub aura_radius: u32,
+    pub aura_level_bonus: u32,
+    pub task_templates: Vec<TaskTemplateConfig>,
+}
+
+impl MonsterNest {
+    pub fn from_template(template: &MonsterNestTemplate) -> Self {
+        Self {
+            name: template.name.clone(),
+            level: template.level,
+            spawn_interval: template.spawn_interval,
+            turns_until_spawn: template.spawn_interval,
+            max_brood: template.max_brood,
+            aura_radius: template.aura_radius,
+            aura_level_bonus: template.aura_level_bonus,
+            task_templates: template.task_templates.clone(),
+        }
+    }
+
+    /// 推进孵化倒计时，到期时返回 true 并重新计时
+    pub fn tick(&mut self) -> bool {
+        self.turns_until_spawn = self.turns_until_spawn.saturating_sub(1);
+        if self.turns_until_spawn > 0 {
+            return false;
+        }
+        self.turns_until_spawn = self.spawn_interval.max(1);
+        true
+    }
+
+    /// 孵化出的妖魔等级
+    pub fn brood_level(&self) -> u32 {
+        (self.level / 2).max(1)
+    }
+
+    /// 坐标是否在巢穴的妖气笼罩范围内
+    pub fn in_aura(&self, nest_position: &Position, position: &Position) -> bool {
+        let distance = (nest_position.x - position.x).abs() + (nest_position.y - position.y).abs();
+        distance as u32 <= self.aura_radius
+    }
+
+    /// 生成讨伐巢穴的多人战斗任务
+    pub fn generate_tasks(&self, task_id_start: usize) -> Vec<Task> {
+        self.task_templates
+            .iter()
+            .filter(|t| t.task_type == "Combat")
+            .enumerate()
+            .map(|(i, template)| {
+                let mut task = Task::new(
+                    task_id_start + i,
+                    template.name_template.replace("{name}", &self.name),
+                    TaskType::Combat(CombatTask {
+                        enemy_id: None,
+                        enemy_name: self.name.clone(),
+                        enemy_level: self.level,
+                        difficulty: template.difficulty.unwrap_or(self.level),
+                    }),
+                    template.progress_reward,
+                    template.resource_reward,
+                );
+                task.reputation_reward = template.reputation_reward;
+                task.dao_heart_impact = template.dao_heart_impact;
+                task.max_participants = NEST_RAID_MAX_PARTICIPANTS;
+                task.attach_chain(template, &self.name);
+                task
+            })
+            .collect()
+    }
+}
+
+/// 草药品质（按品质由低到高排列）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum HerbQuality {
     Common,     // 普通
     Uncommon,   // 良品
@@ -616,6 +941,29 @@ impl HerbQuality {
         }
     }
 
+    /// 从字符串解析品质（支持英文标识和中文名称）
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "Common" | "普通" => Some(HerbQuality::Common),
+            "Uncommon" | "良品" => Some(HerbQuality::Uncommon),
+            "Rare" | "稀有" => Some(HerbQuality::Rare),
+            "Epic" | "珍品" => Some(HerbQuality::Epic),
+            "Legendary" | "仙品" => Some(HerbQuality::Legendary),
+            _ => None,
+        }
+    }
+
+    /// 每株草药的收购价（宗门卖出价）
+    pub fn base_price(&self) -> u32 {
+        match self {
+            HerbQuality::Common => 5,
+            HerbQuality::Uncommon => 15,
+            HerbQuality::Rare => 50,
+            HerbQuality::Epic => 150,
+            HerbQuality::Legendary => 500,
+        }
+    }
+
     /// 获取品质名称
     pub fn name(&self) -> &'static str {
         match self {
@@ -629,7 +977,7 @@ impl HerbQuality {
 }
 
 /// 草药
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Herb {
     pub id: usize,              // 唯一标识符
     pub name: String,           // 草药名称
@@ -642,6 +990,11 @@ pub struct Herb {
 // 全局草药ID计数器
 static NEXT_HERB_ID: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
 
+/// 将草药ID计数器推进到至少 `next_id`
+pub fn advance_herb_id_counter(next_id: usize) {
+    NEXT_HERB_ID.fetch_max(next_id, std::sync::atomic::Ordering::SeqCst);
+}
+
 /// 草药名称池
 const HERB_NAMES: &[&str] = &[
     "灵芝", "人参", "何首乌", "雪莲", "紫芝",
@@ -651,10 +1004,7 @@ const HERB_NAMES: &[&str] = &[
 
 impl Herb {
     /// 创建随机草药
-    pub fn new_random() -> Self {
-        use rand::Rng;
-        let mut rng = rand::thread_rng();
-
+    pub fn new_random<R: rand::Rng>(rng: &mut R) -> Self {
         let id = NEXT_HERB_ID.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
         let name = HERB_NAMES[rng.gen_range(0..HERB_NAMES.len())].to_string();
 
@@ -705,14 +1055,305 @@ impl Herb {
     }
 }
 
-/// 宗门被袭击的状态
+/// 机缘点（临时出现，弟子踩中触发奇遇后消失）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Opportunity {
+    pub id: usize,
+    pub name: String,
+    pub turns_left: u32, // 剩余存在回合数
+}
+
+/// 机缘点名称池
+const OPPORTUNITY_NAMES: &[&str] = &["古修洞府", "灵光乍现", "残碑遗刻", "仙人遗蜕", "异宝出世"];
+
+impl Opportunity {
+    /// 创建随机机缘点
+    pub fn new_random<R: rand::Rng>(id: usize, duration: u32, rng: &mut R) -> Self {
+        let name = OPPORTUNITY_NAMES[rng.gen_range(0..OPPORTUNITY_NAMES.len())].to_string();
+        Self { id, name, turns_left: duration }
+    }
+}
+
+/// 宗门连续无人守卫多少回合后被攻破
+pub const SECT_INVASION_GRACE_TURNS: u32 = 6;
+/// 达到该等级的妖魔可能锁定宗门为进攻目标
+pub const SECT_SIEGE_LEVEL: u32 = 30;
+/// 成魔或高等级妖魔每回合锁定宗门的概率
+const SECT_SIEGE_LOCK_CHANCE: f64 = 0.2;
+/// 守卫宗门任务的地点ID
+pub const SECT_LOCATION_ID: &str = "sect";
+/// 村庄每被一只妖魔劫掠一回合损失的繁荣度
+pub const VILLAGE_RAID_PROSPERITY_LOSS: u32 = 5;
+/// 村庄每被一只妖魔劫掠一回合损失的人口比例
+const VILLAGE_RAID_POPULATION_LOSS_RATE: f32 = 0.1;
+/// 守卫成功后村庄每回合恢复的繁荣度
+pub const VILLAGE_RECOVERY_PER_TURN: u32 = 2;
+/// 被入侵地点连续无人守卫多少回合后失守
+pub const LOCATION_FALL_TURNS: u32 = 5;
+/// 失守的势力倒向妖魔后的关系值（低于敌对阈值）
+const FALLEN_FACTION_RELATIONSHIP: i32 = -50;
+/// 每失守一处地点宗门损失的声望
+pub const LOCATION_FALL_REPUTATION_PENALTY: i32 = 20;
+
+/// 无人守卫而失守的地点
+#[derive(Debug, Clone)]
+pub struct FallenLocation {
+    pub location_id: String,
+    pub name: String,
+    pub monster_name: String,
+}
+
+impl FallenLocation {
+    /// 失守描述
+    pub fn describe(&self) -> String {
+        if self.location_id.starts_with("village_") {
+            format!("{} 无人守卫，被{}占据，村民流离失所", self.name, self.monster_name)
+        } else if self.location_id.starts_with("realm_") {
+            format!("{} 无人守卫，被{}封锁", self.name, self.monster_name)
+        } else {
+            format!("{} 无人守卫，屈服于{}，转为敌对", self.name, self.monster_name)
+        }
+    }
+}
+
+/// 开局时地图上的敌对宗门数量
+pub const RIVAL_SECT_COUNT: usize = 1;
+/// 敌对宗门每回合的基础收入
+pub const RIVAL_BASE_INCOME: u32 = 30;
+/// 敌对宗门每占据一处秘境的额外收入
+pub const RIVAL_REALM_INCOME: u32 = 20;
+/// 敌对宗门招募一名弟子的花费
+pub const RIVAL_RECRUIT_COST: u32 = 50;
+/// 敌对宗门每占据一处秘境所需的弟子数
+pub const RIVAL_DISCIPLES_PER_REALM: u32 = 5;
+/// 宗门战获胜后敌对宗门折损的弟子数
+pub const RIVAL_WAR_DISCIPLE_LOSS: u32 = 4;
+/// 敌对宗门名称池
+const RIVAL_SECT_NAMES: [&str; 4] = ["血煞门", "天魔教", "万毒宗", "合欢宗"];
+
+/// AI敌对宗门（简化的资源/弟子模型）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RivalSect {
+    pub name: String,
+    pub position: Position,
+    pub disciples: u32,
+    pub resources: u32,
+    pub occupied_realms: Vec<String>, // 占据的秘境（location_id）
+}
+
+impl RivalSect {
+    pub fn new(name: String, position: Position) -> Self {
+        Self {
+            name,
+            position,
+            disciples: 3,
+            resources: 0,
+            occupied_realms: Vec::new(),
+        }
+    }
+
+    pub fn location_id(&self) -> String {
+        format!("rival_{}", self.name)
+    }
+
+    /// 实力（宗门战中的敌人等级）：弟子越多、占据秘境越多越强
+    pub fn power(&self) -> u32 {
+        self.disciples + self.occupied_realms.len() as u32 * 2
+    }
+
+    /// 每回合收入
+    pub fn income(&self) -> u32 {
+        RIVAL_BASE_INCOME + RIVAL_REALM_INCOME * self.occupied_realms.len() as u32
+    }
+
+    /// 对该宗门发起的宗门战任务
+    pub fn war_task(&self, task_id: usize) -> Task {
+        let power = self.power();
+        let mut task = Task::new(
+            task_id,
+            format!("宗门战：{}", self.name),
+            TaskType::Combat(CombatTask {
+                enemy_id: None,
+                enemy_name: self.name.clone(),
+                enemy_level: power,
+                difficulty: power,
+            }),
+            power * 10,
+            power * 30,
+        );
+        task.reputation_reward = power as i32;
+        task.location_id = Some(self.location_id());
+        task.position = Some(self.position);
+        task
+    }
+}
+
+/// 敌对宗门占据秘境
+#[derive(Debug, Clone)]
+pub struct RivalOccupation {
+    pub rival_name: String,
+    pub location_id: String,
+    pub realm_name: String,
+}
+
+/// 宗门战结果
+#[derive(Debug, Clone)]
+pub struct RivalWarOutcome {
+    pub rival_name: String,
+    pub released_realm: Option<String>, // 夺回的秘境（location_id）
+    pub destroyed: bool,                // 敌对宗门是否覆灭
+}
+
+/// 巡逻弟子的警戒半径（曼哈顿距离）
+pub const PATROL_RADIUS: i32 = 2;
+
+/// 巡逻区域：以巡逻弟子所在位置为中心
+#[derive(Debug, Clone)]
+pub struct PatrolZone {
+    pub disciple_id: usize,
+    pub center: Position,
+    pub radius: i32,
+}
+
+impl PatrolZone {
+    pub fn new(disciple_id: usize, center: Position) -> Self {
+        Self { disciple_id, center, radius: PATROL_RADIUS }
+    }
+
+    fn distance(&self, position: Position) -> i32 {
+        (position.x - self.center.x).abs() + (position.y - self.center.y).abs()
+    }
+
+    /// 位置是否在警戒范围内
+    pub fn covers(&self, position: Position) -> bool {
+        self.distance(position) <= self.radius
+    }
+
+    /// 是否拦截从from到to的移动（进入或深入警戒范围；撤离则放行）
+    pub fn intercepts(&self, from: Position, to: Position) -> bool {
+        self.covers(to) && self.distance(to) <= self.distance(from)
+    }
+}
+
+/// 高阶天劫震慑妖魔的范围（曼哈顿距离）
+pub const TRIBULATION_SHOCK_RADIUS: i32 = 4;
+
+/// 渡劫失败时天劫余波吸引妖魔靠近的步数
+const TRIBULATION_ATTRACT_STEPS: i32 = 2;
+
+/// 巡逻弟子拦下的妖魔
 #[derive(Debug, Clone)]
+pub struct PatrolInterception {
+    pub disciple_id: usize,
+    pub monster_name: String,
+}
+
+/// 地图更新结果
+#[derive(Debug, Clone, Default)]
+pub struct MapUpdate {
+    pub destroyed_villages: Vec<String>,          // 被妖魔摧毁的村庄
+    pub rival_occupations: Vec<RivalOccupation>, // 敌对宗门新占据的秘境
+    pub patrol_interceptions: Vec<PatrolInterception>, // 被巡逻弟子拦下的妖魔
+}
+
+/// 宗门被袭击的状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SectInvasion {
     pub monster_id: usize,      // 袭击宗门的怪物ID
     pub monster_name: String,   // 怪物名称
-    pub turns_remaining: u32,   // 剩余回合数（6回合内未消灭则游戏失败）
+    pub turns_remaining: u32,   // 剩余回合数（连续无人守卫归零则游戏失败）
+}
+
+/// 配置重载对地图的影响
+#[derive(Debug, Default)]
+pub struct MapReloadReport {
+    pub added: Vec<String>,                // 新增的元素名称
+    pub removed: Vec<String>,              // 移除的元素名称
+    pub updated: usize,                    // 刷新了模板数据的元素数量
+    pub removed_location_ids: Vec<String>, // 被移除元素的地点ID（用于清理任务）
+    pub removed_monster_ids: Vec<usize>,   // 被移除的妖魔ID
+}
+
+/// 按模板位置与尺寸放置地图元素
+fn place_from_template(element: MapElement, position: &PositionConfig, size: &Option<SizeConfig>) -> PositionedElement {
+    PositionedElement {
+        element,
+        position: Position { x: position.x, y: position.y },
+        size: size.as_ref().map(|s| (s.width, s.height)),
+        positions: None,
+    }
+}
+
+/// 年景（每年开始时随机决定，影响村庄收入和妖魔出没）
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum Harvest {
+    Bountiful, // 丰年
+    #[default]
+    Normal,    // 平年
+    Disaster,  // 灾年
+}
+
+/// 丰年、灾年各自的出现概率
+const BOUNTIFUL_CHANCE: f64 = 0.25;
+const DISASTER_CHANCE: f64 = 0.25;
+
+impl Harvest {
+    /// 随机决定当年年景
+    pub fn roll<R: rand::Rng>(rng: &mut R) -> Self {
+        let roll: f64 = rng.gen();
+        if roll < BOUNTIFUL_CHANCE {
+            Harvest::Bountiful
+        } else if roll < BOUNTIFUL_CHANCE + DISASTER_CHANCE {
+            Harvest::Disaster
+        } else {
+            Harvest::Normal
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Harvest::Bountiful => "丰年",
+            Harvest::Normal => "平年",
+            Harvest::Disaster => "灾年",
+        }
+    }
+
+    /// 村庄收入倍率
+    pub fn income_multiplier(&self) -> f32 {
+        match self {
+            Harvest::Bountiful => 1.5,
+            Harvest::Normal => 1.0,
+            Harvest::Disaster => 0.5,
+        }
+    }
+
+    /// 妖魔生成概率倍率
+    pub fn spawn_chance_multiplier(&self) -> f64 {
+        match self {
+            Harvest::Bountiful => 0.5,
+            Harvest::Normal => 1.0,
+            Harvest::Disaster => 2.0,
+        }
+    }
+}
+
+/// 地图二进制导出的内容
+#[derive(Serialize, Deserialize)]
+struct MapExport {
+    width: i32,
+    height: i32,
+    sect_position: Position,
+    sect_invasion: Option<SectInvasion>,
+    next_monster_id: usize,
+    next_opportunity_id: usize,
+    harvest: Harvest,
+    elements: Vec<PositionedElement>,
+    rival_sects: Vec<RivalSect>,
 }
 
+/// 字符地图图例
+pub const ASCII_MAP_LEGEND: &str = "@弟子 !遭袭 M妖魔 N巢穴 H宗门 R敌对宗门 V村庄 F势力 S秘境 D险地 *草药 ?机缘 ^山 ~水 T林 ,平原 .空地";
+
 /// 游戏地图
 #[derive(Debug)]
 pub struct GameMap {
@@ -722,47 +1363,229 @@ pub struct GameMap {
     pub config: ConfigManager,
     pub sect_position: Position,           // 宗门位置
     pub sect_invasion: Option<SectInvasion>, // 宗门被袭击状态
+    pub next_monster_id: usize,            // 本局下一个妖魔ID
+    pub next_opportunity_id: usize,        // 本局下一个机缘点ID
+    pub harvest: Harvest,                  // 当年年景
+    pub rival_sects: Vec<RivalSect>,       // AI敌对宗门
 }
 
+/// 默认地图边长
+pub const DEFAULT_MAP_SIZE: i32 = 20;
+/// 地图边长下限（需容纳位于(10,10)的宗门）
+pub const MIN_MAP_SIZE: i32 = 12;
+/// 地图边长上限
+pub const MAX_MAP_SIZE: i32 = 100;
+
 impl GameMap {
     pub fn new() -> Self {
+        Self::with_size(DEFAULT_MAP_SIZE, DEFAULT_MAP_SIZE)
+    }
+
+    /// 创建指定尺寸的地图（尺寸由调用方按 MIN_MAP_SIZE..=MAX_MAP_SIZE 校验）
+    pub fn with_size(width: i32, height: i32) -> Self {
         Self {
             elements: Vec::new(),
-            width: 20,  // 地图宽度
-            height: 20, // 地图高度
+            width,
+            height,
             config: ConfigManager::create_default(),
             sect_position: Position { x: 10, y: 10 }, // 宗门位置在地图中心
             sect_invasion: None,
+            next_monster_id: 0,
+            next_opportunity_id: 0,
+            harvest: Harvest::Normal,
+            rival_sects: Vec::new(),
         }
     }
 
-    /// 初始化地图（从配置加载）
-    pub fn initialize(&mut self) {
-        // 加载配置
-        match ConfigManager::load() {
-            Ok(config) => {
-                println!("✓ 成功加载配置文件");
-                self.config = config;
-            }
-            Err(e) => {
-                println!("⚠ 加载配置失败: {}, 使用默认配置", e);
-                self.config = ConfigManager::create_default();
+    /// 开始新的一年：随机决定年景
+    pub fn roll_harvest<R: rand::Rng>(&mut self, rng: &mut R) -> Harvest {
+        self.harvest = Harvest::roll(rng);
+        self.harvest
+    }
+
+    /// 按类型和包围盒筛选地图元素
+    pub fn query_elements(&self, element_type: Option<&str>, bounds: &MapBounds) -> Vec<&PositionedElement> {
+        self.elements
+            .iter()
+            .filter(|p| element_type.map_or(true, |t| p.element.type_name() == t))
+            .filter(|p| p.intersects(bounds))
+            .collect()
+    }
+
+    /// 分配一个新的妖魔ID（同一局内唯一且单调递增）
+    pub fn allocate_monster_id(&mut self) -> usize {
+        let id = self.next_monster_id;
+        self.next_monster_id += 1;
+        id
+    }
+
+    /// 分配一个新的机缘点ID（同一局内唯一且单调递增）
+    pub fn allocate_opportunity_id(&mut self) -> usize {
+        let id = self.next_opportunity_id;
+        self.next_opportunity_id += 1;
+        id
+    }
+
+    /// 从存档数据恢复地图（不重新生成静态地图）
+    pub fn restore(
+        elements: Vec<PositionedElement>,
+        sect_invasion: Option<SectInvasion>,
+        next_monster_id: usize,
+        next_opportunity_id: usize,
+    ) -> Self {
+        let mut map = Self::new();
+        map.config = ConfigManager::load().unwrap_or_else(|_| ConfigManager::create_default());
+        map.elements = elements;
+        map.sect_invasion = sect_invasion;
+
+        // 妖魔与机缘点ID计数器不小于已有最大ID+1，草药ID计数器推进到已有最大ID之后
+        let mut next_monster_id = next_monster_id;
+        let mut next_herb_id = 0;
+        let mut next_opportunity_id = next_opportunity_id;
+        for positioned in &map.elements {
+            match &positioned.element {
+                MapElement::Monster(monster) => next_monster_id = next_monster_id.max(monster.id + 1),
+                MapElement::Herb(herb) => next_herb_id = next_herb_id.max(herb.id + 1),
+                MapElement::Opportunity(o) => next_opportunity_id = next_opportunity_id.max(o.id + 1),
+                _ => {}
             }
         }
+        map.next_monster_id = next_monster_id;
+        map.next_opportunity_id = next_opportunity_id;
+        advance_herb_id_counter(next_herb_id);
 
-        // 使用静态地图生成
-        self.generate_static_map();
-
-        /*
-        // ===== 动态地图生成逻辑（已注释）=====
+        map
+    }
 
-        // 生成基础地形元素
-        self.generate_terrain();
+    /// 导出为紧凑二进制格式（不含配置）
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        let export = MapExport {
+            width: self.width,
+            height: self.height,
+            sect_position: self.sect_position,
+            sect_invasion: self.sect_invasion.clone(),
+            next_monster_id: self.next_monster_id,
+            next_opportunity_id: self.next_opportunity_id,
+            harvest: self.harvest,
+            elements: self.elements.clone(),
+            rival_sects: self.rival_sects.clone(),
+        };
+        bincode::serialize(&export).map_err(|e| format!("地图导出失败: {}", e))
+    }
 
-        // 从配置加载村庄
-        for village_template in &self.config.map_elements.villages {
-            self.elements.push(PositionedElement {
-                element: MapElement::Village(Village::from_template(village_template)),
+    /// 从二进制数据导入地图（校验尺寸与坐标范围），任务相关的运行时状态全部重置
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let export: MapExport = bincode::deserialize(bytes).map_err(|e| format!("地图数据无效: {}", e))?;
+        let size_range = MIN_MAP_SIZE..=MAX_MAP_SIZE;
+        if !size_range.contains(&export.width) || !size_range.contains(&export.height) {
+            return Err(format!(
+                "地图尺寸需在{}到{}之间，当前为{}×{}",
+                MIN_MAP_SIZE, MAX_MAP_SIZE, export.width, export.height
+            ));
+        }
+        let in_bounds = |p: &Position| p.x >= 0 && p.x < export.width && p.y >= 0 && p.y < export.height;
+        let out_of_bounds = export
+            .elements
+            .iter()
+            .flat_map(|e| e.get_all_positions())
+            .chain(std::iter::once(export.sect_position))
+            .chain(export.rival_sects.iter().map(|r| r.position))
+            .find(|p| !in_bounds(p));
+        if let Some(p) = out_of_bounds {
+            return Err(format!("地图数据无效: 坐标({}, {})超出地图范围", p.x, p.y));
+        }
+        let mut map = Self::restore(export.elements, export.sect_invasion, export.next_monster_id, export.next_opportunity_id);
+        map.width = export.width;
+        map.height = export.height;
+        map.sect_position = export.sect_position;
+        map.harvest = export.harvest;
+        map.rival_sects = export.rival_sects;
+        for positioned in &mut map.elements {
+            if let MapElement::Monster(monster) = &mut positioned.element {
+                monster.clear_task();
+                monster.is_being_fought = false;
+                monster.has_active_defense_task = false;
+            }
+        }
+        Ok(map)
+    }
+
+    /// 渲染为字符网格（每行一个y坐标），同格多个元素时显示优先级最高者：
+    /// 弟子@ > 入侵标记! > 妖魔M > 巢穴N > 宗门H/敌对宗门R/村庄V/势力F/秘境S/险地D > 草药* > 地形
+    pub fn render_ascii(&self, disciples: &[Disciple]) -> String {
+        let (width, height) = (self.width.max(0) as usize, self.height.max(0) as usize);
+        let mut grid = vec![vec![(0u8, '.'); width]; height];
+        let mut place = |pos: &Position, priority: u8, symbol: char| {
+            if pos.x < 0 || pos.y < 0 || pos.x as usize >= width || pos.y as usize >= height {
+                return;
+            }
+            let cell = &mut grid[pos.y as usize][pos.x as usize];
+            if priority > cell.0 {
+                *cell = (priority, symbol);
+            }
+        };
+
+        let invaded: HashSet<String> = self
+            .elements
+            .iter()
+            .filter_map(|p| p.element.as_monster()?.invaded_location_id.clone())
+            .collect();
+        for positioned in &self.elements {
+            let (priority, symbol) = if invaded.contains(&positioned.element.get_location_id()) {
+                (6, '!')
+            } else {
+                positioned.element.ascii_symbol()
+            };
+            for pos in positioned.get_all_positions() {
+                place(&pos, priority, symbol);
+            }
+        }
+
+        for rival in &self.rival_sects {
+            place(&rival.position, 3, 'R');
+        }
+        let sect_symbol = if self.sect_invasion.is_some() { (6, '!') } else { (3, 'H') };
+        place(&self.sect_position, sect_symbol.0, sect_symbol.1);
+        for disciple in disciples.iter().filter(|d| d.is_alive()) {
+            place(&disciple.position, 7, '@');
+        }
+
+        grid.iter()
+            .map(|row| row.iter().map(|(_, symbol)| *symbol).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// 初始化地图（从配置加载）
+    pub fn initialize<R: rand::Rng>(&mut self, rng: &mut R) {
+        // 加载配置
+        match ConfigManager::load() {
+            Ok(config) => {
+                println!("✓ 成功加载配置文件");
+                self.config = config;
+            }
+            Err(e) => {
+                println!("⚠ 加载配置失败: {}, 使用默认配置", e);
+                self.config = ConfigManager::create_default();
+            }
+        }
+
+        // 使用静态地图生成
+        self.generate_static_map(rng);
+        for _ in 0..RIVAL_SECT_COUNT {
+            self.spawn_rival_sect(rng);
+        }
+
+        /*
+        // ===== 动态地图生成逻辑（已注释）=====
+
+        // 生成基础地形元素
+        self.generate_terrain(rng);
+
+        // 从配置加载村庄
+        for village_template in &self.config.map_elements.villages {
+            self.elements.push(PositionedElement {
+                element: MapElement::Village(Village::from_template(village_template)),
                 position: Position {
                     x: village_template.position.x,
                     y: village_template.position.y,
@@ -816,8 +1639,9 @@ impl GameMap {
         // 从配置加载初始妖魔
         for monster_template in &self.config.monsters.monster_templates {
             if let Some(pos) = &monster_template.position {
+                let monster_id = self.allocate_monster_id();
                 self.elements.push(PositionedElement {
-                    element: MapElement::Monster(Monster::from_template(monster_template)),
+                    element: MapElement::Monster(Monster::from_template(monster_id, monster_template)),
                     position: Position {
                         x: pos.x,
                         y: pos.y,
@@ -829,17 +1653,178 @@ impl GameMap {
         }
 
         // 生成初始草药（3-5个）
-        self.spawn_initial_herbs();
+        self.spawn_initial_herbs(rng);
 
         // ===== 动态地图生成逻辑结束 =====
         */
     }
 
+    /// 应用重新加载的配置：已有元素保持位置并刷新模板数据，新增模板加入地图，
+    /// 模板被删除的元素移出地图（正在交战的妖魔待战斗结束后再处理）
+    pub fn apply_config(&mut self, config: ConfigManager) -> MapReloadReport {
+        let mut report = MapReloadReport::default();
+        let old = std::mem::replace(&mut self.config, config);
+        let new_elements = self.config.map_elements.clone();
+        let new_monsters = self.config.monsters.clone();
+
+        // 仅移除旧配置中存在、新配置中已删除的模板对应元素（静态地形等不受影响）
+        let removed_names = |old_names: Vec<&String>, new_names: Vec<&String>| -> Vec<String> {
+            old_names.into_iter()
+                .filter(|n| !new_names.contains(n))
+                .cloned()
+                .collect()
+        };
+        let removed_villages = removed_names(
+            old.map_elements.villages.iter().map(|t| &t.name).collect(),
+            new_elements.villages.iter().map(|t| &t.name).collect(),
+        );
+        let removed_factions = removed_names(
+            old.map_elements.factions.iter().map(|t| &t.name).collect(),
+            new_elements.factions.iter().map(|t| &t.name).collect(),
+        );
+        let removed_dangers = removed_names(
+            old.map_elements.dangerous_locations.iter().map(|t| &t.name).collect(),
+            new_elements.dangerous_locations.iter().map(|t| &t.name).collect(),
+        );
+        let removed_realms = removed_names(
+            old.map_elements.secret_realms.iter().map(|t| &t.name).collect(),
+            new_elements.secret_realms.iter().map(|t| &t.name).collect(),
+        );
+        let removed_monsters = removed_names(
+            old.monsters.monster_templates.iter().map(|t| &t.name).collect(),
+            new_monsters.monster_templates.iter().map(|t| &t.name).collect(),
+        );
+        let removed_nests = removed_names(
+            old.monsters.nests.iter().map(|t| &t.name).collect(),
+            new_monsters.nests.iter().map(|t| &t.name).collect(),
+        );
+
+        let mut doomed_monsters = Vec::new();
+        self.elements.retain(|p| {
+            let removed = match &p.element {
+                MapElement::Village(v) => removed_villages.contains(&v.name),
+                MapElement::Faction(f) => removed_factions.contains(&f.name),
+                MapElement::DangerousLocation(d) => removed_dangers.contains(&d.name),
+                MapElement::SecretRealm(r) => removed_realms.contains(&r.name),
+                MapElement::MonsterNest(n) => removed_nests.contains(&n.name),
+                MapElement::Monster(m) => {
+                    if removed_monsters.contains(&m.name) && !m.is_being_fought {
+                        doomed_monsters.push((m.id, m.name.clone()));
+                    }
+                    false
+                }
+                _ => false,
+            };
+            if removed {
+                report.removed.push(p.element.get_name());
+                report.removed_location_ids.push(p.element.get_location_id());
+            }
+            !removed
+        });
+
+        for (monster_id, name) in doomed_monsters {
+            report.removed.push(name);
+            report.removed_location_ids.push(format!("monster_{}", monster_id));
+            report.removed_monster_ids.push(monster_id);
+            self.remove_monster_by_id(monster_id);
+        }
+
+        // 刷新保留元素的模板数据（位置与运行时状态不变）
+        for p in &mut self.elements {
+            let updated = match &mut p.element {
+                MapElement::Village(v) => new_elements.villages.iter().find(|t| t.name == v.name)
+                    .map(|t| v.task_templates = t.task_templates.clone()),
+                MapElement::Faction(f) => new_elements.factions.iter().find(|t| t.name == f.name)
+                    .map(|t| {
+                        f.power_level = t.power_level;
+                        f.friendly_task_templates = t.friendly_task_templates.clone();
+                        f.hostile_task_templates = t.hostile_task_templates.clone();
+                    }),
+                MapElement::DangerousLocation(d) => new_elements.dangerous_locations.iter().find(|t| t.name == d.name)
+                    .map(|t| {
+                        d.danger_level = t.danger_level;
+                        d.task_templates = t.task_templates.clone();
+                    }),
+                MapElement::SecretRealm(r) => new_elements.secret_realms.iter().find(|t| t.name == r.name)
+                    .map(|t| *r = SecretRealm::from_template(t)),
+                MapElement::Monster(m) => new_monsters.monster_templates.iter().find(|t| t.name == m.name)
+                    .map(|t| {
+                        m.growth_rate = t.growth_rate;
+                        m.task_templates = t.task_templates.clone();
+                    }),
+                MapElement::MonsterNest(n) => new_monsters.nests.iter().find(|t| t.name == n.name)
+                    .map(|t| {
+                        let turns_until_spawn = n.turns_until_spawn.min(t.spawn_interval);
+                        *n = MonsterNest { turns_until_spawn, ..MonsterNest::from_template(t) };
+                    }),
+                _ => None,
+            };
+            if updated.is_some() {
+                report.updated += 1;
+            }
+        }
+
+        // 新增模板放置到模板指定的位置
+        for t in &new_elements.villages {
+            if !old.map_elements.villages.iter().any(|o| o.name == t.name)
+                && self.place_element(place_from_template(
+                    MapElement::Village(Village::from_template(t)), &t.position, &t.size))
+            {
+                report.added.push(t.name.clone());
+            }
+        }
+        for t in &new_elements.factions {
+            if !old.map_elements.factions.iter().any(|o| o.name == t.name)
+                && self.place_element(place_from_template(
+                    MapElement::Faction(Faction::from_template(t)), &t.position, &t.size))
+            {
+                report.added.push(t.name.clone());
+            }
+        }
+        for t in &new_elements.dangerous_locations {
+            if !old.map_elements.dangerous_locations.iter().any(|o| o.name == t.name)
+                && self.place_element(place_from_template(
+                    MapElement::DangerousLocation(DangerousLocation::from_template(t)), &t.position, &t.size))
+            {
+                report.added.push(t.name.clone());
+            }
+        }
+        for t in &new_elements.secret_realms {
+            if !old.map_elements.secret_realms.iter().any(|o| o.name == t.name)
+                && self.place_element(place_from_template(
+                    MapElement::SecretRealm(SecretRealm::from_template(t)), &t.position, &t.size))
+            {
+                report.added.push(t.name.clone());
+            }
+        }
+        for t in &new_monsters.nests {
+            if !old.monsters.nests.iter().any(|o| o.name == t.name)
+                && self.place_element(place_from_template(
+                    MapElement::MonsterNest(MonsterNest::from_template(t)), &t.position, &None))
+            {
+                report.added.push(t.name.clone());
+            }
+        }
+        for t in &new_monsters.monster_templates {
+            if old.monsters.monster_templates.iter().any(|o| o.name == t.name) {
+                continue;
+            }
+            if let Some(ref pos) = t.position {
+                let monster_id = self.allocate_monster_id();
+                self.elements.push(place_from_template(
+                    MapElement::Monster(Monster::from_template(monster_id, t)), pos, &None));
+                report.added.push(t.name.clone());
+            }
+        }
+
+        report
+    }
+
     /// 生成静态地图（基于预定义数据）
-    fn generate_static_map(&mut self) {
+    fn generate_static_map<R: rand::Rng>(&mut self, rng: &mut R) {
         // 添加势力（Faction - 天剑门）
         if let Some(faction_template) = self.config.map_elements.factions.first() {
-            self.elements.push(PositionedElement {
+            self.place_element(PositionedElement {
                 element: MapElement::Faction(Faction::from_template(faction_template)),
                 position: Position { x: 9, y: 9 },  // core_position
                 size: None,
@@ -852,13 +1837,22 @@ impl GameMap {
             });
         }
 
+        // 添加妖魔巢穴
+        for nest_template in self.config.monsters.nests.clone() {
+            self.place_element(place_from_template(
+                MapElement::MonsterNest(MonsterNest::from_template(&nest_template)),
+                &nest_template.position,
+                &None,
+            ));
+        }
+
         // === 河流 1 - 每个格子单独一个元素 ===
         let river_1_positions = vec![
             (14, 5), (13, 5), (12, 5), (12, 4), (12, 3),
             (11, 3), (10, 3), (9, 3), (9, 4), (9, 5), (9, 7),
         ];
         for (x, y) in river_1_positions {
-            self.elements.push(PositionedElement {
+            self.place_element(PositionedElement {
                 element: MapElement::Terrain(Terrain {
                     terrain_type: TerrainType::Water,
                     name: "河流".to_string(),
@@ -876,7 +1870,7 @@ impl GameMap {
             (7, 10), (8, 10), (5, 10), (4, 10),
         ];
         for (x, y) in river_2_positions {
-            self.elements.push(PositionedElement {
+            self.place_element(PositionedElement {
                 element: MapElement::Terrain(Terrain {
                     terrain_type: TerrainType::Water,
                     name: "河流".to_string(),
@@ -900,7 +1894,7 @@ impl GameMap {
             vec![Position { x: 14, y: 7 }],
         ];
         for positions in mountain1_positions {
-            self.elements.push(PositionedElement {
+            self.place_element(PositionedElement {
                 element: MapElement::Terrain(Terrain {
                     terrain_type: TerrainType::Mountain,
                     name: "山峰".to_string(),
@@ -923,7 +1917,7 @@ impl GameMap {
             vec![Position { x: 15, y: 7 }],
         ];
         for positions in mountain2_positions {
-            self.elements.push(PositionedElement {
+            self.place_element(PositionedElement {
                 element: MapElement::Terrain(Terrain {
                     terrain_type: TerrainType::Mountain,
                     name: "山峰".to_string(),
@@ -947,7 +1941,7 @@ impl GameMap {
             vec![Position { x: 16, y: 8 }, Position { x: 16, y: 9 }],
         ];
         for positions in mountain3_positions {
-            self.elements.push(PositionedElement {
+            self.place_element(PositionedElement {
                 element: MapElement::Terrain(Terrain {
                     terrain_type: TerrainType::Mountain,
                     name: "山峰".to_string(),
@@ -969,7 +1963,7 @@ impl GameMap {
             vec![Position { x: 11, y: 14 }, Position { x: 12, y: 14 }],
         ];
         for positions in mountain4_positions {
-            self.elements.push(PositionedElement {
+            self.place_element(PositionedElement {
                 element: MapElement::Terrain(Terrain {
                     terrain_type: TerrainType::Mountain,
                     name: "山峰".to_string(),
@@ -982,41 +1976,174 @@ impl GameMap {
         }
 
         // 生成初始草药（3-5个）
-        self.spawn_initial_herbs();
+        self.spawn_initial_herbs(rng);
     }
 
-    /// 生成初始草药
-    fn spawn_initial_herbs(&mut self) {
-        use rand::Rng;
-        let mut rng = rand::thread_rng();
+    /// 占格登记：元素占据的所有格子都在地图内且空闲时放入地图，越界或与已有元素重叠则打印警告并跳过
+    pub fn place_element(&mut self, placed: PositionedElement) -> bool {
+        // 配置坐标超出地图范围的元素被裁剪掉
+        if let Some(outside) = placed
+            .get_all_positions()
+            .into_iter()
+            .find(|p| p.x < 0 || p.x >= self.width || p.y < 0 || p.y >= self.height)
+        {
+            println!(
+                "⚠ {} 的坐标({}, {})超出{}×{}地图，跳过放置",
+                placed.element.get_name(),
+                outside.x,
+                outside.y,
+                self.width,
+                self.height
+            );
+            return false;
+        }
+        if placed.occupies_tiles() {
+            let cells = placed.get_all_positions();
+            let conflict = self
+                .elements
+                .iter()
+                .filter(|e| e.occupies_tiles())
+                .find(|e| cells.iter().any(|p| e.contains_position(p.x, p.y)));
+            if let Some(existing) = conflict {
+                println!(
+                    "⚠ {} 与 {} 占据的格子重叠，跳过放置",
+                    placed.element.get_name(),
+                    existing.element.get_name()
+                );
+                return false;
+            }
+        }
+        self.elements.push(placed);
+        true
+    }
 
+    /// 生成初始草药
+    fn spawn_initial_herbs<R: rand::Rng>(&mut self, rng: &mut R) {
         let herb_count = rng.gen_range(3..=5);
         for _ in 0..herb_count {
             let x = rng.gen_range(0..self.width);
             let y = rng.gen_range(0..self.height);
 
-            // 检查该位置是否已经有草药
-            let has_herb = self.elements.iter().any(|e| {
-                matches!(e.element, MapElement::Herb(_)) &&
-                e.position.x == x && e.position.y == y
+            // 检查该位置是否已有草药或被其他元素占据
+            let occupied = self.elements.iter().any(|e| e.contains_position(x, y));
+
+            if !occupied {
+                self.elements.push(PositionedElement {
+                    element: MapElement::Herb(Herb::new_random(rng)),
+                    position: Position { x, y },
+                    size: None,
+                    positions: None,
+                });
+            }
+        }
+    }
+
+    /// 采集指定位置的成熟草药
+    /// 返回 Ok(None) 表示该位置没有草药，Err 携带未成熟的草药（不会被采走）
+    pub fn collect_herb_at(&mut self, x: i32, y: i32) -> Result<Option<Herb>, Herb> {
+        let index = self.elements.iter().position(|e| {
+            matches!(e.element, MapElement::Herb(_)) && e.contains_position(x, y)
+        });
+
+        let index = match index {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+
+        if let MapElement::Herb(herb) = &self.elements[index].element {
+            if !herb.is_mature() {
+                return Err(herb.clone());
+            }
+        }
+
+        let herb = match self.elements.remove(index).element {
+            MapElement::Herb(herb) => herb,
+            _ => unreachable!(),
+        };
+
+        // 采集后在随机空地重新播种，避免草药被采光
+        self.replant_herb(&mut rand::thread_rng());
+
+        Ok(Some(herb))
+    }
+
+    /// 取走指定位置的机缘点（没有则返回 None）
+    pub fn take_opportunity_at(&mut self, x: i32, y: i32) -> Option<Opportunity> {
+        let index = self.elements.iter().position(|e| {
+            matches!(e.element, MapElement::Opportunity(_)) && e.contains_position(x, y)
+        })?;
+        match self.elements.remove(index).element {
+            MapElement::Opportunity(opportunity) => Some(opportunity),
+            _ => unreachable!(),
+        }
+    }
+
+    /// 按配置的概率表抽取一次奇遇（概率表为空时给灵石）
+    pub fn roll_opportunity_reward<R: rand::Rng>(&self, rng: &mut R) -> OpportunityRewardKind {
+        let rewards = &self.config.map_elements.opportunities.rewards;
+        let total: u32 = rewards.iter().map(|r| r.weight).sum();
+        if total == 0 {
+            return OpportunityRewardKind::SpiritStones;
+        }
+        let mut roll = rng.gen_range(0..total);
+        for entry in rewards {
+            if roll < entry.weight {
+                return entry.reward;
+            }
+            roll -= entry.weight;
+        }
+        OpportunityRewardKind::SpiritStones
+    }
+
+    /// 机缘点计时，过期的消散，并按配置概率在空地生成新的机缘点
+    fn update_opportunities<R: rand::Rng>(&mut self, rng: &mut R) {
+        for positioned in &mut self.elements {
+            if let MapElement::Opportunity(opportunity) = &mut positioned.element {
+                opportunity.turns_left = opportunity.turns_left.saturating_sub(1);
+            }
+        }
+        self.elements.retain(|e| !matches!(&e.element, MapElement::Opportunity(o) if o.turns_left == 0));
+
+        let config = &self.config.map_elements.opportunities;
+        let (spawn_chance, duration) = (config.spawn_chance.clamp(0.0, 1.0), config.duration);
+        if !rng.gen_bool(spawn_chance) {
+            return;
+        }
+        let x = rng.gen_range(0..self.width);
+        let y = rng.gen_range(0..self.height);
+        let occupied = self.elements.iter().any(|e| e.contains_position(x, y));
+        if !occupied && (x, y) != (self.sect_position.x, self.sect_position.y) {
+            let id = self.allocate_opportunity_id();
+            self.elements.push(PositionedElement {
+                element: MapElement::Opportunity(Opportunity::new_random(id, duration, rng)),
+                position: Position { x, y },
+                size: None,
+                positions: None,
             });
+        }
+    }
 
-            if !has_herb {
+    /// 在随机空地播种一株新草药（多次尝试仍找不到空地则放弃）
+    fn replant_herb<R: rand::Rng>(&mut self, rng: &mut R) {
+        for _ in 0..20 {
+            let x = rng.gen_range(0..self.width);
+            let y = rng.gen_range(0..self.height);
+
+            let occupied = self.elements.iter().any(|e| e.contains_position(x, y));
+            if !occupied && (x, y) != (self.sect_position.x, self.sect_position.y) {
                 self.elements.push(PositionedElement {
-                    element: MapElement::Herb(Herb::new_random()),
+                    element: MapElement::Herb(Herb::new_random(rng)),
                     position: Position { x, y },
                     size: None,
                     positions: None,
                 });
+                return;
             }
         }
     }
 
     /// 生成基础地形元素
-    fn generate_terrain(&mut self) {
-        use rand::Rng;
-        let mut rng = rand::thread_rng();
-
+    fn generate_terrain<R: rand::Rng>(&mut self, rng: &mut R) {
         // 随机生成山、水、林等地形
         let terrain_types = [
             (TerrainType::Mountain, "太行山"),
@@ -1045,22 +2172,44 @@ impl GameMap {
     }
 
     /// 获取所有可用任务
-    pub fn get_available_tasks(&mut self) -> Vec<Task> {
+    /// 风险超过 max_risk 的地图任务暂不开放（守卫任务不受限制）
+    pub fn get_available_tasks<R: rand::Rng>(&mut self, max_risk: RiskLevel, rng: &mut R) -> Vec<Task> {
         let mut tasks = Vec::new();
         let mut task_id = 0;
 
-        for positioned in &mut self.elements {
-            let mut element_tasks = positioned.element.generate_tasks(task_id);
+        // 巢穴附近的妖魔获得等级加成
+        let nest_bonuses: Vec<u32> = self
+            .elements
+            .iter()
+            .map(|p| match p.element {
+                MapElement::Monster(_) => self.nest_level_bonus(&p.position),
+                _ => 0,
+            })
+            .collect();
 
-            // 为所有从此位置生成的任务设置位置
+        // 被敌对宗门占据的秘境不再为玩家产生任务
+        let rival_occupied: HashSet<String> = self
+            .rival_sects
+            .iter()
+            .flat_map(|r| r.occupied_realms.iter().cloned())
+            .collect();
+
+        for (positioned, nest_bonus) in self.elements.iter_mut().zip(nest_bonuses) {
+            if rival_occupied.contains(&positioned.element.get_location_id()) {
+                continue;
+            }
+            let mut element_tasks = positioned.element.generate_tasks(task_id, rng);
             for task in &mut element_tasks {
-                task.position = Some(positioned.position);
-                // 为大型建筑设置所有有效位置
-                let all_positions = positioned.get_all_positions();
-                if all_positions.len() > 1 {
-                    task.valid_positions = Some(all_positions);
+                if let TaskType::Combat(combat) = &mut task.task_type {
+                    combat.enemy_level += nest_bonus;
                 }
             }
+            element_tasks.retain(|t| t.risk_level() <= max_risk);
+
+            // 为所有从此位置生成的任务设置位置
+            for task in &mut element_tasks {
+                positioned.locate_task(task);
+            }
 
             // 如果是妖魔任务，需要记录任务ID
             if let MapElement::Monster(monster) = &mut positioned.element {
@@ -1078,26 +2227,66 @@ impl GameMap {
 
         // 添加守卫任务（妖魔入侵时）
         let defense_tasks = self.generate_defense_tasks(task_id);
+        task_id += defense_tasks.len();
         tasks.extend(defense_tasks);
 
+        // 对敌对宗门发起宗门战
+        for rival in &self.rival_sects {
+            let task = rival.war_task(task_id);
+            if task.risk_level() <= max_risk {
+                tasks.push(task);
+                task_id += 1;
+            }
+        }
+
         tasks
     }
 
-    /// 计算总资源收入
+    /// 计算总资源收入（村庄收入受年景影响）
     pub fn calculate_income(&self, reputation: i32) -> u32 {
+        self.income_breakdown(reputation).iter().map(|(_, income)| income).sum()
+    }
+
+    /// 各产出元素（村庄、势力）的收入明细，被妖魔入侵的地点不产出收入
+    pub fn income_breakdown(&self, reputation: i32) -> Vec<(String, u32)> {
+        let invaded: HashSet<String> = self
+            .elements
+            .iter()
+            .filter_map(|p| match &p.element {
+                MapElement::Monster(m) => m.invaded_location_id.clone(),
+                _ => None,
+            })
+            .collect();
         self.elements
             .iter()
-            .map(|positioned| positioned.element.get_resource_income(reputation))
-            .sum()
+            .filter(|p| matches!(p.element, MapElement::Village(_) | MapElement::Faction(_)))
+            .map(|positioned| {
+                let element = &positioned.element;
+                let income = if invaded.contains(&element.get_location_id()) {
+                    0
+                } else if let MapElement::Village(v) = element {
+                    (v.get_income(reputation) as f32 * self.harvest.income_multiplier()) as u32
+                } else {
+                    element.get_resource_income(reputation)
+                };
+                (element.get_name(), income)
+            })
+            .collect()
     }
 
-    /// 更新地图（新事件、怪物成长等）
-    pub fn update(&mut self) {
-        use rand::Rng;
-        let mut rng = rand::thread_rng();
+    /// 更新地图（新事件、怪物成长、敌对宗门行动等）
+    /// guarded 为玩家已派弟子驻守（有弟子执行任务）的地点ID，patrols 为巡逻弟子的警戒区域
+    pub fn update<R: rand::Rng>(
+        &mut self,
+        guarded: &HashSet<String>,
+        patrols: &[PatrolZone],
+        rng: &mut R,
+    ) -> MapUpdate {
+        // 妖魔行动：移动或修行，进入巡逻区域的妖魔被拦下
+        let patrol_interceptions = self.monster_actions(patrols, rng);
 
-        // 妖魔行动：移动或修行
-        self.monster_actions();
+        // 被入侵的村庄遭受劫掠，守卫成功的村庄逐步恢复
+        let destroyed_villages = self.raid_villages();
 
         // 怪物可能成长
         for positioned in &mut self.elements {
@@ -1108,8 +2297,10 @@ impl GameMap {
             }
         }
 
-        // 可能出现新的怪物（从配置的随机名称池中选择）
-        let spawn_chance = self.config.monsters.spawn_rules.spawn_chance;
+        // 可能出现新的怪物（从配置的随机名称池中选择，灾年妖魔横行）
+        let spawn_chance = (self.config.monsters.spawn_rules.spawn_chance
+            * self.harvest.spawn_chance_multiplier())
+            .min(1.0);
         if rng.gen_bool(spawn_chance) {
             let (min_level, max_level) = self.config.monsters.spawn_rules.level_range;
             let random_names = &self.config.monsters.spawn_rules.random_names;
@@ -1122,15 +2313,18 @@ impl GameMap {
                 let x = rng.gen_range(0..self.width);
                 let y = rng.gen_range(0..self.height);
 
-                // 使用默认的任务模板（从第一个妖魔模板复制，如果有的话）
-                let task_templates = if let Some(first_template) = self.config.monsters.monster_templates.first() {
-                    first_template.task_templates.clone()
+                // 使用默认的任务模板和掉落表（从第一个妖魔模板复制，如果有的话）
+                let (task_templates, drops) = if let Some(first_template) = self.config.monsters.monster_templates.first() {
+                    (first_template.task_templates.clone(), first_template.drops.clone())
                 } else {
-                    vec![]
+                    (vec![], vec![])
                 };
 
+                let monster_id = self.allocate_monster_id();
+                let mut monster = Monster::new(monster_id, name, level, task_templates);
+                monster.drops = drops;
                 self.elements.push(PositionedElement {
-                    element: MapElement::Monster(Monster::new(name, level, task_templates)),
+                    element: MapElement::Monster(monster),
                     position: Position { x, y },
                     size: None,
                     positions: None,
@@ -1138,6 +2332,9 @@ impl GameMap {
             }
         }
 
+        // 巢穴孵化妖魔
+        self.hatch_nest_broods(rng);
+
         // 草药生长
         for positioned in &mut self.elements {
             if let MapElement::Herb(herb) = &mut positioned.element {
@@ -1150,29 +2347,342 @@ impl GameMap {
             let x = rng.gen_range(0..self.width);
             let y = rng.gen_range(0..self.height);
 
-            // 检查该位置是否已经有草药
-            let has_herb = self.elements.iter().any(|e| {
-                matches!(e.element, MapElement::Herb(_)) &&
-                e.position.x == x && e.position.y == y
-            });
+            // 检查该位置是否已有草药或被其他元素占据
+            let occupied = self.elements.iter().any(|e| e.contains_position(x, y));
 
-            if !has_herb {
+            if !occupied {
                 self.elements.push(PositionedElement {
-                    element: MapElement::Herb(Herb::new_random()),
+                    element: MapElement::Herb(Herb::new_random(rng)),
                     position: Position { x, y },
                     size: None,
                     positions: None,
                 });
             }
         }
+
+        // 机缘点消散与出现
+        self.update_opportunities(rng);
+
+        // 敌对宗门行动
+        let rival_occupations = self.rival_sect_actions(guarded);
+
+        MapUpdate { destroyed_villages, rival_occupations, patrol_interceptions }
+    }
+
+    /// 在空地上建立一个敌对宗门，返回其名称（名称用尽或无空地时返回None）
+    pub fn spawn_rival_sect<R: rand::Rng>(&mut self, rng: &mut R) -> Option<String> {
+        let name = RIVAL_SECT_NAMES
+            .iter()
+            .find(|name| !self.rival_sects.iter().any(|r| r.name == **name))?;
+        for _ in 0..100 {
+            let position = Position { x: rng.gen_range(0..self.width), y: rng.gen_range(0..self.height) };
+            let taken = self.elements.iter().any(|e| e.contains_position(position.x, position.y))
+                || (position.x - self.sect_position.x).abs() + (position.y - self.sect_position.y).abs() < 5
+                || self.rival_sects.iter().any(|r| r.position.x == position.x && r.position.y == position.y);
+            if !taken {
+                self.rival_sects.push(RivalSect::new(name.to_string(), position));
+                return Some(name.to_string());
+            }
+        }
+        None
+    }
+
+    /// 敌对宗门的简单AI：积累资源、招募弟子，弟子足够时占据最近的无人驻守秘境
+    pub fn rival_sect_actions(&mut self, guarded: &HashSet<String>) -> Vec<RivalOccupation> {
+        let mut occupations = Vec::new();
+        for i in 0..self.rival_sects.len() {
+            let rival = &mut self.rival_sects[i];
+            rival.resources += rival.income();
+            if rival.resources >= RIVAL_RECRUIT_COST {
+                rival.resources -= RIVAL_RECRUIT_COST;
+                rival.disciples += 1;
+            }
+            if rival.disciples < RIVAL_DISCIPLES_PER_REALM * (rival.occupied_realms.len() as u32 + 1) {
+                continue;
+            }
+
+            let origin = self.rival_sects[i].position;
+            let target = self
+                .elements
+                .iter()
+                .filter_map(|p| match &p.element {
+                    MapElement::SecretRealm(realm) => Some((p, realm)),
+                    _ => None,
+                })
+                .filter(|(p, _)| {
+                    let id = p.element.get_location_id();
+                    !guarded.contains(&id) && !self.is_occupied_by_rival(&id)
+                })
+                .min_by_key(|(p, _)| (p.position.x - origin.x).abs() + (p.position.y - origin.y).abs())
+                .map(|(p, realm)| (p.element.get_location_id(), realm.name.clone()));
+            if let Some((location_id, realm_name)) = target {
+                let rival = &mut self.rival_sects[i];
+                rival.occupied_realms.push(location_id.clone());
+                occupations.push(RivalOccupation { rival_name: rival.name.clone(), location_id, realm_name });
+            }
+        }
+        occupations
+    }
+
+    /// 某地点是否被敌对宗门占据
+    pub fn is_occupied_by_rival(&self, location_id: &str) -> bool {
+        self.rival_sects.iter().any(|r| r.occupied_realms.iter().any(|id| id == location_id))
+    }
+
+    /// 结算宗门战：获胜后敌对宗门折损弟子并让出最近占据的秘境，弟子折损殆尽则覆灭
+    pub fn settle_rival_war(&mut self, task: &Task) -> Option<RivalWarOutcome> {
+        let location_id = task.location_id.as_deref()?;
+        let index = self.rival_sects.iter().position(|r| r.location_id() == location_id)?;
+        let rival = &mut self.rival_sects[index];
+        rival.disciples = rival.disciples.saturating_sub(RIVAL_WAR_DISCIPLE_LOSS);
+        let released_realm = rival.occupied_realms.pop();
+        let destroyed = rival.disciples == 0;
+        let rival_name = rival.name.clone();
+        if destroyed {
+            self.rival_sects.remove(index);
+        }
+        Some(RivalWarOutcome { rival_name, released_realm, destroyed })
+    }
+
+    /// 推进巢穴孵化倒计时，到期的巢穴在身旁空地孵化一只妖魔（孵化出的妖魔存活数不超过上限）
+    fn hatch_nest_broods<R: rand::Rng>(&mut self, rng: &mut R) {
+        let mut due = Vec::new();
+        for positioned in &mut self.elements {
+            if let MapElement::MonsterNest(nest) = &mut positioned.element {
+                if nest.tick() {
+                    due.push((nest.clone(), positioned.position));
+                }
+            }
+        }
+
+        let task_templates = self
+            .config
+            .monsters
+            .monster_templates
+            .first()
+            .map(|t| t.task_templates.clone())
+            .unwrap_or_default();
+        for (nest, nest_position) in due {
+            let brood = self
+                .elements
+                .iter()
+                .filter_map(|p| p.element.as_monster())
+                .filter(|m| m.nest_name.as_deref() == Some(nest.name.as_str()))
+                .count();
+            if brood >= nest.max_brood as usize {
+                continue;
+            }
+
+            let free: Vec<Position> = [(-1, 0), (1, 0), (0, -1), (0, 1), (-1, -1), (1, -1), (-1, 1), (1, 1)]
+                .iter()
+                .map(|(dx, dy)| Position { x: nest_position.x + dx, y: nest_position.y + dy })
+                .filter(|p| p.x >= 0 && p.x < self.width && p.y >= 0 && p.y < self.height)
+                .filter(|p| !self.elements.iter().any(|e| e.contains_position(p.x, p.y)))
+                .collect();
+            if free.is_empty() {
+                continue;
+            }
+            let position = free[rng.gen_range(0..free.len())];
+
+            let monster_id = self.allocate_monster_id();
+            let mut monster = Monster::new(
+                monster_id,
+                format!("{}妖崽", nest.name),
+                nest.brood_level(),
+                task_templates.clone(),
+            );
+            monster.nest_name = Some(nest.name.clone());
+            println!("🥚 {} 孵化出 {}", nest.name, monster.name);
+            self.elements.push(PositionedElement {
+                element: MapElement::Monster(monster),
+                position,
+                size: None,
+                positions: None,
+            });
+        }
+    }
+
+    /// 妖气加成：坐标处于多少巢穴的笼罩范围内，就叠加多少等级
+    pub fn nest_level_bonus(&self, position: &Position) -> u32 {
+        self.elements
+            .iter()
+            .filter_map(|p| match &p.element {
+                MapElement::MonsterNest(nest) if nest.in_aura(&p.position, position) => Some(nest.aura_level_bonus),
+                _ => None,
+            })
+            .sum()
+    }
+
+    /// 摧毁巢穴（讨伐任务成功时调用），返回巢穴名称；巢穴不复存在后不再孵化
+    pub fn destroy_nest(&mut self, location_id: &str) -> Option<String> {
+        let index = self.elements.iter().position(|p| {
+            matches!(p.element, MapElement::MonsterNest(_)) && p.element.get_location_id() == location_id
+        })?;
+        let name = self.elements.remove(index).element.get_name();
+        println!("💥 {} 被夷为平地，不再孵化妖魔", name);
+        Some(name)
+    }
+
+    /// 推进失守倒计时：被入侵地点连续无人守卫（守卫任务未分配或失败）达到上限后被妖魔占领，
+    /// 村庄和秘境从地图消失，势力转为敌对
+    pub fn occupy_undefended_locations(&mut self) -> Vec<FallenLocation> {
+        let mut occupied: Vec<(String, String)> = Vec::new();
+        for positioned in &mut self.elements {
+            if let MapElement::Monster(monster) = &mut positioned.element {
+                let Some(location_id) = monster.invaded_location_id.clone() else {
+                    continue;
+                };
+                if monster.has_active_defense_task {
+                    monster.undefended_turns = 0;
+                    continue;
+                }
+                monster.undefended_turns += 1;
+                if monster.undefended_turns >= LOCATION_FALL_TURNS
+                    && !occupied.iter().any(|(id, _)| *id == location_id)
+                {
+                    occupied.push((location_id, monster.name.clone()));
+                }
+            }
+        }
+
+        let mut fallen = Vec::new();
+        for (location_id, monster_name) in occupied {
+            let Some(index) = self.elements.iter().position(|p| p.element.get_location_id() == location_id) else {
+                continue;
+            };
+            let name = self.elements[index].element.get_name();
+            match &mut self.elements[index].element {
+                MapElement::Faction(faction) => {
+                    faction.relationship = faction.relationship.min(FALLEN_FACTION_RELATIONSHIP);
+                }
+                _ => {
+                    self.elements.remove(index);
+                }
+            }
+            println!("🏴 {} 失守", name);
+
+            // 占领后妖魔不再围困该地点
+            for positioned in &mut self.elements {
+                if let MapElement::Monster(m) = &mut positioned.element {
+                    if m.invaded_location_id.as_deref() == Some(location_id.as_str()) {
+                        m.invaded_location_id = None;
+                        m.undefended_turns = 0;
+                    }
+                }
+            }
+            fallen.push(FallenLocation { location_id, name, monster_name });
+        }
+        fallen
+    }
+
+    /// 结算村庄劫掠：被入侵的村庄损失人口和繁荣，繁荣归零的村庄从地图移除
+    fn raid_villages(&mut self) -> Vec<String> {
+        let invaded: Vec<String> = self
+            .elements
+            .iter()
+            .filter_map(|p| match &p.element {
+                MapElement::Monster(m) => m.invaded_location_id.clone(),
+                _ => None,
+            })
+            .collect();
+
+        let mut destroyed = Vec::new();
+        for positioned in &mut self.elements {
+            let location_id = positioned.element.get_location_id();
+            if let MapElement::Village(village) = &mut positioned.element {
+                let raiders = invaded.iter().filter(|id| **id == location_id).count();
+                for _ in 0..raiders {
+                    village.suffer_raid();
+                }
+                if raiders == 0 && village.recovering {
+                    village.recover();
+                }
+                if village.prosperity == 0 {
+                    destroyed.push(location_id);
+                }
+            }
+        }
+
+        if destroyed.is_empty() {
+            return Vec::new();
+        }
+
+        let mut names = Vec::new();
+        self.elements.retain(|p| {
+            let keep = !destroyed.contains(&p.element.get_location_id());
+            if !keep {
+                println!("🏚️ {} 被妖魔劫掠一空，从此荒废", p.element.get_name());
+                names.push(p.element.get_name());
+            }
+            keep
+        });
+        for positioned in &mut self.elements {
+            if let MapElement::Monster(m) = &mut positioned.element {
+                if m.invaded_location_id.as_ref().is_some_and(|id| destroyed.contains(id)) {
+                    m.invaded_location_id = None;
+                }
+            }
+        }
+        names
+    }
+
+    /// 叛离弟子在地图上另立门户：元婴及以上自立敌对势力，结丹期堕为妖魔
+    /// 返回新出现的地图元素名称（修为不足或无处落脚时返回 None）
+    pub fn spawn_renegade<R: rand::Rng>(&mut self, disciple: &Disciple, rng: &mut R) -> Option<String> {
+        let level = disciple.cultivation.current_level;
+        if level < CultivationLevel::GoldenCore {
+            return None;
+        }
+        let power = (level as u32 + 1) * 10;
+
+        let position = (0..20)
+            .map(|_| Position { x: rng.gen_range(0..self.width), y: rng.gen_range(0..self.height) })
+            .find(|p| {
+                !self.elements.iter().any(|e| e.contains_position(p.x, p.y))
+                    && (p.x, p.y) != (self.sect_position.x, self.sect_position.y)
+            })?;
+
+        let element = if level >= CultivationLevel::NascentSoul {
+            let hostile_task_templates = self
+                .config
+                .map_elements
+                .factions
+                .first()
+                .map(|f| f.hostile_task_templates.clone())
+                .unwrap_or_default();
+            MapElement::Faction(Faction {
+                name: format!("{}叛宗", disciple.name),
+                power_level: power,
+                relationship: -100,
+                friendly_task_templates: vec![],
+                hostile_task_templates,
+            })
+        } else {
+            let task_templates = self
+                .config
+                .monsters
+                .monster_templates
+                .first()
+                .map(|t| t.task_templates.clone())
+                .unwrap_or_default();
+            let id = self.allocate_monster_id();
+            MapElement::Monster(Monster::new(id, format!("堕魔{}", disciple.name), power, task_templates))
+        };
+
+        let name = element.get_name();
+        self.elements.push(PositionedElement {
+            element,
+            position,
+            size: None,
+            positions: None,
+        });
+        Some(name)
     }
 
-    /// 妖魔行动（移动或修行）
-    fn monster_actions(&mut self) {
-        use rand::Rng;
-        use rand::seq::SliceRandom;
-        let mut rng = rand::thread_rng();
+    /// 妖魔行动（移动或修行），返回被巡逻弟子拦下的妖魔
+    fn monster_actions<R: rand::Rng>(&mut self, patrols: &[PatrolZone], rng: &mut R) -> Vec<PatrolInterception> {
         let mut move_actions = Vec::new(); // (monster_index, new_position)
+        let mut interceptions = Vec::new();
 
         // 收集所有草药位置
         let herb_positions: Vec<Position> = self.elements.iter()
@@ -1187,28 +2697,55 @@ impl GameMap {
 
         let sect_pos = self.sect_position;
 
+        // 成魔或高等级妖魔可能锁定宗门为进攻目标
+        for positioned in &mut self.elements {
+            if let MapElement::Monster(m) = &mut positioned.element {
+                if !m.targeting_sect
+                    && (m.is_demon || m.level >= SECT_SIEGE_LEVEL)
+                    && rng.gen_bool(SECT_SIEGE_LOCK_CHANCE)
+                {
+                    m.targeting_sect = true;
+                    println!("⚠️ {} 盯上了宗门，正向宗门逼近！", m.name);
+                }
+            }
+        }
+
         // 收集怪物信息
-        let monster_infos: Vec<(usize, Position, bool, bool)> = self.elements.iter()
+        let monster_infos: Vec<(usize, Position, bool, bool, bool)> = self.elements.iter()
             .enumerate()
             .filter_map(|(i, e)| {
                 if let MapElement::Monster(m) = &e.element {
-                    Some((i, e.position, m.is_being_fought, m.has_active_defense_task))
+                    Some((i, e.position, m.is_being_fought, m.has_active_defense_task, m.targeting_sect))
                 } else {
                     None
                 }
             })
             .collect();
 
-        for (i, monster_pos, is_being_fought, has_active_defense_task) in monster_infos {
+        for (i, monster_pos, is_being_fought, has_active_defense_task, targeting_sect) in monster_infos {
             // 如果妖魔正在被战斗或有正在执行的守卫任务，则不能行动
             if is_being_fought || has_active_defense_task {
                 continue;
             }
 
+            // 锁定宗门的妖魔沿最短路径逼近，抵达后留在宗门攻打
+            if targeting_sect {
+                if monster_pos.x == sect_pos.x && monster_pos.y == sect_pos.y {
+                    continue;
+                }
+                let next_step = self
+                    .find_path(monster_pos, sect_pos, u32::MAX)
+                    .and_then(|path| path.get(1).copied());
+                if let Some(next_step) = next_step {
+                    move_actions.push((i, next_step));
+                    continue;
+                }
+            }
+
             // 50% 概率选择移动，50% 概率选择修行
             if rng.gen_bool(0.5) {
                 // 计算目标方向（草药或宗门）
-                let target = self.find_monster_target(&monster_pos, &herb_positions, &sect_pos);
+                let target = self.find_monster_target(&monster_pos, &herb_positions, &sect_pos, rng);
 
                 // 根据目标计算移动方向的权重
                 let directions = [(0, 1), (0, -1), (1, 0), (-1, 0)];
@@ -1270,6 +2807,16 @@ impl GameMap {
         // 执行移动
         for (monster_index, new_position) in move_actions {
             if let Some(positioned) = self.elements.get_mut(monster_index) {
+                // 巡逻区域内的妖魔无法前进，留在原地
+                if let Some(zone) = patrols.iter().find(|z| z.intercepts(positioned.position, new_position)) {
+                    if let MapElement::Monster(monster) = &positioned.element {
+                        interceptions.push(PatrolInterception {
+                            disciple_id: zone.disciple_id,
+                            monster_name: monster.name.clone(),
+                        });
+                    }
+                    continue;
+                }
                 positioned.position = new_position;
 
                 // 检查是否移动到了宗门位置（袭击宗门）
@@ -1282,13 +2829,18 @@ impl GameMap {
 
         // 怪物吞噬草药（在移动后检查）
         self.monsters_consume_herbs();
+
+        interceptions
     }
 
     /// 寻找怪物的目标（最近的草药或宗门）
-    fn find_monster_target(&self, monster_pos: &Position, herb_positions: &[Position], sect_pos: &Position) -> Option<Position> {
-        use rand::Rng;
-        let mut rng = rand::thread_rng();
-
+    fn find_monster_target<R: rand::Rng>(
+        &self,
+        monster_pos: &Position,
+        herb_positions: &[Position],
+        sect_pos: &Position,
+        rng: &mut R,
+    ) -> Option<Position> {
         // 找最近的草药
         let nearest_herb = herb_positions.iter()
             .min_by_key(|h| (h.x - monster_pos.x).abs() + (h.y - monster_pos.y).abs());
@@ -1328,14 +2880,18 @@ impl GameMap {
                 return;
             }
 
-            // 获取怪物信息
-            if let Some(positioned) = self.elements.get(monster_index) {
-                if let MapElement::Monster(monster) = &positioned.element {
-                    println!("⚠️ 警告：{} 已抵达宗门！6回合内必须消灭它，否则游戏失败！", monster.name);
+            // 获取怪物信息，抵达宗门的妖魔会留下攻打
+            if let Some(positioned) = self.elements.get_mut(monster_index) {
+                if let MapElement::Monster(monster) = &mut positioned.element {
+                    println!(
+                        "⚠️ 警告：{} 已抵达宗门！连续{}回合无人守卫，宗门将被攻破！",
+                        monster.name, SECT_INVASION_GRACE_TURNS
+                    );
+                    monster.targeting_sect = true;
                     self.sect_invasion = Some(SectInvasion {
                         monster_id: monster.id,
                         monster_name: monster.name.clone(),
-                        turns_remaining: 6,
+                        turns_remaining: SECT_INVASION_GRACE_TURNS,
                     });
                 }
             }
@@ -1384,8 +2940,9 @@ impl GameMap {
             }
         }
 
-        // 移除被吞噬的草药（从后向前删除以保持索引正确）
+        // 移除被吞噬的草药（从后向前删除以保持索引正确，多只妖魔同格时去重）
         herbs_to_remove.sort();
+        herbs_to_remove.dedup();
         herbs_to_remove.reverse();
         for herb_idx in herbs_to_remove {
             self.elements.remove(herb_idx);
@@ -1393,6 +2950,54 @@ impl GameMap {
     }
 
     /// 检查妖魔是否入侵了某个地点
+    /// 高阶天劫波及周边妖魔：渡劫成功时天威震退范围内的妖魔并使其放弃进攻宗门，
+    /// 失败时溃散的劫力吸引两倍范围内的妖魔向渡劫处靠近，返回受影响的妖魔名称
+    pub fn tribulation_shockwave(&mut self, center: Position, success: bool) -> Vec<String> {
+        let reach = if success { TRIBULATION_SHOCK_RADIUS } else { TRIBULATION_SHOCK_RADIUS * 2 };
+        let besieging = self.sect_invasion.as_ref().map(|invasion| invasion.monster_id);
+        let mut moves = Vec::new();
+        for (index, positioned) in self.elements.iter_mut().enumerate() {
+            let MapElement::Monster(monster) = &mut positioned.element else { continue };
+            // 交战中、被守卫锁定或正在攻打宗门的妖魔不受影响
+            if monster.is_being_fought || monster.has_active_defense_task || besieging == Some(monster.id) {
+                continue;
+            }
+            let from = positioned.position;
+            let distance = (from.x - center.x).abs() + (from.y - center.y).abs();
+            if distance > reach {
+                continue;
+            }
+            let (steps, away) = if success {
+                monster.targeting_sect = false;
+                (reach + 1 - distance, true)
+            } else {
+                (distance.min(TRIBULATION_ATTRACT_STEPS), false)
+            };
+            let mut to = from;
+            for _ in 0..steps {
+                let (dx, dy) = (to.x - center.x, to.y - center.y);
+                let sign = if away { 1 } else { -1 };
+                if dx.abs() >= dy.abs() {
+                    to.x += sign * if dx == 0 { 1 } else { dx.signum() };
+                } else {
+                    to.y += sign * dy.signum();
+                }
+            }
+            to.x = to.x.clamp(0, self.width - 1);
+            to.y = to.y.clamp(0, self.height - 1);
+            positioned.position = to;
+            moves.push((index, to, monster.name.clone()));
+        }
+        moves
+            .into_iter()
+            .map(|(index, to, name)| {
+                self.check_sect_invasion(index, to);
+                self.check_monster_invasion(index, to);
+                name
+            })
+            .collect()
+    }
+
     fn check_monster_invasion(&mut self, monster_index: usize, monster_pos: Position) {
         // 先查找同位置的可入侵元素
         let invaded_location_id = self.elements.iter().enumerate()
@@ -1407,7 +3012,10 @@ impl GameMap {
         // 更新妖魔的入侵状态（无论是否找到入侵地点，都要更新）
         if let Some(monster_elem) = self.elements.get_mut(monster_index) {
             if let MapElement::Monster(monster) = &mut monster_elem.element {
-                // 如果找到入侵地点就设置，否则清除
+                // 如果找到入侵地点就设置，否则清除；换了地点重新计算失守回合
+                if monster.invaded_location_id != invaded_location_id {
+                    monster.undefended_turns = 0;
+                }
                 monster.invaded_location_id = invaded_location_id;
             }
         }
@@ -1418,6 +3026,12 @@ impl GameMap {
         let mut tasks = Vec::new();
         let mut task_id = task_id_start;
 
+        // 宗门被袭击时生成高优先级的守卫宗门任务
+        if let Some(task) = self.generate_sect_defense_task(task_id) {
+            tasks.push(task);
+            task_id += 1;
+        }
+
         for positioned in &self.elements {
             if let MapElement::Monster(monster) = &positioned.element {
                 // 如果妖魔入侵了某个地点
@@ -1441,8 +3055,8 @@ impl GameMap {
                                 monster.level * 20,  // 资源奖励
                             );
 
-                            // 设置任务位置为被入侵地点的位置
-                            task.position = Some(invaded_elem.position);
+                            // 设置任务位置为被入侵地点占据的格子
+                            invaded_elem.locate_task(&mut task);
 
                             tasks.push(task);
                             task_id += 1;
@@ -1455,6 +3069,33 @@ impl GameMap {
         tasks
     }
 
+    /// 生成守卫宗门任务（宗门未被袭击时返回 None）
+    fn generate_sect_defense_task(&self, task_id: usize) -> Option<Task> {
+        let invasion = self.sect_invasion.as_ref()?;
+        let monster = self.elements.iter().find_map(|positioned| match &positioned.element {
+            MapElement::Monster(m) if m.id == invasion.monster_id => Some(m),
+            _ => None,
+        })?;
+
+        let mut task = Task::new(
+            task_id,
+            "守卫宗门".to_string(),
+            crate::task::TaskType::Combat(crate::task::CombatTask {
+                enemy_id: Some(monster.id),
+                enemy_name: monster.name.clone(),
+                enemy_level: monster.level,
+                difficulty: monster.level,
+            }),
+            monster.level * 15, // 进度奖励
+            monster.level * 30, // 资源奖励
+        );
+        task.location_id = Some(SECT_LOCATION_ID.to_string());
+        task.position = Some(self.sect_position);
+        task.expiry_turns = SECT_INVASION_GRACE_TURNS;
+
+        Some(task)
+    }
+
     /// 清除妖魔的任务关联（当任务完成或失效时调用）
     pub fn clear_monster_task(&mut self, task_id: usize) {
         for positioned in &mut self.elements {
@@ -1467,39 +3108,45 @@ impl GameMap {
     }
 
     /// 锁定妖魔的移动（当守卫任务被分配时调用）
-    /// 通过敌人名称（如"妖兽#10"）识别妖魔ID
-    pub fn lock_monster_for_defense_task(&mut self, enemy_name: &str) {
-        // 从敌人名称中提取妖魔ID（格式：{怪物名}#{ID}）
-        if let Some(id_str) = enemy_name.split('#').nth(1) {
-            if let Ok(monster_id) = id_str.parse::<usize>() {
-                for positioned in &mut self.elements {
-                    if let MapElement::Monster(monster) = &mut positioned.element {
-                        if monster.id == monster_id {
-                            monster.has_active_defense_task = true;
-                            return;
-                        }
-                    }
+    pub fn lock_monster_for_defense_task(&mut self, monster_id: usize) {
+        self.set_monster_defense_lock(monster_id, true);
+    }
+
+    /// 解锁妖魔的移动（当守卫任务完成、失败或失效时调用）
+    pub fn unlock_monster_for_defense_task(&mut self, monster_id: usize) {
+        self.set_monster_defense_lock(monster_id, false);
+    }
+
+    fn set_monster_defense_lock(&mut self, monster_id: usize, locked: bool) {
+        for positioned in &mut self.elements {
+            if let MapElement::Monster(monster) = &mut positioned.element {
+                if monster.id == monster_id {
+                    monster.has_active_defense_task = locked;
+                    return;
                 }
             }
         }
     }
 
-    /// 解锁妖魔的移动（当守卫任务完成或失效时调用）
-    /// 通过敌人名称（如"妖兽#10"）识别妖魔ID
-    pub fn unlock_monster_for_defense_task(&mut self, enemy_name: &str) {
-        // 从敌人名称中提取妖魔ID（格式：{怪物名}#{ID}）
-        if let Some(id_str) = enemy_name.split('#').nth(1) {
-            if let Ok(monster_id) = id_str.parse::<usize>() {
-                for positioned in &mut self.elements {
-                    if let MapElement::Monster(monster) = &mut positioned.element {
-                        if monster.id == monster_id {
-                            monster.has_active_defense_task = false;
-                            return;
-                        }
-                    }
-                }
+    /// 按任务所属势力结算关系变化，返回 (势力名, 变化前, 变化后)
+    pub fn settle_faction_task(&mut self, task: &Task) -> Option<(String, i32, i32)> {
+        let location_id = task.location_id.as_deref()?;
+        self.elements.iter_mut().find_map(|positioned| match &mut positioned.element {
+            MapElement::Faction(faction) if format!("faction_{}", faction.name) == location_id => {
+                let before = faction.relationship;
+                faction.settle_task(task);
+                Some((faction.name.clone(), before, faction.relationship))
             }
-        }
+            _ => None,
+        })
+    }
+
+    /// 根据ID查找怪物
+    pub fn find_monster(&self, monster_id: usize) -> Option<&Monster> {
+        self.elements.iter().find_map(|positioned| match &positioned.element {
+            MapElement::Monster(monster) if monster.id == monster_id => Some(monster),
+            _ => None,
+        })
     }
 
     /// 获取怪物的当前位置
@@ -1524,6 +3171,21 @@ impl GameMap {
             }
         }
 
+        // 被它入侵的村庄开始恢复繁荣
+        let invaded_location_id = self.elements.iter().find_map(|p| match &p.element {
+            MapElement::Monster(m) if m.id == monster_id => m.invaded_location_id.clone(),
+            _ => None,
+        });
+        if let Some(location_id) = invaded_location_id {
+            for positioned in &mut self.elements {
+                if positioned.element.get_location_id() == location_id {
+                    if let MapElement::Village(village) = &mut positioned.element {
+                        village.recovering = true;
+                    }
+                }
+            }
+        }
+
         self.elements.retain(|positioned| {
             if let MapElement::Monster(monster) = &positioned.element {
                 monster.id != monster_id
@@ -1533,18 +3195,6 @@ impl GameMap {
         });
     }
 
-    /// 解锁指定ID怪物的移动（当守卫任务完成或失效时调用）
-    pub fn unlock_monster_by_id(&mut self, monster_id: usize) {
-        for positioned in &mut self.elements {
-            if let MapElement::Monster(monster) = &mut positioned.element {
-                if monster.id == monster_id {
-                    monster.has_active_defense_task = false;
-                    return;
-                }
-            }
-        }
-    }
-
     /// 标记怪物正在被战斗（当战斗任务被分配时调用）
     pub fn set_monster_being_fought(&mut self, monster_id: usize, is_fighting: bool) {
         for positioned in &mut self.elements {
@@ -1563,35 +3213,20 @@ impl GameMap {
         let mut invalid_task_ids = Vec::new();
 
         for task in current_tasks {
-            // 只检查守卫任务（Combat类型且名称包含"守卫"）
-            if let crate::task::TaskType::Combat(combat_task) = &task.task_type {
-                // 守卫任务的敌人名称格式为 "{怪物名}#{ID}"
-                if task.name.contains("守卫") && combat_task.enemy_name.contains('#') {
-                    // 提取妖魔ID（从 "妖兽#10" 这样的格式中提取）
-                    if let Some(id_str) = combat_task.enemy_name.split('#').nth(1) {
-                        if let Ok(monster_id) = id_str.parse::<usize>() {
-                            // 查找妖魔
-                            if let Some(positioned) = self.elements.iter().find(|p| {
-                                if let MapElement::Monster(m) = &p.element {
-                                    m.id == monster_id
-                                } else {
-                                    false
-                                }
-                            }) {
-                                if let MapElement::Monster(monster) = &positioned.element {
-                                    // 检查妖魔是否还在入侵状态
-                                    if monster.invaded_location_id.is_none() {
-                                        // 妖魔已经离开，任务应该失效
-                                        invalid_task_ids.push(task.id);
-                                    }
-                                }
-                            } else {
-                                // 妖魔不存在了（被消灭），任务应该失效
-                                invalid_task_ids.push(task.id);
-                            }
-                        }
-                    }
-                }
+            // 只检查守卫地点的任务（守卫宗门由宗门袭击状态单独管理）
+            let crate::task::TaskType::Combat(combat_task) = &task.task_type else {
+                continue;
+            };
+            if !task.name.contains("守卫") || task.is_sect_defense() {
+                continue;
+            }
+            let Some(monster_id) = combat_task.enemy_id else {
+                continue;
+            };
+            // 妖魔已被消灭或已经离开入侵地点，任务失效
+            let still_invading = self.find_monster(monster_id).is_some_and(|m| m.invaded_location_id.is_some());
+            if !still_invading {
+                invalid_task_ids.push(task.id);
             }
         }
 
@@ -1609,9 +3244,13 @@ impl GameMap {
         })
     }
 
-    /// 更新宗门袭击倒计时，返回是否游戏结束
-    pub fn update_sect_invasion(&mut self) -> bool {
+    /// 更新宗门袭击倒计时（有弟子守卫时重置），返回是否游戏结束
+    pub fn update_sect_invasion(&mut self, defended: bool) -> bool {
         if let Some(ref mut invasion) = self.sect_invasion {
+            if defended {
+                invasion.turns_remaining = SECT_INVASION_GRACE_TURNS;
+                return false;
+            }
             if invasion.turns_remaining > 0 {
                 invasion.turns_remaining -= 1;
                 println!("⚠️ {} 正在袭击宗门！剩余 {} 回合", invasion.monster_name, invasion.turns_remaining);
@@ -1635,53 +3274,124 @@ impl GameMap {
         self.sect_invasion.is_some()
     }
 
+    /// 获取指定位置的地形（没有地形要素时返回 None）
+    pub fn terrain_at(&self, x: i32, y: i32) -> Option<TerrainType> {
+        self.elements.iter().find_map(|positioned| match &positioned.element {
+            MapElement::Terrain(terrain) if positioned.contains_position(x, y) => {
+                Some(terrain.terrain_type)
+            }
+            _ => None,
+        })
+    }
+
     /// 检查指定位置是否可通行
-    /// 山脉和水域是不可通行的
+    /// 山脉不可通行，水域可通行但移动消耗翻倍
     pub fn is_passable(&self, x: i32, y: i32) -> bool {
+        self.move_cost(x, y).is_some()
+    }
+
+    /// 进入指定格子的移动消耗（不可通行返回 None）
+    pub fn move_cost(&self, x: i32, y: i32) -> Option<u32> {
         // 检查是否在地图范围内
         if x < 0 || x >= self.width || y < 0 || y >= self.height {
-            return false;
+            return None;
         }
 
-        // 检查该位置是否有不可通行的地形
-        for positioned in &self.elements {
-            // 检查该元素是否占据目标位置
-            if let Some(ref positions) = positioned.positions {
-                // 使用explicit positions检查
-                if positions.iter().any(|p| p.x == x && p.y == y) {
-                    // 如果是山脉或水域，则不可通行
-                    if let MapElement::Terrain(terrain) = &positioned.element {
-                        match terrain.terrain_type {
-                            TerrainType::Mountain | TerrainType::Water => return false,
-                            _ => {}
-                        }
-                    }
+        match self.terrain_at(x, y) {
+            Some(TerrainType::Mountain) => None,
+            Some(TerrainType::Water) => Some(2),
+            _ => Some(1), // 平原、林地
+        }
+    }
+
+    /// A* 寻路：返回从 from 到 to 的路径（包含起点和终点），总消耗不超过 max_cost
+    pub fn find_path(&self, from: Position, to: Position, max_cost: u32) -> Option<Vec<Position>> {
+        use std::cmp::Reverse;
+        use std::collections::{BinaryHeap, HashMap};
+
+        if from.x == to.x && from.y == to.y {
+            return Some(vec![from]);
+        }
+        if !self.is_passable(to.x, to.y) {
+            return None;
+        }
+
+        // 曼哈顿距离作为启发函数（每格最低消耗为1，保证可采纳）
+        let heuristic = |x: i32, y: i32| ((x - to.x).abs() + (y - to.y).abs()) as u32;
+
+        let mut open = BinaryHeap::new();
+        let mut best_cost: HashMap<(i32, i32), u32> = HashMap::new();
+        let mut came_from: HashMap<(i32, i32), (i32, i32)> = HashMap::new();
+
+        best_cost.insert((from.x, from.y), 0);
+        open.push(Reverse((heuristic(from.x, from.y), 0u32, from.x, from.y)));
+
+        while let Some(Reverse((_, cost, x, y))) = open.pop() {
+            if (x, y) == (to.x, to.y) {
+                // 回溯路径
+                let mut path = vec![to];
+                let mut current = (x, y);
+                while let Some(&prev) = came_from.get(&current) {
+                    path.push(Position { x: prev.0, y: prev.1 });
+                    current = prev;
                 }
-            } else {
-                // Fallback：使用size-based检查
-                let (width, height) = positioned.size.unwrap_or((1, 1));
-                let in_range = x >= positioned.position.x
-                    && x < positioned.position.x + width as i32
-                    && y >= positioned.position.y
-                    && y < positioned.position.y + height as i32;
-
-                if in_range {
-                    if let MapElement::Terrain(terrain) = &positioned.element {
-                        match terrain.terrain_type {
-                            TerrainType::Mountain | TerrainType::Water => return false,
-                            _ => {}
-                        }
-                    }
+                path.reverse();
+                return Some(path);
+            }
+
+            if cost > *best_cost.get(&(x, y)).unwrap_or(&u32::MAX) {
+                continue;
+            }
+
+            for (dx, dy) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+                let (nx, ny) = (x + dx, y + dy);
+                let step = match self.move_cost(nx, ny) {
+                    Some(step) => step,
+                    None => continue,
+                };
+                let next_cost = cost + step;
+                if next_cost > max_cost || next_cost >= *best_cost.get(&(nx, ny)).unwrap_or(&u32::MAX) {
+                    continue;
                 }
+                best_cost.insert((nx, ny), next_cost);
+                came_from.insert((nx, ny), (x, y));
+                open.push(Reverse((next_cost + heuristic(nx, ny), next_cost, nx, ny)));
             }
         }
 
-        true
+        None
+    }
+
+    /// 计算路径的移动消耗（不计起点）
+    pub fn path_cost(&self, path: &[Position]) -> u32 {
+        path.iter()
+            .skip(1)
+            .map(|p| self.move_cost(p.x, p.y).unwrap_or(0))
+            .sum()
     }
 }
 
+/// 前置任务完成后生成解锁的后续任务（继承前置任务的地点）
+pub fn unlocked_task(task_id: usize, template: &TaskTemplateConfig, parent: &Task) -> Option<Task> {
+    let mut task = Task::new(
+        task_id,
+        template.name_template.clone(),
+        parse_task_type(template)?,
+        template.progress_reward,
+        template.resource_reward,
+    );
+    task.reputation_reward = template.reputation_reward;
+    task.dao_heart_impact = template.dao_heart_impact;
+    task.location_id = parent.location_id.clone();
+    task.position = parent.position;
+    task.valid_positions = parent.valid_positions.clone();
+    task.prerequisite_task_name = Some(parent.name.clone());
+    task.unlocks = template.unlocks.clone();
+    Some(task)
+}
+
 /// 辅助函数：解析任务类型
-fn parse_task_type(template: &TaskTemplateConfig) -> Option<TaskType> {
+pub(crate) fn parse_task_type(template: &TaskTemplateConfig) -> Option<TaskType> {
     match template.task_type.as_str() {
         "Gathering" => Some(TaskType::Gathering(GatheringTask {
             resource_type: template.resource_type.as_ref()?.clone(),
@@ -1696,6 +3406,8 @@ fn parse_task_type(template: &TaskTemplateConfig) -> Option<TaskType> {
         "Exploration" => Some(TaskType::Exploration(ExplorationTask {
             location: "未知地点".to_string(), // 需要在调用处替换
             danger_level: template.danger_level.unwrap_or(10),
+            realm_type: None,
+            depth: 0,
         })),
         "Auxiliary" => {
             let skill_required = template.skill_required.as_ref()
@@ -1705,6 +3417,12 @@ fn parse_task_type(template: &TaskTemplateConfig) -> Option<TaskType> {
                 skill_required,
             }))
         },
+        "Investment" => Some(TaskType::Investment(InvestmentTask {
+            capital: template.capital?,
+            return_rate: template.return_rate.unwrap_or(0.2),
+            maturity_turns: template.maturity_turns.unwrap_or(4),
+            description: template.name_template.clone(),
+        })),
         _ => None,
     }
 }
@@ -1739,3 +3457,732 @@ fn parse_talent_type_option(s: &str) -> Option<TalentType> {
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_monster_ids_are_per_map() {
+        let mut map_a = GameMap::new();
+        let mut map_b = GameMap::new();
+
+        assert_eq!(map_a.allocate_monster_id(), 0);
+        assert_eq!(map_a.allocate_monster_id(), 1);
+        assert_eq!(map_a.allocate_monster_id(), 2);
+
+        // 另一局从0开始，不受影响
+        assert_eq!(map_b.allocate_monster_id(), 0);
+        assert_eq!(map_a.allocate_monster_id(), 3);
+        assert_eq!(map_b.allocate_monster_id(), 1);
+    }
+
+    #[test]
+    fn test_restore_keeps_monster_ids_unique() {
+        let mut map = GameMap::new();
+        let id = map.allocate_monster_id();
+        map.elements.push(PositionedElement {
+            element: MapElement::Monster(Monster::new(id, "噬魂虎".to_string(), 5, vec![])),
+            position: Position { x: 0, y: 0 },
+            size: None,
+            positions: None,
+        });
+        // 旧存档没有计数器字段（默认为0）时，按已有最大ID恢复
+        let restored = GameMap::restore(map.elements.clone(), None, 0, 0);
+        assert_eq!(restored.next_monster_id, 1);
+    }
+
+    #[test]
+    fn test_opportunity_ids_are_allocated_per_map() {
+        let mut rng = rand::thread_rng();
+        let mut map = GameMap::new();
+        let mut other = GameMap::new();
+        assert_eq!((map.allocate_opportunity_id(), map.allocate_opportunity_id()), (0, 1));
+        // 另一局的计数器互不影响
+        assert_eq!(other.allocate_opportunity_id(), 0);
+
+        let id = map.allocate_opportunity_id();
+        map.elements.push(PositionedElement {
+            element: MapElement::Opportunity(Opportunity::new_random(id, 3, &mut rng)),
+            position: Position { x: 0, y: 0 },
+            size: None,
+            positions: None,
+        });
+        // 存档中的计数器原样恢复，旧存档按已有最大ID推进
+        assert_eq!(GameMap::restore(map.elements.clone(), None, 0, 5).next_opportunity_id, 5);
+        assert_eq!(GameMap::restore(map.elements.clone(), None, 0, 0).next_opportunity_id, 3);
+    }
+
+    fn terrain(terrain_type: TerrainType, x: i32, y: i32) -> PositionedElement {
+        PositionedElement {
+            element: MapElement::Terrain(Terrain {
+                terrain_type,
+                name: "测试地形".to_string(),
+                variant_type: None,
+            }),
+            position: Position { x, y },
+            size: None,
+            positions: None,
+        }
+    }
+
+    #[test]
+    fn test_only_mature_herbs_are_collected() {
+        let mut map = GameMap::new();
+        let mut herb = Herb::new_random(&mut rand::thread_rng());
+        herb.growth_stage = 0;
+        map.elements.push(PositionedElement {
+            element: MapElement::Herb(herb),
+            position: Position { x: 3, y: 3 },
+            size: None,
+            positions: None,
+        });
+
+        // 未成熟时不会被采走
+        assert!(map.collect_herb_at(3, 3).is_err());
+        assert_eq!(map.elements.len(), 1);
+
+        // 生长至成熟后可以采集，并在别处重新播种
+        if let MapElement::Herb(herb) = &mut map.elements[0].element {
+            herb.growth_stage = herb.max_growth;
+        }
+        let collected = map.collect_herb_at(3, 3).unwrap();
+        assert!(collected.is_some());
+        assert_eq!(map.elements.len(), 1);
+        assert!(map.elements[0].position.x != 3 || map.elements[0].position.y != 3);
+    }
+
+    #[test]
+    fn test_find_path_blocked_by_mountains() {
+        let mut map = GameMap::new();
+        for (x, y) in [(4, 5), (6, 5), (5, 4), (5, 6)] {
+            map.elements.push(terrain(TerrainType::Mountain, x, y));
+        }
+
+        let from = Position { x: 2, y: 5 };
+        let to = Position { x: 5, y: 5 };
+        assert!(map.find_path(from, to, 100).is_none());
+    }
+
+    #[test]
+    fn test_find_path_water_costs_double() {
+        let mut map = GameMap::new();
+        // 一条竖直河流挡在中间，只能涉水通过
+        for y in 0..map.height {
+            map.elements.push(terrain(TerrainType::Water, 5, y));
+        }
+
+        let from = Position { x: 4, y: 5 };
+        let to = Position { x: 6, y: 5 };
+        let path = map.find_path(from, to, 10).unwrap();
+        assert_eq!(path.len(), 3);
+        assert_eq!(map.path_cost(&path), 3);
+
+        // 移动力不足以涉水
+        assert!(map.find_path(from, to, 2).is_none());
+    }
+
+    #[test]
+    fn test_query_elements_filters_by_bounds_and_type() {
+        let mut map = GameMap::new();
+        map.elements.push(terrain(TerrainType::Forest, 2, 2));
+        map.elements.push(terrain(TerrainType::Water, 8, 8));
+        let id = map.allocate_monster_id();
+        map.elements.push(PositionedElement {
+            element: MapElement::Monster(Monster::new(id, "噬魂虎".to_string(), 5, vec![])),
+            position: Position { x: 3, y: 4 },
+            size: None,
+            positions: None,
+        });
+        // 2x2 大型建筑只有右下角落在框内
+        map.elements.push(PositionedElement {
+            element: MapElement::Terrain(Terrain {
+                terrain_type: TerrainType::Mountain,
+                name: "大山".to_string(),
+                variant_type: None,
+            }),
+            position: Position { x: 4, y: 4 },
+            size: Some((2, 2)),
+            positions: None,
+        });
+
+        let bounds = MapBounds { min_x: Some(0), max_x: Some(5), min_y: Some(0), max_y: Some(5) };
+        let names: Vec<String> = map.query_elements(None, &bounds)
+            .iter()
+            .map(|p| p.element.get_name())
+            .collect();
+        assert_eq!(names, vec!["测试地形", "噬魂虎", "大山"]);
+
+        let monsters = map.query_elements(Some("Monster"), &bounds);
+        assert_eq!(monsters.len(), 1);
+        assert!(map.query_elements(Some("Monster"), &MapBounds { min_x: Some(6), ..Default::default() }).is_empty());
+        assert_eq!(map.query_elements(None, &MapBounds::default()).len(), 4);
+    }
+
+    #[test]
+    fn test_raided_village_declines_until_abandoned() {
+        let mut map = GameMap::new();
+        let village = Village {
+            name: "桃源村".to_string(),
+            population: 100,
+            prosperity: 30,
+            task_templates: vec![],
+            peak_prosperity: 30,
+            recovering: false,
+        };
+        map.elements.push(PositionedElement {
+            element: MapElement::Village(village),
+            position: Position { x: 2, y: 2 },
+            size: None,
+            positions: None,
+        });
+        let invade = |map: &mut GameMap| {
+            let id = map.allocate_monster_id();
+            let mut monster = Monster::new(id, "血狼".to_string(), 5, vec![]);
+            monster.invaded_location_id = Some("village_桃源村".to_string());
+            map.elements.push(PositionedElement {
+                element: MapElement::Monster(monster),
+                position: Position { x: 2, y: 2 },
+                size: None,
+                positions: None,
+            });
+            id
+        };
+        let village_state = |map: &GameMap| {
+            map.elements.iter().find_map(|p| match &p.element {
+                MapElement::Village(v) => Some((v.population, v.prosperity)),
+                _ => None,
+            })
+        };
+        assert_eq!(map.calculate_income(0), 3);
+
+        let wolf = invade(&mut map);
+        map.raid_villages();
+        map.raid_villages();
+        assert_eq!(village_state(&map), Some((81, 20)));
+        // 被妖魔占据期间不产出收入
+        assert_eq!(map.calculate_income(0), 0);
+
+        // 斩杀妖魔后逐步恢复，不超过劫掠前的繁荣
+        map.remove_monster_by_id(wolf);
+        assert_eq!(map.calculate_income(0), 2);
+        map.raid_villages();
+        assert_eq!(village_state(&map).unwrap().1, 20 + VILLAGE_RECOVERY_PER_TURN);
+
+        // 长期无人守卫，村庄荒废并不再提供收入
+        invade(&mut map);
+        let mut destroyed = Vec::new();
+        for _ in 0..10 {
+            destroyed.extend(map.raid_villages());
+        }
+        assert_eq!(destroyed, vec!["桃源村".to_string()]);
+        assert_eq!(village_state(&map), None);
+        assert_eq!(map.calculate_income(0), 0);
+        assert!(map.elements.iter().all(|p| match &p.element {
+            MapElement::Monster(m) => m.invaded_location_id.is_none(),
+            _ => true,
+        }));
+    }
+
+    #[test]
+    fn test_disaster_year_cuts_income_and_breeds_monsters() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let mut map = GameMap::new();
+        map.elements.push(PositionedElement {
+            element: MapElement::Village(Village {
+                name: "丰饶村".to_string(),
+                population: 500,
+                prosperity: 100,
+                task_templates: vec![],
+                peak_prosperity: 100,
+                recovering: false,
+            }),
+            position: Position { x: 0, y: 0 },
+            size: None,
+            positions: None,
+        });
+
+        map.harvest = Harvest::Bountiful;
+        let bountiful_income = map.calculate_income(0);
+        map.harvest = Harvest::Disaster;
+        let disaster_income = map.calculate_income(0);
+        assert!(disaster_income * 2 < bountiful_income);
+
+        let spawned_monsters = |harvest| {
+            let mut map = GameMap::new();
+            map.harvest = harvest;
+            let mut rng = StdRng::seed_from_u64(42);
+            for _ in 0..200 {
+                map.update(&HashSet::new(), &[], &mut rng);
+            }
+            map.elements.iter().filter(|p| matches!(p.element, MapElement::Monster(_))).count()
+        };
+        assert!(spawned_monsters(Harvest::Disaster) > spawned_monsters(Harvest::Bountiful) * 2);
+    }
+
+    #[test]
+    fn test_overlapping_config_elements_keep_only_first() {
+        let mut map = GameMap::new();
+        let mut config = ConfigManager {
+            map_elements: map.config.map_elements.clone(),
+            monsters: map.config.monsters.clone(),
+        };
+        let village = |name: &str| VillageTemplate {
+            name: name.to_string(),
+            population: 100,
+            prosperity: 50,
+            position: PositionConfig { x: 1, y: 1 },
+            size: None,
+            task_templates: vec![],
+        };
+        config.map_elements.villages.push(village("东村"));
+        config.map_elements.villages.push(village("西村"));
+
+        let report = map.apply_config(config);
+        assert!(report.added.contains(&"东村".to_string()));
+        assert!(!report.added.contains(&"西村".to_string()));
+        let at_cell: Vec<_> = map.elements.iter().filter(|e| e.contains_position(1, 1)).collect();
+        assert_eq!(at_cell.len(), 1);
+        assert_eq!(at_cell[0].element.get_name(), "东村");
+    }
+
+    #[test]
+    fn test_every_tile_of_large_realm_matches_task_position() {
+        let mut map = GameMap::new();
+        let template = ConfigManager::create_default().map_elements.secret_realms[0].clone();
+        assert_eq!((template.position.x, template.position.y), (17, 3));
+        assert!(map.place_element(place_from_template(
+            MapElement::SecretRealm(SecretRealm::from_template(&template)), &template.position, &template.size)));
+
+        // 2x2 秘境占据的其他格子不能再放置元素
+        let village = Village {
+            name: "洞口村".to_string(),
+            population: 50,
+            prosperity: 30,
+            task_templates: vec![],
+            peak_prosperity: 30,
+            recovering: false,
+        };
+        assert!(!map.place_element(PositionedElement {
+            element: MapElement::Village(village),
+            position: Position { x: 18, y: 4 },
+            size: None,
+            positions: None,
+        }));
+
+        let tasks = map.get_available_tasks(RiskLevel::Deadly, &mut rand::thread_rng());
+        let task = tasks.iter().find(|t| t.name.contains("火焰洞窟")).unwrap();
+        for (x, y) in [(17, 3), (18, 3), (17, 4), (18, 4)] {
+            assert!(task.is_disciple_at_valid_position(&Position { x, y }));
+        }
+        assert!(!task.is_disciple_at_valid_position(&Position { x: 19, y: 3 }));
+    }
+
+    #[test]
+    fn test_nest_hatches_periodically_until_destroyed() {
+        let mut map = GameMap::new();
+        let mut rng = rand::thread_rng();
+        let template = ConfigManager::create_default().monsters.nests[0].clone();
+        assert!(map.place_element(place_from_template(
+            MapElement::MonsterNest(MonsterNest::from_template(&template)),
+            &template.position,
+            &None,
+        )));
+        let brood = |map: &GameMap| map.elements.iter().filter_map(|p| p.element.as_monster()).count();
+
+        // 每隔 spawn_interval 回合孵化一只
+        for turn in 1..=template.spawn_interval * 2 {
+            map.hatch_nest_broods(&mut rng);
+            assert_eq!(brood(&map), (turn / template.spawn_interval) as usize);
+        }
+        let hatchling = map.elements.iter().find(|p| p.element.as_monster().is_some()).unwrap();
+        assert_eq!(map.nest_level_bonus(&hatchling.position), template.aura_level_bonus);
+
+        // 讨伐巢穴是多人战斗任务
+        let tasks = map.get_available_tasks(RiskLevel::Deadly, &mut rng);
+        let raid = tasks.iter().find(|t| t.location_id.as_deref() == Some("nest_万妖窟")).unwrap();
+        assert_eq!(raid.max_participants, NEST_RAID_MAX_PARTICIPANTS);
+
+        // 摧毁后不再孵化
+        assert_eq!(map.destroy_nest("nest_万妖窟"), Some("万妖窟".to_string()));
+        for _ in 0..template.spawn_interval * 2 {
+            map.hatch_nest_broods(&mut rng);
+        }
+        assert_eq!(brood(&map), 2);
+        assert_eq!(map.destroy_nest("nest_万妖窟"), None);
+    }
+
+    #[test]
+    fn test_higher_level_monsters_drop_more_and_better_loot() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let drops = vec![
+            MonsterDropConfig { item: DropItemConfig::Herb { name: "噬魂草".to_string() }, chance: 0.3 },
+            MonsterDropConfig { item: DropItemConfig::Pill { pill_type: "QiRecovery".to_string() }, chance: 0.3 },
+        ];
+        let monster = |level| {
+            let mut monster = Monster::new(0, "妖兽".to_string(), level, vec![]);
+            monster.drops = drops.clone();
+            monster
+        };
+
+        // 采样统计：掉落件数与平均品质档位
+        let sample = |monster: &Monster| {
+            let mut rng = StdRng::seed_from_u64(7);
+            let (mut count, mut tier_sum) = (0usize, 0usize);
+            for _ in 0..2000 {
+                for drop in monster.roll_drops(&mut rng) {
+                    count += 1;
+                    tier_sum += match drop {
+                        MonsterDrop::Herb { quality, .. } => quality as usize,
+                        MonsterDrop::Pill { grade, .. } => grade as usize,
+                        MonsterDrop::Material { .. } => 0,
+                    };
+                }
+            }
+            (count, tier_sum as f64 / count as f64)
+        };
+
+        let (low_count, low_tier) = sample(&monster(5));
+        let (high_count, high_tier) = sample(&monster(80));
+        assert!(high_count > low_count, "高等级掉落件数 {} 应多于低等级 {}", high_count, low_count);
+        assert!(high_tier > low_tier + 1.0, "高等级平均品质 {} 应高于低等级 {}", high_tier, low_tier);
+
+        // 成魔后掉落更多
+        let mut demon = monster(80);
+        demon.is_demon = true;
+        assert!(demon.drop_chance(0.3) > monster(80).drop_chance(0.3));
+
+        // 没有掉落表的妖魔什么也不掉
+        let mut rng = StdRng::seed_from_u64(1);
+        assert!(Monster::new(1, "野狼".to_string(), 80, vec![]).roll_drops(&mut rng).is_empty());
+    }
+
+    #[test]
+    fn test_undefended_village_falls_after_grace_turns() {
+        let mut map = GameMap::new();
+        map.elements.push(PositionedElement {
+            element: MapElement::Village(Village {
+                name: "青石村".to_string(),
+                population: 500,
+                prosperity: 100,
+                task_templates: vec![],
+                peak_prosperity: 100,
+                recovering: false,
+            }),
+            position: Position { x: 3, y: 3 },
+            size: None,
+            positions: None,
+        });
+        let id = map.allocate_monster_id();
+        let mut monster = Monster::new(id, "赤目妖狼".to_string(), 10, vec![]);
+        monster.invaded_location_id = Some("village_青石村".to_string());
+        map.elements.push(PositionedElement {
+            element: MapElement::Monster(monster),
+            position: Position { x: 3, y: 3 },
+            size: None,
+            positions: None,
+        });
+        let has_village = |map: &GameMap| map.elements.iter().any(|p| matches!(p.element, MapElement::Village(_)));
+
+        // 有人守卫的回合不计入失守倒计时
+        map.lock_monster_for_defense_task(id);
+        assert!(map.occupy_undefended_locations().is_empty());
+        map.unlock_monster_for_defense_task(id);
+
+        for _ in 1..LOCATION_FALL_TURNS {
+            assert!(map.occupy_undefended_locations().is_empty());
+        }
+        assert!(has_village(&map));
+
+        let fallen = map.occupy_undefended_locations();
+        assert_eq!(fallen.len(), 1);
+        assert_eq!(fallen[0].name, "青石村");
+        assert!(!has_village(&map));
+        assert!(map.elements.iter().all(|p| match &p.element {
+            MapElement::Monster(m) => m.invaded_location_id.is_none(),
+            _ => true,
+        }));
+    }
+
+    #[test]
+    fn test_binary_export_round_trip_keeps_elements_and_resets_task_state() {
+        let mut map = GameMap::new();
+        map.initialize(&mut rand::thread_rng());
+        let monster_id = map.allocate_monster_id();
+        let template = ConfigManager::create_default().monsters.monster_templates[0].clone();
+        map.elements.push(PositionedElement {
+            element: MapElement::Monster(Monster::from_template(monster_id, &template)),
+            position: Position { x: 1, y: 1 },
+            size: None,
+            positions: None,
+        });
+        map.set_monster_being_fought(monster_id, true);
+        for positioned in &mut map.elements {
+            if let MapElement::Monster(monster) = &mut positioned.element {
+                monster.current_task_id = Some(42);
+                monster.has_active_defense_task = true;
+            }
+        }
+
+        let bytes = map.to_bytes().unwrap();
+        // 比 JSON 更紧凑
+        assert!(bytes.len() < serde_json::to_vec(&map.elements).unwrap().len());
+
+        let imported = GameMap::from_bytes(&bytes).unwrap();
+        assert_eq!(imported.elements.len(), map.elements.len());
+        assert_eq!((imported.width, imported.height), (map.width, map.height));
+        for (a, b) in map.elements.iter().zip(&imported.elements) {
+            assert_eq!(a.element.get_location_id(), b.element.get_location_id());
+            assert_eq!((a.position.x, a.position.y), (b.position.x, b.position.y));
+            assert_eq!(a.get_all_positions().len(), b.get_all_positions().len());
+        }
+        assert_eq!(imported.next_monster_id, map.next_monster_id);
+        assert_eq!(imported.next_opportunity_id, map.next_opportunity_id);
+
+        // 任务关联的运行时状态被重置，掉落表保留
+        let original = map.find_monster(monster_id).unwrap();
+        let monster = imported.find_monster(monster_id).unwrap();
+        assert!(!monster.is_being_fought && !monster.has_active_defense_task);
+        assert_eq!(monster.current_task_id, None);
+        assert!(!monster.drops.is_empty());
+        assert_eq!(monster.drops.len(), original.drops.len());
+
+        assert!(GameMap::from_bytes(&bytes[..bytes.len() / 2]).is_err());
+
+        // 尺寸越界或元素落在地图外的数据被拒绝
+        let width = map.width;
+        map.width = MAX_MAP_SIZE + 1;
+        assert!(GameMap::from_bytes(&map.to_bytes().unwrap()).is_err());
+        map.width = width;
+        let monster = map.elements.iter_mut().find(|e| e.element.as_monster().is_some()).unwrap();
+        monster.position = Position { x: width, y: 0 };
+        assert!(GameMap::from_bytes(&map.to_bytes().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_render_ascii_shows_highest_priority_symbol_per_cell() {
+        use crate::disciple::DiscipleType;
+
+        let mut map = GameMap::new();
+        map.width = 5;
+        map.height = 3;
+        map.sect_position = Position { x: 4, y: 2 };
+        let mut place = |element, x, y, size| {
+            map.elements.push(PositionedElement { element, position: Position { x, y }, size, positions: None });
+        };
+        place(MapElement::Terrain(Terrain {
+            terrain_type: TerrainType::Mountain,
+            name: "苍山".to_string(),
+            variant_type: None,
+        }), 0, 0, Some((2, 1)));
+        place(MapElement::Village(Village {
+            name: "桃源村".to_string(),
+            population: 100,
+            prosperity: 30,
+            task_templates: vec![],
+            peak_prosperity: 30,
+            recovering: false,
+        }), 1, 0, None);
+        place(MapElement::Monster(Monster::new(0, "血狼".to_string(), 5, vec![])), 2, 1, None);
+        place(MapElement::Monster(Monster::new(1, "影狐".to_string(), 5, vec![])), 3, 1, None);
+        // 越界元素忽略
+        place(MapElement::Monster(Monster::new(2, "游魂".to_string(), 5, vec![])), 9, 9, None);
+
+        let mut disciple = Disciple::new(1, "韩立".to_string(), DiscipleType::Inner, vec![]);
+        disciple.position = Position { x: 3, y: 1 };
+        assert_eq!(map.render_ascii(std::slice::from_ref(&disciple)), "^V...\n..M@.\n....H");
+
+        // 遭袭的村庄显示入侵标记，宗门被袭同理
+        if let MapElement::Monster(monster) = &mut map.elements[2].element {
+            monster.invaded_location_id = Some("village_桃源村".to_string());
+        }
+        map.sect_invasion = Some(SectInvasion { monster_id: 1, monster_name: "影狐".to_string(), turns_remaining: 2 });
+        assert_eq!(map.render_ascii(&[]), "^!...\n..MM.\n....!");
+    }
+
+    #[test]
+    fn test_rival_sect_grows_and_occupies_unguarded_realm() {
+        let mut map = GameMap::new();
+        let realm = |name: &str| MapElement::SecretRealm(SecretRealm {
+            name: name.to_string(),
+            realm_type: TalentType::Fire,
+            difficulty: 30,
+            task_templates: vec![],
+        });
+        // 近处的秘境有玩家弟子驻守，远处的无人看管
+        map.elements.push(PositionedElement { element: realm("火焰洞"), position: Position { x: 1, y: 0 }, size: None, positions: None });
+        map.elements.push(PositionedElement { element: realm("寒冰窟"), position: Position { x: 9, y: 9 }, size: None, positions: None });
+        map.rival_sects.push(RivalSect::new("血煞门".to_string(), Position { x: 0, y: 0 }));
+        let guarded: HashSet<String> = ["realm_火焰洞".to_string()].into_iter().collect();
+
+        let power_before = map.rival_sects[0].power();
+        let war_level = |map: &GameMap| map.rival_sects[0].war_task(0).get_enemy_level().unwrap();
+        let level_before = war_level(&map);
+        let mut occupations = Vec::new();
+        for _ in 0..20 {
+            occupations.extend(map.rival_sect_actions(&guarded));
+            if !occupations.is_empty() {
+                break;
+            }
+        }
+
+        assert_eq!(occupations.len(), 1);
+        assert_eq!(occupations[0].location_id, "realm_寒冰窟");
+        assert!(map.is_occupied_by_rival("realm_寒冰窟"));
+        assert!(!map.is_occupied_by_rival("realm_火焰洞"));
+        assert!(map.rival_sects[0].power() > power_before);
+        assert!(war_level(&map) > level_before);
+
+        // 被占据的秘境不再为玩家产生任务，宗门战任务随之出现
+        let tasks = map.get_available_tasks(RiskLevel::Deadly, &mut rand::thread_rng());
+        assert!(tasks.iter().all(|t| t.location_id.as_deref() != Some("realm_寒冰窟")));
+        let war = tasks.iter().find(|t| t.location_id.as_deref() == Some("rival_血煞门")).unwrap();
+
+        // 宗门战获胜夺回秘境
+        let outcome = map.settle_rival_war(war).unwrap();
+        assert_eq!(outcome.released_realm.as_deref(), Some("realm_寒冰窟"));
+        assert!(!map.is_occupied_by_rival("realm_寒冰窟"));
+    }
+
+    #[test]
+    fn test_patrol_stops_monster_from_crossing_into_village() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let mut map = GameMap::new();
+        map.sect_position = Position { x: 10, y: 10 };
+        map.elements.push(PositionedElement {
+            element: MapElement::Village(Village {
+                name: "桃源村".to_string(),
+                population: 100,
+                prosperity: 30,
+                task_templates: vec![],
+                peak_prosperity: 30,
+                recovering: false,
+            }),
+            position: Position { x: 10, y: 9 },
+            size: None,
+            positions: None,
+        });
+        // 锁定宗门的妖魔沿直线逼近，必经村庄
+        let mut wolf = Monster::new(0, "血狼".to_string(), 40, vec![]);
+        wolf.targeting_sect = true;
+        map.elements.push(PositionedElement {
+            element: MapElement::Monster(wolf),
+            position: Position { x: 10, y: 8 },
+            size: None,
+            positions: None,
+        });
+        let wolf_state = |map: &GameMap| {
+            map.elements
+                .iter()
+                .find_map(|p| match &p.element {
+                    MapElement::Monster(m) if m.id == 0 => Some(((p.position.x, p.position.y), m.invaded_location_id.clone())),
+                    _ => None,
+                })
+                .unwrap()
+        };
+
+        // 村庄旁有弟子巡逻：妖魔被拦在原地
+        let patrol = PatrolZone::new(7, Position { x: 10, y: 10 });
+        assert!(patrol.intercepts(Position { x: 10, y: 7 }, Position { x: 10, y: 8 }));
+        assert!(!patrol.intercepts(Position { x: 10, y: 8 }, Position { x: 10, y: 7 }));
+        let mut rng = StdRng::seed_from_u64(5);
+        let update = map.update(&HashSet::new(), std::slice::from_ref(&patrol), &mut rng);
+        assert_eq!(update.patrol_interceptions.len(), 1);
+        assert_eq!(update.patrol_interceptions[0].disciple_id, 7);
+        assert_eq!(update.patrol_interceptions[0].monster_name, "血狼");
+        assert_eq!(wolf_state(&map), ((10, 8), None));
+
+        // 撤去巡逻后妖魔长驱直入
+        let update = map.update(&HashSet::new(), &[], &mut rng);
+        assert!(update.patrol_interceptions.is_empty());
+        assert_eq!(wolf_state(&map), ((10, 9), Some("village_桃源村".to_string())));
+    }
+
+    #[test]
+    fn test_defense_task_locks_and_unlocks_monster_by_enemy_id() {
+        let mut map = GameMap::new();
+        map.elements.push(PositionedElement {
+            element: MapElement::Village(Village {
+                name: "青石村".to_string(),
+                population: 500,
+                prosperity: 100,
+                task_templates: vec![],
+                peak_prosperity: 100,
+                recovering: false,
+            }),
+            position: Position { x: 3, y: 3 },
+            size: None,
+            positions: None,
+        });
+        let id = map.allocate_monster_id();
+        let mut monster = Monster::new(id, "赤目妖狼".to_string(), 10, vec![]);
+        monster.invaded_location_id = Some("village_青石村".to_string());
+        map.elements.push(PositionedElement {
+            element: MapElement::Monster(monster),
+            position: Position { x: 3, y: 3 },
+            size: None,
+            positions: None,
+        });
+        let defense_lock = |map: &GameMap| map.find_monster(id).unwrap().has_active_defense_task;
+
+        // 守卫任务携带结构化的妖魔ID，敌人名称只是展示用的妖魔名
+        let tasks = map.generate_defense_tasks(0);
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].combat_enemy_id(), Some(id));
+        match &tasks[0].task_type {
+            TaskType::Combat(combat) => assert_eq!(combat.enemy_name, "赤目妖狼"),
+            other => panic!("守卫任务应为战斗任务: {:?}", other),
+        }
+
+        map.lock_monster_for_defense_task(tasks[0].combat_enemy_id().unwrap());
+        assert!(defense_lock(&map));
+        assert!(map.check_defense_tasks_validity(&tasks).is_empty());
+        map.unlock_monster_for_defense_task(id);
+        assert!(!defense_lock(&map));
+
+        // 妖魔离开入侵地点后守卫任务失效
+        if let Some(MapElement::Monster(monster)) = map.elements.iter_mut().map(|p| &mut p.element).find(|e| matches!(e, MapElement::Monster(_))) {
+            monster.invaded_location_id = None;
+        }
+        assert_eq!(map.check_defense_tasks_validity(&tasks), vec![tasks[0].id]);
+    }
+
+    #[test]
+    fn test_income_breakdown_zeroes_invaded_village() {
+        let mut map = GameMap::new();
+        let place = |element| PositionedElement { element, position: Position { x: 2, y: 2 }, size: None, positions: None };
+        map.elements.push(place(MapElement::Village(Village {
+            name: "桃源村".to_string(),
+            population: 100,
+            prosperity: 50,
+            task_templates: vec![],
+            peak_prosperity: 50,
+            recovering: false,
+        })));
+        map.elements.push(place(MapElement::Faction(Faction {
+            name: "青云门".to_string(),
+            power_level: 4,
+            relationship: 10,
+            friendly_task_templates: vec![],
+            hostile_task_templates: vec![],
+        })));
+
+        let breakdown = map.income_breakdown(0);
+        assert_eq!(breakdown, vec![("桃源村".to_string(), 5), ("青云门".to_string(), 8)]);
+        assert_eq!(breakdown.iter().map(|(_, income)| income).sum::<u32>(), map.calculate_income(0));
+
+        // 村庄被妖魔入侵后其收入条目归零，势力照常产出
+        let mut monster = Monster::new(map.allocate_monster_id(), "血狼".to_string(), 5, vec![]);
+        monster.invaded_location_id = Some("village_桃源村".to_string());
+        map.elements.push(place(MapElement::Monster(monster)));
+        let breakdown = map.income_breakdown(0);
+        assert_eq!(breakdown, vec![("桃源村".to_string(), 0), ("青云门".to_string(), 8)]);
+        assert_eq!(map.calculate_income(0), 8);
+    }
+}
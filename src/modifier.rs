@@ -28,13 +28,25 @@ pub enum ModifierTarget {
 
     // 收入相关
     Income,                  // 收入
+    StorageCapacity,         // 灵石仓储上限
 
     // 消耗相关
     EnergyConsumption,       // 精力消耗
     ConstitutionConsumption, // 体魄消耗
 
+    // 恢复相关
+    EnergyRecovery,          // 每回合精力恢复
+    ConstitutionRecovery,    // 每回合体魄恢复
+
     // 修炼相关
     CultivationSpeed,        // 修炼速度
+
+    // 炼丹相关
+    AlchemySuccessRate,      // 炼丹成功率
+
+    // 寿元与移动
+    Lifespan,                // 寿元上限
+    MovementRange,           // 每回合移动范围
 }
 
 /// Modifier应用方式
@@ -63,6 +75,9 @@ pub enum ModifierSource {
     Environment,  // 环境影响
     System,       // 系统效果
     Relationship, // 关系加成
+    Technique,    // 功法
+    Morale,       // 宗门氛围（每回合重算替换）
+    Specialization, // 专精路线
 }
 
 /// Modifier条件 - 用于判断modifier是否对某个弟子生效
@@ -316,10 +331,48 @@ impl Modifier {
     }
 }
 
+/// effective值计算中的一步
+#[derive(Debug, Clone, Serialize)]
+pub struct ModifierStep {
+    pub modifier_name: String,
+    pub source: ModifierSource,
+    pub application: ModifierApplication,
+    pub priority: i32,
+    pub before: f32,
+    pub after: f32,
+    pub applied: bool, // false表示被Override短路
+}
+
+impl ModifierStep {
+    fn new(modifier: &Modifier, before: f32, after: f32, applied: bool) -> Self {
+        Self {
+            modifier_name: modifier.name.clone(),
+            source: modifier.source.clone(),
+            application: modifier.application.clone(),
+            priority: modifier.priority,
+            before,
+            after,
+            applied,
+        }
+    }
+}
+
+/// effective值的计算明细
+#[derive(Debug, Clone, Serialize)]
+pub struct ModifierExplanation {
+    pub target: ModifierTarget,
+    pub native: f32,
+    pub effective: f32,
+    pub steps: Vec<ModifierStep>,       // 按应用顺序排列
+    pub overridden_by: Option<String>, // 生效的Override modifier名称
+}
+
 /// ModifierStack - 管理一个实体上的所有modifier
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct ModifierStack {
     modifiers: Vec<Modifier>,
+    #[serde(default)]
+    conditional_modifiers: Vec<ConditionalModifier>,
 }
 
 impl ModifierStack {
@@ -327,6 +380,7 @@ impl ModifierStack {
     pub fn new() -> Self {
         Self {
             modifiers: Vec::new(),
+            conditional_modifiers: Vec::new(),
         }
     }
 
@@ -337,6 +391,16 @@ impl ModifierStack {
         self.modifiers.sort_by(|a, b| b.priority.cmp(&a.priority));
     }
 
+    /// 添加条件modifier（由持有者在查询时判定是否生效）
+    pub fn add_conditional_modifier(&mut self, modifier: ConditionalModifier) {
+        self.conditional_modifiers.push(modifier);
+    }
+
+    /// 获取所有条件modifier
+    pub fn get_conditional_modifiers(&self) -> &[ConditionalModifier] {
+        &self.conditional_modifiers
+    }
+
     /// 移除指定ID的modifier
     pub fn remove_modifier(&mut self, id: &str) -> bool {
         let len_before = self.modifiers.len();
@@ -347,6 +411,7 @@ impl ModifierStack {
     /// 移除指定来源的所有modifier
     pub fn remove_modifiers_by_source(&mut self, source: &ModifierSource) {
         self.modifiers.retain(|m| &m.source != source);
+        self.conditional_modifiers.retain(|cm| &cm.modifier.source != source);
     }
 
     /// 获取所有指定目标的modifier
@@ -411,6 +476,62 @@ impl ModifierStack {
         value
     }
 
+    /// 逐步解释effective值的计算过程（顺序与短路逻辑同calculate_effective_with_extras）
+    pub fn explain(
+        &self,
+        target: &ModifierTarget,
+        native: f32,
+        extra_modifiers: &[&Modifier],
+    ) -> ModifierExplanation {
+        let mut all_modifiers: Vec<&Modifier> = self.get_modifiers_for_target(target);
+        all_modifiers.extend(
+            extra_modifiers
+                .iter()
+                .filter(|m| &m.target == target)
+                .copied()
+        );
+
+        let override_mod = all_modifiers
+            .iter()
+            .find(|m| matches!(m.application, ModifierApplication::Override(_)))
+            .copied();
+
+        let mut steps = Vec::new();
+        let mut value = native;
+
+        if let Some(override_mod) = override_mod {
+            value = override_mod.apply(native);
+            steps.push(ModifierStep::new(override_mod, native, value, true));
+        }
+
+        // Additive先于Multiplicative；被Override短路的modifier记为未生效
+        let ordered = all_modifiers
+            .iter()
+            .filter(|m| matches!(m.application, ModifierApplication::Additive(_)))
+            .chain(all_modifiers.iter().filter(|m| matches!(m.application, ModifierApplication::Multiplicative(_))))
+            .chain(all_modifiers.iter().filter(|m| {
+                matches!(m.application, ModifierApplication::Override(_))
+                    && override_mod.is_some_and(|o| !std::ptr::eq(**m, o))
+            }));
+        for modifier in ordered {
+            if override_mod.is_some() {
+                steps.push(ModifierStep::new(modifier, value, value, false));
+            } else {
+                let after = modifier.apply(value);
+                steps.push(ModifierStep::new(modifier, value, after, true));
+                value = after;
+            }
+        }
+
+        ModifierExplanation {
+            target: target.clone(),
+            native,
+            effective: value,
+            steps,
+            overridden_by: override_mod.map(|m| m.name.clone()),
+        }
+    }
+
     /// 更新所有临时modifier的持续时间
     /// 返回过期的modifier数量
     pub fn tick(&mut self) -> usize {
@@ -439,6 +560,7 @@ impl ModifierStack {
     /// 清除所有modifier
     pub fn clear(&mut self) {
         self.modifiers.clear();
+        self.conditional_modifiers.clear();
     }
 
     /// 获取modifier数量
@@ -589,4 +711,65 @@ mod tests {
         stack.remove_modifiers_by_source(&ModifierSource::Buff);
         assert_eq!(stack.len(), 1);
     }
+
+    #[test]
+    fn test_explain_orders_steps_and_short_circuits_on_override() {
+        let mut stack = ModifierStack::new();
+        stack.add_modifier(Modifier::new(
+            "心魔",
+            ModifierTarget::DaoHeart,
+            ModifierApplication::Multiplicative(0.5),
+            ModifierSource::Debuff,
+        ));
+        stack.add_modifier(Modifier::new(
+            "清心诀",
+            ModifierTarget::DaoHeart,
+            ModifierApplication::Additive(10.0),
+            ModifierSource::Technique,
+        ));
+        let sect_buff = Modifier::new(
+            "宗门氛围",
+            ModifierTarget::DaoHeart,
+            ModifierApplication::Additive(5.0),
+            ModifierSource::Environment,
+        );
+
+        // 无Override：Additive（个人、额外）先于Multiplicative
+        let explanation = stack.explain(&ModifierTarget::DaoHeart, 50.0, &[&sect_buff]);
+        let names: Vec<&str> = explanation.steps.iter().map(|s| s.modifier_name.as_str()).collect();
+        assert_eq!(names, vec!["清心诀", "宗门氛围", "心魔"]);
+        assert_eq!((explanation.steps[0].before, explanation.steps[0].after), (50.0, 60.0));
+        assert_eq!((explanation.steps[1].before, explanation.steps[1].after), (60.0, 65.0));
+        assert_eq!((explanation.steps[2].before, explanation.steps[2].after), (65.0, 97.5));
+        assert_eq!(explanation.effective, 97.5);
+        assert!(explanation.overridden_by.is_none());
+        assert_eq!(
+            explanation.effective,
+            stack.calculate_effective_with_extras(&ModifierTarget::DaoHeart, 50.0, &[&sect_buff])
+        );
+
+        // 高优先级Override先应用，其余全部短路
+        stack.add_modifier(
+            Modifier::new("道心锁定", ModifierTarget::DaoHeart, ModifierApplication::Override(30.0), ModifierSource::System)
+                .with_priority(10),
+        );
+        stack.add_modifier(Modifier::new(
+            "天道压制",
+            ModifierTarget::DaoHeart,
+            ModifierApplication::Override(1.0),
+            ModifierSource::System,
+        ));
+        let explanation = stack.explain(&ModifierTarget::DaoHeart, 50.0, &[&sect_buff]);
+        assert_eq!(explanation.overridden_by.as_deref(), Some("道心锁定"));
+        assert_eq!(explanation.effective, 30.0);
+        assert_eq!(
+            explanation.effective,
+            stack.calculate_effective_with_extras(&ModifierTarget::DaoHeart, 50.0, &[&sect_buff])
+        );
+        let names: Vec<&str> = explanation.steps.iter().map(|s| s.modifier_name.as_str()).collect();
+        assert_eq!(names, vec!["道心锁定", "清心诀", "宗门氛围", "心魔", "天道压制"]);
+        assert!(explanation.steps[0].applied);
+        assert_eq!((explanation.steps[0].before, explanation.steps[0].after), (50.0, 30.0));
+        assert!(explanation.steps[1..].iter().all(|s| !s.applied && s.before == 30.0 && s.after == 30.0));
+    }
 }
@@ -1,8 +1,10 @@
 use crate::map::HerbQuality;
+use crate::modifier::{Modifier, ModifierApplication, ModifierSource, ModifierTarget};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 /// 草药仓库中的草药条目
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HerbEntry {
     pub name: String,
     pub quality: HerbQuality,
@@ -10,7 +12,10 @@ pub struct HerbEntry {
 }
 
 /// 草药仓库
-#[derive(Debug, Clone)]
+///
+/// 元组键无法直接作为 JSON 对象键，序列化时展开为 `HerbEntry` 列表
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "Vec<HerbEntry>", into = "Vec<HerbEntry>")]
 pub struct HerbInventory {
     /// 按 (名称, 品质) 存储草药
     herbs: HashMap<(String, HerbQuality), u32>,
@@ -75,10 +80,63 @@ impl HerbInventory {
     }
 }
 
+impl From<Vec<HerbEntry>> for HerbInventory {
+    fn from(entries: Vec<HerbEntry>) -> Self {
+        let mut inventory = Self::new();
+        for entry in entries {
+            inventory.add(&entry.name, entry.quality, entry.count);
+        }
+        inventory
+    }
+}
+
+impl From<HerbInventory> for Vec<HerbEntry> {
+    fn from(inventory: HerbInventory) -> Self {
+        inventory.get_all()
+    }
+}
+
+/// 配方等级：基础配方无需建筑，高级、顶级配方需建成相应建筑才能炼制
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecipeTier {
+    Basic,    // 基础
+    Advanced, // 高级：需炼丹房或灵药园
+    Supreme,  // 顶级：需炼器坊
+}
+
+impl RecipeTier {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Basic => "基础",
+            Self::Advanced => "高级",
+            Self::Supreme => "顶级",
+        }
+    }
+
+    /// 解锁所需的建筑（建成其一即可）
+    pub fn required_buildings(&self) -> &'static [&'static str] {
+        match self {
+            Self::Basic => &[],
+            Self::Advanced => &["alchemy_room", "spirit_garden"],
+            Self::Supreme => &[crate::equipment::EQUIPMENT_BUILDING_ID],
+        }
+    }
+
+    /// 解锁条件描述
+    pub fn unlock_condition(&self) -> Option<&'static str> {
+        match self {
+            Self::Basic => None,
+            Self::Advanced => Some("需建成炼丹房或灵药园"),
+            Self::Supreme => Some("需建成炼器坊"),
+        }
+    }
+}
+
 /// 丹药配方
 #[derive(Debug, Clone)]
 pub struct PillRecipe {
     pub pill_type: PillType,
+    pub tier: RecipeTier,                    // 配方等级
     pub required_herb_quality: HerbQuality,  // 需要的草药品质
     pub required_herb_count: u32,            // 需要的草药数量
     pub resource_cost: u32,                  // 额外资源消耗
@@ -93,6 +151,7 @@ impl PillRecipe {
             // 回气丹：2个普通草药
             PillRecipe {
                 pill_type: PillType::QiRecovery,
+                tier: RecipeTier::Basic,
                 required_herb_quality: HerbQuality::Common,
                 required_herb_count: 2,
                 resource_cost: 20,
@@ -102,6 +161,7 @@ impl PillRecipe {
             // 健体丹：2个普通草药
             PillRecipe {
                 pill_type: PillType::BodyStrength,
+                tier: RecipeTier::Basic,
                 required_herb_quality: HerbQuality::Common,
                 required_herb_count: 2,
                 resource_cost: 20,
@@ -111,6 +171,7 @@ impl PillRecipe {
             // 元气丹：2个良品草药
             PillRecipe {
                 pill_type: PillType::VitalityElixir,
+                tier: RecipeTier::Advanced,
                 required_herb_quality: HerbQuality::Uncommon,
                 required_herb_count: 2,
                 resource_cost: 50,
@@ -120,12 +181,53 @@ impl PillRecipe {
             // 修炼丹：1个稀有草药 + 2个良品草药
             PillRecipe {
                 pill_type: PillType::CultivationBoost,
+                tier: RecipeTier::Advanced,
                 required_herb_quality: HerbQuality::Rare,
                 required_herb_count: 1,
                 resource_cost: 100,
                 success_rate: 0.7,
                 output_count: 1,
             },
+            // 破障丹：2个稀有草药
+            PillRecipe {
+                pill_type: PillType::BottleneckBreaker,
+                tier: RecipeTier::Supreme,
+                required_herb_quality: HerbQuality::Rare,
+                required_herb_count: 2,
+                resource_cost: 150,
+                success_rate: 0.6,
+                output_count: 1,
+            },
+            // 神行丹：3个普通草药
+            PillRecipe {
+                pill_type: PillType::SwiftnessPill,
+                tier: RecipeTier::Basic,
+                required_herb_quality: HerbQuality::Common,
+                required_herb_count: 3,
+                resource_cost: 30,
+                success_rate: 0.85,
+                output_count: 1,
+            },
+            // 凝神丹：2个良品草药
+            PillRecipe {
+                pill_type: PillType::FocusPill,
+                tier: RecipeTier::Advanced,
+                required_herb_quality: HerbQuality::Uncommon,
+                required_herb_count: 2,
+                resource_cost: 60,
+                success_rate: 0.8,
+                output_count: 1,
+            },
+            // 延寿丹：2个珍品草药
+            PillRecipe {
+                pill_type: PillType::LongevityPill,
+                tier: RecipeTier::Supreme,
+                required_herb_quality: HerbQuality::Epic,
+                required_herb_count: 2,
+                resource_cost: 200,
+                success_rate: 0.5,
+                output_count: 1,
+            },
         ]
     }
 
@@ -133,15 +235,83 @@ impl PillRecipe {
     pub fn for_pill(pill_type: PillType) -> Option<PillRecipe> {
         Self::all_recipes().into_iter().find(|r| r.pill_type == pill_type)
     }
+
+    /// 成品为中品、上品的概率：草药品质越高、炼丹天赋越高越容易炼出高品级
+    pub fn grade_chances(herb_quality: HerbQuality, alchemy_talent: u32) -> (f64, f64) {
+        let quality_rank = herb_quality as u32 as f64;
+        let talent = alchemy_talent as f64;
+        let high = (0.08 * quality_rank + 0.04 * talent).min(MAX_HIGH_GRADE_CHANCE);
+        let middle = (0.2 + 0.1 * quality_rank + 0.05 * talent).min(1.0 - high);
+        (middle, high)
+    }
+
+    /// 决定成品品级
+    pub fn roll_output_grade<R: rand::Rng>(&self, herb_quality: HerbQuality, alchemy_talent: u32, rng: &mut R) -> PillGrade {
+        let (middle, high) = Self::grade_chances(herb_quality, alchemy_talent);
+        let roll: f64 = rng.gen();
+        if roll < high {
+            PillGrade::High
+        } else if roll < high + middle {
+            PillGrade::Middle
+        } else {
+            PillGrade::Low
+        }
+    }
+}
+
+/// 炼出上品丹药的最高概率
+const MAX_HIGH_GRADE_CHANCE: f64 = 0.7;
+
+/// 丹药品级
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, Default)]
+pub enum PillGrade {
+    #[default]
+    Low,    // 下品
+    Middle, // 中品
+    High,   // 上品
+}
+
+impl PillGrade {
+    pub const ALL: [PillGrade; 3] = [PillGrade::Low, PillGrade::Middle, PillGrade::High];
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "Low" | "下品" => Some(PillGrade::Low),
+            "Middle" | "中品" => Some(PillGrade::Middle),
+            "High" | "上品" => Some(PillGrade::High),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PillGrade::Low => "下品",
+            PillGrade::Middle => "中品",
+            PillGrade::High => "上品",
+        }
+    }
+
+    /// 药效倍率
+    pub fn effect_multiplier(&self) -> f32 {
+        match self {
+            PillGrade::Low => 1.0,
+            PillGrade::Middle => 1.5,
+            PillGrade::High => 2.0,
+        }
+    }
 }
 
 /// 丹药类型
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum PillType {
     QiRecovery,         // 回气丹 - 恢复精力
     BodyStrength,       // 健体丹 - 恢复体魄
     VitalityElixir,     // 元气丹 - 同时恢复精力和体魄
     CultivationBoost,   // 修炼丹 - 增加修为进度（未来扩展）
+    BottleneckBreaker,  // 破障丹 - 破除修为瓶颈
+    LongevityPill,      // 延寿丹 - 延长寿元（不超过当前修为的寿元上限）
+    FocusPill,          // 凝神丹 - 临时提升道心
+    SwiftnessPill,      // 神行丹 - 临时提升移动范围
 }
 
 impl PillType {
@@ -152,6 +322,10 @@ impl PillType {
             "BodyStrength" => Some(PillType::BodyStrength),
             "VitalityElixir" => Some(PillType::VitalityElixir),
             "CultivationBoost" => Some(PillType::CultivationBoost),
+            "BottleneckBreaker" => Some(PillType::BottleneckBreaker),
+            "LongevityPill" => Some(PillType::LongevityPill),
+            "FocusPill" => Some(PillType::FocusPill),
+            "SwiftnessPill" => Some(PillType::SwiftnessPill),
             _ => None,
         }
     }
@@ -163,6 +337,10 @@ impl PillType {
             PillType::BodyStrength => "BodyStrength",
             PillType::VitalityElixir => "VitalityElixir",
             PillType::CultivationBoost => "CultivationBoost",
+            PillType::BottleneckBreaker => "BottleneckBreaker",
+            PillType::LongevityPill => "LongevityPill",
+            PillType::FocusPill => "FocusPill",
+            PillType::SwiftnessPill => "SwiftnessPill",
         }
     }
 
@@ -173,6 +351,10 @@ impl PillType {
             PillType::BodyStrength => "健体丹",
             PillType::VitalityElixir => "元气丹",
             PillType::CultivationBoost => "修炼丹",
+            PillType::BottleneckBreaker => "破障丹",
+            PillType::LongevityPill => "延寿丹",
+            PillType::FocusPill => "凝神丹",
+            PillType::SwiftnessPill => "神行丹",
         }
     }
 
@@ -183,6 +365,10 @@ impl PillType {
             PillType::BodyStrength => "恢复30点体魄",
             PillType::VitalityElixir => "恢复20点精力和20点体魄",
             PillType::CultivationBoost => "增加10点修炼进度",
+            PillType::BottleneckBreaker => "破除修为瓶颈，数回合内提升渡劫成功率",
+            PillType::LongevityPill => "延长20年寿元（不超过当前修为的寿元上限）",
+            PillType::FocusPill => "数回合内提升道心",
+            PillType::SwiftnessPill => "数回合内提升移动范围",
         }
     }
 
@@ -193,25 +379,100 @@ impl PillType {
                 energy_restore: 30,
                 constitution_restore: 0,
                 cultivation_boost: 0,
+                side_effect: None,
+                side_effect_chance: 0.0,
+                breaks_bottleneck: false,
+                lifespan_extension: 0,
+                buff: None,
             },
             PillType::BodyStrength => PillEffect {
                 energy_restore: 0,
                 constitution_restore: 30,
                 cultivation_boost: 0,
+                side_effect: None,
+                side_effect_chance: 0.0,
+                breaks_bottleneck: false,
+                lifespan_extension: 0,
+                buff: None,
             },
             PillType::VitalityElixir => PillEffect {
                 energy_restore: 20,
                 constitution_restore: 20,
                 cultivation_boost: 0,
+                side_effect: Some(PillSideEffect::DaoHeartDisturbance),
+                side_effect_chance: 0.15,
+                breaks_bottleneck: false,
+                lifespan_extension: 0,
+                buff: None,
             },
             PillType::CultivationBoost => PillEffect {
                 energy_restore: 0,
                 constitution_restore: 0,
                 cultivation_boost: 10,
+                side_effect: Some(PillSideEffect::CultivationSlowdown),
+                side_effect_chance: 0.3,
+                breaks_bottleneck: false,
+                lifespan_extension: 0,
+                buff: None,
+            },
+            PillType::BottleneckBreaker => PillEffect {
+                energy_restore: 0,
+                constitution_restore: 0,
+                cultivation_boost: 0,
+                side_effect: Some(PillSideEffect::DaoHeartDisturbance),
+                side_effect_chance: 0.2,
+                breaks_bottleneck: true,
+                lifespan_extension: 0,
+                buff: Some(PillBuff::TribulationInsight),
+            },
+            PillType::LongevityPill => PillEffect {
+                energy_restore: 0,
+                constitution_restore: 0,
+                cultivation_boost: 0,
+                side_effect: None,
+                side_effect_chance: 0.0,
+                breaks_bottleneck: false,
+                lifespan_extension: 20,
+                buff: None,
+            },
+            PillType::FocusPill => PillEffect {
+                energy_restore: 0,
+                constitution_restore: 0,
+                cultivation_boost: 0,
+                side_effect: None,
+                side_effect_chance: 0.0,
+                breaks_bottleneck: false,
+                lifespan_extension: 0,
+                buff: Some(PillBuff::Focus),
+            },
+            PillType::SwiftnessPill => PillEffect {
+                energy_restore: 0,
+                constitution_restore: 0,
+                cultivation_boost: 0,
+                side_effect: None,
+                side_effect_chance: 0.0,
+                breaks_bottleneck: false,
+                lifespan_extension: 0,
+                buff: Some(PillBuff::Swiftness),
             },
         }
     }
 
+    /// 获取指定品级丹药的效果：药效按品级倍率提升，丹毒随之减轻
+    pub fn effects_of_grade(&self, grade: PillGrade) -> PillEffect {
+        let base = self.effects();
+        let multiplier = grade.effect_multiplier();
+        let scale = |value: u32| (value as f32 * multiplier).round() as u32;
+        PillEffect {
+            energy_restore: scale(base.energy_restore),
+            constitution_restore: scale(base.constitution_restore),
+            cultivation_boost: scale(base.cultivation_boost),
+            lifespan_extension: scale(base.lifespan_extension),
+            side_effect_chance: base.side_effect_chance / multiplier as f64,
+            ..base
+        }
+    }
+
     /// 获取丹药炼制成本（资源）
     pub fn crafting_cost(&self) -> u32 {
         match self {
@@ -219,49 +480,178 @@ impl PillType {
             PillType::BodyStrength => 50,
             PillType::VitalityElixir => 100,
             PillType::CultivationBoost => 200,
+            PillType::BottleneckBreaker => 300,
+            PillType::LongevityPill => 400,
+            PillType::FocusPill => 150,
+            PillType::SwiftnessPill => 80,
         }
     }
 }
 
+/// 每回合服药上限，超过后不再增加修为且可能药毒入体
+pub const MAX_PILLS_PER_TURN: u32 = 3;
+/// 超过服药上限后药毒入体（轻伤）的概率
+pub const PILL_TOXICITY_CHANCE: f64 = 0.3;
+/// 炼丹成功率上限
+pub const MAX_ALCHEMY_SUCCESS_RATE: f32 = 0.98;
+/// 炼丹天赋超过该等级后可能超额产出
+pub const ALCHEMY_SURPLUS_TALENT_LEVEL: u32 = 4;
+
 /// 丹药效果
 #[derive(Debug, Clone, Copy)]
 pub struct PillEffect {
     pub energy_restore: u32,        // 恢复精力
     pub constitution_restore: u32,  // 恢复体魄
     pub cultivation_boost: u32,     // 增加修为进度
+    pub side_effect: Option<PillSideEffect>, // 可能的副作用
+    pub side_effect_chance: f64,    // 副作用触发概率（超过服药上限时必定触发）
+    pub breaks_bottleneck: bool,    // 是否破除修为瓶颈
+    pub lifespan_extension: u32,    // 延长寿元（年）
+    pub buff: Option<PillBuff>,     // 服用后的临时增益
+}
+
+/// 丹药增益的持续回合数
+pub const PILL_BUFF_DURATION: u32 = 3;
+
+/// 丹药的临时增益（按品级倍率增强，持续 PILL_BUFF_DURATION 回合）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PillBuff {
+    Focus,              // 凝神：道心提升
+    TribulationInsight, // 破障：渡劫成功率提升
+    Swiftness,          // 神行：移动范围提升
+}
+
+impl PillBuff {
+    pub fn name(&self) -> &str {
+        match self {
+            PillBuff::Focus => "凝神静气",
+            PillBuff::TribulationInsight => "破障明悟",
+            PillBuff::Swiftness => "身轻如燕",
+        }
+    }
+
+    /// 生成增益对应的临时modifier
+    pub fn modifier(&self, grade: PillGrade) -> Modifier {
+        let multiplier = grade.effect_multiplier();
+        let (target, value) = match self {
+            PillBuff::Focus => (ModifierTarget::DaoHeart, 10.0 * multiplier),
+            PillBuff::TribulationInsight => (ModifierTarget::TribulationSuccessRate, 0.1 * multiplier),
+            PillBuff::Swiftness => (ModifierTarget::MovementRange, multiplier.floor()),
+        };
+        Modifier::new_temporary(
+            format!("丹药增益：{}", self.name()),
+            target,
+            ModifierApplication::Additive(value),
+            ModifierSource::Pill,
+            PILL_BUFF_DURATION,
+        )
+    }
+}
+
+/// 丹药副作用
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PillSideEffect {
+    CultivationSlowdown, // 丹毒淤积 - 短期降低修炼速度
+    DaoHeartDisturbance, // 心浮气躁 - 短期降低道心
+}
+
+impl PillSideEffect {
+    /// 获取副作用名称
+    pub fn name(&self) -> &str {
+        match self {
+            PillSideEffect::CultivationSlowdown => "丹毒淤积",
+            PillSideEffect::DaoHeartDisturbance => "心浮气躁",
+        }
+    }
+
+    /// 生成副作用对应的临时Debuff
+    pub fn modifier(&self) -> Modifier {
+        let (target, application, duration) = match self {
+            PillSideEffect::CultivationSlowdown => (
+                ModifierTarget::CultivationSpeed,
+                ModifierApplication::Multiplicative(-0.2),
+                3,
+            ),
+            PillSideEffect::DaoHeartDisturbance => (
+                ModifierTarget::DaoHeart,
+                ModifierApplication::Additive(-10.0),
+                2,
+            ),
+        };
+        Modifier::new_temporary(
+            format!("丹药副作用：{}", self.name()),
+            target,
+            application,
+            ModifierSource::Pill,
+            duration,
+        )
+    }
+}
+
+/// 服药结果
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PillOutcome {
+    pub overdosed: bool,                     // 是否超过本回合服药上限
+    pub side_effect: Option<PillSideEffect>, // 触发的副作用
+    pub poisoned: bool,                      // 是否药毒入体（轻伤）
+    pub bottleneck_broken: bool,             // 是否破除了修为瓶颈
+    pub buff: Option<PillBuff>,              // 获得的临时增益
+}
+
+/// 丹药库存中的丹药条目
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PillEntry {
+    pub pill_type: PillType,
+    #[serde(default)]
+    pub grade: PillGrade,
+    pub count: u32,
+}
+
+/// 丹药库存的存档格式（兼容按丹药类型存储的旧存档，旧存档中的丹药视为下品）
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PillInventoryRepr {
+    Entries(Vec<PillEntry>),
+    Legacy { pills: HashMap<PillType, u32> },
 }
 
 /// 丹药库存
-#[derive(Debug, Clone)]
+///
+/// 按 (丹药类型, 品级) 存储，序列化时展开为 `PillEntry` 列表
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "PillInventoryRepr", into = "Vec<PillEntry>")]
 pub struct PillInventory {
-    pub pills: std::collections::HashMap<PillType, u32>,
+    pills: HashMap<(PillType, PillGrade), u32>,
 }
 
 impl PillInventory {
     pub fn new() -> Self {
-        let mut pills = std::collections::HashMap::new();
-        // 初始库存
-        pills.insert(PillType::QiRecovery, 10);
-        pills.insert(PillType::BodyStrength, 10);
-        pills.insert(PillType::VitalityElixir, 5);
-        pills.insert(PillType::CultivationBoost, 0);
+        let mut inventory = Self { pills: HashMap::new() };
+        // 初始库存（均为下品）
+        inventory.add(PillType::QiRecovery, PillGrade::Low, 10);
+        inventory.add(PillType::BodyStrength, PillGrade::Low, 10);
+        inventory.add(PillType::VitalityElixir, PillGrade::Low, 5);
+        inventory
+    }
 
-        Self { pills }
+    /// 获取某种品级丹药的数量
+    pub fn get_count(&self, pill_type: PillType, grade: PillGrade) -> u32 {
+        *self.pills.get(&(pill_type, grade)).unwrap_or(&0)
     }
 
-    /// 获取某种丹药的数量
-    pub fn get_count(&self, pill_type: PillType) -> u32 {
-        *self.pills.get(&pill_type).unwrap_or(&0)
+    /// 获取某种丹药各品级的总数量
+    pub fn total_count(&self, pill_type: PillType) -> u32 {
+        PillGrade::ALL.iter().map(|g| self.get_count(pill_type, *g)).sum()
     }
 
     /// 添加丹药
-    pub fn add(&mut self, pill_type: PillType, count: u32) {
-        *self.pills.entry(pill_type).or_insert(0) += count;
+    pub fn add(&mut self, pill_type: PillType, grade: PillGrade, count: u32) {
+        *self.pills.entry((pill_type, grade)).or_insert(0) += count;
     }
 
     /// 使用丹药（返回是否成功）
-    pub fn consume(&mut self, pill_type: PillType) -> bool {
-        if let Some(count) = self.pills.get_mut(&pill_type) {
+    pub fn consume(&mut self, pill_type: PillType, grade: PillGrade) -> bool {
+        if let Some(count) = self.pills.get_mut(&(pill_type, grade)) {
             if *count > 0 {
                 *count -= 1;
                 return true;
@@ -270,15 +660,94 @@ impl PillInventory {
         false
     }
 
-    /// 炼制丹药（消耗资源）
+    /// 炼制丹药（消耗资源，产出下品）
     pub fn craft(&mut self, pill_type: PillType, resources: &mut u32) -> bool {
         let cost = pill_type.crafting_cost();
         if *resources >= cost {
             *resources -= cost;
-            self.add(pill_type, 1);
+            self.add(pill_type, PillGrade::Low, 1);
             true
         } else {
             false
         }
     }
 }
+
+impl From<PillInventoryRepr> for PillInventory {
+    fn from(repr: PillInventoryRepr) -> Self {
+        let mut inventory = Self { pills: HashMap::new() };
+        match repr {
+            PillInventoryRepr::Entries(entries) => {
+                for entry in entries {
+                    inventory.add(entry.pill_type, entry.grade, entry.count);
+                }
+            }
+            PillInventoryRepr::Legacy { pills } => {
+                for (pill_type, count) in pills {
+                    inventory.add(pill_type, PillGrade::Low, count);
+                }
+            }
+        }
+        inventory
+    }
+}
+
+impl From<PillInventory> for Vec<PillEntry> {
+    fn from(inventory: PillInventory) -> Self {
+        inventory
+            .pills
+            .into_iter()
+            .filter(|(_, count)| *count > 0)
+            .map(|((pill_type, grade), count)| PillEntry { pill_type, grade, count })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disciple::{Disciple, DiscipleType};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_higher_grade_pill_restores_more() {
+        let take = |grade| {
+            let mut disciple = Disciple::new(1, "韩立".to_string(), DiscipleType::Inner, vec![]);
+            disciple.energy = 0;
+            disciple.take_pill(PillType::QiRecovery, grade, &mut StdRng::seed_from_u64(1));
+            disciple.energy
+        };
+        assert_eq!(take(PillGrade::Low), 30);
+        assert_eq!(take(PillGrade::Middle), 45);
+        assert_eq!(take(PillGrade::High), 60);
+
+        let low = PillType::CultivationBoost.effects_of_grade(PillGrade::Low);
+        let high = PillType::CultivationBoost.effects_of_grade(PillGrade::High);
+        assert!(high.cultivation_boost > low.cultivation_boost);
+        assert!(high.side_effect_chance < low.side_effect_chance);
+
+        // 库存按品级分别计数
+        let mut inventory = PillInventory::new();
+        inventory.add(PillType::QiRecovery, PillGrade::High, 1);
+        assert!(inventory.consume(PillType::QiRecovery, PillGrade::High));
+        assert!(!inventory.consume(PillType::QiRecovery, PillGrade::High));
+        assert_eq!(inventory.total_count(PillType::QiRecovery), 10);
+    }
+
+    #[test]
+    fn test_better_herbs_refine_higher_grade_pills() {
+        let recipe = PillRecipe::for_pill(PillType::QiRecovery).unwrap();
+        let high_grades = |quality| {
+            let mut rng = StdRng::seed_from_u64(9);
+            (0..500)
+                .filter(|_| recipe.roll_output_grade(quality, 0, &mut rng) == PillGrade::High)
+                .count()
+        };
+        assert_eq!(high_grades(HerbQuality::Common), 0);
+        assert!(high_grades(HerbQuality::Epic) > high_grades(HerbQuality::Uncommon));
+
+        let old_save: PillInventory = serde_json::from_str(r#"{"pills":{"QiRecovery":3}}"#).unwrap();
+        assert_eq!(old_save.get_count(PillType::QiRecovery, PillGrade::Low), 3);
+    }
+}
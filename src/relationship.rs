@@ -1,4 +1,8 @@
 use serde::{Deserialize, Serialize};
+use crate::modifier::{
+    ConditionalModifier, Modifier, ModifierApplication, ModifierCondition, ModifierSource,
+    ModifierTarget,
+};
 use crate::task::TaskType;
 
 /// 关系维度类型
@@ -9,6 +13,7 @@ pub enum RelationDimension {
     Comrade,       // 战友关系
     Understanding, // 认知程度
     FatefulBond,   // 机缘关系
+    Enmity,        // 仇恨敌意
 }
 
 impl RelationDimension {
@@ -20,9 +25,15 @@ impl RelationDimension {
             RelationDimension::Comrade => "战友",
             RelationDimension::Understanding => "认知",
             RelationDimension::FatefulBond => "机缘",
+            RelationDimension::Enmity => "仇恨",
         }
     }
 
+    /// 是否为敌对维度
+    pub fn is_hostile(&self) -> bool {
+        matches!(self, RelationDimension::Enmity)
+    }
+
     /// 获取所有维度
     pub fn all() -> Vec<RelationDimension> {
         vec![
@@ -31,6 +42,7 @@ impl RelationDimension {
             RelationDimension::Comrade,
             RelationDimension::Understanding,
             RelationDimension::FatefulBond,
+            RelationDimension::Enmity,
         ]
     }
 }
@@ -92,6 +104,8 @@ pub struct RelationScores {
     pub comrade: u32,       // 战友关系 0-100
     pub understanding: u32, // 认知程度 0-100
     pub fateful_bond: u32,  // 机缘关系 0-100
+    #[serde(default)]
+    pub enmity: u32,        // 仇恨敌意 0-100
 }
 
 impl RelationScores {
@@ -108,6 +122,7 @@ impl RelationScores {
             RelationDimension::Comrade => self.comrade,
             RelationDimension::Understanding => self.understanding,
             RelationDimension::FatefulBond => self.fateful_bond,
+            RelationDimension::Enmity => self.enmity,
         }
     }
 
@@ -120,6 +135,7 @@ impl RelationScores {
             RelationDimension::Comrade => self.comrade = value,
             RelationDimension::Understanding => self.understanding = value,
             RelationDimension::FatefulBond => self.fateful_bond = value,
+            RelationDimension::Enmity => self.enmity = value,
         }
     }
 
@@ -158,10 +174,11 @@ impl RelationScores {
             .collect()
     }
 
-    /// 获取最高的关系等级
+    /// 获取最高的关系等级（不含敌对维度）
     pub fn highest_level(&self) -> RelationLevel {
         RelationDimension::all()
             .into_iter()
+            .filter(|dim| !dim.is_hostile())
             .map(|dim| self.get_level(dim))
             .max()
             .unwrap_or(RelationLevel::Stranger)
@@ -186,9 +203,18 @@ pub struct RelationGrowth {
     pub comrade: i32,
     pub understanding: i32,
     pub fateful_bond: i32,
+    pub enmity: i32,
 }
 
 impl RelationGrowth {
+    /// 争夺中落败（稀缺任务被抢、道侣争夺）带来的敌意
+    pub fn rivalry() -> Self {
+        RelationGrowth {
+            enmity: 15,
+            ..Default::default()
+        }
+    }
+
     /// 根据任务类型计算关系增长
     pub fn from_task_type(task_type: &TaskType) -> Self {
         match task_type {
@@ -259,11 +285,44 @@ impl RelationGrowth {
                 level_ups.push((RelationDimension::FatefulBond, level));
             }
         }
+        if self.enmity != 0 {
+            if let (_, Some(level)) = scores.add(RelationDimension::Enmity, self.enmity) {
+                level_ups.push((RelationDimension::Enmity, level));
+            }
+        }
 
         level_ups
     }
 }
 
+/// 多少年未互动后关系开始衰减
+pub const RELATION_DECAY_GRACE_YEARS: u32 = 3;
+/// 普通维度每年衰减的分数
+const RELATION_DECAY_PER_YEAR: i32 = 2;
+/// 道侣情感跌破该值时貌合神离
+pub const DAO_COMPANION_ESTRANGED_THRESHOLD: u32 = 60;
+/// 敌意达到该等级后视为仇敌，共事时互相掣肘
+pub const HOSTILE_ENMITY_LEVEL: RelationLevel = RelationLevel::Familiar;
+/// 与仇敌共事时的任务奖励惩罚
+const ENMITY_TASK_REWARD_PENALTY: f32 = 0.2;
+
+/// 对指定弟子敌意达到 HOSTILE_ENMITY_LEVEL 时生效的任务奖励Debuff
+pub fn enmity_modifier(target_id: usize) -> ConditionalModifier {
+    ConditionalModifier::new(
+        ModifierCondition::RelationLevelGreaterOrEqual(
+            target_id,
+            RelationDimension::Enmity,
+            HOSTILE_ENMITY_LEVEL,
+        ),
+        Modifier::new(
+            "仇怨掣肘",
+            ModifierTarget::TaskReward,
+            ModifierApplication::Multiplicative(-ENMITY_TASK_REWARD_PENALTY),
+            ModifierSource::Relationship,
+        ),
+    )
+}
+
 /// 单个关系（从一个弟子到另一个弟子的单向关系）
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Relationship {
@@ -273,6 +332,10 @@ pub struct Relationship {
     pub is_dao_companion: bool,    // 是否是道侣（需要双方确认）
     pub is_master: bool,           // 目标是否是自己的师父
     pub is_disciple: bool,         // 目标是否是自己的徒弟
+    #[serde(default)]
+    pub last_interaction_year: u32, // 最近一次互动的年份
+    #[serde(default)]
+    pub deceased: bool,             // 对方是否已故
 }
 
 impl Relationship {
@@ -285,6 +348,8 @@ impl Relationship {
             is_dao_companion: false,
             is_master: false,
             is_disciple: false,
+            last_interaction_year: year,
+            deceased: false,
         }
     }
 
@@ -310,6 +375,54 @@ impl Relationship {
         growth.apply_to(&mut self.scores)
     }
 
+    /// 记录一次互动（重置衰减计时）
+    pub fn record_interaction(&mut self, year: u32) {
+        self.last_interaction_year = year;
+    }
+
+    /// 指定维度每年的衰减值（道侣情感衰减减半，师徒关系不衰减）
+    fn decay_per_year(&self, dimension: RelationDimension) -> i32 {
+        match dimension {
+            RelationDimension::Mentorship if self.is_master || self.is_disciple => 0,
+            RelationDimension::Romance if self.is_dao_companion => RELATION_DECAY_PER_YEAR / 2,
+            _ => RELATION_DECAY_PER_YEAR,
+        }
+    }
+
+    /// 年度衰减：长期未互动的关系分数小幅回落，返回道侣是否因此貌合神离
+    pub fn decay(&mut self, year: u32) -> bool {
+        // 故人的情谊定格，不再消长
+        if self.deceased {
+            return false;
+        }
+        if year.saturating_sub(self.last_interaction_year) <= RELATION_DECAY_GRACE_YEARS {
+            return false;
+        }
+
+        let romance_before = self.scores.romance;
+        for dimension in RelationDimension::all() {
+            let amount = self.decay_per_year(dimension);
+            if amount > 0 {
+                self.scores.add(dimension, -amount);
+            }
+        }
+
+        self.is_dao_companion
+            && romance_before >= DAO_COMPANION_ESTRANGED_THRESHOLD
+            && self.scores.romance < DAO_COMPANION_ESTRANGED_THRESHOLD
+    }
+
+    /// 是否为仇敌：敌意达到敌对等级且压过其他维度
+    pub fn is_sworn_enemy(&self) -> bool {
+        let highest_bond = RelationDimension::all()
+            .into_iter()
+            .filter(|dim| !dim.is_hostile())
+            .map(|dim| self.scores.get(dim))
+            .max()
+            .unwrap_or(0);
+        self.scores.get_level(RelationDimension::Enmity) >= HOSTILE_ENMITY_LEVEL && self.scores.enmity > highest_bond
+    }
+
     /// 获取主要关系类型描述
     pub fn get_primary_relation(&self) -> &'static str {
         if self.is_dao_companion {
@@ -322,6 +435,10 @@ impl Relationship {
             return "徒弟";
         }
 
+        if self.is_sworn_enemy() {
+            return "仇敌";
+        }
+
         // 根据最高分数判断
         let highest = self.scores.highest_level();
         if highest == RelationLevel::Stranger {
@@ -1,18 +1,340 @@
-use crate::disciple::{Disciple, DiscipleType, Heritage};
+use serde::{Deserialize, Serialize};
+use crate::disciple::{Disciple, DiscipleType, Heritage, InjurySeverity, Specialization, Talent, TalentType, RESPECIALIZE_COST, RESPECIALIZE_DAO_HEART_LOSS};
 use crate::cultivation::CultivationLevel;
-use crate::pill::{PillInventory, HerbInventory, PillRecipe, PillType};
-use crate::map::HerbQuality;
-use crate::modifier::ConditionalModifier;
+use crate::pill::{PillGrade, PillInventory, HerbInventory, PillRecipe, PillType};
+use crate::map::{HerbQuality, MonsterDrop};
+use crate::modifier::{ConditionalModifier, Modifier, ModifierApplication, ModifierCondition, ModifierExplanation, ModifierSource, ModifierTarget};
 use crate::building::BuildingTree;
+use crate::achievement::{
+    Achievement, AchievementProgress, AchievementReward, UnlockedAchievement, DAO_COUPLES_TARGET,
+    MONSTER_SLAYER_TARGET, RENOWN_TARGET,
+};
 use crate::relationship::{Relationship, RelationDimension, RelationLevel, RelationGrowth};
-use crate::task::TaskType;
+use crate::task::{RiskLevel, TaskType};
+use crate::event::RecruitmentSystem;
+use crate::technique::{Technique, TECHNIQUES_PER_LIBRARY_LEVEL, TECHNIQUE_BUILDING_ID};
+use crate::equipment::{Equipment, EQUIPMENT_BUILDING_ID};
+use crate::tournament::{
+    duel_win_rate, tournament_power, TournamentMatch, TournamentResult, TournamentStanding,
+    TOURNAMENT_CHAMPION_DAO_HEART, TOURNAMENT_GRACIOUS_DAO_HEART, TOURNAMENT_LOSER_RELATION_GAIN,
+    TOURNAMENT_MIN_PARTICIPANTS, TOURNAMENT_WINNER_COMRADE_GAIN, TOURNAMENT_WIN_DAO_HEART,
+    TOURNAMENT_WIN_REPUTATION,
+};
+
+/// 购买草药相对收购价的溢价倍率
+pub const HERB_BUY_PREMIUM: f32 = 1.5;
+
+/// 新宗门的初始灵石
+pub const STARTING_SPIRIT_STONES: u32 = 1000;
+
+/// 未建仓储建筑时宗门可存放的灵石上限
+pub const BASE_SPIRIT_STONE_CAPACITY: u32 = 3000;
+
+/// 道侣每年生育的最大概率
+const MAX_BIRTH_CHANCE: f64 = 0.15;
+
+/// 紧急操作允许透支的负债上限
+pub const MAX_DEBT: u32 = 500;
+/// 负债期间每年损失的声望
+pub const DEBT_REPUTATION_PENALTY: i32 = 5;
+/// 负债期间弟子每年下降的道心（宗门动荡）
+pub const DEBT_DAO_HEART_PENALTY: u32 = 3;
+/// 连续负债达到该年数后，每年有一名道心最低的弟子叛离
+pub const DEBT_DESERTION_TURNS: u32 = 5;
+/// 道心低于该值的弟子可能叛离
+pub const DESERTION_DAO_HEART_THRESHOLD: u32 = 15;
+/// 道心归零时的年度叛离概率
+const MAX_DAO_HEART_DESERTION_CHANCE: f64 = 0.5;
+/// 与同门结下深仇（敌意达到亲密无间）时额外的叛离概率
+const ENMITY_DESERTION_CHANCE: f64 = 0.15;
+/// 走火入魔暴走时波及的同门人数上限
+const RAMPAGE_MAX_VICTIMS: usize = 2;
+/// 受波及同门对暴走者的敌意增长（同袍情谊等量下降）
+const RAMPAGE_ENMITY_GAIN: i32 = 15;
+/// 受波及同门负伤的概率
+const RAMPAGE_INJURY_CHANCE: f64 = 0.5;
+
+/// 晋升所需功劳：(目标类型, 修为门槛, 任务数门槛, 声望贡献门槛)，满足其一即可
+const PROMOTION_REQUIREMENTS: [(DiscipleType, CultivationLevel, u32, u32); 2] = [
+    (DiscipleType::Inner, CultivationLevel::Foundation, 10, 50),
+    (DiscipleType::Personal, CultivationLevel::GoldenCore, 25, 150),
+];
+/// 晋升消耗的宗门贡献点：(目标类型, 贡献点)
+pub const PROMOTION_CONTRIBUTION_COSTS: [(DiscipleType, u32); 2] = [
+    (DiscipleType::Inner, 50),
+    (DiscipleType::Personal, 150),
+];
+/// 亲传弟子基础名额
+const BASE_PERSONAL_QUOTA: usize = 1;
+/// 以贡献点说服抗命弟子的花费
+pub const PERSUASION_COST: u32 = 30;
+/// 每建成多少座建筑增加一个亲传名额
+const BUILDINGS_PER_PERSONAL_QUOTA: usize = 3;
+
+/// 宗门等级
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SectRank {
+    Mortal,     // 凡品宗门
+    FirstClass, // 一流宗门
+    Super,      // 超级宗门
+    Immortal,   // 仙门
+}
+
+/// 宗门等级晋升条件
+#[derive(Debug, Clone, Copy)]
+pub struct SectRankRequirement {
+    pub rank: SectRank,
+    pub min_cultivation: CultivationLevel, // 存活弟子最高修为
+    pub min_reputation: i32,               // 总声望
+    pub min_buildings: usize,              // 已建成建筑数
+}
+
+/// 各等级的晋升条件（须同时满足）
+const SECT_RANK_REQUIREMENTS: [SectRankRequirement; 3] = [
+    SectRankRequirement {
+        rank: SectRank::FirstClass,
+        min_cultivation: CultivationLevel::Foundation,
+        min_reputation: 100,
+        min_buildings: 2,
+    },
+    SectRankRequirement {
+        rank: SectRank::Super,
+        min_cultivation: CultivationLevel::NascentSoul,
+        min_reputation: 500,
+        min_buildings: 5,
+    },
+    SectRankRequirement {
+        rank: SectRank::Immortal,
+        min_cultivation: CultivationLevel::VoidRefinement,
+        min_reputation: 1500,
+        min_buildings: 9,
+    },
+];
+
+impl SectRank {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Mortal => "凡品宗门",
+            Self::FirstClass => "一流宗门",
+            Self::Super => "超级宗门",
+            Self::Immortal => "仙门",
+        }
+    }
+
+    /// 晋升到下一等级的条件（已是仙门时为 None）
+    pub fn next_requirement(&self) -> Option<&'static SectRankRequirement> {
+        SECT_RANK_REQUIREMENTS.iter().find(|r| r.rank > *self)
+    }
+
+    /// 可承接的地图任务最高风险
+    pub fn max_task_risk(&self) -> RiskLevel {
+        match self {
+            Self::Mortal => RiskLevel::Medium,
+            Self::FirstClass => RiskLevel::High,
+            Self::Super | Self::Immortal => RiskLevel::Deadly,
+        }
+    }
+
+    /// 额外的亲传弟子名额
+    pub fn personal_quota_bonus(&self) -> usize {
+        *self as usize
+    }
+}
+
+/// 士气参考的近期任务数
+pub const MORALE_HISTORY_LEN: usize = 10;
+/// 士气高于该值时宗门士气高昂
+pub const HIGH_MORALE_THRESHOLD: i32 = 70;
+/// 士气低于该值时人心涣散
+pub const LOW_MORALE_THRESHOLD: i32 = 40;
+/// 负债对士气的打击
+const DEBT_MORALE_PENALTY: i32 = 20;
+
+/// 宗门氛围：由平均道心、近期任务胜败与负债综合而成
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SectMorale {
+    High,   // 士气高昂
+    Steady, // 人心安定
+    Low,    // 人心涣散
+}
+
+impl SectMorale {
+    pub fn from_score(score: i32) -> Self {
+        if score >= HIGH_MORALE_THRESHOLD {
+            Self::High
+        } else if score < LOW_MORALE_THRESHOLD {
+            Self::Low
+        } else {
+            Self::Steady
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::High => "士气高昂",
+            Self::Steady => "人心安定",
+            Self::Low => "人心涣散",
+        }
+    }
+
+    /// 该氛围挂给全宗门的modifier（人心安定时没有）
+    pub fn modifier(&self) -> Option<ConditionalModifier> {
+        let bonus = match self {
+            Self::High => 0.1,
+            Self::Steady => return None,
+            Self::Low => -0.15,
+        };
+        Some(ConditionalModifier::new(
+            ModifierCondition::Always,
+            Modifier::new(self.name(), ModifierTarget::TaskReward, ModifierApplication::Multiplicative(bonus), ModifierSource::Morale),
+        ))
+    }
+}
+
+/// 新生弟子的名字
+const CHILD_GIVEN_NAMES: [&str; 8] = ["念", "承", "思远", "若水", "青云", "长生", "灵儿", "问道"];
+
+/// 道侣每年生育的概率：道心越高越易得子，寿元将尽则难有子嗣
+pub fn birth_chance(parent1: &Disciple, parent2: &Disciple) -> f64 {
+    let dao_heart = (parent1.dao_heart + parent2.dao_heart) as f64 / 200.0;
+    let vitality = [parent1, parent2]
+        .iter()
+        .map(|p| 1.0 - p.age as f64 / p.lifespan.max(1) as f64)
+        .fold(1.0, f64::min)
+        .max(0.0);
+    (MAX_BIRTH_CHANCE * dao_heart * vitality).clamp(0.0, MAX_BIRTH_CHANCE)
+}
+
+/// 遗传天赋：双亲共有的天赋有75%概率遗传，仅一方拥有的有40%概率遗传；
+/// 至少继承一项父母天赋
+pub fn inherit_talents<R: rand::Rng>(parent1: &Disciple, parent2: &Disciple, rng: &mut R) -> Vec<Talent> {
+    let mut candidates: Vec<(TalentType, u32, bool)> = Vec::new(); // (天赋, 等级, 是否双亲共有)
+    for talent in parent1.talents.iter().chain(parent2.talents.iter()) {
+        match candidates.iter_mut().find(|(t, _, _)| *t == talent.talent_type) {
+            Some(entry) => {
+                entry.1 = (entry.1 + talent.level) / 2;
+                entry.2 = true;
+            }
+            None => candidates.push((talent.talent_type.clone(), talent.level, false)),
+        }
+    }
+
+    let mut talents = Vec::new();
+    for (talent_type, level, shared) in &candidates {
+        if rng.gen_bool(if *shared { 0.75 } else { 0.4 }) {
+            talents.push(Talent {
+                talent_type: talent_type.clone(),
+                level: (*level as i32 + rng.gen_range(-1..=1)).clamp(1, 10) as u32,
+            });
+        }
+    }
+
+    if talents.is_empty() {
+        if let Some((talent_type, level, _)) = candidates.iter().max_by_key(|(_, level, _)| *level) {
+            talents.push(Talent { talent_type: talent_type.clone(), level: *level });
+        }
+    }
+    talents
+}
+
+/// 生成道侣的后代（随父母一方姓氏，天赋按概率遗传）
+fn breed_child<R: rand::Rng>(id: usize, parent1: &Disciple, parent2: &Disciple, rng: &mut R) -> Disciple {
+    let surname: String = parent1.name.chars().take(1).collect();
+    let given = CHILD_GIVEN_NAMES[rng.gen_range(0..CHILD_GIVEN_NAMES.len())];
+    let talents = inherit_talents(parent1, parent2, rng);
+
+    let mut child = Disciple::new(id, format!("{}{}", surname, given), DiscipleType::Inner, talents);
+    child.age = 0;
+    child.position = parent1.position;
+    child
+}
+
+/// 弟子离开宗门的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DepartureCause {
+    Lifespan,    // 寿元耗尽
+    Battle,      // 战死
+    Tribulation, // 渡劫身死
+    Desertion,   // 叛出宗门
+}
+
+impl DepartureCause {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Lifespan => "寿终",
+            Self::Battle => "战死",
+            Self::Tribulation => "渡劫身死",
+            Self::Desertion => "叛离",
+        }
+    }
+}
+
+/// 纪念堂记录：陨落或叛离的弟子
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FallenRecord {
+    pub disciple_id: usize,
+    pub name: String,
+    pub highest_level: CultivationLevel, // 最高修为
+    pub cause: DepartureCause,
+    pub year: u32,                        // 卒年（叛离之年）
+    pub age: u32,
+    pub tasks_completed: u32,             // 生前完成任务数
+    pub resources_contributed: u32,       // 生前赚取的灵石
+    pub reputation_contributed: u32,      // 生前赢得的声望
+}
+
+impl FallenRecord {
+    fn new(disciple: &Disciple, cause: DepartureCause, year: u32) -> Self {
+        Self {
+            disciple_id: disciple.id,
+            name: disciple.name.clone(),
+            highest_level: disciple.cultivation.current_level,
+            cause,
+            year,
+            age: disciple.age,
+            tasks_completed: disciple.tasks_completed,
+            resources_contributed: disciple.total_resources_contributed,
+            reputation_contributed: disciple.reputation_contributed,
+        }
+    }
+}
+
+/// 炼丹队列中的订单
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RefineOrder {
+    pub pill_type: PillType,
+    pub alchemist_id: usize,
+    pub remaining: u32, // 剩余炉数
+}
+
+/// 批量炼丹中单种丹药的结果
+#[derive(Debug, Clone)]
+pub struct BatchRefineEntry {
+    pub pill_type: PillType,
+    pub requested: u32, // 计划炉数
+    pub succeeded: u32, // 成功炉数
+    pub failed: u32,    // 失败炉数
+    pub output: u32,    // 产出丹药数
+}
+
+/// 批量炼丹报告
+#[derive(Debug, Clone, Default)]
+pub struct BatchRefineReport {
+    pub entries: Vec<BatchRefineEntry>,
+    pub spirit_stones_spent: u32,
+    pub herbs_consumed: u32,
+    pub stop_reason: Option<String>, // 中途停止的原因（全部炼完为None）
+}
 
 /// 宗门
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Sect {
     pub name: String,
     pub disciples: Vec<Disciple>,
-    pub resources: u32,
+    #[serde(alias = "resources")]
+    pub spirit_stones: u32, // 灵石（主要货币）
+    #[serde(default)]
+    pub contribution: u32, // 贡献点（晋升与修习功法）
     pub reputation: i32,
     pub is_immortal_sect: bool,
     pub heritages: Vec<Heritage>, // 传承库
@@ -21,6 +343,30 @@ pub struct Sect {
     pub herb_inventory: HerbInventory, // 草药仓库
     pub sect_modifiers: Vec<ConditionalModifier>, // 宗门级别的条件modifier
     pub building_tree: Option<BuildingTree>, // 建筑树（可选）
+    #[serde(default)]
+    pub debt: u32, // 透支形成的负债
+    #[serde(default)]
+    pub debt_turns: u32, // 连续负债的年数
+    #[serde(default)]
+    pub total_recruited: u32, // 本局累计招募弟子数
+    #[serde(default)]
+    pub total_deaths: u32, // 本局累计陨落弟子数
+    #[serde(default)]
+    pub completed_task_names: std::collections::HashSet<String>, // 完成过的任务名称（任务链前置判定）
+    #[serde(default)]
+    pub material_inventory: std::collections::HashMap<String, u32>, // 炼器材料仓库
+    #[serde(default)]
+    pub refine_queue: Vec<RefineOrder>, // 炼丹队列（每回合自动炼一炉）
+    #[serde(default)]
+    pub monsters_slain: u32, // 本局累计斩杀妖魔数
+    #[serde(default)]
+    pub achievements: Vec<UnlockedAchievement>, // 已解锁的成就
+    #[serde(default)]
+    pub recent_task_outcomes: std::collections::VecDeque<bool>, // 近期任务胜败（士气参考）
+    #[serde(default)]
+    pub fallen_disciples: Vec<FallenRecord>, // 纪念堂：陨落与叛离的弟子
+    #[serde(default)]
+    pub discovered_recipes: Vec<PillType>, // 奇遇所得丹方（无需建筑即可炼制）
 }
 
 impl Sect {
@@ -28,7 +374,8 @@ impl Sect {
         Self {
             name,
             disciples: Vec::new(),
-            resources: 1000, // 初始资源
+            spirit_stones: STARTING_SPIRIT_STONES,
+            contribution: 0,
             reputation: 0,
             is_immortal_sect: false,
             heritages: Vec::new(),
@@ -37,6 +384,18 @@ impl Sect {
             herb_inventory: HerbInventory::new(),
             sect_modifiers: Vec::new(),
             building_tree: None,
+            debt: 0,
+            debt_turns: 0,
+            total_recruited: 0,
+            total_deaths: 0,
+            completed_task_names: std::collections::HashSet::new(),
+            material_inventory: std::collections::HashMap::new(),
+            refine_queue: Vec::new(),
+            monsters_slain: 0,
+            achievements: Vec::new(),
+            recent_task_outcomes: std::collections::VecDeque::new(),
+            fallen_disciples: Vec::new(),
+            discovered_recipes: Vec::new(),
         }
     }
 
@@ -45,39 +404,261 @@ impl Sect {
         self.herb_inventory.add(name, quality, 1);
     }
 
-    /// 炼制丹药（使用草药和资源）
-    pub fn refine_pill(&mut self, pill_type: PillType) -> Result<u32, String> {
-        use rand::Rng;
+    /// 妖魔掉落入库
+    pub fn receive_drop(&mut self, drop: &MonsterDrop) {
+        match drop {
+            MonsterDrop::Herb { name, quality } => self.herb_inventory.add(name, *quality, 1),
+            MonsterDrop::Pill { pill_type, grade } => self.pill_inventory.add(*pill_type, *grade, 1),
+            MonsterDrop::Material { name, count } => {
+                *self.material_inventory.entry(name.clone()).or_insert(0) += count;
+            }
+        }
+    }
+
+    /// 卖出草药换取资源（优先偿还负债），返回实际入账的金额（库房已满时超出部分散失）
+    pub fn sell_herbs(&mut self, name: &str, quality: HerbQuality, count: u32) -> Result<u32, String> {
+        if count == 0 {
+            return Err("数量必须大于0".to_string());
+        }
+
+        let owned = self.herb_inventory.get_count(name, quality);
+        if owned < count {
+            return Err(format!("{}{}库存不足，需要{}，当前只有{}", quality.name(), name, count, owned));
+        }
+
+        let amount = quality
+            .base_price()
+            .checked_mul(count)
+            .ok_or_else(|| "交易数量过大".to_string())?;
+        self.herb_inventory.consume(name, quality, count);
+        let wasted = self.add_spirit_stones(amount);
+        Ok(amount - wasted)
+    }
+
+    /// 花费资源购买草药（按收购价加溢价），返回成交金额
+    pub fn buy_herbs(&mut self, name: &str, quality: HerbQuality, count: u32) -> Result<u32, String> {
+        if count == 0 {
+            return Err("数量必须大于0".to_string());
+        }
+
+        let unit_price = (quality.base_price() as f32 * HERB_BUY_PREMIUM).ceil() as u32;
+        let amount = unit_price
+            .checked_mul(count)
+            .ok_or_else(|| "交易数量过大".to_string())?;
+        if !self.consume_spirit_stones(amount) {
+            return Err(format!("资源不足，需要{}，当前只有{}", amount, self.spirit_stones));
+        }
 
+        self.herb_inventory.add(name, quality, count);
+        Ok(amount)
+    }
+
+    /// 检查能否开炉炼丹（配方、炼丹弟子、资源与草药），返回配方和实际使用的草药品质
+    pub fn check_refine(
+        &self,
+        pill_type: PillType,
+        alchemist_id: usize,
+        herb_quality: Option<HerbQuality>,
+    ) -> Result<(PillRecipe, HerbQuality), String> {
         let recipe = PillRecipe::for_pill(pill_type)
             .ok_or_else(|| "找不到该丹药配方".to_string())?;
+        if !self.is_recipe_unlocked(&recipe) {
+            return Err(format!(
+                "{}为{}配方，{}",
+                pill_type.name(),
+                recipe.tier.name(),
+                recipe.tier.unlock_condition().unwrap_or_default()
+            ));
+        }
+        let herb_quality = herb_quality.unwrap_or(recipe.required_herb_quality);
+        if herb_quality < recipe.required_herb_quality {
+            return Err(format!(
+                "{}需要{}品质以上的草药",
+                pill_type.name(),
+                recipe.required_herb_quality.name()
+            ));
+        }
+
+        if !self.disciples.iter().any(|d| d.id == alchemist_id && d.is_alive()) {
+            return Err("炼丹弟子不存在".to_string());
+        }
 
         // 检查资源
-        if self.resources < recipe.resource_cost {
+        if self.spirit_stones < recipe.resource_cost {
             return Err(format!("资源不足，需要{}资源", recipe.resource_cost));
         }
 
         // 检查草药（按品质消耗任意草药）
-        let available = self.herb_inventory.count_by_quality(recipe.required_herb_quality);
+        let available = self.herb_inventory.count_by_quality(herb_quality);
         if available < recipe.required_herb_count {
             return Err(format!(
                 "{}品质草药不足，需要{}个，当前{}个",
-                recipe.required_herb_quality.name(),
+                herb_quality.name(),
                 recipe.required_herb_count,
                 available
             ));
         }
 
+        Ok((recipe, herb_quality))
+    }
+
+    /// 批量炼丹：按顺序逐炉炼制，资源或草药不足时整批停止
+    pub fn batch_refine<R: rand::Rng>(
+        &mut self,
+        orders: &[(PillType, u32)],
+        alchemist_id: usize,
+        rng: &mut R,
+    ) -> BatchRefineReport {
+        let stones_before = self.spirit_stones;
+        let herbs_before = self.herb_inventory.total_count();
+        let mut report = BatchRefineReport::default();
+
+        'orders: for &(pill_type, times) in orders {
+            let mut entry = BatchRefineEntry {
+                pill_type,
+                requested: times,
+                succeeded: 0,
+                failed: 0,
+                output: 0,
+            };
+            for _ in 0..times {
+                if let Err(reason) = self.check_refine(pill_type, alchemist_id, None) {
+                    report.stop_reason = Some(reason);
+                    report.entries.push(entry);
+                    break 'orders;
+                }
+                match self.refine_pill(pill_type, alchemist_id, None, rng) {
+                    Ok((count, _)) => {
+                        entry.succeeded += 1;
+                        entry.output += count;
+                    }
+                    Err(_) => entry.failed += 1,
+                }
+            }
+            report.entries.push(entry);
+        }
+
+        report.spirit_stones_spent = stones_before - self.spirit_stones;
+        report.herbs_consumed = herbs_before - self.herb_inventory.total_count();
+        report
+    }
+
+    /// 加入炼丹队列
+    pub fn queue_refine(&mut self, pill_type: PillType, alchemist_id: usize, times: u32) -> Result<(), String> {
+        if times == 0 {
+            return Err("炼制炉数必须大于0".to_string());
+        }
+        if PillRecipe::for_pill(pill_type).is_none() {
+            return Err("找不到该丹药配方".to_string());
+        }
+        if !self.disciples.iter().any(|d| d.id == alchemist_id && d.is_alive()) {
+            return Err("炼丹弟子不存在".to_string());
+        }
+        self.refine_queue.push(RefineOrder { pill_type, alchemist_id, remaining: times });
+        Ok(())
+    }
+
+    /// 炼丹队列开一炉：队首订单炼制一次，订单完成后出队
+    /// 炼丹弟子已不在时撤销订单，资源不足时本回合暂停；返回本炉的结果说明
+    pub fn refine_from_queue<R: rand::Rng>(&mut self, rng: &mut R) -> Option<String> {
+        let order = self.refine_queue.first()?.clone();
+        if !self.disciples.iter().any(|d| d.id == order.alchemist_id && d.is_alive()) {
+            self.refine_queue.remove(0);
+            return Some(format!("炼丹弟子已不在，撤销{}的炼制订单", order.pill_type.name()));
+        }
+        if let Err(reason) = self.check_refine(order.pill_type, order.alchemist_id, None) {
+            return Some(format!("炼丹队列暂停：{}", reason));
+        }
+
+        let message = match self.refine_pill(order.pill_type, order.alchemist_id, None, rng) {
+            Ok((count, grade)) => format!("炼丹队列炼成{}个{}{}", count, grade.name(), order.pill_type.name()),
+            Err(reason) => format!("炼丹队列炼制{}：{}", order.pill_type.name(), reason),
+        };
+        let front = &mut self.refine_queue[0];
+        front.remaining -= 1;
+        if front.remaining == 0 {
+            self.refine_queue.remove(0);
+        }
+        Some(message)
+    }
+
+    /// 已建建筑的收入（Income）modifier 对年度收入的加成，按建筑列出
+    pub fn building_income_bonuses(&self, base_income: u32) -> Vec<(String, u32)> {
+        let Some(tree) = &self.building_tree else {
+            return Vec::new();
+        };
+        let mut bonuses: Vec<(String, u32)> = tree
+            .buildings
+            .values()
+            .filter(|b| b.is_built)
+            .filter_map(|b| {
+                let bonus: f32 = b
+                    .conditional_modifiers
+                    .iter()
+                    .filter(|c| c.modifier.target == ModifierTarget::Income)
+                    .map(|c| match c.modifier.application {
+                        ModifierApplication::Additive(value) => value,
+                        ModifierApplication::Multiplicative(value) => base_income as f32 * value,
+                        ModifierApplication::Override(_) => 0.0,
+                    })
+                    .sum();
+                (bonus >= 1.0).then(|| (b.name.clone(), bonus as u32))
+            })
+            .collect();
+        bonuses.sort();
+        bonuses
+    }
+
+    /// 配方是否已由宗门建筑解锁
+    pub fn is_recipe_unlocked(&self, recipe: &PillRecipe) -> bool {
+        let required = recipe.tier.required_buildings();
+        required.is_empty()
+            || self.discovered_recipes.contains(&recipe.pill_type)
+            || self.building_tree.as_ref().is_some_and(|tree| {
+                required.iter().any(|id| tree.buildings.get(*id).is_some_and(|b| b.is_built))
+            })
+    }
+
+    /// 指定弟子炼丹的有效成功率（包含炼丹房等宗门modifier）
+    pub fn alchemy_success_rate(&self, recipe: &PillRecipe, alchemist: &Disciple) -> f64 {
+        let sect_modifiers = self.get_applicable_modifiers_owned(alchemist);
+        alchemist.alchemy_success_rate(recipe.success_rate, &sect_modifiers.iter().collect::<Vec<_>>())
+    }
+
+    /// 由指定弟子炼制丹药（使用草药和资源），返回产出数量和成品品级
+    /// 可指定使用不低于配方要求的更高品质草药（默认按配方品质），草药品质与炼丹天赋决定成品品级；
+    /// 失败时资源照常消耗、草药损耗一半；炼丹天赋高时可能一炉双丹
+    pub fn refine_pill<R: rand::Rng>(
+        &mut self,
+        pill_type: PillType,
+        alchemist_id: usize,
+        herb_quality: Option<HerbQuality>,
+        rng: &mut R,
+    ) -> Result<(u32, PillGrade), String> {
+        let (recipe, herb_quality) = self.check_refine(pill_type, alchemist_id, herb_quality)?;
+        let alchemist = self.disciples.iter().find(|d| d.id == alchemist_id).unwrap();
+        let success_rate = self.alchemy_success_rate(&recipe, alchemist);
+        let surplus_chance = alchemist.alchemy_surplus_chance();
+        let alchemy_talent = alchemist.alchemy_talent_level();
+
         // 消耗资源
-        self.resources -= recipe.resource_cost;
+        self.spirit_stones -= recipe.resource_cost;
+
+        // 判断炼制是否成功
+        let success = rng.gen_bool(success_rate);
 
-        // 消耗草药（消耗任意该品质的草药）
+        // 消耗草药（消耗任意该品质的草药，失败时损耗一半）
         let herbs_to_consume = self.herb_inventory.get_all()
             .into_iter()
-            .filter(|h| h.quality == recipe.required_herb_quality)
+            .filter(|h| h.quality == herb_quality)
             .collect::<Vec<_>>();
 
-        let mut remaining = recipe.required_herb_count;
+        let consumed = if success {
+            recipe.required_herb_count
+        } else {
+            (recipe.required_herb_count + 1) / 2
+        };
+        let mut remaining = consumed;
         for herb in herbs_to_consume {
             if remaining == 0 {
                 break;
@@ -87,13 +668,17 @@ impl Sect {
             remaining -= consume_count;
         }
 
-        // 判断炼制是否成功
-        let mut rng = rand::thread_rng();
-        if rng.gen_bool(recipe.success_rate) {
-            self.pill_inventory.add(pill_type, recipe.output_count);
-            Ok(recipe.output_count)
+        if success {
+            let output = if rng.gen_bool(surplus_chance.min(1.0)) {
+                recipe.output_count * 2
+            } else {
+                recipe.output_count
+            };
+            let grade = recipe.roll_output_grade(herb_quality, alchemy_talent, rng);
+            self.pill_inventory.add(pill_type, grade, output);
+            Ok((output, grade))
         } else {
-            Err("炼制失败，材料已消耗".to_string())
+            Err(format!("炼制失败，损耗了{}株草药", consumed))
         }
     }
 
@@ -145,6 +730,59 @@ impl Sect {
         modifiers
     }
 
+    /// 对弟子生效的全部modifier（个人 + 宗门 + 建筑）及其出处
+    pub fn modifier_origins(&self, disciple: &Disciple) -> Vec<ModifierOrigin> {
+        let mut origins: Vec<ModifierOrigin> = disciple
+            .modifiers
+            .get_all_modifiers()
+            .iter()
+            .chain(disciple.applicable_conditional_modifiers())
+            .map(|m| ModifierOrigin { origin: "个人".to_string(), modifier: m.clone() })
+            .collect();
+        origins.extend(
+            self.get_applicable_modifiers(disciple)
+                .into_iter()
+                .map(|m| ModifierOrigin { origin: "宗门".to_string(), modifier: m.clone() }),
+        );
+        if let Some(ref tree) = self.building_tree {
+            let mut built: Vec<_> = tree.buildings.values().filter(|b| b.is_built).collect();
+            built.sort_by(|a, b| a.id.cmp(&b.id));
+            for building in built {
+                origins.extend(
+                    building
+                        .conditional_modifiers
+                        .iter()
+                        .filter(|cm| cm.applies_to(disciple))
+                        .map(|cm| ModifierOrigin { origin: building.name.clone(), modifier: cm.modifier.clone() }),
+                );
+            }
+        }
+        origins
+    }
+
+    /// 弟子应用全部modifier后的有效属性
+    pub fn effective_attributes(&self, disciple: &Disciple) -> EffectiveAttributes {
+        let sect_modifiers = self.get_applicable_modifiers_owned(disciple);
+        let sect_modifiers: Vec<&crate::modifier::Modifier> = sect_modifiers.iter().collect();
+        let talent_bonuses = TalentType::ALL
+            .iter()
+            .map(|t| (t.clone(), disciple.get_talent_bonus_with_sect_modifiers(t, &sect_modifiers)))
+            .filter(|(_, bonus)| *bonus != 0.0)
+            .collect();
+        EffectiveAttributes {
+            dao_heart: disciple.explain_effective(&ModifierTarget::DaoHeart, disciple.dao_heart as f32, &sect_modifiers),
+            energy: disciple.explain_effective(&ModifierTarget::Energy, disciple.energy as f32, &sect_modifiers),
+            constitution: disciple.explain_effective(
+                &ModifierTarget::Constitution,
+                disciple.constitution as f32,
+                &sect_modifiers,
+            ),
+            talent_bonuses,
+            tribulation_success_rate: disciple.tribulation_success_rate_with_sect_modifiers(&sect_modifiers),
+            modifiers: self.modifier_origins(disciple),
+        }
+    }
+
     /// 获取对指定弟子生效的所有宗门modifier（返回引用，仅包括直接设置的modifiers）
     pub fn get_applicable_modifiers(&self, disciple: &Disciple) -> Vec<&crate::modifier::Modifier> {
         self.sect_modifiers
@@ -155,7 +793,7 @@ impl Sect {
 
     // === 建筑系统方法 ===
 
-    /// 建造建筑
+    /// 建造建筑（扣除资源后开工，工期结束才生效）
     pub fn build_building(&mut self, building_id: &str) -> Result<String, String> {
         // 1. 检查是否有建筑树
         let tree = self.building_tree.as_mut()
@@ -168,26 +806,207 @@ impl Sect {
         let cost = tree.calculate_build_cost(building_id)?;
 
         // 4. 检查资源是否足够
-        if self.resources < cost {
-            return Err(format!("资源不足，需要{}，当前只有{}", cost, self.resources));
+        if self.spirit_stones < cost {
+            return Err(format!("资源不足，需要{}，当前只有{}", cost, self.spirit_stones));
         }
 
         // 5. 扣除资源
-        self.resources -= cost;
+        self.spirit_stones -= cost;
+
+        // 6. 开工（modifiers由建筑树统一管理，竣工后才生效）
+        let build_turns = tree.start_construction(building_id)?;
+
+        // 7. 获取建筑名称用于返回消息
+        let (building_name, effects_count) = tree.buildings.get(building_id)
+            .map(|b| (b.name.clone(), b.conditional_modifiers.len()))
+            .unwrap_or_else(|| (building_id.to_string(), 0));
+
+        if build_turns == 0 {
+            Ok(format!("成功建造'{}'，花费{}资源，获得{}个效果",
+                       building_name, cost, effects_count))
+        } else {
+            Ok(format!("'{}'开工建造，花费{}资源，{}回合后竣工",
+                       building_name, cost, build_turns))
+        }
+    }
+
+    /// 推进在建建筑的工期，返回本回合竣工的建筑名称
+    pub fn advance_construction(&mut self) -> Vec<String> {
+        let tree = match self.building_tree.as_mut() {
+            Some(tree) => tree,
+            None => return Vec::new(),
+        };
+        let completed = tree.advance_construction();
+        completed
+            .iter()
+            .filter_map(|id| tree.buildings.get(id).map(|b| b.name.clone()))
+            .collect()
+    }
+
+    /// 获取已解锁的功法（藏书楼建成后解锁）
+    pub fn available_techniques(&self) -> Vec<Technique> {
+        let unlocked = self
+            .building_tree
+            .as_ref()
+            .and_then(|tree| tree.buildings.get(TECHNIQUE_BUILDING_ID))
+            .map(|b| b.is_built)
+            .unwrap_or(false);
+        if unlocked {
+            Technique::all_techniques()
+        } else {
+            Vec::new()
+        }
+    }
 
-        // 6. 执行建造
-        let modifiers = tree.build(building_id)?;
+    /// 藏书楼等级：未建成为0，建成为1，其下每座已建成的附属建筑再加1
+    pub fn library_level(&self) -> usize {
+        let tree = match self.building_tree.as_ref() {
+            Some(tree) => tree,
+            None => return 0,
+        };
+        match tree.buildings.get(TECHNIQUE_BUILDING_ID) {
+            Some(library) if library.is_built => {
+                1 + tree.get_children(TECHNIQUE_BUILDING_ID).iter().filter(|b| b.is_built).count()
+            }
+            _ => 0,
+        }
+    }
 
-        // 7. 将建筑提供的modifiers添加到宗门modifiers（可选，因为get_applicable_modifiers已经会获取它们）
-        // 这里选择不添加，让modifiers由建筑树统一管理
+    /// 每名弟子可学会的功法上限（由藏书楼等级决定）
+    pub fn technique_capacity(&self) -> usize {
+        self.library_level() * TECHNIQUES_PER_LIBRARY_LEVEL
+    }
+
+    /// 弟子消耗宗门贡献点学习功法，返回花费的贡献点
+    pub fn teach_technique(&mut self, disciple_id: usize, technique_id: &str) -> Result<u32, String> {
+        let technique = match self.available_techniques().into_iter().find(|t| t.id == technique_id) {
+            Some(t) => t,
+            None if Technique::find(technique_id).is_some() => {
+                return Err("需要先建造藏书楼才能修习该功法".to_string())
+            }
+            None => return Err(format!("功法'{}'不存在", technique_id)),
+        };
+        let capacity = self.technique_capacity();
+        let contribution = self.contribution;
+        let disciple = self
+            .disciples
+            .iter_mut()
+            .find(|d| d.id == disciple_id && d.is_alive())
+            .ok_or_else(|| format!("弟子 {} 不存在", disciple_id))?;
+
+        disciple.can_learn_technique(&technique)?;
+        if disciple.learned_techniques.len() >= capacity {
+            return Err(format!("{}已学满{}部功法，扩建藏书楼可提升上限", disciple.name, capacity));
+        }
+        if contribution < technique.learn_cost {
+            return Err(format!("贡献点不足，学习{}需要 {} 贡献点", technique.name, technique.learn_cost));
+        }
 
-        // 8. 获取建筑名称用于返回消息
-        let building_name = tree.buildings.get(building_id)
-            .map(|b| b.name.clone())
-            .unwrap_or_else(|| building_id.to_string());
+        let cost = technique.learn_cost;
+        disciple.learn_technique(technique)?;
+        self.contribution -= cost;
+        Ok(cost)
+    }
 
-        Ok(format!("成功建造'{}'，花费{}资源，获得{}个效果",
-                   building_name, cost, modifiers.len()))
+    /// 获取可炼制的装备（炼器坊建成后解锁）
+    pub fn available_equipment(&self) -> Vec<Equipment> {
+        let unlocked = self
+            .building_tree
+            .as_ref()
+            .and_then(|tree| tree.buildings.get(EQUIPMENT_BUILDING_ID))
+            .map(|b| b.is_built)
+            .unwrap_or(false);
+        if unlocked {
+            Equipment::all_equipment()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// 炼制装备，消耗宗门资源
+    pub fn forge_equipment(&mut self, equipment_id: &str) -> Result<Equipment, String> {
+        let equipment = self
+            .available_equipment()
+            .into_iter()
+            .find(|e| e.id == equipment_id)
+            .ok_or_else(|| match Equipment::find(equipment_id) {
+                Some(_) => "需要先建造炼器坊才能炼制装备".to_string(),
+                None => format!("装备'{}'不存在", equipment_id),
+            })?;
+        if !self.consume_spirit_stones(equipment.cost) {
+            return Err(format!("资源不足，炼制{}需要{}资源", equipment.name, equipment.cost));
+        }
+        Ok(equipment)
+    }
+
+    /// 举办比武大会：参赛弟子两两对决，胜者提升道心并为宗门赢得声望，对手之间的关系随胜负变化
+    pub fn hold_tournament<R: rand::Rng>(&mut self, participant_ids: &[usize], rng: &mut R) -> Result<TournamentResult, String> {
+        let mut standings: Vec<TournamentStanding> = self
+            .disciples
+            .iter()
+            .filter(|d| participant_ids.contains(&d.id) && d.is_alive())
+            .map(|d| TournamentStanding {
+                disciple_id: d.id,
+                name: d.name.clone(),
+                wins: 0,
+                power: tournament_power(d),
+            })
+            .collect();
+        if standings.len() < TOURNAMENT_MIN_PARTICIPANTS {
+            return Err(format!("参赛弟子不足{}人，无法举办比武大会", TOURNAMENT_MIN_PARTICIPANTS));
+        }
+
+        // 循环赛：每两名弟子对决一场
+        let mut matches = Vec::new();
+        for i in 0..standings.len() {
+            for j in (i + 1)..standings.len() {
+                let rate = duel_win_rate(standings[i].power, standings[j].power);
+                let (winner, loser) = if rng.gen_bool(rate as f64) { (i, j) } else { (j, i) };
+                standings[winner].wins += 1;
+                matches.push(TournamentMatch {
+                    winner_id: standings[winner].disciple_id,
+                    winner_name: standings[winner].name.clone(),
+                    loser_id: standings[loser].disciple_id,
+                    loser_name: standings[loser].name.clone(),
+                    winner_rate: if winner == i { rate } else { 1.0 - rate },
+                });
+            }
+        }
+
+        // 对决结算：胜者道心精进，对手之间结下情谊或心生芥蒂
+        let year = self.year;
+        for m in &matches {
+            if let Some(winner) = self.disciples.iter_mut().find(|d| d.id == m.winner_id) {
+                winner.apply_dao_heart_impact(TOURNAMENT_WIN_DAO_HEART);
+                winner.reputation_contributed += TOURNAMENT_WIN_REPUTATION as u32;
+                let rel = winner.get_or_create_relationship(m.loser_id, year);
+                rel.record_interaction(year);
+                rel.scores.add(RelationDimension::Comrade, TOURNAMENT_WINNER_COMRADE_GAIN);
+            }
+            if let Some(loser) = self.disciples.iter_mut().find(|d| d.id == m.loser_id) {
+                let dimension = if loser.dao_heart >= TOURNAMENT_GRACIOUS_DAO_HEART {
+                    RelationDimension::Comrade
+                } else {
+                    RelationDimension::Enmity
+                };
+                let rel = loser.get_or_create_relationship(m.winner_id, year);
+                rel.record_interaction(year);
+                rel.scores.add(dimension, TOURNAMENT_LOSER_RELATION_GAIN);
+            }
+        }
+
+        // 按胜场排名，胜场相同时战力高者居前
+        standings.sort_by(|a, b| b.wins.cmp(&a.wins).then(b.power.total_cmp(&a.power)));
+        if let Some(champion) = standings.first() {
+            if let Some(d) = self.disciples.iter_mut().find(|d| d.id == champion.disciple_id) {
+                d.apply_dao_heart_impact(TOURNAMENT_CHAMPION_DAO_HEART);
+            }
+        }
+
+        let reputation_gained = matches.len() as i32 * TOURNAMENT_WIN_REPUTATION;
+        self.add_reputation(reputation_gained);
+
+        Ok(TournamentResult { year, matches, standings, reputation_gained })
     }
 
     /// 获取可建造的建筑列表（包含成本信息）
@@ -222,6 +1041,7 @@ impl Sect {
     /// 添加弟子
     pub fn recruit_disciple(&mut self, disciple: Disciple) {
         self.disciples.push(disciple);
+        self.total_recruited += 1;
     }
 
     /// 获取存活弟子
@@ -234,6 +1054,78 @@ impl Sect {
         self.disciples.iter_mut().filter(|d| d.is_alive()).collect()
     }
 
+    /// 记录一只妖魔伏诛
+    pub fn record_monster_slain(&mut self) {
+        self.monsters_slain += 1;
+    }
+
+    /// 存活的道侣（每对只算一次）
+    pub fn dao_couples(&self) -> Vec<(usize, usize)> {
+        self.alive_disciples()
+            .iter()
+            .filter_map(|d| {
+                let companion_id = d.get_dao_companion_id()?;
+                let companion_alive = self.disciples.iter().any(|c| c.id == companion_id && c.is_alive());
+                (d.id < companion_id && companion_alive).then_some((d.id, companion_id))
+            })
+            .collect()
+    }
+
+    /// 各成就的当前进度（已解锁的附带解锁年份）
+    pub fn achievement_progress(&self) -> Vec<AchievementProgress> {
+        Achievement::ALL
+            .iter()
+            .map(|&achievement| {
+                let (current, target) = match achievement {
+                    Achievement::FirstGoldenCore => {
+                        let reached = self
+                            .disciples
+                            .iter()
+                            .any(|d| d.cultivation.current_level >= CultivationLevel::GoldenCore);
+                        (reached as u32, 1)
+                    }
+                    Achievement::MonsterSlayer => (self.monsters_slain, MONSTER_SLAYER_TARGET),
+                    Achievement::GrandArchitect => self.building_tree.as_ref().map_or((0, 1), |tree| {
+                        (tree.get_built_count() as u32, tree.get_total_count() as u32)
+                    }),
+                    Achievement::DaoCouples => (self.dao_couples().len() as u32, DAO_COUPLES_TARGET),
+                    Achievement::Renowned => (self.reputation.max(0) as u32, RENOWN_TARGET),
+                };
+                let unlocked_year = self
+                    .achievements
+                    .iter()
+                    .find(|u| u.achievement == achievement)
+                    .map(|u| u.year);
+                AchievementProgress { achievement, current, target, unlocked_year }
+            })
+            .collect()
+    }
+
+    /// 检查并解锁达成的成就，发放一次性奖励，返回本次新解锁的成就及因库房已满散失的灵石
+    pub fn check_achievements(&mut self) -> (Vec<Achievement>, u32) {
+        let unlocked: Vec<Achievement> = self
+            .achievement_progress()
+            .into_iter()
+            .filter(|p| p.unlocked_year.is_none() && p.is_complete())
+            .map(|p| p.achievement)
+            .collect();
+        let mut wasted = 0;
+        for &achievement in &unlocked {
+            self.achievements.push(UnlockedAchievement { achievement, year: self.year });
+            match achievement.reward() {
+                AchievementReward::SpiritStones(amount) => {
+                    wasted += self.add_spirit_stones(amount);
+                }
+                AchievementReward::Blessing(modifier) => {
+                    for disciple in self.alive_disciples_mut() {
+                        disciple.add_modifier(modifier.clone());
+                    }
+                }
+            }
+        }
+        (unlocked, wasted)
+    }
+
     /// 检查是否成为仙门
     pub fn check_immortal_sect(&mut self) -> bool {
         if self.disciples.iter().any(|d| d.is_immortal()) {
@@ -249,26 +1141,372 @@ impl Sect {
         self.alive_disciples().is_empty()
     }
 
-    /// 处理弟子死亡
-    pub fn handle_disciple_death(&mut self, disciple_id: usize) {
+    /// 处理弟子死亡：生成传承、载入纪念堂，并把同门指向该弟子的关系标记为已故
+    pub fn handle_disciple_death(&mut self, disciple_id: usize, cause: DepartureCause) {
         if let Some(disciple) = self.disciples.iter().find(|d| d.id == disciple_id) {
+            self.total_deaths += 1;
+            self.fallen_disciples.push(FallenRecord::new(disciple, cause, self.year));
             // 生成传承
             if let Some(heritage) = disciple.generate_heritage() {
                 println!("{}留下了传承：{}", disciple.name, heritage.name);
                 self.heritages.push(heritage);
             }
         }
+        for rel in self.disciples.iter_mut().flat_map(|d| d.relationships.iter_mut()) {
+            if rel.target_id == disciple_id {
+                rel.deceased = true;
+            }
+        }
     }
 
-    /// 增加资源
-    pub fn add_resources(&mut self, amount: u32) {
-        self.resources += amount;
+    /// 弟子领取传承库中的传承：需灵根、道心达标，继承后获得渡劫加成与起始modifier
+    pub fn inherit_heritage(&mut self, disciple_id: usize, heritage_index: usize) -> Result<Heritage, String> {
+        let heritage = self
+            .heritages
+            .get(heritage_index)
+            .ok_or_else(|| format!("传承库中没有编号为{}的传承", heritage_index))?;
+        let disciple = self
+            .disciples
+            .iter_mut()
+            .find(|d| d.id == disciple_id && d.is_alive())
+            .ok_or_else(|| format!("弟子 {} 不存在", disciple_id))?;
+        if let Some(existing) = &disciple.heritage {
+            return Err(format!("{}已身负{}", disciple.name, existing.name));
+        }
+        heritage.check_inheritor(disciple)?;
+
+        let heritage = self.heritages.remove(heritage_index);
+        disciple.modifiers.add_modifier(heritage.starting_modifier());
+        disciple.heritage = Some(heritage.clone());
+        Ok(heritage)
     }
 
-    /// 消耗资源
-    pub fn consume_resources(&mut self, amount: u32) -> bool {
-        if self.resources >= amount {
-            self.resources -= amount;
+    /// 医道弟子为同门疗伤，治愈伤势最重的几名弟子各一处伤势
+    /// 可治疗人数 = 1 + 医道资质/5，返回 (弟子ID, 治愈的伤势)
+    pub fn treat_injuries(&mut self, healer_id: usize) -> Vec<(usize, InjurySeverity)> {
+        let capacity = match self.disciples.iter().find(|d| d.id == healer_id) {
+            Some(healer) => match healer.talents.iter().find(|t| t.talent_type == TalentType::Medical) {
+                Some(talent) => 1 + talent.level as usize / 5,
+                None => return Vec::new(),
+            },
+            None => return Vec::new(),
+        };
+
+        let mut patients: Vec<&mut Disciple> = self
+            .disciples
+            .iter_mut()
+            .filter(|d| d.id != healer_id && d.is_alive() && d.is_injured())
+            .collect();
+        patients.sort_by_key(|d| std::cmp::Reverse(d.injuries.iter().map(|i| i.severity).max()));
+
+        patients
+            .into_iter()
+            .take(capacity)
+            .filter_map(|d| d.heal_worst_injury().map(|i| (d.id, i.severity)))
+            .collect()
+    }
+
+    /// 增加灵石（优先偿还负债），超出仓储上限的部分被浪费，返回浪费的数量
+    pub fn add_spirit_stones(&mut self, amount: u32) -> u32 {
+        let repaid = amount.min(self.debt);
+        self.debt -= repaid;
+        if self.debt == 0 {
+            self.debt_turns = 0;
+        }
+        let remaining = amount - repaid;
+        let stored = remaining.min(self.spirit_stone_capacity().saturating_sub(self.spirit_stones));
+        self.spirit_stones += stored;
+        remaining - stored
+    }
+
+    /// 灵石仓储上限（基础上限加上已建建筑的仓储加成）
+    pub fn spirit_stone_capacity(&self) -> u32 {
+        let bonus: f32 = self
+            .building_tree
+            .iter()
+            .flat_map(|tree| tree.buildings.values())
+            .filter(|b| b.is_built)
+            .flat_map(|b| &b.conditional_modifiers)
+            .filter(|c| c.modifier.target == ModifierTarget::StorageCapacity)
+            .map(|c| match c.modifier.application {
+                ModifierApplication::Additive(value) => value,
+                ModifierApplication::Multiplicative(value) => BASE_SPIRIT_STONE_CAPACITY as f32 * value,
+                ModifierApplication::Override(_) => 0.0,
+            })
+            .sum();
+        (BASE_SPIRIT_STONE_CAPACITY as f32 + bonus).max(0.0) as u32
+    }
+
+    /// 紧急操作透支资源，资源不足部分记为负债，超过负债上限则失败
+    pub fn overdraw_spirit_stones(&mut self, amount: u32) -> Result<(), String> {
+        let shortfall = amount.saturating_sub(self.spirit_stones);
+        if self.debt + shortfall > MAX_DEBT {
+            return Err(format!(
+                "资源不足，需要{}，当前{}，负债已达{}（上限{}）",
+                amount, self.spirit_stones, self.debt, MAX_DEBT
+            ));
+        }
+        self.spirit_stones -= amount - shortfall;
+        self.debt += shortfall;
+        Ok(())
+    }
+
+    /// 是否处于负债状态
+    pub fn in_debt(&self) -> bool {
+        self.debt > 0
+    }
+
+    /// 记录一次任务胜败（只保留最近 MORALE_HISTORY_LEN 次）
+    pub fn record_task_outcome(&mut self, success: bool) {
+        self.recent_task_outcomes.push_back(success);
+        while self.recent_task_outcomes.len() > MORALE_HISTORY_LEN {
+            self.recent_task_outcomes.pop_front();
+        }
+    }
+
+    /// 士气值：平均道心与近期胜率各占一半，负债时再扣减
+    pub fn morale_score(&self) -> i32 {
+        let alive = self.alive_disciples();
+        let average_dao_heart = if alive.is_empty() {
+            50.0
+        } else {
+            alive.iter().map(|d| d.dao_heart as f32).sum::<f32>() / alive.len() as f32
+        };
+        let win_rate = if self.recent_task_outcomes.is_empty() {
+            0.5
+        } else {
+            self.recent_task_outcomes.iter().filter(|&&s| s).count() as f32 / self.recent_task_outcomes.len() as f32
+        };
+        let debt_penalty = if self.in_debt() { DEBT_MORALE_PENALTY } else { 0 };
+        (average_dao_heart * 0.5 + win_rate * 50.0) as i32 - debt_penalty
+    }
+
+    /// 当前宗门氛围
+    pub fn morale(&self) -> SectMorale {
+        SectMorale::from_score(self.morale_score())
+    }
+
+    /// 重算宗门氛围，替换旧的氛围modifier
+    pub fn refresh_morale(&mut self) -> SectMorale {
+        self.sect_modifiers.retain(|cm| cm.modifier.source != ModifierSource::Morale);
+        let morale = self.morale();
+        if let Some(modifier) = morale.modifier() {
+            self.add_sect_modifier(modifier);
+        }
+        morale
+    }
+
+    /// 负债年度结算：扣声望、降道心，长期负债导致弟子叛离，返回叛离的弟子
+    pub fn apply_debt_penalties(&mut self) -> Vec<Disciple> {
+        if !self.in_debt() {
+            self.debt_turns = 0;
+            return Vec::new();
+        }
+
+        self.debt_turns += 1;
+        self.reputation -= DEBT_REPUTATION_PENALTY;
+        for disciple in self.disciples.iter_mut().filter(|d| d.is_alive()) {
+            disciple.dao_heart = disciple.dao_heart.saturating_sub(DEBT_DAO_HEART_PENALTY);
+        }
+
+        if self.debt_turns < DEBT_DESERTION_TURNS {
+            return Vec::new();
+        }
+        let deserter_id = self.alive_disciples().iter().min_by_key(|d| (d.dao_heart, d.id)).map(|d| d.id);
+        match deserter_id {
+            Some(id) => {
+                let deserter = self.remove_deserter(id);
+                println!("{}不堪宗门积债，叛出师门", deserter.name);
+                vec![deserter]
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// 弟子的年度叛离概率（道心越低越可能叛离，深仇大恨加剧）
+    pub fn desertion_chance(disciple: &Disciple) -> f64 {
+        let mut chance = 0.0;
+        if disciple.dao_heart < DESERTION_DAO_HEART_THRESHOLD {
+            let gap = (DESERTION_DAO_HEART_THRESHOLD - disciple.dao_heart) as f64;
+            chance += gap / DESERTION_DAO_HEART_THRESHOLD as f64 * MAX_DAO_HEART_DESERTION_CHANCE;
+        }
+        let bitter_enmity = disciple
+            .relationships
+            .iter()
+            .any(|r| r.scores.get_level(RelationDimension::Enmity) >= RelationLevel::Intimate);
+        if bitter_enmity {
+            chance += ENMITY_DESERTION_CHANCE;
+        }
+        chance
+    }
+
+    /// 叛离判定，返回叛离的弟子（已移出宗门）
+    pub fn process_desertions<R: rand::Rng>(&mut self, rng: &mut R) -> Vec<Disciple> {
+        let deserter_ids: Vec<usize> = self
+            .alive_disciples()
+            .iter()
+            .filter(|d| {
+                let chance = Self::desertion_chance(d);
+                chance > 0.0 && rng.gen_bool(chance.min(1.0))
+            })
+            .map(|d| d.id)
+            .collect();
+
+        deserter_ids
+            .into_iter()
+            .map(|id| {
+                let deserter = self.remove_deserter(id);
+                println!("{}叛出宗门", deserter.name);
+                deserter
+            })
+            .collect()
+    }
+
+    /// 将弟子移出宗门，并清除其他弟子与其的关系
+    pub fn remove_disciple(&mut self, disciple_id: usize) -> Disciple {
+        let index = self.disciples.iter().position(|d| d.id == disciple_id).unwrap();
+        let disciple = self.disciples.remove(index);
+        for other in &mut self.disciples {
+            other.relationships.retain(|r| r.target_id != disciple_id);
+        }
+        disciple
+    }
+
+    /// 叛离弟子移出宗门并载入纪念堂
+    fn remove_deserter(&mut self, disciple_id: usize) -> Disciple {
+        let deserter = self.remove_disciple(disciple_id);
+        self.fallen_disciples.push(FallenRecord::new(&deserter, DepartureCause::Desertion, self.year));
+        deserter
+    }
+
+    /// 亲传弟子名额（随建成的建筑增加）
+    pub fn personal_disciple_quota(&self) -> usize {
+        let built = self.building_tree.as_ref().map(|t| t.get_built_count()).unwrap_or(0);
+        BASE_PERSONAL_QUOTA + built / BUILDINGS_PER_PERSONAL_QUOTA + self.sect_rank().personal_quota_bonus()
+    }
+
+    /// 存活弟子中的最高修为
+    pub fn highest_cultivation(&self) -> CultivationLevel {
+        self.alive_disciples()
+            .iter()
+            .map(|d| d.cultivation.current_level)
+            .max()
+            .unwrap_or(CultivationLevel::QiRefining)
+    }
+
+    /// 宗门等级：依据最高修为、总声望、建筑数综合评定
+    pub fn sect_rank(&self) -> SectRank {
+        let highest = self.highest_cultivation();
+        let built = self.building_tree.as_ref().map(|t| t.get_built_count()).unwrap_or(0);
+        SECT_RANK_REQUIREMENTS
+            .iter()
+            .take_while(|r| {
+                highest >= r.min_cultivation && self.reputation >= r.min_reputation && built >= r.min_buildings
+            })
+            .last()
+            .map(|r| r.rank)
+            .unwrap_or(SectRank::Mortal)
+    }
+
+    /// 花费贡献点说服弟子，使其下次接受指派
+    pub fn persuade_disciple(&mut self, disciple_id: usize) -> Result<(), String> {
+        let contribution = self.contribution;
+        let disciple = self
+            .disciples
+            .iter_mut()
+            .find(|d| d.id == disciple_id && d.is_alive())
+            .ok_or_else(|| "弟子不存在".to_string())?;
+        if disciple.persuaded {
+            return Err(format!("{}已被说服", disciple.name));
+        }
+        if contribution < PERSUASION_COST {
+            return Err(format!("贡献点不足，说服需要{}贡献点，当前只有{}", PERSUASION_COST, contribution));
+        }
+        disciple.persuaded = true;
+        self.contribution -= PERSUASION_COST;
+        Ok(())
+    }
+
+    /// 晋升弟子：外门→内门→亲传，需修为达标或功劳足够并消耗贡献点，亲传受名额限制
+    pub fn promote_disciple(&mut self, disciple_id: usize) -> Result<DiscipleType, String> {
+        let contribution = self.contribution;
+        let personal_count = self
+            .alive_disciples()
+            .iter()
+            .filter(|d| d.disciple_type == DiscipleType::Personal)
+            .count();
+        let quota = self.personal_disciple_quota();
+
+        let disciple = self
+            .disciples
+            .iter_mut()
+            .find(|d| d.id == disciple_id && d.is_alive())
+            .ok_or_else(|| "弟子不存在".to_string())?;
+        let next = disciple
+            .disciple_type
+            .next()
+            .ok_or_else(|| format!("{}已是亲传弟子", disciple.name))?;
+
+        let (_, min_level, min_tasks, min_reputation) = PROMOTION_REQUIREMENTS
+            .iter()
+            .find(|(t, _, _, _)| *t == next)
+            .copied()
+            .unwrap();
+        let qualified = disciple.cultivation.current_level >= min_level
+            || disciple.tasks_completed >= min_tasks
+            || disciple.reputation_contributed >= min_reputation;
+        if !qualified {
+            return Err(format!(
+                "{}晋升{}弟子需修为达到{}，或完成{}个任务，或贡献{}声望",
+                disciple.name, next.name(), min_level, min_tasks, min_reputation
+            ));
+        }
+        if next == DiscipleType::Personal && personal_count >= quota {
+            return Err(format!("亲传弟子名额已满（{}/{}），建造更多建筑可增加名额", personal_count, quota));
+        }
+        let (_, cost) = PROMOTION_CONTRIBUTION_COSTS
+            .iter()
+            .find(|(t, _)| *t == next)
+            .copied()
+            .unwrap();
+        if contribution < cost {
+            return Err(format!("贡献点不足，晋升{}弟子需要{}贡献点，当前只有{}", next.name(), cost, contribution));
+        }
+
+        disciple.disciple_type = next;
+        self.contribution -= cost;
+        Ok(next)
+    }
+
+    /// 为弟子选择专精：首次免费，改修需消耗资源并折损道心，返回消耗的资源
+    pub fn specialize_disciple(&mut self, disciple_id: usize, specialization: Specialization) -> Result<u32, String> {
+        let spirit_stones = self.spirit_stones;
+        let disciple = self
+            .disciples
+            .iter_mut()
+            .find(|d| d.id == disciple_id && d.is_alive())
+            .ok_or_else(|| "弟子不存在".to_string())?;
+        let cost = if disciple.specialization.is_some() { RESPECIALIZE_COST } else { 0 };
+        if spirit_stones < cost {
+            return Err(format!("资源不足，改修专精需要{}资源", cost));
+        }
+        disciple.specialize(specialization)?;
+        if cost > 0 {
+            disciple.dao_heart = disciple.dao_heart.saturating_sub(RESPECIALIZE_DAO_HEART_LOSS);
+            self.spirit_stones -= cost;
+        }
+        Ok(cost)
+    }
+
+    /// 增加贡献点
+    pub fn add_contribution(&mut self, amount: u32) {
+        self.contribution += amount;
+    }
+
+    /// 消耗灵石
+    pub fn consume_spirit_stones(&mut self, amount: u32) -> bool {
+        if self.spirit_stones >= amount {
+            self.spirit_stones -= amount;
             true
         } else {
             false
@@ -280,8 +1518,8 @@ impl Sect {
         self.reputation += amount;
     }
 
-    /// 年度更新
-    pub fn yearly_update(&mut self) {
+    /// 年度更新（新生弟子的ID由招募系统统一分配）
+    pub fn yearly_update<R: rand::Rng>(&mut self, recruitment: &mut RecruitmentSystem, rng: &mut R) -> YearlyUpdate {
         self.year += 1;
 
         // 收集死亡弟子ID
@@ -291,6 +1529,8 @@ impl Sect {
         for disciple in &mut self.disciples {
             if disciple.is_alive() {
                 disciple.age_one_year();
+                // 临时modifier（如渡劫重伤）持续时间递减
+                disciple.tick_modifiers();
 
                 // 检查是否寿元耗尽
                 if !disciple.is_alive() {
@@ -305,10 +1545,90 @@ impl Sect {
 
         // 处理死亡弟子
         for id in dead_disciples {
-            self.handle_disciple_death(id);
+            self.handle_disciple_death(id, DepartureCause::Lifespan);
+        }
+
+        let estranged_companions = self.decay_relationships();
+
+        // 负债导致宗门动荡，道心崩坏或深陷仇怨的弟子可能叛离
+        let mut deserters = self.apply_debt_penalties();
+        deserters.extend(self.process_desertions(rng));
+
+        // 道侣生育
+        let newborns = self.process_births(rng, recruitment);
+
+        YearlyUpdate {
+            newborns,
+            estranged_companions,
+            deserters,
         }
     }
 
+    /// 关系年度衰减，返回貌合神离的道侣（按ID从小到大排列）
+    pub fn decay_relationships(&mut self) -> Vec<(usize, usize)> {
+        let year = self.year;
+        let mut estranged = Vec::new();
+
+        for disciple in self.disciples.iter_mut().filter(|d| d.is_alive()) {
+            for rel in &mut disciple.relationships {
+                if rel.decay(year) {
+                    let pair = (disciple.id.min(rel.target_id), disciple.id.max(rel.target_id));
+                    if !estranged.contains(&pair) {
+                        estranged.push(pair);
+                    }
+                }
+            }
+        }
+
+        estranged
+    }
+
+    /// 道侣按概率生育后代，返回新生弟子ID
+    pub fn process_births<R: rand::Rng>(&mut self, rng: &mut R, recruitment: &mut RecruitmentSystem) -> Vec<usize> {
+        let couples = self.dao_couples();
+
+        let mut newborns = Vec::new();
+        for (id1, id2) in couples {
+            let parent1 = self.disciples.iter().find(|d| d.id == id1).unwrap();
+            let parent2 = self.disciples.iter().find(|d| d.id == id2).unwrap();
+            if !rng.gen_bool(birth_chance(parent1, parent2)) {
+                continue;
+            }
+
+            let child_id = recruitment.allocate_id();
+            let child = breed_child(child_id, parent1, parent2, rng);
+            println!("👶 {}与{}喜得一子：{}", parent1.name, parent2.name, child.name);
+
+            for parent in self.disciples.iter_mut().filter(|d| d.id == id1 || d.id == id2) {
+                parent.children.push(child_id);
+            }
+            self.disciples.push(child);
+            newborns.push(child_id);
+        }
+        newborns
+    }
+
+    /// 获取弟子的父母
+    pub fn parents_of(&self, disciple_id: usize) -> Vec<&Disciple> {
+        self.disciples
+            .iter()
+            .filter(|d| d.children.contains(&disciple_id))
+            .collect()
+    }
+
+    /// 弟子排行榜（在世弟子按指定指标从高到低排列）
+    pub fn leaderboard(&self, metric: LeaderboardMetric) -> Vec<&Disciple> {
+        let mut disciples = self.alive_disciples();
+        match metric {
+            LeaderboardMetric::Resources => disciples.sort_by_key(|d| std::cmp::Reverse(d.total_resources_contributed)),
+            LeaderboardMetric::Tasks => disciples.sort_by_key(|d| std::cmp::Reverse(d.tasks_completed)),
+            LeaderboardMetric::Cultivation => disciples.sort_by_key(|d| {
+                std::cmp::Reverse((d.cultivation.current_level, d.cultivation.sub_level, d.cultivation.progress))
+            }),
+        }
+        disciples
+    }
+
     /// 获取宗门统计信息
     pub fn get_statistics(&self) -> SectStatistics {
         let alive = self.alive_disciples();
@@ -325,21 +1645,46 @@ impl Sect {
             .filter(|d| d.disciple_type == DiscipleType::Personal)
             .count();
 
-        let mut cultivation_distribution = vec![0; 7];
+        let mut cultivation_distribution = vec![0; CultivationLevel::ALL.len()];
         for disciple in &alive {
             let level_index = disciple.cultivation.current_level as usize;
             cultivation_distribution[level_index] += 1;
         }
 
+        let average = |value: fn(&Disciple) -> u32| {
+            if alive.is_empty() {
+                0.0
+            } else {
+                alive.iter().map(|d| value(d) as f32).sum::<f32>() / alive.len() as f32
+            }
+        };
+        let survival_rate = if self.disciples.is_empty() {
+            1.0
+        } else {
+            alive.len() as f32 / self.disciples.len() as f32
+        };
+
         SectStatistics {
             total_disciples: alive.len(),
             outer_disciples: outer,
             inner_disciples: inner,
             personal_disciples: personal,
-            resources: self.resources,
+            spirit_stones: self.spirit_stones,
+            spirit_stone_capacity: self.spirit_stone_capacity(),
+            contribution: self.contribution,
             reputation: self.reputation,
             year: self.year,
             cultivation_distribution,
+            debt: self.debt,
+            debt_turns: self.debt_turns,
+            average_age: average(|d| d.age),
+            average_dao_heart: average(|d| d.dao_heart),
+            survival_rate,
+            total_recruited: self.total_recruited,
+            total_deaths: self.total_deaths,
+            rank: self.sect_rank(),
+            morale_score: self.morale_score(),
+            morale: self.morale(),
         }
     }
 
@@ -432,6 +1777,21 @@ impl Sect {
             rel.is_dao_companion = true;
         }
 
+        // 情敌对夺走心上人的一方心生敌意
+        let rivals: Vec<(usize, usize)> = self.disciples.iter()
+            .filter(|d| d.id != id1 && d.id != id2 && d.is_alive())
+            .flat_map(|d| {
+                [(id1, id2), (id2, id1)].into_iter().filter_map(move |(beloved, winner)| {
+                    d.get_relationship(beloved)
+                        .filter(|rel| rel.scores.get_level(RelationDimension::Romance) >= RelationLevel::Close)
+                        .map(|_| (d.id, winner))
+                })
+            })
+            .collect();
+        for (rival_id, winner_id) in rivals {
+            self.record_rivalry(rival_id, &[winner_id]);
+        }
+
         Ok(())
     }
 
@@ -454,6 +1814,7 @@ impl Sect {
 
         if let Some(disciple) = self.disciples.iter_mut().find(|d| d.id == from_id) {
             let rel = disciple.get_or_create_relationship(to_id, year);
+            rel.record_interaction(year);
             let (_, level_up) = rel.scores.add(dimension, delta);
             Ok(level_up)
         } else {
@@ -461,6 +1822,103 @@ impl Sect {
         }
     }
 
+    /// 弟子向同门赠礼：礼物从宗门库存扣除，双方战友与情感分数提升
+    /// 受礼者对赠礼者越亲近（战友分数越高），礼物的情谊越重
+    pub fn give_gift(&mut self, from_id: usize, to_id: usize, item: &GiftItem) -> Result<GiftOutcome, String> {
+        if from_id == to_id {
+            return Err("不能向自己赠礼".to_string());
+        }
+        for id in [from_id, to_id] {
+            if !self.disciples.iter().any(|d| d.id == id && d.is_alive()) {
+                return Err(format!("弟子 {} 不存在", id));
+            }
+        }
+        let in_stock = match item {
+            GiftItem::Pill { pill_type, grade } => self.pill_inventory.get_count(*pill_type, *grade) > 0,
+            GiftItem::Herb { name, quality } => self.herb_inventory.get_count(name, *quality) > 0,
+        };
+        if !in_stock {
+            return Err(format!("宗门库存中没有{}", item.name()));
+        }
+
+        let closeness = self
+            .disciples
+            .iter()
+            .find(|d| d.id == to_id)
+            .and_then(|d| d.get_relationship(from_id))
+            .map_or(0, |r| r.scores.get(RelationDimension::Comrade));
+        let comrade_gain = (item.base_affection() as f32 * (1.0 + closeness as f32 / 100.0)).round() as i32;
+        let romance_gain = comrade_gain / 2;
+
+        match item {
+            GiftItem::Pill { pill_type, grade } => {
+                self.pill_inventory.consume(*pill_type, *grade);
+            }
+            GiftItem::Herb { name, quality } => {
+                self.herb_inventory.consume(name, *quality, 1);
+            }
+        }
+
+        let mut level_ups = Vec::new();
+        for (a, b) in [(from_id, to_id), (to_id, from_id)] {
+            for (dimension, delta) in [(RelationDimension::Comrade, comrade_gain), (RelationDimension::Romance, romance_gain)] {
+                if let Some(level) = self.update_relationship_score(a, b, dimension, delta)? {
+                    level_ups.push((a, dimension, level));
+                }
+            }
+        }
+        Ok(GiftOutcome { item_name: item.name(), comrade_gain, romance_gain, level_ups })
+    }
+
+    /// 争夺中落败的弟子对胜者产生敌意（如稀缺任务被抢、道侣争夺）
+    pub fn record_rivalry(&mut self, loser_id: usize, winner_ids: &[usize]) -> Vec<(usize, RelationLevel)> {
+        let year = self.year;
+        let growth = RelationGrowth::rivalry();
+        let mut level_ups = Vec::new();
+
+        if let Some(loser) = self.disciples.iter_mut().find(|d| d.id == loser_id && d.is_alive()) {
+            for &winner_id in winner_ids.iter().filter(|&&id| id != loser_id) {
+                let rel = loser.get_or_create_relationship(winner_id, year);
+                rel.record_interaction(year);
+                for (_, level) in growth.apply_to(&mut rel.scores) {
+                    level_ups.push((winner_id, level));
+                }
+            }
+        }
+
+        level_ups
+    }
+
+    /// 走火入魔暴走：随机波及数名同门，受害者怨恨暴走者，部分负伤
+    /// 返回 (受害者ID, 是否负伤)
+    pub fn heart_demon_rampage<R: rand::Rng>(&mut self, disciple_id: usize, rng: &mut R) -> Vec<(usize, bool)> {
+        use rand::seq::SliceRandom;
+
+        let year = self.year;
+        let candidates: Vec<usize> = self
+            .alive_disciples()
+            .iter()
+            .filter(|d| d.id != disciple_id)
+            .map(|d| d.id)
+            .collect();
+        let victims: Vec<usize> = candidates.choose_multiple(rng, RAMPAGE_MAX_VICTIMS).copied().collect();
+
+        let mut results = Vec::new();
+        for victim_id in victims {
+            let victim = self.disciples.iter_mut().find(|d| d.id == victim_id).unwrap();
+            let rel = victim.get_or_create_relationship(disciple_id, year);
+            rel.record_interaction(year);
+            rel.scores.add(RelationDimension::Enmity, RAMPAGE_ENMITY_GAIN);
+            rel.scores.add(RelationDimension::Comrade, -RAMPAGE_ENMITY_GAIN);
+            let injured = rng.gen_bool(RAMPAGE_INJURY_CHANCE);
+            if injured {
+                victim.add_injury(InjurySeverity::Minor);
+            }
+            results.push((victim_id, injured));
+        }
+        results
+    }
+
     /// 一起完成任务时更新关系
     pub fn update_relationship_from_task(
         &mut self,
@@ -483,6 +1941,7 @@ impl Sect {
 
                 if let Some(disciple) = self.disciples.iter_mut().find(|d| d.id == from_id) {
                     let rel = disciple.get_or_create_relationship(to_id, year);
+                    rel.record_interaction(year);
                     let ups = growth.apply_to(&mut rel.scores);
                     for (dim, level) in ups {
                         level_ups.push((from_id, to_id, dim, level));
@@ -529,42 +1988,141 @@ impl Sect {
     }
 }
 
+/// 年度更新的结果
+#[derive(Debug, Default)]
+pub struct YearlyUpdate {
+    pub newborns: Vec<usize>,                     // 本年出生的弟子ID
+    pub estranged_companions: Vec<(usize, usize)>, // 本年貌合神离的道侣
+    pub deserters: Vec<Disciple>,                  // 本年叛离的弟子
+}
+
+/// 同门之间的赠礼（从宗门库存中取出）
+#[derive(Debug, Clone, PartialEq)]
+pub enum GiftItem {
+    Pill { pill_type: PillType, grade: PillGrade },
+    Herb { name: String, quality: HerbQuality },
+}
+
+impl GiftItem {
+    /// 礼物名称
+    pub fn name(&self) -> String {
+        match self {
+            GiftItem::Pill { pill_type, grade } => format!("{}{}", grade.name(), pill_type.name()),
+            GiftItem::Herb { name, quality } => format!("{}{}", quality.name(), name),
+        }
+    }
+
+    /// 礼物的基础情谊（越珍贵越高）
+    pub fn base_affection(&self) -> i32 {
+        match self {
+            GiftItem::Pill { grade, .. } => match grade {
+                PillGrade::Low => 4,
+                PillGrade::Middle => 6,
+                PillGrade::High => 8,
+            },
+            GiftItem::Herb { quality, .. } => match quality {
+                HerbQuality::Common => 2,
+                HerbQuality::Uncommon => 3,
+                HerbQuality::Rare => 4,
+                HerbQuality::Epic => 6,
+                HerbQuality::Legendary => 8,
+            },
+        }
+    }
+}
+
+/// 赠礼结果
+#[derive(Debug, Clone)]
+pub struct GiftOutcome {
+    pub item_name: String,
+    pub comrade_gain: i32,
+    pub romance_gain: i32,
+    pub level_ups: Vec<(usize, RelationDimension, RelationLevel)>, // (弟子ID, 维度, 新等级)
+}
+
+/// 对弟子生效的modifier及其出处（个人 / 宗门 / 建筑名称）
+#[derive(Debug, Clone)]
+pub struct ModifierOrigin {
+    pub origin: String,
+    pub modifier: crate::modifier::Modifier,
+}
+
+/// 弟子应用全部modifier后的有效属性
+#[derive(Debug, Clone)]
+pub struct EffectiveAttributes {
+    pub dao_heart: ModifierExplanation, // 附计算明细
+    pub energy: ModifierExplanation,
+    pub constitution: ModifierExplanation,
+    pub talent_bonuses: Vec<(TalentType, f32)>, // 有加成的资质
+    pub tribulation_success_rate: f32,
+    pub modifiers: Vec<ModifierOrigin>,
+}
+
+/// 排行榜指标
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeaderboardMetric {
+    Resources,   // 累计灵石贡献
+    Tasks,       // 累计完成任务数
+    Cultivation, // 修为
+}
+
+impl LeaderboardMetric {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "resources" => Some(Self::Resources),
+            "tasks" => Some(Self::Tasks),
+            "cultivation" => Some(Self::Cultivation),
+            _ => None,
+        }
+    }
+}
+
 /// 宗门统计信息
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct SectStatistics {
     pub total_disciples: usize,
     pub outer_disciples: usize,
     pub inner_disciples: usize,
     pub personal_disciples: usize,
-    pub resources: u32,
+    pub spirit_stones: u32,
+    pub spirit_stone_capacity: u32, // 灵石仓储上限
+    pub contribution: u32,
     pub reputation: i32,
     pub year: u32,
     pub cultivation_distribution: Vec<usize>, // 各修为等级的弟子数量
+    pub debt: u32,
+    pub debt_turns: u32,
+    pub average_age: f32,       // 存活弟子平均年龄
+    pub average_dao_heart: f32, // 存活弟子平均道心
+    pub survival_rate: f32,     // 存活率（存活弟子 / 名册上的全部弟子）
+    pub total_recruited: u32,   // 本局累计招募
+    pub total_deaths: u32,      // 本局累计陨落
+    pub rank: SectRank,         // 宗门等级
+    pub morale_score: i32,      // 士气值
+    pub morale: SectMorale,     // 宗门氛围
 }
 
 impl std::fmt::Display for SectStatistics {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "=== 宗门统计 ===")?;
         writeln!(f, "年份: {}", self.year)?;
+        writeln!(f, "宗门等级: {}", self.rank.name())?;
+        writeln!(f, "宗门氛围: {} (士气{})", self.morale.name(), self.morale_score)?;
         writeln!(f, "弟子总数: {}", self.total_disciples)?;
         writeln!(f, "  外门: {}", self.outer_disciples)?;
         writeln!(f, "  内门: {}", self.inner_disciples)?;
         writeln!(f, "  亲传: {}", self.personal_disciples)?;
-        writeln!(f, "资源: {}", self.resources)?;
+        writeln!(f, "灵石: {}/{}", self.spirit_stones, self.spirit_stone_capacity)?;
+        writeln!(f, "贡献点: {}", self.contribution)?;
         writeln!(f, "声望: {}", self.reputation)?;
+        if self.debt > 0 {
+            writeln!(f, "负债: {} (已持续{}年)", self.debt, self.debt_turns)?;
+        }
+        writeln!(f, "平均年龄: {:.1}  平均道心: {:.1}", self.average_age, self.average_dao_heart)?;
+        writeln!(f, "存活率: {:.0}%  累计招募: {}  累计陨落: {}", self.survival_rate * 100.0, self.total_recruited, self.total_deaths)?;
         writeln!(f, "\n修为分布:")?;
 
-        let levels = [
-            CultivationLevel::QiRefining,
-            CultivationLevel::Foundation,
-            CultivationLevel::GoldenCore,
-            CultivationLevel::NascentSoul,
-            CultivationLevel::SpiritSevering,
-            CultivationLevel::VoidRefinement,
-            CultivationLevel::Ascension,
-        ];
-
-        for (i, level) in levels.iter().enumerate() {
+        for (i, level) in CultivationLevel::ALL.iter().enumerate() {
             if self.cultivation_distribution[i] > 0 {
                 writeln!(f, "  {}: {}", level, self.cultivation_distribution[i])?;
             }
@@ -573,3 +2131,695 @@ impl std::fmt::Display for SectStatistics {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::{GatheringTask, Task};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn parent(id: usize, name: &str, talents: Vec<(TalentType, u32)>) -> Disciple {
+        let talents = talents
+            .into_iter()
+            .map(|(talent_type, level)| Talent { talent_type, level })
+            .collect();
+        Disciple::new(id, name.to_string(), DiscipleType::Inner, talents)
+    }
+
+    #[test]
+    fn test_sell_and_buy_herbs() {
+        let mut sect = Sect::new("测试宗".to_string());
+        sect.herb_inventory.add("灵芝", HerbQuality::Rare, 3);
+        let resources = sect.spirit_stones;
+
+        assert!(sect.sell_herbs("灵芝", HerbQuality::Rare, 0).is_err());
+        assert!(sect.sell_herbs("灵芝", HerbQuality::Rare, 4).is_err());
+        assert_eq!(sect.sell_herbs("灵芝", HerbQuality::Rare, 2), Ok(100));
+        assert_eq!(sect.spirit_stones, resources + 100);
+        assert_eq!(sect.herb_inventory.get_count("灵芝", HerbQuality::Rare), 1);
+
+        // 买入价含溢价
+        assert_eq!(sect.buy_herbs("灵芝", HerbQuality::Rare, 2), Ok(150));
+        assert_eq!(sect.spirit_stones, resources - 50);
+        assert_eq!(sect.herb_inventory.get_count("灵芝", HerbQuality::Rare), 3);
+
+        sect.spirit_stones = 10;
+        assert!(sect.buy_herbs("灵芝", HerbQuality::Legendary, 1).is_err());
+        assert_eq!(sect.spirit_stones, 10);
+
+        // 数量过大时拒绝交易而非溢出
+        assert!(sect.buy_herbs("灵芝", HerbQuality::Legendary, u32::MAX).is_err());
+        assert_eq!(sect.spirit_stones, 10);
+
+        // 卖出所得先还债，库房已满时超出部分散失
+        sect.debt = 30;
+        assert_eq!(sect.sell_herbs("灵芝", HerbQuality::Rare, 1), Ok(50));
+        assert_eq!((sect.debt, sect.spirit_stones), (0, 30));
+        sect.spirit_stones = sect.spirit_stone_capacity() - 20;
+        assert_eq!(sect.sell_herbs("灵芝", HerbQuality::Rare, 1), Ok(20));
+        assert_eq!(sect.spirit_stones, sect.spirit_stone_capacity());
+    }
+
+    #[test]
+    fn test_inherited_talents_come_from_parents() {
+        let father = parent(1, "萧炎", vec![(TalentType::Fire, 8), (TalentType::Alchemy, 6)]);
+        let mother = parent(2, "薰儿", vec![(TalentType::Fire, 6), (TalentType::Metal, 5)]);
+        let mut rng = StdRng::seed_from_u64(7);
+
+        for _ in 0..50 {
+            let talents = inherit_talents(&father, &mother, &mut rng);
+            assert!(!talents.is_empty());
+            for talent in &talents {
+                assert!(matches!(
+                    talent.talent_type,
+                    TalentType::Fire | TalentType::Alchemy | TalentType::Metal
+                ));
+                assert!((1..=10).contains(&talent.level));
+            }
+        }
+    }
+
+    #[test]
+    fn test_births_record_children_on_both_parents() {
+        let mut sect = Sect::new("测试宗".to_string());
+        let mut father = parent(1, "萧炎", vec![(TalentType::Fire, 8)]);
+        let mut mother = parent(2, "薰儿", vec![(TalentType::Fire, 6)]);
+        father.dao_heart = 100;
+        mother.dao_heart = 100;
+        father.get_or_create_relationship(2, 0).is_dao_companion = true;
+        mother.get_or_create_relationship(1, 0).is_dao_companion = true;
+        sect.disciples.push(father);
+        sect.disciples.push(mother);
+
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut recruitment = RecruitmentSystem::with_next_id(3);
+        let mut newborns = Vec::new();
+        for _ in 0..200 {
+            newborns.extend(sect.process_births(&mut rng, &mut recruitment));
+            if !newborns.is_empty() {
+                break;
+            }
+        }
+
+        let child_id = newborns[0];
+        assert_eq!(child_id, 3);
+        assert!(sect.disciples.iter().filter(|d| d.id <= 2).all(|d| d.children == vec![child_id]));
+        let parents: Vec<usize> = sect.parents_of(child_id).iter().map(|d| d.id).collect();
+        assert_eq!(parents, vec![1, 2]);
+        let child = sect.disciples.iter().find(|d| d.id == child_id).unwrap();
+        assert!(child.talents.iter().all(|t| t.talent_type == TalentType::Fire));
+    }
+
+    #[test]
+    fn test_idle_relationships_decay_but_mentorship_holds() {
+        let mut sect = Sect::new("测试宗".to_string());
+        for (id, name) in [(1, "墨大夫"), (2, "韩立"), (3, "厉飞雨"), (4, "南宫婉")] {
+            sect.disciples.push(parent(id, name, vec![]));
+        }
+        sect.set_mentorship(1, 2).unwrap();
+        sect.update_relationship_score(2, 3, RelationDimension::Comrade, 40).unwrap();
+        sect.update_relationship_score(2, 4, RelationDimension::Romance, 80).unwrap();
+        sect.update_relationship_score(4, 2, RelationDimension::Romance, 80).unwrap();
+        sect.set_dao_companion(2, 4).unwrap();
+
+        let mut estranged = Vec::new();
+        for _ in 0..30 {
+            sect.year += 1;
+            estranged.extend(sect.decay_relationships());
+        }
+
+        let disciple = sect.disciples.iter().find(|d| d.id == 2).unwrap();
+        assert!(disciple.get_relationship(3).unwrap().scores.comrade < 40);
+        assert_eq!(disciple.get_relationship(1).unwrap().scores.mentorship, 50);
+        // 道侣情感衰减较慢，但久未相伴终会貌合神离
+        assert!(disciple.get_relationship(4).unwrap().scores.romance > 40);
+        assert_eq!(estranged, vec![(2, 4)]);
+    }
+
+    #[test]
+    fn test_enmity_reduces_effective_task_reward() {
+        let mut sect = Sect::new("测试宗".to_string());
+        sect.disciples.push(parent(1, "韩立", vec![]));
+        sect.disciples.push(parent(2, "王蝉", vec![]));
+        let task = Task::new(1, "采药".to_string(), TaskType::Gathering(GatheringTask {
+            resource_type: "灵草".to_string(),
+            difficulty: 20,
+        }), 50, 0);
+
+        let reward_with_partner = |sect: &Sect, id: usize| {
+            let me = sect.disciples.iter().find(|d| d.id == id).unwrap();
+            let teammates: Vec<&Disciple> = sect.disciples.iter().collect();
+            let extras = me.enmity_modifiers(&teammates);
+            me.clone().complete_task_with_modifiers(&task, &extras.iter().collect::<Vec<_>>(), &mut rand::thread_rng())
+        };
+        let baseline = reward_with_partner(&sect, 1);
+
+        // 一次争抢尚不足以结仇
+        sect.record_rivalry(2, &[1]);
+        assert_eq!(reward_with_partner(&sect, 1), baseline);
+
+        // 敌意累积到熟识等级后双方互相掣肘
+        sect.record_rivalry(2, &[1]);
+        sect.record_rivalry(2, &[1]);
+        let rel = sect.disciples[1].get_relationship(1).unwrap();
+        assert!(rel.scores.get_level(RelationDimension::Enmity) >= RelationLevel::Familiar);
+        assert_eq!(rel.get_primary_relation(), "仇敌");
+        assert!(reward_with_partner(&sect, 1) < baseline);
+        assert!(reward_with_partner(&sect, 2) < baseline);
+    }
+
+    #[test]
+    fn test_overdraft_drains_reputation_and_dao_heart_until_desertion() {
+        let mut sect = Sect::new("青云宗".to_string());
+        sect.disciples.push(parent(1, "张小凡", vec![]));
+        sect.disciples.push(parent(2, "林惊羽", vec![]));
+        sect.disciples[1].dao_heart = 60;
+        sect.spirit_stones = 100;
+
+        sect.overdraw_spirit_stones(300).unwrap();
+        assert_eq!((sect.spirit_stones, sect.debt), (0, 200));
+        assert!(sect.overdraw_spirit_stones(400).is_err());
+
+        let mut recruitment = RecruitmentSystem::new();
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut last = (sect.reputation, sect.disciples[0].dao_heart);
+        for _ in 1..DEBT_DESERTION_TURNS {
+            let update = sect.yearly_update(&mut recruitment, &mut rng);
+            assert!(update.deserters.is_empty());
+            let now = (sect.reputation, sect.disciples[0].dao_heart);
+            assert_eq!(now.0, last.0 - DEBT_REPUTATION_PENALTY);
+            assert_eq!(now.1, last.1 - DEBT_DAO_HEART_PENALTY);
+            last = now;
+        }
+        assert_eq!(sect.get_statistics().debt_turns, DEBT_DESERTION_TURNS - 1);
+
+        // 长期负债，道心最低的弟子叛离
+        let update = sect.yearly_update(&mut recruitment, &mut rng);
+        assert_eq!(update.deserters.len(), 1);
+        assert_eq!(update.deserters[0].id, 1);
+        assert!(sect.disciples.iter().all(|d| d.id != 1));
+
+        // 还清负债后动荡停止
+        sect.add_spirit_stones(250);
+        assert_eq!((sect.spirit_stones, sect.debt, sect.debt_turns), (50, 0, 0));
+        let reputation = sect.reputation;
+        sect.yearly_update(&mut recruitment, &mut rng);
+        assert_eq!(sect.reputation, reputation);
+    }
+
+    #[test]
+    fn test_talented_alchemist_refines_more_reliably_and_yields_more() {
+        use crate::building::BuildingTree;
+        use crate::config::BuildingsConfig;
+
+        let recipe = PillRecipe::all_recipes()
+            .into_iter()
+            .min_by(|a, b| a.success_rate.partial_cmp(&b.success_rate).unwrap())
+            .unwrap();
+
+        let refine_many = |talents: Vec<(TalentType, u32)>| {
+            let mut sect = Sect::new("丹鼎派".to_string());
+            let mut tree = BuildingTree::from_config(&BuildingsConfig::default_config());
+            tree.buildings.get_mut(crate::equipment::EQUIPMENT_BUILDING_ID).unwrap().is_built = true;
+            sect.init_building_tree(tree);
+            sect.recruit_disciple(parent(1, "炼丹师", talents));
+            let mut rng = StdRng::seed_from_u64(7);
+            let trials = 400;
+            sect.add_spirit_stones(recipe.resource_cost * trials);
+            sect.herb_inventory.add("灵草", recipe.required_herb_quality, recipe.required_herb_count * trials);
+
+            let rate = sect.alchemy_success_rate(&recipe, &sect.disciples[0]);
+            let mut successes = 0;
+            let mut output = 0;
+            for _ in 0..trials {
+                if let Ok((count, _)) = sect.refine_pill(recipe.pill_type, 1, None, &mut rng) {
+                    successes += 1;
+                    output += count;
+                }
+            }
+            (rate, successes, output)
+        };
+
+        let (novice_rate, novice_successes, novice_output) = refine_many(vec![]);
+        let (master_rate, master_successes, master_output) = refine_many(vec![(TalentType::Alchemy, 9)]);
+
+        assert!(master_rate > novice_rate);
+        assert!(master_successes > novice_successes);
+        assert!(master_output > master_successes, "高炼丹天赋应有超额产出");
+        assert!(master_output > novice_output);
+        assert_eq!(novice_output, novice_successes * recipe.output_count);
+    }
+
+    #[test]
+    fn test_promotion_brings_disciple_under_inner_building_modifiers() {
+        use crate::building::BuildingTree;
+        use crate::config::BuildingsConfig;
+        use crate::modifier::ModifierTarget;
+
+        let mut sect = Sect::new("天机宗".to_string());
+        let mut tree = BuildingTree::from_config(&BuildingsConfig::default_config());
+        tree.buildings.get_mut("heavenly_pavilion").unwrap().is_built = true;
+        sect.init_building_tree(tree);
+        sect.recruit_disciple(Disciple::new(1, "外门弟子".to_string(), DiscipleType::Outer, vec![]));
+        sect.recruit_disciple(Disciple::new(2, "外门弟子乙".to_string(), DiscipleType::Inner, vec![]));
+        sect.contribution = 1000;
+
+        let task_reward = |sect: &Sect| {
+            let d = &sect.disciples[0];
+            let modifiers = sect.get_applicable_modifiers_owned(d);
+            d.modifiers.calculate_effective_with_extras(&ModifierTarget::TaskReward, 100.0, &modifiers.iter().collect::<Vec<_>>())
+        };
+
+        // 功劳不足无法晋升
+        assert!(sect.promote_disciple(1).is_err());
+        assert!((task_reward(&sect) - 100.0).abs() < 1e-3);
+
+        // 立下足够功劳后晋升内门，天机阁加成开始生效
+        sect.disciples[0].tasks_completed = 10;
+        assert_eq!(sect.promote_disciple(1), Ok(DiscipleType::Inner));
+        assert!((task_reward(&sect) - 125.0).abs() < 1e-3);
+
+        // 亲传名额有限：第一位晋升占满名额
+        sect.disciples[0].cultivation.current_level = CultivationLevel::GoldenCore;
+        sect.disciples[1].cultivation.current_level = CultivationLevel::GoldenCore;
+        assert_eq!(sect.personal_disciple_quota(), 1);
+        assert_eq!(sect.promote_disciple(1), Ok(DiscipleType::Personal));
+        assert!((task_reward(&sect) - 100.0).abs() < 1e-3);
+        assert!(sect.promote_disciple(2).unwrap_err().contains("名额已满"));
+    }
+
+    #[test]
+    fn test_statistics_count_recruits_and_deaths() {
+        let mut sect = Sect::new("统计宗".to_string());
+        let mut recruitment = RecruitmentSystem::new();
+        for id in 1..=3 {
+            sect.recruit_disciple(parent(id, "弟子", vec![]));
+        }
+        sect.disciples[0].dao_heart = 80;
+        sect.disciples[1].dao_heart = 20;
+
+        // 一名弟子寿元将尽，下一年坐化
+        sect.disciples[2].age = sect.disciples[2].lifespan - 1;
+        sect.yearly_update(&mut recruitment, &mut StdRng::seed_from_u64(1));
+
+        let stats = sect.get_statistics();
+        assert_eq!(stats.total_recruited, 3);
+        assert_eq!(stats.total_deaths, 1);
+        assert_eq!(stats.total_disciples, 2);
+        assert!((stats.survival_rate - 2.0 / 3.0).abs() < 1e-4);
+        assert!((stats.average_age - 17.0).abs() < 1e-4);
+        assert_eq!(stats.cultivation_distribution[0], 2);
+
+        sect.recruit_disciple(parent(4, "新弟子", vec![]));
+        assert_eq!(sect.get_statistics().total_recruited, 4);
+    }
+
+    #[test]
+    fn test_sect_rank_combines_cultivation_reputation_and_buildings() {
+        use crate::building::BuildingTree;
+        use crate::config::BuildingsConfig;
+
+        let mut sect = Sect::new("青云门".to_string());
+        let mut tree = BuildingTree::from_config(&BuildingsConfig::default_config());
+        let ids: Vec<String> = tree.buildings.keys().cloned().collect();
+        for id in ids.iter().take(5) {
+            tree.buildings.get_mut(id).unwrap().is_built = true;
+        }
+        sect.init_building_tree(tree);
+        sect.recruit_disciple(parent(1, "掌门", vec![]));
+        sect.reputation = 600;
+        assert_eq!(sect.sect_rank(), SectRank::Mortal);
+        assert_eq!(sect.personal_disciple_quota(), 2);
+
+        // 筑基弟子+声望+建筑：一流宗门
+        sect.disciples[0].cultivation.current_level = CultivationLevel::Foundation;
+        assert_eq!(sect.sect_rank(), SectRank::FirstClass);
+        assert_eq!(sect.personal_disciple_quota(), 3);
+
+        // 凝婴弟子：超级宗门，解锁致命任务
+        sect.disciples[0].cultivation.current_level = CultivationLevel::NascentSoul;
+        assert_eq!(sect.sect_rank(), SectRank::Super);
+        assert_eq!(sect.sect_rank().max_task_risk(), RiskLevel::Deadly);
+
+        // 声望不足时修为再高也止步一流宗门
+        sect.reputation = 200;
+        assert_eq!(sect.sect_rank(), SectRank::FirstClass);
+        let next = sect.sect_rank().next_requirement().unwrap();
+        assert_eq!(next.rank, SectRank::Super);
+        assert_eq!(next.min_reputation, 500);
+
+        // 陨落的弟子不计入最高修为
+        sect.disciples[0].constitution = 0;
+        assert_eq!(sect.sect_rank(), SectRank::Mortal);
+        assert!(SectRank::Immortal.next_requirement().is_none());
+    }
+
+    #[test]
+    fn test_protection_array_raises_effective_dao_heart() {
+        use crate::building::BuildingTree;
+        use crate::config::BuildingsConfig;
+
+        let mut sect = Sect::new("护山宗".to_string());
+        sect.init_building_tree(BuildingTree::from_config(&BuildingsConfig::default_config()));
+        sect.recruit_disciple(Disciple::new(1, "守山人".to_string(), DiscipleType::Inner, vec![]));
+
+        let before = sect.effective_attributes(&sect.disciples[0]);
+        assert_eq!(before.dao_heart.effective, sect.disciples[0].dao_heart as f32);
+        assert!(before.modifiers.is_empty());
+
+        sect.building_tree.as_mut().unwrap().buildings.get_mut("protection_array").unwrap().is_built = true;
+        let after = sect.effective_attributes(&sect.disciples[0]);
+        assert!(after.dao_heart.effective > sect.disciples[0].dao_heart as f32);
+        assert_eq!(after.dao_heart.steps[0].modifier_name, "大阵庇护");
+        assert!(after.tribulation_success_rate > before.tribulation_success_rate);
+        assert!(after.modifiers.iter().any(|m| m.origin == "护宗大阵" && m.modifier.name == "大阵庇护"));
+    }
+
+    #[test]
+    fn test_low_dao_heart_disciple_falls_to_heart_demon_and_loses_progress() {
+        use crate::building::BuildingTree;
+        use crate::config::BuildingsConfig;
+        use crate::modifier::ModifierTarget;
+
+        let mut sect = Sect::new("魔门".to_string());
+        sect.init_building_tree(BuildingTree::from_config(&BuildingsConfig::default_config()));
+        let mut unstable = Disciple::new(1, "厉飞雨".to_string(), DiscipleType::Inner, vec![]);
+        unstable.dao_heart = 5;
+        unstable.cultivation.progress = 80;
+        sect.recruit_disciple(unstable);
+        sect.recruit_disciple(Disciple::new(2, "韩立".to_string(), DiscipleType::Inner, vec![]));
+        let sect_modifiers = |sect: &Sect| sect.get_applicable_modifiers_owned(&sect.disciples[0]);
+
+        // 道心坚定者不受心魔侵扰
+        assert_eq!(sect.disciples[1].heart_demon_chance(&[]), 0.0);
+
+        // 护宗大阵稳固道心，降低走火入魔的概率
+        let unprotected = sect.disciples[0].heart_demon_chance(&[]);
+        assert!(unprotected > 0.0);
+        sect.building_tree.as_mut().unwrap().buildings.get_mut("protection_array").unwrap().is_built = true;
+        let modifiers = sect_modifiers(&sect);
+        let protected = sect.disciples[0].heart_demon_chance(&modifiers.iter().collect::<Vec<_>>());
+        assert!(protected < unprotected);
+
+        // 走火入魔：修为大幅倒退并挂上心魔debuff
+        let speed_before = sect.disciples[0].modifiers.calculate_effective(&ModifierTarget::CultivationSpeed, 1.0);
+        assert_eq!(sect.disciples[0].resolve_heart_demon(1.0, &[]), None);
+        let demon = sect.disciples[0].resolve_heart_demon(0.0, &[]).unwrap();
+        assert_eq!(demon.progress_lost, 50);
+        assert!(demon.rampage);
+        assert_eq!(sect.disciples[0].cultivation.progress, 30);
+        assert!(sect.disciples[0].modifiers.calculate_effective(&ModifierTarget::CultivationSpeed, 1.0) < speed_before);
+
+        // 暴走波及同门，受害者心生怨恨
+        let victims = sect.heart_demon_rampage(1, &mut StdRng::seed_from_u64(7));
+        assert_eq!(victims.len(), 1);
+        assert_eq!(victims[0].0, 2);
+        let rel = sect.disciples[1].relationships.iter().find(|r| r.target_id == 1).unwrap();
+        assert!(rel.scores.get(RelationDimension::Enmity) > 0);
+    }
+
+    #[test]
+    fn test_batch_refine_stops_when_herbs_run_out() {
+        let mut sect = Sect::new("丹鼎派".to_string());
+        sect.recruit_disciple(Disciple::new(1, "炼丹师".to_string(), DiscipleType::Inner, vec![]));
+        let recipe = PillRecipe::for_pill(PillType::QiRecovery).unwrap();
+        sect.spirit_stones = recipe.resource_cost * 100;
+        sect.herb_inventory.add("灵草", recipe.required_herb_quality, recipe.required_herb_count * 3);
+        let stones_before = sect.spirit_stones;
+        let pills_before = sect.pill_inventory.total_count(PillType::QiRecovery);
+
+        let orders = [(PillType::QiRecovery, 10), (PillType::BodyStrength, 2)];
+        let report = sect.batch_refine(&orders, 1, &mut StdRng::seed_from_u64(5));
+
+        // 草药只够三炉左右（失败损耗减半），第一种丹药未炼完即整批停止
+        assert_eq!(report.entries.len(), 1);
+        let entry = &report.entries[0];
+        let attempts = entry.succeeded + entry.failed;
+        assert!((3..10).contains(&attempts));
+        assert!(report.stop_reason.as_deref().unwrap().contains("草药不足"));
+        assert_eq!(report.spirit_stones_spent, recipe.resource_cost * attempts);
+        assert_eq!(sect.spirit_stones, stones_before - report.spirit_stones_spent);
+        let remaining = sect.herb_inventory.total_count();
+        assert_eq!(report.herbs_consumed, recipe.required_herb_count * 3 - remaining);
+        assert!(remaining < recipe.required_herb_count);
+        assert_eq!(sect.pill_inventory.total_count(PillType::QiRecovery), pills_before + entry.output);
+
+        // 队列在草药不足时暂停但保留订单
+        sect.queue_refine(PillType::QiRecovery, 1, 2).unwrap();
+        assert!(sect.refine_from_queue(&mut StdRng::seed_from_u64(5)).unwrap().contains("暂停"));
+        assert_eq!(sect.refine_queue[0].remaining, 2);
+    }
+
+    #[test]
+    fn test_inherit_heritage_raises_tribulation_rate_and_empties_vault() {
+        use crate::cultivation::CultivationLevel;
+
+        let mut sect = Sect::new("测试宗".to_string());
+        let fire = |level| vec![Talent { talent_type: TalentType::Fire, level }];
+        let mut elder = Disciple::new(1, "炎长老".to_string(), DiscipleType::Inner, fire(8));
+        elder.cultivation.current_level = CultivationLevel::NascentSoul;
+        sect.disciples.push(elder);
+        sect.disciples.push(Disciple::new(2, "水灵儿".to_string(), DiscipleType::Outer, vec![Talent { talent_type: TalentType::Water, level: 8 }]));
+        let mut shaky = Disciple::new(3, "火娃".to_string(), DiscipleType::Outer, fire(3));
+        shaky.dao_heart = 30;
+        sect.disciples.push(shaky);
+        let mut heir = Disciple::new(4, "炎小子".to_string(), DiscipleType::Outer, fire(5));
+        heir.dao_heart = 60;
+        sect.disciples.push(heir);
+
+        sect.disciples[0].constitution = 0;
+        sect.handle_disciple_death(1, DepartureCause::Battle);
+        assert_eq!(sect.heritages.len(), 1);
+        assert_eq!(sect.heritages[0].talent, Some(TalentType::Fire));
+
+        // 灵根或道心不达标无法继承
+        assert!(sect.inherit_heritage(2, 0).unwrap_err().contains("灵根"));
+        assert!(sect.inherit_heritage(3, 0).unwrap_err().contains("道心"));
+        assert!(sect.inherit_heritage(4, 1).is_err());
+        assert_eq!(sect.heritages.len(), 1);
+
+        let rate_before = sect.disciples[3].tribulation_success_rate();
+        let speed_before = sect.disciples[3].get_effective_cultivation_speed();
+        let heritage = sect.inherit_heritage(4, 0).unwrap();
+        assert_eq!(heritage.name, "炎长老的传承");
+        assert!(sect.heritages.is_empty());
+        assert!((sect.disciples[3].tribulation_success_rate() - rate_before - heritage.tribulation_bonus).abs() < 1e-4);
+        assert!(sect.disciples[3].get_effective_cultivation_speed() > speed_before);
+
+        // 已有传承者不可再领
+        sect.heritages.push(heritage);
+        assert!(sect.inherit_heritage(4, 0).is_err());
+    }
+
+    #[test]
+    fn test_gift_raises_comrade_and_romance_for_both_sides() {
+        let mut sect = Sect::new("测试宗".to_string());
+        sect.recruit_disciple(Disciple::new(1, "许仙".to_string(), DiscipleType::Outer, vec![]));
+        sect.recruit_disciple(Disciple::new(2, "白素贞".to_string(), DiscipleType::Outer, vec![]));
+        let (a, b) = (sect.disciples[0].id, sect.disciples[1].id);
+        let score = |sect: &Sect, from: usize, to: usize, dim| {
+            sect.disciples.iter().find(|d| d.id == from).unwrap().get_relationship(to).map_or(0, |r| r.scores.get(dim))
+        };
+        let pill = GiftItem::Pill { pill_type: PillType::QiRecovery, grade: PillGrade::Low };
+        let stock_before = sect.pill_inventory.get_count(PillType::QiRecovery, PillGrade::Low);
+
+        let first = sect.give_gift(a, b, &pill).unwrap();
+        assert_eq!(sect.pill_inventory.get_count(PillType::QiRecovery, PillGrade::Low), stock_before - 1);
+        for (from, to) in [(a, b), (b, a)] {
+            assert_eq!(score(&sect, from, to, RelationDimension::Comrade), first.comrade_gain as u32);
+            assert_eq!(score(&sect, from, to, RelationDimension::Romance), first.romance_gain as u32);
+        }
+        assert!(first.romance_gain > 0);
+
+        // 交情越深，同样的礼物情谊越重
+        sect.update_relationship_score(b, a, RelationDimension::Comrade, 60).unwrap();
+        let second = sect.give_gift(a, b, &pill).unwrap();
+        assert!(second.comrade_gain > first.comrade_gain);
+        assert_eq!(
+            score(&sect, a, b, RelationDimension::Romance),
+            (first.romance_gain + second.romance_gain) as u32
+        );
+
+        // 库存中没有的礼物无法赠送
+        let herb = GiftItem::Herb { name: "灵芝".to_string(), quality: HerbQuality::Rare };
+        assert!(sect.give_gift(a, b, &herb).unwrap_err().contains("库存"));
+        sect.herb_inventory.add("灵芝", HerbQuality::Rare, 1);
+        assert!(sect.give_gift(a, b, &herb).is_ok());
+        assert_eq!(sect.herb_inventory.get_count("灵芝", HerbQuality::Rare), 0);
+        assert!(sect.give_gift(a, a, &pill).is_err());
+    }
+
+    #[test]
+    fn test_consecutive_task_failures_lower_morale_and_task_reward() {
+        let mut sect = Sect::new("测试宗".to_string());
+        sect.disciples = vec![
+            Disciple::new(1, "林动".to_string(), DiscipleType::Inner, vec![]),
+            Disciple::new(2, "小貂".to_string(), DiscipleType::Outer, vec![]),
+        ];
+        for disciple in &mut sect.disciples {
+            disciple.dao_heart = 50;
+        }
+        let task_reward = |sect: &Sect, disciple: &Disciple| {
+            let modifiers = sect.get_applicable_modifiers_owned(disciple);
+            let refs: Vec<&Modifier> = modifiers.iter().collect();
+            disciple.modifiers.calculate_effective_with_extras(&ModifierTarget::TaskReward, 1.0, &refs)
+        };
+        assert_eq!(sect.refresh_morale(), SectMorale::Steady);
+
+        // 接连失败，人心涣散，全宗任务收益下降
+        for _ in 0..MORALE_HISTORY_LEN {
+            sect.record_task_outcome(false);
+        }
+        assert_eq!(sect.refresh_morale(), SectMorale::Low);
+        for disciple in &sect.disciples {
+            assert!(task_reward(&sect, disciple) < 1.0);
+        }
+
+        // 连战连捷后旧的氛围modifier被替换，不会叠加
+        for _ in 0..MORALE_HISTORY_LEN {
+            sect.record_task_outcome(true);
+        }
+        assert_eq!(sect.refresh_morale(), SectMorale::High);
+        let morale_modifiers = sect.sect_modifiers.iter().filter(|cm| cm.modifier.source == ModifierSource::Morale).count();
+        assert_eq!(morale_modifiers, 1);
+        assert!(task_reward(&sect, &sect.disciples[0]) > 1.0);
+        assert_eq!(sect.get_statistics().morale, SectMorale::High);
+    }
+
+    #[test]
+    fn test_advanced_recipe_unlocks_after_building_alchemy_room() {
+        use crate::building::BuildingTree;
+        use crate::config::BuildingsConfig;
+
+        let mut sect = Sect::new("丹鼎派".to_string());
+        sect.init_building_tree(BuildingTree::from_config(&BuildingsConfig::default_config()));
+        sect.recruit_disciple(Disciple::new(1, "炼丹师".to_string(), DiscipleType::Inner, vec![]));
+        sect.spirit_stones = 1000;
+        sect.herb_inventory.add("灵草", HerbQuality::Common, 10);
+        sect.herb_inventory.add("灵芝", HerbQuality::Uncommon, 10);
+        let recipe = PillRecipe::for_pill(PillType::VitalityElixir).unwrap();
+        assert_eq!(recipe.tier, crate::pill::RecipeTier::Advanced);
+
+        // 基础配方无需建筑，高级配方未建炼丹房不可炼
+        assert!(sect.check_refine(PillType::QiRecovery, 1, None).is_ok());
+        assert!(!sect.is_recipe_unlocked(&recipe));
+        let err = sect.refine_pill(PillType::VitalityElixir, 1, None, &mut StdRng::seed_from_u64(1)).unwrap_err();
+        assert!(err.contains("炼丹房"));
+        assert_eq!(sect.spirit_stones, 1000);
+        assert_eq!(sect.herb_inventory.count_by_quality(HerbQuality::Uncommon), 10);
+
+        // 建成炼丹房后解锁高级配方，顶级配方仍需炼器坊
+        sect.building_tree.as_mut().unwrap().buildings.get_mut("alchemy_room").unwrap().is_built = true;
+        assert!(sect.is_recipe_unlocked(&recipe));
+        assert!(sect.check_refine(PillType::VitalityElixir, 1, None).is_ok());
+        assert!(!sect.is_recipe_unlocked(&PillRecipe::for_pill(PillType::LongevityPill).unwrap()));
+    }
+
+    #[test]
+    fn test_sword_and_alchemy_specializations_boost_their_crafts() {
+        use crate::cultivation::CultivationLevel;
+        use crate::disciple::RESPECIALIZE_COST;
+        use crate::task::{CombatTask, TaskType};
+
+        let mut sect = Sect::new("万剑宗".to_string());
+        sect.recruit_disciple(parent(1, "剑修", vec![]));
+        sect.recruit_disciple(parent(2, "丹修", vec![]));
+        sect.spirit_stones = 1000;
+
+        // 练气期尚不能选择专精
+        assert!(sect.specialize_disciple(1, Specialization::Sword).is_err());
+        for disciple in &mut sect.disciples {
+            disciple.cultivation.current_level = CultivationLevel::Foundation;
+        }
+
+        let combat = Task::new(1, "讨伐妖狼".to_string(), TaskType::Combat(CombatTask {
+            enemy_id: None,
+            enemy_name: "妖狼".to_string(),
+            enemy_level: 1,
+            difficulty: 10,
+        }), 30, 0);
+        let gathering = Task::new(2, "采集灵草".to_string(), TaskType::Gathering(GatheringTask {
+            resource_type: "灵草".to_string(),
+            difficulty: 10,
+        }), 30, 0);
+        let combat_before = sect.disciples[0].estimate_task_reward(&combat, &[]);
+        let gathering_before = sect.disciples[0].estimate_task_reward(&gathering, &[]);
+        assert_eq!(sect.specialize_disciple(1, Specialization::Sword), Ok(0));
+        assert!(sect.disciples[0].estimate_task_reward(&combat, &[]) > combat_before);
+        assert_eq!(sect.disciples[0].estimate_task_reward(&gathering, &[]), gathering_before);
+        assert!(sect.disciples[0].has_skill(&TalentType::Sword));
+
+        // 丹修提升炼丹成功率
+        let recipe = PillRecipe::for_pill(PillType::QiRecovery).unwrap();
+        let rate_before = sect.alchemy_success_rate(&recipe, &sect.disciples[1]);
+        assert_eq!(sect.specialize_disciple(2, Specialization::Alchemy), Ok(0));
+        assert!(sect.alchemy_success_rate(&recipe, &sect.disciples[1]) > rate_before);
+
+        // 改修需付出代价，旧专精的加成随之撤下
+        let dao_heart = sect.disciples[0].dao_heart;
+        assert!(sect.specialize_disciple(1, Specialization::Sword).is_err());
+        assert_eq!(sect.specialize_disciple(1, Specialization::Body), Ok(RESPECIALIZE_COST));
+        assert_eq!(sect.spirit_stones, 1000 - RESPECIALIZE_COST);
+        assert!(sect.disciples[0].dao_heart < dao_heart);
+        assert_eq!(sect.disciples[0].estimate_task_reward(&combat, &[]), combat_before);
+    }
+
+    #[test]
+    fn test_disciple_dying_of_old_age_enters_memorial() {
+        let mut sect = Sect::new("纪念宗".to_string());
+        let mut recruitment = RecruitmentSystem::new();
+        sect.recruit_disciple(parent(1, "老祖", vec![]));
+        sect.recruit_disciple(parent(2, "后辈", vec![]));
+        for disciple in &mut sect.disciples {
+            disciple.dao_heart = 80;
+        }
+        sect.disciples[0].tasks_completed = 12;
+        sect.disciples[0].age = sect.disciples[0].lifespan - 1;
+        assert!(sect.fallen_disciples.is_empty());
+
+        sect.yearly_update(&mut recruitment, &mut StdRng::seed_from_u64(1));
+
+        assert_eq!(sect.fallen_disciples.len(), 1);
+        let record = &sect.fallen_disciples[0];
+        assert_eq!(record.disciple_id, 1);
+        assert_eq!(record.cause, DepartureCause::Lifespan);
+        assert_eq!(record.year, sect.year);
+        assert_eq!(record.tasks_completed, 12);
+
+        // 纪念堂随存档保存
+        let restored: Sect = serde_json::from_str(&serde_json::to_string(&sect).unwrap()).unwrap();
+        assert_eq!(restored.fallen_disciples.len(), 1);
+        assert_eq!(restored.fallen_disciples[0].cause, DepartureCause::Lifespan);
+    }
+
+    #[test]
+    fn test_income_over_capacity_is_wasted_until_storehouse_is_built() {
+        use crate::building::BuildingTree;
+        use crate::config::BuildingsConfig;
+
+        let mut sect = Sect::new("聚宝宗".to_string());
+        sect.init_building_tree(BuildingTree::from_config(&BuildingsConfig::default_config()));
+        assert_eq!(sect.spirit_stone_capacity(), BASE_SPIRIT_STONE_CAPACITY);
+
+        // 库房将满，多出的收入无处存放
+        sect.spirit_stones = BASE_SPIRIT_STONE_CAPACITY - 200;
+        assert_eq!(sect.add_spirit_stones(500), 300);
+        assert_eq!(sect.spirit_stones, BASE_SPIRIT_STONE_CAPACITY);
+        assert_eq!(sect.add_spirit_stones(100), 100);
+
+        // 负债优先偿还，不受上限影响
+        sect.debt = 50;
+        assert_eq!(sect.add_spirit_stones(80), 30);
+        assert_eq!(sect.debt, 0);
+
+        // 建成储物库后上限提高，收入可继续入库
+        sect.building_tree.as_mut().unwrap().buildings.get_mut("storehouse").unwrap().is_built = true;
+        let capacity = sect.spirit_stone_capacity();
+        assert!(capacity > BASE_SPIRIT_STONE_CAPACITY);
+        assert_eq!(sect.add_spirit_stones(500), 0);
+        assert_eq!(sect.spirit_stones, BASE_SPIRIT_STONE_CAPACITY + 500);
+        let stats = sect.get_statistics();
+        assert_eq!((stats.spirit_stones, stats.spirit_stone_capacity), (BASE_SPIRIT_STONE_CAPACITY + 500, capacity));
+    }
+}
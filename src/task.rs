@@ -1,6 +1,25 @@
+use serde::{Deserialize, Serialize};
 use crate::disciple::TalentType;
 use crate::modifier::ModifierTarget;
-use crate::map::Position;
+use crate::map::{HerbQuality, MonsterDrop, Position};
+use crate::config::TaskTemplateConfig;
+
+/// 破除瓶颈的辅助任务名称
+pub const BOTTLENECK_INSIGHT_TASK_NAME: &str = "感悟天道";
+
+/// 任务每续期一回合消耗的资源
+pub const TASK_EXTENSION_COST_PER_TURN: u32 = 20;
+/// 单次续期的最大回合数
+pub const MAX_TASK_EXTENSION_TURNS: u32 = 10;
+/// 守卫任务续期的费用倍率
+pub const DEFENSE_TASK_EXTENSION_COST_MULTIPLIER: u32 = 2;
+
+/// 任务失败时按完成度折算的奖励比例
+pub const PARTIAL_REWARD_SHARE: f32 = 0.5;
+/// 战斗任务完成度低于此值视为惨败（完全失败）
+pub const CRUSHING_DEFEAT_COMPLETION: f32 = 0.5;
+/// 失败任务保留供重新派遣的回合数
+pub const TASK_RETRY_TURNS: u32 = 1;
 
 /// 任务资格检查结果
 #[derive(Debug, Clone)]
@@ -25,8 +44,29 @@ impl TaskEligibility {
     }
 }
 
+/// 任务风险等级
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RiskLevel {
+    Low,    // 低
+    Medium, // 中
+    High,   // 高
+    Deadly, // 致命
+}
+
+impl RiskLevel {
+    /// 按阈值划分风险：低于 thresholds[0] 为低，依次递增，超过最后一档为致命
+    fn from_thresholds(value: u32, thresholds: [u32; 3]) -> Self {
+        match thresholds.iter().position(|t| value < *t) {
+            Some(0) => Self::Low,
+            Some(1) => Self::Medium,
+            Some(2) => Self::High,
+            _ => Self::Deadly,
+        }
+    }
+}
+
 /// 任务类型
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum TaskType {
     Gathering(GatheringTask),     // 采集任务
     Combat(CombatTask),           // 战斗任务
@@ -36,50 +76,138 @@ pub enum TaskType {
 }
 
 /// 采集任务
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GatheringTask {
     pub resource_type: String,
     pub difficulty: u32,
 }
 
 /// 战斗任务
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CombatTask {
     pub enemy_id: Option<usize>,  // 怪物唯一ID（None表示势力战斗，不需要移除）
-    pub enemy_name: String,       // 怪物名称（用于显示）
+    pub enemy_name: String,       // 怪物名称（仅用于显示，识别妖魔用 enemy_id）
     pub enemy_level: u32,
     pub difficulty: u32,
 }
 
 /// 探索任务
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExplorationTask {
     pub location: String,
     pub danger_level: u32,
+    #[serde(default)]
+    pub realm_type: Option<TalentType>, // 所属秘境的灵根类型（非秘境为None）
+    #[serde(default)]
+    pub depth: u32, // 探索深度（0为浅层，越深越凶险、收获越丰厚）
+}
+
+/// 最大探索深度
+pub const MAX_EXPLORATION_DEPTH: u32 = 3;
+/// 每深入一层危险度的增幅
+const EXPLORATION_DEPTH_DANGER_STEP: f32 = 0.5;
+/// 每深入一层的奖励增幅（修为、灵石、贡献、声望）
+pub const EXPLORATION_DEPTH_REWARD_STEP: f32 = 0.5;
+/// 浅层探索的成功率
+const EXPLORATION_BASE_SUCCESS_RATE: f64 = 0.8;
+/// 深层探索失败时每层折损的寿元
+pub const EXPLORATION_DEPTH_LIFESPAN_LOSS: u32 = 5;
+
+/// 灵根不符时秘境危险度的放大倍数
+pub const REALM_MISMATCH_DANGER_MULTIPLIER: f32 = 1.5;
+/// 灵根匹配时秘境探索的额外任务奖励
+pub const REALM_AFFINITY_REWARD_BONUS: f32 = 0.3;
+
+impl ExplorationTask {
+    /// 弟子灵根是否与秘境匹配（非秘境返回None）
+    pub fn realm_affinity(&self, disciple: &crate::disciple::Disciple) -> Option<bool> {
+        self.realm_type
+            .as_ref()
+            .map(|realm| disciple.talents.iter().any(|t| &t.talent_type == realm))
+    }
+
+    /// 进入秘境所需的灵根等级（每深入一层多需一级）
+    pub fn required_talent_level(&self) -> u32 {
+        1 + self.depth
+    }
+
+    /// 弟子是否达到秘境的灵根门槛（非秘境不设门槛）
+    pub fn meets_realm_requirement(&self, disciple: &crate::disciple::Disciple) -> bool {
+        self.realm_type
+            .as_ref()
+            .map_or(true, |realm| disciple.talent_level(realm) >= self.required_talent_level())
+    }
+
+    /// 计入探索深度的危险等级
+    pub fn depth_danger_level(&self) -> u32 {
+        (self.danger_level as f32 * (1.0 + self.depth as f32 * EXPLORATION_DEPTH_DANGER_STEP)) as u32
+    }
+
+    /// 对该弟子生效的危险等级（计入深度；灵根不符时秘境更加凶险）
+    pub fn effective_danger_level(&self, disciple: &crate::disciple::Disciple) -> u32 {
+        let danger_level = self.depth_danger_level();
+        match self.realm_affinity(disciple) {
+            Some(false) => (danger_level as f32 * REALM_MISMATCH_DANGER_MULTIPLIER) as u32,
+            _ => danger_level,
+        }
+    }
+
+    /// 探索成功率：越深危险度的惩罚越大
+    pub fn success_rate(&self) -> f64 {
+        let penalty = self.depth as f64 * self.danger_level as f64 / 400.0;
+        (EXPLORATION_BASE_SUCCESS_RATE - penalty).max(0.1)
+    }
+
+    /// 深度带来的奖励倍率
+    pub fn depth_reward_multiplier(&self) -> f32 {
+        1.0 + self.depth as f32 * EXPLORATION_DEPTH_REWARD_STEP
+    }
+
+    /// 深层探索成功时的额外收获：越深越可能寻得灵草、品质越高
+    pub fn roll_depth_drop<R: rand::Rng>(&self, rng: &mut R) -> Option<MonsterDrop> {
+        if self.depth == 0 || !rng.gen_bool((self.depth as f64 * 0.25).min(1.0)) {
+            return None;
+        }
+        let quality = match self.depth {
+            1 => HerbQuality::Uncommon,
+            2 => HerbQuality::Rare,
+            _ => HerbQuality::Epic,
+        };
+        Some(MonsterDrop::Herb { name: format!("{}灵草", self.location), quality })
+    }
 }
 
 /// 辅助任务
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuxiliaryTask {
     pub task_name: String,
     pub skill_required: Option<TalentType>,
 }
 
 /// 投资任务
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InvestmentTask {
-    pub resource_cost: u32,
+    pub capital: u32,         // 投入本金（开始执行时扣除）
+    pub return_rate: f32,     // 到期收益率（0.3 表示本金的 130% 返还）
+    pub maturity_turns: u32,  // 到期回合数
     pub description: String,
 }
 
+impl InvestmentTask {
+    /// 到期结算返还的资源
+    pub fn settlement(&self) -> u32 {
+        (self.capital as f32 * (1.0 + self.return_rate)).max(0.0) as u32
+    }
+}
+
 /// 任务
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Task {
     pub id: usize,
     pub name: String,
     pub task_type: TaskType,
     pub progress_reward: u32, // 完成后获得的修为进度
-    pub resource_reward: u32, // 完成后获得的资源
+    pub resource_reward: u32, // 完成后获得的灵石（贡献点按任务类型折算）
     pub reputation_reward: i32, // 完成后获得的声望
     pub dao_heart_impact: i32,  // 对道心的影响
     pub duration: u32,          // 任务执行时间（回合数）
@@ -91,6 +219,14 @@ pub struct Task {
     pub position: Option<Position>,  // 任务主位置（用于显示）
     pub valid_positions: Option<Vec<Position>>,  // 所有有效位置（用于大型建筑，弟子在任意位置都可接取）
     pub max_participants: u32,  // 最大参与人数（1=单人任务，>1=多人任务）
+    #[serde(default)]
+    pub prerequisite_task_name: Option<String>, // 前置任务名称
+    #[serde(default)]
+    pub locked: bool,                           // 前置任务尚未完成，暂不可分配
+    #[serde(default)]
+    pub unlocks: Vec<TaskTemplateConfig>,       // 完成后解锁的后续任务
+    #[serde(default)]
+    pub retryable: bool,                        // 上次执行部分成功，可重试一次
 }
 
 impl Task {
@@ -107,7 +243,7 @@ impl Task {
             TaskType::Combat(_) => (2, 15, 10, 3),       // 战斗任务：最多3人
             TaskType::Exploration(_) => (3, 10, 5, 2),   // 探索任务：最多2人
             TaskType::Auxiliary(_) => (1, 5, 3, 1),      // 辅助任务：单人
-            TaskType::Investment(_) => (4, 3, 1, 1),     // 投资任务：单人
+            TaskType::Investment(i) => (i.maturity_turns.max(1), 3, 1, 1), // 投资任务：单人，到期回合即执行时间
         };
 
         Self {
@@ -127,6 +263,10 @@ impl Task {
             position: None,     // 默认无位置要求
             valid_positions: None,  // 默认无多位置支持
             max_participants,
+            prerequisite_task_name: None,
+            locked: false,
+            unlocks: Vec::new(),
+            retryable: false,
         }
     }
 
@@ -147,7 +287,7 @@ impl Task {
             TaskType::Combat(_) => (2, 15, 10, 3),
             TaskType::Exploration(_) => (3, 10, 5, 2),
             TaskType::Auxiliary(_) => (1, 5, 3, 1),
-            TaskType::Investment(_) => (4, 3, 1, 1),
+            TaskType::Investment(i) => (i.maturity_turns.max(1), 3, 1, 1),
         };
 
         Self {
@@ -167,6 +307,10 @@ impl Task {
             position: None,     // 默认无位置要求
             valid_positions: None,  // 默认无多位置支持
             max_participants,
+            prerequisite_task_name: None,
+            locked: false,
+            unlocks: Vec::new(),
+            retryable: false,
         }
     }
 
@@ -186,11 +330,79 @@ impl Task {
         true
     }
 
+    /// 记录模板声明的任务链（{name} 替换为地点名称），有前置任务时先锁定
+    pub fn attach_chain(&mut self, template: &TaskTemplateConfig, location_name: &str) {
+        let chain = template.with_location_name(location_name);
+        self.locked = chain.prerequisite.is_some();
+        self.prerequisite_task_name = chain.prerequisite;
+        self.unlocks = chain.unlocks;
+    }
+
     /// 检查任务是否已失效
     pub fn is_expired(&self, current_turn: u32) -> bool {
         current_turn >= self.created_turn + self.expiry_turns
     }
 
+    /// 距离失效剩余的回合数
+    pub fn remaining_turns(&self, current_turn: u32) -> u32 {
+        (self.created_turn + self.expiry_turns).saturating_sub(current_turn)
+    }
+
+    /// 失败后转为可重试：留出重新派遣并执行完毕的时间
+    pub fn open_retry(&mut self, current_turn: u32) {
+        self.retryable = true;
+        self.expiry_turns = (current_turn + TASK_RETRY_TURNS + self.duration).saturating_sub(self.created_turn);
+    }
+
+    /// 是否为守卫任务（守卫宗门、村庄、势力或秘境）
+    pub fn is_defense(&self) -> bool {
+        self.name.starts_with("守卫")
+    }
+
+    /// 续期指定回合数所需的资源（守卫任务加收）
+    pub fn extension_cost(&self, turns: u32) -> u32 {
+        let cost = turns.saturating_mul(TASK_EXTENSION_COST_PER_TURN);
+        if self.is_defense() {
+            cost.saturating_mul(DEFENSE_TASK_EXTENSION_COST_MULTIPLIER)
+        } else {
+            cost
+        }
+    }
+
+    /// 是否为守卫宗门任务
+    pub fn is_sect_defense(&self) -> bool {
+        self.location_id.as_deref() == Some(crate::map::SECT_LOCATION_ID)
+    }
+
+    /// 战斗任务所针对的妖魔ID（势力战斗与非战斗任务为 None）
+    pub fn combat_enemy_id(&self) -> Option<usize> {
+        match &self.task_type {
+            TaskType::Combat(combat) => combat.enemy_id,
+            _ => None,
+        }
+    }
+
+    /// 创建“感悟天道”辅助任务（完成后破除修为瓶颈）
+    pub fn bottleneck_insight(id: usize) -> Self {
+        let mut task = Task::new(
+            id,
+            BOTTLENECK_INSIGHT_TASK_NAME.to_string(),
+            TaskType::Auxiliary(AuxiliaryTask {
+                task_name: BOTTLENECK_INSIGHT_TASK_NAME.to_string(),
+                skill_required: None,
+            }),
+            0,
+            0,
+        );
+        task.dao_heart_impact = 5;
+        task
+    }
+
+    /// 是否为“感悟天道”任务
+    pub fn is_bottleneck_insight(&self) -> bool {
+        matches!(&self.task_type, TaskType::Auxiliary(a) if a.task_name == BOTTLENECK_INSIGHT_TASK_NAME)
+    }
+
     /// 检查弟子是否适合此任务（应用modifier后的有效判定）
     pub fn is_suitable_for_disciple(&self, disciple: &crate::disciple::Disciple) -> bool {
         self.is_suitable_for_disciple_with_sect_modifiers(disciple, &[])
@@ -202,6 +414,9 @@ impl Task {
         disciple: &crate::disciple::Disciple,
         sect_modifiers: &[&crate::modifier::Modifier],
     ) -> bool {
+        if self.locked || disciple.is_in_seclusion() || disciple.patrolling || disciple.is_exhausted() {
+            return false;
+        }
         match &self.task_type {
             TaskType::Combat(_) => {
                 // 战斗任务不再有等级限制，任何弟子都可以接受
@@ -209,23 +424,32 @@ impl Task {
                 true
             }
             TaskType::Exploration(exploration) => {
+                // 0. 秘境要求对应灵根且等级足够
+                if !exploration.meets_realm_requirement(disciple) {
+                    return false;
+                }
+
                 // 1. 获取native修为等级
                 let native_level = disciple.cultivation.current_level as u32 as f32;
 
                 // 2. 应用TaskSuitability modifier获取effective等级（包含宗门modifiers）
-                let effective_level = disciple.modifiers.calculate_effective_with_extras(
+                let effective_level = disciple.calculate_effective(
                     &ModifierTarget::TaskSuitability,
                     native_level,
                     sect_modifiers
                 ) as u32;
 
-                // 3. 检查修为是否足够应对危险
-                effective_level * 10 >= exploration.danger_level
+                // 3. 检查修为是否足够应对危险（灵根不符的秘境危险更高）
+                effective_level * 10 >= exploration.effective_danger_level(disciple)
             }
             TaskType::Auxiliary(auxiliary) => {
-                // 检查是否有对应的资质（不受modifier影响）
+                // 感悟天道只对卡在瓶颈的弟子有意义
+                if self.is_bottleneck_insight() && !disciple.cultivation.bottleneck {
+                    return false;
+                }
+                // 检查是否有对应的资质或专精（不受modifier影响）
                 if let Some(ref skill) = auxiliary.skill_required {
-                    disciple.talents.iter().any(|t| &t.talent_type == skill)
+                    disciple.has_skill(skill)
                 } else {
                     true
                 }
@@ -254,6 +478,25 @@ impl Task {
         is_already_assigned: bool,
         current_assigned_count: usize,
     ) -> TaskEligibility {
+        // 0. 检查前置任务
+        if self.locked {
+            return TaskEligibility::ineligible(&format!(
+                "需先完成前置任务「{}」",
+                self.prerequisite_task_name.as_deref().unwrap_or_default()
+            ));
+        }
+
+        // 0.5 闭关中的弟子不可接任务
+        if disciple.is_in_seclusion() {
+            return TaskEligibility::ineligible("正在闭关");
+        }
+        if disciple.patrolling {
+            return TaskEligibility::ineligible("正在巡逻");
+        }
+        if disciple.is_exhausted() {
+            return TaskEligibility::ineligible("精力耗尽，正在强制休息");
+        }
+
         // 1. 检查是否已分配
         if is_already_assigned {
             return TaskEligibility::ineligible("已接受此任务");
@@ -302,18 +545,30 @@ impl Task {
                 // 在 check_eligibility 中允许接受任务
             }
             TaskType::Exploration(exploration) => {
+                if !exploration.meets_realm_requirement(disciple) {
+                    if let Some(realm) = &exploration.realm_type {
+                        return TaskEligibility::ineligible(&format!(
+                            "需要{}达到{}级 (当前{}级)",
+                            realm.name(),
+                            exploration.required_talent_level(),
+                            disciple.talent_level(realm)
+                        ));
+                    }
+                }
+
                 let native_level = disciple.cultivation.current_level as u32 as f32;
-                let effective_level = disciple.modifiers.calculate_effective_with_extras(
+                let effective_level = disciple.calculate_effective(
                     &ModifierTarget::TaskSuitability,
                     native_level,
                     sect_modifiers
                 ) as u32;
 
-                if effective_level * 10 < exploration.danger_level {
-                    let required_level = (exploration.danger_level + 9) / 10; // 向上取整
+                let danger_level = exploration.effective_danger_level(disciple);
+                if effective_level * 10 < danger_level {
+                    let required_level = (danger_level + 9) / 10;
                     return TaskEligibility::ineligible(&format!(
                         "修为不足以应对危险 (危险等级{}, 需要约{}级, 当前{:?}={})",
-                        exploration.danger_level,
+                        danger_level,
                         required_level,
                         disciple.cultivation.current_level,
                         native_level as u32
@@ -322,8 +577,7 @@ impl Task {
             }
             TaskType::Auxiliary(auxiliary) => {
                 if let Some(ref skill) = auxiliary.skill_required {
-                    let has_skill = disciple.talents.iter().any(|t| &t.talent_type == skill);
-                    if !has_skill {
+                    if !disciple.has_skill(skill) {
                         return TaskEligibility::ineligible(&format!(
                             "需要技能: {:?}",
                             skill
@@ -337,6 +591,55 @@ impl Task {
         TaskEligibility::eligible()
     }
 
+    /// 任务产出的贡献点：按任务类型折算资源奖励（宗门事务贡献最多，投资只产灵石）
+    pub fn contribution_reward(&self) -> u32 {
+        let ratio = match &self.task_type {
+            TaskType::Auxiliary(_) => 0.6,
+            TaskType::Combat(_) => 0.4,
+            TaskType::Exploration(_) => 0.3,
+            TaskType::Gathering(_) => 0.2,
+            TaskType::Investment(_) => 0.0,
+        };
+        (self.resource_reward as f32 * ratio) as u32
+    }
+
+    /// 结算奖励（灵石、贡献点、声望）：战斗按敌我等级差倍率调整，灵石另享组队协同加成，投资按收益率结算
+    pub fn settlement_rewards(&self, reward_multiplier: f32, synergy: f32) -> (u32, u32, i32) {
+        let resources = match &self.task_type {
+            TaskType::Investment(inv) => inv.settlement(),
+            _ => (self.resource_reward as f32 * reward_multiplier * (1.0 + synergy)) as u32,
+        };
+        let contribution = (self.contribution_reward() as f32 * reward_multiplier) as u32;
+        let reputation = (self.reputation_reward as f32 * reward_multiplier) as i32;
+        (resources, contribution, reputation)
+    }
+
+    /// 失败时按完成度折算的奖励（不含声望）
+    pub fn partial_rewards(&self, reward_multiplier: f32, synergy: f32, completion: f32) -> (u32, u32, i32) {
+        let (resources, contribution, _) = self.settlement_rewards(reward_multiplier, synergy);
+        let share = completion.clamp(0.0, 1.0) * PARTIAL_REWARD_SHARE;
+        ((resources as f32 * share) as u32, (contribution as f32 * share) as u32, 0)
+    }
+
+    /// 结算时的成功率：战斗按全队（team）兵力对比整队判定；其余任务按弟子本人判定，
+    /// 组队协同提升成功率（上限95%）；投资任务到期必定结算
+    pub fn settlement_success_rate(
+        &self,
+        disciple: Option<&crate::disciple::Disciple>,
+        team: &[&crate::disciple::Disciple],
+        synergy: f32,
+        enemy_is_demon: bool,
+    ) -> f64 {
+        match &self.task_type {
+            TaskType::Investment(_) => 1.0,
+            TaskType::Combat(combat) => team_combat_success_rate(team_power_ratio(combat, team, synergy, enemy_is_demon)),
+            _ => {
+                let success_rate = disciple.map_or(0.8, |d| self.calculate_combat_success_rate(d));
+                (success_rate * (1.0 + synergy as f64)).min(0.95)
+            }
+        }
+    }
+
     /// 获取任务类型的字符串表示（用于比较）
     pub fn get_task_type_str(&self) -> &'static str {
         match &self.task_type {
@@ -367,34 +670,62 @@ impl Task {
         major_level * 4 + sub_level + 1
     }
 
-    /// 计算战斗任务的成功率
-    /// 基于弟子等级和敌人等级的差距
+    /// 计算单人执行任务的成功率
+    /// 战斗按单人兵力对比（不计成魔加成），探索按危险度
     /// 返回 0.0 到 1.0 之间的概率
     pub fn calculate_combat_success_rate(&self, disciple: &crate::disciple::Disciple) -> f64 {
         match &self.task_type {
-            TaskType::Combat(combat) => {
-                let disciple_level = Self::calculate_disciple_combat_level(disciple);
-                let enemy_level = combat.enemy_level;
-
-                // 等级差 = 弟子等级 - 敌人等级
-                // 正数表示弟子更强，负数表示敌人更强
-                let level_diff = disciple_level as i32 - enemy_level as i32;
-
-                // 基础成功率 70%
-                // 每高一级 +10%，每低一级 -15%
-                // 最低 5%，最高 95%
-                let base_rate = 0.7;
-                let rate = if level_diff >= 0 {
-                    // 弟子等级 >= 敌人等级
-                    base_rate + (level_diff as f64 * 0.10)
-                } else {
-                    // 弟子等级 < 敌人等级
-                    base_rate + (level_diff as f64 * 0.15)
-                };
+            TaskType::Combat(combat) => team_combat_success_rate(team_power_ratio(combat, &[disciple], 0.0, false)),
+            TaskType::Exploration(exploration) => exploration.success_rate(),
+            _ => 0.8, // 其他任务默认 80% 成功率
+        }
+    }
 
-                rate.clamp(0.05, 0.95)
-            }
-            _ => 0.8, // 非战斗任务默认 80% 成功率
+    /// 预估弟子单独执行此任务的成功率（与结算时的判定一致，不含组队协同）
+    /// 不满足接取条件的任务返回 0，投资任务到期必定结算
+    pub fn estimated_success_rate(&self, disciple: &crate::disciple::Disciple, enemy_is_demon: bool) -> f32 {
+        if !matches!(self.task_type, TaskType::Investment(_)) && !self.is_suitable_for_disciple(disciple) {
+            return 0.0;
+        }
+        self.settlement_success_rate(Some(disciple), &[disciple], 0.0, enemy_is_demon) as f32
+    }
+
+    /// 探索任务的深度（非探索任务返回None）
+    pub fn exploration_depth(&self) -> Option<u32> {
+        match &self.task_type {
+            TaskType::Exploration(exploration) => Some(exploration.depth),
+            _ => None,
+        }
+    }
+
+    /// 设置探索深度（仅探索任务，深度不超过 MAX_EXPLORATION_DEPTH）
+    pub fn set_exploration_depth(&mut self, depth: u32) -> Result<(), String> {
+        let TaskType::Exploration(exploration) = &mut self.task_type else {
+            return Err("只有探索任务可以指定深度".to_string());
+        };
+        if depth > MAX_EXPLORATION_DEPTH {
+            return Err(format!("探索深度最多为{}层", MAX_EXPLORATION_DEPTH));
+        }
+        exploration.depth = depth;
+        Ok(())
+    }
+
+    /// 秘境探索任务的灵根匹配情况（非秘境任务返回None）
+    pub fn realm_affinity(&self, disciple: &crate::disciple::Disciple) -> Option<bool> {
+        match &self.task_type {
+            TaskType::Exploration(exploration) => exploration.realm_affinity(disciple),
+            _ => None,
+        }
+    }
+
+    /// 任务风险等级
+    /// 战斗按敌人有效等级（练气/筑基/金丹/更高），探索按危险度，采集按难度
+    pub fn risk_level(&self) -> RiskLevel {
+        match &self.task_type {
+            TaskType::Combat(combat) => RiskLevel::from_thresholds(combat_enemy_level(combat), [4, 8, 12]),
+            TaskType::Exploration(e) => RiskLevel::from_thresholds(e.depth_danger_level(), [20, 40, 60]),
+            TaskType::Gathering(g) => RiskLevel::from_thresholds(g.difficulty, [10, 30, 60]),
+            TaskType::Auxiliary(_) | TaskType::Investment(_) => RiskLevel::Low,
         }
     }
 
@@ -412,9 +743,220 @@ impl Task {
         match &self.task_type {
             TaskType::Gathering(g) => g.difficulty,
             TaskType::Combat(c) => c.difficulty.max(c.enemy_level),  // 取战斗难度和敌人等级的最大值
-            TaskType::Exploration(e) => e.danger_level,
+            TaskType::Exploration(e) => e.depth_danger_level(),
             TaskType::Auxiliary(_) => 20,  // 辅助任务默认中等难度
             TaskType::Investment(_) => 15,  // 投资任务难度较低
         }
     }
 }
+
+/// 战斗中敌人的有效等级（取敌人等级和战斗难度的较大值）
+pub fn combat_enemy_level(combat: &CombatTask) -> u32 {
+    combat.enemy_level.max(combat.difficulty)
+}
+
+/// 计算弟子对战斗任务的胜率
+/// 基础：等级差决定（基础70%，每高一级+10%，每低一级-15%）
+/// 剑道天赋最多+10%，体魄不足时按比例削弱（体魄为0时胜率减半）
+/// 最低 5%，最高 95%
+pub fn combat_success_rate(disciple: &crate::disciple::Disciple, combat: &CombatTask) -> f32 {
+    let disciple_level = Task::calculate_disciple_combat_level(disciple);
+    let level_diff = disciple_level as i32 - combat_enemy_level(combat) as i32;
+
+    let base_rate = 0.7;
+    let level_rate = if level_diff >= 0 {
+        base_rate + level_diff as f32 * 0.10
+    } else {
+        base_rate + level_diff as f32 * 0.15
+    };
+
+    let sword_bonus = disciple.get_talent_bonus(&TalentType::Sword) * 0.1;
+    let constitution_factor = 0.5 + 0.5 * (disciple.constitution.min(100) as f32 / 100.0);
+
+    ((level_rate + sword_bonus) * constitution_factor).clamp(0.05, 0.95)
+}
+
+/// 战斗胜利后的奖励倍率
+/// 越级挑战每级+15%（最多2倍），欺负弱敌每级-10%（最低0.5倍）
+pub fn combat_reward_multiplier(disciple: &crate::disciple::Disciple, combat: &CombatTask) -> f32 {
+    let disciple_level = Task::calculate_disciple_combat_level(disciple);
+    let level_diff = combat_enemy_level(combat) as i32 - disciple_level as i32;
+
+    let multiplier = if level_diff >= 0 {
+        1.0 + level_diff as f32 * 0.15
+    } else {
+        1.0 + level_diff as f32 * 0.10
+    };
+
+    multiplier.clamp(0.5, 2.0)
+}
+
+/// 每级战斗等级折合的战力（弟子与妖魔同一标尺）
+pub const POWER_PER_COMBAT_LEVEL: f32 = 10.0;
+/// 成魔妖魔的战力加成
+pub const DEMON_POWER_BONUS: f32 = 0.5;
+/// 我方战力达到敌方的该倍数即为压倒性优势，必定速胜
+pub const OVERWHELMING_POWER_RATIO: f32 = 1.5;
+
+/// 弟子的综合战力：战斗等级折算，剑道天赋加成，体魄不足时按比例削弱
+pub fn disciple_combat_power(disciple: &crate::disciple::Disciple) -> f32 {
+    let level = Task::calculate_disciple_combat_level(disciple) as f32;
+    let sword_bonus = disciple.get_talent_bonus(&TalentType::Sword) * 0.1;
+    let constitution_factor = 0.5 + 0.5 * (disciple.constitution.min(100) as f32 / 100.0);
+    level * POWER_PER_COMBAT_LEVEL * (1.0 + sword_bonus) * constitution_factor
+}
+
+/// 敌方战力：有效等级 × 系数，成魔妖魔额外加成
+pub fn enemy_combat_power(combat: &CombatTask, is_demon: bool) -> f32 {
+    let demon_bonus = if is_demon { DEMON_POWER_BONUS } else { 0.0 };
+    combat_enemy_level(combat) as f32 * POWER_PER_COMBAT_LEVEL * (1.0 + demon_bonus)
+}
+
+/// 兵力对比：参战弟子战力之和（计入协同加成）与敌方战力之比
+pub fn team_power_ratio(combat: &CombatTask, team: &[&crate::disciple::Disciple], synergy: f32, enemy_is_demon: bool) -> f32 {
+    let team_power: f32 = team.iter().filter(|d| d.is_alive()).map(|d| disciple_combat_power(d)).sum();
+    team_power * (1.0 + synergy) / enemy_combat_power(combat, enemy_is_demon).max(1.0)
+}
+
+/// 按兵力对比（我方/敌方战力）计算的胜率
+/// 势均力敌时70%，劣势按比值平方衰减；压倒性优势必胜，否则最低5%、最高95%
+pub fn team_combat_success_rate(power_ratio: f32) -> f64 {
+    if power_ratio >= OVERWHELMING_POWER_RATIO {
+        return 1.0;
+    }
+    (0.7 * power_ratio.powi(2)).clamp(0.05, 0.95) as f64
+}
+
+/// 组队协同加成上限
+pub const MAX_TEAM_SYNERGY: f32 = 0.5;
+
+/// 多人任务的协同加成（成功率和资源奖励的百分比加成）
+/// 每对队友基础+5%，战友值越高加成越多（满值再+15%），修为每差一级-2%；
+/// 取各对的平均值乘以（人数-1），单人任务无加成
+pub fn team_synergy_bonus(disciples: &[&crate::disciple::Disciple]) -> f32 {
+    if disciples.len() < 2 {
+        return 0.0;
+    }
+
+    let mut total = 0.0;
+    let mut pairs = 0;
+    for (i, a) in disciples.iter().enumerate() {
+        for b in &disciples[i + 1..] {
+            let comrade = |from: &crate::disciple::Disciple, to_id: usize| {
+                from.get_relationship(to_id).map(|r| r.scores.comrade).unwrap_or(0) as f32
+            };
+            let comrade_score = (comrade(a, b.id) + comrade(b, a.id)) / 2.0;
+            let level_diff = (Task::calculate_disciple_combat_level(a) as i32
+                - Task::calculate_disciple_combat_level(b) as i32)
+                .abs() as f32;
+
+            let pair_bonus = 0.05 + comrade_score / 100.0 * 0.15 - level_diff * 0.02;
+            total += pair_bonus.clamp(0.0, 0.2);
+            pairs += 1;
+        }
+    }
+
+    (total / pairs as f32 * (disciples.len() - 1) as f32).min(MAX_TEAM_SYNERGY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cultivation::{CultivationLevel, SubLevel};
+    use crate::disciple::{Disciple, DiscipleType};
+
+    fn disciple_at(level: CultivationLevel, sub_level: SubLevel) -> Disciple {
+        let mut disciple = Disciple::new(0, "测试".to_string(), DiscipleType::Outer, vec![]);
+        disciple.cultivation.current_level = level;
+        disciple.cultivation.sub_level = sub_level;
+        disciple
+    }
+
+    fn combat(enemy_level: u32) -> CombatTask {
+        CombatTask {
+            enemy_id: None,
+            enemy_name: "妖狼".to_string(),
+            enemy_level,
+            difficulty: 1,
+        }
+    }
+
+    #[test]
+    fn test_combat_success_rate_by_level_diff() {
+        // 练气初期 = 1 级
+        let disciple = disciple_at(CultivationLevel::QiRefining, SubLevel::Early);
+
+        // 同级 70%
+        assert!((combat_success_rate(&disciple, &combat(1)) - 0.7).abs() < 1e-4);
+        // 低两级 40%
+        assert!((combat_success_rate(&disciple, &combat(3)) - 0.4).abs() < 1e-4);
+        // 差距过大时最低 5%
+        assert!((combat_success_rate(&disciple, &combat(20)) - 0.05).abs() < 1e-4);
+
+        // 筑基中期 = 6 级，对 3 级敌人高三级，封顶 95%
+        let strong = disciple_at(CultivationLevel::Foundation, SubLevel::Middle);
+        assert!((combat_success_rate(&strong, &combat(3)) - 0.95).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_combat_success_rate_talent_and_constitution() {
+        let mut disciple = disciple_at(CultivationLevel::QiRefining, SubLevel::Early);
+        disciple.talents.push(crate::disciple::Talent {
+            talent_type: TalentType::Sword,
+            level: 10,
+        });
+        assert!((combat_success_rate(&disciple, &combat(1)) - 0.8).abs() < 1e-4);
+
+        // 体魄为0时胜率减半
+        disciple.constitution = 0;
+        assert!((combat_success_rate(&disciple, &combat(1)) - 0.4).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_combat_reward_multiplier() {
+        let disciple = disciple_at(CultivationLevel::QiRefining, SubLevel::Early);
+        assert!((combat_reward_multiplier(&disciple, &combat(1)) - 1.0).abs() < 1e-4);
+        assert!((combat_reward_multiplier(&disciple, &combat(3)) - 1.3).abs() < 1e-4);
+        assert!((combat_reward_multiplier(&disciple, &combat(20)) - 2.0).abs() < 1e-4);
+
+        let strong = disciple_at(CultivationLevel::GoldenCore, SubLevel::Early);
+        assert!((combat_reward_multiplier(&strong, &combat(1)) - 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_team_synergy_bonus() {
+        let mut a = disciple_at(CultivationLevel::QiRefining, SubLevel::Early);
+        let mut b = disciple_at(CultivationLevel::QiRefining, SubLevel::Early);
+        a.id = 1;
+        b.id = 2;
+
+        assert_eq!(team_synergy_bonus(&[&a]), 0.0);
+        assert!((team_synergy_bonus(&[&a, &b]) - 0.05).abs() < 1e-4);
+
+        // 战友值越高加成越高
+        a.get_or_create_relationship(2, 0).scores.comrade = 100;
+        b.get_or_create_relationship(1, 0).scores.comrade = 100;
+        assert!((team_synergy_bonus(&[&a, &b]) - 0.2).abs() < 1e-4);
+
+        // 修为差距削弱加成
+        let far = disciple_at(CultivationLevel::GoldenCore, SubLevel::Early);
+        assert!(team_synergy_bonus(&[&a, &far]) < 0.05);
+    }
+
+    #[test]
+    fn test_estimated_success_rate_and_risk_level_for_combat() {
+        let task = Task::new(1, "斩妖".to_string(), TaskType::Combat(combat(6)), 10, 0);
+        assert_eq!(task.risk_level(), RiskLevel::Medium);
+
+        // 练气初期（1级）越级挑战6级妖兽，筑基圆满（7级）则稳操胜券
+        let weak = disciple_at(CultivationLevel::QiRefining, SubLevel::Early);
+        let strong = disciple_at(CultivationLevel::Foundation, SubLevel::Perfect);
+        let weak_rate = task.estimated_success_rate(&weak, false);
+        let strong_rate = task.estimated_success_rate(&strong, false);
+        assert!((weak_rate - 0.05).abs() < 1e-4);
+        assert!((strong_rate - 0.95).abs() < 1e-4);
+
+        assert_eq!(Task::new(2, "屠龙".to_string(), TaskType::Combat(combat(20)), 10, 0).risk_level(), RiskLevel::Deadly);
+        assert_eq!(Task::new(3, "驱狼".to_string(), TaskType::Combat(combat(2)), 10, 0).risk_level(), RiskLevel::Low);
+    }
+}
@@ -0,0 +1,239 @@
+use serde::{Deserialize, Serialize};
+use crate::disciple::{Disciple, TalentType};
+use crate::modifier::{
+    ConditionalModifier, Modifier, ModifierApplication, ModifierCondition, ModifierSource,
+    ModifierTarget,
+};
+
+/// 解锁功法所需的建筑
+pub const TECHNIQUE_BUILDING_ID: &str = "library";
+
+/// 藏书楼每级可供每名弟子修习的功法数
+pub const TECHNIQUES_PER_LIBRARY_LEVEL: usize = 2;
+
+/// 功法（修炼技法）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Technique {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub talent_type: TalentType,                         // 适配的灵根
+    pub cultivation_speed_bonus: f32,                    // 修炼速度加成（百分比）
+    pub task_reward_bonus: f32,                          // 任务奖励加成（百分比）
+    #[serde(default)]
+    pub learn_cost: u32,                                 // 学习消耗的贡献点
+    pub conditional_modifiers: Vec<ConditionalModifier>, // 额外的条件modifier
+}
+
+impl Technique {
+    /// 藏书楼收藏的功法
+    pub fn all_techniques() -> Vec<Technique> {
+        vec![
+            // 烈火诀：火灵根，修炼速度大幅提升
+            Technique {
+                id: "blazing_fire".to_string(),
+                name: "烈火诀".to_string(),
+                description: "以心火炼气，修炼一日千里".to_string(),
+                talent_type: TalentType::Fire,
+                cultivation_speed_bonus: 0.3,
+                task_reward_bonus: 0.0,
+                learn_cost: 120,
+                conditional_modifiers: vec![],
+            },
+            // 长春功：木灵根，修炼与任务兼顾，并缓慢滋养体魄
+            Technique {
+                id: "evergreen".to_string(),
+                name: "长春功".to_string(),
+                description: "草木长青，生生不息".to_string(),
+                talent_type: TalentType::Wood,
+                cultivation_speed_bonus: 0.15,
+                task_reward_bonus: 0.1,
+                learn_cost: 100,
+                conditional_modifiers: vec![ConditionalModifier::new(
+                    ModifierCondition::Always,
+                    Modifier::new(
+                        "长春护体",
+                        ModifierTarget::ConstitutionConsumption,
+                        ModifierApplication::Multiplicative(-0.2),
+                        ModifierSource::Technique,
+                    ),
+                )],
+            },
+            // 青莲剑歌：剑道天赋，任务奖励提升，道心坚定时再提升修炼速度
+            Technique {
+                id: "azure_lotus_sword".to_string(),
+                name: "青莲剑歌".to_string(),
+                description: "剑心通明，以剑入道".to_string(),
+                talent_type: TalentType::Sword,
+                cultivation_speed_bonus: 0.1,
+                task_reward_bonus: 0.25,
+                learn_cost: 150,
+                conditional_modifiers: vec![ConditionalModifier::new(
+                    ModifierCondition::DaoHeartGreaterThan(60),
+                    Modifier::new(
+                        "剑心通明",
+                        ModifierTarget::CultivationSpeed,
+                        ModifierApplication::Multiplicative(0.1),
+                        ModifierSource::Technique,
+                    ),
+                )],
+            },
+            // 玄水真经：水灵根，修炼稳健
+            Technique {
+                id: "mystic_water".to_string(),
+                name: "玄水真经".to_string(),
+                description: "上善若水，厚积薄发".to_string(),
+                talent_type: TalentType::Water,
+                cultivation_speed_bonus: 0.2,
+                task_reward_bonus: 0.05,
+                learn_cost: 100,
+                conditional_modifiers: vec![],
+            },
+        ]
+    }
+
+    /// 根据ID获取功法
+    pub fn find(id: &str) -> Option<Technique> {
+        Self::all_techniques().into_iter().find(|t| t.id == id)
+    }
+
+    /// 弟子是否具备适配的灵根
+    pub fn matches(&self, disciple: &Disciple) -> bool {
+        disciple.talents.iter().any(|t| t.talent_type == self.talent_type)
+    }
+
+    /// 缺少对应灵根时加成减半
+    fn scale_for(&self, disciple: &Disciple) -> f32 {
+        if self.matches(disciple) { 1.0 } else { 0.5 }
+    }
+
+    /// 计算功法对指定弟子的固定modifier（缺少对应灵根时加成减半）
+    pub fn modifiers_for(&self, disciple: &Disciple) -> Vec<Modifier> {
+        let scale = self.scale_for(disciple);
+        let mut modifiers = Vec::new();
+
+        if self.cultivation_speed_bonus != 0.0 {
+            modifiers.push(Modifier::new(
+                format!("{}：修炼", self.name),
+                ModifierTarget::CultivationSpeed,
+                ModifierApplication::Multiplicative(self.cultivation_speed_bonus * scale),
+                ModifierSource::Technique,
+            ));
+        }
+        if self.task_reward_bonus != 0.0 {
+            modifiers.push(Modifier::new(
+                format!("{}：历练", self.name),
+                ModifierTarget::TaskReward,
+                ModifierApplication::Multiplicative(self.task_reward_bonus * scale),
+                ModifierSource::Technique,
+            ));
+        }
+
+        modifiers
+    }
+
+    /// 功法的条件modifier（按灵根缩放，条件留待查询时判定）
+    pub fn conditional_modifiers_for(&self, disciple: &Disciple) -> Vec<ConditionalModifier> {
+        let scale = self.scale_for(disciple);
+        self.conditional_modifiers
+            .iter()
+            .cloned()
+            .map(|mut cm| {
+                cm.modifier.source = ModifierSource::Technique;
+                cm.modifier.application = match cm.modifier.application {
+                    ModifierApplication::Additive(v) => ModifierApplication::Additive(v * scale),
+                    ModifierApplication::Multiplicative(v) => {
+                        ModifierApplication::Multiplicative(v * scale)
+                    }
+                    ModifierApplication::Override(v) => ModifierApplication::Override(v),
+                };
+                cm
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disciple::{DiscipleType, Talent};
+
+    fn disciple_with_talent(talent_type: TalentType) -> Disciple {
+        Disciple::new(
+            1,
+            "林动".to_string(),
+            DiscipleType::Inner,
+            vec![Talent { talent_type, level: 5 }],
+        )
+    }
+
+    #[test]
+    fn test_learn_and_deactivate_changes_cultivation_speed() {
+        let mut disciple = disciple_with_talent(TalentType::Fire);
+        assert!((disciple.get_effective_cultivation_speed() - 1.0).abs() < 1e-6);
+
+        // 首部功法学会后自动激活
+        disciple.learn_technique(Technique::find("blazing_fire").unwrap()).unwrap();
+        assert!((disciple.get_effective_cultivation_speed() - 1.3).abs() < 1e-6);
+
+        assert_eq!(disciple.deactivate_technique(), Some(0));
+        assert!((disciple.get_effective_cultivation_speed() - 1.0).abs() < 1e-6);
+        assert!(disciple.current_technique().is_none());
+        assert_eq!(disciple.learned_techniques.len(), 1);
+    }
+
+    #[test]
+    fn test_switching_active_technique_recomputes_modifiers() {
+        let mut disciple = disciple_with_talent(TalentType::Fire);
+        disciple.talents.push(Talent { talent_type: TalentType::Water, level: 5 });
+        disciple.learn_technique(Technique::find("blazing_fire").unwrap()).unwrap();
+        disciple.learn_technique(Technique::find("mystic_water").unwrap()).unwrap();
+        // 已有激活功法时，新学的功法不会自动替换
+        assert_eq!(disciple.current_technique().unwrap().id, "blazing_fire");
+        assert!((disciple.get_effective_cultivation_speed() - 1.3).abs() < 1e-6);
+
+        disciple.activate_technique(1).unwrap();
+        assert_eq!(disciple.current_technique().unwrap().id, "mystic_water");
+        assert!((disciple.get_effective_cultivation_speed() - 1.2).abs() < 1e-6);
+
+        disciple.activate_technique(0).unwrap();
+        assert!((disciple.get_effective_cultivation_speed() - 1.3).abs() < 1e-6);
+        assert!(disciple.activate_technique(2).is_err());
+    }
+
+    #[test]
+    fn test_mismatched_talent_cannot_learn_and_halves_bonus() {
+        let mut disciple = disciple_with_talent(TalentType::Water);
+        let blazing_fire = Technique::find("blazing_fire").unwrap();
+        assert!(disciple.learn_technique(blazing_fire.clone()).is_err());
+        assert!(disciple.learned_techniques.is_empty());
+        assert!((disciple.get_effective_cultivation_speed() - 1.0).abs() < 1e-6);
+
+        // 缺少对应灵根时加成减半
+        let modifiers = blazing_fire.modifiers_for(&disciple);
+        assert!(matches!(modifiers[0].application, ModifierApplication::Multiplicative(v) if (v - 0.15).abs() < 1e-6));
+
+        disciple.learn_technique(Technique::find("mystic_water").unwrap()).unwrap();
+        assert!(disciple.learn_technique(Technique::find("mystic_water").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_conditional_bonus_follows_current_dao_heart() {
+        let mut disciple = disciple_with_talent(TalentType::Sword);
+        disciple.dao_heart = 80;
+        disciple.learn_technique(Technique::find("azure_lotus_sword").unwrap()).unwrap();
+        // 基础+10%，道心坚定时剑心通明再+10%
+        assert!((disciple.get_effective_cultivation_speed() - 1.21).abs() < 1e-6);
+
+        // 道心跌落后无需重新激活，剑心通明即失效
+        disciple.dao_heart = 40;
+        assert!((disciple.get_effective_cultivation_speed() - 1.1).abs() < 1e-6);
+
+        disciple.dao_heart = 70;
+        assert!((disciple.get_effective_cultivation_speed() - 1.21).abs() < 1e-6);
+
+        disciple.deactivate_technique();
+        assert!((disciple.get_effective_cultivation_speed() - 1.0).abs() < 1e-6);
+        assert!(disciple.applicable_conditional_modifiers().is_empty());
+    }
+}
@@ -0,0 +1,127 @@
+use crate::disciple::{Disciple, TalentType};
+use crate::modifier::ModifierTarget;
+use crate::task::Task;
+
+/// 每隔多少年举办一次比武大会
+pub const TOURNAMENT_INTERVAL_YEARS: u32 = 10;
+
+/// 参赛最少人数
+pub const TOURNAMENT_MIN_PARTICIPANTS: usize = 2;
+
+/// 每胜一场为宗门赢得的声望
+pub const TOURNAMENT_WIN_REPUTATION: i32 = 3;
+
+/// 每胜一场的道心提升
+pub const TOURNAMENT_WIN_DAO_HEART: i32 = 2;
+
+/// 夺魁额外的道心提升
+pub const TOURNAMENT_CHAMPION_DAO_HEART: i32 = 5;
+
+/// 胜者对败者的战友关系增长（惺惺相惜）
+pub const TOURNAMENT_WINNER_COMRADE_GAIN: i32 = 3;
+
+/// 败者对胜者的关系变化（道心坚定者心服口服，否则怀恨在心）
+pub const TOURNAMENT_LOSER_RELATION_GAIN: i32 = 5;
+
+/// 败者心服口服所需的道心
+pub const TOURNAMENT_GRACIOUS_DAO_HEART: u32 = 50;
+
+/// 比武战力：修为为主，体魄、剑道天赋与装备加成为辅
+pub fn tournament_power(disciple: &Disciple) -> f32 {
+    let cultivation = Task::calculate_disciple_combat_level(disciple) as f32 * 10.0;
+    let constitution = disciple.get_effective_constitution() / 10.0;
+    let sword = disciple.get_talent_bonus(&TalentType::Sword) * 20.0;
+    let equipment: f32 = disciple
+        .equipment
+        .values()
+        .flat_map(|e| e.modifiers.iter())
+        .filter(|m| m.target == ModifierTarget::TaskSuitability)
+        .map(|m| m.apply(0.0))
+        .sum::<f32>()
+        * 5.0;
+    (cultivation + constitution + sword + equipment).max(1.0)
+}
+
+/// 一场对决的胜率（战力平方占比，实力差距越大越难爆冷）
+pub fn duel_win_rate(power: f32, opponent_power: f32) -> f32 {
+    let (a, b) = (power * power, opponent_power * opponent_power);
+    a / (a + b)
+}
+
+/// 单场对决结果
+#[derive(Debug, Clone)]
+pub struct TournamentMatch {
+    pub winner_id: usize,
+    pub winner_name: String,
+    pub loser_id: usize,
+    pub loser_name: String,
+    pub winner_rate: f32, // 胜者赛前胜率
+}
+
+/// 参赛弟子的最终名次
+#[derive(Debug, Clone)]
+pub struct TournamentStanding {
+    pub disciple_id: usize,
+    pub name: String,
+    pub wins: u32,
+    pub power: f32,
+}
+
+/// 一届比武大会的结果
+#[derive(Debug, Clone)]
+pub struct TournamentResult {
+    pub year: u32,
+    pub matches: Vec<TournamentMatch>,
+    pub standings: Vec<TournamentStanding>, // 按名次排列
+    pub reputation_gained: i32,
+}
+
+impl TournamentResult {
+    /// 冠军
+    pub fn champion(&self) -> Option<&TournamentStanding> {
+        self.standings.first()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cultivation::CultivationLevel;
+    use crate::disciple::{Disciple, DiscipleType};
+    use crate::relationship::RelationDimension;
+    use crate::sect::Sect;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_tournament_champion_gains_dao_heart_and_rivals_relations_change() {
+        let mut sect = Sect::new("测试宗".to_string());
+        let mut champion = Disciple::new(1, "叶凡".to_string(), DiscipleType::Personal, vec![]);
+        champion.cultivation.current_level = CultivationLevel::NascentSoul;
+        let mut gracious = Disciple::new(2, "庞博".to_string(), DiscipleType::Outer, vec![]);
+        gracious.dao_heart = 80;
+        let mut bitter = Disciple::new(3, "姬皓月".to_string(), DiscipleType::Outer, vec![]);
+        bitter.dao_heart = 20;
+        sect.disciples = vec![champion, gracious, bitter];
+        let dao_heart_before = sect.disciples[0].dao_heart;
+        let reputation_before = sect.reputation;
+
+        let result = sect.hold_tournament(&[1, 2, 3], &mut StdRng::seed_from_u64(3)).unwrap();
+        assert_eq!(result.matches.len(), 3);
+        let champion = result.champion().unwrap();
+        assert_eq!(champion.disciple_id, 1);
+        assert_eq!(champion.wins, 2);
+        assert!(sect.disciples[0].dao_heart > dao_heart_before);
+        assert!(sect.reputation > reputation_before);
+
+        // 道心坚定的败者心服口服，道心浅薄的败者怀恨在心
+        let score = |from: usize, to: usize, dim| {
+            sect.disciples[from].relationships.iter().find(|r| r.target_id == to).map_or(0, |r| r.scores.get(dim))
+        };
+        assert!(score(1, 1, RelationDimension::Comrade) > 0);
+        assert!(score(2, 1, RelationDimension::Enmity) > 0);
+        assert!(score(0, 2, RelationDimension::Comrade) > 0);
+
+        // 参赛人数不足时无法举办
+        assert!(sect.hold_tournament(&[1], &mut StdRng::seed_from_u64(3)).is_err());
+    }
+}
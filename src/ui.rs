@@ -1,4 +1,6 @@
 use std::io::{self, Write};
+use crate::disciple::Disciple;
+use crate::map::{GameMap, ASCII_MAP_LEGEND};
 
 /// 用户界面工具
 pub struct UI;
@@ -107,4 +109,11 @@ impl UI {
     pub fn warning(message: &str) {
         println!("⚠️  {}", message);
     }
+
+    /// 打印字符地图
+    pub fn print_map(map: &GameMap, disciples: &[Disciple]) {
+        Self::print_subtitle(&format!("地图 {}×{}", map.width, map.height));
+        println!("{}", map.render_ascii(disciples));
+        println!("图例：{}", ASCII_MAP_LEGEND);
+    }
 }
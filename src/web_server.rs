@@ -1,82 +1,202 @@
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, Query, State,
+    },
+    body::Bytes,
+    http::{header, StatusCode},
     response::IntoResponse,
-    routing::{delete, get, post},
+    routing::{delete, get, post, put},
     Json, Router,
 };
 use dashmap::DashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tower_http::cors::{Any, CorsLayer};
 use uuid::Uuid;
 
 use crate::api_types::*;
-use crate::interactive::InteractiveGame;
+use crate::disciple::{PreferenceConflict, TaskTypePreference, TribulationOutcome, SECLUSION_COST_PER_TURN};
+use crate::interactive::{AssignStrategy, BatchAction, DiscipleFilter, GameLogEntry, GameSetup, InteractiveGame};
+use crate::event::TaskOutcome;
+use crate::map::HerbQuality;
+use crate::sect::{DepartureCause, GiftItem};
+use crate::technique::Technique;
+use crate::equipment::EquipmentSlot;
+
+/// 对局过期时间默认值（2小时未访问即清理）
+const DEFAULT_GAME_TTL: Duration = Duration::from_secs(2 * 60 * 60);
+
+/// 过期对局扫描间隔
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// 对局条目（游戏实例 + 最后访问时间）
+pub struct GameEntry {
+    pub game: Arc<tokio::sync::Mutex<InteractiveGame>>,
+    pub last_accessed: Instant,
+}
+
+impl GameEntry {
+    fn new(game: InteractiveGame) -> Self {
+        Self {
+            game: Arc::new(tokio::sync::Mutex::new(game)),
+            last_accessed: Instant::now(),
+        }
+    }
+}
 
 /// 全局游戏状态
 pub struct GameStore {
-    games: DashMap<String, Arc<tokio::sync::Mutex<InteractiveGame>>>,
+    games: DashMap<String, GameEntry>,
+    ttl: Duration, // 对局过期时间
 }
 
 impl GameStore {
-    pub fn new() -> Self {
+    /// 指定对局过期时间
+    pub fn with_ttl(ttl: Duration) -> Self {
         Self {
             games: DashMap::new(),
+            ttl,
         }
     }
 
-    pub fn create_game(&self, sect_name: String) -> String {
+    pub fn create_game(&self, sect_name: String, setup: &GameSetup) -> Result<String, String> {
         let game_id = Uuid::new_v4().to_string();
-        let game = InteractiveGame::new_with_mode(sect_name, true); // Web模式
-        self.games.insert(game_id.clone(), Arc::new(tokio::sync::Mutex::new(game)));
-        game_id
+        let game = InteractiveGame::with_setup(sect_name, true, setup)?; // Web模式
+        self.games.insert(game_id.clone(), GameEntry::new(game));
+        Ok(game_id)
     }
 
+    /// 获取对局（同时刷新最后访问时间）
     pub fn get_game(&self, game_id: &str) -> Option<Arc<tokio::sync::Mutex<InteractiveGame>>> {
-        self.games.get(game_id).map(|entry| entry.value().clone())
+        self.games.get_mut(game_id).map(|mut entry| {
+            entry.last_accessed = Instant::now();
+            entry.game.clone()
+        })
     }
 
     pub fn remove_game(&self, game_id: &str) {
         self.games.remove(game_id);
     }
+
+    /// 插入（或替换）一局游戏
+    pub fn insert_game(&self, game_id: String, game: InteractiveGame) {
+        self.games.insert(game_id, GameEntry::new(game));
+    }
+
+    /// 清理超过TTL未访问的对局（仍有实时事件订阅或正被占用的对局保留），返回清理数量
+    pub fn remove_expired(&self) -> usize {
+        let before = self.games.len();
+        let ttl = self.ttl;
+        self.games.retain(|_, entry| {
+            entry.last_accessed.elapsed() <= ttl
+                || entry.game.try_lock().map_or(true, |game| game.has_live_subscribers())
+        });
+        before - self.games.len()
+    }
+
+    /// 所有活跃对局的ID及其空闲时长
+    pub fn active_games(&self) -> Vec<(String, Duration)> {
+        self.games
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().last_accessed.elapsed()))
+            .collect()
+    }
+}
+
+/// 存档目录
+const SAVE_DIR: &str = "saves";
+
+/// 事件日志默认每页条数
+const DEFAULT_LOG_PAGE_SIZE: usize = 50;
+
+/// 事件日志每页最大条数
+const MAX_LOG_PAGE_SIZE: usize = 200;
+
+/// 获取存档文件路径（game_id 只允许字母、数字和连字符，防止路径穿越）
+fn save_path(game_id: &str) -> Option<std::path::PathBuf> {
+    if game_id.is_empty() || !game_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return None;
+    }
+    Some(std::path::Path::new(SAVE_DIR).join(format!("{}.json", game_id)))
 }
 
 pub type AppState = Arc<GameStore>;
 
 /// 创建路由
-pub fn create_router() -> Router {
-    let store = Arc::new(GameStore::new());
-
+pub fn create_router(store: AppState) -> Router {
     Router::new()
         // 版本信息
         .route("/api/version", get(get_version))
 
         // 游戏管理
+        .route("/api/games", get(list_games))
         .route("/api/game/new", post(create_game))
         .route("/api/game/:game_id", get(get_game_info))
+        .route("/api/game/:game_id/save", post(save_game))
+        .route("/api/game/:game_id/load", post(load_game))
+        .route("/api/game/:game_id/config/reload", post(reload_config))
 
         // 回合管理
         .route("/api/game/:game_id/turn/start", post(start_turn))
         .route("/api/game/:game_id/turn/end", post(end_turn))
+        .route("/api/game/:game_id/turn/undo", post(undo_turn))
 
         // 弟子管理
         .route("/api/game/:game_id/disciples", get(get_disciples))
         .route("/api/game/:game_id/disciples/:disciple_id", get(get_disciple))
+        .route("/api/game/:game_id/disciples/batch", post(batch_disciple_action))
+        .route("/api/game/:game_id/formation/move", post(move_formation))
+        .route("/api/game/:game_id/disciples/gift", post(give_gift))
         .route("/api/game/:game_id/recruit", post(recruit_disciple))
+        .route("/api/game/:game_id/recruit/pool", get(get_recruitment_pool))
+        .route("/api/game/:game_id/recruit/refresh", post(refresh_recruitment_pool))
         .route("/api/game/:game_id/disciples/:disciple_id/move", post(move_disciple))
+        .route("/api/game/:game_id/disciples/:disciple_id/heal", post(heal_disciple))
+        .route("/api/game/:game_id/disciples/:disciple_id/technique", post(equip_technique))
+        .route("/api/game/:game_id/disciples/:disciple_id/effective", get(get_disciple_effective_attributes))
+        .route("/api/game/:game_id/disciples/:disciple_id/techniques", get(get_disciple_techniques))
+        .route("/api/game/:game_id/disciples/:disciple_id/techniques/learn", post(learn_technique))
+        .route("/api/game/:game_id/techniques", get(get_techniques))
+        .route("/api/game/:game_id/disciples/:disciple_id/equip", post(equip_disciple))
+        .route("/api/game/:game_id/disciples/:disciple_id/promote", post(promote_disciple))
+        .route("/api/game/:game_id/disciples/:disciple_id/persuade", post(persuade_disciple))
+        .route("/api/game/:game_id/disciples/:disciple_id/specialize", post(specialize_disciple))
+        .route("/api/game/:game_id/disciples/:disciple_id/seclusion", post(enter_seclusion))
+        .route("/api/game/:game_id/disciples/:disciple_id/seclusion", delete(end_seclusion))
+        .route("/api/game/:game_id/disciples/:disciple_id/patrol", post(start_patrol))
+        .route("/api/game/:game_id/disciples/:disciple_id/patrol", delete(end_patrol))
+        .route("/api/game/:game_id/disciples/:disciple_id/preference", put(set_task_preference))
+        .route("/api/game/:game_id/heritages", get(get_heritages))
+        .route("/api/game/:game_id/achievements", get(get_achievements))
+        .route("/api/game/:game_id/disciples/:disciple_id/inherit", post(inherit_heritage))
+        .route("/api/game/:game_id/equipment", get(get_equipment))
+        .route("/api/game/:game_id/tournament", post(hold_tournament))
 
         // 任务管理
         .route("/api/game/:game_id/tasks", get(get_tasks))
         .route("/api/game/:game_id/tasks/:task_id/assign", post(assign_task))
         .route("/api/game/:game_id/tasks/:task_id/assign", delete(unassign_task))
+        .route("/api/game/:game_id/tasks/:task_id/extend", post(extend_task))
+        .route("/api/game/:game_id/tasks/:task_id/preview", get(preview_task_reward))
         .route("/api/game/:game_id/tasks/auto-assign", post(auto_assign_tasks))
+        .route("/api/game/:game_id/tasks/batch-assign", post(batch_assign_tasks))
         .route("/api/game/:game_id/tasks/check-eligibility", post(check_task_eligibility))
 
         // 统计信息
         .route("/api/game/:game_id/statistics", get(get_statistics))
+        .route("/api/game/:game_id/leaderboard", get(get_leaderboard))
+        .route("/api/game/:game_id/income", get(get_income))
+        .route("/api/game/:game_id/memorial", get(get_memorial))
+        .route("/api/game/:game_id/log", get(get_event_log))
+        .route("/api/game/:game_id/validate", get(validate_game))
+        .route("/api/game/:game_id/ws", get(live_events_ws))
 
         // 地图
         .route("/api/game/:game_id/map", get(get_map))
+        .route("/api/game/:game_id/map/export", get(export_map))
+        .route("/api/game/:game_id/map/render", get(render_map))
+        .route("/api/game/:game_id/map/import", post(import_map))
 
         // 渡劫
         .route("/api/game/:game_id/tribulation/candidates", get(get_tribulation_candidates))
@@ -88,8 +208,12 @@ pub fn create_router() -> Router {
 
         // 草药和炼丹
         .route("/api/game/:game_id/herbs", get(get_herb_inventory))
+        .route("/api/game/:game_id/herbs/sell", post(sell_herbs))
+        .route("/api/game/:game_id/herbs/buy", post(buy_herbs))
         .route("/api/game/:game_id/recipes", get(get_all_recipes))
         .route("/api/game/:game_id/refine", post(refine_pill))
+        .route("/api/game/:game_id/refine/batch", post(batch_refine))
+        .route("/api/game/:game_id/refine/queue", get(get_refine_queue).post(queue_refine).delete(clear_refine_queue))
 
         // 建筑
         .route("/api/game/:game_id/buildings", get(get_building_tree))
@@ -97,6 +221,7 @@ pub fn create_router() -> Router {
 
         // 关系系统
         .route("/api/game/:game_id/disciples/:disciple_id/relationships", get(get_disciple_relationships))
+        .route("/api/game/:game_id/disciples/:disciple_id/family", get(get_disciple_family))
         .route("/api/game/:game_id/relationships", get(get_all_relationships))
         .route("/api/game/:game_id/relationships/mentorship", post(set_mentorship))
         .route("/api/game/:game_id/relationships/dao-companion", post(set_dao_companion))
@@ -111,7 +236,28 @@ pub fn create_router() -> Router {
 
 /// 启动服务器
 pub async fn start_server() {
-    let app = create_router();
+    // 对局过期时间可通过 GAME_TTL_SECS 环境变量配置
+    let ttl = std::env::var("GAME_TTL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_GAME_TTL);
+    let store = Arc::new(GameStore::with_ttl(ttl));
+
+    // 后台定时清理过期对局
+    let cleanup_store = store.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CLEANUP_INTERVAL);
+        loop {
+            interval.tick().await;
+            let removed = cleanup_store.remove_expired();
+            if removed > 0 {
+                println!("🧹 清理了 {} 个过期对局", removed);
+            }
+        }
+    });
+
+    let app = create_router(store);
 
     let addr = "0.0.0.0:3000".parse().unwrap();
 
@@ -135,12 +281,45 @@ async fn get_version() -> impl IntoResponse {
     (StatusCode::OK, Json(ApiResponse::ok(response)))
 }
 
+/// 获取活跃对局列表
+async fn list_games(State(store): State<AppState>) -> impl IntoResponse {
+    let games: Vec<ActiveGameDto> = store
+        .active_games()
+        .into_iter()
+        .map(|(game_id, idle)| ActiveGameDto {
+            game_id,
+            idle_seconds: idle.as_secs(),
+        })
+        .collect();
+
+    let response = ActiveGamesResponse {
+        count: games.len(),
+        games,
+    };
+    (StatusCode::OK, Json(ApiResponse::ok(response)))
+}
+
 /// 创建新游戏
 async fn create_game(
     State(store): State<AppState>,
     Json(req): Json<CreateGameRequest>,
 ) -> impl IntoResponse {
-    let game_id = store.create_game(req.sect_name.clone());
+    let defaults = GameSetup::default();
+    let setup = GameSetup {
+        map_width: req.map_width.unwrap_or(defaults.map_width),
+        map_height: req.map_height.unwrap_or(defaults.map_height),
+        initial_disciples: req.initial_disciples.unwrap_or(defaults.initial_disciples),
+        starting_resources: req.starting_resources.unwrap_or(defaults.starting_resources),
+    };
+    let game_id = match store.create_game(req.sect_name.clone(), &setup) {
+        Ok(game_id) => game_id,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<GameInfoResponse>::error("INVALID_GAME_SETUP".to_string(), e)),
+            );
+        }
+    };
 
     if let Some(game) = store.get_game(&game_id) {
         let game = game.lock().await;
@@ -185,6 +364,145 @@ async fn get_game_info(
     }
 }
 
+/// 保存游戏存档
+async fn save_game(
+    State(store): State<AppState>,
+    Path(game_id): Path<String>,
+) -> impl IntoResponse {
+    let path = match save_path(&game_id) {
+        Some(path) => path,
+        None => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<SaveGameResponse>::error(
+                    "INVALID_GAME_ID".to_string(),
+                    "无效的游戏ID".to_string(),
+                )),
+            );
+        }
+    };
+
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let game = game_mutex.lock().await;
+        match game.save_to_file(&path) {
+            Ok(()) => {
+                let response = SaveGameResponse {
+                    game_id: game_id.clone(),
+                    path: path.display().to_string(),
+                    year: game.sect.year,
+                };
+                (StatusCode::OK, Json(ApiResponse::ok(response)))
+            }
+            Err(e) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::<SaveGameResponse>::error(
+                    "SAVE_FAILED".to_string(),
+                    format!("保存存档失败: {}", e),
+                )),
+            ),
+        }
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<SaveGameResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+/// 读取游戏存档（对局不在内存中时会重新创建）
+async fn load_game(
+    State(store): State<AppState>,
+    Path(game_id): Path<String>,
+) -> impl IntoResponse {
+    let path = match save_path(&game_id) {
+        Some(path) => path,
+        None => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<GameInfoResponse>::error(
+                    "INVALID_GAME_ID".to_string(),
+                    "无效的游戏ID".to_string(),
+                )),
+            );
+        }
+    };
+
+    if !path.exists() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<GameInfoResponse>::error(
+                "SAVE_NOT_FOUND".to_string(),
+                "存档不存在".to_string(),
+            )),
+        );
+    }
+
+    let loaded = match InteractiveGame::load_from_file(&path) {
+        Ok(game) => game,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::<GameInfoResponse>::error(
+                    "LOAD_FAILED".to_string(),
+                    format!("读取存档失败: {}", e),
+                )),
+            );
+        }
+    };
+
+    let response = GameInfoResponse {
+        game_id: game_id.clone(),
+        sect: (&loaded.sect).into(),
+        state: format!("{:?}", loaded.state),
+    };
+
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let mut game = game_mutex.lock().await;
+        *game = loaded;
+    } else {
+        store.insert_game(game_id, loaded);
+    }
+
+    (StatusCode::OK, Json(ApiResponse::ok(response)))
+}
+
+/// 从磁盘重新加载配置并应用到对局（配置无效时返回全部错误，不做任何修改）
+async fn reload_config(
+    State(store): State<AppState>,
+    Path(game_id): Path<String>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let (config, buildings) = match crate::config::ConfigManager::reload_from_disk() {
+            Ok(loaded) => loaded,
+            Err(errors) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(ApiResponse::<ConfigReloadResponse>::error_with_details(
+                        "INVALID_CONFIG".to_string(),
+                        format!("配置校验失败，共 {} 处错误", errors.len()),
+                        serde_json::json!(errors).to_string(),
+                    )),
+                );
+            }
+        };
+
+        let mut game = game_mutex.lock().await;
+        let report = game.with_write(|game| game.apply_config_reload(config, &buildings));
+        (StatusCode::OK, Json(ApiResponse::ok(ConfigReloadResponse::from(report))))
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<ConfigReloadResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
 /// 开始新回合
 async fn start_turn(
     State(store): State<AppState>,
@@ -194,60 +512,31 @@ async fn start_turn(
         let mut game = game_mutex.lock().await;
 
         // 开始回合
-        game.start_turn();
+        let log_start = game.event_log.len();
+        game.with_write(|game| game.start_turn());
 
-        // 收集事件（简化版）
-        let events = vec![
-            GameEventDto {
-                event_type: "Income".to_string(),
-                message: format!("年度收入"),
-            },
-        ];
+        // 本回合开始时产生的事件
+        let events: Vec<GameEventDto> = game.event_log[log_start..]
+            .iter()
+            .map(|entry| GameEventDto {
+                event_type: format!("{:?}", entry.category),
+                message: entry.message.clone(),
+            })
+            .collect();
 
         // 获取任务和弟子
         let current_turn = game.sect.year;
+        let matcher = game.task_matcher();
         let tasks: Vec<TaskDto> = game.current_tasks
             .iter()
             .map(|task| {
                 let assignment = game.task_assignments.iter().find(|a| a.task_id == task.id);
                 let progress = assignment.map(|a| a.progress).unwrap_or(0);
                 let assigned_to = assignment.map(|a| a.disciple_ids.clone()).unwrap_or_default();
-                let remaining_turns = if task.created_turn + task.expiry_turns > current_turn {
-                    task.created_turn + task.expiry_turns - current_turn
-                } else {
-                    0
-                };
-
-                // 找出适合该任务的弟子
-                let mut free_disciples = Vec::new();
-                let mut busy_disciples = Vec::new();
-
-                for disciple in &game.sect.disciples {
-                    // 检查弟子是否适合该任务（技能和修为检查）
-                    if task.is_suitable_for_disciple(disciple) {
-                        // 检查弟子是否在任务位置（如果任务有位置要求）
-                        let is_at_location = if let Some(task_pos) = &task.position {
-                            disciple.position.x == task_pos.x && disciple.position.y == task_pos.y
-                        } else {
-                            true // 没有位置要求的任务，所有弟子都可以
-                        };
-
-                        if !is_at_location {
-                            continue; // 弟子不在任务位置，跳过
-                        }
-
-                        // 检查弟子是否正在执行其他任务
-                        let is_busy = game.task_assignments.iter().any(|a|
-                            a.disciple_ids.contains(&disciple.id) && a.task_id != task.id
-                        );
+                let remaining_turns = task.remaining_turns(current_turn);
 
-                        if is_busy {
-                            busy_disciples.push(disciple.id);
-                        } else {
-                            free_disciples.push(disciple.id);
-                        }
-                    }
-                }
+                // 找出适合该任务的弟子（空闲 / 忙碌）
+                let candidates = matcher.candidates(task);
 
                 // 提取敌人信息（如果是战斗任务）
                 let enemy_info = if let crate::task::TaskType::Combat(combat_task) = &task.task_type {
@@ -283,14 +572,20 @@ async fn start_turn(
                     constitution_cost: task.constitution_cost,
                     skill_required: task.get_skill_required(),
                     suitable_disciples: SuitableDisciples {
-                        free: free_disciples,
-                        busy: busy_disciples,
+                        free: candidates.free,
+                        busy: candidates.busy,
                     },
                     enemy_info,
                     position: task.position.as_ref().map(|p| PositionDto { x: p.x, y: p.y }),
                     valid_positions: task.valid_positions.as_ref().map(|positions|
                         positions.iter().map(|p| PositionDto { x: p.x, y: p.y }).collect()
                     ),
+                    risk_level: format!("{:?}", task.risk_level()),
+                    estimated_success_rate: None,
+                    prerequisite_task_name: task.prerequisite_task_name.clone(),
+                    locked: task.locked,
+                    retryable: task.retryable,
+                    exploration_depth: task.exploration_depth(),
                 }
             })
             .collect();
@@ -301,8 +596,8 @@ async fn start_turn(
             .map(|d| (*d).into())
             .collect();
 
-        // 获取待招募弟子信息
-        let pending_recruitment = game.pending_recruitment.as_ref().map(|d| d.into());
+        // 获取招募池
+        let recruitment_pool = RecruitCandidateDto::from_pool(&game.recruitment_pool);
 
         // 获取宗门袭击状态
         let sect_invasion = game.map.get_sect_invasion().map(|inv| SectInvasionDto {
@@ -316,8 +611,9 @@ async fn start_turn(
             events,
             tasks,
             disciples,
-            pending_recruitment,
+            recruitment_pool,
             sect_invasion,
+            harvest: format!("{:?}", game.map.harvest),
         };
 
         (StatusCode::OK, Json(ApiResponse::ok(response)))
@@ -332,6 +628,129 @@ async fn start_turn(
     }
 }
 
+/// 升级为 WebSocket，实时推送本局关键事件（突破、渡劫、陨落、妖魔入侵）
+async fn live_events_ws(
+    State(store): State<AppState>,
+    Path(game_id): Path<String>,
+    ws: WebSocketUpgrade,
+) -> axum::response::Response {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        // 在升级前订阅，避免握手期间漏掉事件；订阅后立即释放游戏锁
+        let receiver = game_mutex.lock().await.subscribe_live_events();
+        ws.on_upgrade(move |socket| forward_live_events(socket, receiver))
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<()>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+            .into_response()
+    }
+}
+
+/// 把订阅到的事件逐条转发给客户端，客户端断开或对局被删除时结束（接收端随之丢弃，自动取消订阅）
+async fn forward_live_events(
+    mut socket: WebSocket,
+    mut receiver: tokio::sync::broadcast::Receiver<GameLogEntry>,
+) {
+    use tokio::sync::broadcast::error::RecvError;
+
+    loop {
+        tokio::select! {
+            event = receiver.recv() => {
+                let entry = match event {
+                    Ok(entry) => entry,
+                    // 客户端处理过慢丢失了部分事件，继续推送后续事件
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                };
+                let frame = match serde_json::to_string(&entry) {
+                    Ok(frame) => frame,
+                    Err(_) => continue,
+                };
+                if socket.send(Message::Text(frame)).await.is_err() {
+                    break;
+                }
+            }
+            incoming = socket.recv() => {
+                match incoming {
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => {}
+                }
+            }
+        }
+    }
+}
+
+/// 分页查询事件日志
+async fn get_event_log(
+    State(store): State<AppState>,
+    Path(game_id): Path<String>,
+    Query(query): Query<EventLogQuery>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let game = game_mutex.lock().await;
+
+        let since_year = query.since_year.unwrap_or(0);
+        let page_size = query.page_size.unwrap_or(DEFAULT_LOG_PAGE_SIZE).clamp(1, MAX_LOG_PAGE_SIZE);
+        let page = query.page.unwrap_or(1).max(1);
+
+        let matched: Vec<&GameLogEntry> = game
+            .event_log
+            .iter()
+            .filter(|entry| entry.year >= since_year)
+            .collect();
+
+        let response = EventLogResponse {
+            total: matched.len(),
+            page,
+            page_size,
+            entries: matched
+                .into_iter()
+                .skip((page - 1) * page_size)
+                .take(page_size)
+                .map(|entry| entry.into())
+                .collect(),
+        };
+
+        (StatusCode::OK, Json(ApiResponse::ok(response)))
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<EventLogResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+/// 运行完整一致性检查，返回发现的问题
+async fn validate_game(
+    State(store): State<AppState>,
+    Path(game_id): Path<String>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let game = game_mutex.lock().await;
+        let issues = game.validate();
+        let response = ValidateResponse {
+            valid: issues.is_empty(),
+            issues,
+        };
+        (StatusCode::OK, Json(ApiResponse::ok(response)))
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<ValidateResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
 /// 结束回合
 async fn end_turn(
     State(store): State<AppState>,
@@ -342,13 +761,14 @@ async fn end_turn(
         let mut game = game_mutex.lock().await;
 
         // 执行任务并收集结果
-        let task_results = game.execute_turn();
+        let summary = game.with_write(|game| game.execute_turn());
 
         // 检查游戏状态
         let _is_running = game.check_game_state();
 
         // 转换任务结果为DTO
-        let results: Vec<TaskResultDto> = task_results
+        let results: Vec<TaskResultDto> = summary
+            .task_results
             .iter()
             .map(|result| {
                 let message = if result.disciple_died {
@@ -359,6 +779,12 @@ async fn end_turn(
                         result.progress_gained,
                         result.resources_gained,
                         result.reputation_gained)
+                } else if result.outcome == TaskOutcome::PartialSuccess {
+                    format!("{} 任务部分成功（完成度{:.0}%），获得修为+{}, 资源+{}",
+                        result.disciple_name,
+                        result.completion * 100.0,
+                        result.progress_gained,
+                        result.resources_gained)
                 } else {
                     format!("{} 执行任务失败", result.disciple_name)
                 };
@@ -368,7 +794,9 @@ async fn end_turn(
                     disciple_id: result.disciple_id,
                     disciple_name: result.disciple_name.clone(),
                     success: result.success,
-                    rewards: if result.success {
+                    outcome: result.outcome.name().to_string(),
+                    completion: result.completion,
+                    rewards: if result.outcome != TaskOutcome::Failure {
                         Some(TaskRewards {
                             progress: result.progress_gained,
                             resources: result.resources_gained,
@@ -379,12 +807,17 @@ async fn end_turn(
                     },
                     message,
                     disciple_died: result.disciple_died,
+                    combat: result.combat.as_ref().map(CombatDetailDto::from),
+                    team_size: result.team_size,
+                    synergy_bonus: result.synergy_bonus,
+                    drops: result.drops.iter().map(MonsterDropDto::from).collect(),
                 }
             })
             .collect();
 
         let response = TurnEndResponse {
             results,
+            summary: TurnSummaryDto::from(&summary),
             game_state: format!("{:?}", game.state),
         };
 
@@ -400,6 +833,42 @@ async fn end_turn(
     }
 }
 
+/// 撤销上一回合
+async fn undo_turn(
+    State(store): State<AppState>,
+    Path(game_id): Path<String>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let mut game = game_mutex.lock().await;
+
+        match game.with_write(|game| game.undo_turn()) {
+            Ok(()) => {
+                let response = UndoTurnResponse {
+                    year: game.sect.year,
+                    resources: game.sect.spirit_stones,
+                    undo_remaining: game.undo_available(),
+                };
+                (StatusCode::OK, Json(ApiResponse::ok(response)))
+            }
+            Err(e) => (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<UndoTurnResponse>::error(
+                    "NO_TURN_HISTORY".to_string(),
+                    e,
+                )),
+            ),
+        }
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<UndoTurnResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
 /// 获取所有弟子
 async fn get_disciples(
     State(store): State<AppState>,
@@ -416,16 +885,16 @@ async fn get_disciples(
 
         // 填充当前任务信息
         for disciple_dto in &mut disciples {
-            // 查找弟子的任务分配
-            if let Some(assignment) = game.task_assignments.iter().find(|a| a.contains_disciple(disciple_dto.id)) {
-                if let Some(task) = game.current_tasks.iter().find(|t| t.id == assignment.task_id) {
-                    disciple_dto.current_task_info = Some(CurrentTaskInfo {
-                        task_id: task.id,
-                        task_name: task.name.clone(),
-                        duration: task.duration,
-                        progress: assignment.progress,
-                    });
-                }
+            // 查找弟子的任务分配（含远征的前往与返程）
+            if let Some(status) = game.disciple_task_status(disciple_dto.id) {
+                disciple_dto.current_task_info = Some(CurrentTaskInfo {
+                    task_id: status.task_id,
+                    task_name: status.task_name,
+                    duration: status.duration,
+                    progress: status.progress,
+                    phase: status.phase.name().to_string(),
+                    estimated_remaining_turns: status.remaining_turns,
+                });
             }
         }
 
@@ -472,74 +941,43 @@ async fn get_disciple(
     }
 }
 
-/// 招募弟子（接受或拒绝）
+/// 从招募池招募一名候选弟子（按候选标价扣除灵石，可多次招募直到资源耗尽或池清空）
 async fn recruit_disciple(
     State(store): State<AppState>,
     Path(game_id): Path<String>,
     Json(req): Json<RecruitDiscipleRequest>,
 ) -> impl IntoResponse {
-    const RECRUITMENT_COST: u32 = 1000;
-
     if let Some(game_mutex) = store.get_game(&game_id) {
         let mut game = game_mutex.lock().await;
 
-        // 检查是否有待招募的弟子
-        if let Some(disciple) = game.pending_recruitment.take() {
-            if req.accept {
-                // 检查资源是否足够
-                let resources_before = game.sect.resources;
-                if resources_before < RECRUITMENT_COST {
-                    // 资源不足，放回pending
-                    game.pending_recruitment = Some(disciple);
-                    return (
-                        StatusCode::BAD_REQUEST,
-                        Json(ApiResponse::<RecruitDiscipleResponse>::error(
-                            "INSUFFICIENT_RESOURCES".to_string(),
-                            format!("资源不足，需要{}资源", RECRUITMENT_COST),
-                        )),
-                    );
-                }
-
-                // 扣除资源
-                game.sect.resources -= RECRUITMENT_COST;
-                let resources_after = game.sect.resources;
-
-                // 添加弟子
-                let disciple_dto: DiscipleDto = (&disciple).into();
-                game.sect.recruit_disciple(disciple);
+        if req.candidate_index >= game.recruitment_pool.len() {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<RecruitDiscipleResponse>::error(
+                    "NO_PENDING_RECRUITMENT".to_string(),
+                    "招募池中没有该候选".to_string(),
+                )),
+            );
+        }
 
+        let resources_before = game.sect.spirit_stones;
+        match game.recruit_candidate(req.candidate_index) {
+            Ok((disciple_id, cost)) => {
+                let disciple = game.sect.disciples.iter().find(|d| d.id == disciple_id).map(DiscipleDto::from);
                 let response = RecruitDiscipleResponse {
                     success: true,
-                    message: format!("成功招募弟子「{}」", disciple_dto.name),
-                    disciple: Some(disciple_dto),
+                    message: format!("成功招募弟子「{}」", disciple.as_ref().map(|d| d.name.as_str()).unwrap_or_default()),
+                    disciple,
                     resources_before,
-                    resources_after,
-                    cost: RECRUITMENT_COST,
-                };
-
-                (StatusCode::OK, Json(ApiResponse::ok(response)))
-            } else {
-                // 用户拒绝招募
-                let response = RecruitDiscipleResponse {
-                    success: true,
-                    message: "已拒绝招募".to_string(),
-                    disciple: None,
-                    resources_before: game.sect.resources,
-                    resources_after: game.sect.resources,
-                    cost: 0,
+                    resources_after: game.sect.spirit_stones,
+                    cost,
                 };
-
                 (StatusCode::OK, Json(ApiResponse::ok(response)))
             }
-        } else {
-            // 没有待招募的弟子
-            (
+            Err(msg) => (
                 StatusCode::BAD_REQUEST,
-                Json(ApiResponse::<RecruitDiscipleResponse>::error(
-                    "NO_PENDING_RECRUITMENT".to_string(),
-                    "当前没有待招募的弟子".to_string(),
-                )),
-            )
+                Json(ApiResponse::<RecruitDiscipleResponse>::error("INSUFFICIENT_RESOURCES".to_string(), msg)),
+            ),
         }
     } else {
         (
@@ -552,143 +990,1242 @@ async fn recruit_disciple(
     }
 }
 
-/// 移动弟子
+fn recruitment_pool_response(game: &InteractiveGame) -> RecruitmentPoolResponse {
+    RecruitmentPoolResponse {
+        candidates: RecruitCandidateDto::from_pool(&game.recruitment_pool),
+        refresh_cost: crate::event::RECRUITMENT_POOL_REFRESH_COST,
+        resources: game.sect.spirit_stones,
+    }
+}
+
+/// GET /api/game/:game_id/recruit/pool - 查看招募池
+async fn get_recruitment_pool(
+    State(store): State<AppState>,
+    Path(game_id): Path<String>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let game = game_mutex.lock().await;
+        (StatusCode::OK, Json(ApiResponse::ok(recruitment_pool_response(&game))))
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<RecruitmentPoolResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+/// POST /api/game/:game_id/recruit/refresh - 花费灵石刷新招募池
+async fn refresh_recruitment_pool(
+    State(store): State<AppState>,
+    Path(game_id): Path<String>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let mut game = game_mutex.lock().await;
+        match game.reroll_recruitment_pool() {
+            Ok(()) => (StatusCode::OK, Json(ApiResponse::ok(recruitment_pool_response(&game)))),
+            Err(msg) => (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<RecruitmentPoolResponse>::error("INSUFFICIENT_RESOURCES".to_string(), msg)),
+            ),
+        }
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<RecruitmentPoolResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+/// 移动弟子
 async fn move_disciple(
     State(store): State<AppState>,
     Path((game_id, disciple_id)): Path<(String, usize)>,
-    Json(req): Json<MoveDiscipleRequest>,
+    Json(req): Json<MoveDiscipleRequest>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let mut game = game_mutex.lock().await;
+
+        let to = crate::map::Position { x: req.x, y: req.y };
+        match game.with_write(|game| game.move_disciple(disciple_id, to)) {
+            Ok(outcome) => {
+                let collected_herb = outcome.collected_herb.map(|herb| CollectedHerbInfo {
+                    name: herb.name,
+                    quality: herb.quality.name().to_string(),
+                });
+                let opportunity = outcome
+                    .opportunity
+                    .map(|encounter| format!("{}：{}", encounter.name, encounter.gain.describe()));
+                let mut message = if let Some(ref herb) = collected_herb {
+                    format!("{}已移动至({}, {})，采集了{}({})", outcome.disciple_name, req.x, req.y, herb.name, herb.quality)
+                } else if let Some(herb) = outcome.immature_herb {
+                    format!(
+                        "{}已移动至({}, {})，{}尚未成熟（{}/{}），无法采集",
+                        outcome.disciple_name, req.x, req.y, herb.name, herb.growth_stage, herb.max_growth
+                    )
+                } else {
+                    format!("{}已移动至({}, {})", outcome.disciple_name, req.x, req.y)
+                };
+                if let Some(ref opportunity) = opportunity {
+                    message.push_str(&format!("，觅得机缘「{}」", opportunity));
+                }
+
+                let response = MoveDiscipleResponse {
+                    success: true,
+                    message,
+                    disciple_id,
+                    disciple_name: outcome.disciple_name,
+                    old_position: PositionDto { x: outcome.from.x, y: outcome.from.y },
+                    new_position: PositionDto { x: req.x, y: req.y },
+                    moves_remaining: outcome.moves_remaining,
+                    collected_herb,
+                    opportunity,
+                };
+
+                (StatusCode::OK, Json(ApiResponse::ok(response)))
+            }
+            Err(error) => {
+                let status = if error.code == "DISCIPLE_NOT_FOUND" {
+                    StatusCode::NOT_FOUND
+                } else {
+                    StatusCode::BAD_REQUEST
+                };
+                (
+                    status,
+                    Json(ApiResponse::<MoveDiscipleResponse>::error(error.code.to_string(), error.message)),
+                )
+            }
+        }
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<MoveDiscipleResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+/// 编队移动：多名弟子集结到目标位置及其周围
+async fn move_formation(
+    State(store): State<AppState>,
+    Path(game_id): Path<String>,
+    Json(req): Json<FormationMoveRequest>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let mut game = game_mutex.lock().await;
+
+        let target = crate::map::Position { x: req.target.x, y: req.target.y };
+        let results: Vec<FormationMoveResultDto> = game
+            .with_write(|game| game.move_formation(&req.disciple_ids, target))
+            .into_iter()
+            .map(|r| {
+                let arrived = r.arrived();
+                let destination = r.destination.map(|p| PositionDto { x: p.x, y: p.y });
+                match r.result {
+                    Ok(pos) => FormationMoveResultDto {
+                        message: if arrived {
+                            format!("{}已抵达({}, {})", r.disciple_name, pos.x, pos.y)
+                        } else {
+                            format!("{}移动力不足，本回合行至({}, {})", r.disciple_name, pos.x, pos.y)
+                        },
+                        disciple_id: r.disciple_id,
+                        disciple_name: r.disciple_name,
+                        success: true,
+                        code: None,
+                        destination,
+                        position: Some(PositionDto { x: pos.x, y: pos.y }),
+                        arrived,
+                    },
+                    Err(error) => FormationMoveResultDto {
+                        disciple_id: r.disciple_id,
+                        disciple_name: r.disciple_name,
+                        success: false,
+                        code: Some(error.code.to_string()),
+                        message: error.message,
+                        destination,
+                        position: None,
+                        arrived,
+                    },
+                }
+            })
+            .collect();
+
+        let response = FormationMoveResponse {
+            arrived: results.iter().filter(|r| r.arrived).count(),
+            results,
+        };
+        (StatusCode::OK, Json(ApiResponse::ok(response)))
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<FormationMoveResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+/// 花费资源为弟子快速疗伤（治愈全部伤势）
+async fn heal_disciple(
+    State(store): State<AppState>,
+    Path((game_id, disciple_id)): Path<(String, usize)>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let mut game = game_mutex.lock().await;
+
+        let cost: u32 = match game.sect.disciples.iter().find(|d| d.id == disciple_id && d.is_alive()) {
+            Some(disciple) if disciple.is_injured() => {
+                disciple.injuries.iter().map(|i| i.severity.heal_cost()).sum()
+            }
+            Some(_) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(ApiResponse::<HealDiscipleResponse>::error(
+                        "NOT_INJURED".to_string(),
+                        "该弟子没有伤势".to_string(),
+                    )),
+                )
+            }
+            None => {
+                return (
+                    StatusCode::NOT_FOUND,
+                    Json(ApiResponse::<HealDiscipleResponse>::error(
+                        "DISCIPLE_NOT_FOUND".to_string(),
+                        "弟子不存在".to_string(),
+                    )),
+                )
+            }
+        };
+
+        // 疗伤属于紧急操作，资源不足时允许透支
+        if let Err(message) = game.sect.overdraw_spirit_stones(cost) {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<HealDiscipleResponse>::error(
+                    "INSUFFICIENT_RESOURCES".to_string(),
+                    message,
+                )),
+            );
+        }
+
+        let healed = game
+            .sect
+            .disciples
+            .iter_mut()
+            .find(|d| d.id == disciple_id)
+            .map(|d| d.heal_all_injuries())
+            .unwrap_or_default();
+
+        let response = HealDiscipleResponse {
+            disciple_id,
+            healed: healed.iter().map(|i| i.severity.name().to_string()).collect(),
+            cost,
+            resources_remaining: game.sect.spirit_stones,
+            debt: game.sect.debt,
+        };
+
+        (StatusCode::OK, Json(ApiResponse::ok(response)))
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<HealDiscipleResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+/// 获取已解锁的功法列表
+async fn get_techniques(
+    State(store): State<AppState>,
+    Path(game_id): Path<String>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let game = game_mutex.lock().await;
+        let techniques: Vec<TechniqueDto> = game
+            .sect
+            .available_techniques()
+            .iter()
+            .map(|t| t.into())
+            .collect();
+        (StatusCode::OK, Json(ApiResponse::ok(techniques)))
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<Vec<TechniqueDto>>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+/// 获取弟子已学会的功法及当前激活项
+async fn get_disciple_techniques(
+    State(store): State<AppState>,
+    Path((game_id, disciple_id)): Path<(String, usize)>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let game = game_mutex.lock().await;
+
+        match game.sect.disciples.iter().find(|d| d.id == disciple_id && d.is_alive()) {
+            Some(disciple) => {
+                let response = DiscipleTechniquesResponse {
+                    disciple_id,
+                    learned: disciple.learned_techniques.iter().map(|t| t.into()).collect(),
+                    active: disciple.current_technique().map(|t| t.id.clone()),
+                    capacity: game.sect.technique_capacity(),
+                };
+                (StatusCode::OK, Json(ApiResponse::ok(response)))
+            }
+            None => (
+                StatusCode::NOT_FOUND,
+                Json(ApiResponse::<DiscipleTechniquesResponse>::error(
+                    "DISCIPLE_NOT_FOUND".to_string(),
+                    "弟子不存在".to_string(),
+                )),
+            ),
+        }
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<DiscipleTechniquesResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+/// 弟子的有效属性及生效modifier明细
+async fn get_disciple_effective_attributes(
+    State(store): State<AppState>,
+    Path((game_id, disciple_id)): Path<(String, usize)>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let game = game_mutex.lock().await;
+
+        match game.sect.disciples.iter().find(|d| d.id == disciple_id && d.is_alive()) {
+            Some(disciple) => {
+                let attributes = game.sect.effective_attributes(disciple);
+                let response = EffectiveAttributesResponse::new(disciple, &attributes);
+                (StatusCode::OK, Json(ApiResponse::ok(response)))
+            }
+            None => (
+                StatusCode::NOT_FOUND,
+                Json(ApiResponse::<EffectiveAttributesResponse>::error(
+                    "DISCIPLE_NOT_FOUND".to_string(),
+                    "弟子不存在".to_string(),
+                )),
+            ),
+        }
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<EffectiveAttributesResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+/// 消耗资源学习功法（需对应灵根，数量受藏书楼等级限制）
+async fn learn_technique(
+    State(store): State<AppState>,
+    Path((game_id, disciple_id)): Path<(String, usize)>,
+    Json(req): Json<LearnTechniqueRequest>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let mut game = game_mutex.lock().await;
+
+        if !game.sect.disciples.iter().any(|d| d.id == disciple_id && d.is_alive()) {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(ApiResponse::<LearnTechniqueResponse>::error(
+                    "DISCIPLE_NOT_FOUND".to_string(),
+                    "弟子不存在".to_string(),
+                )),
+            );
+        }
+
+        match game.sect.teach_technique(disciple_id, &req.technique_id) {
+            Ok(cost) => {
+                let disciple = game.sect.disciples.iter().find(|d| d.id == disciple_id).unwrap();
+                let technique = disciple.learned_techniques.last().unwrap();
+                let response = LearnTechniqueResponse {
+                    disciple_id,
+                    technique: technique.into(),
+                    active: disciple.current_technique().map(|t| t.id.clone()),
+                    cost,
+                    contribution_remaining: game.sect.contribution,
+                };
+                (StatusCode::OK, Json(ApiResponse::ok(response)))
+            }
+            Err(message) => (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<LearnTechniqueResponse>::error(
+                    "LEARN_TECHNIQUE_FAILED".to_string(),
+                    message,
+                )),
+            ),
+        }
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<LearnTechniqueResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+/// 切换激活的功法或停用功法
+async fn equip_technique(
+    State(store): State<AppState>,
+    Path((game_id, disciple_id)): Path<(String, usize)>,
+    Json(req): Json<EquipTechniqueRequest>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let mut game = game_mutex.lock().await;
+
+        let disciple = match game.sect.disciples.iter_mut().find(|d| d.id == disciple_id && d.is_alive()) {
+            Some(d) => d,
+            None => {
+                return (
+                    StatusCode::NOT_FOUND,
+                    Json(ApiResponse::<EquipTechniqueResponse>::error(
+                        "DISCIPLE_NOT_FOUND".to_string(),
+                        "弟子不存在".to_string(),
+                    )),
+                )
+            }
+        };
+
+        match req.technique_id {
+            Some(ref technique_id) => {
+                let index = match disciple.learned_techniques.iter().position(|t| &t.id == technique_id) {
+                    Some(index) => index,
+                    None => {
+                        let (code, message) = if Technique::find(technique_id).is_some() {
+                            ("TECHNIQUE_NOT_LEARNED", format!("{}尚未学会该功法", disciple.name))
+                        } else {
+                            ("TECHNIQUE_NOT_FOUND", format!("功法'{}'不存在", technique_id))
+                        };
+                        return (
+                            StatusCode::BAD_REQUEST,
+                            Json(ApiResponse::<EquipTechniqueResponse>::error(
+                                code.to_string(),
+                                message,
+                            )),
+                        );
+                    }
+                };
+                if let Err(message) = disciple.activate_technique(index) {
+                    return (
+                        StatusCode::BAD_REQUEST,
+                        Json(ApiResponse::<EquipTechniqueResponse>::error(
+                            "TECHNIQUE_NOT_LEARNED".to_string(),
+                            message,
+                        )),
+                    );
+                }
+            }
+            None => {
+                disciple.deactivate_technique();
+            }
+        }
+
+        let talent_matched = disciple.current_technique().map(|t| t.matches(disciple)).unwrap_or(false);
+        let response = EquipTechniqueResponse {
+            disciple_id,
+            equipped: disciple.current_technique().map(|t| t.into()),
+            talent_matched,
+            effective_cultivation_speed: disciple.get_effective_cultivation_speed(),
+        };
+
+        (StatusCode::OK, Json(ApiResponse::ok(response)))
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<EquipTechniqueResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+/// 获取炼器坊可炼制的装备列表
+async fn get_equipment(
+    State(store): State<AppState>,
+    Path(game_id): Path<String>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let game = game_mutex.lock().await;
+        let equipment: Vec<EquipmentDto> = game
+            .sect
+            .available_equipment()
+            .iter()
+            .map(|e| e.into())
+            .collect();
+        (StatusCode::OK, Json(ApiResponse::ok(equipment)))
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<Vec<EquipmentDto>>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+/// 花费贡献点说服弟子，使其下次接受任务指派
+async fn persuade_disciple(
+    State(store): State<AppState>,
+    Path((game_id, disciple_id)): Path<(String, usize)>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let mut game = game_mutex.lock().await;
+
+        match game.with_write(|game| game.sect.persuade_disciple(disciple_id)) {
+            Ok(()) => {
+                let response = PersuadeDiscipleResponse {
+                    disciple_id,
+                    cost: crate::sect::PERSUASION_COST,
+                    contribution: game.sect.contribution,
+                };
+                (StatusCode::OK, Json(ApiResponse::ok(response)))
+            }
+            Err(message) => (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<PersuadeDiscipleResponse>::error(
+                    "PERSUADE_FAILED".to_string(),
+                    message,
+                )),
+            ),
+        }
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<PersuadeDiscipleResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+/// 晋升弟子（外门→内门→亲传）
+async fn promote_disciple(
+    State(store): State<AppState>,
+    Path((game_id, disciple_id)): Path<(String, usize)>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let mut game = game_mutex.lock().await;
+
+        match game.sect.promote_disciple(disciple_id) {
+            Ok(disciple_type) => {
+                let personal_count = game.sect.alive_disciples()
+                    .iter()
+                    .filter(|d| d.disciple_type == crate::disciple::DiscipleType::Personal)
+                    .count();
+                let response = PromoteDiscipleResponse {
+                    disciple_id,
+                    disciple_type: format!("{:?}", disciple_type),
+                    personal_count,
+                    personal_quota: game.sect.personal_disciple_quota(),
+                };
+                (StatusCode::OK, Json(ApiResponse::ok(response)))
+            }
+            Err(message) => (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<PromoteDiscipleResponse>::error(
+                    "PROMOTE_FAILED".to_string(),
+                    message,
+                )),
+            ),
+        }
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<PromoteDiscipleResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+/// POST /api/game/:game_id/disciples/:disciple_id/specialize - 选择专精路线
+async fn specialize_disciple(
+    State(store): State<AppState>,
+    Path((game_id, disciple_id)): Path<(String, usize)>,
+    Json(req): Json<SpecializeRequest>,
+) -> impl IntoResponse {
+    let Some(specialization) = crate::disciple::Specialization::from_str(&req.specialization) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::<SpecializeResponse>::error(
+                "INVALID_SPECIALIZATION".to_string(),
+                format!("无效的专精：{}（可选 Sword、Alchemy、Formation、Body）", req.specialization),
+            )),
+        );
+    };
+
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let mut game = game_mutex.lock().await;
+
+        match game.with_write(|game| game.sect.specialize_disciple(disciple_id, specialization)) {
+            Ok(cost) => {
+                let response = SpecializeResponse {
+                    disciple_id,
+                    specialization: specialization.name().to_string(),
+                    cost,
+                };
+                (StatusCode::OK, Json(ApiResponse::ok(response)))
+            }
+            Err(message) => (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<SpecializeResponse>::error(
+                    "SPECIALIZE_FAILED".to_string(),
+                    message,
+                )),
+            ),
+        }
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<SpecializeResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+/// 消耗资源为任务续期（守卫任务加收）
+async fn extend_task(
+    State(store): State<AppState>,
+    Path((game_id, task_id)): Path<(String, usize)>,
+    Json(req): Json<ExtendTaskRequest>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let mut game = game_mutex.lock().await;
+
+        if !game.current_tasks.iter().any(|t| t.id == task_id) {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(ApiResponse::<ExtendTaskResponse>::error(
+                    "TASK_NOT_FOUND".to_string(),
+                    "任务不存在".to_string(),
+                )),
+            );
+        }
+
+        let turns = req.turns.unwrap_or(1);
+        if turns == 0 || turns > crate::task::MAX_TASK_EXTENSION_TURNS {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<ExtendTaskResponse>::error(
+                    "INVALID_TURNS".to_string(),
+                    format!("续期回合数必须在1到{}之间", crate::task::MAX_TASK_EXTENSION_TURNS),
+                )),
+            );
+        }
+
+        match game.with_write(|game| game.extend_task(task_id, turns)) {
+            Ok(cost) => {
+                let current_turn = game.sect.year;
+                let task = game.current_tasks.iter().find(|t| t.id == task_id).unwrap();
+                let response = ExtendTaskResponse {
+                    task_id,
+                    expiry_turns: task.expiry_turns,
+                    remaining_turns: task.remaining_turns(current_turn),
+                    cost,
+                    resources_remaining: game.sect.spirit_stones,
+                };
+                (StatusCode::OK, Json(ApiResponse::ok(response)))
+            }
+            Err(message) => (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<ExtendTaskResponse>::error(
+                    "EXTEND_FAILED".to_string(),
+                    message,
+                )),
+            ),
+        }
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<ExtendTaskResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+/// 解析赠礼物品
+fn parse_gift_item(item: &GiftItemDto) -> Result<GiftItem, String> {
+    use crate::pill::{PillGrade, PillType};
+
+    Ok(match item {
+        GiftItemDto::Pill { pill_type, grade } => GiftItem::Pill {
+            pill_type: PillType::from_str(pill_type).ok_or_else(|| "无效的丹药类型".to_string())?,
+            grade: match grade.as_deref() {
+                None => PillGrade::Low,
+                Some(s) => PillGrade::from_str(s).ok_or_else(|| "无效的丹药品级，可选：Low、Middle、High".to_string())?,
+            },
+        },
+        GiftItemDto::Herb { name, quality } => GiftItem::Herb {
+            name: name.clone(),
+            quality: HerbQuality::from_str(quality).ok_or_else(|| "无效的草药品质".to_string())?,
+        },
+    })
+}
+
+/// 弟子向同门赠送宗门库存中的丹药或草药，增进彼此关系
+async fn give_gift(
+    State(store): State<AppState>,
+    Path(game_id): Path<String>,
+    Json(req): Json<GiftRequest>,
+) -> impl IntoResponse {
+    let item = match parse_gift_item(&req.item) {
+        Ok(item) => item,
+        Err(message) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<GiftResponse>::error("INVALID_GIFT_ITEM".to_string(), message)),
+            );
+        }
+    };
+
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let mut game = game_mutex.lock().await;
+
+        for id in [req.from_id, req.to_id] {
+            if !game.sect.disciples.iter().any(|d| d.id == id && d.is_alive()) {
+                return (
+                    StatusCode::NOT_FOUND,
+                    Json(ApiResponse::<GiftResponse>::error(
+                        "DISCIPLE_NOT_FOUND".to_string(),
+                        format!("弟子 {} 不存在", id),
+                    )),
+                );
+            }
+        }
+
+        match game.with_write(|game| game.give_gift(req.from_id, req.to_id, &item)) {
+            Ok(outcome) => {
+                let scores = |from: usize, to: usize| {
+                    game.sect
+                        .disciples
+                        .iter()
+                        .find(|d| d.id == from)
+                        .and_then(|d| d.get_relationship(to))
+                        .map(|r| (&r.scores).into())
+                };
+                let response = GiftResponse {
+                    item_name: outcome.item_name,
+                    comrade_gain: outcome.comrade_gain,
+                    romance_gain: outcome.romance_gain,
+                    from_scores: scores(req.from_id, req.to_id),
+                    to_scores: scores(req.to_id, req.from_id),
+                };
+                (StatusCode::OK, Json(ApiResponse::ok(response)))
+            }
+            Err(message) => (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<GiftResponse>::error("GIFT_FAILED".to_string(), message)),
+            ),
+        }
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<GiftResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+/// 设置弟子的长期任务偏好，自动分配时优先尊重
+async fn set_task_preference(
+    State(store): State<AppState>,
+    Path((game_id, disciple_id)): Path<(String, usize)>,
+    Json(req): Json<TaskPreferenceRequest>,
+) -> impl IntoResponse {
+    let on_conflict = match req.on_conflict.as_deref() {
+        None => PreferenceConflict::default(),
+        Some(s) => match PreferenceConflict::from_str(s) {
+            Some(policy) => policy,
+            None => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(ApiResponse::<TaskPreferenceResponse>::error(
+                        "INVALID_CONFLICT_POLICY".to_string(),
+                        format!("未知的冲突权衡: {}", s),
+                    )),
+                );
+            }
+        },
+    };
+    let preference = match req.task_type.as_deref().map(|t| TaskTypePreference::new(t, on_conflict)).transpose() {
+        Ok(preference) => preference,
+        Err(message) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<TaskPreferenceResponse>::error("INVALID_TASK_TYPE".to_string(), message)),
+            );
+        }
+    };
+
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let mut game = game_mutex.lock().await;
+
+        match game.with_write(|game| game.set_task_preference(disciple_id, preference.clone())) {
+            Ok(()) => (
+                StatusCode::OK,
+                Json(ApiResponse::ok(TaskPreferenceResponse { disciple_id, task_preference: preference })),
+            ),
+            Err(message) => (
+                StatusCode::NOT_FOUND,
+                Json(ApiResponse::<TaskPreferenceResponse>::error(
+                    "DISCIPLE_NOT_FOUND".to_string(),
+                    message,
+                )),
+            ),
+        }
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<TaskPreferenceResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+/// 宗门传承库
+async fn get_heritages(
+    State(store): State<AppState>,
+    Path(game_id): Path<String>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let game = game_mutex.lock().await;
+        let heritages: Vec<HeritageEntryDto> = game
+            .sect
+            .heritages
+            .iter()
+            .enumerate()
+            .map(|(heritage_index, h)| HeritageEntryDto { heritage_index, heritage: h.into() })
+            .collect();
+        (StatusCode::OK, Json(ApiResponse::ok(heritages)))
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<Vec<HeritageEntryDto>>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+/// 获取成就列表（已解锁/未解锁及进度）
+async fn get_achievements(
+    State(store): State<AppState>,
+    Path(game_id): Path<String>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let game = game_mutex.lock().await;
+        let achievements: Vec<AchievementDto> = game.sect.achievement_progress().iter().map(AchievementDto::from).collect();
+        (StatusCode::OK, Json(ApiResponse::ok(achievements)))
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<Vec<AchievementDto>>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+/// 弟子领取传承库中的传承
+async fn inherit_heritage(
+    State(store): State<AppState>,
+    Path((game_id, disciple_id)): Path<(String, usize)>,
+    Json(req): Json<InheritHeritageRequest>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let mut game = game_mutex.lock().await;
+
+        if !game.sect.disciples.iter().any(|d| d.id == disciple_id && d.is_alive()) {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(ApiResponse::<InheritHeritageResponse>::error(
+                    "DISCIPLE_NOT_FOUND".to_string(),
+                    "弟子不存在".to_string(),
+                )),
+            );
+        }
+        if req.heritage_index >= game.sect.heritages.len() {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(ApiResponse::<InheritHeritageResponse>::error(
+                    "HERITAGE_NOT_FOUND".to_string(),
+                    "传承不存在".to_string(),
+                )),
+            );
+        }
+
+        match game.with_write(|game| game.inherit_heritage(disciple_id, req.heritage_index)) {
+            Ok(heritage) => {
+                let disciple = game.sect.disciples.iter().find(|d| d.id == disciple_id).unwrap();
+                let sect_modifiers = game.sect.get_applicable_modifiers_owned(disciple);
+                let sect_modifiers: Vec<&crate::modifier::Modifier> = sect_modifiers.iter().collect();
+                let response = InheritHeritageResponse {
+                    disciple_id,
+                    heritage: (&heritage).into(),
+                    tribulation_success_rate: disciple.tribulation_success_rate_with_sect_modifiers(&sect_modifiers),
+                };
+                (StatusCode::OK, Json(ApiResponse::ok(response)))
+            }
+            Err(message) => (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<InheritHeritageResponse>::error(
+                    "INHERIT_FAILED".to_string(),
+                    message,
+                )),
+            ),
+        }
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<InheritHeritageResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+/// 巡逻状态响应
+fn patrol_response(game: &InteractiveGame, disciple_id: usize) -> PatrolResponse {
+    let disciple = game.sect.disciples.iter().find(|d| d.id == disciple_id).unwrap();
+    PatrolResponse {
+        disciple_id,
+        patrolling: disciple.patrolling,
+        center: PositionDto { x: disciple.position.x, y: disciple.position.y },
+        radius: crate::map::PATROL_RADIUS,
+    }
+}
+
+/// 派弟子在所在位置巡逻（先移动到目标区域再开始巡逻）
+async fn start_patrol(
+    State(store): State<AppState>,
+    Path((game_id, disciple_id)): Path<(String, usize)>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let mut game = game_mutex.lock().await;
+
+        if !game.sect.disciples.iter().any(|d| d.id == disciple_id && d.is_alive()) {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(ApiResponse::<PatrolResponse>::error(
+                    "DISCIPLE_NOT_FOUND".to_string(),
+                    "弟子不存在".to_string(),
+                )),
+            );
+        }
+
+        match game.with_write(|game| game.start_patrol(disciple_id)) {
+            Ok(_) => {
+                let response = patrol_response(&game, disciple_id);
+                (StatusCode::OK, Json(ApiResponse::ok(response)))
+            }
+            Err(message) => (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<PatrolResponse>::error(
+                    "PATROL_FAILED".to_string(),
+                    message,
+                )),
+            ),
+        }
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<PatrolResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+/// 结束巡逻
+async fn end_patrol(
+    State(store): State<AppState>,
+    Path((game_id, disciple_id)): Path<(String, usize)>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let mut game = game_mutex.lock().await;
+
+        if !game.sect.disciples.iter().any(|d| d.id == disciple_id && d.is_alive()) {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(ApiResponse::<PatrolResponse>::error(
+                    "DISCIPLE_NOT_FOUND".to_string(),
+                    "弟子不存在".to_string(),
+                )),
+            );
+        }
+
+        match game.with_write(|game| game.end_patrol(disciple_id)) {
+            Ok(()) => {
+                let response = patrol_response(&game, disciple_id);
+                (StatusCode::OK, Json(ApiResponse::ok(response)))
+            }
+            Err(message) => (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<PatrolResponse>::error(
+                    "PATROL_FAILED".to_string(),
+                    message,
+                )),
+            ),
+        }
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<PatrolResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+/// 闭关状态响应（包含闭关期间每回合的修为与消耗）
+fn seclusion_response(game: &InteractiveGame, disciple_id: usize) -> SeclusionResponse {
+    let disciple = game.sect.disciples.iter().find(|d| d.id == disciple_id).unwrap();
+    let sect_modifiers = game.sect.get_applicable_modifiers_owned(disciple);
+    let sect_modifiers: Vec<&crate::modifier::Modifier> = sect_modifiers.iter().collect();
+    SeclusionResponse {
+        disciple_id,
+        seclusion_turns: disciple.seclusion_turns,
+        cost_per_turn: SECLUSION_COST_PER_TURN,
+        progress_per_turn: disciple.seclusion_progress(&sect_modifiers),
+    }
+}
+
+/// 弟子闭关若干回合
+async fn enter_seclusion(
+    State(store): State<AppState>,
+    Path((game_id, disciple_id)): Path<(String, usize)>,
+    Json(req): Json<SeclusionRequest>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let mut game = game_mutex.lock().await;
+
+        if !game.sect.disciples.iter().any(|d| d.id == disciple_id && d.is_alive()) {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(ApiResponse::<SeclusionResponse>::error(
+                    "DISCIPLE_NOT_FOUND".to_string(),
+                    "弟子不存在".to_string(),
+                )),
+            );
+        }
+
+        match game.with_write(|game| game.enter_seclusion(disciple_id, req.turns.unwrap_or(1))) {
+            Ok(()) => {
+                let response = seclusion_response(&game, disciple_id);
+                (StatusCode::OK, Json(ApiResponse::ok(response)))
+            }
+            Err(message) => (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<SeclusionResponse>::error(
+                    "SECLUSION_FAILED".to_string(),
+                    message,
+                )),
+            ),
+        }
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<SeclusionResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+/// 提前结束闭关
+async fn end_seclusion(
+    State(store): State<AppState>,
+    Path((game_id, disciple_id)): Path<(String, usize)>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let mut game = game_mutex.lock().await;
+
+        if !game.sect.disciples.iter().any(|d| d.id == disciple_id && d.is_alive()) {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(ApiResponse::<SeclusionResponse>::error(
+                    "DISCIPLE_NOT_FOUND".to_string(),
+                    "弟子不存在".to_string(),
+                )),
+            );
+        }
+
+        match game.with_write(|game| game.end_seclusion(disciple_id)) {
+            Ok(_) => {
+                let response = seclusion_response(&game, disciple_id);
+                (StatusCode::OK, Json(ApiResponse::ok(response)))
+            }
+            Err(message) => (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<SeclusionResponse>::error(
+                    "SECLUSION_FAILED".to_string(),
+                    message,
+                )),
+            ),
+        }
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<SeclusionResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+/// POST /api/game/:game_id/tournament
+/// 手动发起比武大会，返回对阵结果与排名
+async fn hold_tournament(
+    State(store): State<AppState>,
+    Path(game_id): Path<String>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let mut game = game_mutex.lock().await;
+
+        match game.with_write(|game| game.hold_tournament()) {
+            Ok(result) => (StatusCode::OK, Json(ApiResponse::ok(TournamentResponse::from(&result)))),
+            Err(message) => (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<TournamentResponse>::error(
+                    "TOURNAMENT_FAILED".to_string(),
+                    message,
+                )),
+            ),
+        }
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<TournamentResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+/// 炼制并穿戴装备，或卸下指定槽位的装备
+async fn equip_disciple(
+    State(store): State<AppState>,
+    Path((game_id, disciple_id)): Path<(String, usize)>,
+    Json(req): Json<EquipRequest>,
 ) -> impl IntoResponse {
     if let Some(game_mutex) = store.get_game(&game_id) {
         let mut game = game_mutex.lock().await;
 
-        // 查找弟子并获取所需信息
-        let disciple_info = game.sect.disciples.iter().find(|d| d.id == disciple_id).map(|d| {
-            (
-                d.position.x,
-                d.position.y,
-                d.name.clone(),
-                d.cultivation.current_level.movement_range(),
-                d.moves_remaining,
-            )
-        });
-
-        if let Some((old_x, old_y, disciple_name, max_range, moves_remaining)) = disciple_info {
-            let old_position = PositionDto { x: old_x, y: old_y };
-
-            // 计算距离（曼哈顿距离）
-            let distance = ((req.x as i32 - old_x as i32).abs()
-                + (req.y as i32 - old_y as i32).abs()) as u32;
-
-            // 检查移动距离是否在范围内
-            if distance > max_range {
-                return (
-                    StatusCode::BAD_REQUEST,
-                    Json(ApiResponse::<MoveDiscipleResponse>::error(
-                        "MOVEMENT_OUT_OF_RANGE".to_string(),
-                        format!(
-                            "移动距离({})超出范围！{}的最大移动距离为{}格",
-                            distance, disciple_name, max_range
-                        ),
-                    )),
-                );
-            }
+        if !game.sect.disciples.iter().any(|d| d.id == disciple_id && d.is_alive()) {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(ApiResponse::<EquipResponse>::error(
+                    "DISCIPLE_NOT_FOUND".to_string(),
+                    "弟子不存在".to_string(),
+                )),
+            );
+        }
 
-            // 检查本回合剩余移动距离
-            if distance > moves_remaining {
-                return (
-                    StatusCode::BAD_REQUEST,
-                    Json(ApiResponse::<MoveDiscipleResponse>::error(
-                        "INSUFFICIENT_MOVES".to_string(),
-                        format!(
-                            "本回合移动距离不足！需要{}格，剩余{}格",
-                            distance, moves_remaining
-                        ),
-                    )),
-                );
-            }
+        let equipment = match req.equipment_id {
+            Some(ref equipment_id) => match game.sect.forge_equipment(equipment_id) {
+                Ok(e) => Some(e),
+                Err(message) => {
+                    return (
+                        StatusCode::BAD_REQUEST,
+                        Json(ApiResponse::<EquipResponse>::error(
+                            "FORGE_FAILED".to_string(),
+                            message,
+                        )),
+                    );
+                }
+            },
+            None => None,
+        };
 
-            // 检查目标位置是否可通行
-            if !game.map.is_passable(req.x, req.y) {
+        let slot = match (&equipment, req.slot.as_deref().map(EquipmentSlot::from_str)) {
+            (Some(_), _) => None,
+            (None, Some(Some(slot))) => Some(slot),
+            _ => {
                 return (
                     StatusCode::BAD_REQUEST,
-                    Json(ApiResponse::<MoveDiscipleResponse>::error(
-                        "POSITION_BLOCKED".to_string(),
-                        format!(
-                            "目标位置 ({}, {}) 不可通行！该位置可能是山脉或水域",
-                            req.x, req.y
-                        ),
+                    Json(ApiResponse::<EquipResponse>::error(
+                        "INVALID_SLOT".to_string(),
+                        "卸下装备需指定槽位：Weapon、Armor、Artifact".to_string(),
                     )),
                 );
             }
+        };
 
-            // 更新弟子位置和移动距离
-            let moves_remaining = if let Some(disciple) = game.sect.disciples.iter_mut().find(|d| d.id == disciple_id) {
-                disciple.moves_remaining -= distance;
-                let new_position = crate::map::Position { x: req.x, y: req.y };
-                disciple.move_to(new_position);
-                disciple.moves_remaining
-            } else {
-                0  // 如果找不到弟子，返回0（理论上不会发生）
-            };
-
-            // 检查并采集草药
-            let mut collected_herb: Option<CollectedHerbInfo> = None;
-            let mut herb_to_collect: Option<(String, crate::map::HerbQuality)> = None;
-            let mut herb_index_to_remove: Option<usize> = None;
-
-            for (idx, positioned) in game.map.elements.iter().enumerate() {
-                if positioned.position.x == req.x && positioned.position.y == req.y {
-                    if let crate::map::MapElement::Herb(herb) = &positioned.element {
-                        herb_to_collect = Some((herb.name.clone(), herb.quality));
-                        herb_index_to_remove = Some(idx);
-                        break;
-                    }
-                }
-            }
-
-            // 移除草药并添加到仓库
-            if let (Some(idx), Some((herb_name, herb_quality))) = (herb_index_to_remove, herb_to_collect) {
-                game.map.elements.remove(idx);
-                game.sect.add_herb(&herb_name, herb_quality);
-                collected_herb = Some(CollectedHerbInfo {
-                    name: herb_name,
-                    quality: herb_quality.name().to_string(),
-                });
-            }
-
-            let new_position_dto = PositionDto { x: req.x, y: req.y };
-
-            let message = if let Some(ref herb) = collected_herb {
-                format!("{}已移动至({}, {})，采集了{}({})", disciple_name, req.x, req.y, herb.name, herb.quality)
-            } else {
-                format!("{}已移动至({}, {})", disciple_name, req.x, req.y)
-            };
+        let remaining_resources = game.sect.spirit_stones;
+        let disciple = game
+            .sect
+            .disciples
+            .iter_mut()
+            .find(|d| d.id == disciple_id)
+            .unwrap();
+        let replaced = match (equipment, slot) {
+            (Some(equipment), _) => disciple.equip(equipment),
+            (None, Some(slot)) => disciple.unequip(slot),
+            (None, None) => None,
+        };
 
-            let response = MoveDiscipleResponse {
-                success: true,
-                message,
-                disciple_id,
-                disciple_name,
-                old_position,
-                new_position: new_position_dto,
-                moves_remaining,
-                collected_herb,
-            };
+        let response = EquipResponse {
+            disciple_id,
+            equipment: EquipmentSlot::ALL
+                .iter()
+                .filter_map(|slot| disciple.equipment.get(slot))
+                .map(|e| e.into())
+                .collect(),
+            replaced: replaced.as_ref().map(|e| e.into()),
+            remaining_resources,
+        };
 
-            (StatusCode::OK, Json(ApiResponse::ok(response)))
-        } else {
-            (
-                StatusCode::NOT_FOUND,
-                Json(ApiResponse::<MoveDiscipleResponse>::error(
-                    "DISCIPLE_NOT_FOUND".to_string(),
-                    "弟子不存在".to_string(),
-                )),
-            )
-        }
+        (StatusCode::OK, Json(ApiResponse::ok(response)))
     } else {
         (
             StatusCode::NOT_FOUND,
-            Json(ApiResponse::<MoveDiscipleResponse>::error(
+            Json(ApiResponse::<EquipResponse>::error(
                 "GAME_NOT_FOUND".to_string(),
                 "游戏不存在".to_string(),
             )),
@@ -700,53 +2237,35 @@ async fn move_disciple(
 async fn get_tasks(
     State(store): State<AppState>,
     Path(game_id): Path<String>,
+    Query(query): Query<TaskQuery>,
 ) -> impl IntoResponse {
     if let Some(game_mutex) = store.get_game(&game_id) {
         let game = game_mutex.lock().await;
         let current_turn = game.sect.year;
+        let estimator = query.disciple_id
+            .map(|id| game.sect.disciples.iter().find(|d| d.id == id && d.is_alive()));
+        if let Some(None) = estimator {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(ApiResponse::<Vec<TaskDto>>::error(
+                    "DISCIPLE_NOT_FOUND".to_string(),
+                    "弟子不存在".to_string(),
+                )),
+            );
+        }
+        let estimator = estimator.flatten();
 
+        let matcher = game.task_matcher();
         let tasks: Vec<TaskDto> = game.current_tasks
             .iter()
             .map(|task| {
                 let assignment = game.task_assignments.iter().find(|a| a.task_id == task.id);
                 let progress = assignment.map(|a| a.progress).unwrap_or(0);
                 let assigned_to = assignment.map(|a| a.disciple_ids.clone()).unwrap_or_default();
-                let remaining_turns = if task.created_turn + task.expiry_turns > current_turn {
-                    task.created_turn + task.expiry_turns - current_turn
-                } else {
-                    0
-                };
-
-                // 找出适合该任务的弟子
-                let mut free_disciples = Vec::new();
-                let mut busy_disciples = Vec::new();
-
-                for disciple in &game.sect.disciples {
-                    // 检查弟子是否适合该任务（技能和修为检查）
-                    if task.is_suitable_for_disciple(disciple) {
-                        // 检查弟子是否在任务位置（如果任务有位置要求）
-                        let is_at_location = if let Some(task_pos) = &task.position {
-                            disciple.position.x == task_pos.x && disciple.position.y == task_pos.y
-                        } else {
-                            true // 没有位置要求的任务，所有弟子都可以
-                        };
-
-                        if !is_at_location {
-                            continue; // 弟子不在任务位置，跳过
-                        }
+                let remaining_turns = task.remaining_turns(current_turn);
 
-                        // 检查弟子是否正在执行其他任务
-                        let is_busy = game.task_assignments.iter().any(|a|
-                            a.disciple_ids.contains(&disciple.id) && a.task_id != task.id
-                        );
-
-                        if is_busy {
-                            busy_disciples.push(disciple.id);
-                        } else {
-                            free_disciples.push(disciple.id);
-                        }
-                    }
-                }
+                // 找出适合该任务的弟子（空闲 / 忙碌）
+                let candidates = matcher.candidates(task);
 
                 // 提取敌人信息（如果是战斗任务）
                 let enemy_info = if let crate::task::TaskType::Combat(combat_task) = &task.task_type {
@@ -782,14 +2301,20 @@ async fn get_tasks(
                     constitution_cost: task.constitution_cost,
                     skill_required: task.get_skill_required(),
                     suitable_disciples: SuitableDisciples {
-                        free: free_disciples,
-                        busy: busy_disciples,
+                        free: candidates.free,
+                        busy: candidates.busy,
                     },
                     enemy_info,
                     position: task.position.as_ref().map(|p| PositionDto { x: p.x, y: p.y }),
                     valid_positions: task.valid_positions.as_ref().map(|positions|
                         positions.iter().map(|p| PositionDto { x: p.x, y: p.y }).collect()
                     ),
+                    risk_level: format!("{:?}", task.risk_level()),
+                    estimated_success_rate: estimator.map(|d| task.estimated_success_rate(d, game.enemy_is_demon(task))),
+                    prerequisite_task_name: task.prerequisite_task_name.clone(),
+                    locked: task.locked,
+                    retryable: task.retryable,
+                    exploration_depth: task.exploration_depth(),
                 }
             })
             .collect();
@@ -806,6 +2331,56 @@ async fn get_tasks(
     }
 }
 
+/// GET /api/game/:game_id/tasks/:task_id/preview?disciple_id= - 预览弟子执行任务的收获
+async fn preview_task_reward(
+    State(store): State<AppState>,
+    Path((game_id, task_id)): Path<(String, usize)>,
+    Query(query): Query<TaskPreviewQuery>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let game = game_mutex.lock().await;
+        let Some(task) = game.current_tasks.iter().find(|t| t.id == task_id) else {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(ApiResponse::<TaskRewardPreviewResponse>::error(
+                    "TASK_NOT_FOUND".to_string(),
+                    "任务不存在".to_string(),
+                )),
+            );
+        };
+        let Some(disciple) = game.sect.disciples.iter().find(|d| d.id == query.disciple_id && d.is_alive()) else {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(ApiResponse::<TaskRewardPreviewResponse>::error(
+                    "DISCIPLE_NOT_FOUND".to_string(),
+                    "弟子不存在".to_string(),
+                )),
+            );
+        };
+
+        let preview = game.preview_task_reward(task, disciple);
+        let response = TaskRewardPreviewResponse {
+            task_id,
+            disciple_id: disciple.id,
+            progress: preview.progress,
+            resources: preview.resources,
+            contribution: preview.contribution,
+            reputation: preview.reputation,
+            dao_heart_change: preview.dao_heart_change,
+            success_rate: preview.success_rate,
+        };
+        (StatusCode::OK, Json(ApiResponse::ok(response)))
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<TaskRewardPreviewResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
 /// 分配任务
 async fn assign_task(
     State(store): State<AppState>,
@@ -817,43 +2392,76 @@ async fn assign_task(
 
         // 检查任务是否存在
         if let Some(task) = game.current_tasks.iter().find(|t| t.id == task_id) {
+            // 任务链前置未完成时不可分配
+            if task.locked {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(ApiResponse::<AssignTaskResponse>::error(
+                        "TASK_LOCKED".to_string(),
+                        format!(
+                            "需先完成前置任务「{}」",
+                            task.prerequisite_task_name.as_deref().unwrap_or_default()
+                        ),
+                    )),
+                );
+            }
+
+            // 指定探索深度（按新深度校验弟子是否胜任，已开始的探索不可改变深度）
+            let mut task = task.clone();
+            if let Some(depth) = req.depth {
+                let started = game.task_assignments.iter().any(|a| a.task_id == task_id && a.started_turn.is_some());
+                if started && task.exploration_depth() != Some(depth) {
+                    return (
+                        StatusCode::BAD_REQUEST,
+                        Json(ApiResponse::<AssignTaskResponse>::error(
+                            "TASK_ALREADY_STARTED".to_string(),
+                            "探索已经开始，无法改变深度".to_string(),
+                        )),
+                    );
+                }
+                if let Err(e) = task.set_exploration_depth(depth) {
+                    return (
+                        StatusCode::BAD_REQUEST,
+                        Json(ApiResponse::<AssignTaskResponse>::error("INVALID_DEPTH".to_string(), e)),
+                    );
+                }
+            }
+
             // 检查弟子是否存在
             if let Some(disciple) = game.sect.disciples.iter().find(|d| d.id == req.disciple_id) {
-                // 检查弟子是否适合该任务
-                if !task.is_suitable_for_disciple(disciple) {
+                // 闭关中的弟子不可接任务
+                if disciple.is_in_seclusion() {
                     return (
                         StatusCode::BAD_REQUEST,
                         Json(ApiResponse::<AssignTaskResponse>::error(
-                            "DISCIPLE_NOT_SUITABLE".to_string(),
-                            format!("弟子 {} 不适合该任务（可能缺少所需技能或修为不足）", disciple.name),
+                            "DISCIPLE_IN_SECLUSION".to_string(),
+                            format!("弟子 {} 正在闭关", disciple.name),
                         )),
                     );
                 }
 
-                // 检查弟子是否在任务的有效位置（支持大型建筑的多位置）
-                if task.position.is_some() && !task.is_disciple_at_valid_position(&disciple.position) {
-                    let position_hint = if let Some(positions) = &task.valid_positions {
-                        if positions.len() > 1 {
-                            format!("任务区域内任意位置")
-                        } else {
-                            format!("({}, {})", positions[0].x, positions[0].y)
-                        }
-                    } else if let Some(pos) = &task.position {
-                        format!("({}, {})", pos.x, pos.y)
-                    } else {
-                        "未知位置".to_string()
-                    };
+                // 检查弟子是否适合该任务
+                if !task.is_suitable_for_disciple(disciple) {
                     return (
                         StatusCode::BAD_REQUEST,
                         Json(ApiResponse::<AssignTaskResponse>::error(
-                            "DISCIPLE_NOT_AT_LOCATION".to_string(),
-                            format!("弟子 {} 不在任务位置 {}，当前位置({}, {})",
-                                disciple.name, position_hint,
-                                disciple.position.x, disciple.position.y),
+                            "DISCIPLE_NOT_SUITABLE".to_string(),
+                            format!("弟子 {} 不适合该任务（可能缺少所需技能或修为不足）", disciple.name),
                         )),
                     );
                 }
 
+                // 弟子不在任务的有效位置时规划远征（支持大型建筑的多位置）
+                let travel_turns = match game.plan_expedition(req.disciple_id, task_id) {
+                    Ok(turns) => turns,
+                    Err(error) => {
+                        return (
+                            StatusCode::BAD_REQUEST,
+                            Json(ApiResponse::<AssignTaskResponse>::error(error.code.to_string(), error.message)),
+                        );
+                    }
+                };
+
                 // 检查任务是否已满
                 let max_participants = task.max_participants;
                 let current_count = game.task_assignments.iter()
@@ -862,6 +2470,12 @@ async fn assign_task(
                     .unwrap_or(0);
 
                 if current_count >= max_participants as usize {
+                    // 稀缺任务被抢，落败者对占位者心生敌意
+                    let occupants = game.task_assignments.iter()
+                        .find(|a| a.task_id == task_id)
+                        .map(|a| a.disciple_ids.clone())
+                        .unwrap_or_default();
+                    game.sect.record_rivalry(req.disciple_id, &occupants);
                     return (
                         StatusCode::BAD_REQUEST,
                         Json(ApiResponse::<AssignTaskResponse>::error(
@@ -885,72 +2499,274 @@ async fn assign_task(
                     );
                 }
 
+                // 高傲或与队中仇敌同行的弟子可能抗命
+                if let Err(error) = game.check_assignment_consent(req.disciple_id, task_id, &[], rand::random()) {
+                    return (
+                        StatusCode::BAD_REQUEST,
+                        Json(ApiResponse::<AssignTaskResponse>::error(error.code.to_string(), error.message)),
+                    );
+                }
+
                 // 克隆战斗任务相关信息以避免借用冲突
-                let combat_info = if let crate::task::TaskType::Combat(combat_task) = &task.task_type {
-                    Some((
-                        combat_task.enemy_id,
-                        combat_task.enemy_name.clone(),
-                        task.name.contains("守卫")
-                    ))
+                let combat_info = task.combat_enemy_id().map(|enemy_id| (enemy_id, task.name.contains("守卫")));
+
+                // 在 task_assignments 中找到对应的分配记录
+                let assigned = game.with_write(|game| {
+                    if let Some(depth) = req.depth {
+                        let current = game.current_tasks.iter_mut().find(|t| t.id == task_id)?;
+                        current.set_exploration_depth(depth).ok()?;
+                    }
+                    game.dispatch_to_task(task_id, req.disciple_id).ok()?;
+                    let current_count = game.task_assignments.iter().find(|a| a.task_id == task_id)?.disciple_ids.len();
+
+                    // 如果是战斗任务，锁定怪物的移动
+                    if let Some((enemy_id, is_defense_task)) = combat_info {
+                        // 标记怪物正在被战斗
+                        game.map.set_monster_being_fought(enemy_id, true);
+                        // 如果是守卫任务，额外设置 has_active_defense_task
+                        if is_defense_task {
+                            game.map.lock_monster_for_defense_task(enemy_id);
+                        }
+                    }
+                    Some(current_count)
+                });
+                if let Some(current_count) = assigned {
+                    let message = match travel_turns {
+                        Some(turns) => format!("任务分配成功 ({}/{}人)，弟子踏上远征，预计{}回合抵达", current_count, max_participants, turns),
+                        None => format!("任务分配成功 ({}/{}人)", current_count, max_participants),
+                    };
+                    let response = AssignTaskResponse {
+                        task_id,
+                        disciple_id: req.disciple_id,
+                        message,
+                    };
+
+                    (StatusCode::OK, Json(ApiResponse::ok(response)))
                 } else {
-                    None
-                };
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Json(ApiResponse::<AssignTaskResponse>::error(
+                            "ASSIGNMENT_NOT_FOUND".to_string(),
+                            "任务分配记录不存在".to_string(),
+                        )),
+                    )
+                }
+            } else {
+                (
+                    StatusCode::NOT_FOUND,
+                    Json(ApiResponse::<AssignTaskResponse>::error(
+                        "DISCIPLE_NOT_FOUND".to_string(),
+                        "弟子不存在".to_string(),
+                    )),
+                )
+            }
+        } else {
+            (
+                StatusCode::NOT_FOUND,
+                Json(ApiResponse::<AssignTaskResponse>::error(
+                    "TASK_NOT_FOUND".to_string(),
+                    "任务不存在".to_string(),
+                )),
+            )
+        }
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<AssignTaskResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+/// 批量分配校验失败信息
+struct BatchAssignError {
+    index: usize,
+    task_id: usize,
+    disciple_id: Option<usize>,
+    code: &'static str,
+    message: String,
+}
+
+/// 校验整批分配（不修改游戏状态）
+fn validate_batch_assignment(game: &InteractiveGame, items: &[BatchAssignItem]) -> Result<(), BatchAssignError> {
+    use std::collections::HashMap;
+
+    // 本批次中每个弟子计划加入的任务
+    let mut planned: HashMap<usize, usize> = HashMap::new();
+    // 本批次中每个任务新增的人数
+    let mut planned_counts: HashMap<usize, usize> = HashMap::new();
+
+    for (index, item) in items.iter().enumerate() {
+        let fail = |disciple_id: Option<usize>, code: &'static str, message: String| BatchAssignError {
+            index,
+            task_id: item.task_id,
+            disciple_id,
+            code,
+            message,
+        };
+
+        let task = game.current_tasks.iter().find(|t| t.id == item.task_id)
+            .ok_or_else(|| fail(None, "TASK_NOT_FOUND", format!("任务 {} 不存在", item.task_id)))?;
+        let assignment = game.task_assignments.iter().find(|a| a.task_id == item.task_id)
+            .ok_or_else(|| fail(None, "ASSIGNMENT_NOT_FOUND", "任务分配记录不存在".to_string()))?;
+        if task.locked {
+            return Err(fail(
+                None,
+                "TASK_LOCKED",
+                format!("任务 {} 需先完成前置任务", task.name),
+            ));
+        }
+
+        for &disciple_id in &item.disciple_ids {
+            let disciple = game.sect.disciples.iter().find(|d| d.id == disciple_id && d.is_alive())
+                .ok_or_else(|| fail(Some(disciple_id), "DISCIPLE_NOT_FOUND", format!("弟子 {} 不存在", disciple_id)))?;
+
+            if disciple.is_in_seclusion() {
+                return Err(fail(
+                    Some(disciple_id),
+                    "DISCIPLE_IN_SECLUSION",
+                    format!("弟子 {} 正在闭关", disciple.name),
+                ));
+            }
+
+            if !task.is_suitable_for_disciple(disciple) {
+                return Err(fail(
+                    Some(disciple_id),
+                    "DISCIPLE_NOT_SUITABLE",
+                    format!("弟子 {} 不适合任务 {}", disciple.name, task.name),
+                ));
+            }
+
+            // 不在任务地点的弟子与单个分配一样规划远征，无法前往时拒绝
+            if !assignment.contains_disciple(disciple_id) {
+                game.plan_expedition(disciple_id, item.task_id)
+                    .map_err(|error| fail(Some(disciple_id), error.code, error.message))?;
+            }
+
+            let busy_elsewhere = game.task_assignments.iter()
+                .any(|a| a.task_id != item.task_id && a.disciple_ids.contains(&disciple_id));
+            let planned_elsewhere = planned.get(&disciple_id).map(|&t| t != item.task_id).unwrap_or(false);
+            if busy_elsewhere || planned_elsewhere {
+                return Err(fail(
+                    Some(disciple_id),
+                    "DISCIPLE_BUSY",
+                    format!("弟子 {} 已在执行其他任务", disciple.name),
+                ));
+            }
+
+            if !assignment.contains_disciple(disciple_id) {
+                // 同批派往该任务的弟子视同队友
+                let teammates: Vec<usize> = items
+                    .iter()
+                    .filter(|other| other.task_id == item.task_id)
+                    .flat_map(|other| other.disciple_ids.iter().copied())
+                    .collect();
+                game.check_assignment_consent(disciple_id, item.task_id, &teammates, rand::random())
+                    .map_err(|error| fail(Some(disciple_id), error.code, error.message))?;
+            }
+
+            // 已在该任务中或本批次已计划加入的弟子不重复计数
+            if assignment.contains_disciple(disciple_id) || planned.contains_key(&disciple_id) {
+                continue;
+            }
+
+            let new_count = planned_counts.entry(item.task_id).or_insert(0);
+            if assignment.disciple_ids.len() + *new_count >= task.max_participants as usize {
+                return Err(fail(
+                    Some(disciple_id),
+                    "TASK_FULL",
+                    format!("任务 {} 已满，最多允许{}人参与", task.name, task.max_participants),
+                ));
+            }
+            *new_count += 1;
+            planned.insert(disciple_id, item.task_id);
+        }
+    }
+
+    Ok(())
+}
+
+/// 批量分配任务（全部校验通过后才生效，任一失败整批不做改动）
+async fn batch_assign_tasks(
+    State(store): State<AppState>,
+    Path(game_id): Path<String>,
+    Json(req): Json<Vec<BatchAssignItem>>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let mut game = game_mutex.lock().await;
+
+        if let Err(e) = validate_batch_assignment(&game, &req) {
+            let details = serde_json::json!({
+                "index": e.index,
+                "task_id": e.task_id,
+                "disciple_id": e.disciple_id,
+            });
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<BatchAssignResponse>::error_with_details(
+                    e.code.to_string(),
+                    format!("第{}条分配失败: {}", e.index + 1, e.message),
+                    details.to_string(),
+                )),
+            );
+        }
 
-                // 在 task_assignments 中找到对应的分配记录
-                if let Some(assignment) = game.task_assignments.iter_mut().find(|a| a.task_id == task_id) {
-                    assignment.add_disciple(req.disciple_id);
-                    let current_count = assignment.disciple_ids.len();
+        // 全部校验通过，逐项派遣（与单个分配一致），任一失败则整批撤回
+        let batch: Vec<(usize, &[usize])> = req.iter().map(|item| (item.task_id, item.disciple_ids.as_slice())).collect();
+        if let Err((index, disciple_id, error)) = game.with_write(|game| game.dispatch_batch(&batch)) {
+            let details = serde_json::json!({
+                "index": index,
+                "task_id": req[index].task_id,
+                "disciple_id": disciple_id,
+            });
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<BatchAssignResponse>::error_with_details(
+                    error.code.to_string(),
+                    format!("第{}条分配失败: {}", index + 1, error.message),
+                    details.to_string(),
+                )),
+            );
+        }
 
-                    // 如果是战斗任务，锁定怪物的移动
-                    if let Some((enemy_id_opt, enemy_name, is_defense_task)) = combat_info {
-                        // 标记怪物正在被战斗
-                        if let Some(enemy_id) = enemy_id_opt {
-                            game.map.set_monster_being_fought(enemy_id, true);
-                        }
-                        // 如果是守卫任务，额外设置 has_active_defense_task
-                        if is_defense_task {
-                            game.map.lock_monster_for_defense_task(&enemy_name);
-                        }
-                    }
+        // 整批成功后再锁定战斗任务的妖魔
+        let mut results = Vec::new();
+        for item in &req {
+            let task = match game.current_tasks.iter().find(|t| t.id == item.task_id) {
+                Some(task) => task,
+                None => continue,
+            };
+            let max_participants = task.max_participants;
+            let combat_info = task.combat_enemy_id().map(|enemy_id| (enemy_id, task.name.contains("守卫")));
 
-                    let response = AssignTaskResponse {
-                        task_id,
-                        disciple_id: req.disciple_id,
-                        message: format!("任务分配成功 ({}/{}人)", current_count, max_participants),
-                    };
+            let participant_count = game.task_assignments.iter()
+                .find(|a| a.task_id == item.task_id)
+                .map(|a| a.disciple_ids.len())
+                .unwrap_or(0);
 
-                    (StatusCode::OK, Json(ApiResponse::ok(response)))
-                } else {
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(ApiResponse::<AssignTaskResponse>::error(
-                            "ASSIGNMENT_NOT_FOUND".to_string(),
-                            "任务分配记录不存在".to_string(),
-                        )),
-                    )
+            if participant_count > 0 {
+                if let Some((enemy_id, is_defense_task)) = combat_info {
+                    game.map.set_monster_being_fought(enemy_id, true);
+                    if is_defense_task {
+                        game.map.lock_monster_for_defense_task(enemy_id);
+                    }
                 }
-            } else {
-                (
-                    StatusCode::NOT_FOUND,
-                    Json(ApiResponse::<AssignTaskResponse>::error(
-                        "DISCIPLE_NOT_FOUND".to_string(),
-                        "弟子不存在".to_string(),
-                    )),
-                )
             }
-        } else {
-            (
-                StatusCode::NOT_FOUND,
-                Json(ApiResponse::<AssignTaskResponse>::error(
-                    "TASK_NOT_FOUND".to_string(),
-                    "任务不存在".to_string(),
-                )),
-            )
+
+            results.push(BatchAssignResultDto {
+                task_id: item.task_id,
+                participant_count,
+                max_participants,
+            });
         }
+
+        (StatusCode::OK, Json(ApiResponse::ok(BatchAssignResponse { results })))
     } else {
         (
             StatusCode::NOT_FOUND,
-            Json(ApiResponse::<AssignTaskResponse>::error(
+            Json(ApiResponse::<BatchAssignResponse>::error(
                 "GAME_NOT_FOUND".to_string(),
                 "游戏不存在".to_string(),
             )),
@@ -969,33 +2785,26 @@ async fn unassign_task(
         // 检查任务是否存在
         if let Some(task) = game.current_tasks.iter().find(|t| t.id == task_id) {
             // 克隆战斗任务相关信息以避免借用冲突
-            let combat_info = if let crate::task::TaskType::Combat(combat_task) = &task.task_type {
-                Some((
-                    combat_task.enemy_id,
-                    combat_task.enemy_name.clone(),
-                    task.name.contains("守卫")
-                ))
-            } else {
-                None
-            };
+            let combat_info = task.combat_enemy_id().map(|enemy_id| (enemy_id, task.name.contains("守卫")));
 
             // 在 task_assignments 中找到对应的分配记录
-            if let Some(assignment) = game.task_assignments.iter_mut().find(|a| a.task_id == task_id) {
+            let removed = game.with_write(|game| {
+                let assignment = game.task_assignments.iter_mut().find(|a| a.task_id == task_id)?;
                 let removed_count = assignment.disciple_ids.len();
                 assignment.disciple_ids.clear();
 
                 // 如果是战斗任务，解锁怪物的移动
-                if let Some((enemy_id_opt, enemy_name, is_defense_task)) = combat_info {
+                if let Some((enemy_id, is_defense_task)) = combat_info {
                     // 清除怪物的战斗状态
-                    if let Some(enemy_id) = enemy_id_opt {
-                        game.map.set_monster_being_fought(enemy_id, false);
-                    }
+                    game.map.set_monster_being_fought(enemy_id, false);
                     // 如果是守卫任务，额外清除 has_active_defense_task
                     if is_defense_task {
-                        game.map.unlock_monster_for_defense_task(&enemy_name);
+                        game.map.unlock_monster_for_defense_task(enemy_id);
                     }
                 }
-
+                Some(removed_count)
+            });
+            if let Some(removed_count) = removed {
                 (StatusCode::OK, Json(ApiResponse::ok(format!("取消成功，移除了{}名弟子", removed_count))))
             } else {
                 (
@@ -1030,13 +2839,33 @@ async fn unassign_task(
 async fn auto_assign_tasks(
     State(store): State<AppState>,
     Path(game_id): Path<String>,
+    Query(query): Query<AutoAssignQuery>,
 ) -> impl IntoResponse {
+    let strategy = match query.strategy.as_deref() {
+        None => AssignStrategy::default(),
+        Some(s) => match AssignStrategy::from_str(s) {
+            Some(strategy) => strategy,
+            None => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(ApiResponse::<String>::error(
+                        "INVALID_STRATEGY".to_string(),
+                        format!("未知的分配策略: {}", s),
+                    )),
+                );
+            }
+        },
+    };
+
     if let Some(game_mutex) = store.get_game(&game_id) {
         let mut game = game_mutex.lock().await;
 
-        game.auto_assign_remaining();
+        let assigned = game.with_write(|game| game.auto_assign_remaining(strategy));
 
-        (StatusCode::OK, Json(ApiResponse::ok("自动分配完成".to_string())))
+        (
+            StatusCode::OK,
+            Json(ApiResponse::ok(format!("自动分配完成（{}），共分配 {} 个任务", strategy.name(), assigned))),
+        )
     } else {
         (
             StatusCode::NOT_FOUND,
@@ -1084,11 +2913,8 @@ async fn check_task_eligibility(
         let disciple = disciple.unwrap();
 
         // 检查弟子是否在任务位置
-        let is_at_position = if let Some(task_pos) = &task.position {
-            disciple.position.x == task_pos.x && disciple.position.y == task_pos.y
-        } else {
-            true // 无位置要求的任务默认在位置
-        };
+        // 无位置要求的任务默认在位置，大型元素的任务在其任一格子均可
+        let is_at_position = task.is_disciple_at_valid_position(&disciple.position);
 
         // 检查弟子是否正在执行其他任务
         let is_busy = game.task_assignments.iter()
@@ -1122,7 +2948,7 @@ async fn check_task_eligibility(
         // 计算战斗任务的成功率和等级信息
         let is_combat = matches!(task.task_type, crate::task::TaskType::Combat(_));
         let (success_rate, disciple_combat_level, enemy_level) = if is_combat {
-            let rate = task.calculate_combat_success_rate(disciple);
+            let rate = task.settlement_success_rate(Some(disciple), &[disciple], 0.0, game.enemy_is_demon(task));
             let disciple_lvl = crate::task::Task::calculate_disciple_combat_level(disciple);
             let enemy_lvl = task.get_enemy_level();
             (Some(rate), Some(disciple_lvl), enemy_lvl)
@@ -1154,6 +2980,104 @@ async fn check_task_eligibility(
     }
 }
 
+/// GET /api/game/:game_id/income - 年度收入明细
+async fn get_income(
+    State(store): State<AppState>,
+    Path(game_id): Path<String>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let game = game_mutex.lock().await;
+        let report = game.income_report();
+        let entries = |items: Vec<(String, u32)>| {
+            items.into_iter().map(|(source, amount)| IncomeEntryDto { source, amount }).collect()
+        };
+        let response = IncomeResponse {
+            sources: entries(report.sources),
+            building_bonuses: entries(report.building_bonuses),
+            total: report.total,
+        };
+        (StatusCode::OK, Json(ApiResponse::ok(response)))
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<IncomeResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+/// GET /api/game/:game_id/memorial - 纪念堂（陨落与叛离的弟子）
+async fn get_memorial(
+    State(store): State<AppState>,
+    Path(game_id): Path<String>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let game = game_mutex.lock().await;
+        let response = MemorialResponse {
+            records: game.sect.fallen_disciples.iter().map(FallenRecordDto::from).collect(),
+        };
+        (StatusCode::OK, Json(ApiResponse::ok(response)))
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<MemorialResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+/// GET /api/game/:game_id/leaderboard - 弟子排行榜
+async fn get_leaderboard(
+    State(store): State<AppState>,
+    Path(game_id): Path<String>,
+    Query(query): Query<LeaderboardQuery>,
+) -> impl IntoResponse {
+    use crate::sect::LeaderboardMetric;
+
+    let by = query.by.unwrap_or_else(|| "resources".to_string());
+    let Some(metric) = LeaderboardMetric::from_str(&by) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::<LeaderboardResponse>::error(
+                "INVALID_LEADERBOARD_METRIC".to_string(),
+                format!("无效的排行指标：{}（可选 resources、tasks、cultivation）", by),
+            )),
+        );
+    };
+
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let game = game_mutex.lock().await;
+        let entries = game
+            .sect
+            .leaderboard(metric)
+            .into_iter()
+            .enumerate()
+            .map(|(i, d)| LeaderboardEntryDto {
+                rank: i + 1,
+                disciple_id: d.id,
+                name: d.name.clone(),
+                cultivation: DiscipleDto::from(d).cultivation,
+                tasks_completed: d.tasks_completed,
+                total_resources_contributed: d.total_resources_contributed,
+                monsters_slain: d.monsters_slain,
+            })
+            .collect();
+        (StatusCode::OK, Json(ApiResponse::ok(LeaderboardResponse { by, entries })))
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<LeaderboardResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
 /// 获取统计信息
 async fn get_statistics(
     State(store): State<AppState>,
@@ -1171,9 +3095,25 @@ async fn get_statistics(
                 inner: stats.inner_disciples,
                 personal: stats.personal_disciples,
             },
-            resources: stats.resources,
+            spirit_stones: stats.spirit_stones,
+            spirit_stone_capacity: stats.spirit_stone_capacity,
+            contribution: stats.contribution,
             reputation: stats.reputation,
-            cultivation_distribution: std::collections::HashMap::new(),
+            cultivation_distribution: crate::cultivation::CultivationLevel::ALL
+                .iter()
+                .zip(&stats.cultivation_distribution)
+                .map(|(level, count)| (format!("{:?}", level), *count))
+                .collect(),
+            debt: stats.debt,
+            debt_turns: stats.debt_turns,
+            average_age: stats.average_age,
+            average_dao_heart: stats.average_dao_heart,
+            survival_rate: stats.survival_rate,
+            total_recruited: stats.total_recruited,
+            total_deaths: stats.total_deaths,
+            rank: stats.rank.into(),
+            morale_score: stats.morale_score,
+            morale: stats.morale.name().to_string(),
         };
 
         (StatusCode::OK, Json(ApiResponse::ok(response)))
@@ -1207,6 +3147,7 @@ async fn get_tribulation_candidates(
                 success_rate: d.tribulation_success_rate(),
                 dao_heart: d.dao_heart,
                 heritage_bonus: d.heritage.as_ref().map(|h| h.tribulation_bonus).unwrap_or(0.0),
+                bottleneck: d.cultivation.bottleneck,
             })
             .collect();
 
@@ -1232,27 +3173,51 @@ async fn execute_tribulation(
     if let Some(game_mutex) = store.get_game(&game_id) {
         let mut game = game_mutex.lock().await;
 
-        if let Some(disciple) = game.sect.disciples.iter_mut().find(|d| d.id == req.disciple_id) {
-            let name = disciple.name.clone();
-            let success = disciple.attempt_tribulation();
-
-            let response = if success {
-                TribulationResponse {
-                    success: true,
-                    disciple_id: req.disciple_id,
-                    name: name.clone(),
-                    new_level: Some(format!("{:?}", disciple.cultivation.current_level)),
-                    message: format!("{}渡劫成功！", name),
-                }
-            } else {
-                TribulationResponse {
-                    success: false,
-                    disciple_id: req.disciple_id,
-                    name,
-                    new_level: None,
-                    message: "渡劫失败".to_string(),
+        let name = game.sect.disciples.iter().find(|d| d.id == req.disciple_id).map(|d| d.name.clone());
+        if let Some(name) = name {
+            let tribulation = match game.with_write(|game| {
+                game.attempt_guarded_tribulation(req.disciple_id, &req.guardian_ids, &mut rand::thread_rng())
+            }) {
+                Ok(tribulation) => tribulation,
+                Err(error) => {
+                    let status = if error.code == "DISCIPLE_NOT_FOUND" { StatusCode::NOT_FOUND } else { StatusCode::BAD_REQUEST };
+                    return (status, Json(ApiResponse::<TribulationResponse>::error(error.code.to_string(), error.message)));
                 }
             };
+            let (outcome, from_level) = (tribulation.outcome, tribulation.from_level);
+            let level = game.sect.disciples.iter().find(|d| d.id == req.disciple_id).map_or(from_level, |d| d.cultivation.current_level);
+            let new_level = format!("{:?}", level);
+
+            let mut message = match outcome {
+                TribulationOutcome::Success => format!("{}渡劫成功！", name),
+                TribulationOutcome::Injured => format!("{}渡劫失败，身受重伤，休养后可再次尝试", name),
+                TribulationOutcome::Died => format!("{}渡劫失败，身死道消", name),
+                TribulationOutcome::NotReady => format!("{}尚未满足渡劫条件", name),
+            };
+            if !tribulation.injured_guardians.is_empty() {
+                let names: Vec<&str> = tribulation.injured_guardians.iter().map(|(_, name)| name.as_str()).collect();
+                message.push_str(&format!("，护法的{}被波及受伤", names.join("、")));
+            }
+
+            // 身死时生成传承
+            if outcome == TribulationOutcome::Died {
+                game.sect.handle_disciple_death(req.disciple_id, DepartureCause::Tribulation);
+                game.release_dead_disciple_tasks();
+            }
+            game.log_tribulation(&name, outcome, level);
+            let aftermath = game.apply_tribulation_aftermath(req.disciple_id, from_level, outcome, &mut rand::thread_rng());
+            game.check_achievements();
+
+            let response = TribulationResponse {
+                success: outcome == TribulationOutcome::Success,
+                disciple_id: req.disciple_id,
+                name,
+                new_level: if outcome == TribulationOutcome::Success { Some(new_level) } else { None },
+                outcome: format!("{:?}", outcome),
+                message,
+                aftermath: aftermath.as_ref().map(TribulationAftermathDto::from),
+                injured_guardians: tribulation.injured_guardians.iter().map(|(id, _)| *id).collect(),
+            };
 
             (StatusCode::OK, Json(ApiResponse::ok(response)))
         } else {
@@ -1275,18 +3240,124 @@ async fn execute_tribulation(
     }
 }
 
+/// 导出地图（紧凑二进制格式）
+async fn export_map(
+    State(store): State<AppState>,
+    Path(game_id): Path<String>,
+) -> axum::response::Response {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let game = game_mutex.lock().await;
+        match game.map.to_bytes() {
+            Ok(bytes) => ([(header::CONTENT_TYPE, "application/octet-stream")], bytes).into_response(),
+            Err(e) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::<()>::error("MAP_EXPORT_FAILED".to_string(), e)),
+            )
+                .into_response(),
+        }
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<()>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+            .into_response()
+    }
+}
+
+/// 渲染字符地图（调试地图布局用）
+async fn render_map(
+    State(store): State<AppState>,
+    Path(game_id): Path<String>,
+    Query(query): Query<MapRenderQuery>,
+) -> impl IntoResponse {
+    let format = query.format.as_deref().unwrap_or("ascii");
+    if format != "ascii" {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::<MapRenderResponse>::error(
+                "INVALID_MAP_FORMAT".to_string(),
+                format!("不支持的渲染格式：{}", format),
+            )),
+        );
+    }
+
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let game = game_mutex.lock().await;
+        let response = MapRenderResponse {
+            width: game.map.width,
+            height: game.map.height,
+            text: game.map.render_ascii(&game.sect.disciples),
+            legend: crate::map::ASCII_MAP_LEGEND.to_string(),
+        };
+        (StatusCode::OK, Json(ApiResponse::ok(response)))
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<MapRenderResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+/// 导入二进制地图（当前任务与分配随旧地图作废）
+async fn import_map(
+    State(store): State<AppState>,
+    Path(game_id): Path<String>,
+    body: Bytes,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let mut game = game_mutex.lock().await;
+        match game.with_write(|game| game.import_map(&body)) {
+            Ok(()) => (
+                StatusCode::OK,
+                Json(ApiResponse::ok(format!("导入成功，共{}个地图元素", game.map.elements.len()))),
+            ),
+            Err(e) => (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<String>::error("MAP_IMPORT_FAILED".to_string(), e)),
+            ),
+        }
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<String>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
 /// 获取地图数据
 async fn get_map(
     State(store): State<AppState>,
     Path(game_id): Path<String>,
+    Query(query): Query<MapQuery>,
 ) -> impl IntoResponse {
+    use crate::map::{MapBounds, MapElement};
+    use std::collections::HashMap;
+
+    if let Some(ref element_type) = query.element_type {
+        if !MapElement::TYPE_NAMES.contains(&element_type.as_str()) {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<MapDataResponse>::error(
+                    "INVALID_ELEMENT_TYPE".to_string(),
+                    format!("未知的元素类型: {}", element_type),
+                )),
+            );
+        }
+    }
+
     if let Some(game_mutex) = store.get_game(&game_id) {
         let game = game_mutex.lock().await;
 
-        use crate::map::MapElement;
-        use std::collections::HashMap;
-
-        // 第一步：收集所有妖魔的入侵信息
+        // 第一步：收集所有妖魔的入侵信息（在过滤前统计，入侵者被过滤掉时受攻击标记仍然保留）
         let mut attacks: HashMap<String, AttackInfo> = HashMap::new();
         for positioned in &game.map.elements {
             if let MapElement::Monster(monster) = &positioned.element {
@@ -1303,9 +3374,16 @@ async fn get_map(
             }
         }
 
-        // 第二步：遍历所有元素，为被攻击的元素添加攻击信息
-        let elements: Vec<MapElementDto> = game.map.elements
-            .iter()
+        // 第二步：按类型和区域过滤后，为被攻击的元素添加攻击信息
+        let bounds = MapBounds {
+            min_x: query.min_x,
+            max_x: query.max_x,
+            min_y: query.min_y,
+            max_y: query.max_y,
+        };
+        let elements: Vec<MapElementDto> = game.map
+            .query_elements(query.element_type.as_deref(), &bounds)
+            .into_iter()
             .map(|positioned| {
                 let location_id = positioned.element.get_location_id();
                 let under_attack = attacks.get(&location_id).cloned();
@@ -1356,6 +3434,16 @@ async fn get_map(
                             invading_location: m.invaded_location_id.clone(),
                         },
                     ),
+                    MapElement::MonsterNest(n) => (
+                        "MonsterNest".to_string(),
+                        n.name.clone(),
+                        MapElementDetails::MonsterNest {
+                            level: n.level,
+                            turns_until_spawn: n.turns_until_spawn,
+                            aura_radius: n.aura_radius,
+                            aura_level_bonus: n.aura_level_bonus,
+                        },
+                    ),
                     MapElement::Terrain(t) => (
                         "Terrain".to_string(),
                         t.name.clone(),
@@ -1375,6 +3463,14 @@ async fn get_map(
                             is_mature: h.is_mature(),
                         },
                     ),
+                    MapElement::Opportunity(o) => (
+                        "Opportunity".to_string(),
+                        o.name.clone(),
+                        MapElementDetails::Opportunity {
+                            opportunity_id: format!("opportunity_{}", o.id),
+                            turns_left: o.turns_left,
+                        },
+                    ),
                 };
 
                 MapElementDto {
@@ -1393,10 +3489,23 @@ async fn get_map(
             })
             .collect();
 
+        let rival_sects = game
+            .map
+            .rival_sects
+            .iter()
+            .map(|rival| RivalSectDto {
+                name: rival.name.clone(),
+                position: PositionDto { x: rival.position.x, y: rival.position.y },
+                disciples: rival.disciples,
+                power: rival.power(),
+                occupied_realms: rival.occupied_realms.clone(),
+            })
+            .collect();
         let response = MapDataResponse {
             width: game.map.width,
             height: game.map.height,
             elements,
+            rival_sects,
         };
 
         (StatusCode::OK, Json(ApiResponse::ok(response)))
@@ -1415,41 +3524,202 @@ async fn get_map(
 async fn get_pill_inventory(
     State(store): State<AppState>,
     Path(game_id): Path<String>,
+    Query(query): Query<PillInventoryQuery>,
 ) -> impl IntoResponse {
     if let Some(game_mutex) = store.get_game(&game_id) {
         let game = game_mutex.lock().await;
 
         let mut pills = std::collections::HashMap::new();
 
-        use crate::pill::PillType;
+        use crate::pill::{PillGrade, PillType};
+
+        let grades: Vec<PillGrade> = match query.grade.as_deref() {
+            Some(s) => match PillGrade::from_str(s) {
+                Some(grade) => vec![grade],
+                None => {
+                    return (
+                        StatusCode::BAD_REQUEST,
+                        Json(ApiResponse::<PillInventoryResponse>::error(
+                            "INVALID_PILL_GRADE".to_string(),
+                            "无效的丹药品级，可选：Low、Middle、High".to_string(),
+                        )),
+                    );
+                }
+            },
+            None => PillGrade::ALL.to_vec(),
+        };
 
         for pill_type in [
             PillType::QiRecovery,
             PillType::BodyStrength,
             PillType::VitalityElixir,
             PillType::CultivationBoost,
+            PillType::BottleneckBreaker,
+            PillType::LongevityPill,
+            PillType::FocusPill,
+            PillType::SwiftnessPill,
         ] {
             let effects = pill_type.effects();
+            let grade_infos: Vec<PillGradeInfo> = grades
+                .iter()
+                .map(|grade| {
+                    let graded = pill_type.effects_of_grade(*grade);
+                    PillGradeInfo {
+                        grade: format!("{:?}", grade),
+                        grade_name: grade.name().to_string(),
+                        count: game.sect.pill_inventory.get_count(pill_type, *grade),
+                        energy_restore: graded.energy_restore,
+                        constitution_restore: graded.constitution_restore,
+                        cultivation_boost: graded.cultivation_boost,
+                    }
+                })
+                .collect();
             pills.insert(
                 pill_type.to_string().to_string(),
                 PillInfo {
-                    count: game.sect.pill_inventory.get_count(pill_type),
+                    count: grade_infos.iter().map(|g| g.count).sum(),
                     name: pill_type.name().to_string(),
                     description: pill_type.description().to_string(),
                     energy_restore: effects.energy_restore,
                     constitution_restore: effects.constitution_restore,
                     cultivation_boost: effects.cultivation_boost,
+                    grades: grade_infos,
                 },
             );
         }
 
-        let response = PillInventoryResponse { pills };
+        let response = PillInventoryResponse { pills };
+
+        (StatusCode::OK, Json(ApiResponse::ok(response)))
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<PillInventoryResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+/// 解析批量操作的筛选条件与动作
+fn parse_batch_request(req: &BatchDiscipleRequest) -> Result<(DiscipleFilter, BatchAction), String> {
+    use crate::cultivation::CultivationLevel;
+    use crate::disciple::{DiscipleType, TalentType};
+    use crate::pill::{PillGrade, PillType};
+
+    let parse_level = |level: &Option<String>| -> Result<Option<CultivationLevel>, String> {
+        level
+            .as_deref()
+            .map(|s| {
+                CultivationLevel::ALL
+                    .into_iter()
+                    .find(|l| format!("{:?}", l) == s)
+                    .ok_or_else(|| format!("未知的修为等级: {}", s))
+            })
+            .transpose()
+    };
+    let disciple_type = req
+        .filter
+        .disciple_type
+        .as_deref()
+        .map(|s| match s {
+            "Outer" => Ok(DiscipleType::Outer),
+            "Inner" => Ok(DiscipleType::Inner),
+            "Personal" => Ok(DiscipleType::Personal),
+            _ => Err(format!("未知的弟子类型: {}", s)),
+        })
+        .transpose()?;
+    let talent = req
+        .filter
+        .talent
+        .as_deref()
+        .map(|s| {
+            TalentType::ALL
+                .into_iter()
+                .find(|t| format!("{:?}", t) == s)
+                .ok_or_else(|| format!("未知的灵根: {}", s))
+        })
+        .transpose()?;
+    let filter = DiscipleFilter {
+        min_level: parse_level(&req.filter.min_level)?,
+        max_level: parse_level(&req.filter.max_level)?,
+        disciple_type,
+        idle: req.filter.idle,
+        talent,
+    };
+
+    let action = match &req.action {
+        BatchActionDto::UsePill { pill_type, grade } => BatchAction::UsePill {
+            pill_type: PillType::from_str(pill_type).ok_or_else(|| "无效的丹药类型".to_string())?,
+            grade: match grade.as_deref() {
+                None => PillGrade::Low,
+                Some(s) => PillGrade::from_str(s).ok_or_else(|| "无效的丹药品级，可选：Low、Middle、High".to_string())?,
+            },
+        },
+        BatchActionDto::Move { x, y } => BatchAction::MoveTo(crate::map::Position { x: *x, y: *y }),
+        BatchActionDto::AutoAssign { strategy } => BatchAction::AutoAssign(match strategy.as_deref() {
+            None => AssignStrategy::default(),
+            Some(s) => AssignStrategy::from_str(s).ok_or_else(|| format!("未知的分配策略: {}", s))?,
+        }),
+    };
+
+    Ok((filter, action))
+}
+
+/// 按条件批量操作弟子（服药、移动、自动分配），逐个返回结果
+async fn batch_disciple_action(
+    State(store): State<AppState>,
+    Path(game_id): Path<String>,
+    Json(req): Json<BatchDiscipleRequest>,
+) -> impl IntoResponse {
+    let (filter, action) = match parse_batch_request(&req) {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<BatchDiscipleResponse>::error(
+                    "INVALID_BATCH_REQUEST".to_string(),
+                    message,
+                )),
+            );
+        }
+    };
+
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let mut game = game_mutex.lock().await;
+
+        let results: Vec<BatchDiscipleResultDto> = game
+            .with_write(|game| game.batch_disciple_action(&filter, &action))
+            .into_iter()
+            .map(|r| match r.result {
+                Ok(message) => BatchDiscipleResultDto {
+                    disciple_id: r.disciple_id,
+                    disciple_name: r.disciple_name,
+                    success: true,
+                    code: None,
+                    message,
+                },
+                Err(error) => BatchDiscipleResultDto {
+                    disciple_id: r.disciple_id,
+                    disciple_name: r.disciple_name,
+                    success: false,
+                    code: Some(error.code.to_string()),
+                    message: error.message,
+                },
+            })
+            .collect();
 
+        let response = BatchDiscipleResponse {
+            matched: results.len(),
+            succeeded: results.iter().filter(|r| r.success).count(),
+            results,
+        };
         (StatusCode::OK, Json(ApiResponse::ok(response)))
     } else {
         (
             StatusCode::NOT_FOUND,
-            Json(ApiResponse::<PillInventoryResponse>::error(
+            Json(ApiResponse::<BatchDiscipleResponse>::error(
                 "GAME_NOT_FOUND".to_string(),
                 "游戏不存在".to_string(),
             )),
@@ -1466,7 +3736,7 @@ async fn use_pill(
     if let Some(game_mutex) = store.get_game(&game_id) {
         let mut game = game_mutex.lock().await;
 
-        use crate::pill::PillType;
+        use crate::pill::{PillGrade, PillType, PILL_BUFF_DURATION};
 
         // 解析丹药类型
         let pill_type = match PillType::from_str(&req.pill_type) {
@@ -1482,69 +3752,82 @@ async fn use_pill(
             }
         };
 
-        // 检查库存
-        if game.sect.pill_inventory.get_count(pill_type) == 0 {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(ApiResponse::<UsePillResponse>::error(
-                    "NO_PILLS".to_string(),
-                    format!("{}库存不足", pill_type.name()),
-                )),
-            );
-        }
-
-        // 查找弟子
-        let disciple_index = game.sect.disciples.iter().position(|d| d.id == req.disciple_id);
-
-        if let Some(index) = disciple_index {
-            // 消耗丹药
-            if !game.sect.pill_inventory.consume(pill_type) {
+        // 解析丹药品级（默认下品）
+        let grade = match req.grade.as_deref().map(PillGrade::from_str) {
+            None => PillGrade::Low,
+            Some(Some(grade)) => grade,
+            Some(None) => {
                 return (
                     StatusCode::BAD_REQUEST,
                     Json(ApiResponse::<UsePillResponse>::error(
-                        "NO_PILLS".to_string(),
-                        format!("{}库存不足", pill_type.name()),
+                        "INVALID_PILL_GRADE".to_string(),
+                        "无效的丹药品级，可选：Low、Middle、High".to_string(),
                     )),
                 );
             }
+        };
 
-            let disciple = &mut game.sect.disciples[index];
-            let name = disciple.name.clone();
-            let energy_before = disciple.energy;
-            let constitution_before = disciple.constitution;
-            let progress_before = disciple.cultivation.progress;
+        let snapshot = game.sect.disciples.iter().find(|d| d.id == req.disciple_id).map(|d| {
+            (d.name.clone(), d.energy, d.constitution, d.cultivation.progress, d.lifespan)
+        });
 
-            // 应用效果
-            let effects = pill_type.effects();
-            disciple.restore_energy(effects.energy_restore);
-            disciple.restore_constitution(effects.constitution_restore);
-
-            // 应用修为进度加成
-            if effects.cultivation_boost > 0 {
-                disciple.cultivation.add_progress(effects.cultivation_boost);
-            }
-
-            let response = UsePillResponse {
-                success: true,
-                message: format!("{}服用了{}", name, pill_type.name()),
-                disciple_name: name,
-                energy_before,
-                energy_after: disciple.energy,
-                constitution_before,
-                constitution_after: disciple.constitution,
-                progress_before,
-                progress_after: disciple.cultivation.progress,
-            };
+        match game.use_pill(req.disciple_id, pill_type, grade) {
+            Ok(outcome) => {
+                let (name, energy_before, constitution_before, progress_before, lifespan_before) = snapshot.unwrap();
+                let disciple = game.sect.disciples.iter().find(|d| d.id == req.disciple_id).unwrap();
 
-            (StatusCode::OK, Json(ApiResponse::ok(response)))
-        } else {
-            (
-                StatusCode::NOT_FOUND,
-                Json(ApiResponse::<UsePillResponse>::error(
-                    "DISCIPLE_NOT_FOUND".to_string(),
-                    "弟子不存在".to_string(),
-                )),
-            )
+                let mut message = format!("{}服用了{}{}", name, grade.name(), pill_type.name());
+                if outcome.overdosed {
+                    message.push_str("，但本回合服药过多，药力已无法化开");
+                }
+                if let Some(side_effect) = outcome.side_effect {
+                    message.push_str(&format!("，出现副作用：{}", side_effect.name()));
+                }
+                if outcome.poisoned {
+                    message.push_str("，药毒入体受了轻伤");
+                }
+                if outcome.bottleneck_broken {
+                    message.push_str("，一举破除了修为瓶颈");
+                }
+                if let Some(buff) = outcome.buff {
+                    message.push_str(&format!("，获得「{}」{}回合", buff.name(), PILL_BUFF_DURATION));
+                }
+
+                let response = UsePillResponse {
+                    success: true,
+                    message,
+                    disciple_name: name,
+                    energy_before,
+                    energy_after: disciple.energy,
+                    constitution_before,
+                    constitution_after: disciple.constitution,
+                    progress_before,
+                    progress_after: disciple.cultivation.progress,
+                    lifespan_before,
+                    lifespan_after: disciple.lifespan,
+                    pills_taken_this_turn: disciple.pills_taken_this_turn,
+                    overdosed: outcome.overdosed,
+                    side_effect_triggered: outcome.side_effect.is_some(),
+                    side_effect: outcome.side_effect.map(|e| e.name().to_string()),
+                    poisoned: outcome.poisoned,
+                    bottleneck_broken: outcome.bottleneck_broken,
+                    buff: outcome.buff.map(|b| b.name().to_string()),
+                    grade: format!("{:?}", grade),
+                };
+
+                (StatusCode::OK, Json(ApiResponse::ok(response)))
+            }
+            Err(error) => {
+                let status = if error.code == "DISCIPLE_NOT_FOUND" {
+                    StatusCode::NOT_FOUND
+                } else {
+                    StatusCode::BAD_REQUEST
+                };
+                (
+                    status,
+                    Json(ApiResponse::<UsePillResponse>::error(error.code.to_string(), error.message)),
+                )
+            }
         }
     } else {
         (
@@ -1565,19 +3848,7 @@ async fn get_herb_inventory(
     if let Some(game_mutex) = store.get_game(&game_id) {
         let game = game_mutex.lock().await;
 
-        let herbs_list = game.sect.herb_inventory.get_all();
-        let total_count = game.sect.herb_inventory.total_count();
-
-        let herbs: Vec<HerbEntryDto> = herbs_list
-            .iter()
-            .map(|h| HerbEntryDto {
-                name: h.name.clone(),
-                quality: h.quality.name().to_string(),
-                count: h.count,
-            })
-            .collect();
-
-        let response = HerbInventoryResponse { total_count, herbs };
+        let response = herb_inventory_response(&game.sect);
 
         (StatusCode::OK, Json(ApiResponse::ok(response)))
     } else {
@@ -1591,6 +3862,113 @@ async fn get_herb_inventory(
     }
 }
 
+/// 草药仓库DTO
+fn herb_inventory_response(sect: &crate::sect::Sect) -> HerbInventoryResponse {
+    let herbs: Vec<HerbEntryDto> = sect
+        .herb_inventory
+        .get_all()
+        .iter()
+        .map(|h| HerbEntryDto {
+            name: h.name.clone(),
+            quality: h.quality.name().to_string(),
+            count: h.count,
+        })
+        .collect();
+
+    HerbInventoryResponse {
+        total_count: sect.herb_inventory.total_count(),
+        herbs,
+    }
+}
+
+/// 卖出草药
+async fn sell_herbs(
+    State(store): State<AppState>,
+    Path(game_id): Path<String>,
+    Json(req): Json<HerbTradeRequest>,
+) -> impl IntoResponse {
+    trade_herbs(&store, &game_id, req, false).await
+}
+
+/// 购买草药
+async fn buy_herbs(
+    State(store): State<AppState>,
+    Path(game_id): Path<String>,
+    Json(req): Json<HerbTradeRequest>,
+) -> impl IntoResponse {
+    trade_herbs(&store, &game_id, req, true).await
+}
+
+/// 草药交易（buy 为 true 时买入，否则卖出）
+async fn trade_herbs(
+    store: &AppState,
+    game_id: &str,
+    req: HerbTradeRequest,
+    buy: bool,
+) -> (StatusCode, Json<ApiResponse<HerbTradeResponse>>) {
+    let game_mutex = match store.get_game(game_id) {
+        Some(g) => g,
+        None => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(ApiResponse::<HerbTradeResponse>::error(
+                    "GAME_NOT_FOUND".to_string(),
+                    "游戏不存在".to_string(),
+                )),
+            )
+        }
+    };
+    let mut game = game_mutex.lock().await;
+
+    let quality = match HerbQuality::from_str(&req.quality) {
+        Some(q) => q,
+        None => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<HerbTradeResponse>::error(
+                    "INVALID_HERB_QUALITY".to_string(),
+                    format!("无效的草药品质：{}", req.quality),
+                )),
+            )
+        }
+    };
+
+    if req.count == 0 {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::<HerbTradeResponse>::error(
+                "INVALID_COUNT".to_string(),
+                "数量必须大于0".to_string(),
+            )),
+        );
+    }
+
+    let result = if buy {
+        game.sect.buy_herbs(&req.name, quality, req.count)
+    } else {
+        game.sect.sell_herbs(&req.name, quality, req.count)
+    };
+
+    match result {
+        Ok(amount) => {
+            let response = HerbTradeResponse {
+                amount,
+                resources_remaining: game.sect.spirit_stones,
+                herb_count: game.sect.herb_inventory.get_count(&req.name, quality),
+                inventory: herb_inventory_response(&game.sect),
+            };
+            (StatusCode::OK, Json(ApiResponse::ok(response)))
+        }
+        Err(msg) => {
+            let code = if buy { "INSUFFICIENT_RESOURCES" } else { "INSUFFICIENT_HERBS" };
+            (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<HerbTradeResponse>::error(code.to_string(), msg)),
+            )
+        }
+    }
+}
+
 /// 获取所有炼丹配方
 async fn get_all_recipes(
     State(store): State<AppState>,
@@ -1608,9 +3986,13 @@ async fn get_all_recipes(
             // 检查是否可以炼制
             let herb_count = game.sect.herb_inventory.count_by_quality(recipe.required_herb_quality);
             let has_enough_herbs = herb_count >= recipe.required_herb_count;
-            let has_enough_resources = game.sect.resources >= recipe.resource_cost;
+            let has_enough_resources = game.sect.spirit_stones >= recipe.resource_cost;
+            let unlocked = game.sect.is_recipe_unlocked(&recipe);
+            let unlock_condition = recipe.tier.unlock_condition().map(|c| c.to_string());
 
-            let (can_craft, reason) = if !has_enough_herbs {
+            let (can_craft, reason) = if !unlocked {
+                (false, unlock_condition.clone())
+            } else if !has_enough_herbs {
                 (false, Some(format!("需要{}个{}品质草药，当前{}个",
                     recipe.required_herb_count,
                     recipe.required_herb_quality.name(),
@@ -1618,7 +4000,7 @@ async fn get_all_recipes(
             } else if !has_enough_resources {
                 (false, Some(format!("需要{}资源，当前{}资源",
                     recipe.resource_cost,
-                    game.sect.resources)))
+                    game.sect.spirit_stones)))
             } else {
                 (true, None)
             };
@@ -1632,6 +4014,9 @@ async fn get_all_recipes(
                 resource_cost: recipe.resource_cost,
                 success_rate: recipe.success_rate,
                 output_count: recipe.output_count,
+                tier: recipe.tier.name().to_string(),
+                unlocked,
+                unlock_condition,
                 can_craft,
                 reason,
             });
@@ -1676,14 +4061,30 @@ async fn refine_pill(
             }
         };
 
+        // 解析草药品质（默认按配方要求）
+        let herb_quality = match req.herb_quality.as_deref().map(HerbQuality::from_str) {
+            None => None,
+            Some(Some(quality)) => Some(quality),
+            Some(None) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(ApiResponse::<RefinePillResponse>::error(
+                        "INVALID_HERB_QUALITY".to_string(),
+                        "无效的草药品质".to_string(),
+                    )),
+                );
+            }
+        };
+
         // 尝试炼制
-        match game.sect.refine_pill(pill_type) {
-            Ok(count) => {
+        match game.sect.refine_pill(pill_type, req.alchemist_id, herb_quality, &mut rand::thread_rng()) {
+            Ok((count, grade)) => {
                 let response = RefinePillResponse {
                     success: true,
-                    message: format!("成功炼制{}个{}", count, pill_type.name()),
+                    message: format!("成功炼制{}个{}{}", count, grade.name(), pill_type.name()),
                     pill_name: Some(pill_type.name().to_string()),
                     output_count: Some(count),
+                    grade: Some(format!("{:?}", grade)),
                 };
                 (StatusCode::OK, Json(ApiResponse::ok(response)))
             }
@@ -1693,6 +4094,7 @@ async fn refine_pill(
                     message: msg.clone(),
                     pill_name: None,
                     output_count: None,
+                    grade: None,
                 };
                 (StatusCode::OK, Json(ApiResponse::ok(response)))
             }
@@ -1708,6 +4110,159 @@ async fn refine_pill(
     }
 }
 
+/// POST /api/game/:game_id/refine/batch - 批量炼丹
+async fn batch_refine(
+    State(store): State<AppState>,
+    Path(game_id): Path<String>,
+    Json(req): Json<BatchRefineRequest>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let mut game = game_mutex.lock().await;
+
+        use crate::pill::PillType;
+
+        let mut orders = Vec::new();
+        for item in &req.items {
+            match PillType::from_str(&item.pill_type) {
+                Some(pill_type) => orders.push((pill_type, item.times)),
+                None => {
+                    return (
+                        StatusCode::BAD_REQUEST,
+                        Json(ApiResponse::<BatchRefineResponse>::error(
+                            "INVALID_PILL_TYPE".to_string(),
+                            format!("无效的丹药类型：{}", item.pill_type),
+                        )),
+                    );
+                }
+            }
+        }
+
+        let report = game.sect.batch_refine(&orders, req.alchemist_id, &mut rand::thread_rng());
+        let response = BatchRefineResponse {
+            entries: report
+                .entries
+                .iter()
+                .map(|entry| BatchRefineEntryDto {
+                    pill_type: entry.pill_type.to_string().to_string(),
+                    pill_name: entry.pill_type.name().to_string(),
+                    requested: entry.requested,
+                    succeeded: entry.succeeded,
+                    failed: entry.failed,
+                    output_count: entry.output,
+                })
+                .collect(),
+            spirit_stones_spent: report.spirit_stones_spent,
+            herbs_consumed: report.herbs_consumed,
+            completed: report.stop_reason.is_none(),
+            stop_reason: report.stop_reason,
+        };
+        (StatusCode::OK, Json(ApiResponse::ok(response)))
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<BatchRefineResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+fn refine_queue_response(sect: &crate::sect::Sect) -> RefineQueueResponse {
+    RefineQueueResponse {
+        orders: sect
+            .refine_queue
+            .iter()
+            .map(|order| RefineOrderDto {
+                pill_type: order.pill_type.to_string().to_string(),
+                pill_name: order.pill_type.name().to_string(),
+                alchemist_id: order.alchemist_id,
+                remaining: order.remaining,
+            })
+            .collect(),
+    }
+}
+
+/// GET /api/game/:game_id/refine/queue - 查看炼丹队列
+async fn get_refine_queue(
+    State(store): State<AppState>,
+    Path(game_id): Path<String>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let game = game_mutex.lock().await;
+        (StatusCode::OK, Json(ApiResponse::ok(refine_queue_response(&game.sect))))
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<RefineQueueResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+/// POST /api/game/:game_id/refine/queue - 加入炼丹队列（每回合开始时自动炼一炉）
+async fn queue_refine(
+    State(store): State<AppState>,
+    Path(game_id): Path<String>,
+    Json(req): Json<QueueRefineRequest>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let mut game = game_mutex.lock().await;
+
+        use crate::pill::PillType;
+
+        let pill_type = match PillType::from_str(&req.pill_type) {
+            Some(pt) => pt,
+            None => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(ApiResponse::<RefineQueueResponse>::error(
+                        "INVALID_PILL_TYPE".to_string(),
+                        "无效的丹药类型".to_string(),
+                    )),
+                );
+            }
+        };
+        if let Err(msg) = game.sect.queue_refine(pill_type, req.alchemist_id, req.times) {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<RefineQueueResponse>::error("INVALID_REFINE_ORDER".to_string(), msg)),
+            );
+        }
+        (StatusCode::OK, Json(ApiResponse::ok(refine_queue_response(&game.sect))))
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<RefineQueueResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
+/// DELETE /api/game/:game_id/refine/queue - 清空炼丹队列
+async fn clear_refine_queue(
+    State(store): State<AppState>,
+    Path(game_id): Path<String>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let mut game = game_mutex.lock().await;
+        game.sect.refine_queue.clear();
+        (StatusCode::OK, Json(ApiResponse::ok(refine_queue_response(&game.sect))))
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<RefineQueueResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
 /// GET /api/game/:game_id/buildings - 获取建筑树信息
 async fn get_building_tree(
     State(store): State<AppState>,
@@ -1736,9 +4291,15 @@ async fn get_building_tree(
                         ModifierTarget::TaskSuitability => "任务适配度",
                         ModifierTarget::TaskDifficulty => "任务难度",
                         ModifierTarget::Income => "收入",
+                        ModifierTarget::StorageCapacity => "灵石上限",
                         ModifierTarget::EnergyConsumption => "精力消耗",
                         ModifierTarget::ConstitutionConsumption => "体魄消耗",
+                        ModifierTarget::EnergyRecovery => "精力恢复",
+                        ModifierTarget::ConstitutionRecovery => "体魄恢复",
                         ModifierTarget::CultivationSpeed => "修炼速度",
+                        ModifierTarget::AlchemySuccessRate => "炼丹成功率",
+                        ModifierTarget::Lifespan => "寿元",
+                        ModifierTarget::MovementRange => "移动范围",
                     };
 
                     let value_str = match &cm.modifier.application {
@@ -1771,6 +4332,8 @@ async fn get_building_tree(
                     actual_cost,
                     parent_id: b.parent_id.clone(),
                     is_built: b.is_built,
+                    build_turns: b.build_turns,
+                    turns_remaining: tree.turns_remaining(&b.id),
                     can_build,
                     effects,
                 }
@@ -1781,7 +4344,7 @@ async fn get_building_tree(
                 built_count: tree.get_built_count(),
                 buildings_built_count: tree.buildings_built_count,
                 cost_multiplier: 2_u32.pow(tree.buildings_built_count),
-                available_resources: game.sect.resources,
+                available_resources: game.sect.spirit_stones,
                 buildings,
             };
 
@@ -1846,16 +4409,19 @@ async fn build_building(
             }
         };
 
-        let resources_before = game.sect.resources;
+        let resources_before = game.sect.spirit_stones;
 
         // 尝试建造
         match game.sect.build_building(&req.building_id) {
             Ok(message) => {
-                // 获取建筑提供的效果数量
+                // 获取建筑提供的效果数量和剩余工期
                 let effects_count = game.sect.building_tree.as_ref()
                     .and_then(|tree| tree.buildings.get(&req.building_id))
                     .map(|b| b.conditional_modifiers.len())
                     .unwrap_or(0);
+                let turns_remaining = game.sect.building_tree.as_ref()
+                    .and_then(|tree| tree.turns_remaining(&req.building_id))
+                    .unwrap_or(0);
 
                 let response = BuildBuildingResponse {
                     success: true,
@@ -1863,8 +4429,9 @@ async fn build_building(
                     building_name,
                     cost,
                     resources_before,
-                    resources_after: game.sect.resources,
+                    resources_after: game.sect.spirit_stones,
                     effects_count,
+                    turns_remaining,
                 };
 
                 (StatusCode::OK, Json(ApiResponse::ok(response)))
@@ -1892,6 +4459,47 @@ async fn build_building(
 
 // ==================== 关系系统 API ====================
 
+/// 获取弟子的家族树（父母、道侣、子女）
+async fn get_disciple_family(
+    State(store): State<AppState>,
+    Path((game_id, disciple_id)): Path<(String, usize)>,
+) -> impl IntoResponse {
+    if let Some(game_mutex) = store.get_game(&game_id) {
+        let game = game_mutex.lock().await;
+        let find = |id: usize| game.sect.disciples.iter().find(|d| d.id == id);
+
+        let disciple = match find(disciple_id) {
+            Some(d) => d,
+            None => {
+                return (
+                    StatusCode::NOT_FOUND,
+                    Json(ApiResponse::<FamilyTreeResponse>::error(
+                        "DISCIPLE_NOT_FOUND".to_string(),
+                        "弟子不存在".to_string(),
+                    )),
+                )
+            }
+        };
+
+        let response = FamilyTreeResponse {
+            disciple: disciple.into(),
+            parents: game.sect.parents_of(disciple_id).into_iter().map(|d| d.into()).collect(),
+            dao_companion: disciple.get_dao_companion_id().and_then(find).map(|d| d.into()),
+            children: disciple.children.iter().filter_map(|&id| find(id)).map(|d| d.into()).collect(),
+        };
+
+        (StatusCode::OK, Json(ApiResponse::ok(response)))
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<FamilyTreeResponse>::error(
+                "GAME_NOT_FOUND".to_string(),
+                "游戏不存在".to_string(),
+            )),
+        )
+    }
+}
+
 /// 获取弟子的所有关系
 async fn get_disciple_relationships(
     State(store): State<AppState>,
@@ -1909,9 +4517,11 @@ async fn get_disciple_relationships(
                         target_name: target.name.clone(),
                         scores: (&rel.scores).into(),
                         established_year: rel.established_year,
+                        last_interaction_year: rel.last_interaction_year,
                         is_dao_companion: rel.is_dao_companion,
                         is_master: rel.is_master,
                         is_disciple: rel.is_disciple,
+                        deceased: rel.deceased,
                         primary_relation: rel.get_primary_relation().to_string(),
                         highest_level: rel.scores.highest_level().name().to_string(),
                     })
@@ -2105,6 +4715,7 @@ async fn update_relationship(
             "Comrade" | "comrade" => RelationDimension::Comrade,
             "Understanding" | "understanding" => RelationDimension::Understanding,
             "FatefulBond" | "fateful_bond" => RelationDimension::FatefulBond,
+            "Enmity" | "enmity" => RelationDimension::Enmity,
             _ => {
                 return (
                     StatusCode::BAD_REQUEST,
@@ -2175,3 +4786,67 @@ async fn update_relationship(
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disciple::{Disciple, DiscipleType};
+    use crate::interactive::TaskAssignment;
+    use crate::relationship::RelationDimension;
+    use crate::task::{GatheringTask, Task, TaskType};
+
+    /// 两名道心不稳、互为仇敌的弟子和一个空缺的采集任务
+    fn game_with_sworn_enemies() -> InteractiveGame {
+        let mut game = InteractiveGame::new_with_mode("测试宗".to_string(), true);
+        game.sect.disciples = vec![
+            Disciple::new(1, "韩立".to_string(), DiscipleType::Outer, vec![]),
+            Disciple::new(2, "墨大夫".to_string(), DiscipleType::Outer, vec![]),
+        ];
+        for disciple in &mut game.sect.disciples {
+            disciple.dao_heart = 40;
+        }
+        game.sect.update_relationship_score(1, 2, RelationDimension::Enmity, 50).unwrap();
+        game.sect.update_relationship_score(2, 1, RelationDimension::Enmity, 50).unwrap();
+        game.current_tasks = vec![Task::new(1, "采集灵草".to_string(), TaskType::Gathering(GatheringTask {
+            resource_type: "灵草".to_string(),
+            difficulty: 1,
+        }), 10, 10)];
+        game.task_assignments = vec![TaskAssignment { task_id: 1, ..Default::default() }];
+        game
+    }
+
+    #[test]
+    fn test_expired_game_is_kept_while_live_events_are_subscribed() {
+        let store = GameStore::with_ttl(Duration::ZERO);
+        store.insert_game("live".to_string(), InteractiveGame::new_with_mode("测试宗".to_string(), true));
+        let receiver = store.get_game("live").unwrap().try_lock().unwrap().subscribe_live_events();
+        std::thread::sleep(Duration::from_millis(1));
+
+        // 只挂着 WebSocket 的对局不会被清理
+        assert_eq!(store.remove_expired(), 0);
+        drop(receiver);
+        assert_eq!(store.remove_expired(), 1);
+        assert!(store.get_game("live").is_none());
+    }
+
+    #[test]
+    fn test_batch_assignment_refuses_sworn_enemies_planned_together() {
+        let game = game_with_sworn_enemies();
+
+        // 同一条中一并派出
+        let together = [BatchAssignItem { task_id: 1, disciple_ids: vec![1, 2] }];
+        let error = validate_batch_assignment(&game, &together).unwrap_err();
+        assert_eq!((error.index, error.code), (0, "DISCIPLE_REFUSED"));
+
+        // 分两条派往同一任务
+        let split = [
+            BatchAssignItem { task_id: 1, disciple_ids: vec![1] },
+            BatchAssignItem { task_id: 1, disciple_ids: vec![2] },
+        ];
+        let error = validate_batch_assignment(&game, &split).unwrap_err();
+        assert_eq!(error.code, "DISCIPLE_REFUSED");
+
+        // 单独派出一人不受影响
+        assert!(validate_batch_assignment(&game, &split[..1]).is_ok());
+    }
+}